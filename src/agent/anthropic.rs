@@ -0,0 +1,193 @@
+use crate::agent::usage::{TokenUsage, UsageMeter};
+use crate::agent::Agent;
+use crate::agent::Message;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+const ANTHROPIC_VERSION: &str = "2023-06-01";
+
+/// Agent of Anthropic's Messages API.
+pub struct AnthropicAgent {
+    client: reqwest::Client,
+    base_url: String,
+    api_key: String,
+    model: String,
+    max_tokens: u32,
+    prompt: String,
+    usage: Option<UsageMeter>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesResponse {
+    content: Vec<ContentBlock>,
+    #[serde(default)]
+    usage: Option<MessagesUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+struct MessagesUsage {
+    input_tokens: u64,
+    output_tokens: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct ContentBlock {
+    #[serde(rename = "type")]
+    kind: String,
+    #[serde(default)]
+    text: String,
+}
+
+impl AnthropicAgent {
+    pub fn new(
+        client: reqwest::Client,
+        base_url: String,
+        api_key: String,
+        model: String,
+        max_tokens: u32,
+    ) -> Self {
+        Self {
+            client,
+            base_url,
+            api_key,
+            model,
+            max_tokens,
+            prompt: String::new(),
+            usage: None,
+        }
+    }
+
+    /// Record the tokens of every call with `meter`, when set.
+    pub fn with_usage(mut self, meter: Option<UsageMeter>) -> Self {
+        self.usage = meter;
+        self
+    }
+
+    /// Messages API body for `messages`; system messages go in the separate system prompt.
+    fn request_body(&self, messages: Vec<Message>) -> Value {
+        let (system, messages): (Vec<Message>, Vec<Message>) = messages
+            .into_iter()
+            .partition(|message| message.role == "system");
+        let system: Vec<String> = system.into_iter().map(|message| message.content).collect();
+        let messages: Vec<Value> = messages
+            .into_iter()
+            .map(|message| {
+                let role = if message.role == "assistant" {
+                    "assistant"
+                } else {
+                    "user"
+                };
+                json!({ "role": role, "content": message.content })
+            })
+            .collect();
+        let mut body = json!({
+            "model": self.model,
+            "max_tokens": self.max_tokens,
+            "messages": messages,
+        });
+        if !system.is_empty() {
+            body["system"] = json!(system.join("\n\n"));
+        }
+        body
+    }
+}
+
+/// Text of the reply, its text blocks joined.
+fn reply_text(response: MessagesResponse) -> String {
+    response
+        .content
+        .into_iter()
+        .filter(|block| block.kind == "text")
+        .map(|block| block.text)
+        .collect::<Vec<_>>()
+        .join("")
+}
+
+#[async_trait]
+impl Agent for AnthropicAgent {
+    fn set_prompt(&mut self, prompt: String) -> &mut Self {
+        self.prompt = prompt;
+        self
+    }
+
+    fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> Result<String> {
+        let body = self.request_body(messages);
+        let response = self
+            .client
+            .post(format!(
+                "{}/v1/messages",
+                self.base_url.trim_end_matches('/')
+            ))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", ANTHROPIC_VERSION)
+            .json(&body)
+            .send()
+            .await?;
+        let status = response.status();
+        let text = response.text().await?;
+        if !status.is_success() {
+            return Err(anyhow!("Anthropic API error ({}): {}", status, text));
+        }
+        let response: MessagesResponse = serde_json::from_str(&text)
+            .map_err(|e| anyhow!("Failed to parse Anthropic response: {}", e))?;
+        if let (Some(meter), Some(usage)) = (&self.usage, &response.usage) {
+            meter.record(
+                &self.model,
+                TokenUsage {
+                    prompt_tokens: usage.input_tokens,
+                    completion_tokens: usage.output_tokens,
+                },
+            );
+        }
+        Ok(reply_text(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_anthropic_request() {
+        let agent = AnthropicAgent::new(
+            reqwest::Client::new(),
+            "https://api.anthropic.com".to_string(),
+            "key".to_string(),
+            "claude-sonnet-4-5".to_string(),
+            1024,
+        );
+        let body = agent.request_body(vec![
+            Message::new("system", "Be brief.".to_string()),
+            Message::new("user", "Hedge?".to_string()),
+            Message::new("assistant", "Yes.".to_string()),
+        ]);
+        assert_eq!(
+            body,
+            json!({
+                "model": "claude-sonnet-4-5",
+                "max_tokens": 1024,
+                "system": "Be brief.",
+                "messages": [
+                    { "role": "user", "content": "Hedge?" },
+                    { "role": "assistant", "content": "Yes." }
+                ]
+            })
+        );
+
+        let response: MessagesResponse = serde_json::from_value(json!({
+            "content": [
+                { "type": "text", "text": "{\"a\":" },
+                { "type": "tool_use", "id": "x", "name": "y", "input": {} },
+                { "type": "text", "text": "1}" }
+            ]
+        }))
+        .unwrap();
+        assert_eq!(reply_text(response), "{\"a\":1}");
+    }
+}
@@ -0,0 +1,160 @@
+use alloy::primitives::{keccak256, Address, Bytes, PrimitiveSignature, B256};
+use alloy::sol_types::SolValue;
+use anyhow::{anyhow, Result};
+use serde::Deserialize;
+use std::collections::HashSet;
+use thiserror::Error;
+
+/// Why a task response is not trusted.
+#[derive(Debug, Error)]
+pub enum AttestationError {
+    #[error("Othentic response is not signed by its performer")]
+    Unsigned,
+    #[error("Othentic response signature is malformed: {0}")]
+    Malformed(String),
+    #[error("Othentic response signature is by {signer}, not its performer {performer}")]
+    WrongSigner { signer: Address, performer: Address },
+    #[error("Othentic response is signed by {0}, not a configured operator")]
+    UnknownOperator(Address),
+}
+
+/// `data` of a task response: the strategy, and the performer's signature over it.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TaskResponse {
+    pub strategy: Option<String>,
+    #[serde(default)]
+    pub proof_of_task: String,
+    pub performer: Option<String>,
+    pub signature: Option<String>,
+}
+
+/// Hash a performer signs, as Othentic attesters check it:
+/// `keccak256(abi.encode(proofOfTask, data, performer, taskDefinitionId))`.
+pub fn task_hash(
+    proof_of_task: &str,
+    data: &[u8],
+    performer: Address,
+    task_definition_id: u16,
+) -> B256 {
+    keccak256(
+        (
+            proof_of_task.to_string(),
+            Bytes::copy_from_slice(data),
+            performer,
+            task_definition_id,
+        )
+            .abi_encode_params(),
+    )
+}
+
+/// Operators whose signed task responses are executed.
+#[derive(Debug, Clone, Default)]
+pub struct OperatorSet {
+    operators: HashSet<Address>,
+}
+
+impl OperatorSet {
+    pub fn new(operators: &[String]) -> Result<Self> {
+        let operators = operators
+            .iter()
+            .map(|operator| {
+                operator
+                    .parse()
+                    .map_err(|e| anyhow!("Invalid Othentic operator {}: {}", operator, e))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { operators })
+    }
+
+    /// Operator that signed `response` for task definition `task_definition_id`, if
+    /// its signature is valid and the operator is in the set.
+    pub fn verify(
+        &self,
+        response: &TaskResponse,
+        task_definition_id: u16,
+    ) -> Result<Address, AttestationError> {
+        let (Some(strategy), Some(performer), Some(signature)) =
+            (&response.strategy, &response.performer, &response.signature)
+        else {
+            return Err(AttestationError::Unsigned);
+        };
+        let performer: Address = performer
+            .parse()
+            .map_err(|e| AttestationError::Malformed(format!("performer {}: {}", performer, e)))?;
+        let signature: PrimitiveSignature = signature
+            .parse()
+            .map_err(|e| AttestationError::Malformed(format!("{}", e)))?;
+        let hash = task_hash(
+            &response.proof_of_task,
+            strategy.as_bytes(),
+            performer,
+            task_definition_id,
+        );
+        let signer = signature
+            .recover_address_from_prehash(&hash)
+            .map_err(|e| AttestationError::Malformed(e.to_string()))?;
+        if signer != performer {
+            return Err(AttestationError::WrongSigner { signer, performer });
+        }
+        if !self.operators.contains(&signer) {
+            return Err(AttestationError::UnknownOperator(signer));
+        }
+        Ok(signer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::signers::local::PrivateKeySigner;
+    use alloy::signers::SignerSync;
+
+    fn signed(signer: &PrivateKeySigner, strategy: &str, signed_strategy: &str) -> TaskResponse {
+        let hash = task_hash("proof", signed_strategy.as_bytes(), signer.address(), 0);
+        TaskResponse {
+            strategy: Some(strategy.to_string()),
+            proof_of_task: "proof".to_string(),
+            performer: Some(signer.address().to_string()),
+            signature: Some(format!(
+                "0x{}",
+                hex::encode(signer.sign_hash_sync(&hash).unwrap().as_bytes())
+            )),
+        }
+    }
+
+    #[test]
+    fn test_verify_task_response() -> Result<()> {
+        let operator = PrivateKeySigner::random();
+        let operators = OperatorSet::new(&[operator.address().to_string().to_lowercase()])?;
+
+        let response = signed(&operator, "{}", "{}");
+        assert_eq!(operators.verify(&response, 0)?, operator.address());
+        // Signed for another task definition
+        assert!(matches!(
+            operators.verify(&response, 1),
+            Err(AttestationError::WrongSigner { .. })
+        ));
+        // Strategy changed after signing
+        assert!(matches!(
+            operators.verify(&signed(&operator, "{\"a\":1}", "{}"), 0),
+            Err(AttestationError::WrongSigner { .. })
+        ));
+
+        let stranger = PrivateKeySigner::random();
+        assert!(matches!(
+            operators.verify(&signed(&stranger, "{}", "{}"), 0),
+            Err(AttestationError::UnknownOperator(address)) if address == stranger.address()
+        ));
+
+        let unsigned = TaskResponse {
+            signature: None,
+            ..signed(&operator, "{}", "{}")
+        };
+        assert!(matches!(
+            operators.verify(&unsigned, 0),
+            Err(AttestationError::Unsigned)
+        ));
+        Ok(())
+    }
+}
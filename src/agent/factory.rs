@@ -0,0 +1,194 @@
+use super::anthropic::AnthropicAgent;
+use super::openai::OpenAIAgent;
+use super::usage::{UsageLedger, UsageMeter};
+use super::Agent;
+use crate::config::{LlmConfig, LlmProviderConfig};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Builds a provider's agent asked with a model, or with the provider's default model.
+pub type AgentBuilder = Box<dyn Fn(Option<&str>) -> Result<Box<dyn Agent>> + Send + Sync>;
+
+/// Agent builders keyed by provider name, so strategies can be asked from any LLM
+/// registered here.
+#[derive(Default)]
+pub struct AgentFactory {
+    builders: HashMap<String, AgentBuilder>,
+    client: reqwest::Client,
+}
+
+impl AgentFactory {
+    /// Factory of the providers configured in `config`, whose agents record the
+    /// tokens of their calls in `usage` when set.
+    pub fn from_config(
+        client: reqwest::Client,
+        config: &LlmConfig,
+        usage: Option<Arc<UsageLedger>>,
+    ) -> Self {
+        let mut factory = Self {
+            client: client.clone(),
+            ..Self::default()
+        };
+        for (name, provider) in &config.providers {
+            let client = client.clone();
+            let provider = provider.clone();
+            let meter = usage.clone().map(|ledger| {
+                let api_key = match &provider {
+                    LlmProviderConfig::OpenAI { api_key_env, .. }
+                    | LlmProviderConfig::Anthropic { api_key_env, .. } => api_key_env.as_str(),
+                    LlmProviderConfig::OpenAICompatible { api_key_env, .. } => {
+                        api_key_env.as_deref().unwrap_or("none")
+                    }
+                };
+                UsageMeter::new(ledger, name, api_key)
+            });
+            factory.register(name, move |model| {
+                build(&client, &provider, model, meter.clone())
+            });
+        }
+        factory
+    }
+
+    /// Register `builder` as `name`, replacing any provider of that name.
+    pub fn register<F>(&mut self, name: &str, builder: F)
+    where
+        F: Fn(Option<&str>) -> Result<Box<dyn Agent>> + Send + Sync + 'static,
+    {
+        self.builders.insert(name.to_lowercase(), Box::new(builder));
+    }
+
+    /// Client the configured agents call their providers with.
+    pub fn client(&self) -> &reqwest::Client {
+        &self.client
+    }
+
+    /// Names of the registered providers, sorted.
+    pub fn providers(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.builders.keys().map(String::as_str).collect();
+        names.sort();
+        names
+    }
+
+    /// Agent of `provider` asked with `model`; the provider's default model when unset.
+    pub fn create(&self, provider: &str, model: Option<&str>) -> Result<Box<dyn Agent>> {
+        let builder = self.builders.get(&provider.to_lowercase()).ok_or_else(|| {
+            anyhow!(
+                "Unknown LLM provider {}, expected one of {}",
+                provider,
+                self.providers().join(", ")
+            )
+        })?;
+        builder(model)
+    }
+}
+
+fn api_key(env: &str) -> Result<String> {
+    std::env::var(env).map_err(|_| anyhow!("{} is not set", env))
+}
+
+fn build(
+    client: &reqwest::Client,
+    provider: &LlmProviderConfig,
+    model: Option<&str>,
+    meter: Option<UsageMeter>,
+) -> Result<Box<dyn Agent>> {
+    let agent: Box<dyn Agent> = match provider {
+        LlmProviderConfig::OpenAI {
+            api_key_env,
+            model: default,
+        } => Box::new(
+            OpenAIAgent::new(
+                client.clone(),
+                api_key(api_key_env)?,
+                model.unwrap_or(default).to_string(),
+                0.0,
+            )
+            .with_usage(meter),
+        ),
+        LlmProviderConfig::Anthropic {
+            api_key_env,
+            base_url,
+            model: default,
+            max_tokens,
+        } => Box::new(
+            AnthropicAgent::new(
+                client.clone(),
+                base_url.clone(),
+                api_key(api_key_env)?,
+                model.unwrap_or(default).to_string(),
+                *max_tokens,
+            )
+            .with_usage(meter),
+        ),
+        LlmProviderConfig::OpenAICompatible {
+            base_url,
+            api_key_env,
+            model: default,
+            structured_outputs,
+        } => Box::new(
+            OpenAIAgent::with_base_url(
+                client.clone(),
+                base_url.clone(),
+                api_key_env.as_deref().map(api_key).transpose()?,
+                model.unwrap_or(default).to_string(),
+                0.0,
+            )
+            .with_structured_outputs(*structured_outputs)
+            .with_usage(meter),
+        ),
+    };
+    Ok(agent)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::Message;
+    use async_trait::async_trait;
+
+    struct Echo(String);
+
+    #[async_trait]
+    impl Agent for Echo {
+        fn set_prompt(&mut self, prompt: String) -> &mut Self {
+            self.0 = prompt;
+            self
+        }
+
+        async fn chat(&self, _messages: Vec<Message>) -> Result<String> {
+            Ok(self.0.clone())
+        }
+
+        fn prompt(&self) -> &str {
+            &self.0
+        }
+    }
+
+    #[test]
+    fn test_agent_factory() {
+        let mut config = LlmConfig::default();
+        config.providers.insert(
+            "openai".to_string(),
+            LlmProviderConfig::OpenAI {
+                api_key_env: "CHILL_PM_TEST_UNSET_OPENAI_KEY".to_string(),
+                model: "o1".to_string(),
+            },
+        );
+        let mut factory = AgentFactory::from_config(reqwest::Client::new(), &config, None);
+        assert_eq!(factory.providers(), vec!["anthropic", "local", "openai"]);
+
+        // A local server needs no key
+        assert!(factory.create("local", Some("qwen2.5")).is_ok());
+        let error = factory.create("openai", None).err().unwrap();
+        assert!(error.to_string().contains("CHILL_PM_TEST_UNSET_OPENAI_KEY"));
+        let error = factory.create("mistral", None).err().unwrap();
+        assert!(error.to_string().contains("anthropic, local, openai"));
+
+        factory.register("Echo", |model| {
+            Ok(Box::new(Echo(model.unwrap_or("none").to_string())))
+        });
+        let agent = factory.create("echo", Some("tiny")).unwrap();
+        assert_eq!(agent.prompt(), "tiny");
+    }
+}
@@ -0,0 +1,392 @@
+use crate::config::ExposureConfig;
+use crate::executor::binance::{OrderSide, Status};
+use crate::portfolio::history::PortfolioSnapshot;
+use crate::report::{BridgeOutcome, OrderOutcome, StoredReport, SwapOutcome};
+use crate::risk::{base_asset, symbol_token};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// One of the wallet's past executions as the agent is reminded of it, so it builds on
+/// what its strategies did rather than recommending the same legs again.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionMemory {
+    pub job_id: String,
+    pub executed_at: DateTime<Utc>,
+    /// Every leg of the strategy, executed or not.
+    pub recommended: Vec<String>,
+    /// Legs that executed, with what they filled.
+    pub filled: Vec<String>,
+    /// Legs skipped or failed, and why.
+    pub not_executed: Vec<String>,
+    /// Change in portfolio value from just before the execution to just before the next
+    /// one, or to now for the latest; None without snapshots on both sides.
+    pub pnl_usd: Option<f64>,
+    /// Net USD exposure per base asset right after the execution, stablecoins left out.
+    pub net_delta_usd: BTreeMap<String, f64>,
+}
+
+fn side(side: OrderSide) -> &'static str {
+    match side {
+        OrderSide::Buy => "BUY",
+        OrderSide::Sell => "SELL",
+    }
+}
+
+/// Signed USD of perp positions and priced holdings per base asset of `snapshot`.
+fn net_delta(snapshot: &PortfolioSnapshot, exposure: &ExposureConfig) -> BTreeMap<String, f64> {
+    let mut delta = BTreeMap::new();
+    let holdings = snapshot.holdings.iter().filter_map(|holding| {
+        let usd_value = holding.usd_value?;
+        Some((base_asset(exposure, &holding.symbol), usd_value))
+    });
+    let positions = snapshot.positions.iter().map(|position| {
+        (
+            base_asset(exposure, symbol_token(&position.symbol)),
+            position.notional,
+        )
+    });
+    for (asset, usd) in holdings.chain(positions) {
+        *delta.entry(asset).or_insert(0.0) += usd;
+    }
+    delta.remove("USD");
+    delta
+}
+
+impl ExecutionMemory {
+    fn new(stored: &StoredReport) -> Self {
+        let report = &stored.report;
+        let mut memory = Self {
+            job_id: report.job_id.clone(),
+            executed_at: stored.executed_at,
+            recommended: Vec::new(),
+            filled: Vec::new(),
+            not_executed: Vec::new(),
+            pnl_usd: None,
+            net_delta_usd: BTreeMap::new(),
+        };
+        for order in &report.orders {
+            let leg = match order.quantity {
+                Some(quantity) => format!("{} {} {}", side(order.side), quantity, order.symbol),
+                None => format!("{} {}", side(order.side), order.symbol),
+            };
+            match &order.outcome {
+                // What filled is as of the latest order update the report was reconciled with
+                OrderOutcome::Placed { order, .. } if order.executed_qty.is_zero() => {
+                    memory.not_executed.push(match order.status {
+                        Status::New | Status::PartiallyFilled => {
+                            format!("{}: resting on the book, unfilled", leg)
+                        }
+                        status => format!("{}: {:?} unfilled", leg, status),
+                    })
+                }
+                OrderOutcome::Placed { order, unwind, .. } => memory.filled.push(format!(
+                    "{}: filled {} at {}{}",
+                    leg,
+                    order.executed_qty,
+                    order.avg_price,
+                    if unwind.is_some() {
+                        ", then unwound"
+                    } else if matches!(order.status, Status::New | Status::PartiallyFilled) {
+                        " so far, still open"
+                    } else {
+                        ""
+                    }
+                )),
+                OrderOutcome::Skipped { reason } => memory
+                    .not_executed
+                    .push(format!("{}: skipped, {}", leg, reason)),
+                OrderOutcome::Failed { error } => memory
+                    .not_executed
+                    .push(format!("{}: failed, {}", leg, error)),
+            }
+            memory.recommended.push(leg);
+        }
+        for bridge in &report.bridges {
+            let leg = format!(
+                "bridge {} {} from chain {} to chain {}",
+                bridge.amount_in, bridge.token, bridge.from_chain_id, bridge.to_chain_id
+            );
+            match &bridge.outcome {
+                BridgeOutcome::Executed(receipt) => memory.filled.push(match receipt.amount_out {
+                    Some(amount_out) => {
+                        format!("{}: received {} {}", leg, amount_out, bridge.token_out)
+                    }
+                    None => format!("{}: sent", leg),
+                }),
+                BridgeOutcome::Skipped { reason } => memory
+                    .not_executed
+                    .push(format!("{}: skipped, {}", leg, reason)),
+                BridgeOutcome::Failed { error } => memory
+                    .not_executed
+                    .push(format!("{}: failed, {}", leg, error)),
+            }
+            memory.recommended.push(leg);
+        }
+        for swap in &report.swaps {
+            let leg = format!(
+                "swap {} {} to {} on chain {}",
+                swap.amount_in, swap.token_in, swap.token_out, swap.chain_id
+            );
+            let amount_out = match &swap.outcome {
+                SwapOutcome::Executed(receipt) => Some(receipt.amount_out),
+                SwapOutcome::Split { amount_out, .. } => Some(*amount_out),
+                SwapOutcome::Skipped { reason } => {
+                    memory
+                        .not_executed
+                        .push(format!("{}: skipped, {}", leg, reason));
+                    None
+                }
+                SwapOutcome::Failed { error } => {
+                    memory
+                        .not_executed
+                        .push(format!("{}: failed, {}", leg, error));
+                    None
+                }
+            };
+            match amount_out {
+                Some(Some(amount_out)) => memory.filled.push(format!(
+                    "{}: received {} {}",
+                    leg, amount_out, swap.token_out
+                )),
+                Some(None) => memory.filled.push(format!("{}: executed", leg)),
+                None => {}
+            }
+            memory.recommended.push(leg);
+        }
+        if let Some(aborted) = &report.aborted {
+            memory.not_executed.push(format!("aborted: {}", aborted));
+        }
+        memory
+    }
+}
+
+/// What came of `reports`, newest first, valued from the wallet's `snapshots` (oldest
+/// first) and `now`, its portfolio as it is.
+pub fn recall(
+    reports: &[StoredReport],
+    snapshots: &[PortfolioSnapshot],
+    now: &PortfolioSnapshot,
+    exposure: &ExposureConfig,
+) -> Vec<ExecutionMemory> {
+    let before = |at: DateTime<Utc>| snapshots.iter().rev().find(|s| s.taken_at <= at);
+    reports
+        .iter()
+        .enumerate()
+        .map(|(i, stored)| {
+            let mut memory = ExecutionMemory::new(stored);
+            // The execution after it, whose own moves are not this one's doing
+            let next = i.checked_sub(1).map(|i| reports[i].executed_at);
+            let end = match next {
+                Some(next) => before(next),
+                None => Some(now),
+            }
+            .filter(|end| end.taken_at > stored.executed_at);
+            let after = snapshots
+                .iter()
+                .filter(|s| next.is_none_or(|next| s.taken_at <= next))
+                .find(|s| s.taken_at > stored.executed_at)
+                .or(end);
+            memory.pnl_usd = before(stored.executed_at)
+                .zip(end)
+                .map(|(start, end)| end.total_usd - start.total_usd);
+            if let Some(after) = after {
+                memory.net_delta_usd = net_delta(after, exposure);
+            }
+            memory
+        })
+        .collect()
+}
+
+impl fmt::Display for ExecutionMemory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let list = |legs: &[String]| match legs {
+            [] => "none".to_string(),
+            legs => legs.join("; "),
+        };
+        writeln!(
+            f,
+            "{} (job {}):",
+            self.executed_at.format("%Y-%m-%d %H:%M UTC"),
+            self.job_id
+        )?;
+        writeln!(f, "  Recommended: {}", list(&self.recommended))?;
+        writeln!(f, "  Filled: {}", list(&self.filled))?;
+        if !self.not_executed.is_empty() {
+            writeln!(f, "  Not executed: {}", list(&self.not_executed))?;
+        }
+        match self.pnl_usd {
+            Some(pnl) => writeln!(f, "  Resulting PnL: {:+.2} USD", pnl)?,
+            None => writeln!(f, "  Resulting PnL: unknown")?,
+        }
+        let delta: Vec<String> = self
+            .net_delta_usd
+            .iter()
+            .map(|(asset, usd)| format!("{} {:+.2} USD", asset, usd))
+            .collect();
+        writeln!(f, "  Net delta after: {}", list(&delta))
+    }
+}
+
+/// Prompt section of `memories`; empty without any.
+pub fn format_memories(memories: &[ExecutionMemory]) -> String {
+    if memories.is_empty() {
+        return String::new();
+    }
+    let memories: String = memories.iter().map(ToString::to_string).collect();
+    format!(
+        "Recent executions (newest first), to build on rather than repeat:\n{}",
+        memories
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::history::{HoldingSnapshot, PositionSnapshot};
+    use crate::report::{ExecutionReport, OrderReport, SwapReport};
+    use chrono::Duration as TimeDelta;
+
+    fn snapshot(
+        taken_at: DateTime<Utc>,
+        total_usd: f64,
+        eth_usd: f64,
+        short_usd: f64,
+    ) -> PortfolioSnapshot {
+        PortfolioSnapshot {
+            wallet_address: "0xaa".to_string(),
+            taken_at,
+            btc_price: 60000.0,
+            eth_price: 2000.0,
+            binance_usd: 0.0,
+            unrealized_pnl: 0.0,
+            onchain_usd: 0.0,
+            total_usd,
+            positions: vec![PositionSnapshot {
+                symbol: "ETHUSDT".to_string(),
+                amount: short_usd / 2000.0,
+                notional: short_usd,
+                unrealized_pnl: 0.0,
+            }],
+            holdings: vec![
+                HoldingSnapshot {
+                    chain_id: 8453,
+                    protocol: None,
+                    symbol: "WETH".to_string(),
+                    amount: eth_usd / 2000.0,
+                    usd_value: Some(eth_usd),
+                },
+                HoldingSnapshot {
+                    chain_id: 8453,
+                    protocol: None,
+                    symbol: "USDC".to_string(),
+                    amount: 500.0,
+                    usd_value: Some(500.0),
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn test_recall() {
+        let t0 = Utc::now() - TimeDelta::hours(10);
+        let at = |hours: i64| t0 + TimeDelta::hours(hours);
+        let older = StoredReport {
+            wallet_address: "0xaa".to_string(),
+            executed_at: at(1),
+            report: ExecutionReport {
+                job_id: "job-1".to_string(),
+                orders: vec![OrderReport {
+                    symbol: "ETHUSDT".to_string(),
+                    side: OrderSide::Sell,
+                    quantity: Some("0.5".parse().unwrap()),
+                    outcome: OrderOutcome::Failed {
+                        error: "Margin is insufficient".to_string(),
+                    },
+                }],
+                swaps: vec![SwapReport {
+                    chain_id: 8453,
+                    token_in: "usdc".to_string(),
+                    token_out: "weth".to_string(),
+                    amount_in: 1000.0,
+                    outcome: SwapOutcome::Skipped {
+                        reason: "No route".to_string(),
+                    },
+                }],
+                ..Default::default()
+            },
+        };
+        let newer = StoredReport {
+            wallet_address: "0xaa".to_string(),
+            executed_at: at(5),
+            report: ExecutionReport {
+                job_id: "job-2".to_string(),
+                orders: vec![OrderReport {
+                    symbol: "ETHUSDT".to_string(),
+                    side: OrderSide::Buy,
+                    quantity: Some("1".parse().unwrap()),
+                    outcome: OrderOutcome::Placed {
+                        order: serde_json::from_value(serde_json::json!({
+                            "clientOrderId": "x", "executedQty": "0", "orderId": 7,
+                            "avgPrice": "0", "origQty": "1", "price": "1900",
+                            "reduceOnly": false, "side": "BUY", "positionSide": "BOTH",
+                            "status": "NEW", "stopPrice": "0", "closePosition": false,
+                            "symbol": "ETHUSDT", "timeInForce": "GTC", "type": "LIMIT",
+                            "updateTime": 10, "workingType": "CONTRACT_PRICE",
+                            "priceProtect": false
+                        }))
+                        .unwrap(),
+                        unwind: None,
+                        unwind_error: None,
+                    },
+                }],
+                ..Default::default()
+            },
+        };
+        let snapshots = vec![
+            snapshot(at(0), 10_000.0, 2000.0, 0.0),
+            snapshot(at(2), 10_100.0, 2000.0, -1000.0),
+            snapshot(at(4), 10_250.0, 2000.0, -1000.0),
+        ];
+        let now = snapshot(at(10), 10_200.0, 2000.0, -2000.0);
+
+        let memories = recall(
+            &[newer, older],
+            &snapshots,
+            &now,
+            &ExposureConfig::default(),
+        );
+        assert_eq!(memories[1].job_id, "job-1");
+        assert_eq!(
+            memories[1].recommended,
+            ["SELL 0.5 ETHUSDT", "swap 1000 usdc to weth on chain 8453"]
+        );
+        assert!(memories[1].filled.is_empty());
+        assert_eq!(memories[1].not_executed.len(), 2);
+        // From the snapshot before it to the last one before job-2
+        assert_eq!(memories[1].pnl_usd, Some(250.0));
+        assert_eq!(
+            memories[1].net_delta_usd,
+            BTreeMap::from([("ETH".to_string(), 1000.0)])
+        );
+        // A limit order still waiting for its fill did not fill
+        assert!(memories[0].filled.is_empty());
+        assert_eq!(
+            memories[0].not_executed,
+            ["BUY 1 ETHUSDT: resting on the book, unfilled"]
+        );
+        // The latest is valued up to now
+        assert_eq!(memories[0].pnl_usd, Some(-50.0));
+        assert_eq!(
+            memories[0].net_delta_usd,
+            BTreeMap::from([("ETH".to_string(), 0.0)])
+        );
+
+        let section = format_memories(&memories);
+        assert!(section.contains("SELL 0.5 ETHUSDT: failed, Margin is insufficient"));
+        assert!(section.contains("Resulting PnL: +250.00 USD"));
+        assert!(format_memories(&[]).is_empty());
+    }
+}
@@ -1,14 +1,99 @@
+pub mod anthropic;
+pub mod attestation;
+pub mod factory;
+pub mod memory;
 pub mod openai;
 pub mod othentic;
-use anyhow::Result;
+pub mod parse;
+pub mod payload;
+pub mod usage;
+pub mod validation;
+use crate::config::DexFilter;
+use crate::executor::chains::BASE_CHAIN_ID;
+use anyhow::{anyhow, Result};
 use async_trait::async_trait;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+pub use factory::AgentFactory;
+
+/// What a [`ReplySink`] relays.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplyEvent {
+    /// The agent is asked again, numbered from 2, to repair its reply or after a retry;
+    /// what was relayed before is void.
+    Attempt(u32),
+    /// The next chunk of the reply.
+    Delta(String),
+}
+
+/// Where a streaming agent relays its reply, chunk by chunk as it is written.
+#[derive(Debug, Clone)]
+pub struct ReplySink {
+    sender: mpsc::UnboundedSender<ReplyEvent>,
+    attempts: Arc<AtomicU32>,
+}
+
+impl ReplySink {
+    /// A sink, and the receiver of what is relayed to it.
+    pub fn channel() -> (Self, mpsc::UnboundedReceiver<ReplyEvent>) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let sink = Self {
+            sender,
+            attempts: Arc::new(AtomicU32::new(0)),
+        };
+        (sink, receiver)
+    }
+
+    /// Start relaying another reply, announcing it when one came before.
+    pub fn begin(&self) {
+        let attempt = self.attempts.fetch_add(1, Ordering::SeqCst) + 1;
+        if attempt > 1 {
+            let _ = self.sender.send(ReplyEvent::Attempt(attempt));
+        }
+    }
+
+    /// Relay `text`; nobody listening any more is not an error of the agent's.
+    pub fn send(&self, text: &str) {
+        let _ = self.sender.send(ReplyEvent::Delta(text.to_string()));
+    }
+}
 
 // Define the Agent trait
 #[async_trait]
-pub trait Agent {
-    fn set_prompt(&mut self, prompt: String) -> &mut Self;
+pub trait Agent: Send + Sync {
+    fn set_prompt(&mut self, prompt: String) -> &mut Self
+    where
+        Self: Sized;
     async fn chat(&self, messages: Vec<Message>) -> Result<String>;
+    /// Reply holding JSON matching `schema`, named `name`. Agents without structured
+    /// outputs only have the instructions in `messages` to go by.
+    async fn chat_json(
+        &self,
+        messages: Vec<Message>,
+        _name: &str,
+        _schema: &Value,
+    ) -> Result<String> {
+        self.chat(messages).await
+    }
+    /// `chat_json`, relaying the reply to `sink` as it comes in. Agents that do not
+    /// stream relay it whole once it is in.
+    async fn chat_json_streamed(
+        &self,
+        messages: Vec<Message>,
+        name: &str,
+        schema: &Value,
+        sink: &ReplySink,
+    ) -> Result<String> {
+        let reply = self.chat_json(messages, name, schema).await?;
+        sink.send(&reply);
+        Ok(reply)
+    }
     fn prompt(&self) -> &str;
 }
 
@@ -18,10 +103,25 @@ pub struct Message {
     content: String,
 }
 
+impl Message {
+    pub fn new(role: &str, content: String) -> Self {
+        Self {
+            role: role.to_string(),
+            content,
+        }
+    }
+
+    pub fn content(&self) -> &str {
+        &self.content
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Exchanges {
     pub binance: BinanceExchange,
     pub eisen: EisenExchange,
+    #[serde(default)]
+    pub bridges: BridgeExchange,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -33,6 +133,16 @@ pub struct Explanation {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BinanceExchange {
     pub orders: Option<Vec<BinanceOrder>>,
+    pub transfers: Option<Vec<BinanceTransfer>>,
+}
+
+/// Move `amount` of `asset` between Binance wallets ("spot", "futures" or "coin_futures").
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BinanceTransfer {
+    pub from: String,
+    pub to: String,
+    pub asset: String,
+    pub amount: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,6 +152,77 @@ pub struct BinanceOrder {
     pub amount: String,
     pub price: String,
     pub side: String,
+    /// Quote asset of the pair: "USDT" (default), "USDC" or "USD" for coin-margined perpetuals.
+    pub quote: Option<String>,
+    /// Stage to execute the leg in; 0 when unset. See [`Strategy::stages`].
+    pub sequence: Option<u32>,
+    /// Name other legs' `dependsOn` refer to the leg by.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Ids of the legs to execute before this one. See [`Strategy::resolve_dependencies`].
+    #[serde(default, rename = "dependsOn")]
+    pub depends_on: Option<Vec<String>>,
+    /// How sure the agent is of the leg, from 0 to 1.
+    pub confidence: Option<f64>,
+    /// Rank of the leg among the strategy's, 1 the most important.
+    pub priority: Option<u32>,
+    /// Limit order that only rests on the book, expiring rather than taking liquidity.
+    #[serde(default, rename = "postOnly")]
+    pub post_only: Option<bool>,
+}
+
+impl BinanceOrder {
+    /// Limit price of the order, None for a market one (an empty `price`).
+    pub fn limit_price(&self) -> Result<Option<Decimal>> {
+        let price = self.price.trim();
+        if price.is_empty() {
+            return Ok(None);
+        }
+        match Decimal::from_str_exact(price) {
+            Ok(price) if price > Decimal::ZERO => Ok(Some(price)),
+            _ => Err(anyhow!("price {:?} is not a positive number", self.price)),
+        }
+    }
+
+    pub fn is_post_only(&self) -> bool {
+        self.post_only.unwrap_or(false)
+    }
+}
+
+/// Cross-chain transfers, executed before the swaps of their stage so bridged funds can
+/// be swapped.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct BridgeExchange {
+    pub transfers: Option<Vec<BridgeTransfer>>,
+}
+
+/// Move `amount` of `token` from one chain to another.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeTransfer {
+    pub token: String,
+    /// Token received on the destination chain; `token` when unset.
+    pub token_out: Option<String>,
+    /// Amount of `token`, or "max" for the wallet's whole balance.
+    pub amount: String,
+    pub from_chain_id: u64,
+    pub to_chain_id: u64,
+    /// Stage to execute the leg in; 0 when unset. See [`Strategy::stages`].
+    pub sequence: Option<u32>,
+    /// Name other legs' `dependsOn` refer to the leg by.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Ids of the legs to execute before this one. See [`Strategy::resolve_dependencies`].
+    #[serde(default)]
+    pub depends_on: Option<Vec<String>>,
+    pub confidence: Option<f64>,
+    pub priority: Option<u32>,
+}
+
+impl BridgeTransfer {
+    pub fn token_out(&self) -> &str {
+        self.token_out.as_deref().unwrap_or(&self.token)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -54,11 +235,191 @@ pub struct EisenExchange {
 pub struct EisenSwap {
     pub token_in: String,
     pub token_out: String,
+    /// Amount of `token_in`, or "max" for the wallet's whole balance.
     pub amount: String,
+    /// Chain to swap on; Base when unset.
+    pub chain_id: Option<u64>,
+    /// Slippage tolerance in bps; the configured default when unset.
+    pub slippage_bps: Option<u16>,
+    /// DEXes the swap may use; the chain's configured filter when unset.
+    pub dex_filter: Option<DexFilter>,
+    /// Stage to execute the leg in; 0 when unset. See [`Strategy::stages`].
+    pub sequence: Option<u32>,
+    /// Name other legs' `dependsOn` refer to the leg by.
+    #[serde(default)]
+    pub id: Option<String>,
+    /// Ids of the legs to execute before this one. See [`Strategy::resolve_dependencies`].
+    #[serde(default)]
+    pub depends_on: Option<Vec<String>>,
+    pub confidence: Option<f64>,
+    pub priority: Option<u32>,
+}
+
+impl EisenSwap {
+    pub fn chain_id(&self) -> u64 {
+        self.chain_id.unwrap_or(BASE_CHAIN_ID)
+    }
+
+    /// The swap's own DEX filter, or the one configured for its chain.
+    pub fn dex_filter_or(&self, configured: &HashMap<u64, DexFilter>) -> DexFilter {
+        self.dex_filter
+            .clone()
+            .or_else(|| configured.get(&self.chain_id()).cloned())
+            .unwrap_or_default()
+    }
 }
 
 #[derive(Deserialize, Debug, Serialize)]
 pub struct Strategy {
     pub exchanges: Exchanges,
     pub explanations: Vec<Explanation>,
+    /// Why the strategy as a whole, as the agent sees the market.
+    #[serde(default)]
+    pub rationale: Option<String>,
+}
+
+impl Strategy {
+    /// Stages the legs execute in, ascending. Stages run one after the other; within one
+    /// the Binance orders run first, so a failed one can stop the rest, then the bridge
+    /// transfers and swaps, which wait for the transfers into their chain.
+    pub fn stages(&self) -> Vec<u32> {
+        let exchanges = &self.exchanges;
+        let orders = exchanges
+            .binance
+            .orders
+            .iter()
+            .flatten()
+            .map(|o| o.sequence);
+        let transfers = exchanges
+            .bridges
+            .transfers
+            .iter()
+            .flatten()
+            .map(|t| t.sequence);
+        let swaps = exchanges.eisen.swaps.iter().flatten().map(|s| s.sequence);
+        let stages: BTreeSet<u32> = orders
+            .chain(transfers)
+            .chain(swaps)
+            .map(|sequence| sequence.unwrap_or_default())
+            .collect();
+        stages.into_iter().collect()
+    }
+
+    /// Stage of every leg, orders first, then bridge transfers and swaps: its
+    /// `sequence`, moved past the stages of the legs it depends on.
+    pub fn dependency_stages(&self) -> Result<Vec<u32>, String> {
+        let exchanges = &self.exchanges;
+        let orders = exchanges
+            .binance
+            .orders
+            .iter()
+            .flatten()
+            .map(|o| (o.id.as_deref(), o.sequence, o.depends_on.as_deref()));
+        let transfers = exchanges
+            .bridges
+            .transfers
+            .iter()
+            .flatten()
+            .map(|t| (t.id.as_deref(), t.sequence, t.depends_on.as_deref()));
+        let swaps = exchanges
+            .eisen
+            .swaps
+            .iter()
+            .flatten()
+            .map(|s| (s.id.as_deref(), s.sequence, s.depends_on.as_deref()));
+        let legs: Vec<_> = orders.chain(transfers).chain(swaps).collect();
+
+        let mut ids = HashMap::new();
+        for (i, (id, _, _)) in legs.iter().enumerate() {
+            if let Some(id) = id {
+                if ids.insert(*id, i).is_some() {
+                    return Err(format!("leg id {:?} is used twice", id));
+                }
+            }
+        }
+        let mut stages: Vec<u32> = legs
+            .iter()
+            .map(|(_, sequence, _)| sequence.unwrap_or_default())
+            .collect();
+        // Every pass settles one more link of the longest chain; a pass more than there
+        // are legs is a cycle
+        for _ in 0..=legs.len() {
+            let mut moved = false;
+            for (i, (_, _, depends_on)) in legs.iter().enumerate() {
+                for id in depends_on.unwrap_or_default() {
+                    let &j = ids
+                        .get(id.as_str())
+                        .ok_or_else(|| format!("dependency {:?} is no leg's id", id))?;
+                    if stages[i] <= stages[j] {
+                        stages[i] = stages[j] + 1;
+                        moved = true;
+                    }
+                }
+            }
+            if !moved {
+                return Ok(stages);
+            }
+        }
+        Err("legs depend on each other in a cycle".to_string())
+    }
+
+    /// Moves every leg to a stage after those of the legs it depends on, so dependent
+    /// legs execute in order and independent ones alongside each other.
+    pub fn resolve_dependencies(&mut self) -> Result<(), String> {
+        let stages = self.dependency_stages()?;
+        let exchanges = &mut self.exchanges;
+        let orders = exchanges.binance.orders.iter_mut().flatten();
+        let transfers = exchanges.bridges.transfers.iter_mut().flatten();
+        let swaps = exchanges.eisen.swaps.iter_mut().flatten();
+        let sequences = orders
+            .map(|o| &mut o.sequence)
+            .chain(transfers.map(|t| &mut t.sequence))
+            .chain(swaps.map(|s| &mut s.sequence));
+        for (sequence, stage) in sequences.zip(stages) {
+            if sequence.unwrap_or_default() != stage {
+                *sequence = Some(stage);
+            }
+        }
+        Ok(())
+    }
+
+    /// Legs the agent rated less confident than `min_confidence`; legs without a
+    /// confidence are not counted.
+    pub fn low_confidence_legs(&self, min_confidence: f64) -> Vec<String> {
+        let exchanges = &self.exchanges;
+        let orders = exchanges
+            .binance
+            .orders
+            .iter()
+            .flatten()
+            .map(|o| (format!("{} {} {}", o.side, o.amount, o.token), o.confidence));
+        let transfers = exchanges.bridges.transfers.iter().flatten().map(|t| {
+            (
+                format!(
+                    "bridge {} {} from chain {} to chain {}",
+                    t.amount, t.token, t.from_chain_id, t.to_chain_id
+                ),
+                t.confidence,
+            )
+        });
+        let swaps = exchanges.eisen.swaps.iter().flatten().map(|s| {
+            (
+                format!("swap {} {} to {}", s.amount, s.token_in, s.token_out),
+                s.confidence,
+            )
+        });
+        orders
+            .chain(transfers)
+            .chain(swaps)
+            .filter_map(|(leg, confidence)| {
+                let confidence = confidence.filter(|confidence| *confidence < min_confidence)?;
+                Some(format!("{} (confidence {:.2})", leg, confidence))
+            })
+            .collect()
+    }
+}
+
+/// Whether a leg with `sequence` executes in `stage`; every leg does without a stage.
+pub fn in_stage(sequence: Option<u32>, stage: Option<u32>) -> bool {
+    stage.is_none_or(|stage| sequence.unwrap_or_default() == stage)
 }
@@ -1,5 +1,6 @@
-use crate::agent::Agent;
+use crate::agent::usage::{TokenUsage, UsageMeter};
 use crate::agent::Message;
+use crate::agent::{Agent, ReplySink};
 use anyhow::Result;
 use async_openai::{
     config::OpenAIConfig,
@@ -7,45 +8,110 @@ use async_openai::{
         ChatCompletionRequestAssistantMessage, ChatCompletionRequestAssistantMessageContent,
         ChatCompletionRequestMessage, ChatCompletionRequestSystemMessage,
         ChatCompletionRequestSystemMessageContent, ChatCompletionRequestUserMessage,
-        ChatCompletionRequestUserMessageContent, CreateChatCompletionRequest,
+        ChatCompletionRequestUserMessageContent, ChatCompletionStreamOptions, CompletionUsage,
+        CreateChatCompletionRequest, ResponseFormat, ResponseFormatJsonSchema,
     },
     Client,
 };
 use async_trait::async_trait;
+use futures::StreamExt;
+use serde_json::Value;
+
+fn token_usage(usage: &CompletionUsage) -> TokenUsage {
+    TokenUsage {
+        prompt_tokens: usage.prompt_tokens.into(),
+        completion_tokens: usage.completion_tokens.into(),
+    }
+}
 
 pub struct OpenAIAgent {
     client: Client<OpenAIConfig>,
     model: String,
     temperature: f32,
     prompt: String,
+    structured_outputs: bool,
+    usage: Option<UsageMeter>,
 }
 
 impl OpenAIAgent {
-    pub fn new(api_key: String, model: String, temperature: f32) -> Self {
-        let config = OpenAIConfig::new().with_api_key(api_key);
-        let client = Client::with_config(config);
+    pub fn new(
+        http_client: reqwest::Client,
+        api_key: String,
+        model: String,
+        temperature: f32,
+    ) -> Self {
+        Self::with_config(
+            http_client,
+            OpenAIConfig::new().with_api_key(api_key),
+            model,
+            temperature,
+        )
+    }
+
+    /// Agent of any OpenAI-compatible API, e.g. a local Ollama or vLLM server.
+    pub fn with_base_url(
+        http_client: reqwest::Client,
+        base_url: String,
+        api_key: Option<String>,
+        model: String,
+        temperature: f32,
+    ) -> Self {
+        let config = OpenAIConfig::new()
+            .with_api_base(base_url)
+            .with_api_key(api_key.unwrap_or_default());
+        Self::with_config(http_client, config, model, temperature)
+    }
+
+    fn with_config(
+        http_client: reqwest::Client,
+        config: OpenAIConfig,
+        model: String,
+        temperature: f32,
+    ) -> Self {
+        let client = Client::with_config(config).with_http_client(http_client);
 
         Self {
             client,
             model,
             temperature,
             prompt: String::new(),
+            structured_outputs: true,
+            usage: None,
         }
     }
-}
 
-#[async_trait]
-impl Agent for OpenAIAgent {
-    fn set_prompt(&mut self, prompt: String) -> &mut Self {
-        self.prompt = prompt;
+    /// Whether `chat_json` holds replies to the schema; servers without support for
+    /// JSON schema response formats reject requests asking for one.
+    pub fn with_structured_outputs(mut self, structured_outputs: bool) -> Self {
+        self.structured_outputs = structured_outputs;
         self
     }
 
-    fn prompt(&self) -> &str {
-        &self.prompt
+    /// Record the tokens of every call with `meter`, when set.
+    pub fn with_usage(mut self, meter: Option<UsageMeter>) -> Self {
+        self.usage = meter;
+        self
     }
 
-    async fn chat(&self, messages: Vec<Message>) -> Result<String> {
+    /// Format holding replies to `schema`, if the server supports one.
+    fn json_format(&self, name: &str, schema: &Value) -> Option<ResponseFormat> {
+        self.structured_outputs.then(|| ResponseFormat::JsonSchema {
+            json_schema: ResponseFormatJsonSchema {
+                description: None,
+                name: name.to_string(),
+                schema: Some(schema.clone()),
+                strict: Some(true),
+            },
+        })
+    }
+
+    /// Completion of `messages`, streamed into `sink` when set.
+    async fn complete(
+        &self,
+        messages: Vec<Message>,
+        response_format: Option<ResponseFormat>,
+        sink: Option<&ReplySink>,
+    ) -> Result<String> {
         // Convert our Message type to the library's ChatCompletionRequestMessage type
         // Debug print all messages
         println!("Sending the following messages to OpenAI:");
@@ -88,13 +154,20 @@ impl Agent for OpenAIAgent {
             model: self.model.clone(),
             messages: request_messages,
             temperature: None,
+            response_format,
             ..Default::default()
         };
+        if let Some(sink) = sink {
+            return self.stream(request, sink).await;
+        }
 
         // Send the request
         let response = self.client.chat().create(request).await?;
 
         println!("Response: {:?}", response);
+        if let (Some(meter), Some(usage)) = (&self.usage, &response.usage) {
+            meter.record(&response.model, token_usage(usage));
+        }
 
         // Extract the response content
         let choice = response
@@ -104,4 +177,67 @@ impl Agent for OpenAIAgent {
 
         Ok(choice.message.content.clone().unwrap_or_default())
     }
+
+    async fn stream(
+        &self,
+        mut request: CreateChatCompletionRequest,
+        sink: &ReplySink,
+    ) -> Result<String> {
+        // The last chunk, without choices, carries the usage of the whole completion
+        request.stream_options = Some(ChatCompletionStreamOptions {
+            include_usage: true,
+        });
+        let mut chunks = self.client.chat().create_stream(request).await?;
+        let mut reply = String::new();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            if let (Some(meter), Some(usage)) = (&self.usage, &chunk.usage) {
+                meter.record(&chunk.model, token_usage(usage));
+            }
+            for choice in chunk.choices.iter().filter(|choice| choice.index == 0) {
+                if let Some(content) = &choice.delta.content {
+                    sink.send(content);
+                    reply.push_str(content);
+                }
+            }
+        }
+        Ok(reply)
+    }
+}
+
+#[async_trait]
+impl Agent for OpenAIAgent {
+    fn set_prompt(&mut self, prompt: String) -> &mut Self {
+        self.prompt = prompt;
+        self
+    }
+
+    fn prompt(&self) -> &str {
+        &self.prompt
+    }
+
+    async fn chat(&self, messages: Vec<Message>) -> Result<String> {
+        self.complete(messages, None, None).await
+    }
+
+    async fn chat_json(
+        &self,
+        messages: Vec<Message>,
+        name: &str,
+        schema: &Value,
+    ) -> Result<String> {
+        self.complete(messages, self.json_format(name, schema), None)
+            .await
+    }
+
+    async fn chat_json_streamed(
+        &self,
+        messages: Vec<Message>,
+        name: &str,
+        schema: &Value,
+        sink: &ReplySink,
+    ) -> Result<String> {
+        self.complete(messages, self.json_format(name, schema), Some(sink))
+            .await
+    }
 }
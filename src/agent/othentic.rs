@@ -1,3 +1,7 @@
+use crate::agent::attestation::{OperatorSet, TaskResponse};
+use crate::agent::parse::parse_strategy;
+use crate::agent::payload::StrategyPayload;
+use crate::agent::usage::{TokenUsage, UsageMeter};
 use crate::agent::Strategy;
 use anyhow::Result;
 use reqwest::Client as ReqwestClient;
@@ -7,54 +11,87 @@ pub struct OthenticAgent {
     port: u16,
     client: ReqwestClient,
     task_definition_id: String,
+    // Set to only trust responses signed by one of these operators
+    operators: Option<OperatorSet>,
+    usage: Option<UsageMeter>,
 }
 
 impl OthenticAgent {
-    pub fn new(host: String, port: u16, task_definition_id: Option<String>) -> Self {
-        let client = ReqwestClient::new();
+    pub fn new(
+        client: ReqwestClient,
+        host: String,
+        port: u16,
+        task_definition_id: Option<String>,
+    ) -> Self {
         Self {
             host,
             port,
             client,
             task_definition_id: task_definition_id.unwrap_or("0".to_string()),
+            operators: None,
+            usage: None,
         }
     }
 
+    /// Reject responses not signed by their performer, or whose performer is not one
+    /// of `operators`.
+    pub fn with_operators(mut self, operators: OperatorSet) -> Self {
+        self.operators = Some(operators);
+        self
+    }
+
+    /// Record each call in `meter`. The node reports no tokens, so only calls count.
+    pub fn with_usage(mut self, meter: Option<UsageMeter>) -> Self {
+        self.usage = meter;
+        self
+    }
+
+    /// Strategy for `payload`, sent as the task's versioned JSON document alongside the
+    /// model and task definition.
     pub async fn get_strategy(
         &self,
-        model: &String,
-        price: &String,
-        portfolio: &String,
-        apr: &String,
+        model: &str,
+        payload: &StrategyPayload<'_>,
     ) -> Result<Strategy> {
         let url = format!("http://{}:{}/task/execute", self.host, self.port);
 
+        let mut task = serde_json::to_value(payload)?;
+        task["model"] = serde_json::json!(model);
+        task["taskDefinitionId"] = serde_json::json!(self.task_definition_id);
         let response = self
             .client
             .post(&url)
-            .json(&serde_json::json!({
-                "model": model,
-                "price": price,
-                "portfolio": portfolio,
-                "apr": apr,
-                "taskDefinitionId": self.task_definition_id
-            }))
+            .json(&task)
             .send()
             .await?;
+        if let Some(meter) = &self.usage {
+            meter.record(model, TokenUsage::default());
+        }
 
         let strategy = response.text().await?;
         // Parse the strategy string to JSON
         let strategy_json: serde_json::Value = serde_json::from_str(&strategy)?;
 
-        let strategy = strategy_json
-            .get("data")
-            .and_then(|d| d.get("strategy"))
-            .and_then(|s| s.as_str())
+        let task_response: TaskResponse = serde_json::from_value(
+            strategy_json
+                .get("data")
+                .cloned()
+                .unwrap_or(serde_json::json!({})),
+        )?;
+        if let Some(operators) = &self.operators {
+            let task_definition_id = self.task_definition_id.parse().map_err(|e| {
+                anyhow::anyhow!("Invalid task definition id {}: {}", self.task_definition_id, e)
+            })?;
+            let operator = operators.verify(&task_response, task_definition_id)?;
+            println!("Strategy attested by operator {}", operator);
+        }
+        let strategy = task_response
+            .strategy
+            .as_deref()
             .unwrap_or("No strategy found");
         // Parse the strategy string to a Strategy struct
         println!("Strategy from the agent:\n{}", strategy);
-        let strategy_struct: Strategy = serde_json::from_str(strategy)
-            .map_err(|e| anyhow::anyhow!("Failed to parse strategy: {}", e))?;
+        let strategy_struct: Strategy = parse_strategy(strategy)?;
 
         // Pretty print the strategy struct as JSON
         let pretty_json = serde_json::to_string_pretty(&strategy_struct)
@@ -0,0 +1,134 @@
+use crate::agent::Strategy;
+use serde_json::Value;
+use thiserror::Error;
+
+/// Why an agent's reply is not a strategy.
+#[derive(Debug, Error)]
+pub enum StrategyParseError {
+    #[error("Strategy reply holds no JSON object")]
+    NoJson,
+    #[error("Strategy reply does not match the schema at {path}: {message}")]
+    Schema { path: String, message: String },
+}
+
+/// Text of the balanced `{...}` starting at the start of `text`, braces inside strings
+/// left out of the count.
+fn balanced_object(text: &str) -> Option<&str> {
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '{' => depth += 1,
+            '}' => {
+                depth = depth.checked_sub(1)?;
+                if depth == 0 {
+                    return Some(&text[..=i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// `json` without commas right before a closing `}` or `]`.
+fn strip_trailing_commas(json: &str) -> String {
+    let mut stripped = String::with_capacity(json.len());
+    let mut in_string = false;
+    let mut escaped = false;
+    for (i, c) in json.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == ',' {
+            let next = json[i + 1..].trim_start().chars().next();
+            if matches!(next, Some('}') | Some(']')) {
+                continue;
+            }
+        }
+        stripped.push(c);
+    }
+    stripped
+}
+
+/// First JSON object in `reply` that parses, once trailing commas are dropped. Code
+/// fences and commentary around it are skipped over.
+pub fn extract_json(reply: &str) -> Option<Value> {
+    reply
+        .char_indices()
+        .filter(|(_, c)| *c == '{')
+        .filter_map(|(start, _)| balanced_object(&reply[start..]))
+        .find_map(|candidate| {
+            serde_json::from_str::<Value>(candidate)
+                .or_else(|_| serde_json::from_str(&strip_trailing_commas(candidate)))
+                .ok()
+        })
+}
+
+/// Strategy in an agent's reply, or where the reply departs from the schema.
+pub fn parse_strategy(reply: &str) -> Result<Strategy, StrategyParseError> {
+    let json = extract_json(reply).ok_or(StrategyParseError::NoJson)?;
+    serde_path_to_error::deserialize(json).map_err(|e| StrategyParseError::Schema {
+        path: e.path().to_string(),
+        message: e.inner().to_string(),
+    })
+}
+
+/// Re-prompt asking an agent to fix a reply that was not a strategy.
+pub fn repair_prompt(error: &StrategyParseError) -> String {
+    format!(
+        "Your previous reply could not be read: {}. Reply with only the corrected \
+         strategy as a JSON object.",
+        error
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_strategy() {
+        let reply = "Here is the plan {not json}:\n```json\n{\n  \"exchanges\": {\
+            \"binance\": {\"orders\": [{\"position\": \"short\", \"token\": \"ETH\", \
+            \"amount\": \"0.5\", \"price\": \"\", \"side\": \"SELL\",},], \"transfers\": null},\
+            \"eisen\": {\"swaps\": null}\n  },\n  \"explanations\": [{\"title\": \"{Hedge}\", \
+            \"content\": \"Funding \\\"pays\\\"\"}],\n}\n```\nLet me know!";
+        let strategy = parse_strategy(reply).unwrap();
+        let orders = strategy.exchanges.binance.orders.unwrap();
+        assert_eq!(orders[0].side, "SELL");
+        assert_eq!(strategy.explanations[0].title, "{Hedge}");
+
+        assert!(matches!(
+            parse_strategy("No strategy found"),
+            Err(StrategyParseError::NoJson)
+        ));
+        let error = parse_strategy(
+            r#"{"exchanges": {"binance": {"orders": [{"token": "ETH"}], "transfers": null},
+                "eisen": {"swaps": null}}, "explanations": []}"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "Strategy reply does not match the schema at exchanges.binance.orders[0]: \
+             missing field `position`"
+        );
+    }
+}
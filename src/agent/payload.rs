@@ -0,0 +1,156 @@
+use crate::agent::memory::ExecutionMemory;
+use crate::config::RiskConfig;
+use crate::engine::StrategyInput;
+use crate::executor::eisen::ChainPortfolio;
+use crate::feed::binance::Positioning;
+use crate::feed::indicators::TechnicalIndicators;
+use crate::portfolio::binance::AccountInfo;
+use crate::portfolio::margin::MarginHealth;
+use crate::types::MarketPrices;
+use crate::utils::price::PriceData;
+use crate::yields::CachedYields;
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Layout of [`StrategyPayload`]; bumped whenever a field changes meaning or is removed.
+pub const PAYLOAD_VERSION: u32 = 2;
+
+/// What the agent is asked a strategy from, as one JSON document rather than prompt
+/// text, versioned so the node can tell layouts apart.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StrategyPayload<'a> {
+    pub version: u32,
+    pub prices: PricesPayload<'a>,
+    pub portfolio: PortfolioPayload<'a>,
+    pub yields: &'a CachedYields,
+    pub risk_limits: RiskLimitsPayload<'a>,
+    pub recent_executions: &'a [ExecutionMemory],
+    /// Why the previous strategy was rejected or could not be read, when asked again.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub feedback: Option<String>,
+}
+
+/// Market data, by futures symbol where per symbol; symbols that could not be fetched
+/// are missing.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PricesPayload<'a> {
+    pub market: &'a MarketPrices,
+    pub funding: &'a HashMap<String, PriceData>,
+    pub positioning: &'a HashMap<String, Positioning>,
+    pub indicators: &'a HashMap<String, TechnicalIndicators>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortfolioPayload<'a> {
+    pub binance: &'a AccountInfo,
+    pub onchain: &'a ChainPortfolio,
+    pub margin_health: &'a MarginHealth,
+}
+
+/// The configured risk limits, in the payload's camelCase; unset limits are null.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskLimitsPayload<'a> {
+    pub max_order_notional_usd: Option<f64>,
+    pub max_leverage: Option<f64>,
+    pub max_daily_turnover_usd: Option<f64>,
+    pub max_net_delta_usd: Option<f64>,
+    pub max_price_deviation_pct: Option<f64>,
+    pub forbidden_symbols: &'a [String],
+}
+
+impl<'a> From<&'a RiskConfig> for RiskLimitsPayload<'a> {
+    fn from(risk: &'a RiskConfig) -> Self {
+        Self {
+            max_order_notional_usd: risk.max_order_notional_usd,
+            max_leverage: risk.max_leverage,
+            max_daily_turnover_usd: risk.max_daily_turnover_usd,
+            max_net_delta_usd: risk.max_net_delta_usd,
+            max_price_deviation_pct: risk.max_price_deviation_pct,
+            forbidden_symbols: &risk.forbidden_symbols,
+        }
+    }
+}
+
+impl<'a> StrategyPayload<'a> {
+    pub fn new(input: &StrategyInput<'a>, feedback: Option<String>) -> Self {
+        Self {
+            version: PAYLOAD_VERSION,
+            prices: PricesPayload {
+                market: input.prices,
+                funding: input.funding,
+                positioning: input.positioning,
+                indicators: input.indicators,
+            },
+            portfolio: PortfolioPayload {
+                binance: input.binance,
+                onchain: input.onchain,
+                margin_health: input.margin_health,
+            },
+            yields: input.yield_data,
+            risk_limits: input.risk.into(),
+            recent_executions: input.history,
+            feedback,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::MarginHealthConfig;
+    use crate::portfolio::binance::test_account;
+    use crate::types::test_prices;
+    use serde_json::json;
+
+    #[test]
+    fn test_strategy_payload() {
+        let binance = test_account("1000", serde_json::json!([]));
+        let onchain = ChainPortfolio::default();
+        let prices = test_prices();
+        let (funding, positioning, indicators) = (HashMap::new(), HashMap::new(), HashMap::new());
+        let margin_health = MarginHealth::new(&binance, &MarginHealthConfig::default());
+        let yields = CachedYields::default();
+        let risk = RiskConfig {
+            max_leverage: Some(3.0),
+            ..RiskConfig::default()
+        };
+        let input = StrategyInput {
+            price_data: "",
+            portfolio: "",
+            yields: "",
+            binance: &binance,
+            onchain: &onchain,
+            prices: &prices,
+            funding: &funding,
+            positioning: &positioning,
+            indicators: &indicators,
+            margin_health: &margin_health,
+            yield_data: &yields,
+            risk: &risk,
+            history: &[],
+        };
+
+        let payload = serde_json::to_value(StrategyPayload::new(&input, None)).unwrap();
+        assert_eq!(payload["version"], json!(PAYLOAD_VERSION));
+        assert_eq!(payload["prices"]["market"]["eth"], json!(2000.0));
+        assert_eq!(
+            payload["portfolio"]["binance"]["totalWalletBalance"],
+            json!("1000")
+        );
+        assert_eq!(payload["riskLimits"]["maxLeverage"], json!(3.0));
+        assert_eq!(payload["riskLimits"]["maxOrderNotionalUsd"], json!(null));
+        assert_eq!(payload["riskLimits"]["forbiddenSymbols"], json!([]));
+        assert_eq!(payload["recentExecutions"], json!([]));
+        assert!(payload.get("feedback").is_none());
+
+        let payload = StrategyPayload::new(&input, Some("Too much leverage".to_string()));
+        assert_eq!(
+            serde_json::to_value(payload).unwrap()["feedback"],
+            json!("Too much leverage")
+        );
+    }
+}
@@ -0,0 +1,250 @@
+use crate::config::ModelPricing;
+use crate::storage::WalStore;
+use anyhow::Result;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+/// Tokens one agent call used, as its provider reports them.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct TokenUsage {
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// Calls of one model through one API key on one day, and what they cost.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DailyUsage {
+    pub day: NaiveDate,
+    pub provider: String,
+    /// Environment variable the API key is read from, or "none" without a key, so
+    /// the key itself is never stored.
+    pub api_key: String,
+    pub model: String,
+    pub calls: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    /// Estimated from the configured pricing; 0 for unpriced models.
+    pub cost_usd: f64,
+}
+
+/// Token usage and estimated cost of agent calls per day, API key and model,
+/// persisted in a WAL store.
+pub struct UsageLedger {
+    days: WalStore<DailyUsage>,
+    pricing: HashMap<String, ModelPricing>,
+    // Serializes the read-modify-write of a day's entry
+    lock: Mutex<()>,
+}
+
+impl UsageLedger {
+    pub fn open(data_dir: &Path, pricing: &HashMap<String, ModelPricing>) -> Result<Self> {
+        Ok(Self {
+            days: WalStore::open(data_dir, "agent_usage")?,
+            pricing: pricing
+                .iter()
+                .map(|(model, pricing)| (model.to_lowercase(), pricing.clone()))
+                .collect(),
+            lock: Mutex::new(()),
+        })
+    }
+
+    /// Estimated USD of `usage` on `model`, priced by the longest configured model
+    /// name it starts with, so dated snapshots are priced as their model.
+    pub fn cost(&self, model: &str, usage: TokenUsage) -> f64 {
+        let model = model.to_lowercase();
+        let Some((_, pricing)) = self
+            .pricing
+            .iter()
+            .filter(|(name, _)| model.starts_with(name.as_str()))
+            .max_by_key(|(name, _)| name.len())
+        else {
+            return 0.0;
+        };
+        (usage.prompt_tokens as f64 * pricing.input_per_mtok
+            + usage.completion_tokens as f64 * pricing.output_per_mtok)
+            / 1_000_000.0
+    }
+
+    pub fn record(
+        &self,
+        provider: &str,
+        api_key: &str,
+        model: &str,
+        usage: TokenUsage,
+        at: DateTime<Utc>,
+    ) -> Result<()> {
+        let day = at.date_naive();
+        let key = format!("{}/{}/{}/{}", day, provider, api_key, model);
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let mut entry = self.days.get(&key).unwrap_or_else(|| DailyUsage {
+            day,
+            provider: provider.to_string(),
+            api_key: api_key.to_string(),
+            model: model.to_string(),
+            calls: 0,
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            cost_usd: 0.0,
+        });
+        entry.calls += 1;
+        entry.prompt_tokens += usage.prompt_tokens;
+        entry.completion_tokens += usage.completion_tokens;
+        entry.cost_usd += self.cost(model, usage);
+        self.days.put(key, entry)
+    }
+
+    /// Usage on `since` and later days, of `provider` when set, oldest day first.
+    pub fn query(&self, since: NaiveDate, provider: Option<&str>) -> Vec<DailyUsage> {
+        let mut days: Vec<DailyUsage> = self
+            .days
+            .entries()
+            .into_iter()
+            .map(|(_, usage)| usage)
+            .filter(|usage| usage.day >= since)
+            .filter(|usage| provider.is_none_or(|p| usage.provider.eq_ignore_ascii_case(p)))
+            .collect();
+        days.sort_by(|a, b| {
+            (a.day, &a.provider, &a.api_key, &a.model).cmp(&(
+                b.day,
+                &b.provider,
+                &b.api_key,
+                &b.model,
+            ))
+        });
+        days
+    }
+
+    /// Counters of every recorded call, in the Prometheus text format.
+    pub fn metrics(&self) -> String {
+        let mut totals: BTreeMap<(String, String, String), UsageTotal> = BTreeMap::new();
+        for (_, usage) in self.days.entries() {
+            let total = totals
+                .entry((usage.provider, usage.api_key, usage.model))
+                .or_default();
+            total.calls += usage.calls;
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+            total.cost_usd += usage.cost_usd;
+        }
+        let mut metrics = String::new();
+        let mut counter = |name: &str, help: &str, value: &dyn Fn(&UsageTotal) -> f64| {
+            let _ = writeln!(metrics, "# HELP chill_pm_{} {}", name, help);
+            let _ = writeln!(metrics, "# TYPE chill_pm_{} counter", name);
+            for ((provider, api_key, model), total) in &totals {
+                let _ = writeln!(
+                    metrics,
+                    "chill_pm_{}{{provider=\"{}\",api_key=\"{}\",model=\"{}\"}} {}",
+                    name,
+                    provider,
+                    api_key,
+                    model,
+                    value(total)
+                );
+            }
+        };
+        counter("llm_calls_total", "Agent calls", &|t| t.calls as f64);
+        counter("llm_prompt_tokens_total", "Prompt tokens", &|t| {
+            t.prompt_tokens as f64
+        });
+        counter("llm_completion_tokens_total", "Completion tokens", &|t| {
+            t.completion_tokens as f64
+        });
+        counter("llm_cost_usd_total", "Estimated cost in USD", &|t| {
+            t.cost_usd
+        });
+        metrics
+    }
+}
+
+#[derive(Debug, Default)]
+struct UsageTotal {
+    calls: u64,
+    prompt_tokens: u64,
+    completion_tokens: u64,
+    cost_usd: f64,
+}
+
+/// Where an agent records the usage of its calls: the ledger, and the provider and
+/// API key they are made with.
+#[derive(Clone)]
+pub struct UsageMeter {
+    ledger: Arc<UsageLedger>,
+    provider: String,
+    api_key: String,
+}
+
+impl UsageMeter {
+    pub fn new(ledger: Arc<UsageLedger>, provider: &str, api_key: &str) -> Self {
+        Self {
+            ledger,
+            provider: provider.to_string(),
+            api_key: api_key.to_string(),
+        }
+    }
+
+    /// Record a call; a failure to is logged, never failing the call.
+    pub fn record(&self, model: &str, usage: TokenUsage) {
+        println!(
+            "{} agent call to {}: {} prompt and {} completion tokens",
+            self.provider, model, usage.prompt_tokens, usage.completion_tokens
+        );
+        if let Err(e) = self
+            .ledger
+            .record(&self.provider, &self.api_key, model, usage, Utc::now())
+        {
+            println!("Failed to record the agent's token usage: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_usage_ledger() -> Result<()> {
+        let dir = tempdir()?;
+        let pricing = HashMap::from([
+            ("gpt-4o".to_string(), ModelPricing::new(2.5, 10.0)),
+            ("gpt-4o-mini".to_string(), ModelPricing::new(0.15, 0.6)),
+        ]);
+        let ledger = UsageLedger::open(dir.path(), &pricing)?;
+        let usage = TokenUsage {
+            prompt_tokens: 1_000_000,
+            completion_tokens: 100_000,
+        };
+        assert_eq!(ledger.cost("gpt-4o-2024-08-06", usage), 3.5);
+        assert!((ledger.cost("gpt-4o-mini", usage) - 0.21).abs() < 1e-9);
+        assert_eq!(ledger.cost("llama3.1", usage), 0.0);
+
+        let day = |d: u32| NaiveDate::from_ymd_opt(2026, 10, d).unwrap();
+        let at = |d: u32| day(d).and_hms_opt(12, 0, 0).unwrap().and_utc();
+        ledger.record("openai", "OPENAI_API_KEY", "gpt-4o", usage, at(1))?;
+        ledger.record("openai", "OPENAI_API_KEY", "gpt-4o", usage, at(1))?;
+        ledger.record("openai", "OPENAI_API_KEY", "gpt-4o", usage, at(2))?;
+        ledger.record("local", "none", "llama3.1", usage, at(2))?;
+
+        let ledger = UsageLedger::open(dir.path(), &pricing)?;
+        let days = ledger.query(day(1), None);
+        assert_eq!(days.len(), 3);
+        assert_eq!((days[0].day, days[0].calls), (day(1), 2));
+        assert_eq!(days[0].prompt_tokens, 2_000_000);
+        assert_eq!(days[0].cost_usd, 7.0);
+        assert_eq!(ledger.query(day(2), Some("OpenAI")).len(), 1);
+
+        let metrics = ledger.metrics();
+        assert!(metrics.contains(
+            "chill_pm_llm_calls_total{provider=\"openai\",api_key=\"OPENAI_API_KEY\",model=\"gpt-4o\"} 3"
+        ));
+        assert!(metrics.contains(
+            "chill_pm_llm_cost_usd_total{provider=\"local\",api_key=\"none\",model=\"llama3.1\"} 0"
+        ));
+        Ok(())
+    }
+}
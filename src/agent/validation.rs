@@ -0,0 +1,556 @@
+use super::Strategy;
+use crate::config::{ExposureConfig, StrategyValidationConfig};
+use crate::executor::binance::{futures_symbol, TransferType};
+use crate::executor::eisen::{validate_slippage_bps, ChainData, ChainPortfolio, SwapAmount};
+use crate::portfolio::binance::AccountInfo;
+use crate::rebalance::token_price;
+use crate::types::MarketPrices;
+use crate::utils::rounding::round_scaled_amount;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Every problem found in a strategy; it is rejected as a whole.
+#[derive(Debug, thiserror::Error)]
+#[error("Strategy rejected: {}", .violations.join("; "))]
+pub struct StrategyValidationError {
+    pub violations: Vec<String>,
+}
+
+/// What a strategy is checked against: the portfolio it was made for, prices to value
+/// its legs and the metadata of the chains it swaps and bridges on.
+pub struct StrategyContext<'a> {
+    pub binance: &'a AccountInfo,
+    pub onchain: &'a ChainPortfolio,
+    pub prices: &'a MarketPrices,
+    pub exposure: &'a ExposureConfig,
+    pub chain_data: &'a HashMap<u64, ChainData>,
+}
+
+impl StrategyContext<'_> {
+    fn price(&self, token: &str) -> Option<f64> {
+        token_price(
+            token,
+            self.exposure,
+            self.binance,
+            self.onchain,
+            self.prices,
+        )
+    }
+}
+
+/// Check an agent's strategy before any of it executes: known tokens, positive amounts,
+/// order sides matching their positions, per-order notional and total turnover caps.
+/// Legs that cannot be priced are left out of the turnover.
+pub fn validate_strategy(
+    strategy: &Strategy,
+    context: &StrategyContext,
+    config: &StrategyValidationConfig,
+) -> Result<(), StrategyValidationError> {
+    let mut violations = Vec::new();
+    let mut turnover_usd = 0.0;
+    // Position per symbol as the orders before the current one leave it
+    let mut positions: HashMap<String, f64> = HashMap::new();
+
+    for (i, order) in strategy
+        .exchanges
+        .binance
+        .orders
+        .iter()
+        .flatten()
+        .enumerate()
+    {
+        let symbol = futures_symbol(&order.token, order.quote.as_deref());
+        let mut violation =
+            |problem: String| violations.push(format!("order {} ({}): {}", i + 1, symbol, problem));
+        if !config
+            .perp_tokens
+            .iter()
+            .any(|token| token.eq_ignore_ascii_case(&order.token))
+        {
+            violation(format!(
+                "token {} is not tradeable, expected one of {}",
+                order.token,
+                config.perp_tokens.join(", ")
+            ));
+            continue;
+        }
+        let amount = match order.amount.trim().parse::<f64>() {
+            Ok(amount) if amount.is_finite() && amount > 0.0 => amount,
+            _ => {
+                violation(format!(
+                    "amount {:?} is not a positive number",
+                    order.amount
+                ));
+                continue;
+            }
+        };
+        let buy = match order.side.to_uppercase().as_str() {
+            "BUY" => true,
+            "SELL" => false,
+            _ => {
+                violation(format!("side {:?} is not BUY or SELL", order.side));
+                continue;
+            }
+        };
+        match order.limit_price() {
+            Err(e) => violation(e.to_string()),
+            Ok(None) if order.is_post_only() => {
+                violation("post-only order has no limit price".to_string())
+            }
+            Ok(_) => {}
+        }
+        let current = positions.entry(symbol.clone()).or_insert_with(|| {
+            context
+                .binance
+                .positions
+                .iter()
+                .filter(|position| position.symbol == symbol)
+                .filter_map(|position| position.position_amt.to_f64())
+                .sum()
+        });
+        // Opening trades follow the position; the opposite side may only reduce it, as
+        // left by the earlier orders
+        let fits = match (order.position.to_lowercase().as_str(), buy) {
+            ("long", true) | ("short", false) => true,
+            ("long", false) if amount <= *current => true,
+            ("short", true) if amount <= -*current => true,
+            ("long", false) | ("short", true) => {
+                violation(format!(
+                    "{} {} of a {} position exceeds the open position of {}",
+                    order.side, amount, order.position, current
+                ));
+                false
+            }
+            _ => {
+                violation(format!(
+                    "position {:?} is not long or short",
+                    order.position
+                ));
+                false
+            }
+        };
+        if fits {
+            *current += if buy { amount } else { -amount };
+        }
+        match context.price(&order.token) {
+            Some(price) => {
+                let notional = amount * price;
+                turnover_usd += notional;
+                if notional > config.max_order_notional_usd {
+                    violation(format!(
+                        "notional ${:.2} exceeds the ${:.2} cap",
+                        notional, config.max_order_notional_usd
+                    ));
+                }
+            }
+            None => violation(format!("{} cannot be priced", order.token)),
+        }
+    }
+
+    for (i, transfer) in strategy
+        .exchanges
+        .binance
+        .transfers
+        .iter()
+        .flatten()
+        .enumerate()
+    {
+        if TransferType::from_wallets(&transfer.from, &transfer.to).is_none() {
+            violations.push(format!(
+                "transfer {}: no transfer from {:?} to {:?}",
+                i + 1,
+                transfer.from,
+                transfer.to
+            ));
+        }
+        if !transfer
+            .amount
+            .trim()
+            .parse::<f64>()
+            .is_ok_and(|amount| amount > 0.0)
+        {
+            violations.push(format!(
+                "transfer {}: amount {:?} is not a positive number",
+                i + 1,
+                transfer.amount
+            ));
+        }
+    }
+
+    let known = |chain_id: u64, token: &str| -> Result<(), String> {
+        let chain_data = context
+            .chain_data
+            .get(&chain_id)
+            .ok_or_else(|| format!("chain {} is not supported", chain_id))?;
+        chain_data
+            .token(token)
+            .map(|_| ())
+            .map_err(|e| e.to_string())
+    };
+    let mut value = |amount: &str, token: &str| {
+        let amount = SwapAmount::parse(amount).map_err(|e| e.to_string())?;
+        let amount = match amount {
+            SwapAmount::Max => context.onchain.balance_of(token),
+            SwapAmount::Exact(amount) => amount,
+        };
+        if let Some(price) = context.price(token) {
+            turnover_usd += amount * price;
+        }
+        Ok::<_, String>(())
+    };
+
+    for (i, transfer) in strategy
+        .exchanges
+        .bridges
+        .transfers
+        .iter()
+        .flatten()
+        .enumerate()
+    {
+        let problems = [
+            known(transfer.from_chain_id, &transfer.token),
+            known(transfer.to_chain_id, transfer.token_out()),
+            (transfer.from_chain_id != transfer.to_chain_id)
+                .then_some(())
+                .ok_or_else(|| "source and destination chain are the same".to_string()),
+            value(&transfer.amount, &transfer.token),
+        ];
+        for problem in problems.into_iter().filter_map(Result::err) {
+            violations.push(format!("bridge transfer {}: {}", i + 1, problem));
+        }
+    }
+
+    for (i, swap) in strategy.exchanges.eisen.swaps.iter().flatten().enumerate() {
+        let problems = [
+            known(swap.chain_id(), &swap.token_in),
+            known(swap.chain_id(), &swap.token_out),
+            (!swap.token_in.eq_ignore_ascii_case(&swap.token_out))
+                .then_some(())
+                .ok_or_else(|| "token in and token out are the same".to_string()),
+            swap.slippage_bps
+                .map_or(Ok(()), |bps| validate_slippage_bps(bps).map(|_| ()))
+                .map_err(|e| e.to_string()),
+            value(&swap.amount, &swap.token_in),
+        ];
+        for problem in problems.into_iter().filter_map(Result::err) {
+            violations.push(format!(
+                "swap {} ({} -> {}): {}",
+                i + 1,
+                swap.token_in,
+                swap.token_out,
+                problem
+            ));
+        }
+    }
+
+    if let Err(problem) = strategy.dependency_stages() {
+        violations.push(problem);
+    }
+
+    if turnover_usd > config.max_turnover_usd {
+        violations.push(format!(
+            "turnover ${:.2} exceeds the ${:.2} cap",
+            turnover_usd, config.max_turnover_usd
+        ));
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(StrategyValidationError { violations })
+    }
+}
+
+/// Check a strategy of the stable yield farming engine keeps to stablecoins: no Binance
+/// orders, and swaps and bridges only between tokens whose base asset is USD.
+pub fn validate_stable_farming(
+    strategy: &Strategy,
+    context: &StrategyContext,
+) -> Result<(), StrategyValidationError> {
+    let stable = |token: &str| context.exposure.base_asset(token) == Some("USD");
+    let exchanges = &strategy.exchanges;
+    let orders = exchanges.binance.orders.iter().flatten();
+    let mut violations: Vec<String> = orders
+        .enumerate()
+        .map(|(i, order)| {
+            format!(
+                "order {} ({}): yield farming places no Binance orders",
+                i + 1,
+                futures_symbol(&order.token, order.quote.as_deref())
+            )
+        })
+        .collect();
+    for (i, transfer) in exchanges.bridges.transfers.iter().flatten().enumerate() {
+        if let Some(token) = [transfer.token.as_str(), transfer.token_out()]
+            .into_iter()
+            .find(|token| !stable(token))
+        {
+            violations.push(format!(
+                "bridge transfer {}: {} is not a stablecoin",
+                i + 1,
+                token
+            ));
+        }
+    }
+    for (i, swap) in exchanges.eisen.swaps.iter().flatten().enumerate() {
+        if let Some(token) = [&swap.token_in, &swap.token_out]
+            .into_iter()
+            .find(|token| !stable(token))
+        {
+            violations.push(format!(
+                "swap {} ({} -> {}): {} is not a stablecoin",
+                i + 1,
+                swap.token_in,
+                swap.token_out,
+                token
+            ));
+        }
+    }
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(StrategyValidationError { violations })
+    }
+}
+
+/// Combined USD notional of the strategy's orders, bridges and swaps, "max" amounts
+/// valued at the wallet's balance. Legs that cannot be priced count for nothing.
+pub fn notional_usd(strategy: &Strategy, context: &StrategyContext) -> f64 {
+    let value = |amount: &str, token: &str| {
+        let amount = match SwapAmount::parse(amount) {
+            Ok(SwapAmount::Max) => context.onchain.balance_of(token),
+            Ok(SwapAmount::Exact(amount)) => amount,
+            Err(_) => return 0.0,
+        };
+        context.price(token).map_or(0.0, |price| amount * price)
+    };
+    let exchanges = &strategy.exchanges;
+    let orders = exchanges.binance.orders.iter().flatten();
+    let bridges = exchanges.bridges.transfers.iter().flatten();
+    let swaps = exchanges.eisen.swaps.iter().flatten();
+    orders
+        .map(|order| value(&order.amount, &order.token))
+        .chain(bridges.map(|bridge| value(&bridge.amount, &bridge.token)))
+        .chain(swaps.map(|swap| value(&swap.amount, &swap.token_in)))
+        .sum()
+}
+
+/// `amount` times `factor`, "max" taken as `balance`, truncated to `SCALED_AMOUNT_DP`
+/// decimals.
+fn scale_amount(amount: &mut String, balance: f64, factor: f64) {
+    let scaled = match SwapAmount::parse(amount) {
+        Ok(SwapAmount::Max) => Decimal::from_f64(balance * factor),
+        Ok(SwapAmount::Exact(_)) => Decimal::from_str_exact(amount.trim())
+            .ok()
+            .zip(Decimal::from_f64(factor))
+            .map(|(amount, factor)| amount * factor),
+        Err(_) => None,
+    };
+    if let Some(scaled) = scaled {
+        *amount = round_scaled_amount(scaled).normalize().to_string();
+    }
+}
+
+/// Scale every order, bridge and swap down by the same factor when their combined
+/// notional exceeds `max_notional_usd`, so the legs keep their ratios to each other
+/// and a delta-neutral strategy stays neutral. Binance transfers, moving collateral,
+/// are left as they are. The factor applied, if any.
+pub fn scale_to_budget(
+    strategy: &mut Strategy,
+    context: &StrategyContext,
+    max_notional_usd: f64,
+) -> Option<f64> {
+    let notional = notional_usd(strategy, context);
+    if notional <= max_notional_usd {
+        return None;
+    }
+    let factor = max_notional_usd / notional;
+    println!(
+        "Strategy notional ${:.2} exceeds the ${:.2} budget, scaling its legs by {:.4}",
+        notional, max_notional_usd, factor
+    );
+    let exchanges = &mut strategy.exchanges;
+    for order in exchanges.binance.orders.iter_mut().flatten() {
+        scale_amount(&mut order.amount, 0.0, factor);
+    }
+    for bridge in exchanges.bridges.transfers.iter_mut().flatten() {
+        let balance = context.onchain.balance_of(&bridge.token);
+        scale_amount(&mut bridge.amount, balance, factor);
+    }
+    for swap in exchanges.eisen.swaps.iter_mut().flatten() {
+        let balance = context.onchain.balance_of(&swap.token_in);
+        scale_amount(&mut swap.amount, balance, factor);
+    }
+    Some(factor)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::eisen::TokenBalance;
+    use crate::portfolio::binance::test_account;
+    use crate::types::test_prices;
+
+    fn strategy(value: serde_json::Value) -> Strategy {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_validate_strategy() {
+        let binance = test_account(
+            "1000",
+            serde_json::json!([{
+                "symbol": "ETHUSDT", "positionSide": "BOTH", "positionAmt": "-1.0",
+                "unrealizedProfit": "0", "notional": "-2000", "initialMargin": "0",
+                "maintMargin": "0", "updateTime": 0
+            }]),
+        );
+        let onchain = ChainPortfolio {
+            balances: vec![TokenBalance {
+                symbol: "usdc".to_string(),
+                balance: 5000.0,
+                usd_value: Some(5000.0),
+            }],
+            positions: vec![],
+        };
+        let base: ChainData = serde_json::from_value(serde_json::json!({
+            "id": 8453, "name": "base", "addr_to_sym": {}, "native_symbol": "eth",
+            "wrapped_native": null,
+            "sym_to_addr_n_decimals": {"usdc": ["0x01", 6], "wsteth": ["0x02", 18]}
+        }))
+        .unwrap();
+        let chain_data = HashMap::from([(8453, base)]);
+        let context = StrategyContext {
+            binance: &binance,
+            onchain: &onchain,
+            prices: &test_prices(),
+            exposure: &ExposureConfig::default(),
+            chain_data: &chain_data,
+        };
+        let config = StrategyValidationConfig::default();
+
+        let valid = strategy(serde_json::json!({
+            "exchanges": {
+                "binance": {
+                    "orders": [
+                        {"position": "short", "token": "eth", "amount": "0.5", "price": "", "side": "SELL"},
+                        {"position": "short", "token": "eth", "amount": "1", "price": "", "side": "BUY"}
+                    ],
+                    "transfers": [{"from": "spot", "to": "futures", "asset": "USDT", "amount": "100"}]
+                },
+                "eisen": {"swaps": [{"tokenIn": "usdc", "tokenOut": "wsteth", "amount": "max"}]}
+            },
+            "explanations": []
+        }));
+        assert!(validate_strategy(&valid, &context, &config).is_ok());
+
+        // $3000 of orders and the whole $5000 of USDC swapped, halved
+        let mut scaled = strategy(serde_json::to_value(&valid).unwrap());
+        assert_eq!(notional_usd(&scaled, &context), 8000.0);
+        assert_eq!(scale_to_budget(&mut scaled, &context, 10_000.0), None);
+        assert_eq!(scale_to_budget(&mut scaled, &context, 4000.0), Some(0.5));
+        let orders = scaled.exchanges.binance.orders.as_ref().unwrap();
+        assert_eq!((&*orders[0].amount, &*orders[1].amount), ("0.25", "0.5"));
+        assert_eq!(
+            scaled.exchanges.eisen.swaps.as_ref().unwrap()[0].amount,
+            "2500"
+        );
+        assert_eq!(
+            scaled.exchanges.binance.transfers.as_ref().unwrap()[0].amount,
+            "100"
+        );
+
+        // Each buy back fits the short alone, both together close more than it holds
+        let overlapping = strategy(serde_json::json!({
+            "exchanges": {
+                "binance": {"orders": [
+                    {"position": "short", "token": "eth", "amount": "0.6", "price": "", "side": "BUY"},
+                    {"position": "short", "token": "eth", "amount": "0.6", "price": "", "side": "BUY"}
+                ]},
+                "eisen": {}
+            },
+            "explanations": []
+        }));
+        let violations = validate_strategy(&overlapping, &context, &config)
+            .unwrap_err()
+            .violations;
+        assert_eq!(violations.len(), 1, "{:#?}", violations);
+        assert!(violations[0].starts_with("order 2 (ETHUSDT)"));
+        assert!(violations[0].contains("exceeds the open position of -0.4"));
+
+        let invalid = strategy(serde_json::json!({
+            "exchanges": {
+                "binance": {
+                    "orders": [
+                        {"position": "short", "token": "doge", "amount": "10", "price": "", "side": "SELL"},
+                        {"position": "long", "token": "eth", "amount": "-1", "price": "", "side": "BUY"},
+                        {"position": "short", "token": "eth", "amount": "2", "price": "", "side": "BUY"},
+                        {"position": "long", "token": "btc", "amount": "1", "price": "", "side": "BUY"},
+                        {"position": "long", "token": "eth", "amount": "0.1", "price": "cheap", "side": "BUY"}
+                    ],
+                    "transfers": [{"from": "spot", "to": "margin", "asset": "USDT", "amount": "100"}]
+                },
+                "eisen": {"swaps": [{"tokenIn": "usdc", "tokenOut": "pepe", "amount": "0"}]}
+            },
+            "explanations": []
+        }));
+        let violations = validate_strategy(&invalid, &context, &config)
+            .unwrap_err()
+            .violations;
+        assert_eq!(violations.len(), 8, "{:#?}", violations);
+        assert!(violations[0].starts_with("order 1 (DOGEUSDT): token doge"));
+        assert!(violations[1].contains("not a positive number"));
+        assert!(violations[2].contains("exceeds the open position of -1"));
+        assert!(violations[3].contains("notional $60000.00 exceeds"));
+        assert!(violations[4].ends_with("price \"cheap\" is not a positive number"));
+        assert!(violations[5].starts_with("transfer 1"));
+        assert!(violations[6].contains("token pepe is not supported"));
+        assert!(violations[7].contains("Invalid swap amount"));
+
+        // Dependencies must name legs and not loop
+        let cyclic = strategy(serde_json::json!({
+            "exchanges": {
+                "binance": {"orders": [
+                    {"position": "short", "token": "eth", "amount": "0.5", "price": "", "side": "SELL", "id": "short", "dependsOn": ["swap"]}
+                ]},
+                "eisen": {"swaps": [{"tokenIn": "usdc", "tokenOut": "wsteth", "amount": "100", "id": "swap", "dependsOn": ["short"]}]}
+            },
+            "explanations": []
+        }));
+        let violations = validate_strategy(&cyclic, &context, &config)
+            .unwrap_err()
+            .violations;
+        assert_eq!(violations, ["legs depend on each other in a cycle"]);
+        let mut dangling = cyclic;
+        dangling.exchanges.eisen.swaps.as_mut().unwrap()[0].depends_on =
+            Some(vec!["bridge".to_string()]);
+        assert_eq!(
+            dangling.dependency_stages(),
+            Err("dependency \"bridge\" is no leg's id".to_string())
+        );
+        dangling.exchanges.eisen.swaps.as_mut().unwrap()[0].depends_on = None;
+        dangling.resolve_dependencies().unwrap();
+        assert_eq!(dangling.stages(), [0, 1]);
+
+        // Farming keeps to stablecoins
+        let farming = strategy(serde_json::json!({
+            "exchanges": {
+                "binance": {"orders": [
+                    {"position": "short", "token": "eth", "amount": "0.5", "price": "", "side": "SELL"}
+                ]},
+                "eisen": {"swaps": [
+                    {"tokenIn": "usdc", "tokenOut": "dai", "amount": "100"},
+                    {"tokenIn": "usdc", "tokenOut": "wsteth", "amount": "100"}
+                ]}
+            },
+            "explanations": []
+        }));
+        let violations = validate_stable_farming(&farming, &context)
+            .unwrap_err()
+            .violations;
+        assert_eq!(violations.len(), 2, "{:#?}", violations);
+        assert!(violations[0].starts_with("order 1 (ETHUSDT)"));
+        assert!(violations[1].starts_with("swap 2 (usdc -> wsteth): wsteth"));
+    }
+}
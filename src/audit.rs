@@ -0,0 +1,287 @@
+use crate::storage::WalStore;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::future::Future;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, OnceLock};
+
+/// What kind of side effect a record is of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AuditKind {
+    /// Binance request changing the account: orders, transfers and listen keys.
+    BinanceRequest,
+    /// Signed transaction broadcast to a chain, replacements included.
+    Transaction,
+}
+
+/// One external side effect, as it was sent and answered.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuditRecord {
+    pub timestamp: DateTime<Utc>,
+    pub kind: AuditKind,
+    /// Endpoint of a Binance request; chain, sender and nonce of a transaction.
+    pub target: String,
+    /// SHA-256 of the signed Binance request body; the hash of a broadcast transaction,
+    /// or the SHA-256 of its request when it was not accepted.
+    pub payload_hash: String,
+    pub success: bool,
+    /// Response body, transaction hash or error.
+    pub response: String,
+    /// Job and wallet the side effect was made for.
+    pub job_id: String,
+    pub user: String,
+}
+
+/// Which records `AuditLog::query` returns.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub kind: Option<AuditKind>,
+    pub job_id: Option<String>,
+    pub user: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl AuditFilter {
+    fn matches(&self, record: &AuditRecord) -> bool {
+        self.kind.is_none_or(|kind| record.kind == kind)
+            && self
+                .job_id
+                .as_ref()
+                .is_none_or(|job_id| &record.job_id == job_id)
+            && self
+                .user
+                .as_ref()
+                .is_none_or(|user| record.user.eq_ignore_ascii_case(user))
+            && self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp < until)
+    }
+}
+
+/// Append-only log of external side effects, one JSON record per line of
+/// `audit.jsonl`, synced as each is written. The file is only ever appended to, never
+/// compacted or rewritten.
+pub struct AuditLog {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+impl AuditLog {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        fs::create_dir_all(data_dir)?;
+        let path = data_dir.join("audit.jsonl");
+        let legacy = !path.exists() && data_dir.join("audit.wal").exists();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let log = Self {
+            path,
+            file: Mutex::new(file),
+        };
+        // Records of the WAL store the log used to be kept in, oldest first
+        if legacy {
+            let mut records: Vec<(String, AuditRecord)> =
+                WalStore::open(data_dir, "audit")?.entries();
+            records.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (_, record) in records {
+                log.append(record)?;
+            }
+        }
+        Ok(log)
+    }
+
+    pub fn append(&self, record: AuditRecord) -> Result<()> {
+        let mut line = serde_json::to_vec(&record)?;
+        line.push(b'\n');
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(&line)?;
+        file.sync_data()?;
+        Ok(())
+    }
+
+    /// Records matching `filter`, newest first, at most `limit` of them. A line that
+    /// does not parse, such as one torn by a crash, is skipped.
+    pub fn query(&self, filter: &AuditFilter, limit: usize) -> Vec<AuditRecord> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) => {
+                println!("Failed to read the audit log {:?}: {}", self.path, e);
+                return Vec::new();
+            }
+        };
+        let mut records: Vec<AuditRecord> = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter_map(|line| serde_json::from_str(&line).ok())
+            .filter(|record| filter.matches(record))
+            .collect();
+        records.reverse();
+        records.truncate(limit);
+        records
+    }
+}
+
+/// Job a task is executing, so side effects deep in the executors are recorded
+/// against it without every call passing it along.
+#[derive(Clone)]
+pub struct AuditScope {
+    pub log: Arc<AuditLog>,
+    pub job_id: String,
+    pub user: String,
+}
+
+tokio::task_local! {
+    static SCOPE: AuditScope;
+}
+
+/// Log of side effects made outside of any job, e.g. by background tasks.
+static FALLBACK: OnceLock<Arc<AuditLog>> = OnceLock::new();
+
+/// Record side effects made outside of a job in `log`, under no job and user.
+pub fn install(log: Arc<AuditLog>) {
+    if FALLBACK.set(log).is_err() {
+        println!("An audit log is already installed");
+    }
+}
+
+/// Run `f` with its side effects recorded under `scope`. Tasks `f` spawns take the
+/// scope along only when spawned with [`spawn`].
+pub async fn scoped<F: Future>(scope: AuditScope, f: F) -> F::Output {
+    SCOPE.scope(scope, f).await
+}
+
+/// `tokio::spawn`, recording the task's side effects under the job spawning it.
+pub fn spawn<F>(f: F) -> tokio::task::JoinHandle<F::Output>
+where
+    F: Future + Send + 'static,
+    F::Output: Send + 'static,
+{
+    match SCOPE.try_with(AuditScope::clone) {
+        Ok(scope) => tokio::spawn(scoped(scope, f)),
+        Err(_) => tokio::spawn(f),
+    }
+}
+
+pub fn sha256_hex(payload: &[u8]) -> String {
+    hex::encode(Sha256::digest(payload))
+}
+
+/// Record a side effect in the log of the running job. The side effect has happened
+/// either way, so a failed write is only logged.
+pub fn record(
+    kind: AuditKind,
+    target: String,
+    payload_hash: String,
+    response: Result<String, String>,
+) {
+    let (success, response) = match response {
+        Ok(response) => (true, response),
+        Err(error) => (false, error),
+    };
+    let scope = SCOPE.try_with(AuditScope::clone).ok().or_else(|| {
+        FALLBACK.get().map(|log| AuditScope {
+            log: log.clone(),
+            job_id: String::new(),
+            user: String::new(),
+        })
+    });
+    let Some(scope) = scope else {
+        println!(
+            "{:?} to {} ({}) made without an audit log, not audited",
+            kind, target, payload_hash
+        );
+        return;
+    };
+    let recorded = scope.log.append(AuditRecord {
+        timestamp: Utc::now(),
+        kind,
+        target: target.clone(),
+        payload_hash,
+        success,
+        response,
+        job_id: scope.job_id,
+        user: scope.user,
+    });
+    if let Err(e) = recorded {
+        println!("Failed to audit {:?} to {}: {}", kind, target, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[tokio::test]
+    async fn test_audit_log() -> Result<()> {
+        let dir = tempdir()?;
+        let log = Arc::new(AuditLog::open(dir.path())?);
+        let scope = |job_id: &str| AuditScope {
+            log: log.clone(),
+            job_id: job_id.to_string(),
+            user: "0xAbC".to_string(),
+        };
+        scoped(scope("job-1"), async {
+            record(
+                AuditKind::BinanceRequest,
+                "POST https://testnet.binancefuture.com/fapi/v1/order".to_string(),
+                sha256_hex(b"symbol=ETHUSDT"),
+                Ok("{\"orderId\":1}".to_string()),
+            );
+        })
+        .await;
+        scoped(scope("job-2"), async {
+            record(
+                AuditKind::Transaction,
+                "chain 8453".to_string(),
+                "0x01".to_string(),
+                Err("nonce too low".to_string()),
+            );
+        })
+        .await;
+        // Outside of a job nothing is recorded in the job's log
+        record(
+            AuditKind::Transaction,
+            "chain 1".to_string(),
+            "0x02".to_string(),
+            Ok("0x02".to_string()),
+        );
+
+        // Read back from the file, newest first
+        let log = AuditLog::open(dir.path())?;
+        let records = log.query(&AuditFilter::default(), 10);
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[0].job_id, "job-2");
+        assert!(!records[0].success);
+
+        let filter = AuditFilter {
+            kind: Some(AuditKind::BinanceRequest),
+            user: Some("0xabc".to_string()),
+            ..AuditFilter::default()
+        };
+        let records = log.query(&filter, 10);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].payload_hash, sha256_hex(b"symbol=ETHUSDT"));
+        assert_eq!(log.query(&AuditFilter::default(), 1).len(), 1);
+
+        // Spawned work of a job is recorded under it
+        scoped(scope("job-3"), async {
+            spawn(async {
+                record(
+                    AuditKind::Transaction,
+                    "chain 8453".to_string(),
+                    "0x03".to_string(),
+                    Ok("0x03".to_string()),
+                );
+            })
+            .await
+        })
+        .await?;
+        assert_eq!(log.query(&AuditFilter::default(), 1)[0].job_id, "job-3");
+        Ok(())
+    }
+}
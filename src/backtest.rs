@@ -0,0 +1,188 @@
+use crate::config::FundingRebalancerConfig;
+use crate::engine::funding::{funding_rate, target_hedge};
+use crate::utils::price::PriceData;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+// Hours between funding settlements of the USDT perps
+const FUNDING_PERIOD_HOURS: f64 = 8.0;
+
+/// One order the rebalancer would have placed.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BacktestTrade {
+    pub at: DateTime<Utc>,
+    pub side: String,
+    pub quantity: f64,
+    pub price: f64,
+    pub reason: String,
+}
+
+/// How the funding rebalancer would have done over recorded prices of a perp, holding
+/// `notional_usd` of its asset on-chain from the first price on.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BacktestReport {
+    pub symbol: String,
+    pub notional_usd: f64,
+    pub from: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    /// Prices replayed; those without a market price are left out.
+    pub samples: usize,
+    pub trades: Vec<BacktestTrade>,
+    /// Price PnL of the holdings alone, what they made unhedged.
+    pub holding_pnl_usd: f64,
+    /// Price PnL of the short.
+    pub hedge_pnl_usd: f64,
+    /// Funding the short received, negative when it paid.
+    pub funding_usd: f64,
+    pub fees_usd: f64,
+    pub total_pnl_usd: f64,
+    /// Share of the time replayed with a short open.
+    pub hedged_pct: f64,
+}
+
+fn time(timestamp: u128) -> DateTime<Utc> {
+    DateTime::from_timestamp_millis(timestamp as i64).unwrap_or_default()
+}
+
+/// Replay `prices` of `symbol`, oldest first, through the rebalancer's target hedge.
+/// Orders fill at the market price paying `fee_bps` of their notional, and funding
+/// accrues between prices at the rate the hedge was decided on.
+pub fn backtest_funding(
+    symbol: &str,
+    prices: &[PriceData],
+    config: &FundingRebalancerConfig,
+    notional_usd: f64,
+    fee_bps: f64,
+) -> BacktestReport {
+    let mut report = BacktestReport {
+        symbol: symbol.to_string(),
+        notional_usd,
+        from: None,
+        until: None,
+        samples: 0,
+        trades: Vec::new(),
+        holding_pnl_usd: 0.0,
+        hedge_pnl_usd: 0.0,
+        funding_usd: 0.0,
+        fees_usd: 0.0,
+        total_pnl_usd: 0.0,
+        hedged_pct: 0.0,
+    };
+    // Quantities of the asset held and of the short, negative while open
+    let (mut held, mut hedge) = (0.0, 0.0);
+    let mut last: Option<(u128, f64, Option<f64>)> = None;
+    let (mut first, mut hedged_ms) = (None, 0u128);
+    for price_data in prices {
+        let Some(price) = price_data.market_price.filter(|price| *price > 0.0) else {
+            continue;
+        };
+        let rate = funding_rate(price_data);
+        match last {
+            None => {
+                held = notional_usd / price;
+                first = Some(price_data.timestamp);
+            }
+            Some((at, last_price, last_rate)) => {
+                let elapsed = price_data.timestamp.saturating_sub(at);
+                let periods = elapsed as f64 / 3_600_000.0 / FUNDING_PERIOD_HOURS;
+                report.holding_pnl_usd += held * (price - last_price);
+                report.hedge_pnl_usd += hedge * (price - last_price);
+                // Shorts receive positive funding
+                report.funding_usd -= hedge * last_price * last_rate.unwrap_or(0.0) * periods;
+                if hedge != 0.0 {
+                    hedged_ms += elapsed;
+                }
+            }
+        }
+        report.samples += 1;
+        last = Some((price_data.timestamp, price, rate));
+
+        let Some(rate) = rate else {
+            continue;
+        };
+        let hedge_usd = hedge * price;
+        let (target_usd, reason) = target_hedge(config, rate, held * price, hedge_usd);
+        let gap_usd = target_usd - hedge_usd;
+        if gap_usd.abs() < config.min_order_usd {
+            continue;
+        }
+        hedge = target_usd / price;
+        report.fees_usd += gap_usd.abs() * fee_bps / 10_000.0;
+        report.trades.push(BacktestTrade {
+            at: time(price_data.timestamp),
+            side: if gap_usd > 0.0 { "BUY" } else { "SELL" }.to_string(),
+            quantity: gap_usd.abs() / price,
+            price,
+            reason,
+        });
+    }
+
+    if let (Some(first), Some((until, _, _))) = (first, last) {
+        report.from = Some(time(first));
+        report.until = Some(time(until));
+        if until > first {
+            report.hedged_pct = hedged_ms as f64 / (until - first) as f64 * 100.0;
+        }
+    }
+    report.total_pnl_usd =
+        report.holding_pnl_usd + report.hedge_pnl_usd + report.funding_usd - report.fees_usd;
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::price::PriceSource;
+
+    fn price(hours: u128, market_price: f64, rate: Option<f64>) -> PriceData {
+        PriceData {
+            timestamp: hours * 3_600_000,
+            market_price: Some(market_price),
+            buy_long_price: None,
+            sell_short_price: None,
+            cur_funding_rate: rate,
+            predicted_funding_rate: None,
+            next_funding_time: None,
+            funding_history: None,
+            source: PriceSource::Binance,
+            depth: None,
+        }
+    }
+
+    #[test]
+    fn test_backtest_funding() {
+        let config = FundingRebalancerConfig::default();
+        let prices = vec![
+            price(0, 2000.0, Some(0.0002)),
+            // Between the exit and entry: the short stays open
+            price(8, 2200.0, Some(0.00005)),
+            PriceData {
+                market_price: None,
+                ..price(12, 0.0, None)
+            },
+            price(16, 1800.0, Some(-0.0001)),
+            price(24, 1900.0, None),
+        ];
+        let report = backtest_funding("ETHUSDT", &prices, &config, 10_000.0, 5.0);
+
+        assert_eq!(report.samples, 4);
+        assert_eq!(report.trades.len(), 2);
+        assert_eq!(report.trades[0].side, "SELL");
+        assert!((report.trades[0].quantity - 5.0).abs() < 1e-9);
+        // Buys back the 5 ETH short at 1800
+        assert_eq!(report.trades[1].side, "BUY");
+        assert!((report.trades[1].quantity - 5.0).abs() < 1e-9);
+        assert!((report.holding_pnl_usd + 500.0).abs() < 1e-6);
+        assert!((report.hedge_pnl_usd - 1000.0).abs() < 1e-6);
+        // 0.02% on $10000, then 0.005% on $11000
+        assert!((report.funding_usd - 2.55).abs() < 1e-6);
+        assert!((report.fees_usd - 9.5).abs() < 1e-6);
+        assert!((report.total_pnl_usd - 493.05).abs() < 1e-6);
+        assert!((report.hedged_pct - 200.0 / 3.0).abs() < 1e-6);
+
+        let empty = backtest_funding("ETHUSDT", &[], &config, 10_000.0, 5.0);
+        assert_eq!((empty.samples, empty.from), (0, None));
+    }
+}
@@ -1,18 +1,80 @@
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
 
 /// Chill PM Web Server
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     /// Environment to use (test or prod)
-    #[arg(long, default_value = "test")]
+    #[arg(long, default_value = "test", global = true)]
     pub environment: String,
 
+    /// Path to a YAML config file
+    #[arg(long, global = true)]
+    pub config: Option<PathBuf>,
+
+    #[command(flatten)]
+    pub serve: ServeArgs,
+
+    /// What to run; serves the API when left out
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Run the HTTP server
+    Serve,
+    /// Print the Binance and on-chain portfolio of a wallet
+    Portfolio {
+        /// Wallet address of the on-chain portfolio
+        #[arg(long)]
+        wallet: String,
+    },
+    /// Print the yields of every configured source
+    Yields,
+    /// Fetch a strategy for a wallet and execute it once
+    Execute {
+        /// Wallet address to execute for
+        #[arg(long)]
+        wallet: String,
+
+        /// Only print the strategy and its preview, executing nothing
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Replay recorded prices through the funding rebalancer
+    Backtest {
+        /// Base asset hedged on its USDT perp
+        #[arg(long, default_value = "ETH")]
+        asset: String,
+
+        /// Days of recorded prices replayed, up to now
+        #[arg(long, default_value = "7")]
+        days: u32,
+
+        /// USD of the asset held on-chain at the start
+        #[arg(long, default_value = "10000")]
+        notional_usd: f64,
+
+        /// Fee of each order, in basis points of its notional
+        #[arg(long, default_value = "5")]
+        fee_bps: f64,
+    },
+}
+
+/// Server options, taken with or without the `serve` subcommand.
+#[derive(clap::Args, Debug)]
+pub struct ServeArgs {
     /// Port to run the server on
-    #[arg(long, default_value = "8080")]
+    #[arg(long, default_value = "8080", global = true)]
     pub port: u16,
 
     /// Host address to bind to
-    #[arg(long, default_value = "127.0.0.1")]
+    #[arg(long, default_value = "127.0.0.1", global = true)]
     pub host: String,
+
+    /// Origin allowed to call the API cross-origin, on top of the config's; repeatable
+    #[arg(long = "cors-origin", global = true)]
+    pub cors_origins: Vec<String>,
 }
@@ -0,0 +1,1789 @@
+use crate::constants::Interval;
+use crate::engine::OTHENTIC_PROVIDER;
+use crate::executor::eisen::{validate_slippage_bps, ApprovalPolicy, DEFAULT_SLIPPAGE_BPS};
+use crate::notify::NotificationEvent;
+use crate::scheduler::Schedule;
+use crate::utils::sign::MAX_RECV_WINDOW_MS;
+use alloy::primitives::{address, Address};
+use anyhow::{anyhow, Result};
+use http::{HeaderName, HeaderValue, Method};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use strum::IntoEnumIterator;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+/// Runtime configuration, loaded from the YAML file given with `--config`.
+///
+/// Every field has a default, so a config file only needs the sections it changes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Directory holding local state (WAL stores and snapshots).
+    pub data_dir: PathBuf,
+    /// Binance USD-M futures API; the testnet unless configured otherwise.
+    pub binance_base_url: String,
+    /// Milliseconds a signed Binance request is valid for after its timestamp.
+    pub binance_recv_window_ms: u64,
+    /// How long Eisen chain metadata is cached before it is refetched.
+    pub chain_metadata_ttl_secs: u64,
+    pub paper_trading: PaperTradingConfig,
+    /// Chains swaps can be executed on, keyed by chain id. Replaces the defaults when set.
+    pub chains: HashMap<u64, ChainConfig>,
+    pub swaps: SwapConfig,
+    pub gas: GasConfig,
+    pub simulation: SimulationConfig,
+    pub bridge: BridgeConfig,
+    /// Signers of the wallets strategies run for, keyed by address. Other wallets sign
+    /// with the PRIVATE_KEY_DEPLOYER key, if it is theirs.
+    pub wallets: HashMap<String, SignerConfig>,
+    pub yields: YieldConfig,
+    pub market_data: MarketDataConfig,
+    pub price_check: PriceCheckConfig,
+    pub price_fallback: PriceFallbackConfig,
+    pub onchain_prices: OnchainPriceConfig,
+    pub portfolio_history: PortfolioHistoryConfig,
+    pub exposure: ExposureConfig,
+    pub margin_health: MarginHealthConfig,
+    pub rebalance: RebalanceConfig,
+    pub binance_accounts: BinanceAccountsConfig,
+    pub strategy_validation: StrategyValidationConfig,
+    pub strategy_memory: StrategyMemoryConfig,
+    pub risk: RiskConfig,
+    pub hedge_unwind: HedgeUnwindConfig,
+    pub scheduler: SchedulerConfig,
+    pub funding_rebalancer: FundingRebalancerConfig,
+    pub notifications: NotificationConfig,
+    pub stop_loss: StopLossConfig,
+    pub llm: LlmConfig,
+    pub othentic: OthenticConfig,
+    pub tls: TlsConfig,
+    pub cors: CorsConfig,
+    pub http: HttpConfig,
+    pub admin: AdminConfig,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            data_dir: PathBuf::from("data"),
+            binance_base_url: "https://testnet.binancefuture.com".to_string(),
+            binance_recv_window_ms: 5000,
+            chain_metadata_ttl_secs: 600,
+            paper_trading: PaperTradingConfig::default(),
+            chains: HashMap::from([
+                (1, ChainConfig::new("https://ethereum-rpc.publicnode.com")),
+                (8453, ChainConfig::new("https://mainnet.base.org")),
+                (34443, ChainConfig::new("https://mainnet.mode.network")),
+            ]),
+            swaps: SwapConfig::default(),
+            gas: GasConfig::default(),
+            simulation: SimulationConfig::default(),
+            bridge: BridgeConfig::default(),
+            wallets: HashMap::new(),
+            yields: YieldConfig::default(),
+            market_data: MarketDataConfig::default(),
+            price_check: PriceCheckConfig::default(),
+            price_fallback: PriceFallbackConfig::default(),
+            onchain_prices: OnchainPriceConfig::default(),
+            portfolio_history: PortfolioHistoryConfig::default(),
+            exposure: ExposureConfig::default(),
+            margin_health: MarginHealthConfig::default(),
+            rebalance: RebalanceConfig::default(),
+            binance_accounts: BinanceAccountsConfig::default(),
+            strategy_validation: StrategyValidationConfig::default(),
+            strategy_memory: StrategyMemoryConfig::default(),
+            risk: RiskConfig::default(),
+            hedge_unwind: HedgeUnwindConfig::default(),
+            scheduler: SchedulerConfig::default(),
+            funding_rebalancer: FundingRebalancerConfig::default(),
+            notifications: NotificationConfig::default(),
+            stop_loss: StopLossConfig::default(),
+            llm: LlmConfig::default(),
+            othentic: OthenticConfig::default(),
+            tls: TlsConfig::default(),
+            cors: CorsConfig::default(),
+            http: HttpConfig::default(),
+            admin: AdminConfig::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SwapConfig {
+    /// Slippage tolerance of swaps that do not set one, in bps (1-1000).
+    pub slippage_bps: u16,
+    /// Approve exactly the swap amount ("exact") or an unlimited amount ("max").
+    pub approval: ApprovalPolicy,
+    /// Sign Permit2 permits instead of approving the router, on chains with a
+    /// `permit2_spender`.
+    pub permit2: bool,
+    /// Native token left in the wallet for gas when a swap spends it, in whole units.
+    pub native_gas_reserve: f64,
+    pub tranches: TrancheConfig,
+    /// DEXes swaps may be routed through, keyed by chain id. Swaps can override it.
+    pub dex_filters: HashMap<u64, DexFilter>,
+}
+
+/// DEX ids (as in Eisen quotes, e.g. "uniswap_v3") a swap may or may not use.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DexFilter {
+    /// Only route through these DEXes; any DEX when empty.
+    pub allow: Vec<String>,
+    /// Never route through these DEXes.
+    pub deny: Vec<String>,
+}
+
+impl DexFilter {
+    pub fn allows(&self, dex_id: &str) -> bool {
+        let listed = |ids: &[String]| ids.iter().any(|id| id.eq_ignore_ascii_case(dex_id));
+        (self.allow.is_empty() || listed(&self.allow)) && !listed(&self.deny)
+    }
+}
+
+/// Splitting of large swaps into separately quoted and executed tranches.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TrancheConfig {
+    /// Split swaps worth more than this, in the chain's native token (ETH on every
+    /// supported chain). Swaps are never split when unset.
+    pub split_above_native: Option<f64>,
+    /// Number of tranches a large swap is split into.
+    pub count: u32,
+    /// Pause between tranches, giving pools time to rebalance.
+    pub delay_secs: u64,
+}
+
+impl Default for TrancheConfig {
+    fn default() -> Self {
+        Self {
+            split_above_native: None,
+            count: 4,
+            delay_secs: 0,
+        }
+    }
+}
+
+impl Default for SwapConfig {
+    fn default() -> Self {
+        Self {
+            slippage_bps: DEFAULT_SLIPPAGE_BPS,
+            approval: ApprovalPolicy::default(),
+            permit2: false,
+            native_gas_reserve: 0.002,
+            tranches: TrancheConfig::default(),
+            dex_filters: HashMap::new(),
+        }
+    }
+}
+
+/// EIP-1559 fee estimation and limits for on-chain transactions.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GasConfig {
+    /// Ceiling on maxFeePerGas; swaps abort while the base fee is above it.
+    pub max_fee_gwei: f64,
+    /// Ceiling on maxPriorityFeePerGas.
+    pub max_priority_fee_gwei: f64,
+    /// Abort a swap whose worst case gas cost exceeds this percentage of its value.
+    pub max_cost_pct: f64,
+    /// Gas a swap is priced at when Eisen's build response carries no estimate.
+    pub fallback_swap_gas: u64,
+    /// Blocks of fee history the priority fee is estimated from.
+    pub fee_history_blocks: u64,
+    /// Percentile of each block's priority fees used as its tip.
+    pub priority_fee_percentile: f64,
+    /// How long to wait for a transaction before replacing it with bumped fees.
+    pub confirmation_timeout_secs: u64,
+    /// Replacements sent for a stuck or dropped transaction before giving up.
+    pub max_replacements: u32,
+    /// Percentage both fees are raised by in a replacement (nodes require at least 10).
+    pub replacement_bump_pct: u64,
+    /// Blocks a transaction has to be buried under before the next leg depending on it
+    /// starts (1 = mined).
+    pub confirmations: u64,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            max_fee_gwei: 200.0,
+            max_priority_fee_gwei: 3.0,
+            max_cost_pct: 2.0,
+            fallback_swap_gas: 500_000,
+            fee_history_blocks: 10,
+            priority_fee_percentile: 50.0,
+            confirmation_timeout_secs: 120,
+            max_replacements: 3,
+            replacement_bump_pct: 15,
+            confirmations: 1,
+        }
+    }
+}
+
+/// Pre-flight simulation of swap transactions before they are sent.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SimulationConfig {
+    pub enabled: bool,
+    /// Anvil fork (`anvil --fork-url <rpc>`) to execute swaps on first; without one they
+    /// are simulated with `eth_call`.
+    pub fork_rpc_url: Option<String>,
+}
+
+impl Default for SimulationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            fork_rpc_url: None,
+        }
+    }
+}
+
+/// Cross-chain transfers, routed through the LI.FI aggregator.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BridgeConfig {
+    pub base_url: String,
+    /// LI.FI contracts a quote may have the wallet approve or call; quotes naming any
+    /// other contract are rejected.
+    pub allowed_contracts: Vec<Address>,
+    /// Wait for transfers to arrive on the destination chain before the swaps run;
+    /// otherwise, and once the wait times out, they are watched in the background.
+    pub wait_for_completion: bool,
+    pub poll_interval_secs: u64,
+    /// Give up waiting after this long; the transfer is then reported as pending.
+    pub timeout_secs: u64,
+}
+
+impl Default for BridgeConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://li.quest/v1".to_string(),
+            // The LI.FI diamond, deployed at the same address on the EVM chains it serves
+            allowed_contracts: vec![address!("1231DEB6f5749EF6cE6943a275A1D3E7486F4EaE")],
+            wait_for_completion: true,
+            poll_interval_secs: 15,
+            timeout_secs: 600,
+        }
+    }
+}
+
+/// Where the key of a wallet lives.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum SignerConfig {
+    /// Hex private key in the environment variable `var`.
+    Env { var: String },
+    /// Encrypted JSON keystore, its password in the environment variable `password_env`.
+    Keystore { path: PathBuf, password_env: String },
+    /// HTTP signing service (KMS, HSM or hardware wallet proxy) signing hashes for the
+    /// wallet, with an optional bearer token from `auth_token_env`.
+    Remote {
+        url: String,
+        auth_token_env: Option<String>,
+    },
+    /// Quotes and previews only; executing a strategy that sends transactions fails.
+    ReadOnly,
+}
+
+/// Protocols whose APRs are fetched for strategies and the yields endpoint.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct YieldConfig {
+    /// Names of the yield sources to query ("aave", "lido", "eigenlayer", "morpho",
+    /// "pendle", "etherfi", "rocketpool", "kelp", "binance_funding", "lp_fees").
+    pub sources: Vec<String>,
+    /// How often the cached APRs are refreshed in the background.
+    pub refresh_interval_secs: u64,
+    /// How long fetched APRs are kept in the yield history.
+    pub history_retention_days: u32,
+    pub aave: AaveYieldConfig,
+    pub funding: FundingYieldConfig,
+    pub eigen: EigenYieldConfig,
+    pub lp: LpYieldConfig,
+    pub costs: YieldCostConfig,
+}
+
+impl Default for YieldConfig {
+    fn default() -> Self {
+        Self {
+            sources: vec![
+                "aave".to_string(),
+                "lido".to_string(),
+                "eigenlayer".to_string(),
+                "morpho".to_string(),
+                "pendle".to_string(),
+                "etherfi".to_string(),
+                "rocketpool".to_string(),
+                "kelp".to_string(),
+                "binance_funding".to_string(),
+                "lp_fees".to_string(),
+            ],
+            refresh_interval_secs: 300,
+            history_retention_days: 30,
+            aave: AaveYieldConfig::default(),
+            funding: FundingYieldConfig::default(),
+            eigen: EigenYieldConfig::default(),
+            lp: LpYieldConfig::default(),
+            costs: YieldCostConfig::default(),
+        }
+    }
+}
+
+/// Concentrated liquidity pools on Base whose fee APRs are listed.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LpYieldConfig {
+    /// DefiLlama project ids of the DEXes.
+    pub projects: Vec<String>,
+    /// Largest pools listed per project.
+    pub top_pools: usize,
+    /// Pools with less TVL are too thin to provide liquidity to.
+    pub min_tvl_usd: f64,
+    /// DefiLlama lists every pool it tracks in one response of tens of MB, so the Base
+    /// pools of the projects are kept this long before downloading it again.
+    pub pools_ttl_secs: u64,
+}
+
+impl Default for LpYieldConfig {
+    fn default() -> Self {
+        Self {
+            projects: vec!["uniswap-v3".to_string(), "aerodrome-slipstream".to_string()],
+            top_pools: 5,
+            min_tvl_usd: 1_000_000.0,
+            pools_ttl_secs: 3600,
+        }
+    }
+}
+
+/// Dune query the EigenLayer APRs are computed by.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct EigenYieldConfig {
+    /// Environment variable holding the Dune API key; the source is skipped without it.
+    pub dune_api_key_env: String,
+    pub query_id: u64,
+    /// Saved results older than this are refreshed by executing the query, which costs
+    /// Dune credits (0 executes it on every fetch).
+    pub max_result_age_secs: u64,
+    pub poll_interval_secs: u64,
+    /// Give up on an execution (and use the saved result) after this long.
+    pub execution_timeout_secs: u64,
+}
+
+impl Default for EigenYieldConfig {
+    fn default() -> Self {
+        Self {
+            dune_api_key_env: "DUNE_API_KEY".to_string(),
+            query_id: 4127474,
+            max_result_age_secs: 24 * 60 * 60,
+            poll_interval_secs: 5,
+            execution_timeout_secs: 300,
+        }
+    }
+}
+
+/// Costs of entering and exiting a yield, subtracted from its APR to get the
+/// effective APR at a given size.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct YieldCostConfig {
+    /// Token the notional is held in before entering a yield and returns to on exit.
+    pub entry_token: String,
+    /// How long a position is expected to be held; the one-off costs are spread over it.
+    pub holding_days: f64,
+    /// Gas of a full round trip: approvals, the swaps in and out, deposit and withdraw.
+    pub round_trip_gas: u64,
+    /// Notional the effective APRs given to the agent are computed for (0 leaves them
+    /// out of the prompt).
+    pub prompt_notional_usd: f64,
+    /// Yields priced per estimate, the highest APRs first; each costs two Eisen quotes
+    /// per distinct token.
+    pub max_quoted_yields: usize,
+    /// Deposit and withdraw fees per yield source name.
+    pub protocol_fees: HashMap<String, ProtocolFeeConfig>,
+}
+
+impl Default for YieldCostConfig {
+    fn default() -> Self {
+        Self {
+            entry_token: "USDC".to_string(),
+            holding_days: 30.0,
+            round_trip_gas: 800_000,
+            prompt_notional_usd: 10_000.0,
+            max_quoted_yields: 20,
+            protocol_fees: HashMap::from([(
+                "rocketpool".to_string(),
+                ProtocolFeeConfig {
+                    deposit_bps: 5,
+                    withdraw_bps: 0,
+                },
+            )]),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(default)]
+pub struct ProtocolFeeConfig {
+    pub deposit_bps: u32,
+    pub withdraw_bps: u32,
+}
+
+/// Perps whose funding rates are listed as the yield of the short leg.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FundingYieldConfig {
+    /// Binance USDⓈ-M futures API the rates are read from. Mainnet, since testnet
+    /// funding rates say nothing about the real carry.
+    pub base_url: String,
+    pub symbols: Vec<String>,
+    /// Funding periods the trailing average covers (21 is a week of 8h fundings).
+    pub trailing_periods: u32,
+}
+
+impl Default for FundingYieldConfig {
+    fn default() -> Self {
+        Self {
+            base_url: "https://fapi.binance.com".to_string(),
+            symbols: vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()],
+            trailing_periods: 21,
+        }
+    }
+}
+
+/// Aave markets whose reserve rates are read from The Graph.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AaveYieldConfig {
+    /// Environment variable holding The Graph gateway API key.
+    pub graph_api_key_env: String,
+    pub markets: Vec<AaveMarketConfig>,
+}
+
+impl Default for AaveYieldConfig {
+    fn default() -> Self {
+        Self {
+            graph_api_key_env: "GRAPH_API_KEY".to_string(),
+            markets: vec![
+                AaveMarketConfig::v3(8453, "GQFbb95cE6d8mV989mL5figjaGaKCQB3xqYrr1bRyXqF", "aBas"),
+                AaveMarketConfig::v3(1, "Cd2gEDVeqnjBn1hSeqFMitw8Q1iiyV9FYUZkLNRcL87g", "aEth"),
+                AaveMarketConfig::v3(
+                    42161,
+                    "DLuE98kEb5pQNXAcKFQGQgfSQ57Xdou4jnVbAEqMfy3B",
+                    "aArb",
+                ),
+            ],
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AaveMarketConfig {
+    pub chain_id: u64,
+    /// Market label, e.g. "v3".
+    pub market: String,
+    /// The Graph subgraph id of the market.
+    pub subgraph_id: String,
+    /// Prefix of the market's aToken symbols, e.g. "aBas" for aBasUSDC.
+    pub symbol_prefix: String,
+}
+
+impl AaveMarketConfig {
+    fn v3(chain_id: u64, subgraph_id: &str, symbol_prefix: &str) -> Self {
+        Self {
+            chain_id,
+            market: "v3".to_string(),
+            subgraph_id: subgraph_id.to_string(),
+            symbol_prefix: symbol_prefix.to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ChainConfig {
+    pub rpc_url: String,
+    /// Eisen router address Permit2 permits are signed for; without it swaps use approvals.
+    #[serde(default)]
+    pub permit2_spender: Option<String>,
+    /// Private relay (a Flashbots Protect style RPC) Eisen swaps are submitted through
+    /// instead of the public mempool, so large swaps cannot be sandwiched.
+    #[serde(default)]
+    pub private_rpc_url: Option<String>,
+}
+
+impl ChainConfig {
+    fn new(rpc_url: &str) -> Self {
+        Self {
+            rpc_url: rpc_url.to_string(),
+            permit2_spender: None,
+            private_rpc_url: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PaperTradingConfig {
+    /// Fill Binance orders against the live order book instead of placing them.
+    pub enabled: bool,
+    /// Slippage applied to the best bid/ask when filling, in bps.
+    pub slippage_bps: u16,
+    /// USDT wallet balance of the simulated account.
+    pub initial_balance: f64,
+}
+
+impl Default for PaperTradingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            slippage_bps: 5,
+            initial_balance: 10_000.0,
+        }
+    }
+}
+
+/// Snapshots of the configured wallets' portfolios, for the history and PnL endpoints.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PortfolioHistoryConfig {
+    /// How often every wallet in `wallets` is snapshotted; 0 only snapshots on
+    /// portfolio requests.
+    pub snapshot_interval_secs: u64,
+    pub retention_days: u32,
+}
+
+impl Default for PortfolioHistoryConfig {
+    fn default() -> Self {
+        Self {
+            snapshot_interval_secs: 3600,
+            retention_days: 90,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MarketDataConfig {
+    /// Futures symbols streamed into the price cache.
+    pub symbols: Vec<String>,
+    /// Interval of the streamed klines ("1m", "5m", ...).
+    pub kline_interval: String,
+    /// Older streamed prices are not used; REST is queried instead.
+    pub max_price_age_secs: u64,
+    /// Timeframes OHLCV data and indicators are fetched for.
+    pub ohlcv_intervals: Vec<Interval>,
+    /// Timeframe of the indicators summarized in the strategy prompt.
+    pub indicator_interval: Interval,
+    /// Candles the prompt's indicators are computed over.
+    pub indicator_candles: usize,
+    /// How often the indicator pipeline polls the latest candle of each symbol.
+    pub pipeline_interval_secs: u64,
+    /// Order book levels per side summed into the depth metrics.
+    pub depth_levels: usize,
+    /// Binance orders are refused while a symbol's spread is wider, in bps.
+    pub max_spread_bps: f64,
+    /// Limit orders priced further past the best price of the other side are refused,
+    /// in %.
+    pub max_limit_cross_pct: f64,
+    /// How long collected candles and prices are kept in the market history.
+    pub history_retention_days: u32,
+}
+
+impl Default for MarketDataConfig {
+    fn default() -> Self {
+        Self {
+            symbols: vec!["BTCUSDT".to_string(), "ETHUSDT".to_string()],
+            kline_interval: "1m".to_string(),
+            max_price_age_secs: 10,
+            ohlcv_intervals: Interval::iter().collect(),
+            indicator_interval: Interval::Hour1,
+            indicator_candles: 100,
+            pipeline_interval_secs: 60,
+            depth_levels: 20,
+            max_spread_bps: 10.0,
+            max_limit_cross_pct: 0.5,
+            history_retention_days: 30,
+        }
+    }
+}
+
+/// Chainlink aggregators Binance mark prices are checked against before a strategy executes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PriceCheckConfig {
+    pub enabled: bool,
+    /// Chain the aggregators are read on.
+    pub chain_id: u64,
+    /// Aggregator address per Binance symbol.
+    pub feeds: HashMap<String, String>,
+    /// Largest allowed gap between the Binance and the Chainlink price, in bps.
+    pub max_divergence_bps: u32,
+    /// Older Chainlink answers reject execution; the USD feeds update at least hourly.
+    pub max_age_secs: u64,
+}
+
+impl Default for PriceCheckConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            chain_id: 1,
+            feeds: HashMap::from([
+                (
+                    "BTCUSDT".to_string(),
+                    "0xF4030086522a5bEEa4988F8cA5B36dbC97BeE88c".to_string(),
+                ),
+                (
+                    "ETHUSDT".to_string(),
+                    "0x5f4eC3Df9cbd43714FE2740F5E3616155c5b8419".to_string(),
+                ),
+            ]),
+            max_divergence_bps: 100,
+            max_age_secs: 3900,
+        }
+    }
+}
+
+/// How on-chain holdings are valued in USD for the strategy prompt.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OnchainPriceConfig {
+    /// Stablecoin tokens are quoted into.
+    pub quote_token: String,
+    /// Whole tokens quoted to price one; small enough to stay near the spot price.
+    pub quote_amount: f64,
+    /// Tokens valued at $1 without a quote.
+    pub stablecoins: Vec<String>,
+}
+
+impl Default for OnchainPriceConfig {
+    fn default() -> Self {
+        Self {
+            quote_token: "usdc".to_string(),
+            quote_amount: 1.0,
+            stablecoins: ["usdc", "usdbc", "usdt", "dai"].map(String::from).to_vec(),
+        }
+    }
+}
+
+/// Limits every agent strategy is checked against before it executes.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StrategyValidationConfig {
+    /// Tokens Binance orders may trade.
+    pub perp_tokens: Vec<String>,
+    /// Largest notional of a single Binance order, in USD.
+    pub max_order_notional_usd: f64,
+    /// Largest combined USD value of a strategy's orders, bridge transfers and swaps.
+    pub max_turnover_usd: f64,
+    /// Times the agent is asked again, given the violations, before the strategy is
+    /// rejected.
+    pub reprompt_attempts: u32,
+    /// Strategies with a leg the agent is less confident of than this are previewed
+    /// for confirmation rather than executed; unset executes them all.
+    pub approval_confidence: Option<f64>,
+}
+
+impl Default for StrategyValidationConfig {
+    fn default() -> Self {
+        Self {
+            perp_tokens: ["BTC", "ETH"].map(String::from).to_vec(),
+            max_order_notional_usd: 50_000.0,
+            max_turnover_usd: 250_000.0,
+            reprompt_attempts: 1,
+            approval_confidence: None,
+        }
+    }
+}
+
+/// Past executions of the wallet the agent is reminded of when asked for a strategy.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StrategyMemoryConfig {
+    /// Latest executions summarised; 0 leaves the summary out.
+    pub executions: usize,
+    /// Executions older than this are not recalled.
+    pub lookback_days: u32,
+}
+
+impl Default for StrategyMemoryConfig {
+    fn default() -> Self {
+        Self {
+            executions: 5,
+            lookback_days: 30,
+        }
+    }
+}
+
+/// Limits every Binance order and Eisen swap is gated by as it executes; a leg
+/// breaking one is not sent. Unset limits are not enforced.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RiskConfig {
+    /// Largest notional of a single order or swap, in USD.
+    pub max_order_notional_usd: Option<f64>,
+    /// Largest gross position notional over the futures margin balance.
+    pub max_leverage: Option<f64>,
+    /// Largest USD value executed per UTC day, across jobs.
+    pub max_daily_turnover_usd: Option<f64>,
+    /// Largest net USD exposure to one base asset (on-chain balances plus perp
+    /// positions); legs reducing the exposure are always allowed.
+    pub max_net_delta_usd: Option<f64>,
+    /// Largest gap between an order's limit price and the live mark price, in % of the
+    /// mark price, so an agent's stale or made-up price is never placed.
+    pub max_price_deviation_pct: Option<f64>,
+    /// Futures symbols and on-chain tokens that are never traded (case-insensitive).
+    pub forbidden_symbols: Vec<String>,
+}
+
+impl RiskConfig {
+    /// The tighter of each limit of both configs, and the symbols either forbids.
+    pub fn tightened(&self, other: &RiskConfig) -> RiskConfig {
+        let tighter = |a: Option<f64>, b: Option<f64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        let mut forbidden_symbols = self.forbidden_symbols.clone();
+        forbidden_symbols.extend(other.forbidden_symbols.iter().cloned());
+        RiskConfig {
+            max_order_notional_usd: tighter(
+                self.max_order_notional_usd,
+                other.max_order_notional_usd,
+            ),
+            max_leverage: tighter(self.max_leverage, other.max_leverage),
+            max_daily_turnover_usd: tighter(
+                self.max_daily_turnover_usd,
+                other.max_daily_turnover_usd,
+            ),
+            max_net_delta_usd: tighter(self.max_net_delta_usd, other.max_net_delta_usd),
+            max_price_deviation_pct: tighter(
+                self.max_price_deviation_pct,
+                other.max_price_deviation_pct,
+            ),
+            forbidden_symbols,
+        }
+    }
+}
+
+/// Compensation of the exposure a partly failed execution leaves unhedged, e.g. a
+/// filled perp short whose on-chain buy failed.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HedgeUnwindConfig {
+    /// Operators opt out to handle partial executions by hand.
+    pub enabled: bool,
+    /// Unmatched exposure per base asset below this, in USD, is left alone.
+    pub tolerance_usd: f64,
+}
+
+impl Default for HedgeUnwindConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tolerance_usd: 50.0,
+        }
+    }
+}
+
+/// Strategy runs triggered on a cron schedule instead of an HTTP call.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct SchedulerConfig {
+    /// Schedules only run when enabled; they can be managed through the API either way.
+    pub enabled: bool,
+    /// How often schedules are checked for firings.
+    pub tick_secs: u64,
+    /// Loaded on startup, replacing stored schedules with the same id.
+    pub schedules: Vec<Schedule>,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            tick_secs: 30,
+            schedules: Vec::new(),
+        }
+    }
+}
+
+/// Rule-based engine hedging on-chain holdings with perp shorts while funding pays.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct FundingRebalancerConfig {
+    /// Base assets hedged, each on its USDT perp.
+    pub assets: Vec<String>,
+    /// Funding rate per period (0.0001 is 0.01%) at or above which holdings are hedged.
+    pub enter_funding_rate: f64,
+    /// Funding rate per period at or below which the hedge is closed.
+    pub exit_funding_rate: f64,
+    /// Gaps to the target hedge below this, in USD, are left alone.
+    pub min_order_usd: f64,
+}
+
+impl Default for FundingRebalancerConfig {
+    fn default() -> Self {
+        Self {
+            assets: vec!["ETH".to_string(), "BTC".to_string()],
+            enter_funding_rate: 0.0001,
+            exit_funding_rate: 0.0,
+            min_order_usd: 50.0,
+        }
+    }
+}
+
+/// Background monitor cutting Binance positions independently of strategy runs.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct StopLossConfig {
+    pub enabled: bool,
+    /// How often positions are checked.
+    pub interval_secs: u64,
+    /// Unrealized loss, in percent of the entry notional, at which the part of a
+    /// position not offset by on-chain holdings is closed.
+    pub max_loss_pct: f64,
+    /// Funding rate per period a position pays (longs positive funding, shorts
+    /// negative) at which the part of it not offset by on-chain holdings is closed;
+    /// 0 disables.
+    pub max_funding_paid_rate: f64,
+}
+
+impl Default for StopLossConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 60,
+            max_loss_pct: 10.0,
+            max_funding_paid_rate: 0.001,
+        }
+    }
+}
+
+/// Othentic AVS node the "othentic" provider asks for strategies.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct OthenticConfig {
+    pub host: String,
+    pub port: u16,
+    pub task_definition_id: u16,
+    /// Only execute strategies signed by their performer, an operator of `operators`.
+    pub verify_signatures: bool,
+    /// Addresses of the trusted operators.
+    pub operators: Vec<String>,
+}
+
+impl Default for OthenticConfig {
+    fn default() -> Self {
+        Self {
+            host: "localhost".to_string(),
+            port: 4003,
+            task_definition_id: 0,
+            verify_signatures: true,
+            operators: Vec::new(),
+        }
+    }
+}
+
+/// LLM providers strategies can be asked from.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct LlmConfig {
+    /// Provider asked when a request does not name one: "othentic" for the local
+    /// Othentic node, or one of `providers`.
+    pub provider: String,
+    /// Chat provider the stablecoin yield farming engine asks when a request does not
+    /// name one.
+    pub yield_farming_provider: String,
+    /// Chat providers by name, added to the defaults; one named like a default
+    /// replaces it.
+    #[serde(deserialize_with = "with_default_providers")]
+    pub providers: HashMap<String, LlmProviderConfig>,
+    /// How long one ask may take before it is abandoned.
+    pub timeout_secs: u64,
+    /// Asks repeated after a timeout or failed request, waiting `retry_backoff_ms`
+    /// before the first and twice as long before each next one.
+    pub retries: u32,
+    pub retry_backoff_ms: u64,
+    pub circuit_breaker: CircuitBreakerConfig,
+    pub prompts: PromptConfig,
+    /// Prices of models by name, or by the prefix of their names, that agent calls
+    /// are costed at. Replaces the defaults when set.
+    pub pricing: HashMap<String, ModelPricing>,
+    /// Models requests and schedules may name, by their exact name or a pattern ending
+    /// in `*` for every name starting with the rest; the configured model of each
+    /// provider always is.
+    pub allowed_models: Vec<String>,
+}
+
+fn with_default_providers<'de, D>(
+    deserializer: D,
+) -> Result<HashMap<String, LlmProviderConfig>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let mut providers = LlmConfig::default().providers;
+    providers.extend(HashMap::<String, LlmProviderConfig>::deserialize(
+        deserializer,
+    )?);
+    Ok(providers)
+}
+
+impl LlmConfig {
+    pub fn allows_model(&self, model: &str) -> bool {
+        let model = model.to_lowercase();
+        self.providers
+            .values()
+            .any(|provider| provider.model().eq_ignore_ascii_case(&model))
+            || self.allowed_models.iter().any(|allowed| {
+                let allowed = allowed.to_lowercase();
+                match allowed.strip_suffix('*') {
+                    Some(prefix) => model.starts_with(prefix),
+                    None => model == allowed,
+                }
+            })
+    }
+}
+
+impl Default for LlmConfig {
+    fn default() -> Self {
+        Self {
+            provider: OTHENTIC_PROVIDER.to_string(),
+            yield_farming_provider: "openai".to_string(),
+            timeout_secs: 120,
+            retries: 2,
+            retry_backoff_ms: 1000,
+            circuit_breaker: CircuitBreakerConfig::default(),
+            prompts: PromptConfig::default(),
+            allowed_models: [
+                "o1",
+                "o1-20*",
+                "gpt-4o",
+                "gpt-4o-20*",
+                "claude-sonnet-4-5",
+                "claude-sonnet-4-5-20*",
+            ]
+            .map(String::from)
+            .to_vec(),
+            pricing: HashMap::from([
+                ("o1".to_string(), ModelPricing::new(15.0, 60.0)),
+                ("gpt-4o".to_string(), ModelPricing::new(2.5, 10.0)),
+                ("gpt-4o-mini".to_string(), ModelPricing::new(0.15, 0.6)),
+                (
+                    "claude-sonnet-4-5".to_string(),
+                    ModelPricing::new(3.0, 15.0),
+                ),
+            ]),
+            providers: HashMap::from([
+                (
+                    "openai".to_string(),
+                    LlmProviderConfig::OpenAI {
+                        api_key_env: "OPENAI_API_KEY".to_string(),
+                        model: "o1".to_string(),
+                    },
+                ),
+                (
+                    "anthropic".to_string(),
+                    LlmProviderConfig::Anthropic {
+                        api_key_env: "ANTHROPIC_API_KEY".to_string(),
+                        base_url: anthropic_base_url(),
+                        model: "claude-sonnet-4-5".to_string(),
+                        max_tokens: anthropic_max_tokens(),
+                    },
+                ),
+                (
+                    "local".to_string(),
+                    LlmProviderConfig::OpenAICompatible {
+                        base_url: "http://localhost:11434/v1".to_string(),
+                        api_key_env: None,
+                        model: "llama3.1".to_string(),
+                        structured_outputs: true,
+                    },
+                ),
+            ]),
+        }
+    }
+}
+
+/// Stops asking a provider that keeps failing, serving `fallback` strategies instead
+/// until `cooldown_secs` have passed and one ask gets through again.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CircuitBreakerConfig {
+    /// Asks in a row, retries exhausted, that fail before the circuit opens.
+    pub failure_threshold: u32,
+    pub cooldown_secs: u64,
+    pub fallback: AgentFallback,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 3,
+            cooldown_secs: 300,
+            fallback: AgentFallback::NoOp,
+        }
+    }
+}
+
+/// USD per million prompt (input) and completion (output) tokens.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ModelPricing {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+impl ModelPricing {
+    pub fn new(input_per_mtok: f64, output_per_mtok: f64) -> Self {
+        Self {
+            input_per_mtok,
+            output_per_mtok,
+        }
+    }
+}
+
+/// Templates of the prompts chat providers are sent, in addition to the built-in
+/// "system" and "request" ones, which they replace under the same name.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PromptConfig {
+    /// Directory of template files, each named after its file without the extension.
+    pub dir: Option<PathBuf>,
+    /// Templates by name, taking precedence over those of `dir`.
+    pub templates: HashMap<String, String>,
+}
+
+/// Strategy served while a provider is down.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AgentFallback {
+    /// No legs, so nothing changes until the provider is back.
+    #[default]
+    NoOp,
+    /// The rule-based funding rebalancer.
+    FundingRebalancer,
+}
+
+/// API a chat provider is reached through, and the model asked unless a request
+/// names one.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum LlmProviderConfig {
+    #[serde(rename = "openai")]
+    OpenAI {
+        /// Environment variable holding the API key.
+        api_key_env: String,
+        model: String,
+    },
+    Anthropic {
+        api_key_env: String,
+        #[serde(default = "anthropic_base_url")]
+        base_url: String,
+        model: String,
+        #[serde(default = "anthropic_max_tokens")]
+        max_tokens: u32,
+    },
+    /// Any OpenAI-compatible API, e.g. a local Ollama, vLLM or LM Studio server.
+    #[serde(rename = "openai_compatible")]
+    OpenAICompatible {
+        base_url: String,
+        /// Unset for servers without authentication.
+        #[serde(default)]
+        api_key_env: Option<String>,
+        model: String,
+        /// Whether the server supports JSON schema response formats; without them
+        /// replies are only asked to match the schema.
+        #[serde(default = "enabled")]
+        structured_outputs: bool,
+    },
+}
+
+impl LlmProviderConfig {
+    pub fn model(&self) -> &str {
+        match self {
+            LlmProviderConfig::OpenAI { model, .. }
+            | LlmProviderConfig::Anthropic { model, .. }
+            | LlmProviderConfig::OpenAICompatible { model, .. } => model,
+        }
+    }
+}
+
+fn anthropic_base_url() -> String {
+    "https://api.anthropic.com".to_string()
+}
+
+fn anthropic_max_tokens() -> u32 {
+    8192
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// Where event messages are pushed, and what they say.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct NotificationConfig {
+    pub channels: Vec<NotificationChannel>,
+    /// Message per event, overriding the built-in one; `{field}`s such as `{job_id}`
+    /// are filled in from the event.
+    pub templates: HashMap<NotificationEvent, String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NotificationChannel {
+    #[serde(flatten)]
+    pub target: NotificationTarget,
+    /// Events sent to this channel; all of them when empty.
+    #[serde(default)]
+    pub events: Vec<NotificationEvent>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum NotificationTarget {
+    /// Discord channel webhook.
+    Discord { url: String },
+    /// Slack incoming webhook.
+    Slack { url: String },
+    /// Any endpoint taking a JSON `{"event", "text"}` POST.
+    Webhook { url: String },
+    /// Telegram chat, messaged by the bot whose token is in the environment variable
+    /// `bot_token_env`.
+    Telegram {
+        bot_token_env: String,
+        chat_id: String,
+    },
+}
+
+/// Binance wallets merged into the futures account, since collateral often sits in spot.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct BinanceAccountsConfig {
+    pub spot: bool,
+    /// Cross margin account; needs margin trading enabled on the API key.
+    pub margin: bool,
+}
+
+impl Default for BinanceAccountsConfig {
+    fn default() -> Self {
+        Self {
+            spot: true,
+            margin: false,
+        }
+    }
+}
+
+/// One leg of a target allocation, weighted as a share of the unified portfolio's
+/// USD value (Binance margin balance plus priced on-chain balances).
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AllocationLeg {
+    /// USDT-margined perpetual on `token`, by notional; a negative weight is a short.
+    Perp { token: String, weight: f64 },
+    /// Base wallet balance of `token`.
+    Onchain { token: String, weight: f64 },
+}
+
+impl AllocationLeg {
+    pub fn token(&self) -> &str {
+        match self {
+            Self::Perp { token, .. } | Self::Onchain { token, .. } => token,
+        }
+    }
+
+    pub fn weight(&self) -> f64 {
+        match self {
+            Self::Perp { weight, .. } | Self::Onchain { weight, .. } => *weight,
+        }
+    }
+}
+
+/// Target allocation the rebalance endpoint closes the gap to, e.g. a 50% ETH short
+/// against 50% wstETH.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct RebalanceConfig {
+    /// No rebalancing, fallback or validation when empty.
+    pub target: Vec<AllocationLeg>,
+    /// Token on-chain legs are bought with and sold into.
+    pub funding_token: String,
+    /// Gaps smaller than this, in USD, are left open.
+    pub min_trade_usd: f64,
+    /// Execute the rebalance when the agent fails to return a strategy.
+    pub fallback: bool,
+}
+
+impl Default for RebalanceConfig {
+    fn default() -> Self {
+        Self {
+            target: Vec::new(),
+            funding_token: "usdc".to_string(),
+            min_trade_usd: 10.0,
+            fallback: false,
+        }
+    }
+}
+
+/// When the Binance account counts as near liquidation, warning the strategy prompt.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MarginHealthConfig {
+    /// Maintenance margin over margin balance; Binance liquidates at 1.
+    pub warn_margin_ratio: f64,
+    /// Warn on positions an adverse move of less than this, in percent, liquidates.
+    pub warn_liquidation_distance_pct: f64,
+}
+
+impl Default for MarginHealthConfig {
+    fn default() -> Self {
+        Self {
+            warn_margin_ratio: 0.5,
+            warn_liquidation_distance_pct: 10.0,
+        }
+    }
+}
+
+/// Which base asset each on-chain token is exposure to, for hedging against Binance.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ExposureConfig {
+    /// Base asset ("ETH", "BTC", "USD") per token symbol; replaces the defaults when
+    /// set. Symbols are matched case-insensitively.
+    pub base_assets: HashMap<String, String>,
+}
+
+impl ExposureConfig {
+    /// Base asset of `symbol`, or `None` for a token that is not mapped.
+    pub fn base_asset(&self, symbol: &str) -> Option<&str> {
+        self.base_assets
+            .iter()
+            .find(|(token, _)| token.eq_ignore_ascii_case(symbol))
+            .map(|(_, base)| base.as_str())
+    }
+}
+
+impl Default for ExposureConfig {
+    fn default() -> Self {
+        let tokens = |base: &str, symbols: &[&str]| {
+            symbols
+                .iter()
+                .map(|symbol| (symbol.to_string(), base.to_string()))
+                .collect::<Vec<_>>()
+        };
+        Self {
+            base_assets: [
+                tokens(
+                    "ETH",
+                    &[
+                        "ETH", "WETH", "stETH", "wstETH", "weETH", "ezETH", "rETH", "cbETH",
+                    ],
+                ),
+                tokens("BTC", &["BTC", "WBTC", "cbBTC", "tBTC"]),
+                tokens("USD", &["USDC", "USDbC", "USDT", "DAI"]),
+            ]
+            .concat()
+            .into_iter()
+            .collect(),
+        }
+    }
+}
+
+/// Pyth prices used when Binance's price endpoints fail or lag.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PriceFallbackConfig {
+    /// Base URL of the Pyth Hermes API.
+    pub hermes_url: String,
+    /// Pyth price feed id per Binance symbol; other symbols have no fallback.
+    pub price_ids: HashMap<String, String>,
+    /// Older prices count as stale, from either source.
+    pub max_age_secs: u64,
+}
+
+impl Default for PriceFallbackConfig {
+    fn default() -> Self {
+        Self {
+            hermes_url: "https://hermes.pyth.network".to_string(),
+            price_ids: HashMap::from([
+                (
+                    "BTCUSDT".to_string(),
+                    "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43".to_string(),
+                ),
+                (
+                    "ETHUSDT".to_string(),
+                    "ff61491a931112ddf1bd8147cd1b641375f79f5825126d665480874634fd0ace".to_string(),
+                ),
+            ]),
+            max_age_secs: 60,
+        }
+    }
+}
+
+/// HTTPS for the API server, which serves plain HTTP unless enabled.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// PEM certificate chain and private key, read at startup; unused with `acme`.
+    pub cert_path: Option<PathBuf>,
+    pub key_path: Option<PathBuf>,
+    /// Certificates issued and renewed through ACME (TLS-ALPN-01) instead.
+    pub acme: Option<AcmeConfig>,
+}
+
+/// Let's Encrypt certificates of the domains the server is reached at.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct AcmeConfig {
+    pub domains: Vec<String>,
+    /// Contact emails of the ACME account.
+    pub contacts: Vec<String>,
+    /// Issue from the production directory rather than staging.
+    pub production: bool,
+    /// Where the account and certificates are cached across restarts; `acme` in the
+    /// data directory when unset.
+    pub cache_dir: Option<PathBuf>,
+}
+
+/// Browser origins allowed to call the API cross-origin; none by default, so only
+/// same-origin pages and non-browser clients reach it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CorsConfig {
+    /// Origins such as "https://app.example.com", or "*" for any.
+    pub allowed_origins: Vec<String>,
+    pub allowed_methods: Vec<String>,
+    pub allowed_headers: Vec<String>,
+    /// Let cross-origin requests carry cookies and credentials; never with "*".
+    pub allow_credentials: bool,
+    /// How long browsers may cache a preflight response.
+    pub max_age_secs: u64,
+}
+
+impl Default for CorsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_origins: Vec::new(),
+            allowed_methods: ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec(),
+            allowed_headers: ["authorization", "content-type", "accept"]
+                .map(String::from)
+                .to_vec(),
+            allow_credentials: false,
+            max_age_secs: 600,
+        }
+    }
+}
+
+impl CorsConfig {
+    /// Layer enforcing the policy, failing on an origin, method or header that does not
+    /// parse.
+    pub fn layer(&self) -> Result<CorsLayer> {
+        let any_origin = self.allowed_origins.iter().any(|origin| origin == "*");
+        if any_origin && self.allow_credentials {
+            return Err(anyhow!(
+                "Invalid cors: credentials cannot be allowed for any origin"
+            ));
+        }
+        let origins = self
+            .allowed_origins
+            .iter()
+            .map(|origin| {
+                HeaderValue::from_str(origin)
+                    .map_err(|e| anyhow!("Invalid cors origin {}: {}", origin, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let methods = self
+            .allowed_methods
+            .iter()
+            .map(|method| {
+                Method::from_bytes(method.to_uppercase().as_bytes())
+                    .map_err(|e| anyhow!("Invalid cors method {}: {}", method, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let headers = self
+            .allowed_headers
+            .iter()
+            .map(|header| {
+                HeaderName::from_bytes(header.as_bytes())
+                    .map_err(|e| anyhow!("Invalid cors header {}: {}", header, e))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(CorsLayer::new()
+            .allow_origin(if any_origin {
+                AllowOrigin::any()
+            } else {
+                AllowOrigin::list(origins)
+            })
+            .allow_methods(methods)
+            .allow_headers(headers)
+            .allow_credentials(self.allow_credentials)
+            .max_age(Duration::from_secs(self.max_age_secs)))
+    }
+
+    /// Why a request a browser could have sent from another site is refused, if it is:
+    /// state-changing requests must come from the server's own host or an allowed origin,
+    /// and those with a body must send JSON, which cross-site forms cannot without a
+    /// preflight the CORS policy answers.
+    pub fn refuses(&self, method: &Method, headers: &http::HeaderMap) -> Option<String> {
+        if method.is_safe() {
+            return None;
+        }
+        let header = |name: http::header::HeaderName| {
+            headers.get(name).and_then(|value| value.to_str().ok())
+        };
+        if let Some(origin) = header(http::header::ORIGIN) {
+            let same_host = origin
+                .split_once("://")
+                .is_some_and(|(_, host)| header(http::header::HOST) == Some(host));
+            let allowed = self
+                .allowed_origins
+                .iter()
+                .any(|allowed| allowed == "*" || allowed.eq_ignore_ascii_case(origin));
+            if !same_host && !allowed {
+                return Some(format!("Origin {} is not allowed", origin));
+            }
+        }
+        let sends_body = [Method::POST, Method::PUT, Method::PATCH].contains(method);
+        let json = header(http::header::CONTENT_TYPE).is_some_and(|content_type| {
+            content_type
+                .split(';')
+                .next()
+                .is_some_and(|mime| mime.trim().eq_ignore_ascii_case("application/json"))
+        });
+        if sends_body && !json {
+            return Some(format!(
+                "{} requests must send Content-Type: application/json",
+                method
+            ));
+        }
+        None
+    }
+}
+
+/// Who may pause and resume trading.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct AdminConfig {
+    /// Environment variable holding the bearer token admin requests must send; while it
+    /// is unset the admin endpoints refuse every request.
+    pub token_env: String,
+}
+
+impl Default for AdminConfig {
+    fn default() -> Self {
+        Self {
+            token_env: "ADMIN_TOKEN".to_string(),
+        }
+    }
+}
+
+/// The HTTP client every module calls its APIs through, pooling connections per host.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct HttpConfig {
+    /// Longest an API request may take, its response included. Agent calls are bounded
+    /// by `llm.timeout_secs` instead.
+    pub timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    /// Pooled connections idle for longer are closed.
+    pub pool_idle_timeout_secs: u64,
+    /// Proxy every request goes through, e.g. "http://proxy.internal:3128".
+    pub proxy: Option<String>,
+}
+
+impl Default for HttpConfig {
+    fn default() -> Self {
+        Self {
+            timeout_secs: 30,
+            connect_timeout_secs: 10,
+            pool_idle_timeout_secs: 90,
+            proxy: None,
+        }
+    }
+}
+
+impl HttpConfig {
+    /// Client of these settings whose requests give up after `timeout`.
+    pub fn client(&self, timeout: Duration) -> Result<reqwest::Client> {
+        let mut builder = reqwest::Client::builder()
+            .timeout(timeout)
+            .connect_timeout(Duration::from_secs(self.connect_timeout_secs))
+            .pool_idle_timeout(Duration::from_secs(self.pool_idle_timeout_secs));
+        if let Some(proxy) = &self.proxy {
+            let proxy = reqwest::Proxy::all(proxy)
+                .map_err(|e| anyhow!("Invalid http.proxy {}: {}", proxy, e))?;
+            builder = builder.proxy(proxy);
+        }
+        builder
+            .build()
+            .map_err(|e| anyhow!("Failed to build the HTTP client: {}", e))
+    }
+}
+
+impl Config {
+    /// Load the config file, or the defaults when no path is given.
+    pub fn load(path: Option<&Path>) -> Result<Self> {
+        let Some(path) = path else {
+            return Ok(Self::default());
+        };
+        let raw = fs::read_to_string(path)
+            .map_err(|e| anyhow!("Failed to read config {:?}: {}", path, e))?;
+        let config: Self =
+            serde_yaml::from_str(&raw).map_err(|e| anyhow!("Invalid config {:?}: {}", path, e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        validate_slippage_bps(self.swaps.slippage_bps)
+            .map_err(|e| anyhow!("Invalid swaps.slippage_bps: {}", e))?;
+        if !(1..=MAX_RECV_WINDOW_MS).contains(&self.binance_recv_window_ms) {
+            return Err(anyhow!(
+                "Invalid binance_recv_window_ms {}: must be 1 to {}",
+                self.binance_recv_window_ms,
+                MAX_RECV_WINDOW_MS
+            ));
+        }
+        if self.stop_loss.interval_secs == 0 {
+            return Err(anyhow!(
+                "Invalid stop_loss.interval_secs: must be at least 1"
+            ));
+        }
+        // A zero period panics the scheduler's ticker
+        if self.scheduler.tick_secs == 0 {
+            return Err(anyhow!("Invalid scheduler.tick_secs: must be at least 1"));
+        }
+        // A zero estimate would let any swap through the gas cost check
+        if self.gas.fallback_swap_gas == 0 {
+            return Err(anyhow!("Invalid gas.fallback_swap_gas: must be at least 1"));
+        }
+        if self.market_data.pipeline_interval_secs == 0 {
+            return Err(anyhow!(
+                "Invalid market_data.pipeline_interval_secs: must be at least 1"
+            ));
+        }
+        // A zero interval would refresh the yield sources in a busy loop
+        if self.yields.refresh_interval_secs == 0 {
+            return Err(anyhow!(
+                "Invalid yields.refresh_interval_secs: must be at least 1"
+            ));
+        }
+        if self.bridge.allowed_contracts.is_empty() {
+            return Err(anyhow!(
+                "Invalid bridge.allowed_contracts: list the LI.FI contracts to allow"
+            ));
+        }
+        if self.bridge.poll_interval_secs == 0 {
+            return Err(anyhow!(
+                "Invalid bridge.poll_interval_secs: must be at least 1"
+            ));
+        }
+        self.cors.layer().map(drop)?;
+        let tls = &self.tls;
+        if tls.enabled {
+            match &tls.acme {
+                Some(acme) if acme.domains.is_empty() => {
+                    return Err(anyhow!("Invalid tls.acme: no domains"));
+                }
+                None if tls.cert_path.is_none() || tls.key_path.is_none() => {
+                    return Err(anyhow!("Invalid tls: set cert_path and key_path, or acme"));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ohlcv_intervals() -> Result<()> {
+        assert_eq!(
+            Config::default().market_data.ohlcv_intervals.last(),
+            Some(&Interval::Week1)
+        );
+        let config: Config =
+            serde_yaml::from_str("market_data:\n  ohlcv_intervals: [\"1h\", \"4h\", \"1d\"]\n")?;
+        assert_eq!(
+            config.market_data.ohlcv_intervals,
+            vec![Interval::Hour1, Interval::Hour4, Interval::Day1]
+        );
+        assert!(
+            serde_yaml::from_str::<Config>("market_data:\n  ohlcv_intervals: [\"2h\"]\n").is_err()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_partial_config_uses_defaults() -> Result<()> {
+        let config: Config = serde_yaml::from_str("paper_trading:\n  enabled: true\n")?;
+        assert!(config.paper_trading.enabled);
+        assert_eq!(config.paper_trading.slippage_bps, 5);
+        assert_eq!(config.data_dir, PathBuf::from("data"));
+        assert!(!Config::load(None)?.paper_trading.enabled);
+        assert!(config.chains.contains_key(&8453));
+        assert_eq!(config.swaps.approval, ApprovalPolicy::Exact);
+
+        let config: Config =
+            serde_yaml::from_str("chains:\n  10:\n    rpc_url: https://mainnet.optimism.io\n")?;
+        assert_eq!(config.chains.len(), 1);
+        assert_eq!(config.chains[&10].rpc_url, "https://mainnet.optimism.io");
+
+        let config: Config = serde_yaml::from_str(
+            "llm:\n  providers:\n    openai:\n      kind: openai\n      api_key_env: KEY\n      model: gpt-4o\n    groq:\n      kind: openai_compatible\n      base_url: https://api.groq.com/openai/v1\n      model: llama-3.3-70b\n",
+        )?;
+        assert_eq!(config.llm.providers.len(), 4);
+        assert_eq!(config.llm.providers["openai"].model(), "gpt-4o");
+        assert_eq!(
+            config.llm.providers["anthropic"].model(),
+            "claude-sonnet-4-5"
+        );
+
+        let config: Config = serde_yaml::from_str("swaps:\n  approval: max\n")?;
+        assert_eq!(config.swaps.approval, ApprovalPolicy::Max);
+        assert_eq!(config.swaps.slippage_bps, DEFAULT_SLIPPAGE_BPS);
+        assert!(config.validate().is_ok());
+
+        let config: Config = serde_yaml::from_str("swaps:\n  slippage_bps: 5000\n")?;
+        assert!(config.validate().is_err());
+        let config: Config = serde_yaml::from_str("stop_loss:\n  interval_secs: 0\n")?;
+        assert!(config.validate().is_err());
+        let config: Config = serde_yaml::from_str("scheduler:\n  tick_secs: 0\n")?;
+        assert!(config.validate().is_err());
+
+        let config: Config =
+            serde_yaml::from_str("tls:\n  enabled: true\n  cert_path: cert.pem\n")?;
+        assert!(config.validate().is_err());
+        let config: Config = serde_yaml::from_str(
+            "tls:\n  enabled: true\n  acme:\n    domains: [pm.example.com]\n",
+        )?;
+        assert!(config.validate().is_ok());
+        assert!(!config.tls.acme.unwrap().production);
+
+        assert!(HttpConfig::default().client(Duration::from_secs(1)).is_ok());
+        let config: Config = serde_yaml::from_str("http:\n  proxy: 'not a proxy'\n")?;
+        assert!(config.http.client(Duration::from_secs(1)).is_err());
+
+        let llm = LlmConfig::default();
+        assert!(llm.allows_model("gpt-4o-2024-08-06"));
+        assert!(llm.allows_model("GPT-4o"));
+        assert!(llm.allows_model("llama3.1"));
+        assert!(!llm.allows_model("gpt-3.5-turbo"));
+        // Only the snapshots of an allowed model, not every model named like it
+        assert!(!llm.allows_model("gpt-4o-mini"));
+        assert!(!llm.allows_model("o1-pro"));
+
+        assert!(CorsConfig::default().layer().is_ok());
+        let config: Config = serde_yaml::from_str(
+            "cors:\n  allowed_origins: ['https://app.example.com']\n  allow_credentials: true\n",
+        )?;
+        assert!(config.validate().is_ok());
+        let config: Config =
+            serde_yaml::from_str("cors:\n  allowed_origins: ['*']\n  allow_credentials: true\n")?;
+        assert!(config.validate().is_err());
+        let config: Config = serde_yaml::from_str("cors:\n  allowed_methods: ['GET POST']\n")?;
+        assert!(config.validate().is_err());
+
+        let cors = CorsConfig {
+            allowed_origins: vec!["https://app.example.com".to_string()],
+            ..Default::default()
+        };
+        let headers = |pairs: &[(&'static str, &'static str)]| {
+            let mut headers = http::HeaderMap::new();
+            for (name, value) in pairs {
+                headers.insert(*name, HeaderValue::from_static(value));
+            }
+            headers
+        };
+        let json = ("content-type", "application/json; charset=utf-8");
+        assert!(cors
+            .refuses(
+                &Method::GET,
+                &headers(&[("origin", "https://evil.example")])
+            )
+            .is_none());
+        assert!(cors.refuses(&Method::POST, &headers(&[json])).is_none());
+        assert!(cors
+            .refuses(
+                &Method::POST,
+                &headers(&[json, ("origin", "https://app.example.com")])
+            )
+            .is_none());
+        assert!(cors
+            .refuses(
+                &Method::POST,
+                &headers(&[
+                    json,
+                    ("origin", "http://pm.local:8080"),
+                    ("host", "pm.local:8080")
+                ])
+            )
+            .is_none());
+        assert!(cors
+            .refuses(
+                &Method::POST,
+                &headers(&[json, ("origin", "https://evil.example")])
+            )
+            .is_some());
+        assert!(cors
+            .refuses(
+                &Method::DELETE,
+                &headers(&[("origin", "https://evil.example")])
+            )
+            .is_some());
+        // Forms post urlencoded bodies
+        assert!(cors
+            .refuses(
+                &Method::POST,
+                &headers(&[("content-type", "application/x-www-form-urlencoded")])
+            )
+            .is_some());
+        assert!(cors.refuses(&Method::POST, &headers(&[])).is_some());
+        assert!(cors.refuses(&Method::DELETE, &headers(&[])).is_none());
+
+        let exposure = ExposureConfig::default();
+        assert_eq!(exposure.base_asset("WSTETH"), Some("ETH"));
+        assert_eq!(exposure.base_asset("cbbtc"), Some("BTC"));
+        assert_eq!(exposure.base_asset("PEPE"), None);
+        let config: Config = serde_yaml::from_str("exposure:\n  base_assets:\n    sfrxETH: ETH\n")?;
+        assert_eq!(config.exposure.base_asset("sfrxeth"), Some("ETH"));
+        assert_eq!(config.exposure.base_asset("weth"), None);
+
+        let config: Config = serde_yaml::from_str("gas:\n  max_cost_pct: 0.5\n")?;
+        assert_eq!(config.gas.max_cost_pct, 0.5);
+        assert_eq!(config.gas.max_fee_gwei, 200.0);
+
+        let config: Config =
+            serde_yaml::from_str("swaps:\n  dex_filters:\n    8453:\n      deny: [aerodrome]\n")?;
+        let filter = &config.swaps.dex_filters[&8453];
+        assert!(!filter.allows("Aerodrome"));
+        assert!(filter.allows("uniswap_v3"));
+        let allow_only = DexFilter {
+            allow: vec!["uniswap_v3".to_string()],
+            deny: vec![],
+        };
+        assert!(allow_only.allows("uniswap_v3"));
+        assert!(!allow_only.allows("aerodrome"));
+
+        let config: Config = serde_yaml::from_str(
+            "wallets:\n  '0x00000000000000000000000000000000000000aa':\n    kind: keystore\n    path: keys/hot.json\n    password_env: HOT_WALLET_PASSWORD\n  '0x00000000000000000000000000000000000000bb':\n    kind: read_only\n",
+        )?;
+        assert!(matches!(
+            &config.wallets["0x00000000000000000000000000000000000000aa"],
+            SignerConfig::Keystore { password_env, .. } if password_env == "HOT_WALLET_PASSWORD"
+        ));
+        assert!(matches!(
+            config.wallets["0x00000000000000000000000000000000000000bb"],
+            SignerConfig::ReadOnly
+        ));
+        Ok(())
+    }
+}
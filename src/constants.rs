@@ -1,17 +1,35 @@
+use serde::{Deserialize, Serialize};
 use strum_macros::{Display, EnumIter, EnumString};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash, EnumString, Display, EnumIter)]
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, EnumString, Display, EnumIter, Serialize, Deserialize,
+)]
 pub enum Interval {
     #[strum(serialize = "1m")]
+    #[serde(rename = "1m")]
     Min1,
     #[strum(serialize = "3m")]
+    #[serde(rename = "3m")]
     Min3,
     #[strum(serialize = "5m")]
+    #[serde(rename = "5m")]
     Min5,
     #[strum(serialize = "15m")]
+    #[serde(rename = "15m")]
     Min15,
     #[strum(serialize = "30m")]
+    #[serde(rename = "30m")]
     Min30,
-    // #[strum(serialize = "1h")]
-    // Hour1,
+    #[strum(serialize = "1h")]
+    #[serde(rename = "1h")]
+    Hour1,
+    #[strum(serialize = "4h")]
+    #[serde(rename = "4h")]
+    Hour4,
+    #[strum(serialize = "1d")]
+    #[serde(rename = "1d")]
+    Day1,
+    #[strum(serialize = "1w")]
+    #[serde(rename = "1w")]
+    Week1,
 }
@@ -0,0 +1,335 @@
+use super::prompt::{PromptContext, PromptTemplates, REQUEST_TEMPLATE, SYSTEM_TEMPLATE};
+use super::{rejection, StrategyEngine, StrategyInput};
+use crate::agent::parse::{parse_strategy, repair_prompt};
+use crate::agent::{Agent, Message, ReplySink, Strategy};
+use anyhow::Result;
+use async_trait::async_trait;
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+/// Strategies from a chat agent of any provider, so no Othentic node is needed. Replies
+/// are held to the shape of [`Strategy`] by structured outputs where the agent has them,
+/// and by the schema in the prompt otherwise. Prompts are rendered from `templates`,
+/// the request from `template` when set. Replies are relayed to `sink`, when set, as
+/// they are written.
+pub struct ChatEngine {
+    agent: Box<dyn Agent>,
+    schema: Value,
+    templates: Arc<PromptTemplates>,
+    system: String,
+    template: Option<String>,
+    sink: Option<ReplySink>,
+}
+
+impl ChatEngine {
+    pub fn new(
+        agent: Box<dyn Agent>,
+        templates: Arc<PromptTemplates>,
+        template: Option<String>,
+    ) -> Self {
+        Self {
+            agent,
+            schema: strategy_schema(),
+            templates,
+            system: SYSTEM_TEMPLATE.to_string(),
+            template,
+            sink: None,
+        }
+    }
+
+    /// Render the system prompt from `system` rather than the built-in instructions.
+    pub fn with_system_template(mut self, system: &str) -> Self {
+        self.system = system.to_string();
+        self
+    }
+
+    pub fn with_sink(mut self, sink: Option<ReplySink>) -> Self {
+        self.sink = sink;
+        self
+    }
+
+    async fn ask(&self, messages: Vec<Message>) -> Result<String> {
+        match &self.sink {
+            Some(sink) => {
+                sink.begin();
+                self.agent
+                    .chat_json_streamed(messages, "strategy", &self.schema, sink)
+                    .await
+            }
+            None => {
+                self.agent
+                    .chat_json(messages, "strategy", &self.schema)
+                    .await
+            }
+        }
+    }
+}
+
+fn nullable(kind: &str) -> Value {
+    json!({ "type": [kind, "null"] })
+}
+
+fn list(items: Value) -> Value {
+    json!({ "type": ["array", "null"], "items": items })
+}
+
+/// Strict mode wants every property required and no others allowed; optional fields
+/// are nullable instead.
+fn object(properties: Value) -> Value {
+    let required: Vec<&String> = properties
+        .as_object()
+        .map(Map::keys)
+        .into_iter()
+        .flatten()
+        .collect();
+    json!({
+        "type": "object",
+        "properties": properties,
+        "required": required,
+        "additionalProperties": false
+    })
+}
+
+/// JSON schema of [`Strategy`]. Swaps leave `dexFilter` to the configured one.
+pub fn strategy_schema() -> Value {
+    let string = || json!({ "type": "string" });
+    let order = object(json!({
+        "position": string(),
+        "token": string(),
+        "amount": string(),
+        "price": string(),
+        "side": { "type": "string", "enum": ["BUY", "SELL"] },
+        "quote": nullable("string"),
+        "sequence": nullable("integer"),
+        "id": nullable("string"),
+        "dependsOn": list(string()),
+        "confidence": nullable("number"),
+        "priority": nullable("integer"),
+        "postOnly": nullable("boolean"),
+    }));
+    let transfer = object(json!({
+        "from": string(),
+        "to": string(),
+        "asset": string(),
+        "amount": string(),
+    }));
+    let swap = object(json!({
+        "tokenIn": string(),
+        "tokenOut": string(),
+        "amount": string(),
+        "chainId": nullable("integer"),
+        "slippageBps": nullable("integer"),
+        "sequence": nullable("integer"),
+        "id": nullable("string"),
+        "dependsOn": list(string()),
+        "confidence": nullable("number"),
+        "priority": nullable("integer"),
+    }));
+    let bridge = object(json!({
+        "token": string(),
+        "tokenOut": nullable("string"),
+        "amount": string(),
+        "fromChainId": { "type": "integer" },
+        "toChainId": { "type": "integer" },
+        "sequence": nullable("integer"),
+        "id": nullable("string"),
+        "dependsOn": list(string()),
+        "confidence": nullable("number"),
+        "priority": nullable("integer"),
+    }));
+    object(json!({
+        "exchanges": object(json!({
+            "binance": object(json!({ "orders": list(order), "transfers": list(transfer) })),
+            "eisen": object(json!({ "swaps": list(swap) })),
+            "bridges": object(json!({ "transfers": list(bridge) })),
+        })),
+        "explanations": {
+            "type": "array",
+            "items": object(json!({ "title": string(), "content": string() })),
+        },
+        "rationale": nullable("string"),
+    }))
+}
+
+#[async_trait]
+impl StrategyEngine for ChatEngine {
+    async fn strategy(
+        &self,
+        input: &StrategyInput<'_>,
+        feedback: Option<&str>,
+    ) -> Result<Strategy> {
+        let schema = self.schema.to_string();
+        let context = PromptContext::new(input, &schema, feedback.map(rejection));
+        let prompt = self.templates.render(&self.system, &context)?;
+        let request = self.templates.render(
+            self.template.as_deref().unwrap_or(REQUEST_TEMPLATE),
+            &context,
+        )?;
+        let reply = self
+            .ask(vec![
+                Message::new("system", prompt.clone()),
+                Message::new("user", request.clone()),
+            ])
+            .await?;
+        println!("Strategy from the agent:\n{}", reply);
+        let error = match parse_strategy(&reply) {
+            Ok(strategy) => return Ok(strategy),
+            Err(error) => error,
+        };
+
+        // Asked once more, shown its reply and what was wrong with it
+        println!("{}, asking the agent for a repair", error);
+        let repaired = self
+            .ask(vec![
+                Message::new("system", prompt),
+                Message::new("user", request),
+                Message::new("assistant", reply),
+                Message::new("user", repair_prompt(&error)),
+            ])
+            .await?;
+        println!("Repaired strategy from the agent:\n{}", repaired);
+        Ok(parse_strategy(&repaired)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::agent::ReplyEvent;
+    use crate::config::{MarginHealthConfig, RiskConfig};
+    use crate::executor::eisen::ChainPortfolio;
+    use crate::portfolio::binance::test_account;
+    use crate::portfolio::margin::MarginHealth;
+    use crate::types::MarketPrices;
+    use crate::yields::CachedYields;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn test_strategy_schema() -> Result<()> {
+        let schema = strategy_schema();
+        let binance = &schema["properties"]["exchanges"]["properties"]["binance"];
+        assert_eq!(binance["required"], json!(["orders", "transfers"]));
+        assert_eq!(binance["additionalProperties"], json!(false));
+
+        // A reply in the schema's shape, nulls and all, is a strategy, also when fenced
+        let reply = json!({
+            "exchanges": {
+                "binance": {
+                    "orders": [{
+                        "position": "short", "token": "ETH", "amount": "0.5", "price": "",
+                        "side": "SELL", "quote": null, "sequence": null, "id": "short",
+                        "dependsOn": ["sell"], "confidence": 0.4, "priority": 1
+                    }],
+                    "transfers": null
+                },
+                "eisen": {
+                    "swaps": [{
+                        "tokenIn": "USDC", "tokenOut": "WETH", "amount": "max", "chainId": null,
+                        "slippageBps": 50, "sequence": 1, "id": "sell", "dependsOn": null,
+                        "confidence": 0.9, "priority": 2
+                    }]
+                },
+                "bridges": { "transfers": null }
+            },
+            "explanations": [{ "title": "Hedge", "content": "Funding is positive" }],
+            "rationale": "Funding pays shorts"
+        });
+        let mut strategy = parse_strategy(&format!("```json\n{}\n```", reply))?;
+        assert_eq!(strategy.rationale.as_deref(), Some("Funding pays shorts"));
+        // The short waits for the swap it depends on
+        strategy
+            .resolve_dependencies()
+            .map_err(anyhow::Error::msg)?;
+        assert_eq!(strategy.stages(), [1, 2]);
+        assert_eq!(
+            strategy.low_confidence_legs(0.5),
+            ["SELL 0.5 ETH (confidence 0.40)"]
+        );
+        let swaps = strategy.exchanges.eisen.swaps.unwrap();
+        assert_eq!(swaps[0].chain_id(), 8453);
+        assert_eq!(swaps[0].slippage_bps, Some(50));
+        assert!(strategy.exchanges.bridges.transfers.is_none());
+        Ok(())
+    }
+
+    /// Replies with `replies` in turn, keeping what it was sent.
+    struct Scripted {
+        replies: Mutex<Vec<String>>,
+        sent: Arc<Mutex<Vec<Vec<String>>>>,
+    }
+
+    #[async_trait]
+    impl Agent for Scripted {
+        fn set_prompt(&mut self, _prompt: String) -> &mut Self {
+            self
+        }
+
+        async fn chat(&self, messages: Vec<Message>) -> Result<String> {
+            let contents = messages
+                .into_iter()
+                .map(|message| message.content().to_string());
+            self.sent.lock().unwrap().push(contents.collect());
+            Ok(self.replies.lock().unwrap().remove(0))
+        }
+
+        fn prompt(&self) -> &str {
+            ""
+        }
+    }
+
+    #[tokio::test]
+    async fn test_strategy_repair() -> Result<()> {
+        let sent = Arc::new(Mutex::new(Vec::new()));
+        let agent = Box::new(Scripted {
+            replies: Mutex::new(vec![
+                r#"Sure: {"exchanges": {"binance": {"orders": null}}}"#.to_string(),
+                r#"{"exchanges": {"binance": {"orders": null, "transfers": null},
+                    "eisen": {"swaps": null}}, "explanations": [],}"#
+                    .to_string(),
+            ]),
+            sent: sent.clone(),
+        });
+        let binance = test_account("0", serde_json::json!([]));
+        let onchain = ChainPortfolio::default();
+        let prices = MarketPrices::default();
+        let (funding, positioning, indicators) = (HashMap::new(), HashMap::new(), HashMap::new());
+        let margin_health = MarginHealth::new(&binance, &MarginHealthConfig::default());
+        let yields = CachedYields::default();
+        let input = StrategyInput {
+            price_data: "prices",
+            portfolio: "portfolio",
+            yields: "yields",
+            binance: &binance,
+            onchain: &onchain,
+            prices: &prices,
+            funding: &funding,
+            positioning: &positioning,
+            indicators: &indicators,
+            margin_health: &margin_health,
+            yield_data: &yields,
+            risk: &RiskConfig::default(),
+            history: &[],
+        };
+
+        let templates = PromptTemplates::new(&Default::default())?;
+        let (sink, mut relayed) = ReplySink::channel();
+        let engine = ChatEngine::new(agent, Arc::new(templates), None).with_sink(Some(sink));
+        let strategy = engine.strategy(&input, None).await?;
+        assert!(strategy.explanations.is_empty());
+        // Both replies reach the sink, the agent not streaming them, the repair announced
+        let delta = |event: ReplyEvent| match event {
+            ReplyEvent::Delta(text) => text,
+            event => panic!("expected a delta, got {:?}", event),
+        };
+        assert!(delta(relayed.try_recv()?).starts_with("Sure:"));
+        assert_eq!(relayed.try_recv()?, ReplyEvent::Attempt(2));
+        assert!(delta(relayed.try_recv()?).contains("\"explanations\": []"));
+        let sent = sent.lock().unwrap();
+        assert_eq!(sent.len(), 2);
+        // The repair carries the reply and where it went wrong
+        assert!(sent[1][2].starts_with("Sure:"));
+        assert!(sent[1][3].contains("at exchanges: missing field `eisen`"));
+        Ok(())
+    }
+}
@@ -0,0 +1,333 @@
+use super::{StrategyEngine, StrategyInput};
+use crate::agent::{
+    BinanceExchange, BinanceOrder, BridgeExchange, EisenExchange, Exchanges, Explanation, Strategy,
+};
+use crate::config::{ExposureConfig, FundingRebalancerConfig};
+use crate::executor::binance::{futures_symbol, FuturesMarket};
+use crate::rebalance::token_price;
+use crate::risk::{base_asset, symbol_token};
+use crate::utils::price::PriceData;
+use crate::utils::rounding::round_order_quantity;
+use anyhow::Result;
+use async_trait::async_trait;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+
+/// Delta-neutral rebalancer: while an asset's funding rate is at or above the entry
+/// rate, the on-chain holdings of the asset are hedged with a USDT perp short of the
+/// same value; at or below the exit rate the short is closed. In between, the short is
+/// left as it is so the hedge does not flap around one threshold.
+pub struct FundingRebalancer {
+    config: FundingRebalancerConfig,
+    exposure: ExposureConfig,
+}
+
+impl FundingRebalancer {
+    pub fn new(config: &FundingRebalancerConfig, exposure: &ExposureConfig) -> Self {
+        Self {
+            config: config.clone(),
+            exposure: exposure.clone(),
+        }
+    }
+
+    /// Order closing the gap to the target hedge of `asset`, with why, or only why not.
+    fn rebalance(
+        &self,
+        asset: &str,
+        input: &StrategyInput<'_>,
+    ) -> (Option<BinanceOrder>, Explanation) {
+        let asset = asset.to_uppercase();
+        let symbol = futures_symbol(&asset, None);
+        let explanation = |content: String| Explanation {
+            title: format!("{} funding hedge", asset),
+            content,
+        };
+        let Some(rate) = input.funding.get(&symbol).and_then(funding_rate) else {
+            return (None, explanation(format!("No {} funding rate", symbol)));
+        };
+        let price = token_price(
+            &asset,
+            &self.exposure,
+            input.binance,
+            input.onchain,
+            input.prices,
+        );
+        let Some(price) = price.filter(|price| *price > 0.0) else {
+            return (None, explanation(format!("No {} price", asset)));
+        };
+
+        let wallet_usd: f64 = input
+            .onchain
+            .balances
+            .iter()
+            .filter(|balance| base_asset(&self.exposure, &balance.symbol) == asset)
+            .filter_map(|balance| balance.usd_value)
+            .sum();
+        // Protocol positions only report their underlying amount
+        let protocol_usd: f64 = input
+            .onchain
+            .positions
+            .iter()
+            .flat_map(|chain| &chain.protocol_details)
+            .flat_map(|protocol| &protocol.assets)
+            .filter(|asset_feed| base_asset(&self.exposure, &asset_feed.symbol) == asset)
+            .filter_map(|asset_feed| {
+                let price = token_price(
+                    &asset_feed.symbol,
+                    &self.exposure,
+                    input.binance,
+                    input.onchain,
+                    input.prices,
+                )?;
+                Some(asset_feed.underlying_amount * price)
+            })
+            .sum();
+        let held_usd = wallet_usd + protocol_usd;
+        let hedge_usd: f64 = input
+            .binance
+            .positions
+            .iter()
+            .filter(|position| {
+                matches!(
+                    FuturesMarket::of_symbol(&position.symbol),
+                    FuturesMarket::UsdMargined
+                )
+            })
+            .filter(|position| base_asset(&self.exposure, symbol_token(&position.symbol)) == asset)
+            .filter_map(|position| position.notional.to_f64())
+            .sum();
+        let (target_usd, reason) = target_hedge(&self.config, rate, held_usd, hedge_usd);
+        let gap_usd = target_usd - hedge_usd;
+        if gap_usd.abs() < self.config.min_order_usd {
+            return (
+                None,
+                explanation(format!(
+                    "{}; the ${:.2} short is within ${:.2} of the target",
+                    reason, -hedge_usd, self.config.min_order_usd
+                )),
+            );
+        }
+
+        // Toward zero, so neither side orders more than the gap
+        let quantity =
+            round_order_quantity(Decimal::from_f64(gap_usd.abs() / price).unwrap_or(Decimal::ZERO));
+        if quantity.is_zero() {
+            return (
+                None,
+                explanation(format!("{}; the gap is below one lot", reason)),
+            );
+        }
+        let side = if gap_usd > 0.0 { "BUY" } else { "SELL" };
+        let order = BinanceOrder {
+            position: "short".to_string(),
+            token: asset.clone(),
+            amount: format!("{}", quantity.normalize()),
+            price: String::new(),
+            side: side.to_string(),
+            quote: None,
+            sequence: None,
+            id: None,
+            depends_on: None,
+            confidence: None,
+            priority: None,
+            post_only: None,
+        };
+        let content = format!(
+            "{}: {} {} {} to move the short from ${:.2} to ${:.2}",
+            reason, side, order.amount, symbol, -hedge_usd, -target_usd
+        );
+        (Some(order), explanation(content))
+    }
+}
+
+/// Signed USD of the short `config` aims for at funding `rate`, given `held_usd` on-chain
+/// and the `hedge_usd` short already open, with why.
+pub fn target_hedge(
+    config: &FundingRebalancerConfig,
+    rate: f64,
+    held_usd: f64,
+    hedge_usd: f64,
+) -> (f64, String) {
+    let pct = |rate: f64| format!("{:.4}%", rate * 100.0);
+    if rate >= config.enter_funding_rate {
+        (
+            -held_usd,
+            format!(
+                "funding {} is at or above the {} entry, hedging ${:.2} held on-chain",
+                pct(rate),
+                pct(config.enter_funding_rate),
+                held_usd
+            ),
+        )
+    } else if rate <= config.exit_funding_rate {
+        (
+            0.0,
+            format!(
+                "funding {} is at or below the {} exit, closing the hedge",
+                pct(rate),
+                pct(config.exit_funding_rate)
+            ),
+        )
+    } else {
+        (
+            hedge_usd,
+            format!(
+                "funding {} is between the exit and entry, holding",
+                pct(rate)
+            ),
+        )
+    }
+}
+
+/// Funding per period the hedge is decided on: the trailing day, else the running
+/// period's estimate, else the last settled rate.
+pub fn funding_rate(price: &PriceData) -> Option<f64> {
+    price
+        .funding_history
+        .and_then(|history| history.avg_24h)
+        .or(price.predicted_funding_rate)
+        .or(price.cur_funding_rate)
+}
+
+#[async_trait]
+impl StrategyEngine for FundingRebalancer {
+    /// Rules give the same answer twice, so `feedback` is not used.
+    async fn strategy(
+        &self,
+        input: &StrategyInput<'_>,
+        _feedback: Option<&str>,
+    ) -> Result<Strategy> {
+        let mut orders = Vec::new();
+        let mut explanations = Vec::new();
+        for asset in &self.config.assets {
+            let (order, explanation) = self.rebalance(asset, input);
+            orders.extend(order);
+            explanations.push(explanation);
+        }
+        Ok(Strategy {
+            exchanges: Exchanges {
+                binance: BinanceExchange {
+                    orders: (!orders.is_empty()).then_some(orders),
+                    transfers: None,
+                },
+                eisen: EisenExchange { swaps: None },
+                bridges: BridgeExchange::default(),
+            },
+            explanations,
+            rationale: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MarginHealthConfig, RiskConfig};
+    use crate::executor::eisen::{ChainPortfolio, TokenBalance};
+    use crate::portfolio::binance::test_account;
+    use crate::portfolio::eisen::{AssetFeed, ChainDetailFeed, ProtocolDetailFeed};
+    use crate::portfolio::margin::MarginHealth;
+    use crate::types::test_prices;
+    use crate::utils::price::FundingHistory;
+    use crate::yields::CachedYields;
+    use std::collections::HashMap;
+
+    fn funding(avg_24h: f64) -> PriceData {
+        serde_json::from_value(serde_json::json!({
+            "timestamp": 0, "market_price": null, "buy_long_price": null,
+            "sell_short_price": null, "cur_funding_rate": 0.0,
+            "funding_history": FundingHistory {
+                avg_8h: None,
+                avg_24h: Some(avg_24h),
+                avg_7d: None,
+            }
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_funding_rebalancer() -> Result<()> {
+        let binance = test_account(
+            "1000",
+            serde_json::json!([{
+                "symbol": "ETHUSDT", "positionSide": "BOTH", "positionAmt": "-0.5",
+                "unrealizedProfit": "0", "notional": "-1000", "initialMargin": "0",
+                "maintMargin": "0", "updateTime": 0
+            }]),
+        );
+        let onchain = |wsteth_usd: f64| ChainPortfolio {
+            balances: vec![TokenBalance {
+                symbol: "wsteth".to_string(),
+                balance: 1.0,
+                usd_value: Some(wsteth_usd),
+            }],
+            positions: vec![ChainDetailFeed {
+                chain_id: 1,
+                protocol_details: vec![ProtocolDetailFeed {
+                    name: "Lido".to_string(),
+                    assets: vec![AssetFeed {
+                        symbol: "steth".to_string(),
+                        balance: 0.5,
+                        underlying_amount: 0.5,
+                    }],
+                }],
+            }],
+        };
+        let prices = test_prices();
+        let margin_health = MarginHealth::new(&binance, &MarginHealthConfig::default());
+        let (positioning, indicators) = (HashMap::new(), HashMap::new());
+        let yields = CachedYields::default();
+        let rebalancer = FundingRebalancer::new(
+            &FundingRebalancerConfig::default(),
+            &ExposureConfig::default(),
+        );
+        let strategy = async |funding: &HashMap<String, PriceData>, onchain: &ChainPortfolio| {
+            let input = StrategyInput {
+                price_data: "",
+                portfolio: "",
+                yields: "",
+                binance: &binance,
+                onchain,
+                prices: &prices,
+                funding,
+                positioning: &positioning,
+                indicators: &indicators,
+                margin_health: &margin_health,
+                yield_data: &yields,
+                risk: &RiskConfig::default(),
+                history: &[],
+            };
+            rebalancer.strategy(&input, None).await.unwrap()
+        };
+        let orders = |strategy: &Strategy| -> Vec<(String, String)> {
+            let orders = strategy.exchanges.binance.orders.iter().flatten();
+            orders
+                .map(|order| (order.side.clone(), order.amount.clone()))
+                .collect()
+        };
+
+        // $3000 of wstETH and $1000 of stETH staked against a $1000 short: sell $3000
+        // more at $2000
+        let paying = HashMap::from([("ETHUSDT".to_string(), funding(0.0003))]);
+        let sell = strategy(&paying, &onchain(3000.0)).await;
+        assert_eq!(orders(&sell), vec![("SELL".to_string(), "1.5".to_string())]);
+        // No BTC funding was fetched
+        assert_eq!(sell.explanations.len(), 2);
+        assert!(sell.explanations[1].content.contains("No BTCUSDT funding"));
+
+        let holding = HashMap::from([("ETHUSDT".to_string(), funding(0.00005))]);
+        assert!(orders(&strategy(&holding, &onchain(3000.0)).await).is_empty());
+
+        let negative = HashMap::from([("ETHUSDT".to_string(), funding(-0.0001))]);
+        assert_eq!(
+            orders(&strategy(&negative, &onchain(3000.0)).await),
+            vec![("BUY".to_string(), "0.5".to_string())]
+        );
+        // A $3001 gap is 1.5005 ETH, half a lot over 1.500: the short never grows past it
+        assert_eq!(
+            orders(&strategy(&paying, &onchain(3001.0)).await),
+            vec![("SELL".to_string(), "1.5".to_string())]
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,343 @@
+use super::{StrategyEngine, StrategyInput};
+use crate::agent::attestation::AttestationError;
+use crate::agent::parse::StrategyParseError;
+use crate::agent::{
+    BinanceExchange, BridgeExchange, EisenExchange, Exchanges, Explanation, Strategy,
+};
+use crate::config::{CircuitBreakerConfig, LlmConfig};
+use anyhow::Result;
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+#[error("Agent did not answer within {0:?}")]
+pub struct AgentTimeout(pub Duration);
+
+// Errors of an agent that answered; asking it again gets the same kind of answer
+fn is_retryable(err: &anyhow::Error) -> bool {
+    !(err.is::<StrategyParseError>() || err.is::<AttestationError>())
+}
+
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+    // Set while the one ask let through after the cooldown is out
+    probing: bool,
+}
+
+/// Failures in a row of one provider, opening the circuit at the threshold.
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        Self {
+            failure_threshold: config.failure_threshold.max(1),
+            cooldown: Duration::from_secs(config.cooldown_secs),
+            state: Mutex::new(BreakerState::default()),
+        }
+    }
+
+    /// Permit for an ask at `now`: always while closed, and once the cooldown is over
+    /// for a single probe. A permit dropped without its outcome recorded, such as when
+    /// the ask is cancelled, lets the next probe out.
+    pub fn allow(&self, now: Instant) -> Option<BreakerPermit<'_>> {
+        let mut state = self.lock();
+        match state.open_until {
+            None => {}
+            Some(until) if now >= until && !state.probing => state.probing = true,
+            Some(_) => return None,
+        }
+        Some(BreakerPermit { breaker: self })
+    }
+
+    fn record(&self, success: bool, now: Instant) {
+        let mut state = self.lock();
+        state.probing = false;
+        if success {
+            state.failures = 0;
+            state.open_until = None;
+            return;
+        }
+        state.failures += 1;
+        // A failed probe opens the circuit again straight away
+        if state.failures >= self.failure_threshold || state.open_until.is_some() {
+            state.open_until = Some(now + self.cooldown);
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.lock().open_until.is_some()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BreakerState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// One ask let through by a [`CircuitBreaker`].
+pub struct BreakerPermit<'a> {
+    breaker: &'a CircuitBreaker,
+}
+
+impl BreakerPermit<'_> {
+    /// Count the ask's outcome towards opening or closing the circuit.
+    pub fn record(self, success: bool, now: Instant) {
+        self.breaker.record(success, now);
+    }
+}
+
+impl Drop for BreakerPermit<'_> {
+    fn drop(&mut self) {
+        self.breaker.lock().probing = false;
+    }
+}
+
+/// Circuit breaker of each provider, kept across requests.
+#[derive(Debug)]
+pub struct CircuitBreakers {
+    config: CircuitBreakerConfig,
+    breakers: Mutex<HashMap<String, Arc<CircuitBreaker>>>,
+}
+
+impl CircuitBreakers {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        Self {
+            config: config.clone(),
+            breakers: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn get(&self, provider: &str) -> Arc<CircuitBreaker> {
+        self.breakers
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .entry(provider.to_lowercase())
+            .or_insert_with(|| Arc::new(CircuitBreaker::new(&self.config)))
+            .clone()
+    }
+}
+
+/// Strategy without legs, explaining why.
+pub struct NoOpEngine {
+    reason: String,
+}
+
+impl NoOpEngine {
+    pub fn new(reason: String) -> Self {
+        Self { reason }
+    }
+}
+
+#[async_trait]
+impl StrategyEngine for NoOpEngine {
+    async fn strategy(
+        &self,
+        _input: &StrategyInput<'_>,
+        _feedback: Option<&str>,
+    ) -> Result<Strategy> {
+        Ok(Strategy {
+            exchanges: Exchanges {
+                binance: BinanceExchange {
+                    orders: None,
+                    transfers: None,
+                },
+                eisen: EisenExchange { swaps: None },
+                bridges: BridgeExchange::default(),
+            },
+            explanations: vec![Explanation {
+                title: "No changes".to_string(),
+                content: self.reason.clone(),
+            }],
+            rationale: None,
+        })
+    }
+}
+
+/// An agent-backed engine asked with a timeout and bounded retries, whose `fallback`
+/// answers once the agent is deemed down, so a dead endpoint never holds up a request.
+pub struct GuardedEngine {
+    provider: String,
+    engine: Box<dyn StrategyEngine>,
+    fallback: Box<dyn StrategyEngine>,
+    breaker: Arc<CircuitBreaker>,
+    timeout: Duration,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl GuardedEngine {
+    pub fn new(
+        provider: &str,
+        engine: Box<dyn StrategyEngine>,
+        fallback: Box<dyn StrategyEngine>,
+        breaker: Arc<CircuitBreaker>,
+        config: &LlmConfig,
+    ) -> Self {
+        Self {
+            provider: provider.to_string(),
+            engine,
+            fallback,
+            breaker,
+            timeout: Duration::from_secs(config.timeout_secs),
+            retries: config.retries,
+            backoff: Duration::from_millis(config.retry_backoff_ms),
+        }
+    }
+
+    async fn ask(&self, input: &StrategyInput<'_>, feedback: Option<&str>) -> Result<Strategy> {
+        match tokio::time::timeout(self.timeout, self.engine.strategy(input, feedback)).await {
+            Ok(strategy) => strategy,
+            Err(_) => Err(AgentTimeout(self.timeout).into()),
+        }
+    }
+}
+
+#[async_trait]
+impl StrategyEngine for GuardedEngine {
+    async fn strategy(
+        &self,
+        input: &StrategyInput<'_>,
+        feedback: Option<&str>,
+    ) -> Result<Strategy> {
+        let Some(permit) = self.breaker.allow(Instant::now()) else {
+            println!(
+                "The {} agent circuit is open, serving the fallback strategy",
+                self.provider
+            );
+            return self.fallback.strategy(input, feedback).await;
+        };
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match self.ask(input, feedback).await {
+                Ok(strategy) => {
+                    permit.record(true, Instant::now());
+                    return Ok(strategy);
+                }
+                // The agent is up but answered badly, which says nothing about the circuit
+                Err(err) if !is_retryable(&err) => return Err(err),
+                Err(err) if attempt <= self.retries => {
+                    println!(
+                        "Asking the {} agent failed (attempt {}): {}, retrying",
+                        self.provider, attempt, err
+                    );
+                    tokio::time::sleep(self.backoff * 2u32.saturating_pow(attempt - 1)).await;
+                }
+                Err(err) => {
+                    permit.record(false, Instant::now());
+                    println!(
+                        "The {} agent is down ({}), serving the fallback strategy",
+                        self.provider, err
+                    );
+                    return self.fallback.strategy(input, feedback).await;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MarginHealthConfig, RiskConfig};
+    use crate::executor::eisen::ChainPortfolio;
+    use crate::portfolio::binance::test_account;
+    use crate::portfolio::margin::MarginHealth;
+    use crate::types::MarketPrices;
+    use crate::yields::CachedYields;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Never answers, counting the asks.
+    struct Hanging(Arc<AtomicU32>);
+
+    #[async_trait]
+    impl StrategyEngine for Hanging {
+        async fn strategy(
+            &self,
+            _input: &StrategyInput<'_>,
+            _feedback: Option<&str>,
+        ) -> Result<Strategy> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            std::future::pending().await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_guarded_engine() -> Result<()> {
+        let binance = test_account("0", serde_json::json!([]));
+        let onchain = ChainPortfolio::default();
+        let prices = MarketPrices::default();
+        let (funding, positioning, indicators) = (HashMap::new(), HashMap::new(), HashMap::new());
+        let margin_health = MarginHealth::new(&binance, &MarginHealthConfig::default());
+        let yields = CachedYields::default();
+        let input = StrategyInput {
+            price_data: "",
+            portfolio: "",
+            yields: "",
+            binance: &binance,
+            onchain: &onchain,
+            prices: &prices,
+            funding: &funding,
+            positioning: &positioning,
+            indicators: &indicators,
+            margin_health: &margin_health,
+            yield_data: &yields,
+            risk: &RiskConfig::default(),
+            history: &[],
+        };
+
+        let config = LlmConfig {
+            timeout_secs: 0,
+            retries: 1,
+            retry_backoff_ms: 0,
+            circuit_breaker: CircuitBreakerConfig {
+                failure_threshold: 2,
+                ..CircuitBreakerConfig::default()
+            },
+            ..LlmConfig::default()
+        };
+        let asks = Arc::new(AtomicU32::new(0));
+        let breaker = Arc::new(CircuitBreaker::new(&config.circuit_breaker));
+        let engine = GuardedEngine::new(
+            "othentic",
+            Box::new(Hanging(asks.clone())),
+            Box::new(NoOpEngine::new("Agent down".to_string())),
+            breaker.clone(),
+            &config,
+        );
+
+        // Timed out and retried once, then answered with no legs
+        let strategy = engine.strategy(&input, None).await?;
+        assert!(strategy.exchanges.binance.orders.is_none());
+        assert_eq!(strategy.explanations[0].content, "Agent down");
+        assert_eq!(asks.load(Ordering::SeqCst), 2);
+        assert!(!breaker.is_open());
+
+        engine.strategy(&input, None).await?;
+        assert_eq!(asks.load(Ordering::SeqCst), 4);
+        assert!(breaker.is_open());
+        // Open: the agent is not asked at all
+        engine.strategy(&input, None).await?;
+        assert_eq!(asks.load(Ordering::SeqCst), 4);
+
+        // After the cooldown one probe goes out; its success closes the circuit
+        let later = Instant::now() + Duration::from_secs(301);
+        let probe = breaker.allow(later).unwrap();
+        assert!(breaker.allow(later).is_none());
+        // A probe dropped unanswered lets another one out
+        drop(probe);
+        let probe = breaker.allow(later).unwrap();
+        probe.record(true, later);
+        assert!(!breaker.is_open());
+        Ok(())
+    }
+}
@@ -0,0 +1,125 @@
+pub mod chat;
+pub mod funding;
+pub mod guard;
+pub mod prompt;
+
+use crate::agent::memory::ExecutionMemory;
+use crate::agent::othentic::OthenticAgent;
+use crate::agent::parse::{repair_prompt, StrategyParseError};
+use crate::agent::payload::StrategyPayload;
+use crate::agent::Strategy;
+use crate::config::RiskConfig;
+use crate::executor::eisen::ChainPortfolio;
+use crate::feed::binance::Positioning;
+use crate::feed::indicators::TechnicalIndicators;
+use crate::portfolio::binance::AccountInfo;
+use crate::portfolio::margin::MarginHealth;
+use crate::types::MarketPrices;
+use crate::utils::price::PriceData;
+use crate::yields::CachedYields;
+use anyhow::Result;
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub use chat::ChatEngine;
+pub use funding::FundingRebalancer;
+pub use guard::{CircuitBreakers, GuardedEngine, NoOpEngine};
+
+/// Engine a strategy is asked from.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EngineKind {
+    /// An LLM agent, of the requested provider.
+    #[default]
+    Llm,
+    /// Hedge on-chain holdings with perp shorts while funding pays for them.
+    FundingRebalancer,
+    /// A chat agent farming yield on stablecoins, leaving the delta as it is.
+    StableYieldFarming,
+}
+
+/// Provider name of the agent served by a local Othentic node; other providers are
+/// chat agents of the [`crate::agent::AgentFactory`].
+pub const OTHENTIC_PROVIDER: &str = "othentic";
+
+/// What a strategy is made from: the market and the portfolio, both as the prompt
+/// sections the agent reads and as data.
+pub struct StrategyInput<'a> {
+    pub price_data: &'a str,
+    pub portfolio: &'a str,
+    pub yields: &'a str,
+    pub binance: &'a AccountInfo,
+    pub onchain: &'a ChainPortfolio,
+    pub prices: &'a MarketPrices,
+    /// Funding of each streamed futures symbol, by symbol; missing where it could not
+    /// be fetched.
+    pub funding: &'a HashMap<String, PriceData>,
+    /// Positioning and indicators of each streamed symbol, like funding.
+    pub positioning: &'a HashMap<String, Positioning>,
+    pub indicators: &'a HashMap<String, TechnicalIndicators>,
+    pub margin_health: &'a MarginHealth,
+    pub yield_data: &'a CachedYields,
+    /// Limits the strategy is checked against once returned.
+    pub risk: &'a RiskConfig,
+    /// The wallet's latest executions and what came of them, newest first.
+    pub history: &'a [ExecutionMemory],
+}
+
+#[async_trait]
+pub trait StrategyEngine: Send + Sync {
+    /// Strategy for `input`; `feedback` says why the previous one was rejected.
+    async fn strategy(&self, input: &StrategyInput<'_>, feedback: Option<&str>)
+        -> Result<Strategy>;
+}
+
+/// What an engine asked again is told of its rejected strategy.
+pub fn rejection(feedback: &str) -> String {
+    format!(
+        "Your previous strategy was rejected, return a corrected one. {}",
+        feedback
+    )
+}
+
+/// The Othentic agent, asked with `model`.
+pub struct LlmEngine {
+    agent: OthenticAgent,
+    model: String,
+}
+
+impl LlmEngine {
+    pub fn new(agent: OthenticAgent, model: String) -> Self {
+        Self { agent, model }
+    }
+}
+
+#[async_trait]
+impl StrategyEngine for LlmEngine {
+    async fn strategy(
+        &self,
+        input: &StrategyInput<'_>,
+        feedback: Option<&str>,
+    ) -> Result<Strategy> {
+        let feedback = feedback.map(rejection);
+        let ask = |feedback: Option<String>| async move {
+            let payload = StrategyPayload::new(input, feedback);
+            self.agent.get_strategy(&self.model, &payload).await
+        };
+        match ask(feedback.clone()).await {
+            // Asked once more with what was wrong; failing requests are not repeated
+            Err(e) => match e.downcast_ref::<StrategyParseError>() {
+                Some(error) => {
+                    println!("{}, asking the agent for a repair", error);
+                    let repair = feedback
+                        .into_iter()
+                        .chain([repair_prompt(error)])
+                        .collect::<Vec<_>>()
+                        .join("\n\n");
+                    ask(Some(repair)).await
+                }
+                None => Err(e),
+            },
+            strategy => strategy,
+        }
+    }
+}
@@ -0,0 +1,237 @@
+use super::StrategyInput;
+use crate::agent::memory::format_memories;
+use crate::agent::payload::StrategyPayload;
+use crate::config::PromptConfig;
+use anyhow::{anyhow, Context, Result};
+use minijinja::{AutoEscape, Environment};
+use serde::Serialize;
+use std::fs;
+
+/// Template of the system prompt, holding the instructions and the reply schema.
+pub const SYSTEM_TEMPLATE: &str = "system";
+/// System prompt of the stablecoin yield farming engine.
+pub const YIELD_FARMING_TEMPLATE: &str = "stable_yield_farming";
+/// Template of the request, holding the market and portfolio; requests may name
+/// another.
+pub const REQUEST_TEMPLATE: &str = "request";
+// How legs are written, included by the system prompts
+const LEGS_TEMPLATE: &str = "legs";
+
+const SYSTEM_PROMPT: &str = "You manage a delta-neutral crypto portfolio spread over \
+Binance futures and on-chain wallets. From the market data, portfolio and yields you are \
+given, return the strategy to execute now, with an explanation of each decision.
+{% include \"legs\" %}";
+
+const YIELD_FARMING_PROMPT: &str = "You farm yield on stablecoins held on-chain. From the \
+yields, portfolio and market data you are given, return the strategy moving the wallet's \
+USDC, USDT and DAI into the stablecoin pools and lending markets paying the best APR for \
+their risk, net of gas and slippage, with an explanation of each decision.
+- Only swap between stablecoins and into or out of stablecoin yield positions; leave \
+ETH and BTC holdings and Binance positions as they are, so the portfolio's delta does \
+not change.
+- Move funds only when the APR gained outweighs the cost within a week, and keep no more \
+than half of the stablecoins in one protocol.
+{% include \"legs\" %}";
+
+const LEGS_PROMPT: &str = "- Binance orders: `position` is \"long\" or \"short\", `token` the base asset (e.g. \"ETH\"), \
+`side` \"BUY\" or \"SELL\", `amount` the quantity of the token and `price` \"\" for a market \
+order, or the limit price of a GTC limit order, `postOnly` true for one that must only rest \
+on the book. `quote` is \"USDT\" when null, or \"USDC\", or \"USD\" for coin-margined perpetuals, \
+whose `amount` is a number of contracts.
+- Binance transfers move an asset between the \"spot\", \"futures\" and \"coin_futures\" wallets.
+- Swaps and bridge transfers take an amount of the input token, or \"max\" for the whole \
+balance. Swaps are on Base (chain 8453) when `chainId` is null.
+- Legs run in stages by ascending `sequence`, 0 when null; bridge transfers of a stage \
+complete before its swaps. A leg with `dependsOn`, the `id`s of other legs, runs in a \
+stage after theirs.
+- Rate each order, swap and bridge transfer with a `confidence` from 0 to 1 and a \
+`priority`, 1 for the most important leg, and give the `rationale` of the strategy as a whole.
+Leave a list null when there is nothing to do.
+
+Reply with only a JSON object matching this schema:
+{{ schema }}";
+
+const REQUEST_PROMPT: &str = "{{ price_data }}
+
+{{ portfolio }}
+
+{{ yields }}
+{%- if history %}
+
+{{ history }}
+{%- endif %}
+{%- if feedback %}
+
+{{ feedback }}
+{%- endif %}";
+
+/// What templates render from: the prompt sections as text, and the same data as
+/// values under `data`, laid out as the Othentic payload.
+#[derive(Debug, Serialize)]
+pub struct PromptContext<'a> {
+    /// JSON schema the reply must match.
+    pub schema: &'a str,
+    pub price_data: &'a str,
+    pub portfolio: &'a str,
+    pub yields: &'a str,
+    /// The wallet's recent executions; empty without any.
+    pub history: String,
+    /// Why the previous strategy was rejected, when asked again.
+    pub feedback: Option<String>,
+    pub data: StrategyPayload<'a>,
+}
+
+impl<'a> PromptContext<'a> {
+    pub fn new(input: &StrategyInput<'a>, schema: &'a str, feedback: Option<String>) -> Self {
+        Self {
+            schema,
+            price_data: input.price_data,
+            portfolio: input.portfolio,
+            yields: input.yields,
+            history: format_memories(input.history),
+            feedback: feedback.clone(),
+            data: StrategyPayload::new(input, feedback),
+        }
+    }
+}
+
+/// Named prompt templates: the built-in ones, then those of the configured directory,
+/// then those of the config, each replacing an earlier one of its name.
+pub struct PromptTemplates {
+    env: Environment<'static>,
+}
+
+impl PromptTemplates {
+    pub fn new(config: &PromptConfig) -> Result<Self> {
+        let mut templates = vec![
+            (SYSTEM_TEMPLATE.to_string(), SYSTEM_PROMPT.to_string()),
+            (
+                YIELD_FARMING_TEMPLATE.to_string(),
+                YIELD_FARMING_PROMPT.to_string(),
+            ),
+            (LEGS_TEMPLATE.to_string(), LEGS_PROMPT.to_string()),
+            (REQUEST_TEMPLATE.to_string(), REQUEST_PROMPT.to_string()),
+        ];
+        if let Some(dir) = &config.dir {
+            let entries = fs::read_dir(dir)
+                .with_context(|| format!("Failed to read prompt templates in {}", dir.display()))?;
+            for entry in entries {
+                let path = entry?.path();
+                let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                    continue;
+                };
+                if path.is_file() {
+                    templates.push((name.to_string(), fs::read_to_string(&path)?));
+                }
+            }
+        }
+        templates.extend(config.templates.clone());
+
+        let mut env = Environment::new();
+        // Prompts are plain text, whatever their file extension
+        env.set_auto_escape_callback(|_| AutoEscape::None);
+        for (name, source) in templates {
+            env.add_template_owned(name.clone(), source)
+                .map_err(|e| anyhow!("Invalid prompt template {}: {}", name, e))?;
+        }
+        Ok(Self { env })
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.env.get_template(name).is_ok()
+    }
+
+    pub fn render(&self, name: &str, context: &PromptContext<'_>) -> Result<String> {
+        let template = self
+            .env
+            .get_template(name)
+            .map_err(|_| anyhow!("No prompt template {}", name))?;
+        template
+            .render(context)
+            .map_err(|e| anyhow!("Failed to render prompt template {}: {}", name, e))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{MarginHealthConfig, RiskConfig};
+    use crate::executor::eisen::ChainPortfolio;
+    use crate::portfolio::binance::test_account;
+    use crate::portfolio::margin::MarginHealth;
+    use crate::types::test_prices;
+    use crate::yields::CachedYields;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_prompt_templates() -> Result<()> {
+        let binance = test_account("0", serde_json::json!([]));
+        let onchain = ChainPortfolio::default();
+        let prices = test_prices();
+        let (funding, positioning, indicators) = (HashMap::new(), HashMap::new(), HashMap::new());
+        let margin_health = MarginHealth::new(&binance, &MarginHealthConfig::default());
+        let yields = CachedYields::default();
+        let risk = RiskConfig {
+            max_leverage: Some(3.0),
+            ..RiskConfig::default()
+        };
+        let input = StrategyInput {
+            price_data: "Prices",
+            portfolio: "Portfolio",
+            yields: "Yields",
+            binance: &binance,
+            onchain: &onchain,
+            prices: &prices,
+            funding: &funding,
+            positioning: &positioning,
+            indicators: &indicators,
+            margin_health: &margin_health,
+            yield_data: &yields,
+            risk: &risk,
+            history: &[],
+        };
+
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("terse.j2"),
+            "ETH at {{ data.prices.market.eth }}, leverage up to {{ data.riskLimits.maxLeverage }}",
+        )?;
+        let templates = PromptTemplates::new(&PromptConfig {
+            dir: Some(dir.path().to_path_buf()),
+            templates: HashMap::from([(
+                SYSTEM_TEMPLATE.to_string(),
+                "Match {{ schema }}".to_string(),
+            )]),
+        })?;
+
+        let context = PromptContext::new(&input, "{}", None);
+        assert_eq!(
+            templates.render(REQUEST_TEMPLATE, &context)?,
+            "Prices\n\nPortfolio\n\nYields"
+        );
+        assert_eq!(templates.render(SYSTEM_TEMPLATE, &context)?, "Match {}");
+        // Built-in prompts hold the leg rules and the schema
+        let farming = templates.render(YIELD_FARMING_TEMPLATE, &context)?;
+        assert!(farming.starts_with("You farm yield on stablecoins"));
+        assert!(farming.contains("- Binance orders: `position`"));
+        assert!(farming.ends_with("matching this schema:\n{}"));
+        assert_eq!(
+            templates.render("terse", &context)?,
+            "ETH at 2000.0, leverage up to 3.0"
+        );
+        assert!(!templates.contains("verbose"));
+
+        let context = PromptContext::new(&input, "{}", Some("Too much leverage".to_string()));
+        assert!(templates
+            .render(REQUEST_TEMPLATE, &context)?
+            .ends_with("Yields\n\nToo much leverage"));
+
+        let invalid = PromptConfig {
+            templates: HashMap::from([("broken".to_string(), "{% if %}".to_string())]),
+            ..PromptConfig::default()
+        };
+        assert!(PromptTemplates::new(&invalid).is_err());
+        Ok(())
+    }
+}
@@ -1,3 +1,4 @@
+use crate::executor::error::QuoteError;
 use axum::{
     http::StatusCode,
     response::{IntoResponse, Response},
@@ -5,18 +6,92 @@ use axum::{
 };
 use serde::Serialize;
 use std::fmt;
+use thiserror::Error;
+
+/// Why a strategy could not be produced or executed, by where it went wrong.
+#[derive(Debug, Error)]
+pub enum ExecutionError {
+    /// The strategy engine failed or returned no usable strategy.
+    #[error("{0}")]
+    Agent(String),
+    /// The strategy breaks the validation rules.
+    #[error("{0}")]
+    Validation(String),
+    /// A leg cannot run as written: a bad amount, tolerance, token or address.
+    #[error("{0}")]
+    InvalidLeg(String),
+    /// Binance rejected a request or could not be reached.
+    #[error("{0}")]
+    Binance(String),
+    /// An RPC call or transaction failed.
+    #[error("{0}")]
+    Onchain(String),
+    #[error(transparent)]
+    Quote(#[from] QuoteError),
+    /// A chain the strategy needs has no provider or metadata configured.
+    #[error("{0}")]
+    Config(String),
+}
+
+impl ExecutionError {
+    /// Machine-readable `code` of error responses.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ExecutionError::Agent(_) => "agent_error",
+            ExecutionError::Validation(_) => "validation_error",
+            ExecutionError::InvalidLeg(_) => "invalid_leg",
+            ExecutionError::Binance(_) => "binance_error",
+            ExecutionError::Onchain(_) => "onchain_error",
+            ExecutionError::Quote(_) => "quote_error",
+            ExecutionError::Config(_) => "config_error",
+        }
+    }
+
+    /// Upstream failures are 502s; strategies that cannot run as written are 422s.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ExecutionError::Agent(_) | ExecutionError::Binance(_) | ExecutionError::Onchain(_) => {
+                StatusCode::BAD_GATEWAY
+            }
+            ExecutionError::Validation(_)
+            | ExecutionError::InvalidLeg(_)
+            | ExecutionError::Quote(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ExecutionError::Config(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum AppError {
-    BadRequest(String),
+    /// The request is malformed; `field` names the parameter at fault, when one is.
+    BadRequest {
+        message: String,
+        field: Option<String>,
+    },
     InternalError(String),
     NotFound(String),
+    /// The request lacks valid credentials.
+    Unauthorized(String),
+    /// The request is refused, whatever it asks for.
+    Forbidden(String),
+    ServiceUnavailable(String),
+    Execution(ExecutionError),
 }
 
 impl AppError {
     pub fn bad_request(message: String) -> Self {
         println!("Bad request: {}", message);
-        AppError::BadRequest(message)
+        AppError::BadRequest {
+            message,
+            field: None,
+        }
+    }
+    pub fn invalid_field(field: &str, message: String) -> Self {
+        println!("Bad request ({}): {}", field, message);
+        AppError::BadRequest {
+            message,
+            field: Some(field.to_string()),
+        }
     }
     pub fn internal_error(message: String) -> Self {
         println!("Internal error: {}", message);
@@ -26,36 +101,77 @@ impl AppError {
         println!("Not found: {}", message);
         AppError::NotFound(message)
     }
+    pub fn unauthorized(message: String) -> Self {
+        println!("Unauthorized: {}", message);
+        AppError::Unauthorized(message)
+    }
+    pub fn forbidden(message: String) -> Self {
+        println!("Forbidden: {}", message);
+        AppError::Forbidden(message)
+    }
+    pub fn service_unavailable(message: String) -> Self {
+        println!("Service unavailable: {}", message);
+        AppError::ServiceUnavailable(message)
+    }
+}
+
+impl From<ExecutionError> for AppError {
+    fn from(error: ExecutionError) -> Self {
+        AppError::Execution(error)
+    }
 }
 
 #[derive(Debug, Serialize)]
 struct ErrorResponse {
     status: String,
+    code: String,
     message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    field: Option<String>,
 }
 
 impl fmt::Display for AppError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let message = match self {
-            AppError::BadRequest(msg) => msg,
-            AppError::InternalError(msg) => msg,
-            AppError::NotFound(msg) => msg,
-        };
-        write!(f, "{}", message)
+        match self {
+            AppError::BadRequest { message, .. } => write!(f, "{}", message),
+            AppError::InternalError(msg) => write!(f, "{}", msg),
+            AppError::NotFound(msg) => write!(f, "{}", msg),
+            AppError::Unauthorized(msg) => write!(f, "{}", msg),
+            AppError::Forbidden(msg) => write!(f, "{}", msg),
+            AppError::ServiceUnavailable(msg) => write!(f, "{}", msg),
+            AppError::Execution(error) => write!(f, "{}", error),
+        }
     }
 }
 
 impl IntoResponse for AppError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            AppError::BadRequest(msg) => (StatusCode::BAD_REQUEST, msg),
-            AppError::InternalError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
-            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
+        let mut field = None;
+        let (status, code, message) = match self {
+            AppError::BadRequest {
+                message,
+                field: bad_field,
+            } => {
+                field = bad_field;
+                (StatusCode::BAD_REQUEST, "bad_request", message)
+            }
+            AppError::InternalError(msg) => {
+                (StatusCode::INTERNAL_SERVER_ERROR, "internal_error", msg)
+            }
+            AppError::NotFound(msg) => (StatusCode::NOT_FOUND, "not_found", msg),
+            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, "unauthorized", msg),
+            AppError::Forbidden(msg) => (StatusCode::FORBIDDEN, "forbidden", msg),
+            AppError::ServiceUnavailable(msg) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "service_unavailable", msg)
+            }
+            AppError::Execution(error) => (error.status(), error.code(), error.to_string()),
         };
 
         let body = Json(ErrorResponse {
             status: "error".to_string(),
+            code: code.to_string(),
             message,
+            field,
         });
 
         (status, body).into_response()
@@ -63,3 +179,43 @@ impl IntoResponse for AppError {
 }
 
 impl std::error::Error for AppError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_execution_error_response() {
+        let response =
+            AppError::from(ExecutionError::Binance("HTTP 503".to_string())).into_response();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+
+        let error = ExecutionError::from(QuoteError::NoRoute {
+            from: "WETH".to_string(),
+            to: "XYZ".to_string(),
+        });
+        assert_eq!(error.code(), "quote_error");
+        assert_eq!(error.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(error.to_string(), "no swap path from WETH to XYZ");
+    }
+
+    #[tokio::test]
+    async fn test_invalid_field_response() {
+        let response = AppError::invalid_field("days", "days must be at most 3650".to_string())
+            .into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["code"], "bad_request");
+        assert_eq!(body["field"], "days");
+
+        let response = AppError::bad_request("Plan expired".to_string()).into_response();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert!(body.get("field").is_none());
+    }
+}
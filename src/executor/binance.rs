@@ -1,12 +1,16 @@
+use crate::audit::{self, AuditKind};
 use crate::executor::error::ExchangeError;
 use crate::utils::sign::BinanceKey;
 use anyhow::Result;
+use async_trait::async_trait;
 use positions::Asset;
 use reqwest::header::HeaderValue;
 use reqwest::Client;
 use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
 
 /// Position side.
 #[derive(Debug, Clone, Copy, Serialize, Deserialize)]
@@ -67,7 +71,7 @@ pub struct OrderState {
 }
 
 /// Status.
-#[derive(Debug, Clone, Copy, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "SCREAMING_SNAKE_CASE")]
 pub enum Status {
     /// New.
@@ -201,10 +205,23 @@ pub struct PlaceOrder {
     /// Price protect.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub price_protect: Option<String>,
+    /// Response type; set when the order is sent.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_order_resp_type: Option<OrderRespType>,
+}
+
+/// How much of a new order Binance answers with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum OrderRespType {
+    /// Only the order id; fills read as zero.
+    Ack,
+    /// The order as it stands once matched, fills included.
+    Result,
 }
 
 /// Usd-Margin Futures Order.
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct UsdMarginFuturesOrder {
     /// Client id.
@@ -268,7 +285,9 @@ pub enum TimeInForce {
     Gtx,
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn place_binance_order(
+    client: &Client,
     base_url: &str,
     key: &BinanceKey,
     symbol: &str,
@@ -277,7 +296,297 @@ pub async fn place_binance_order(
     price: Option<Decimal>,
     stop_price: Option<Decimal>,
 ) -> Result<UsdMarginFuturesOrder> {
-    // Create an empty parameter map to sign
+    let place_order_params = build_place_order(symbol, side, quantity, price, stop_price, None);
+    submit_place_order(client, base_url, key, place_order_params).await
+}
+
+/// Futures market a symbol trades on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FuturesMarket {
+    /// USDⓈ-M futures (BTCUSDT, ETHUSDC), served under `/fapi`.
+    UsdMargined,
+    /// COIN-M futures (BTCUSD_PERP, ETHUSD_240628), served under `/dapi`.
+    CoinMargined,
+}
+
+impl FuturesMarket {
+    /// COIN-M symbols are USD pairs with a `_PERP` or `_YYMMDD` delivery suffix;
+    /// USDⓈ-M delivery contracts (BTCUSDT_240628) pair with a stablecoin instead.
+    pub fn of_symbol(symbol: &str) -> Self {
+        let coin_margined = symbol.rsplit_once('_').is_some_and(|(pair, suffix)| {
+            let delivery = suffix.len() == 6 && suffix.bytes().all(|b| b.is_ascii_digit());
+            pair.ends_with("USD") && (suffix == "PERP" || delivery)
+        });
+        if coin_margined {
+            Self::CoinMargined
+        } else {
+            Self::UsdMargined
+        }
+    }
+
+    /// Url of `endpoint` (e.g. "order") on this market, given the USDⓈ-M base url.
+    ///
+    /// Mainnet serves COIN-M on its own host; the testnet serves both markets.
+    pub fn endpoint_url(&self, base_url: &str, endpoint: &str) -> String {
+        match self {
+            Self::UsdMargined => format!("{}/fapi/v1/{}", base_url, endpoint),
+            Self::CoinMargined => format!(
+                "{}/dapi/v1/{}",
+                base_url.replace("://fapi.", "://dapi."),
+                endpoint
+            ),
+        }
+    }
+}
+
+/// Size and quantity step of a COIN-M contract.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ContractSpec {
+    /// USD value of one contract.
+    pub contract_size: Decimal,
+    /// Step order quantities, in contracts, are a multiple of.
+    pub step_size: Decimal,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExchangeInfo {
+    symbols: Vec<ExchangeSymbol>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ExchangeSymbol {
+    symbol: String,
+    contract_size: Option<Decimal>,
+    #[serde(default)]
+    filters: Vec<serde_json::Value>,
+}
+
+/// COIN-M contract specs by symbol, from the market's exchangeInfo and read
+/// synchronously by the sizing and risk checks.
+#[derive(Debug, Clone, Default)]
+pub struct ContractSpecs {
+    specs: Arc<RwLock<HashMap<String, ContractSpec>>>,
+}
+
+impl ContractSpecs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<ContractSpec> {
+        self.specs
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .get(symbol)
+            .copied()
+    }
+
+    pub fn insert(&self, symbol: impl Into<String>, spec: ContractSpec) {
+        self.specs
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .insert(symbol.into(), spec);
+    }
+
+    /// Load the specs of every COIN-M symbol, returning how many there are.
+    pub async fn refresh(&self, client: &Client, base_url: &str) -> Result<usize> {
+        let url = FuturesMarket::CoinMargined.endpoint_url(base_url, "exchangeInfo");
+        let info: ExchangeInfo = client
+            .get(&url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let mut loaded = 0;
+        for symbol in info.symbols {
+            let Some(contract_size) = symbol.contract_size else {
+                continue;
+            };
+            let step_size = symbol
+                .filters
+                .iter()
+                .find(|filter| filter["filterType"] == "LOT_SIZE")
+                .and_then(|filter| filter["stepSize"].as_str()?.parse().ok())
+                .unwrap_or(Decimal::ONE);
+            self.insert(
+                symbol.symbol,
+                ContractSpec {
+                    contract_size,
+                    step_size,
+                },
+            );
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+}
+
+/// Futures symbol for `token` quoted in `quote` (USDT when unset).
+pub fn futures_symbol(token: &str, quote: Option<&str>) -> String {
+    let token = token.to_uppercase();
+    match quote.map(|q| q.trim().to_uppercase()).as_deref() {
+        None | Some("") | Some("USDT") => format!("{}USDT", token),
+        Some("USD") => format!("{}USD_PERP", token),
+        Some(quote) => format!("{}{}", token, quote),
+    }
+}
+
+/// Maximum number of placement attempts for an idempotent order.
+const MAX_PLACE_ATTEMPTS: usize = 3;
+
+/// Binance error code for a `newClientOrderId` that is already in use.
+const DUPLICATE_CLIENT_ORDER_ID: i64 = -4116;
+
+/// Binance error code returned when querying an order that does not exist.
+const ORDER_DOES_NOT_EXIST: i64 = -2013;
+
+/// Error body returned by the Binance API.
+#[derive(Debug, Deserialize, thiserror::Error)]
+#[error("Binance API error {code}: {msg}")]
+struct BinanceApiError {
+    code: i64,
+    msg: String,
+}
+
+/// Deterministic `newClientOrderId` for leg `leg_index` of job `job_id`.
+///
+/// Binance accepts at most 36 characters from `[.A-Z:/a-z0-9_-]`, so the job id is hashed.
+pub fn client_order_id(job_id: &str, leg_index: usize) -> String {
+    let digest = hex::encode(Sha256::digest(job_id.as_bytes()));
+    format!("cpm-{}-{}", &digest[..24], leg_index)
+}
+
+/// Place an order under a deterministic client order id, never submitting it twice.
+///
+/// Before every attempt the order is looked up by `client_order_id`; if Binance already
+/// has it (e.g. an earlier attempt timed out after reaching the exchange), the existing
+/// order is returned instead of placing a new one.
+pub async fn place_binance_order_idempotent(
+    client: &Client,
+    base_url: &str,
+    key: &BinanceKey,
+    order: &PlaceOrder,
+    client_order_id: &str,
+) -> Result<UsdMarginFuturesOrder> {
+    let symbol = order.symbol.as_str();
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        if let Some(existing) =
+            get_binance_order(client, base_url, key, symbol, client_order_id).await?
+        {
+            println!(
+                "Order {} already exists on Binance, not placing it again",
+                client_order_id
+            );
+            return Ok(existing);
+        }
+
+        let params = PlaceOrder {
+            new_client_order_id: Some(client_order_id.to_string()),
+            ..order.clone()
+        };
+        match submit_place_order(client, base_url, key, params).await {
+            Ok(order) => return Ok(order),
+            Err(err) if attempt < MAX_PLACE_ATTEMPTS && is_retryable(&err) => {
+                println!(
+                    "Placing order {} failed (attempt {}): {}, retrying",
+                    client_order_id, attempt, err
+                );
+            }
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+/// Something that can place futures orders: Binance itself or a paper trading simulator.
+#[async_trait]
+pub trait OrderExecutor: Send + Sync {
+    /// Place `order` under `client_order_id`; placing the same id twice returns the first order.
+    async fn place_order(
+        &self,
+        order: &PlaceOrder,
+        client_order_id: &str,
+    ) -> Result<UsdMarginFuturesOrder>;
+}
+
+/// Places orders on Binance.
+pub struct LiveBinanceExecutor<'a> {
+    pub client: &'a Client,
+    pub base_url: &'a str,
+    pub key: &'a BinanceKey,
+}
+
+#[async_trait]
+impl OrderExecutor for LiveBinanceExecutor<'_> {
+    async fn place_order(
+        &self,
+        order: &PlaceOrder,
+        client_order_id: &str,
+    ) -> Result<UsdMarginFuturesOrder> {
+        place_binance_order_idempotent(self.client, self.base_url, self.key, order, client_order_id)
+            .await
+    }
+}
+
+/// Look up an order by client order id, returning `None` if Binance does not know it.
+pub async fn get_binance_order(
+    client: &Client,
+    base_url: &str,
+    key: &BinanceKey,
+    symbol: &str,
+    client_order_id: &str,
+) -> Result<Option<UsdMarginFuturesOrder>> {
+    #[derive(Serialize)]
+    #[serde(rename_all = "camelCase")]
+    struct QueryOrder {
+        symbol: String,
+        orig_client_order_id: String,
+    }
+
+    let signed_params = key
+        .sign(QueryOrder {
+            symbol: symbol.to_uppercase(),
+            orig_client_order_id: client_order_id.to_string(),
+        })
+        .map_err(|e| anyhow::anyhow!("Error signing parameters: {}", e))?;
+    let url = format!(
+        "{}?{}",
+        FuturesMarket::of_symbol(symbol).endpoint_url(base_url, "order"),
+        serde_urlencoded::to_string(signed_params)?
+    );
+
+    let response = client
+        .get(&url)
+        .header(
+            "X-MBX-APIKEY",
+            HeaderValue::from_str(key.api_key())
+                .map_err(|e| anyhow::anyhow!("Invalid API key: {}", e))?,
+        )
+        .send()
+        .await?;
+
+    if response.status().is_success() {
+        return Ok(Some(response.json().await?));
+    }
+    let err = api_error(response).await;
+    match err.downcast_ref::<BinanceApiError>() {
+        Some(api_err) if api_err.code == ORDER_DOES_NOT_EXIST => Ok(None),
+        _ => Err(err),
+    }
+}
+
+fn build_place_order(
+    symbol: &str,
+    side: OrderSide,
+    quantity: Option<Decimal>,
+    price: Option<Decimal>,
+    stop_price: Option<Decimal>,
+    new_client_order_id: Option<String>,
+) -> PlaceOrder {
     let (order_type, time_in_force, close_position) = if price.is_some() {
         (OrderType::Limit, Some(TimeInForce::Gtc), Some(false))
     } else if stop_price.is_some() {
@@ -286,7 +595,7 @@ pub async fn place_binance_order(
         (OrderType::Market, None, Some(false))
     };
 
-    let place_order_params = PlaceOrder {
+    PlaceOrder {
         symbol: symbol.to_uppercase(),
         side,
         position_side: Some(PositionSide::Both),
@@ -294,7 +603,7 @@ pub async fn place_binance_order(
         reduce_only: None,
         quantity,
         price,
-        new_client_order_id: None,
+        new_client_order_id,
         stop_price,
         close_position,
         activation_price: None,
@@ -302,38 +611,198 @@ pub async fn place_binance_order(
         time_in_force,
         working_type: None,
         price_protect: None,
-    };
+        new_order_resp_type: None,
+    }
+}
+
+// What is sent for `order`: all of it, asking for the matched result so fills are known
+fn submitted(order: PlaceOrder) -> PlaceOrder {
+    PlaceOrder {
+        new_order_resp_type: Some(OrderRespType::Result),
+        ..order
+    }
+}
+
+async fn submit_place_order(
+    client: &Client,
+    base_url: &str,
+    key: &BinanceKey,
+    place_order_params: PlaceOrder,
+) -> Result<UsdMarginFuturesOrder> {
+    let place_order_params = submitted(place_order_params);
+    let url = FuturesMarket::of_symbol(&place_order_params.symbol).endpoint_url(base_url, "order");
 
     // Sign the parameters
     let signed_params = key
         .sign(place_order_params)
         .map_err(|e| anyhow::anyhow!("Error signing parameters: {}", e))?;
 
-    // Construct the full URL with the signed query string
-    let url = format!("{}/fapi/v1/order", base_url);
     let hyper_body = serde_urlencoded::to_string(signed_params)?;
-    // Create a client and set the necessary headers
-    let client = Client::new();
+    let body = post_signed(client, &url, key, hyper_body).await?;
+    let order: UsdMarginFuturesOrder = serde_json::from_str(&body)?;
+    Ok(order)
+}
+
+/// POST a signed body changing the account, recording it and its response in the audit
+/// log, and return the response body.
+async fn post_signed(client: &Client, url: &str, key: &BinanceKey, body: String) -> Result<String> {
+    let target = format!("POST {}", url);
+    let payload_hash = audit::sha256_hex(body.as_bytes());
     let response = client
-        .post(&url)
+        .post(url)
         .header(
             "X-MBX-APIKEY",
-            HeaderValue::from_str(&key.api_key)
+            HeaderValue::from_str(key.api_key())
                 .map_err(|e| anyhow::anyhow!("Invalid API key: {}", e))?,
         )
-        .body(hyper_body)
+        .body(body)
         .send()
-        .await?;
+        .await;
+    // A request that timed out may still have reached Binance, so errors are audited too
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            audit::record(
+                AuditKind::BinanceRequest,
+                target,
+                payload_hash,
+                Err(e.to_string()),
+            );
+            return Err(e.into());
+        }
+    };
+    let status = response.status();
+    let text = match response.text().await {
+        Ok(text) => text,
+        Err(e) => {
+            let error = format!("HTTP {}, unreadable body: {}", status, e);
+            audit::record(AuditKind::BinanceRequest, target, payload_hash, Err(error));
+            return Err(e.into());
+        }
+    };
+    let outcome = if status.is_success() {
+        Ok(text.clone())
+    } else {
+        Err(format!("HTTP {}: {}", status, text))
+    };
+    audit::record(AuditKind::BinanceRequest, target, payload_hash, outcome);
+    if !status.is_success() {
+        return Err(api_error_body(status, &text));
+    }
+    Ok(text)
+}
+
+// Classify a failed response: 429 / 5xx are temporary, anything else carries an API error body
+async fn api_error(response: reqwest::Response) -> anyhow::Error {
+    let status = response.status();
+    let body = response.text().await.unwrap_or_default();
+    api_error_body(status, &body)
+}
 
-    if !response.status().is_success() {
-        return Err(anyhow::anyhow!(
-            "Failed to get order status: HTTP {}",
-            response.status()
-        ));
+fn api_error_body(status: reqwest::StatusCode, body: &str) -> anyhow::Error {
+    let detail = anyhow::anyhow!("HTTP {}: {}", status, body);
+    if status.as_u16() == 429 || status.as_u16() == 418 {
+        return ExchangeError::RateLimited(detail).into();
     }
+    if status.is_server_error() {
+        return ExchangeError::Unavailable(detail).into();
+    }
+    match serde_json::from_str::<BinanceApiError>(body) {
+        Ok(err) => err.into(),
+        Err(_) => ExchangeError::unexpected_response_type(detail.to_string()).into(),
+    }
+}
 
-    let order: UsdMarginFuturesOrder = response.json().await?;
-    Ok(order)
+// Errors after which the order may or may not have reached Binance
+fn is_retryable(err: &anyhow::Error) -> bool {
+    if let Some(err) = err.downcast_ref::<ExchangeError>() {
+        return err.is_temporary();
+    }
+    if let Some(err) = err.downcast_ref::<reqwest::Error>() {
+        return err.is_timeout() || err.is_connect() || err.is_request();
+    }
+    // The order reached Binance on an earlier attempt; the next lookup returns it
+    matches!(
+        err.downcast_ref::<BinanceApiError>(),
+        Some(api_err) if api_err.code == DUPLICATE_CLIENT_ORDER_ID
+    )
+}
+
+/// Universal transfer directions between Binance wallets.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TransferType {
+    /// Spot to USDⓈ-M futures.
+    MainUmfuture,
+    /// USDⓈ-M futures to spot.
+    UmfutureMain,
+    /// Spot to COIN-M futures.
+    MainCmfuture,
+    /// COIN-M futures to spot.
+    CmfutureMain,
+}
+
+impl TransferType {
+    /// Direction for a transfer between two wallets named as in the strategy schema.
+    pub fn from_wallets(from: &str, to: &str) -> Option<Self> {
+        match (from.to_lowercase().as_str(), to.to_lowercase().as_str()) {
+            ("spot", "futures") => Some(Self::MainUmfuture),
+            ("futures", "spot") => Some(Self::UmfutureMain),
+            ("spot", "coin_futures") => Some(Self::MainCmfuture),
+            ("coin_futures", "spot") => Some(Self::CmfutureMain),
+            _ => None,
+        }
+    }
+}
+
+/// Universal transfer request.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UniversalTransfer {
+    /// Direction.
+    #[serde(rename = "type")]
+    pub transfer_type: TransferType,
+    /// Asset.
+    pub asset: String,
+    /// Amount.
+    pub amount: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct UniversalTransferResponse {
+    tran_id: u64,
+}
+
+/// Spot (SAPI) host matching a futures REST base url; transfers live on the spot API.
+pub fn spot_base_url(futures_base_url: &str) -> &'static str {
+    if futures_base_url.contains("testnet") {
+        "https://testnet.binance.vision"
+    } else {
+        "https://api.binance.com"
+    }
+}
+
+/// Move funds between wallets via `POST /sapi/v1/asset/transfer`, returning the transfer id.
+pub async fn universal_transfer(
+    client: &Client,
+    spot_base_url: &str,
+    key: &BinanceKey,
+    transfer: UniversalTransfer,
+) -> Result<u64> {
+    let signed_params = key
+        .sign(transfer)
+        .map_err(|e| anyhow::anyhow!("Error signing parameters: {}", e))?;
+
+    let url = format!("{}/sapi/v1/asset/transfer", spot_base_url);
+    let body = post_signed(
+        client,
+        &url,
+        key,
+        serde_urlencoded::to_string(signed_params)?,
+    )
+    .await?;
+    let transfer: UniversalTransferResponse = serde_json::from_str(&body)?;
+    Ok(transfer.tran_id)
 }
 
 #[cfg(test)]
@@ -342,14 +811,125 @@ mod tests {
     use dotenv::dotenv;
     use std::env;
 
+    #[test]
+    fn test_client_order_id_is_deterministic() {
+        let id = client_order_id("job-1", 0);
+        assert_eq!(id, client_order_id("job-1", 0));
+        assert_ne!(id, client_order_id("job-1", 1));
+        assert_ne!(id, client_order_id("job-2", 0));
+        assert!(id.len() <= 36);
+        assert!(id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || ".:/_-".contains(c)));
+    }
+
+    #[test]
+    fn test_futures_symbol_and_market() {
+        assert_eq!(futures_symbol("eth", None), "ETHUSDT");
+        assert_eq!(futures_symbol("eth", Some("usdc")), "ETHUSDC");
+        assert_eq!(futures_symbol("btc", Some("USD")), "BTCUSD_PERP");
+        assert_eq!(
+            FuturesMarket::of_symbol("ETHUSDC").endpoint_url("https://fapi.binance.com", "order"),
+            "https://fapi.binance.com/fapi/v1/order"
+        );
+        assert_eq!(
+            FuturesMarket::of_symbol("BTCUSD_PERP")
+                .endpoint_url("https://fapi.binance.com", "order"),
+            "https://dapi.binance.com/dapi/v1/order"
+        );
+        assert_eq!(
+            FuturesMarket::of_symbol("BTCUSD_PERP")
+                .endpoint_url("https://testnet.binancefuture.com", "order"),
+            "https://testnet.binancefuture.com/dapi/v1/order"
+        );
+        assert_eq!(
+            FuturesMarket::of_symbol("ETHUSD_240628"),
+            FuturesMarket::CoinMargined
+        );
+        // USDⓈ-M delivery contracts and other underscores are not COIN-M
+        assert_eq!(
+            FuturesMarket::of_symbol("BTCUSDT_240628"),
+            FuturesMarket::UsdMargined
+        );
+        assert_eq!(
+            FuturesMarket::of_symbol("ETHUSD_NEXT"),
+            FuturesMarket::UsdMargined
+        );
+    }
+
+    #[test]
+    fn test_transfer_type_from_wallets() {
+        assert_eq!(
+            TransferType::from_wallets("Spot", "futures"),
+            Some(TransferType::MainUmfuture)
+        );
+        assert_eq!(
+            TransferType::from_wallets("coin_futures", "spot"),
+            Some(TransferType::CmfutureMain)
+        );
+        assert_eq!(TransferType::from_wallets("futures", "coin_futures"), None);
+        assert_eq!(
+            serde_urlencoded::to_string(UniversalTransfer {
+                transfer_type: TransferType::MainUmfuture,
+                asset: "USDT".to_string(),
+                amount: Decimal::new(1505, 1),
+            })
+            .unwrap(),
+            "type=MAIN_UMFUTURE&asset=USDT&amount=150.5"
+        );
+    }
+
+    #[test]
+    fn test_is_retryable() {
+        assert!(is_retryable(&anyhow::Error::from(
+            ExchangeError::Unavailable(anyhow::anyhow!("HTTP 503"))
+        )));
+        let api_error = |code, msg: &str| {
+            anyhow::Error::from(BinanceApiError {
+                code,
+                msg: msg.to_string(),
+            })
+        };
+        assert!(is_retryable(&api_error(
+            -4116,
+            "ClientOrderId is duplicated."
+        )));
+        assert!(!is_retryable(&api_error(-2019, "Margin is insufficient.")));
+    }
+
+    #[test]
+    fn test_submitted_order_keeps_every_param() -> Result<()> {
+        let order = PlaceOrder {
+            reduce_only: Some(true),
+            time_in_force: Some(TimeInForce::Gtx),
+            ..build_place_order(
+                "ethusdt",
+                OrderSide::Sell,
+                Some(Decimal::ONE),
+                Some(Decimal::from(2500)),
+                None,
+                Some("cpm-1".to_string()),
+            )
+        };
+        let query = serde_urlencoded::to_string(submitted(order))?;
+        for param in [
+            "reduceOnly=true",
+            "timeInForce=GTX",
+            "newClientOrderId=cpm-1",
+            "newOrderRespType=RESULT",
+        ] {
+            assert!(query.contains(param), "{} missing from {}", param, query);
+        }
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_place_binance_order() -> Result<()> {
         dotenv().unwrap();
-        let binance_key = BinanceKey {
-            api_key: env::var("BINANCE_API_KEY").expect("BINANCE_API_KEY must be set in .env"),
-            secret_key: env::var("BINANCE_API_SECRET")
-                .expect("BINANCE_SECRET_KEY must be set in .env"),
-        };
+        let binance_key = BinanceKey::hmac(
+            env::var("BINANCE_API_KEY").expect("BINANCE_API_KEY must be set in .env"),
+            env::var("BINANCE_API_SECRET").expect("BINANCE_SECRET_KEY must be set in .env"),
+        );
         let binance_base_url =
             if env::var("ENVIRONMENT").expect("BINANCE_ENV must be set in .env") == "test" {
                 "https://testnet.binancefuture.com"
@@ -359,6 +939,7 @@ mod tests {
 
         // market order
         let order = place_binance_order(
+            &Client::new(),
             binance_base_url,
             &binance_key,
             "ETH",
@@ -0,0 +1,494 @@
+use crate::config::{BridgeConfig, SimulationConfig};
+use crate::executor::eisen::{
+    balance_of, ensure_allowance, is_native_token, send_and_confirm, ApprovalPolicy, ChainData,
+};
+use crate::executor::error::QuoteError;
+use crate::executor::gas;
+use crate::executor::nonce::TxSender;
+use crate::executor::simulation::{self, ForkApproval};
+use crate::utils::rounding::{bps_to_fraction, f64_to_units, units_to_f64};
+use alloy::network::TransactionBuilder;
+use alloy::primitives::{Address, Bytes, FixedBytes, U256};
+use alloy::rpc::types::TransactionRequest;
+use anyhow::{anyhow, Result};
+use reqwest::{Client, StatusCode};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// A LI.FI route for a cross-chain transfer, with the transaction that starts it.
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeQuote {
+    /// Bridge the route goes through, e.g. "across" or "stargate".
+    pub tool: String,
+    pub estimate: BridgeEstimate,
+    pub transaction_request: BridgeTransactionRequest,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeEstimate {
+    /// Spender the source token has to be approved for.
+    pub approval_address: Address,
+    pub to_amount: String,
+    pub to_amount_min: String,
+    #[serde(rename = "fromAmountUSD", default)]
+    pub from_amount_usd: Option<String>,
+    #[serde(default)]
+    pub gas_costs: Vec<BridgeGasCost>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct BridgeGasCost {
+    /// Native token the gas is paid in.
+    pub token: GasToken,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct GasToken {
+    pub decimals: u8,
+    #[serde(rename = "priceUSD")]
+    pub price_usd: String,
+}
+
+impl BridgeQuote {
+    /// Fail unless every contract the quote has the wallet approve or call is allowed.
+    fn check_contracts(&self, allowed: &[Address]) -> Result<()> {
+        for (role, contract) in [
+            ("approval address", self.estimate.approval_address),
+            ("transaction target", self.transaction_request.to),
+        ] {
+            if !allowed.contains(&contract) {
+                return Err(anyhow!(
+                    "Bridge quote via {} has an unknown {} {}",
+                    self.tool,
+                    role,
+                    contract
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// Value of the transfer in wei of the native token, from the quote's USD estimates
+    /// when the token is not native.
+    fn native_value(&self, amount_in: U256, native_token: bool) -> Result<U256> {
+        if native_token {
+            return Ok(amount_in);
+        }
+        let unpriced = || anyhow!("Bridge quote via {} carries no USD estimate", self.tool);
+        let from_usd: f64 = self
+            .estimate
+            .from_amount_usd
+            .as_deref()
+            .ok_or_else(unpriced)?
+            .parse()?;
+        let native = &self.estimate.gas_costs.first().ok_or_else(unpriced)?.token;
+        let native_usd: f64 = native.price_usd.parse()?;
+        if native_usd <= 0.0 {
+            return Err(unpriced());
+        }
+        f64_to_units(from_usd / native_usd, native.decimals)
+    }
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeTransactionRequest {
+    pub to: Address,
+    pub data: Bytes,
+    pub value: U256,
+    pub gas_limit: Option<U256>,
+}
+
+impl BridgeTransactionRequest {
+    fn request(&self) -> TransactionRequest {
+        let tx = TransactionRequest::default()
+            .with_to(self.to)
+            .with_value(self.value)
+            .with_input(self.data.clone());
+        match self.gas_limit {
+            Some(gas_limit) if !gas_limit.is_zero() => tx.with_gas_limit(gas_limit.saturating_to()),
+            _ => tx,
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct StatusResponse {
+    status: String,
+    substatus: Option<String>,
+    receiving: Option<ReceivingInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ReceivingInfo {
+    amount: Option<String>,
+    tx_hash: Option<String>,
+}
+
+/// Where a bridge transfer stands on the destination chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BridgeStatus {
+    /// Arrived as the requested token.
+    Completed,
+    /// Arrived, but as a different token than requested.
+    Partial,
+    /// Returned to the wallet on the source chain.
+    Refunded,
+    Failed,
+    /// Not arrived yet, or not waited for.
+    Pending,
+}
+
+impl BridgeStatus {
+    /// Status for a LI.FI status/substatus pair, `None` while the transfer is in flight.
+    fn of(status: &str, substatus: Option<&str>) -> Option<Self> {
+        match (status, substatus) {
+            ("DONE", Some("PARTIAL")) => Some(Self::Partial),
+            ("DONE", Some("REFUNDED")) => Some(Self::Refunded),
+            ("DONE", _) => Some(Self::Completed),
+            ("FAILED", _) | ("INVALID", _) => Some(Self::Failed),
+            _ => None,
+        }
+    }
+}
+
+/// Outcome of a bridge transfer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeReceipt {
+    pub tool: String,
+    /// Transaction on the source chain.
+    pub tx_hash: FixedBytes<32>,
+    pub gas_used: u64,
+    #[serde(deserialize_with = "crate::executor::eisen::deserialize_gas_price")]
+    pub effective_gas_price: u128,
+    pub expected_amount_out: f64,
+    pub min_amount_out: f64,
+    pub delivery: BridgeStatus,
+    /// Amount received on the destination chain, once it has arrived.
+    pub amount_out: Option<f64>,
+    pub receiving_tx_hash: Option<String>,
+}
+
+/// LI.FI addresses native tokens with the zero address.
+fn lifi_token_address(token_addr: &str) -> String {
+    if is_native_token(token_addr) {
+        Address::ZERO.to_string()
+    } else {
+        token_addr.to_string()
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+pub async fn get_bridge_quote(
+    client: &Client,
+    base_url: &str,
+    from_chain: u64,
+    to_chain: u64,
+    from_token: &str,
+    to_token: &str,
+    amount: U256,
+    from: Address,
+    slippage_bps: u16,
+) -> Result<BridgeQuote> {
+    let response = client
+        .get(format!("{}/quote", base_url))
+        .query(&[
+            ("fromChain", from_chain.to_string()),
+            ("toChain", to_chain.to_string()),
+            ("fromToken", lifi_token_address(from_token)),
+            ("toToken", lifi_token_address(to_token)),
+            ("fromAmount", amount.to_string()),
+            ("fromAddress", from.to_string()),
+            ("slippage", bps_to_fraction(slippage_bps).to_string()),
+        ])
+        .send()
+        .await?;
+
+    if response.status() == StatusCode::NOT_FOUND {
+        return Err(QuoteError::NoRoute {
+            from: format!("{} on chain {}", from_token, from_chain),
+            to: format!("{} on chain {}", to_token, to_chain),
+        }
+        .into());
+    }
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Failed to fetch bridge quote: HTTP {}",
+            response.status()
+        ));
+    }
+    Ok(response.json().await?)
+}
+
+/// Where the transfer of `receipt` stands, `None` while it is in flight.
+async fn transfer_status(
+    client: &Client,
+    base_url: &str,
+    receipt: &BridgeReceipt,
+    from_chain: u64,
+    to_chain: u64,
+) -> Result<Option<(BridgeStatus, Option<ReceivingInfo>)>> {
+    let response = client
+        .get(format!("{}/status", base_url))
+        .query(&[
+            ("txHash", receipt.tx_hash.to_string()),
+            ("bridge", receipt.tool.clone()),
+            ("fromChain", from_chain.to_string()),
+            ("toChain", to_chain.to_string()),
+        ])
+        .send()
+        .await?;
+    // The transfer is not indexed yet right after it is mined
+    if !response.status().is_success() {
+        return Ok(None);
+    }
+    let status: StatusResponse = response.json().await?;
+    Ok(
+        BridgeStatus::of(&status.status, status.substatus.as_deref())
+            .map(|delivery| (delivery, status.receiving)),
+    )
+}
+
+/// Poll LI.FI until the transfer of `receipt` settles or `timeout` passes, recording how
+/// it arrived; `to_decimals` are those of the token it arrives as.
+pub async fn await_arrival(
+    client: &Client,
+    config: &BridgeConfig,
+    receipt: &mut BridgeReceipt,
+    from_chain: u64,
+    to_chain: u64,
+    to_decimals: u8,
+    timeout: Duration,
+) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some((delivery, receiving)) =
+            transfer_status(client, &config.base_url, receipt, from_chain, to_chain).await?
+        {
+            receipt.delivery = delivery;
+            if let Some(receiving) = receiving {
+                receipt.amount_out = receiving
+                    .amount
+                    .and_then(|amount| units_to_f64(&amount, to_decimals).ok());
+                receipt.receiving_tx_hash = receiving.tx_hash;
+            }
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            println!(
+                "Bridge transfer {} still in flight after {}s",
+                receipt.tx_hash,
+                timeout.as_secs()
+            );
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_secs(config.poll_interval_secs)).await;
+    }
+}
+
+/// Bridge `amount` of `token` from `from_chain` to `to_token` on `to_chain`, approving
+/// the bridge for the amount if needed. Returns once the source transaction is mined,
+/// with the transfer pending; `await_arrival` follows it to the destination.
+#[allow(clippy::too_many_arguments)]
+pub async fn bridge(
+    client: &Client,
+    config: &BridgeConfig,
+    sender: &TxSender<'_>,
+    from_chain: &ChainData,
+    to_chain: &ChainData,
+    token: &str,
+    to_token: &str,
+    amount: f64,
+    slippage_bps: u16,
+    simulation: &SimulationConfig,
+) -> Result<BridgeReceipt> {
+    let (src_token_addr, src_token_decimals) = from_chain.token(token)?;
+    let (dst_token_addr, dst_token_decimals) = to_chain.token(to_token)?;
+
+    let mut amount_in = f64_to_units(amount, src_token_decimals)?;
+    let balance = balance_of(sender.provider, src_token_addr, sender.from).await?;
+    if amount_in > balance {
+        println!(
+            "Bridge amount {} of {} is above the balance of {}, clamping it",
+            amount_in, token, balance
+        );
+        amount_in = balance;
+    }
+    if amount_in.is_zero() {
+        return Err(anyhow!("Wallet holds no {}", token));
+    }
+
+    let quote = get_bridge_quote(
+        client,
+        &config.base_url,
+        from_chain.id(),
+        to_chain.id(),
+        src_token_addr,
+        dst_token_addr,
+        amount_in,
+        sender.from,
+        slippage_bps,
+    )
+    .await?;
+    println!(
+        "Bridging {} {} from {} to {} via {}",
+        amount,
+        token,
+        from_chain.name(),
+        to_chain.name(),
+        quote.tool
+    );
+
+    quote.check_contracts(&config.allowed_contracts)?;
+
+    // Checked before approving the bridge, so an uneconomic transfer sends nothing
+    let tx = quote.transaction_request.request();
+    let gas_limit = tx
+        .gas
+        .filter(|gas| *gas > 0)
+        .ok_or_else(|| anyhow!("Bridge quote via {} carries no gas limit", quote.tool))?;
+    let native_token = is_native_token(src_token_addr);
+    gas::check_gas_cost(
+        sender.fees.max_cost(gas_limit),
+        quote.native_value(amount_in, native_token)?,
+        sender.gas.max_cost_pct,
+    )?;
+
+    if !native_token {
+        ensure_allowance(
+            sender,
+            src_token_addr.parse()?,
+            quote.estimate.approval_address,
+            amount_in,
+            ApprovalPolicy::Exact,
+        )
+        .await?;
+    }
+    if simulation.enabled {
+        // The fork may predate the approval, so the simulation repeats it
+        let approval = if native_token {
+            None
+        } else {
+            Some(ForkApproval {
+                token: src_token_addr.parse()?,
+                spender: quote.estimate.approval_address,
+                amount: amount_in,
+            })
+        };
+        simulation::simulate_swap(
+            sender.provider,
+            simulation,
+            &tx,
+            sender.from,
+            None,
+            approval,
+        )
+        .await
+        .map_err(|e| anyhow!("Bridge transfer via {}: {}", quote.tool, e))?;
+    }
+    let receipt = send_and_confirm(sender, tx).await?;
+    println!("Bridge transaction mined: {}", receipt.transaction_hash);
+
+    Ok(BridgeReceipt {
+        tool: quote.tool,
+        tx_hash: receipt.transaction_hash,
+        gas_used: receipt.gas_used,
+        effective_gas_price: receipt.effective_gas_price,
+        expected_amount_out: units_to_f64(&quote.estimate.to_amount, dst_token_decimals)?,
+        min_amount_out: units_to_f64(&quote.estimate.to_amount_min, dst_token_decimals)?,
+        delivery: BridgeStatus::Pending,
+        amount_out: None,
+        receiving_tx_hash: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bridge_status() {
+        assert_eq!(
+            BridgeStatus::of("DONE", Some("COMPLETED")),
+            Some(BridgeStatus::Completed)
+        );
+        assert_eq!(
+            BridgeStatus::of("DONE", Some("PARTIAL")),
+            Some(BridgeStatus::Partial)
+        );
+        assert_eq!(
+            BridgeStatus::of("DONE", Some("REFUNDED")),
+            Some(BridgeStatus::Refunded)
+        );
+        assert_eq!(BridgeStatus::of("FAILED", None), Some(BridgeStatus::Failed));
+        assert_eq!(
+            BridgeStatus::of("PENDING", Some("WAIT_DESTINATION_TRANSACTION")),
+            None
+        );
+        assert_eq!(BridgeStatus::of("NOT_FOUND", None), None);
+    }
+
+    #[test]
+    fn test_bridge_quote_transaction() {
+        let quote: BridgeQuote = serde_json::from_value(serde_json::json!({
+            "tool": "across",
+            "estimate": {
+                "approvalAddress": "0x1231deb6f5749ef6ce6943a275a1d3e7486f4eae",
+                "toAmount": "999000", "toAmountMin": "994000", "executionDuration": 60
+            },
+            "transactionRequest": {
+                "to": "0x1231deb6f5749ef6ce6943a275a1d3e7486f4eae", "data": "0x1234",
+                "value": "0x0", "gasLimit": "0x7a120", "chainId": 8453
+            }
+        }))
+        .unwrap();
+        let tx = quote.transaction_request.request();
+        assert_eq!(tx.gas, Some(500_000));
+        assert_eq!(tx.value, Some(U256::ZERO));
+        assert_eq!(
+            lifi_token_address("0xEeeeeEeeeEeEeeEeEeEeeEEEeeeeEeeeeeeeEEeE"),
+            Address::ZERO.to_string()
+        );
+    }
+
+    #[test]
+    fn test_bridge_quote_checks() -> Result<()> {
+        let quote = |to: &str| -> BridgeQuote {
+            serde_json::from_value(serde_json::json!({
+                "tool": "across",
+                "estimate": {
+                    "approvalAddress": "0x1231deb6f5749ef6ce6943a275a1d3e7486f4eae",
+                    "toAmount": "999000", "toAmountMin": "994000", "fromAmountUSD": "1000.00",
+                    "gasCosts": [{"amountUSD": "0.05", "token": {"decimals": 18, "priceUSD": "2500"}}]
+                },
+                "transactionRequest": {
+                    "to": to, "data": "0x1234", "value": "0x0", "gasLimit": "0x7a120"
+                }
+            }))
+            .unwrap()
+        };
+        let allowed = BridgeConfig::default().allowed_contracts;
+        quote("0x1231deb6f5749ef6ce6943a275a1d3e7486f4eae").check_contracts(&allowed)?;
+        assert!(quote("0x000000000000000000000000000000000000dead")
+            .check_contracts(&allowed)
+            .is_err());
+
+        // 1000 USD at 2500 USD per native token is 0.4 of it
+        let quote = quote("0x1231deb6f5749ef6ce6943a275a1d3e7486f4eae");
+        assert_eq!(
+            quote.native_value(U256::from(1_000_000_000u64), false)?,
+            U256::from(400_000_000_000_000_000u64)
+        );
+        assert_eq!(
+            quote.native_value(U256::from(7u64), true)?,
+            U256::from(7u64)
+        );
+        Ok(())
+    }
+}
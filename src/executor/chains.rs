@@ -0,0 +1,253 @@
+use crate::config::{ChainConfig, SignerConfig};
+use crate::executor::eisen::{get_chain_metadata, ChainData};
+use crate::executor::signer::{private_key_from_env, WalletSigner};
+use alloy::primitives::Address;
+use alloy::providers::{Provider, ProviderBuilder};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Chain swaps and on-chain portfolios default to when no chain id is given.
+pub const BASE_CHAIN_ID: u64 = 8453;
+
+/// RPC endpoints (and Permit2 spenders) of the chains Eisen swaps can be routed to, and
+/// the signers of the wallets sending the transactions.
+#[derive(Debug, Clone)]
+pub struct ChainRegistry {
+    chains: HashMap<u64, ChainConfig>,
+    wallets: HashMap<Address, WalletSigner>,
+}
+
+impl ChainRegistry {
+    pub fn new(chains: HashMap<u64, ChainConfig>) -> Self {
+        Self {
+            chains,
+            wallets: HashMap::new(),
+        }
+    }
+
+    /// Registry with the configured wallet signers, loaded (and keystores decrypted) once.
+    pub fn with_wallets(
+        chains: HashMap<u64, ChainConfig>,
+        wallets: &HashMap<String, SignerConfig>,
+        client: &reqwest::Client,
+    ) -> Result<Self> {
+        let wallets = wallets
+            .iter()
+            .map(|(address, config)| {
+                let address = address
+                    .parse::<Address>()
+                    .map_err(|e| anyhow!("Invalid wallet address {}: {}", address, e))?;
+                Ok((address, WalletSigner::load(address, config, client)?))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Self { chains, wallets })
+    }
+
+    pub fn rpc_url(&self, chain_id: u64) -> Result<&str> {
+        self.chains
+            .get(&chain_id)
+            .map(|chain| chain.rpc_url.as_str())
+            .ok_or_else(|| anyhow!("No RPC configured for chain {}", chain_id))
+    }
+
+    pub fn private_rpc_url(&self, chain_id: u64) -> Option<&str> {
+        self.chains
+            .get(&chain_id)
+            .and_then(|chain| chain.private_rpc_url.as_deref())
+    }
+
+    /// Permit2 spender (the Eisen router) of every chain that configures one.
+    pub fn permit2_spenders(&self) -> Result<HashMap<u64, Address>> {
+        self.chains
+            .iter()
+            .filter_map(|(chain_id, chain)| {
+                chain.permit2_spender.as_ref().map(|spender| {
+                    let spender = spender.parse::<Address>().map_err(|e| {
+                        anyhow!("Invalid permit2_spender for chain {}: {}", chain_id, e)
+                    })?;
+                    Ok((*chain_id, spender))
+                })
+            })
+            .collect()
+    }
+
+    /// Signer of `wallet`: the configured one, or the PRIVATE_KEY_DEPLOYER key if it is
+    /// the wallet's.
+    pub fn signer(&self, wallet: Address) -> Result<WalletSigner> {
+        if let Some(signer) = self.wallets.get(&wallet) {
+            return Ok(signer.clone());
+        }
+        let deployer = private_key_from_env("PRIVATE_KEY_DEPLOYER")?;
+        if deployer.address() != wallet {
+            return Err(anyhow!("No signer configured for wallet {}", wallet));
+        }
+        Ok(WalletSigner::Local(deployer))
+    }
+
+    /// Provider for `chain_id` that only reads, e.g. to price gas.
+    pub fn read_provider(&self, chain_id: u64) -> Result<Box<dyn Provider>> {
+        let rpc_url = self.rpc_url(chain_id)?;
+        let provider = ProviderBuilder::new().on_http(reqwest::Url::parse(rpc_url)?);
+        Ok(Box::new(provider))
+    }
+
+    /// Provider for `chain_id` signing with the key of `wallet`.
+    pub fn provider(&self, chain_id: u64, wallet: Address) -> Result<Box<dyn Provider>> {
+        let rpc_url = self.rpc_url(chain_id)?;
+        let wallet = self.signer(wallet)?.wallet()?;
+
+        let provider = ProviderBuilder::new()
+            .wallet(wallet)
+            .on_http(reqwest::Url::parse(rpc_url)?);
+
+        Ok(Box::new(provider))
+    }
+
+    /// Provider submitting transactions of `wallet` through the private relay of
+    /// `chain_id`, if the chain configures one.
+    pub fn relay_provider(
+        &self,
+        chain_id: u64,
+        wallet: Address,
+    ) -> Result<Option<Box<dyn Provider>>> {
+        let Some(relay_url) = self.private_rpc_url(chain_id) else {
+            return Ok(None);
+        };
+        let wallet = self.signer(wallet)?.wallet()?;
+
+        let provider = ProviderBuilder::new()
+            .wallet(wallet)
+            .on_http(reqwest::Url::parse(relay_url)?);
+
+        Ok(Some(Box::new(provider)))
+    }
+}
+
+/// Eisen chain metadata per chain id, refetched once older than the TTL.
+#[derive(Clone)]
+pub struct ChainMetadataCache {
+    base_url: String,
+    client: reqwest::Client,
+    ttl: Duration,
+    entries: Arc<Mutex<HashMap<u64, (Instant, ChainData)>>>,
+}
+
+impl ChainMetadataCache {
+    pub fn new(base_url: String, client: reqwest::Client, ttl: Duration) -> Self {
+        Self {
+            base_url,
+            client,
+            ttl,
+            entries: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    /// Cached metadata of `chain_id`, fetched if missing or expired.
+    pub async fn get(&self, chain_id: u64) -> Result<ChainData> {
+        match self.fresh(chain_id) {
+            Some(chain_data) => Ok(chain_data),
+            None => self.refresh(chain_id).await,
+        }
+    }
+
+    /// Fetch the metadata of `chain_id` regardless of what is cached.
+    pub async fn refresh(&self, chain_id: u64) -> Result<ChainData> {
+        let chain_data = get_chain_metadata(&self.client, &self.base_url, chain_id).await?;
+        self.insert(chain_id, chain_data.clone());
+        Ok(chain_data)
+    }
+
+    /// Chain ids with cached metadata, expired or not.
+    pub fn cached_chain_ids(&self) -> Vec<u64> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        let mut chain_ids: Vec<u64> = entries.keys().copied().collect();
+        chain_ids.sort_unstable();
+        chain_ids
+    }
+
+    fn fresh(&self, chain_id: u64) -> Option<ChainData> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&chain_id)
+            .filter(|(fetched_at, _)| fetched_at.elapsed() <= self.ttl)
+            .map(|(_, chain_data)| chain_data.clone())
+    }
+
+    fn insert(&self, chain_id: u64, chain_data: ChainData) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries.insert(chain_id, (Instant::now(), chain_data));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chain_lookup() {
+        let chains = ChainRegistry::new(HashMap::from([
+            (
+                BASE_CHAIN_ID,
+                ChainConfig {
+                    rpc_url: "https://mainnet.base.org".to_string(),
+                    permit2_spender: Some("0x00000000000000000000000000000000000000aa".to_string()),
+                    private_rpc_url: None,
+                },
+            ),
+            (
+                1,
+                ChainConfig {
+                    rpc_url: "https://ethereum-rpc.publicnode.com".to_string(),
+                    permit2_spender: None,
+                    private_rpc_url: Some("https://rpc.flashbots.net/fast".to_string()),
+                },
+            ),
+        ]));
+        assert_eq!(
+            chains.rpc_url(BASE_CHAIN_ID).unwrap(),
+            "https://mainnet.base.org"
+        );
+        assert!(chains.rpc_url(34443).is_err());
+        assert_eq!(
+            chains.private_rpc_url(1),
+            Some("https://rpc.flashbots.net/fast")
+        );
+        assert_eq!(chains.private_rpc_url(BASE_CHAIN_ID), None);
+
+        let spenders = chains.permit2_spenders().unwrap();
+        assert_eq!(spenders.len(), 1);
+        assert_eq!(
+            spenders[&BASE_CHAIN_ID],
+            "0x00000000000000000000000000000000000000aa"
+                .parse::<Address>()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_metadata_cache_ttl() {
+        let chain_data: ChainData = serde_json::from_value(serde_json::json!({
+            "id": 8453, "name": "base", "sym_to_addr_n_decimals": {}, "addr_to_sym": {},
+            "native_symbol": "eth", "wrapped_native": null
+        }))
+        .unwrap();
+        let cache = ChainMetadataCache::new(
+            "https://api.eisenfinance.com".to_string(),
+            reqwest::Client::new(),
+            Duration::from_secs(60),
+        );
+        cache.insert(BASE_CHAIN_ID, chain_data.clone());
+        assert!(cache.fresh(BASE_CHAIN_ID).is_some());
+        assert!(cache.fresh(1).is_none());
+        assert_eq!(cache.cached_chain_ids(), vec![BASE_CHAIN_ID]);
+
+        let expired = ChainMetadataCache {
+            ttl: Duration::ZERO,
+            ..cache.clone()
+        };
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(expired.fresh(BASE_CHAIN_ID).is_none());
+    }
+}
@@ -1,13 +1,28 @@
+use crate::config::{DexFilter, GasConfig, SimulationConfig, TrancheConfig};
+use crate::executor::error::{QuoteError, UnsupportedToken};
+use crate::executor::gas;
+use crate::executor::nonce::{NonceManager, TxSender};
+use crate::executor::paper::PaperOnchain;
+use crate::executor::queue::JobTracker;
+use crate::executor::signer::WalletSigner;
+use crate::executor::simulation::{self, revert_reason, ForkApproval};
+use crate::portfolio::eisen::ChainDetailFeed;
+use crate::utils::rounding::{apply_bps_down, f64_to_units, units_to_f64};
 use alloy::network::TransactionBuilder;
+use alloy::primitives::aliases::{U160, U48};
 use alloy::primitives::FixedBytes;
-use alloy::primitives::{Address, Bytes, U256};
+use alloy::primitives::{address, Address, Bytes, U256};
 use alloy::providers::Provider;
-use alloy::rpc::types::TransactionRequest;
+use alloy::rpc::types::{Log, TransactionReceipt, TransactionRequest};
+use alloy::sol;
+use alloy::sol_types::{eip712_domain, SolCall, SolStruct};
 use anyhow::Result;
 use itertools::Itertools;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 #[derive(Deserialize, Debug)]
 #[serde(rename_all = "camelCase")]
@@ -44,12 +59,130 @@ struct Token {
     decimals: u8,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ChainData {
     id: u64,
     name: String,
     sym_to_addr_n_decimals: HashMap<String, (String, u8)>,
     addr_to_sym: HashMap<String, String>,
+    /// Lowercase symbol of the native token, e.g. "eth".
+    native_symbol: String,
+    /// Address of the wrapped native token ("w" + native symbol), if the chain lists one.
+    wrapped_native: Option<String>,
+}
+
+fn normalize_symbol(symbol: &str) -> String {
+    symbol
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Swaps between the native token and its wrapped version, done directly on the
+/// wrapped token contract instead of through the router.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WrapKind {
+    Wrap,
+    Unwrap,
+}
+
+impl ChainData {
+    fn from_metadata(metadata: ChainMetadata) -> Result<Self> {
+        let id = metadata.id.parse::<u64>()?;
+        let native_symbol = metadata.native_symbol.to_lowercase();
+        let mut sym_to_addr_n_decimals: HashMap<String, (String, u8)> = metadata
+            .tokens
+            .iter()
+            .map(|token| {
+                (
+                    token.symbol.to_lowercase(),
+                    (token.address.to_lowercase(), token.decimals),
+                )
+            })
+            .collect();
+        let mut addr_to_sym: HashMap<String, String> = metadata
+            .tokens
+            .iter()
+            .map(|token| (token.address.to_lowercase(), token.symbol.to_lowercase()))
+            .collect();
+        // Metadata only lists ERC-20s; the native token is swapped via the placeholder address
+        if !sym_to_addr_n_decimals.contains_key(&native_symbol) {
+            sym_to_addr_n_decimals.insert(
+                native_symbol.clone(),
+                (NATIVE_TOKEN_ADDRESS.to_string(), 18),
+            );
+            addr_to_sym.insert(NATIVE_TOKEN_ADDRESS.to_string(), native_symbol.clone());
+        }
+        let wrapped_native = sym_to_addr_n_decimals
+            .get(&format!("w{}", native_symbol))
+            .map(|(address, _)| address.clone());
+
+        Ok(Self {
+            id,
+            name: convert_chain_id_to_name(id),
+            sym_to_addr_n_decimals,
+            addr_to_sym,
+            native_symbol,
+            wrapped_native,
+        })
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn native_symbol(&self) -> &str {
+        &self.native_symbol
+    }
+
+    /// Whether `symbol` resolves to the chain's native token.
+    pub fn is_native(&self, symbol: &str) -> bool {
+        self.token(symbol)
+            .is_ok_and(|(token_addr, _)| is_native_token(token_addr))
+    }
+
+    /// Address and decimals of `symbol`, matched case-insensitively. Separators are
+    /// ignored ("wstETH" and "wst-eth" are the same token) only when that leaves one
+    /// listed token, so bridged variants like USDC.e never stand in for each other.
+    pub fn token(&self, symbol: &str) -> Result<(&str, u8), UnsupportedToken> {
+        let normalized = normalize_symbol(symbol);
+        let exact = self.sym_to_addr_n_decimals.get(&symbol.to_lowercase());
+        let mut similar = self
+            .sym_to_addr_n_decimals
+            .iter()
+            .filter(|(known, _)| normalize_symbol(known) == normalized)
+            .map(|(_, token)| token);
+        let unique = match (similar.next(), similar.next()) {
+            (Some(token), None) => Some(token),
+            _ => None,
+        };
+        exact
+            .or(unique)
+            .map(|(address, decimals)| (address.as_str(), *decimals))
+            .ok_or_else(|| UnsupportedToken {
+                symbol: symbol.to_string(),
+                chain: self.name.clone(),
+                available: self.sym_to_addr_n_decimals.keys().sorted().join(", "),
+            })
+    }
+
+    /// Whether swapping `src_token_addr` into `dst_token_addr` only wraps or unwraps
+    /// the native token.
+    pub fn wrap_kind(&self, src_token_addr: &str, dst_token_addr: &str) -> Option<WrapKind> {
+        let wrapped = self.wrapped_native.as_deref()?;
+        if is_native_token(src_token_addr) && dst_token_addr.eq_ignore_ascii_case(wrapped) {
+            Some(WrapKind::Wrap)
+        } else if src_token_addr.eq_ignore_ascii_case(wrapped) && is_native_token(dst_token_addr) {
+            Some(WrapKind::Unwrap)
+        } else {
+            None
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -79,6 +212,46 @@ pub struct QuoteResult {
     cexes: Vec<Cex>,
 }
 
+impl QuoteResult {
+    /// The on-chain (DEX aggregator) route of the quote, rejected if it uses a DEX
+    /// `dex_filter` excludes.
+    pub fn dex_route(
+        self,
+        from: &str,
+        to: &str,
+        dex_filter: &DexFilter,
+    ) -> Result<AggregateMergeSwapInfo, QuoteError> {
+        match self.dex_agg {
+            Some(dex_agg) if self.is_swap_path_exists => {
+                let excluded = dex_agg
+                    .split_infos
+                    .iter()
+                    .map(|split| split.swap_info.dex_id.as_str())
+                    .filter(|dex_id| !dex_filter.allows(dex_id))
+                    .unique()
+                    .join(", ");
+                if !excluded.is_empty() {
+                    return Err(QuoteError::ExcludedDex {
+                        from: from.to_string(),
+                        to: to.to_string(),
+                        dex_ids: excluded,
+                    });
+                }
+                Ok(dex_agg)
+            }
+            _ if !self.cexes.is_empty() => Err(QuoteError::CexOnlyRoute {
+                from: from.to_string(),
+                to: to.to_string(),
+                cexes: self.cexes.iter().map(|cex| cex.cex_id.as_str()).join(", "),
+            }),
+            _ => Err(QuoteError::NoRoute {
+                from: from.to_string(),
+                to: to.to_string(),
+            }),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Cex {
@@ -154,6 +327,33 @@ pub struct BuildResponse {
     result: Transaction,
 }
 
+impl BuildResponse {
+    /// The swap transaction, without gas settings.
+    fn swap_request(&self) -> TransactionRequest {
+        TransactionRequest::default()
+            .with_to(self.result.to)
+            .with_value(self.result.value)
+            .with_input(self.result.data.clone())
+    }
+
+    /// Gas the swap is expected to use, or `fallback` when Eisen estimates none.
+    fn estimated_gas(&self, fallback: u64) -> u64 {
+        match (self.result.estimated_gas, self.result.gas_limit) {
+            (0, 0) => fallback,
+            (0, limit) => limit,
+            (gas, _) => gas,
+        }
+    }
+
+    /// Gas limit to send the swap with, 20% above the estimate when Eisen gives none.
+    fn gas_limit(&self) -> u64 {
+        match self.result.gas_limit {
+            0 => self.result.estimated_gas.saturating_mul(6) / 5,
+            limit => limit,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Transaction {
@@ -166,6 +366,173 @@ pub struct Transaction {
     error: Option<String>,
 }
 
+sol! {
+    interface IERC20 {
+        event Transfer(address indexed from, address indexed to, uint256 value);
+        function allowance(address owner, address spender) external view returns (uint256);
+        function balanceOf(address owner) external view returns (uint256);
+        function approve(address spender, uint256 amount) external returns (bool);
+    }
+
+    interface IWETH {
+        function deposit() external payable;
+        function withdraw(uint256 amount) external;
+    }
+}
+
+/// How much the router is approved to spend when its allowance is too low.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalPolicy {
+    /// Approve exactly the amount of the swap.
+    #[default]
+    Exact,
+    /// Approve `U256::MAX` so later swaps of the same token skip the approval.
+    Max,
+}
+
+impl ApprovalPolicy {
+    pub fn approval_amount(&self, required: U256) -> U256 {
+        match self {
+            Self::Exact => required,
+            Self::Max => U256::MAX,
+        }
+    }
+}
+
+/// Canonical Permit2 deployment, at the same address on every chain.
+pub const PERMIT2_ADDRESS: Address = address!("000000000022D473030F116dDEE9F6B43aC78BA3");
+
+/// Address Eisen uses for the chain's native token.
+const NATIVE_TOKEN_ADDRESS: &str = "0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee";
+
+/// How long the allowance granted by a signed permit lasts.
+const PERMIT_EXPIRATION_SECS: u64 = 30 * 60;
+
+/// How long a permit signature can be submitted for.
+const PERMIT_SIG_DEADLINE_SECS: u64 = 30 * 60;
+
+pub mod permit2 {
+    alloy::sol! {
+        #[derive(Debug)]
+        struct PermitDetails {
+            address token;
+            uint160 amount;
+            uint48 expiration;
+            uint48 nonce;
+        }
+
+        #[derive(Debug)]
+        struct PermitSingle {
+            PermitDetails details;
+            address spender;
+            uint256 sigDeadline;
+        }
+
+        interface IAllowanceTransfer {
+            function allowance(address user, address token, address spender)
+                external view returns (uint160 amount, uint48 expiration, uint48 nonce);
+        }
+    }
+}
+
+impl From<&permit2::PermitSingle> for PermitSingle {
+    fn from(permit: &permit2::PermitSingle) -> Self {
+        Self {
+            details: PermitDetails {
+                token: permit.details.token.to_string(),
+                amount: permit.details.amount.to_string(),
+                expiration: permit.details.expiration.to(),
+                nonce: permit.details.nonce.to(),
+            },
+            spender: permit.spender.to_string(),
+            sig_deadline: permit.sigDeadline.to_string(),
+        }
+    }
+}
+
+/// Signs Permit2 permits so swaps need no approval transaction for the router.
+#[derive(Debug, Clone)]
+pub struct Permit2Signer {
+    pub signer: WalletSigner,
+    /// Spender (Eisen router) per chain id; chains without one fall back to approvals.
+    pub spenders: HashMap<u64, Address>,
+}
+
+/// Slippage tolerance used when neither the swap nor the config sets one (1%).
+pub const DEFAULT_SLIPPAGE_BPS: u16 = 100;
+
+/// Bounds accepted for a slippage tolerance, in bps (0.01% to 10%).
+pub const MIN_SLIPPAGE_BPS: u16 = 1;
+pub const MAX_SLIPPAGE_BPS: u16 = 1_000;
+
+pub fn validate_slippage_bps(slippage_bps: u16) -> Result<u16> {
+    if !(MIN_SLIPPAGE_BPS..=MAX_SLIPPAGE_BPS).contains(&slippage_bps) {
+        return Err(anyhow::anyhow!(
+            "Slippage of {} bps is outside {}-{} bps",
+            slippage_bps,
+            MIN_SLIPPAGE_BPS,
+            MAX_SLIPPAGE_BPS
+        ));
+    }
+    Ok(slippage_bps)
+}
+
+/// Amount of a strategy swap: a number of `token_in`, or "max" for the wallet's whole
+/// balance.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SwapAmount {
+    Max,
+    Exact(f64),
+}
+
+impl SwapAmount {
+    pub fn parse(amount: &str) -> Result<Self> {
+        let amount = amount.trim();
+        if amount.eq_ignore_ascii_case("max") {
+            return Ok(Self::Max);
+        }
+        match amount.parse::<f64>() {
+            Ok(amount) if amount.is_finite() && amount > 0.0 => Ok(Self::Exact(amount)),
+            _ => Err(anyhow::anyhow!(
+                "Invalid swap amount {:?}, expected a positive number or \"max\"",
+                amount
+            )),
+        }
+    }
+
+    /// Amount to swap out of `balance`, keeping `reserve` (gas, when spending the native
+    /// token). Requests above what is available are clamped to it.
+    pub fn resolve(self, balance: f64, reserve: f64) -> f64 {
+        let available = (balance - reserve).max(0.0);
+        match self {
+            Self::Max => available,
+            Self::Exact(amount) => amount.min(available),
+        }
+    }
+}
+
+/// Execution options shared by every swap of a strategy.
+#[derive(Debug, Clone)]
+pub struct SwapOptions {
+    /// Default slippage tolerance; swaps setting their own override it.
+    pub slippage_bps: u16,
+    pub approval: ApprovalPolicy,
+    pub permit2: Option<Permit2Signer>,
+    pub gas: GasConfig,
+    pub simulation: SimulationConfig,
+    pub tranches: TrancheConfig,
+    pub native_gas_reserve: f64,
+    /// DEX filter per chain id; swaps setting their own override it.
+    pub dex_filters: HashMap<u64, DexFilter>,
+    /// Nonces of the swap wallet, shared with every other job sending from it.
+    pub nonces: Arc<NonceManager>,
+    /// Leg of the job's transaction queue the swap's transactions are recorded in.
+    pub tracker: Option<JobTracker>,
+    /// Set when paper trading: swaps and bridge transfers are booked here instead of sent.
+    pub paper: Option<Arc<PaperOnchain>>,
+}
+
 fn convert_chain_id_to_name(chain_id: u64) -> String {
     match chain_id {
         1 => "mainnet".to_string(),
@@ -176,26 +543,93 @@ fn convert_chain_id_to_name(chain_id: u64) -> String {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct TokenBalance {
-    symbol: String,
-    balance: f64,
+    pub symbol: String,
+    pub balance: f64,
+    /// Set once the portfolio is priced; `None` when the token could not be.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub usd_value: Option<f64>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct ChainPortfolio {
     pub balances: Vec<TokenBalance>,
+    /// Protocol positions (Aave, Lido, ...) on every chain, by underlying asset.
+    #[serde(default)]
+    pub positions: Vec<ChainDetailFeed>,
+}
+
+impl ChainPortfolio {
+    /// Balance of `symbol` (case-insensitive), 0 if the wallet holds none.
+    pub fn balance_of(&self, symbol: &str) -> f64 {
+        self.balances
+            .iter()
+            .filter(|b| b.symbol.eq_ignore_ascii_case(symbol))
+            .map(|b| b.balance)
+            .fold(0.0, |sum, balance| sum + balance)
+    }
+
+    /// Value every balance with a price in `prices`, keyed by symbol.
+    pub fn apply_prices(&mut self, prices: &HashMap<String, f64>) {
+        for balance in &mut self.balances {
+            balance.usd_value = prices
+                .get(&balance.symbol)
+                .map(|price| price * balance.balance);
+        }
+    }
+
+    /// Total value of the priced balances.
+    pub fn total_usd(&self) -> f64 {
+        self.balances.iter().filter_map(|b| b.usd_value).sum()
+    }
+}
+
+impl std::fmt::Display for ChainPortfolio {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for balance in &self.balances {
+            match balance.usd_value {
+                Some(usd_value) => writeln!(
+                    f,
+                    "  {}: {} (${:.2})",
+                    balance.symbol, balance.balance, usd_value
+                )?,
+                None => writeln!(f, "  {}: {} (unpriced)", balance.symbol, balance.balance)?,
+            }
+        }
+        write!(f, "Total: ${:.2}", self.total_usd())?;
+        if !self.positions.is_empty() {
+            write!(f, "\nProtocol positions:")?;
+        }
+        for chain in &self.positions {
+            for protocol in &chain.protocol_details {
+                for asset in &protocol.assets {
+                    write!(
+                        f,
+                        "\n  chain {} {}: {} {} (underlying {})",
+                        chain.chain_id,
+                        protocol.name,
+                        asset.balance,
+                        asset.symbol,
+                        asset.underlying_amount
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 pub async fn fetch_chain_portfolio(
+    client: &Client,
     base_url: &str,
-    chain_id: u64,
+    chain_metadata: &ChainData,
     wallet_addr: &String,
 ) -> Result<ChainPortfolio> {
     let url = format!(
         "{}/chains/{}/balances?walletAddress={}",
-        base_url, chain_id, wallet_addr
+        base_url, chain_metadata.id, wallet_addr
     );
-    let client = Client::new();
     let response = client.get(url).send().await?;
 
     if !response.status().is_success() {
@@ -206,7 +640,6 @@ pub async fn fetch_chain_portfolio(
     }
 
     let metadata: BalanceAllowResponse = response.json().await?;
-    let chain_metadata = get_chain_metadata(base_url, chain_id).await?;
     let balance_allow = metadata
         .result
         .iter()
@@ -219,18 +652,23 @@ pub async fn fetch_chain_portfolio(
             let decimals = chain_metadata.sym_to_addr_n_decimals.get(symbol).unwrap().1;
             TokenBalance {
                 symbol: symbol.to_string(),
-                balance: token.balance.parse::<f64>().unwrap() / 10.0_f64.powi(decimals as i32),
+                balance: units_to_f64(&token.balance, decimals).unwrap(),
+                usd_value: None,
             }
         })
         .collect();
     Ok(ChainPortfolio {
         balances: balance_allow,
+        positions: Vec::new(),
     })
 }
 
-pub async fn get_chain_metadata(base_url: &str, chain_id: u64) -> Result<ChainData> {
+pub async fn get_chain_metadata(
+    client: &Client,
+    base_url: &str,
+    chain_id: u64,
+) -> Result<ChainData> {
     let url = format!("{}/chains/{}/metadata", base_url, chain_id);
-    let client = Client::new();
     let response = client.get(url).send().await?;
 
     if !response.status().is_success() {
@@ -241,42 +679,21 @@ pub async fn get_chain_metadata(base_url: &str, chain_id: u64) -> Result<ChainDa
     }
 
     let metadata: ChainMetadataResponse = response.json().await?;
-
-    let chain_data = ChainData {
-        id: metadata.result.id.parse::<u64>()?,
-        name: convert_chain_id_to_name(metadata.result.id.parse::<u64>()?),
-        sym_to_addr_n_decimals: metadata
-            .result
-            .tokens
-            .iter()
-            .map(|token| {
-                (
-                    token.symbol.to_lowercase(),
-                    (token.address.to_lowercase(), token.decimals),
-                )
-            })
-            .collect(),
-        addr_to_sym: metadata
-            .result
-            .tokens
-            .iter()
-            .map(|token| (token.address.to_lowercase(), token.symbol.to_lowercase()))
-            .collect(),
-    };
-
-    Ok(chain_data)
+    ChainData::from_metadata(metadata.result)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_quote(
+    client: &Client,
     base_url: &str,
     chain_id: u64,
     from_token: &str,
     to_token: &str,
     amount: U256,
     from: Option<String>,
+    dex_filter: &DexFilter,
 ) -> Result<QuoteResponse> {
     let url = format!("{}/chains/{}/v2/quote", base_url, chain_id);
-    let client = Client::new();
 
     let quote_request_body = QuoteRequestBody {
         token_in_addr: from_token.to_string(),
@@ -285,7 +702,8 @@ pub async fn get_quote(
         max_split: "10".to_string(),
         max_edge: "3".to_string(),
         with_cycle: false,
-        dex_id_filter: vec![],
+        // Eisen only takes the DEXes to use; denied ones are checked in `dex_route`
+        dex_id_filter: dex_filter.allow.clone(),
         custom_tokens: None,
         from,
     };
@@ -310,7 +728,9 @@ pub async fn get_quote(
     Ok(quote_response)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub async fn get_tx_data(
+    client: &Client,
     base_url: &str,
     chain_id: u64,
     dex_agg: AggregateMergeSwapInfo,
@@ -320,7 +740,6 @@ pub async fn get_tx_data(
     slippage_bps: u16,
 ) -> Result<BuildResponse> {
     let url = format!("{}/chains/{}/v2/build", base_url, chain_id);
-    let client = Client::new();
 
     let build_request_body = BuildRequestBody {
         from: from.to_string(),
@@ -351,74 +770,671 @@ pub async fn get_tx_data(
     Ok(build_response)
 }
 
+/// Outcome of a mined swap transaction.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapReceipt {
+    pub tx_hash: FixedBytes<32>,
+    pub block_number: Option<u64>,
+    pub gas_used: u64,
+    #[serde(deserialize_with = "deserialize_gas_price")]
+    pub effective_gas_price: u128,
+    /// Output received by the wallet, parsed from the token's Transfer logs or, for
+    /// native outputs, the wallet's balance change. None when it could not be measured.
+    pub amount_out: Option<f64>,
+    /// Output quoted before the swap.
+    pub expected_amount_out: f64,
+    /// Quoted output minus the slippage tolerance.
+    pub min_amount_out: f64,
+    pub slippage_check: SlippageCheck,
+}
+
+/// Reads a gas price stored as a JSON number. Receipts are read back from inside
+/// flattened reports, which serde cannot buffer `u128`s through, and wei per gas
+/// always fits a `u64`.
+pub fn deserialize_gas_price<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<u128, D::Error> {
+    u64::deserialize(deserializer).map(u128::from)
+}
+
+/// Whether a swap delivered at least its minimum output.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SlippageCheck {
+    #[serde(rename = "passed")]
+    Passed,
+    #[serde(rename = "failed slippage check")]
+    Failed,
+    /// The output could not be measured.
+    #[serde(rename = "unverified")]
+    Unverified,
+}
+
+impl SlippageCheck {
+    pub fn of(amount_out: Option<U256>, min_amount_out: U256) -> Self {
+        match amount_out {
+            Some(amount_out) if amount_out >= min_amount_out => Self::Passed,
+            Some(_) => Self::Failed,
+            None => Self::Unverified,
+        }
+    }
+}
+
+/// Send `tx`, wait for its receipt and fail if it reverted, with the decoded reason when
+/// the node returns one.
+pub async fn send_and_confirm(
+    sender: &TxSender<'_>,
+    tx: TransactionRequest,
+) -> Result<TransactionReceipt> {
+    let receipt = sender.send(tx.clone()).await?;
+    if receipt.status() {
+        return Ok(receipt);
+    }
+
+    // Replay the call at the block it was mined in to recover the revert reason
+    let tx = tx.with_from(sender.from);
+    let mut replay = sender.provider.call(&tx);
+    if let Some(block_number) = receipt.block_number {
+        replay = replay.block(block_number.into());
+    }
+    let reason = replay.await.err().map(|err| revert_reason(&err));
+    Err(anyhow::anyhow!(
+        "Transaction {} reverted (gas used {}): {}",
+        receipt.transaction_hash,
+        receipt.gas_used,
+        reason.unwrap_or_else(|| "unknown reason".to_string())
+    ))
+}
+
 pub async fn send_tx(
-    provider: &dyn Provider,
+    sender: &TxSender<'_>,
     build_response: BuildResponse,
+) -> Result<TransactionReceipt> {
+    let gas_limit = build_response.gas_limit();
+    let mut tx = build_response.swap_request();
+    if gas_limit > 0 {
+        tx = tx.with_gas_limit(gas_limit);
+    }
+    send_and_confirm(sender, tx).await
+}
+
+/// Sum of `token` transferred to `recipient` in `logs`, in base units.
+pub fn received_from_logs(logs: &[Log], token: Address, recipient: Address) -> Option<U256> {
+    logs.iter()
+        .filter(|log| log.address() == token)
+        .filter_map(|log| log.log_decode::<IERC20::Transfer>().ok())
+        .filter(|transfer| transfer.inner.to == recipient)
+        .map(|transfer| transfer.inner.value)
+        .reduce(|sum, value| sum + value)
+}
+
+/// Native token received by `wallet` in the receipt's transaction, from its balance
+/// change over the block with the gas it paid added back.
+async fn native_received(
+    provider: &dyn Provider,
+    wallet: Address,
+    receipt: &TransactionReceipt,
+) -> Result<Option<U256>> {
+    let Some(block_number) = receipt.block_number.filter(|n| *n > 0) else {
+        return Ok(None);
+    };
+    let before = provider
+        .get_balance(wallet)
+        .number(block_number - 1)
+        .await?;
+    let after = provider.get_balance(wallet).number(block_number).await?;
+    let gas_paid = U256::from(receipt.gas_used) * U256::from(receipt.effective_gas_price);
+    Ok((after + gas_paid).checked_sub(before))
+}
+
+/// Balance of `owner` in `token_addr` base units (wei for the native token).
+pub async fn balance_of(provider: &dyn Provider, token_addr: &str, owner: Address) -> Result<U256> {
+    if is_native_token(token_addr) {
+        return Ok(provider.get_balance(owner).await?);
+    }
+    let tx = TransactionRequest::default()
+        .with_to(token_addr.parse::<Address>()?)
+        .with_input(IERC20::balanceOfCall { owner }.abi_encode());
+    let output = provider.call(&tx).await?;
+    Ok(IERC20::balanceOfCall::abi_decode_returns(&output, true)?._0)
+}
+
+/// Live balance of `owner` in `token`, in whole units.
+pub async fn wallet_balance(
+    provider: &dyn Provider,
+    chain_data: &ChainData,
+    token: &str,
+    owner: Address,
+) -> Result<f64> {
+    let (token_addr, decimals) = chain_data.token(token)?;
+    let balance = balance_of(provider, token_addr, owner).await?;
+    units_to_f64(&balance.to_string(), decimals)
+}
+
+pub async fn get_allowance(
+    provider: &dyn Provider,
+    token: Address,
+    owner: Address,
+    spender: Address,
+) -> Result<U256> {
+    let tx = TransactionRequest::default()
+        .with_to(token)
+        .with_input(IERC20::allowanceCall { owner, spender }.abi_encode());
+    let output = provider.call(&tx).await?;
+    Ok(IERC20::allowanceCall::abi_decode_returns(&output, true)?._0)
+}
+
+/// Approve `spender` for `amount` of `token` and wait for the transaction to be mined.
+pub async fn approve(
+    sender: &TxSender<'_>,
+    token: Address,
+    spender: Address,
+    amount: U256,
 ) -> Result<FixedBytes<32>> {
     let tx = TransactionRequest::default()
-        .with_to(build_response.result.to)
-        .with_value(build_response.result.value)
-        .with_input(build_response.result.data);
-    let receipt = provider.send_transaction(tx).await?.watch().await?;
-    Ok(receipt)
+        .with_to(token)
+        .with_input(IERC20::approveCall { spender, amount }.abi_encode());
+    let receipt = send_and_confirm(sender, tx).await?;
+    Ok(receipt.transaction_hash)
+}
+
+/// Send an approval first if `spender` cannot already spend `required` of the sender's
+/// `token`. Returns the approval transaction hash when one was sent.
+pub async fn ensure_allowance(
+    sender: &TxSender<'_>,
+    token: Address,
+    spender: Address,
+    required: U256,
+    policy: ApprovalPolicy,
+) -> Result<Option<FixedBytes<32>>> {
+    let allowance = get_allowance(sender.provider, token, sender.from, spender).await?;
+    if allowance >= required {
+        return Ok(None);
+    }
+    println!(
+        "Allowance of {} for {} is {}, approving {:?} for {}",
+        token, spender, allowance, policy, required
+    );
+    let tx = approve(sender, token, spender, policy.approval_amount(required)).await?;
+    println!("Approval transaction mined: {}", tx);
+    Ok(Some(tx))
+}
+
+pub fn is_native_token(token_addr: &str) -> bool {
+    token_addr.eq_ignore_ascii_case(NATIVE_TOKEN_ADDRESS)
+        || token_addr
+            .parse::<Address>()
+            .is_ok_and(|addr| addr.is_zero())
+}
+
+/// Next unused Permit2 nonce of `owner` for `token` and `spender`.
+pub async fn get_permit2_nonce(
+    provider: &dyn Provider,
+    owner: Address,
+    token: Address,
+    spender: Address,
+) -> Result<u64> {
+    let call = permit2::IAllowanceTransfer::allowanceCall {
+        user: owner,
+        token,
+        spender,
+    };
+    let tx = TransactionRequest::default()
+        .with_to(PERMIT2_ADDRESS)
+        .with_input(call.abi_encode());
+    let output = provider.call(&tx).await?;
+    let allowance = permit2::IAllowanceTransfer::allowanceCall::abi_decode_returns(&output, true)?;
+    Ok(allowance.nonce.to())
+}
+
+/// Permit letting `spender` pull `amount` of `token`, issued at `now` (unix seconds).
+pub fn build_permit_single(
+    token: Address,
+    amount: U256,
+    spender: Address,
+    nonce: u64,
+    now: u64,
+) -> permit2::PermitSingle {
+    permit2::PermitSingle {
+        details: permit2::PermitDetails {
+            token,
+            amount: U160::saturating_from(amount),
+            expiration: U48::from(now + PERMIT_EXPIRATION_SECS),
+            nonce: U48::from(nonce),
+        },
+        spender,
+        sigDeadline: U256::from(now + PERMIT_SIG_DEADLINE_SECS),
+    }
+}
+
+/// EIP-712 signature of `permit` for the Permit2 contract on `chain_id`, as 0x-prefixed hex.
+pub async fn sign_permit_single(
+    signer: &WalletSigner,
+    permit: &permit2::PermitSingle,
+    chain_id: u64,
+) -> Result<String> {
+    let domain = eip712_domain! {
+        name: "Permit2",
+        chain_id: chain_id,
+        verifying_contract: PERMIT2_ADDRESS,
+    };
+    let signature = signer
+        .sign_hash(&permit.eip712_signing_hash(&domain))
+        .await?;
+    Ok(format!("0x{}", hex::encode(signature.as_bytes())))
+}
+
+async fn signed_permit(
+    sender: &TxSender<'_>,
+    permit2: &Permit2Signer,
+    spender: Address,
+    token_addr: &str,
+    amount: U256,
+    chain_id: u64,
+) -> Result<(PermitSingle, String)> {
+    let owner = &sender.from;
+    if permit2.signer.address() != *owner {
+        return Err(anyhow::anyhow!(
+            "Permit2 signer {} does not own wallet {}",
+            permit2.signer.address(),
+            owner
+        ));
+    }
+    let token = token_addr.parse::<Address>()?;
+    let nonce = get_permit2_nonce(sender.provider, *owner, token, spender).await?;
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let permit = build_permit_single(token, amount, spender, nonce, now);
+    let signature = sign_permit_single(&permit2.signer, &permit, chain_id).await?;
+    Ok((PermitSingle::from(&permit), signature))
+}
+
+/// Quote `amount` of `from_token` into `to_token` without building a transaction.
+/// Returns the expected output in whole `to_token` units.
+pub async fn quote_amount_out(
+    client: &Client,
+    base_url: &str,
+    chain_data: &ChainData,
+    from_token: &str,
+    to_token: &str,
+    amount: f64,
+    dex_filter: &DexFilter,
+) -> Result<f64> {
+    let (src_token_addr, src_token_decimals) = chain_data.token(from_token)?;
+    let (dst_token_addr, dst_token_decimals) = chain_data.token(to_token)?;
+
+    let amount_in = f64_to_units(amount, src_token_decimals)?;
+    if chain_data
+        .wrap_kind(src_token_addr, dst_token_addr)
+        .is_some()
+    {
+        return units_to_f64(&amount_in.to_string(), dst_token_decimals);
+    }
+
+    let quote = get_quote(
+        client,
+        base_url,
+        chain_data.id,
+        src_token_addr,
+        dst_token_addr,
+        amount_in,
+        None,
+        dex_filter,
+    )
+    .await?;
+
+    let dex_agg = quote.result.dex_route(from_token, to_token, dex_filter)?;
+    units_to_f64(&dex_agg.expected_amount_out, dst_token_decimals)
+}
+
+/// Value to send with a router swap: the input amount for native inputs, nothing for
+/// ERC-20 inputs. A build response disagreeing with that is rejected, except for a native
+/// input it left at zero.
+fn swap_value(pulls_token: bool, amount_in: U256, built_value: U256) -> Result<U256> {
+    let value = if pulls_token { U256::ZERO } else { amount_in };
+    let omitted_native_value = !pulls_token && built_value.is_zero();
+    if built_value != value && !omitted_native_value {
+        return Err(anyhow::anyhow!(
+            "Swap transaction sends {} wei but the swap needs {}",
+            built_value,
+            value
+        ));
+    }
+    Ok(value)
+}
+
+/// Wrap or unwrap `amount` of the native token on the `weth` contract.
+async fn wrap_native(
+    sender: &TxSender<'_>,
+    weth: Address,
+    kind: WrapKind,
+    amount: U256,
+    decimals: u8,
+) -> Result<SwapReceipt> {
+    let tx = match kind {
+        WrapKind::Wrap => TransactionRequest::default()
+            .with_to(weth)
+            .with_value(amount)
+            .with_input(IWETH::depositCall {}.abi_encode()),
+        WrapKind::Unwrap => TransactionRequest::default()
+            .with_to(weth)
+            .with_input(IWETH::withdrawCall { amount }.abi_encode()),
+    };
+    let receipt = send_and_confirm(sender, tx).await?;
+    println!(
+        "{:?} of {} mined: {}",
+        kind, amount, receipt.transaction_hash
+    );
+
+    // Wrapping is 1:1, a mined transaction delivered exactly the amount
+    let amount = units_to_f64(&amount.to_string(), decimals)?;
+    Ok(SwapReceipt {
+        tx_hash: receipt.transaction_hash,
+        block_number: receipt.block_number,
+        gas_used: receipt.gas_used,
+        effective_gas_price: receipt.effective_gas_price,
+        amount_out: Some(amount),
+        expected_amount_out: amount,
+        min_amount_out: amount,
+        slippage_check: SlippageCheck::Passed,
+    })
 }
 
+/// Value of `amount` of `token_addr` in wei of the chain's native token. Tokens without
+/// a route into the native token are valued through the wrapped native token, which
+/// is worth its amount.
+async fn native_value(
+    client: &Client,
+    base_url: &str,
+    chain_data: &ChainData,
+    token_addr: &str,
+    amount: U256,
+) -> Result<U256> {
+    let wrapped = chain_data.wrapped_native.as_deref();
+    if is_native_token(token_addr) || wrapped.is_some_and(|w| w.eq_ignore_ascii_case(token_addr)) {
+        return Ok(amount);
+    }
+    let native_out = quote_expected_out(
+        client,
+        base_url,
+        chain_data.id,
+        token_addr,
+        NATIVE_TOKEN_ADDRESS,
+        amount,
+    )
+    .await;
+    match (native_out, wrapped) {
+        (Err(e), Some(wrapped)) if e.downcast_ref::<QuoteError>().is_some() => {
+            quote_expected_out(client, base_url, chain_data.id, token_addr, wrapped, amount).await
+        }
+        (native_out, _) => native_out,
+    }
+}
+
+/// Amount of `dst_token_addr` base units the quoted route returns for `amount`.
+async fn quote_expected_out(
+    client: &Client,
+    base_url: &str,
+    chain_id: u64,
+    src_token_addr: &str,
+    dst_token_addr: &str,
+    amount: U256,
+) -> Result<U256> {
+    let quote = get_quote(
+        client,
+        base_url,
+        chain_id,
+        src_token_addr,
+        dst_token_addr,
+        amount,
+        None,
+        &DexFilter::default(),
+    )
+    .await?;
+    let amount_out = quote
+        .result
+        .dex_route(src_token_addr, dst_token_addr, &DexFilter::default())?
+        .expected_amount_out;
+    U256::from_str_radix(&amount_out, 10)
+        .map_err(|e| anyhow::anyhow!("Invalid quoted amount {:?}: {}", amount_out, e))
+}
+
+/// Value of `amount` of `token` in whole units of the chain's native token.
+pub async fn quote_native_value(
+    client: &Client,
+    base_url: &str,
+    chain_data: &ChainData,
+    token: &str,
+    amount: f64,
+) -> Result<f64> {
+    let (token_addr, decimals) = chain_data.token(token)?;
+    let value = native_value(
+        client,
+        base_url,
+        chain_data,
+        token_addr,
+        f64_to_units(amount, decimals)?,
+    )
+    .await?;
+    units_to_f64(&value.to_string(), 18)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub async fn quote_and_send_tx(
     provider: &dyn Provider,
+    relay: Option<&dyn Provider>,
+    client: &Client,
     base_url: &str,
     chain_data: &ChainData,
     from_token: &str,
     to_token: &str,
     amount: f64,
     wallet_addr: &Address,
-    slippage_bps: u16,
-) -> Result<FixedBytes<32>> {
+    options: &SwapOptions,
+) -> Result<SwapReceipt> {
     let chain_id = provider.get_chain_id().await?;
+    if chain_id != chain_data.id {
+        return Err(anyhow::anyhow!(
+            "Provider is on chain {} but the swap targets {} ({})",
+            chain_id,
+            chain_data.id,
+            chain_data.name
+        ));
+    }
 
-    let (src_token_addr, src_token_decimals) =
-        &chain_data.sym_to_addr_n_decimals[&from_token.to_lowercase()];
-    let (dst_token_addr, dst_token_decimals) =
-        &chain_data.sym_to_addr_n_decimals[&to_token.to_lowercase()];
+    let (src_token_addr, src_token_decimals) = chain_data.token(from_token)?;
+    let (dst_token_addr, dst_token_decimals) = chain_data.token(to_token)?;
+
+    // Amounts are clamped in whole units before this; converting them back can still
+    // round a few base units above the balance
+    let mut amount_in = f64_to_units(amount, src_token_decimals)?;
+    let balance = balance_of(provider, src_token_addr, *wallet_addr).await?;
+    if amount_in > balance {
+        println!(
+            "Swap amount {} of {} is above the balance of {}, clamping it",
+            amount_in, from_token, balance
+        );
+        amount_in = balance;
+    }
+    if amount_in.is_zero() {
+        return Err(anyhow::anyhow!("Wallet holds no {}", from_token));
+    }
 
-    let amount_in = U256::from_str_radix(
-        &((amount * 10.0_f64.powi(*src_token_decimals as i32))
-            .floor()
-            .to_string()),
-        10,
-    )
-    .unwrap();
+    let sender = TxSender {
+        provider,
+        from: *wallet_addr,
+        nonces: &options.nonces,
+        fees: gas::estimate_fees(provider, &options.gas).await?,
+        gas: &options.gas,
+        tracker: options.tracker.as_ref(),
+        relay,
+    };
+    if let Some(kind) = chain_data.wrap_kind(src_token_addr, dst_token_addr) {
+        let weth = match kind {
+            WrapKind::Wrap => dst_token_addr,
+            WrapKind::Unwrap => src_token_addr,
+        };
+        return wrap_native(&sender, weth.parse()?, kind, amount_in, dst_token_decimals).await;
+    }
 
+    let dex_filter = options
+        .dex_filters
+        .get(&chain_id)
+        .cloned()
+        .unwrap_or_default();
     let quote = get_quote(
+        client,
         base_url,
         chain_id,
         src_token_addr,
         dst_token_addr,
         amount_in,
         None,
+        &dex_filter,
     )
     .await?;
+    // Checked before anything is approved or signed for the swap
+    let dex_agg = quote.result.dex_route(from_token, to_token, &dex_filter)?;
+    let expected_amount_out = dex_agg.expected_amount_out.clone();
+    let permit2 = options
+        .permit2
+        .as_ref()
+        .filter(|_| !is_native_token(src_token_addr))
+        .and_then(|permit2| Some((permit2, *permit2.spenders.get(&chain_id)?)));
+    let (permit, permit_signature) = match permit2 {
+        Some((permit2, spender)) => {
+            let (permit, signature) = signed_permit(
+                &sender,
+                permit2,
+                spender,
+                src_token_addr,
+                amount_in,
+                chain_id,
+            )
+            .await?;
+            (Some(permit), signature)
+        }
+        None => (None, String::new()),
+    };
+    let uses_permit = permit.is_some();
 
-    let tx_data = get_tx_data(
+    let mut tx_data = get_tx_data(
+        client,
         base_url,
         chain_id,
-        quote.result.dex_agg.unwrap(),
-        None,
-        String::new(),
+        dex_agg,
+        permit,
+        permit_signature,
         wallet_addr.to_string().as_str(),
-        slippage_bps,
+        options.slippage_bps,
     )
     .await?;
 
-    let tx = send_tx(provider, tx_data).await?;
+    // Checked before approving the router or Permit2, so an uneconomic swap sends
+    // nothing; the permit is only signed so far
+    let trade_value = if is_native_token(dst_token_addr) {
+        U256::from_str_radix(&expected_amount_out, 10)?
+    } else {
+        native_value(client, base_url, chain_data, src_token_addr, amount_in).await?
+    };
+    gas::check_gas_cost(
+        sender
+            .fees
+            .max_cost(tx_data.estimated_gas(options.gas.fallback_swap_gas)),
+        trade_value,
+        options.gas.max_cost_pct,
+    )?;
+
+    // Native inputs are sent as value; ERC-20 inputs are pulled by the router
+    let pulls_token = !is_native_token(src_token_addr);
+    tx_data.result.value = swap_value(pulls_token, amount_in, tx_data.result.value)?;
+    if pulls_token {
+        // Permit2 pulls the tokens itself, so it needs a standing allowance once per token
+        let (spender, policy) = if uses_permit {
+            (PERMIT2_ADDRESS, ApprovalPolicy::Max)
+        } else {
+            (tx_data.result.to, options.approval)
+        };
+        ensure_allowance(&sender, src_token_addr.parse()?, spender, amount_in, policy).await?;
+    }
+
+    let expected = U256::from_str_radix(&expected_amount_out, 10)?;
+    let min_amount_out = apply_bps_down(expected, options.slippage_bps);
+    let token_out = dst_token_addr
+        .parse::<Address>()
+        .ok()
+        .filter(|_| !is_native_token(dst_token_addr));
+    if options.simulation.enabled {
+        // The fork may predate the approval, so the simulation repeats it
+        let approval = if pulls_token {
+            Some(ForkApproval {
+                token: src_token_addr.parse()?,
+                spender: if uses_permit {
+                    PERMIT2_ADDRESS
+                } else {
+                    tx_data.result.to
+                },
+                amount: amount_in,
+            })
+        } else {
+            None
+        };
+        let simulated = simulation::simulate_swap(
+            provider,
+            &options.simulation,
+            &tx_data.swap_request(),
+            *wallet_addr,
+            token_out,
+            approval,
+        )
+        .await?;
+        match simulated {
+            Some(simulated) => simulation::check_simulated_output(simulated, min_amount_out)?,
+            None => println!("Swap simulation succeeded; output could not be measured"),
+        }
+    }
 
-    Ok(tx)
+    let receipt = send_tx(&sender, tx_data).await?;
+    let received = match token_out {
+        Some(token) => received_from_logs(receipt.inner.logs(), token, *wallet_addr),
+        // The swap is already mined, so a failed balance lookup only leaves it unverified
+        None => native_received(provider, *wallet_addr, &receipt)
+            .await
+            .unwrap_or_else(|e| {
+                println!(
+                    "Could not measure native output of {}: {}",
+                    receipt.transaction_hash, e
+                );
+                None
+            }),
+    };
+    let slippage_check = SlippageCheck::of(received, min_amount_out);
+    if slippage_check == SlippageCheck::Failed {
+        println!(
+            "Swap {} failed slippage check: received {:?}, minimum {}",
+            receipt.transaction_hash, received, min_amount_out
+        );
+    }
+    let to_units = |amount: U256| units_to_f64(&amount.to_string(), dst_token_decimals);
+
+    Ok(SwapReceipt {
+        tx_hash: receipt.transaction_hash,
+        block_number: receipt.block_number,
+        gas_used: receipt.gas_used,
+        effective_gas_price: receipt.effective_gas_price,
+        amount_out: received.map(to_units).transpose()?,
+        expected_amount_out: to_units(expected)?,
+        min_amount_out: to_units(min_amount_out)?,
+        slippage_check,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::portfolio::eisen::{AssetFeed, ProtocolDetailFeed};
     use alloy::network::EthereumWallet;
     use alloy::signers::local::PrivateKeySigner;
+    use alloy::sol_types::SolEvent;
     use reqwest::Url;
     use tokio;
 
@@ -432,18 +1448,298 @@ mod tests {
     fn cmp_amount() -> Result<()> {
         let amount = 1.1;
         let src_token_decimals = 6;
-        let amount_in = U256::from_str_radix(
-            &((amount * 10.0_f64.powi(src_token_decimals))
-                .floor()
-                .to_string()),
-            10,
-        )
-        .unwrap();
+        let amount_in = f64_to_units(amount, src_token_decimals)?;
 
         println!("amount_in: {}", amount_in);
         assert_eq!(amount_in, U256::from_str_radix("1100000", 10).unwrap());
         Ok(())
     }
+
+    #[test]
+    fn test_native_token_resolution() -> Result<()> {
+        let metadata: ChainMetadata = serde_json::from_value(serde_json::json!({
+            "id": "8453",
+            "nativeSymbol": "ETH",
+            "tokens": [
+                {"address": "0x4200000000000000000000000000000000000006", "symbol": "WETH", "decimals": 18},
+                {"address": "0x833589fCD6eDb6E08f4c7C32D4f71b54bdA02913", "symbol": "USDC", "decimals": 6}
+            ]
+        }))?;
+        let chain_data = ChainData::from_metadata(metadata)?;
+        assert_eq!(chain_data.name, "base");
+        let (eth, eth_decimals) = &chain_data.sym_to_addr_n_decimals["eth"];
+        assert!(is_native_token(eth));
+        assert_eq!(*eth_decimals, 18);
+        let (weth, _) = &chain_data.sym_to_addr_n_decimals["weth"];
+        let (usdc, _) = &chain_data.sym_to_addr_n_decimals["usdc"];
+        assert_eq!(chain_data.token("WETH")?, (weth.as_str(), 18));
+        assert_eq!(chain_data.token("u-s-d-c")?.0, usdc);
+        // Bridged USDC is a different token
+        assert!(chain_data.token("USDbC").is_err());
+        assert!(chain_data.token("usdc.e").is_err());
+        let metadata: ChainMetadata = serde_json::from_value(serde_json::json!({
+            "id": "42161",
+            "nativeSymbol": "ETH",
+            "tokens": [
+                {"address": "0xff970a61a04b1ca14834a43f5de4533ebddb5cc8", "symbol": "USDC.e", "decimals": 6},
+                {"address": "0x0000000000000000000000000000000000000001", "symbol": "USDCe", "decimals": 6}
+            ]
+        }))?;
+        let listed = ChainData::from_metadata(metadata)?;
+        assert_eq!(
+            listed.token("USDC.E")?.0,
+            "0xff970a61a04b1ca14834a43f5de4533ebddb5cc8"
+        );
+        // Two tokens read the same without separators, so neither is guessed
+        assert!(listed.token("usdc-e").is_err());
+        let unsupported = chain_data.token("mETH").unwrap_err();
+        assert_eq!(unsupported.chain, "base");
+        assert_eq!(unsupported.available, "eth, usdc, weth");
+
+        assert_eq!(chain_data.wrap_kind(eth, weth), Some(WrapKind::Wrap));
+        assert_eq!(chain_data.wrap_kind(weth, eth), Some(WrapKind::Unwrap));
+        assert_eq!(chain_data.wrap_kind(eth, usdc), None);
+        assert_eq!(chain_data.wrap_kind(usdc, weth), None);
+
+        let amount = U256::from(5u64);
+        assert_eq!(swap_value(false, amount, U256::ZERO)?, amount);
+        assert_eq!(swap_value(false, amount, amount)?, amount);
+        assert_eq!(swap_value(true, amount, U256::ZERO)?, U256::ZERO);
+        assert!(swap_value(true, amount, amount).is_err());
+        assert!(swap_value(false, amount, U256::from(4u64)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_estimated_gas() {
+        let build = |gas_limit: u64, estimated_gas: u64| BuildResponse {
+            result: Transaction {
+                from: Address::ZERO,
+                to: Address::ZERO,
+                value: U256::ZERO,
+                data: Bytes::new(),
+                gas_limit,
+                estimated_gas,
+                error: None,
+            },
+        };
+        assert_eq!(build(300_000, 250_000).estimated_gas(500_000), 250_000);
+        assert_eq!(build(300_000, 0).estimated_gas(500_000), 300_000);
+        // No estimate at all is priced at the fallback, not as free
+        assert_eq!(build(0, 0).estimated_gas(500_000), 500_000);
+    }
+
+    #[test]
+    fn test_dex_route() {
+        let quote =
+            |body: serde_json::Value| -> QuoteResult { serde_json::from_value(body).unwrap() };
+        let no_route = quote(serde_json::json!({
+            "isSwapPathExists": false, "dexAgg": null, "cexes": []
+        }));
+        assert_eq!(
+            no_route
+                .dex_route("usdc", "meth", &DexFilter::default())
+                .unwrap_err(),
+            QuoteError::NoRoute {
+                from: "usdc".to_string(),
+                to: "meth".to_string()
+            }
+        );
+        let cex_only = quote(serde_json::json!({
+            "isSwapPathExists": false, "dexAgg": null,
+            "cexes": [{"cexId": "binance", "amountIn": "1", "expectedAmountOut": "1"}]
+        }));
+        assert!(matches!(
+            cex_only.dex_route("usdc", "meth", &DexFilter::default()),
+            Err(QuoteError::CexOnlyRoute { cexes, .. }) if cexes == "binance"
+        ));
+
+        let routed = || {
+            quote(serde_json::json!({
+                "isSwapPathExists": true, "cexes": [], "dexAgg": {
+                    "blockNumber": 1, "fromToken": "0x1", "amountIn": "1", "toToken": "0x2",
+                    "weights": [], "totalAddrs": [], "srcIndices": [], "dstIndices": [],
+                    "expectedAmountOut": "1", "splitInfos": [{
+                        "srcIdx": 0, "dstIdx": 1, "weight": 1, "totalWeights": 1,
+                        "swapInfo": {
+                            "fromToken": "0x1", "toToken": "0x2",
+                            "dexId": "aerodrome", "pool": "0x3"
+                        }
+                    }]
+                }
+            }))
+        };
+        assert!(routed()
+            .dex_route("usdc", "meth", &DexFilter::default())
+            .is_ok());
+        let deny = DexFilter {
+            allow: vec![],
+            deny: vec!["aerodrome".to_string()],
+        };
+        assert!(matches!(
+            routed().dex_route("usdc", "meth", &deny),
+            Err(QuoteError::ExcludedDex { dex_ids, .. }) if dex_ids == "aerodrome"
+        ));
+    }
+
+    #[test]
+    fn test_portfolio_valuation() {
+        let balance = |symbol: &str, balance: f64| TokenBalance {
+            symbol: symbol.to_string(),
+            balance,
+            usd_value: None,
+        };
+        let mut portfolio = ChainPortfolio {
+            balances: vec![
+                balance("usdc", 100.0),
+                balance("wsteth", 0.5),
+                balance("aero", 10.0),
+            ],
+            positions: vec![ChainDetailFeed {
+                chain_id: 1,
+                protocol_details: vec![ProtocolDetailFeed {
+                    name: "Lido".to_string(),
+                    assets: vec![AssetFeed {
+                        symbol: "stETH".to_string(),
+                        balance: 1.5,
+                        underlying_amount: 1.5,
+                    }],
+                }],
+            }],
+        };
+        portfolio.apply_prices(&HashMap::from([
+            ("usdc".to_string(), 1.0),
+            ("wsteth".to_string(), 4000.0),
+        ]));
+        assert_eq!(portfolio.total_usd(), 2100.0);
+        assert_eq!(
+            portfolio.to_string(),
+            "  usdc: 100 ($100.00)\n  wsteth: 0.5 ($2000.00)\n  aero: 10 (unpriced)\nTotal: $2100.00\n\
+             Protocol positions:\n  chain 1 Lido: 1.5 stETH (underlying 1.5)"
+        );
+    }
+
+    #[test]
+    fn test_validate_slippage_bps() {
+        assert_eq!(validate_slippage_bps(1).unwrap(), 1);
+        assert_eq!(validate_slippage_bps(1_000).unwrap(), 1_000);
+        assert!(validate_slippage_bps(0).is_err());
+        assert!(validate_slippage_bps(1_001).is_err());
+    }
+
+    #[test]
+    fn test_swap_amount() {
+        assert_eq!(SwapAmount::parse("max").unwrap(), SwapAmount::Max);
+        assert_eq!(SwapAmount::parse(" MAX ").unwrap(), SwapAmount::Max);
+        assert_eq!(SwapAmount::parse("1.5").unwrap(), SwapAmount::Exact(1.5));
+        assert!(SwapAmount::parse("0").is_err());
+        assert!(SwapAmount::parse("-1").is_err());
+        assert!(SwapAmount::parse("all").is_err());
+
+        assert_eq!(SwapAmount::Max.resolve(2.0, 0.5), 1.5);
+        assert_eq!(SwapAmount::Exact(1.0).resolve(2.0, 0.0), 1.0);
+        assert_eq!(SwapAmount::Exact(3.0).resolve(2.0, 0.0), 2.0);
+        assert_eq!(SwapAmount::Exact(1.0).resolve(0.1, 0.5), 0.0);
+    }
+
+    #[test]
+    fn test_approval_calls() {
+        let required = U256::from(1_500_000u64);
+        assert_eq!(ApprovalPolicy::Exact.approval_amount(required), required);
+        assert_eq!(ApprovalPolicy::Max.approval_amount(required), U256::MAX);
+
+        let approve_call = IERC20::approveCall {
+            spender: Address::ZERO,
+            amount: required,
+        }
+        .abi_encode();
+        assert_eq!(&approve_call[..4], &[0x09, 0x5e, 0xa7, 0xb3]);
+        assert_eq!(&IERC20::allowanceCall::SELECTOR, &[0xdd, 0x62, 0xed, 0x3e]);
+    }
+
+    #[tokio::test]
+    async fn test_sign_permit_single() -> Result<()> {
+        let key = PrivateKeySigner::random();
+        let signer = WalletSigner::Local(key.clone());
+        let token = address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913");
+        let spender = address!("00000000000000000000000000000000000000aa");
+        let permit = build_permit_single(token, U256::MAX, spender, 3, 1_700_000_000);
+        assert_eq!(permit.details.amount, U160::MAX);
+        assert_eq!(permit.details.nonce, U48::from(3));
+        assert_eq!(permit.sigDeadline, U256::from(1_700_001_800u64));
+
+        let signature = sign_permit_single(&signer, &permit, 8453).await?;
+        assert_eq!(signature.len(), 2 + 65 * 2);
+        let domain = eip712_domain! {
+            name: "Permit2",
+            chain_id: 8453,
+            verifying_contract: PERMIT2_ADDRESS,
+        };
+        let parsed: alloy::primitives::PrimitiveSignature = signature.parse()?;
+        assert_eq!(
+            parsed.recover_address_from_prehash(&permit.eip712_signing_hash(&domain))?,
+            signer.address()
+        );
+
+        let body = PermitSingle::from(&permit);
+        assert_eq!(body.details.nonce, 3);
+        assert_eq!(body.sig_deadline, "1700001800");
+        assert!(is_native_token(
+            "0xEeeeeEeeeEeEeeEeEeEeeEEEeeeeEeeeeeeeEEeE"
+        ));
+        assert!(!is_native_token(&token.to_string()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_received_from_logs() {
+        let token = address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913");
+        let wallet = address!("dAf87a186345f26d107d000fAD351E79Ff696d2C");
+        let router = address!("00000000000000000000000000000000000000aa");
+        let transfer_log = |address: Address, to: Address, value: u64| Log {
+            inner: alloy::primitives::Log {
+                address,
+                data: IERC20::Transfer {
+                    from: router,
+                    to,
+                    value: U256::from(value),
+                }
+                .encode_log_data(),
+            },
+            ..Default::default()
+        };
+        let logs = vec![
+            transfer_log(token, wallet, 1_000_000),
+            transfer_log(token, router, 5),
+            transfer_log(router, wallet, 7),
+            transfer_log(token, wallet, 500_000),
+        ];
+        let received = received_from_logs(&logs, token, wallet);
+        assert_eq!(received, Some(U256::from(1_500_000u64)));
+        assert_eq!(
+            received_from_logs(&logs, token, router),
+            Some(U256::from(5u64))
+        );
+        assert_eq!(received_from_logs(&logs[2..3], token, wallet), None);
+
+        let min_amount_out = U256::from(1_500_000u64);
+        assert_eq!(
+            SlippageCheck::of(received, min_amount_out),
+            SlippageCheck::Passed
+        );
+        assert_eq!(
+            SlippageCheck::of(Some(U256::from(5u64)), min_amount_out),
+            SlippageCheck::Failed
+        );
+        assert_eq!(
+            SlippageCheck::of(None, min_amount_out),
+            SlippageCheck::Unverified
+        );
+        assert_eq!(
+            serde_json::to_value(SlippageCheck::Failed).unwrap(),
+            "failed slippage check"
+        );
+    }
     use dotenv::dotenv;
     use std::env;
     use std::sync::Arc;
@@ -475,7 +1771,8 @@ mod tests {
         let chain_id = provider.get_chain_id().await?;
 
         // Call the function
-        let result = get_chain_metadata(&base_url, chain_id).await?;
+        let client = Client::new();
+        let result = get_chain_metadata(&client, &base_url, chain_id).await?;
         let src_token = "eth";
         let dst_token = "weeth";
 
@@ -485,18 +1782,21 @@ mod tests {
             &result.sym_to_addr_n_decimals[&dst_token.to_lowercase()];
         let amount_in = U256::from_str_radix("1000000000000000", 10).unwrap();
         let quote = get_quote(
+            &client,
             &base_url,
             chain_id,
             src_token_addr,
             dst_token_addr,
             amount_in,
             None,
+            &DexFilter::default(),
         )
         .await?;
 
         let addr = "0xdAf87a186345f26d107d000fAD351E79Ff696d2C".to_string();
 
         let tx_data = get_tx_data(
+            &client,
             &base_url,
             chain_id,
             quote.result.dex_agg.unwrap(),
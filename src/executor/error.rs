@@ -10,6 +10,37 @@ pub enum InstrumentError {
     NotFound,
 }
 
+/// Eisen quote errors.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum QuoteError {
+    /// No route at all.
+    #[error("no swap path from {from} to {to}")]
+    NoRoute { from: String, to: String },
+    /// Only centralized exchanges can fill the swap, which cannot be executed on-chain.
+    #[error("only CEX routes ({cexes}) from {from} to {to}")]
+    CexOnlyRoute {
+        from: String,
+        to: String,
+        cexes: String,
+    },
+    /// The route goes through DEXes the swap's DEX filter excludes.
+    #[error("route from {from} to {to} uses excluded DEXes ({dex_ids})")]
+    ExcludedDex {
+        from: String,
+        to: String,
+        dex_ids: String,
+    },
+}
+
+/// A token symbol the chain metadata does not list.
+#[derive(Debug, Error, PartialEq, Eq)]
+#[error("token {symbol} is not supported on {chain}; available tokens: {available}")]
+pub struct UnsupportedToken {
+    pub symbol: String,
+    pub chain: String,
+    pub available: String,
+}
+
 /// Rest API Errors.
 #[derive(Debug, Error)]
 pub enum RestError {
@@ -0,0 +1,150 @@
+use crate::config::GasConfig;
+use alloy::eips::BlockNumberOrTag;
+use alloy::network::TransactionBuilder;
+use alloy::primitives::U256;
+use alloy::providers::Provider;
+use alloy::rpc::types::{FeeHistory, TransactionRequest};
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+/// Wei per gwei.
+pub const GWEI: u128 = 1_000_000_000;
+
+/// EIP-1559 fees for a transaction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GasFees {
+    pub max_fee_per_gas: u128,
+    pub max_priority_fee_per_gas: u128,
+}
+
+impl GasFees {
+    /// Set the fees on `tx`.
+    pub fn apply(&self, tx: TransactionRequest) -> TransactionRequest {
+        tx.with_max_fee_per_gas(self.max_fee_per_gas)
+            .with_max_priority_fee_per_gas(self.max_priority_fee_per_gas)
+    }
+
+    /// Worst case cost of `gas_limit` gas, in wei.
+    pub fn max_cost(&self, gas_limit: u64) -> U256 {
+        U256::from(gas_limit) * U256::from(self.max_fee_per_gas)
+    }
+}
+
+/// Fees from an `eth_feeHistory` response: the median of the per-block priority fee
+/// percentile as tip, and twice the next block's base fee plus the tip as max fee, so the
+/// transaction stays includable over a few blocks of rising base fees. Both are capped at
+/// the configured ceilings; a base fee above the ceiling is an error since the
+/// transaction could not be mined.
+pub fn fees_from_history(history: &FeeHistory, config: &GasConfig) -> Result<GasFees> {
+    let base_fee = history
+        .next_block_base_fee()
+        .ok_or_else(|| anyhow!("Fee history has no base fee"))?;
+    let max_fee_cap = gwei_to_wei(config.max_fee_gwei);
+    if base_fee > max_fee_cap {
+        return Err(anyhow!(
+            "Base fee {} gwei is above the {} gwei ceiling",
+            base_fee as f64 / GWEI as f64,
+            config.max_fee_gwei
+        ));
+    }
+
+    let mut rewards: Vec<u128> = history
+        .reward
+        .iter()
+        .flatten()
+        .filter_map(|block| block.first().copied())
+        .collect();
+    rewards.sort_unstable();
+    let priority_fee = rewards.get(rewards.len() / 2).copied().unwrap_or(0);
+
+    let max_priority_fee_per_gas = priority_fee.min(gwei_to_wei(config.max_priority_fee_gwei));
+    let max_fee_per_gas = base_fee
+        .saturating_mul(2)
+        .saturating_add(max_priority_fee_per_gas)
+        .min(max_fee_cap);
+    Ok(GasFees {
+        max_fee_per_gas,
+        max_priority_fee_per_gas: max_priority_fee_per_gas.min(max_fee_per_gas),
+    })
+}
+
+/// Estimate fees from the last `config.fee_history_blocks` blocks.
+pub async fn estimate_fees(provider: &dyn Provider, config: &GasConfig) -> Result<GasFees> {
+    let history = provider
+        .get_fee_history(
+            config.fee_history_blocks,
+            BlockNumberOrTag::Latest,
+            &[config.priority_fee_percentile],
+        )
+        .await?;
+    fees_from_history(&history, config)
+}
+
+/// Fail if spending `gas_cost` on a trade worth `trade_value` (both in wei of the native
+/// token) exceeds `max_cost_pct` percent of the trade.
+pub fn check_gas_cost(gas_cost: U256, trade_value: U256, max_cost_pct: f64) -> Result<()> {
+    // Compare in bps of integer wei to stay exact for large amounts
+    let max_cost_bps = (max_cost_pct * 100.0).max(0.0) as u64;
+    if gas_cost * U256::from(10_000u64) > trade_value * U256::from(max_cost_bps) {
+        return Err(anyhow!(
+            "Estimated gas cost {} wei exceeds {}% of the trade value {} wei",
+            gas_cost,
+            max_cost_pct,
+            trade_value
+        ));
+    }
+    Ok(())
+}
+
+pub fn gwei_to_wei(gwei: f64) -> u128 {
+    (gwei * GWEI as f64) as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(base_fees: Vec<u128>, rewards: Vec<u128>) -> FeeHistory {
+        FeeHistory {
+            base_fee_per_gas: base_fees,
+            reward: Some(rewards.into_iter().map(|r| vec![r]).collect()),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_fees_from_history() -> Result<()> {
+        let config = GasConfig {
+            max_fee_gwei: 50.0,
+            max_priority_fee_gwei: 2.0,
+            ..Default::default()
+        };
+        let fees = fees_from_history(
+            &history(vec![9 * GWEI, 10 * GWEI], vec![GWEI, 3 * GWEI, GWEI / 2]),
+            &config,
+        )?;
+        assert_eq!(fees.max_priority_fee_per_gas, GWEI);
+        assert_eq!(fees.max_fee_per_gas, 21 * GWEI);
+
+        // Tips are capped, and so is the max fee
+        let fees = fees_from_history(
+            &history(vec![20 * GWEI, 30 * GWEI], vec![5 * GWEI]),
+            &config,
+        )?;
+        assert_eq!(fees.max_priority_fee_per_gas, 2 * GWEI);
+        assert_eq!(fees.max_fee_per_gas, 50 * GWEI);
+
+        assert!(fees_from_history(&history(vec![60 * GWEI], vec![]), &config).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_gas_cost() {
+        let eth = U256::from(10u64).pow(U256::from(18u64));
+        let trade = eth;
+        assert!(check_gas_cost(eth / U256::from(100u64), trade, 1.0).is_ok());
+        assert!(check_gas_cost(eth / U256::from(99u64), trade, 1.0).is_err());
+        assert!(check_gas_cost(U256::ZERO, U256::ZERO, 1.0).is_ok());
+    }
+}
@@ -2,4 +2,12 @@ pub mod error;
 pub use super::constants;
 pub use crate::utils::price_data;
 pub mod binance;
+pub mod bridge;
+pub mod chains;
 pub mod eisen;
+pub mod gas;
+pub mod nonce;
+pub mod paper;
+pub mod queue;
+pub mod signer;
+pub mod simulation;
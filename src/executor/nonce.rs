@@ -0,0 +1,330 @@
+use crate::audit::{self, AuditKind};
+use crate::config::GasConfig;
+use crate::executor::gas::{gwei_to_wei, GasFees};
+use crate::executor::queue::JobTracker;
+use alloy::network::TransactionBuilder;
+use alloy::primitives::{Address, TxHash};
+use alloy::providers::{PendingTransactionError, Provider, WatchTxError};
+use alloy::rpc::types::{TransactionReceipt, TransactionRequest};
+use anyhow::{anyhow, Result};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// Hands out nonces per (chain id, signer) so transactions sent back to back, or from
+/// concurrent jobs, never share one.
+///
+/// The next nonce is the larger of the last one handed out plus one and the signer's
+/// pending transaction count, so transactions sent from elsewhere are picked up too.
+#[derive(Debug, Default)]
+pub struct NonceManager {
+    next: Mutex<HashMap<(u64, Address), u64>>,
+}
+
+impl NonceManager {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Reserve the next nonce of `address` on the provider's chain.
+    pub async fn reserve(&self, provider: &dyn Provider, address: Address) -> Result<u64> {
+        let chain_id = provider.get_chain_id().await?;
+        // Held across the RPC call so two reservations cannot read the same count
+        let mut next = self.next.lock().await;
+        let pending = provider.get_transaction_count(address).pending().await?;
+        let nonce = next
+            .get(&(chain_id, address))
+            .map_or(pending, |cached| (*cached).max(pending));
+        next.insert((chain_id, address), nonce + 1);
+        Ok(nonce)
+    }
+
+    /// Forget the cached nonce, e.g. after a reserved nonce was never used.
+    pub async fn reset(&self, chain_id: u64, address: Address) {
+        self.next.lock().await.remove(&(chain_id, address));
+    }
+}
+
+/// Fees of a replacement transaction: both fees raised by `bump_pct` percent, rounded up.
+/// Nodes reject replacements bumping either by less than 10%.
+pub fn bump_fees(fees: &GasFees, bump_pct: u64) -> GasFees {
+    let bump = |fee: u128| (fee * (100 + bump_pct as u128)).div_ceil(100).max(fee + 1);
+    GasFees {
+        max_fee_per_gas: bump(fees.max_fee_per_gas),
+        max_priority_fee_per_gas: bump(fees.max_priority_fee_per_gas),
+    }
+}
+
+/// Sends transactions from one signer with managed nonces and EIP-1559 fees.
+///
+/// A transaction not mined within the confirmation timeout is replaced by one with the
+/// same nonce and bumped fees, and resent the same way when it was dropped from the
+/// mempool, up to `max_replacements` times.
+pub struct TxSender<'a> {
+    pub provider: &'a dyn Provider,
+    pub from: Address,
+    pub nonces: &'a NonceManager,
+    pub fees: GasFees,
+    pub gas: &'a GasConfig,
+    /// Private relay transactions are submitted through instead of the public mempool;
+    /// receipts are still read from `provider`.
+    pub relay: Option<&'a dyn Provider>,
+    /// Leg of a queued job the transactions are recorded in.
+    pub tracker: Option<&'a JobTracker>,
+}
+
+/// How often the chain head is polled while waiting for confirmations.
+const CONFIRMATION_POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+impl TxSender<'_> {
+    /// Send `tx` and return its receipt once mined, whatever its status, and buried
+    /// under the configured number of confirmations when it succeeded.
+    pub async fn send(&self, tx: TransactionRequest) -> Result<TransactionReceipt> {
+        let mut index = None;
+        let result = self.send_with_replacements(tx, &mut index).await;
+        let tracked = self.tracker.zip(index);
+        let receipt = match result {
+            Ok(receipt) => receipt,
+            Err(e) => {
+                if let Some((tracker, index)) = tracked {
+                    tracker.failed(index, e.to_string());
+                }
+                return Err(e);
+            }
+        };
+        if let Some((tracker, index)) = tracked {
+            tracker.mined(
+                index,
+                receipt.transaction_hash,
+                receipt.block_number,
+                receipt.status(),
+            );
+        }
+
+        let result = self.wait_for_confirmations(receipt).await;
+        if let Some((tracker, index)) = tracked {
+            match &result {
+                Ok(receipt) if receipt.status() => {
+                    tracker.confirmed(index, self.gas.confirmations.max(1))
+                }
+                Ok(_) => {}
+                Err(e) => tracker.failed(index, e.to_string()),
+            }
+        }
+        result
+    }
+
+    /// Send `tx`, replacing it while it is not mined, and record the latest hash sent in
+    /// `index` of the tracked leg.
+    async fn send_with_replacements(
+        &self,
+        tx: TransactionRequest,
+        index: &mut Option<usize>,
+    ) -> Result<TransactionReceipt> {
+        let chain_id = self.provider.get_chain_id().await?;
+        let nonce = self.nonces.reserve(self.provider, self.from).await?;
+        let tx = tx.with_from(self.from).with_nonce(nonce);
+        let timeout = Duration::from_secs(self.gas.confirmation_timeout_secs);
+        let max_fee_cap = gwei_to_wei(self.gas.max_fee_gwei);
+
+        let mut fees = self.fees;
+        let mut sent: Vec<TxHash> = Vec::new();
+        for attempt in 0..=self.gas.max_replacements {
+            if attempt > 0 {
+                fees = bump_fees(&fees, self.gas.replacement_bump_pct);
+                if fees.max_fee_per_gas > max_fee_cap {
+                    println!(
+                        "Not replacing nonce {}: bumped fee would exceed the {} gwei ceiling",
+                        nonce, self.gas.max_fee_gwei
+                    );
+                    break;
+                }
+            }
+
+            let submitter = self.relay.unwrap_or(self.provider);
+            let request = fees.apply(tx.clone());
+            let target = format!("chain {} from {} nonce {}", chain_id, self.from, nonce);
+            let request_hash = audit::sha256_hex(&serde_json::to_vec(&request)?);
+            let pending = submitter.send_transaction(request).await;
+            match &pending {
+                Ok(pending) => audit::record(
+                    AuditKind::Transaction,
+                    target,
+                    pending.tx_hash().to_string(),
+                    Ok(format!("broadcast, attempt {}", attempt + 1)),
+                ),
+                Err(e) => audit::record(
+                    AuditKind::Transaction,
+                    target,
+                    request_hash,
+                    Err(e.to_string()),
+                ),
+            }
+            let pending = match pending {
+                Ok(pending) => pending,
+                Err(e) if sent.is_empty() => {
+                    // The nonce was never used, let the next reservation hand it out again
+                    self.nonces.reset(chain_id, self.from).await;
+                    return Err(e.into());
+                }
+                Err(e) => {
+                    println!("Replacement for nonce {} rejected: {}", nonce, e);
+                    break;
+                }
+            };
+            let hash = *pending.tx_hash();
+            sent.push(hash);
+            if let Some(tracker) = self.tracker {
+                match *index {
+                    Some(index) => tracker.replaced(index, hash),
+                    None => *index = Some(tracker.sent(&tx, hash)),
+                }
+            }
+
+            match pending.with_timeout(Some(timeout)).get_receipt().await {
+                Ok(receipt) => return Ok(receipt),
+                Err(PendingTransactionError::TxWatcher(WatchTxError::Timeout)) => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            // Any of the transactions sent for this nonce may have been mined meanwhile
+            if let Some(receipt) = self.find_receipt(&sent).await? {
+                return Ok(receipt);
+            }
+            let mined_count = self.provider.get_transaction_count(self.from).await?;
+            if mined_count > nonce {
+                return Err(anyhow!(
+                    "Nonce {} of {} was used by another transaction",
+                    nonce,
+                    self.from
+                ));
+            }
+            if self.is_dropped(&sent, nonce).await? {
+                println!(
+                    "Transaction {} was dropped, resending nonce {} with bumped fees",
+                    hash, nonce
+                );
+            } else {
+                println!(
+                    "Transaction {} not mined after {:?}, replacing nonce {} with bumped fees",
+                    hash, timeout, nonce
+                );
+            }
+        }
+
+        // Last chance for a transaction mined after the final timeout
+        if let Some(receipt) = self.find_receipt(&sent).await? {
+            return Ok(receipt);
+        }
+        self.nonces.reset(chain_id, self.from).await;
+        Err(anyhow!(
+            "Transaction with nonce {} not mined after {} attempts: {:?}",
+            nonce,
+            sent.len(),
+            sent
+        ))
+    }
+
+    /// Wait until a successful `receipt` is `confirmations` blocks deep, following it
+    /// when a reorg moves it to another block.
+    async fn wait_for_confirmations(
+        &self,
+        mut receipt: TransactionReceipt,
+    ) -> Result<TransactionReceipt> {
+        let confirmations = self.gas.confirmations;
+        if confirmations <= 1 || !receipt.status() {
+            return Ok(receipt);
+        }
+        let hash = receipt.transaction_hash;
+        let timeout = Duration::from_secs(self.gas.confirmation_timeout_secs * confirmations);
+        let deadline = Instant::now() + timeout;
+        loop {
+            let block_number = receipt
+                .block_number
+                .ok_or_else(|| anyhow!("Receipt of {} has no block number", hash))?;
+            let head = self.provider.get_block_number().await?;
+            if head + 1 >= block_number + confirmations {
+                // Make sure the transaction is still where it was when counting
+                match self.provider.get_transaction_receipt(hash).await? {
+                    Some(current) if current.block_number == receipt.block_number => {
+                        return Ok(current)
+                    }
+                    Some(current) => {
+                        println!(
+                            "Transaction {} moved from block {} to {:?} in a reorg",
+                            hash, block_number, current.block_number
+                        );
+                        if !current.status() {
+                            return Err(anyhow!("Transaction {} reverted after a reorg", hash));
+                        }
+                        receipt = current;
+                        continue;
+                    }
+                    None => return Err(anyhow!("Transaction {} was removed by a reorg", hash)),
+                }
+            }
+            if Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "Transaction {} not {} blocks deep after {:?}",
+                    hash,
+                    confirmations,
+                    timeout
+                ));
+            }
+            tokio::time::sleep(CONFIRMATION_POLL_INTERVAL).await;
+        }
+    }
+
+    async fn find_receipt(&self, hashes: &[TxHash]) -> Result<Option<TransactionReceipt>> {
+        for hash in hashes {
+            if let Some(receipt) = self.provider.get_transaction_receipt(*hash).await? {
+                return Ok(Some(receipt));
+            }
+        }
+        Ok(None)
+    }
+
+    /// True when none of `hashes` is known to the node (or relay) they were sent to any
+    /// more and its pending nonce of the signer has not advanced past `nonce`, so nothing
+    /// pending there holds it. Transactions sent through a relay never show up in the
+    /// public mempool.
+    async fn is_dropped(&self, hashes: &[TxHash], nonce: u64) -> Result<bool> {
+        let submitter = self.relay.unwrap_or(self.provider);
+        for hash in hashes {
+            if submitter.get_transaction_by_hash(*hash).await?.is_some() {
+                return Ok(false);
+            }
+        }
+        // A node may stop serving a hash it still holds the nonce for
+        let pending = submitter.get_transaction_count(self.from).pending().await?;
+        Ok(pending <= nonce)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::gas::GWEI;
+
+    #[test]
+    fn test_bump_fees() {
+        let fees = GasFees {
+            max_fee_per_gas: 20 * GWEI,
+            max_priority_fee_per_gas: GWEI,
+        };
+        let bumped = bump_fees(&fees, 15);
+        assert_eq!(bumped.max_fee_per_gas, 23 * GWEI);
+        assert_eq!(bumped.max_priority_fee_per_gas, 1_150_000_000);
+
+        // Zero and tiny fees still go up so the replacement is accepted
+        let bumped = bump_fees(
+            &GasFees {
+                max_fee_per_gas: 3,
+                max_priority_fee_per_gas: 0,
+            },
+            10,
+        );
+        assert_eq!(bumped.max_fee_per_gas, 4);
+        assert_eq!(bumped.max_priority_fee_per_gas, 1);
+    }
+}
@@ -0,0 +1,562 @@
+use crate::config::{BridgeConfig, DexFilter, PaperTradingConfig};
+use crate::executor::binance::{
+    FuturesMarket, OrderExecutor, OrderSide, OrderType, PlaceOrder, PositionSide, Status,
+    TimeInForce, UsdMarginFuturesOrder,
+};
+use crate::executor::bridge::{get_bridge_quote, BridgeReceipt, BridgeStatus};
+use crate::executor::eisen::{
+    quote_amount_out, ChainData, ChainPortfolio, SlippageCheck, SwapReceipt, TokenBalance,
+};
+use crate::feed::binance::BinancePriceFeed;
+use crate::portfolio::binance::{AccountInfo, Asset, Position};
+use crate::storage::WalStore;
+use crate::utils::rounding::{bps_to_fraction, f64_to_units, units_to_f64, BPS_DENOMINATOR};
+use alloy::primitives::Address;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use reqwest::Client as ReqwestClient;
+use rust_decimal::prelude::FromPrimitive;
+use rust_decimal::Decimal;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Simulates order placement by filling against the live Binance order book.
+///
+/// Market orders fill in full at the best bid/ask moved against us by the configured
+/// slippage; limit orders fill at that price if their limit crosses it and rest
+/// unfilled otherwise. Every order is recorded in a local WAL store keyed by client
+/// order id, which also backs the simulated account returned by `account_info`.
+pub struct PaperBinanceExecutor {
+    base_url: String,
+    client: ReqwestClient,
+    slippage_bps: u16,
+    initial_balance: f64,
+    orders: WalStore<UsdMarginFuturesOrder>,
+}
+
+impl PaperBinanceExecutor {
+    pub fn new(
+        base_url: String,
+        client: ReqwestClient,
+        config: &PaperTradingConfig,
+        data_dir: &Path,
+    ) -> Result<Self> {
+        Ok(Self {
+            base_url,
+            client,
+            slippage_bps: config.slippage_bps,
+            initial_balance: config.initial_balance,
+            orders: WalStore::open(data_dir, "paper_orders")?,
+        })
+    }
+
+    /// Simulated futures account: the initial USDT balance plus the PnL the fills realized,
+    /// and the net position per symbol at its average entry price.
+    pub fn account_info(&self) -> AccountInfo {
+        let mut orders: Vec<UsdMarginFuturesOrder> = self
+            .orders
+            .entries()
+            .into_iter()
+            .map(|(_, order)| order)
+            .collect();
+        orders.sort_by_key(|order| (order.update_time, order.order_id));
+        // Per symbol, the signed amount, its average entry price and the last update
+        let mut positions: BTreeMap<String, (Decimal, Decimal, u64)> = BTreeMap::new();
+        let mut realized = Decimal::ZERO;
+        for order in orders {
+            let signed_qty = match order.side {
+                OrderSide::Buy => order.executed_qty,
+                OrderSide::Sell => -order.executed_qty,
+            };
+            let (amount, entry_price, update_time) = positions
+                .entry(order.symbol.clone())
+                .or_insert((Decimal::ZERO, Decimal::ZERO, 0));
+            *update_time = (*update_time).max(order.update_time as u64);
+            if signed_qty.is_zero() {
+                continue;
+            }
+            if amount.is_zero() || amount.is_sign_negative() == signed_qty.is_sign_negative() {
+                let total = *amount + signed_qty;
+                *entry_price = (*entry_price * amount.abs() + order.avg_price * signed_qty.abs())
+                    / total.abs();
+                *amount = total;
+            } else {
+                // Closing realizes the PnL of the closed part; going past flat opens the
+                // rest at the fill price
+                let closed = signed_qty.abs().min(amount.abs());
+                let gain = closed * (order.avg_price - *entry_price);
+                realized += if amount.is_sign_negative() {
+                    -gain
+                } else {
+                    gain
+                };
+                *amount += signed_qty;
+                if amount.is_zero() {
+                    *entry_price = Decimal::ZERO;
+                } else if closed < signed_qty.abs() {
+                    *entry_price = order.avg_price;
+                }
+            }
+        }
+
+        let balance = Decimal::from_f64(self.initial_balance).unwrap_or_default() + realized;
+        AccountInfo {
+            total_maint_margin: Decimal::ZERO,
+            total_wallet_balance: balance,
+            total_unrealized_profit: Decimal::ZERO,
+            total_margin_balance: balance,
+            total_position_initial_margin: Decimal::ZERO,
+            total_open_order_initial_margin: Decimal::ZERO,
+            available_balance: balance,
+            assets: vec![Asset {
+                wallet_balance: balance,
+                unrealized_profit: Decimal::ZERO,
+                margin_balance: balance,
+                maint_margin: Decimal::ZERO,
+                initial_margin: Decimal::ZERO,
+                available_balance: balance,
+                update_time: 0,
+                asset: "USDT".to_string(),
+            }],
+            positions: positions
+                .into_iter()
+                .filter(|(_, (amount, _, _))| !amount.is_zero())
+                .map(|(symbol, (amount, entry_price, update_time))| Position {
+                    symbol,
+                    position_side: "BOTH".to_string(),
+                    position_amt: amount,
+                    unrealized_profit: Decimal::ZERO,
+                    notional: amount * entry_price,
+                    initial_margin: Decimal::ZERO,
+                    maint_margin: Decimal::ZERO,
+                    update_time,
+                })
+                .collect(),
+            spot_balances: Vec::new(),
+            margin_assets: Vec::new(),
+        }
+    }
+
+    async fn best_bid_ask(&self, symbol: &str) -> Result<(Decimal, Decimal)> {
+        let symbol = symbol.to_string();
+        let depth = BinancePriceFeed::new(&self.base_url, &self.client, &symbol)
+            .fetch_market_depth()
+            .await?;
+        let best = |levels: &[(String, String)]| {
+            levels
+                .first()
+                .and_then(|(price, _)| Decimal::from_str(price).ok())
+                .ok_or_else(|| anyhow!("Empty order book for {}", symbol))
+        };
+        Ok((best(&depth.bids)?, best(&depth.asks)?))
+    }
+}
+
+#[async_trait]
+impl OrderExecutor for PaperBinanceExecutor {
+    async fn place_order(
+        &self,
+        order: &PlaceOrder,
+        client_order_id: &str,
+    ) -> Result<UsdMarginFuturesOrder> {
+        if let Some(existing) = self.orders.get(client_order_id) {
+            println!(
+                "Paper order {} already recorded, not filling it again",
+                client_order_id
+            );
+            return Ok(existing);
+        }
+        if FuturesMarket::of_symbol(&order.symbol) == FuturesMarket::CoinMargined {
+            return Err(anyhow!(
+                "Paper trading does not support COIN-M symbol {}",
+                order.symbol
+            ));
+        }
+        let quantity = order
+            .quantity
+            .filter(|q| *q > Decimal::ZERO)
+            .ok_or_else(|| anyhow!("Paper order {} has no quantity", client_order_id))?;
+
+        let (best_bid, best_ask) = self.best_bid_ask(&order.symbol).await?;
+        let fill_price = simulate_fill_price(
+            order.side,
+            best_bid,
+            best_ask,
+            order.price,
+            self.slippage_bps,
+        );
+        let (status, executed_qty, avg_price) = match fill_price {
+            Some(price) => (Status::Filled, quantity, price),
+            None => (Status::New, Decimal::ZERO, Decimal::ZERO),
+        };
+
+        let filled = UsdMarginFuturesOrder {
+            client_order_id: client_order_id.to_string(),
+            cum_qty: Some(executed_qty),
+            cum_quote: Some(executed_qty * avg_price),
+            executed_qty,
+            order_id: self.orders.len() as i64 + 1,
+            avg_price,
+            orig_qty: quantity,
+            price: order.price.unwrap_or(Decimal::ZERO),
+            reduce_only: order.reduce_only.unwrap_or(false),
+            side: order.side,
+            position_side: PositionSide::Both,
+            status,
+            stop_price: Decimal::ZERO,
+            close_position: false,
+            symbol: order.symbol.clone(),
+            time_in_force: order.time_in_force.unwrap_or(TimeInForce::Gtc),
+            order_type: if order.price.is_some() {
+                OrderType::Limit
+            } else {
+                OrderType::Market
+            },
+            activate_price: None,
+            price_rate: None,
+            update_time: chrono::Utc::now().timestamp_millis(),
+            working_type: "CONTRACT_PRICE".to_string(),
+            price_protect: false,
+        };
+        self.orders.put(client_order_id, filled.clone())?;
+        println!(
+            "Paper order {} {:?} {} {}: {:?} at {}",
+            client_order_id, order.side, quantity, order.symbol, status, avg_price
+        );
+        Ok(filled)
+    }
+}
+
+/// Simulated on-chain wallet for paper trading: swaps and bridge transfers are quoted
+/// but never sent, and what they would have moved is kept as balance changes on top of
+/// the live wallet, in a local WAL store keyed by chain and token.
+pub struct PaperOnchain {
+    deltas: WalStore<f64>,
+    // Held between reading and writing a delta, so concurrent legs add up
+    lock: Mutex<()>,
+}
+
+impl fmt::Debug for PaperOnchain {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PaperOnchain")
+            .field("deltas", &self.deltas.entries())
+            .finish()
+    }
+}
+
+impl PaperOnchain {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        Ok(Self {
+            deltas: WalStore::open(data_dir, "paper_balances")?,
+            lock: Mutex::new(()),
+        })
+    }
+
+    fn key(chain_id: u64, token: &str) -> String {
+        format!("{}:{}", chain_id, token.to_lowercase())
+    }
+
+    /// `live` balance of `token` moved by the simulated legs.
+    pub fn balance(&self, chain_id: u64, token: &str, live: f64) -> f64 {
+        let delta = self.deltas.get(&Self::key(chain_id, token)).unwrap_or(0.0);
+        (live + delta).max(0.0)
+    }
+
+    fn add(&self, chain_id: u64, token: &str, amount: f64) -> Result<()> {
+        let _lock = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+        let key = Self::key(chain_id, token);
+        let delta = self.deltas.get(&key).unwrap_or(0.0);
+        self.deltas.put(key, delta + amount)
+    }
+
+    /// `portfolio` of `chain_id` with the simulated balances; changed balances lose
+    /// their USD value unless the token's unit price is known from it.
+    pub fn apply_to(&self, chain_id: u64, portfolio: &mut ChainPortfolio) {
+        let prefix = format!("{}:", chain_id);
+        for (key, delta) in self.deltas.entries() {
+            let Some(token) = key.strip_prefix(&prefix) else {
+                continue;
+            };
+            match portfolio
+                .balances
+                .iter_mut()
+                .find(|balance| balance.symbol.eq_ignore_ascii_case(token))
+            {
+                Some(balance) => {
+                    let unit_price = balance
+                        .usd_value
+                        .filter(|_| balance.balance > 0.0)
+                        .map(|usd| usd / balance.balance);
+                    balance.balance = (balance.balance + delta).max(0.0);
+                    balance.usd_value = unit_price.map(|price| price * balance.balance);
+                }
+                None if delta > 0.0 => portfolio.balances.push(TokenBalance {
+                    symbol: token.to_string(),
+                    balance: delta,
+                    usd_value: None,
+                }),
+                None => {}
+            }
+        }
+    }
+
+    /// Quote `amount` of `token_in` into `token_out` on Eisen and book the swap as if it
+    /// filled at the quote.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn swap(
+        &self,
+        client: &ReqwestClient,
+        base_url: &str,
+        chain_data: &ChainData,
+        token_in: &str,
+        token_out: &str,
+        amount: f64,
+        slippage_bps: u16,
+        dex_filter: &DexFilter,
+    ) -> Result<SwapReceipt> {
+        let expected_amount_out = quote_amount_out(
+            client, base_url, chain_data, token_in, token_out, amount, dex_filter,
+        )
+        .await?;
+        self.add(chain_data.id(), token_in, -amount)?;
+        self.add(chain_data.id(), token_out, expected_amount_out)?;
+        println!(
+            "Paper swap on chain {}: {} {} -> {} {}",
+            chain_data.id(),
+            amount,
+            token_in,
+            expected_amount_out,
+            token_out
+        );
+        Ok(SwapReceipt {
+            tx_hash: Default::default(),
+            block_number: None,
+            gas_used: 0,
+            effective_gas_price: 0,
+            amount_out: Some(expected_amount_out),
+            expected_amount_out,
+            min_amount_out: expected_amount_out * (1.0 - bps_to_fraction(slippage_bps)),
+            slippage_check: SlippageCheck::Passed,
+        })
+    }
+
+    /// Quote the transfer on LI.FI and book it as if it arrived at the quote.
+    #[allow(clippy::too_many_arguments)]
+    pub async fn bridge(
+        &self,
+        client: &ReqwestClient,
+        config: &BridgeConfig,
+        from_chain: &ChainData,
+        to_chain: &ChainData,
+        token: &str,
+        to_token: &str,
+        amount: f64,
+        from: Address,
+        slippage_bps: u16,
+    ) -> Result<BridgeReceipt> {
+        let (src_token_addr, src_token_decimals) = from_chain.token(token)?;
+        let (dst_token_addr, dst_token_decimals) = to_chain.token(to_token)?;
+        let quote = get_bridge_quote(
+            client,
+            &config.base_url,
+            from_chain.id(),
+            to_chain.id(),
+            src_token_addr,
+            dst_token_addr,
+            f64_to_units(amount, src_token_decimals)?,
+            from,
+            slippage_bps,
+        )
+        .await?;
+        let expected_amount_out = units_to_f64(&quote.estimate.to_amount, dst_token_decimals)?;
+        self.add(from_chain.id(), token, -amount)?;
+        self.add(to_chain.id(), to_token, expected_amount_out)?;
+        println!(
+            "Paper bridge transfer via {}: {} {} on chain {} -> {} {} on chain {}",
+            quote.tool,
+            amount,
+            token,
+            from_chain.id(),
+            expected_amount_out,
+            to_token,
+            to_chain.id()
+        );
+        Ok(BridgeReceipt {
+            tool: quote.tool,
+            tx_hash: Default::default(),
+            gas_used: 0,
+            effective_gas_price: 0,
+            expected_amount_out,
+            min_amount_out: units_to_f64(&quote.estimate.to_amount_min, dst_token_decimals)?,
+            delivery: BridgeStatus::Completed,
+            amount_out: Some(expected_amount_out),
+            receiving_tx_hash: None,
+        })
+    }
+}
+
+/// Fill price for an order against the top of book, `None` if a limit order would rest.
+pub fn simulate_fill_price(
+    side: OrderSide,
+    best_bid: Decimal,
+    best_ask: Decimal,
+    limit: Option<Decimal>,
+    slippage_bps: u16,
+) -> Option<Decimal> {
+    let slippage = Decimal::from(slippage_bps) / Decimal::from(BPS_DENOMINATOR);
+    match side {
+        OrderSide::Buy => {
+            let price = best_ask * (Decimal::ONE + slippage);
+            match limit {
+                Some(limit) if limit < best_ask => None,
+                Some(limit) => Some(price.min(limit)),
+                None => Some(price),
+            }
+        }
+        OrderSide::Sell => {
+            let price = best_bid * (Decimal::ONE - slippage);
+            match limit {
+                Some(limit) if limit > best_bid => None,
+                Some(limit) => Some(price.max(limit)),
+                None => Some(price),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_simulate_fill_price() {
+        let (bid, ask) = (dec("99"), dec("100"));
+        assert_eq!(
+            simulate_fill_price(OrderSide::Buy, bid, ask, None, 10),
+            Some(dec("100.1"))
+        );
+        assert_eq!(
+            simulate_fill_price(OrderSide::Sell, bid, ask, None, 10),
+            Some(dec("98.901"))
+        );
+        // Crossing limits are capped at the limit, non-crossing ones rest
+        assert_eq!(
+            simulate_fill_price(OrderSide::Buy, bid, ask, Some(dec("100.05")), 10),
+            Some(dec("100.05"))
+        );
+        assert_eq!(
+            simulate_fill_price(OrderSide::Buy, bid, ask, Some(dec("99.5")), 10),
+            None
+        );
+        assert_eq!(
+            simulate_fill_price(OrderSide::Sell, bid, ask, Some(dec("99.5")), 10),
+            None
+        );
+    }
+
+    #[test]
+    fn test_account_info_nets_positions() -> Result<()> {
+        let dir = tempdir()?;
+        let executor = PaperBinanceExecutor::new(
+            "https://fapi.binance.com".to_string(),
+            ReqwestClient::new(),
+            &PaperTradingConfig::default(),
+            dir.path(),
+        )?;
+        let order = |side, qty: &str| -> UsdMarginFuturesOrder {
+            serde_json::from_value(serde_json::json!({
+                "clientOrderId": "x", "executedQty": qty, "orderId": 1, "avgPrice": "2000",
+                "origQty": qty, "price": "0", "reduceOnly": false, "side": side,
+                "positionSide": "BOTH", "status": "FILLED", "stopPrice": "0",
+                "closePosition": false, "symbol": "ETHUSDT", "timeInForce": "GTC",
+                "type": "MARKET", "updateTime": 1, "workingType": "CONTRACT_PRICE",
+                "priceProtect": false
+            }))
+            .unwrap()
+        };
+        executor.orders.put("a", order("SELL", "0.5"))?;
+        executor.orders.put("b", order("BUY", "0.2"))?;
+
+        let account = executor.account_info();
+        assert_eq!(account.total_wallet_balance, Decimal::new(10000, 0));
+        assert_eq!(account.positions.len(), 1);
+        assert_eq!(account.positions[0].position_amt, Decimal::new(-3, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_account_info_realizes_pnl() -> Result<()> {
+        let dir = tempdir()?;
+        let executor = PaperBinanceExecutor::new(
+            "https://fapi.binance.com".to_string(),
+            ReqwestClient::new(),
+            &PaperTradingConfig::default(),
+            dir.path(),
+        )?;
+        let order = |side, qty: &str, price: &str, update_time: u64| -> UsdMarginFuturesOrder {
+            serde_json::from_value(serde_json::json!({
+                "clientOrderId": "x", "executedQty": qty, "orderId": 1, "avgPrice": price,
+                "origQty": qty, "price": "0", "reduceOnly": false, "side": side,
+                "positionSide": "BOTH", "status": "FILLED", "stopPrice": "0",
+                "closePosition": false, "symbol": "ETHUSDT", "timeInForce": "GTC",
+                "type": "MARKET", "updateTime": update_time, "workingType": "CONTRACT_PRICE",
+                "priceProtect": false
+            }))
+            .unwrap()
+        };
+        // Covering 0.2 of a 0.5 short 100 higher loses 20
+        executor.orders.put("b", order("BUY", "0.2", "2100", 2))?;
+        executor.orders.put("a", order("SELL", "0.5", "2000", 1))?;
+
+        let account = executor.account_info();
+        assert_eq!(account.total_wallet_balance, dec("9980"));
+        assert_eq!(account.available_balance, dec("9980"));
+        assert_eq!(account.positions[0].position_amt, dec("-0.3"));
+        assert_eq!(account.positions[0].notional, dec("-600"));
+
+        // Buying past flat opens the rest long at the fill price
+        executor.orders.put("c", order("BUY", "0.5", "1900", 3))?;
+        let account = executor.account_info();
+        assert_eq!(account.total_wallet_balance, dec("10010"));
+        assert_eq!(account.positions[0].position_amt, dec("0.2"));
+        assert_eq!(account.positions[0].notional, dec("380"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_paper_onchain_balances() -> Result<()> {
+        let dir = tempdir()?;
+        let paper = PaperOnchain::open(dir.path())?;
+        paper.add(8453, "USDC", -40.0)?;
+        paper.add(8453, "weth", 0.01)?;
+        assert_eq!(paper.balance(8453, "usdc", 100.0), 60.0);
+        assert_eq!(paper.balance(8453, "USDC", 10.0), 0.0);
+        assert_eq!(paper.balance(1, "USDC", 100.0), 100.0);
+
+        let mut portfolio = ChainPortfolio {
+            balances: vec![TokenBalance {
+                symbol: "USDC".to_string(),
+                balance: 100.0,
+                usd_value: Some(100.0),
+            }],
+            positions: Vec::new(),
+        };
+        paper.apply_to(8453, &mut portfolio);
+        assert_eq!(portfolio.balance_of("usdc"), 60.0);
+        assert_eq!(portfolio.balances[0].usd_value, Some(60.0));
+        assert_eq!(portfolio.balance_of("weth"), 0.01);
+
+        // The changes survive a restart
+        let reopened = PaperOnchain::open(dir.path())?;
+        assert_eq!(reopened.balance(8453, "weth", 0.0), 0.01);
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+use crate::executor::eisen::{IERC20, IWETH};
+use crate::notify::Notifier;
+use alloy::primitives::TxHash;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol_types::SolCall;
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+/// Jobs whose on-chain legs are kept for the jobs API; older ones are dropped.
+const MAX_JOBS: usize = 100;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LegKind {
+    Bridge,
+    Swap,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum LegState {
+    /// Waiting for the legs it depends on.
+    Waiting,
+    Running,
+    Done,
+    /// Not executed, e.g. for lack of a route or balance.
+    Skipped,
+    Failed,
+}
+
+/// Where a transaction stands: sent, included in a block, or as deep as the configured
+/// number of confirmations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TxState {
+    Pending,
+    Mined,
+    Confirmed,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedTx {
+    /// "approve", "wrap", "unwrap", or the kind of the leg.
+    pub label: String,
+    pub state: TxState,
+    /// Latest hash sent; replacements overwrite it.
+    pub tx_hash: TxHash,
+    pub block_number: Option<u64>,
+    pub confirmations: u64,
+    pub error: Option<String>,
+}
+
+/// One bridge transfer or swap of a job and the transactions sent for it.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct QueuedLeg {
+    pub kind: LegKind,
+    pub chain_id: u64,
+    pub description: String,
+    /// Legs that have to finish first.
+    pub depends_on: Vec<usize>,
+    pub state: LegState,
+    pub transactions: Vec<QueuedTx>,
+}
+
+impl QueuedLeg {
+    pub fn new(kind: LegKind, chain_id: u64, description: String, depends_on: Vec<usize>) -> Self {
+        Self {
+            kind,
+            chain_id,
+            description,
+            depends_on,
+            state: LegState::Waiting,
+            transactions: Vec::new(),
+        }
+    }
+}
+
+/// Job id and legs of a queued job.
+type QueuedJob = (String, Vec<QueuedLeg>);
+
+/// On-chain legs of recent jobs, updated as their transactions progress.
+#[derive(Debug, Clone, Default)]
+pub struct TxQueue {
+    jobs: Arc<Mutex<VecDeque<QueuedJob>>>,
+    // Told about every confirmed transaction
+    notifier: Option<Notifier>,
+}
+
+impl TxQueue {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_notifier(notifier: Notifier) -> Self {
+        Self {
+            notifier: Some(notifier),
+            ..Self::default()
+        }
+    }
+
+    /// Queue the legs of `job_id`, replacing those of an earlier run of the same job.
+    pub fn plan(&self, job_id: &str, legs: Vec<QueuedLeg>) -> JobTracker {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.retain(|(id, _)| id != job_id);
+        if jobs.len() >= MAX_JOBS {
+            jobs.pop_front();
+        }
+        jobs.push_back((job_id.to_string(), legs));
+        JobTracker {
+            queue: self.clone(),
+            job_id: job_id.to_string(),
+            leg: 0,
+        }
+    }
+
+    pub fn job(&self, job_id: &str) -> Option<Vec<QueuedLeg>> {
+        let jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.iter()
+            .find(|(id, _)| id == job_id)
+            .map(|(_, legs)| legs.clone())
+    }
+
+    fn update<T>(
+        &self,
+        job_id: &str,
+        leg: usize,
+        f: impl FnOnce(&mut QueuedLeg) -> T,
+    ) -> Option<T> {
+        let mut jobs = self.jobs.lock().unwrap_or_else(|e| e.into_inner());
+        jobs.iter_mut()
+            .find(|(id, _)| id == job_id)
+            .and_then(|(_, legs)| legs.get_mut(leg))
+            .map(f)
+    }
+}
+
+/// Records the progress of one leg of a job in the queue.
+#[derive(Debug, Clone)]
+pub struct JobTracker {
+    queue: TxQueue,
+    job_id: String,
+    leg: usize,
+}
+
+impl JobTracker {
+    /// Tracker of leg `leg` of the same job.
+    pub fn leg(&self, leg: usize) -> Self {
+        Self {
+            leg,
+            ..self.clone()
+        }
+    }
+
+    pub fn set_state(&self, state: LegState) {
+        self.queue
+            .update(&self.job_id, self.leg, |leg| leg.state = state);
+    }
+
+    /// Record `tx` as sent with `hash` and return its index in the leg.
+    pub fn sent(&self, tx: &TransactionRequest, hash: TxHash) -> usize {
+        self.queue
+            .update(&self.job_id, self.leg, |leg| {
+                leg.transactions.push(QueuedTx {
+                    label: tx_label(tx, leg.kind),
+                    state: TxState::Pending,
+                    tx_hash: hash,
+                    block_number: None,
+                    confirmations: 0,
+                    error: None,
+                });
+                leg.transactions.len() - 1
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn replaced(&self, index: usize, hash: TxHash) {
+        self.update_tx(index, |tx| tx.tx_hash = hash);
+    }
+
+    pub fn mined(&self, index: usize, hash: TxHash, block_number: Option<u64>, success: bool) {
+        self.update_tx(index, |tx| {
+            tx.tx_hash = hash;
+            tx.block_number = block_number;
+            tx.confirmations = 1;
+            if success {
+                tx.state = TxState::Mined;
+            } else {
+                tx.state = TxState::Failed;
+                tx.error = Some("reverted".to_string());
+            }
+        });
+    }
+
+    pub fn confirmed(&self, index: usize, confirmations: u64) {
+        let confirmed = self.queue.update(&self.job_id, self.leg, |leg| {
+            let tx = leg.transactions.get_mut(index)?;
+            tx.state = TxState::Confirmed;
+            tx.confirmations = confirmations;
+            Some((leg.description.clone(), leg.chain_id, tx.clone()))
+        });
+        if let (Some(notifier), Some(Some((leg, chain_id, tx)))) = (&self.queue.notifier, confirmed)
+        {
+            notifier.tx_confirmed(&self.job_id, &leg, chain_id, &tx);
+        }
+    }
+
+    pub fn failed(&self, index: usize, error: String) {
+        self.update_tx(index, |tx| {
+            tx.state = TxState::Failed;
+            tx.error = Some(error);
+        });
+    }
+
+    fn update_tx(&self, index: usize, f: impl FnOnce(&mut QueuedTx)) {
+        self.queue.update(&self.job_id, self.leg, |leg| {
+            if let Some(tx) = leg.transactions.get_mut(index) {
+                f(tx)
+            }
+        });
+    }
+}
+
+fn tx_label(tx: &TransactionRequest, kind: LegKind) -> String {
+    let selector = tx.input.input().and_then(|input| input.get(..4));
+    let label = match selector {
+        Some(selector) if selector == IERC20::approveCall::SELECTOR => "approve",
+        Some(selector) if selector == IWETH::depositCall::SELECTOR => "wrap",
+        Some(selector) if selector == IWETH::withdrawCall::SELECTOR => "unwrap",
+        _ => match kind {
+            LegKind::Bridge => "bridge",
+            LegKind::Swap => "swap",
+        },
+    };
+    label.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloy::network::TransactionBuilder;
+    use alloy::primitives::{Address, U256};
+
+    #[test]
+    fn test_queue_tracks_legs() {
+        let queue = TxQueue::new();
+        let tracker = queue.plan(
+            "job-1",
+            vec![
+                QueuedLeg::new(LegKind::Swap, 8453, "usdc -> eth".to_string(), vec![]),
+                QueuedLeg::new(LegKind::Swap, 8453, "eth -> weeth".to_string(), vec![0]),
+            ],
+        );
+        let leg = tracker.leg(1);
+        leg.set_state(LegState::Running);
+        let approve = TransactionRequest::default().with_input(
+            IERC20::approveCall {
+                spender: Address::ZERO,
+                amount: U256::MAX,
+            }
+            .abi_encode(),
+        );
+        let index = leg.sent(&approve, TxHash::repeat_byte(1));
+        leg.replaced(index, TxHash::repeat_byte(2));
+        leg.mined(index, TxHash::repeat_byte(2), Some(10), true);
+        leg.confirmed(index, 3);
+        let swap = leg.sent(&TransactionRequest::default(), TxHash::repeat_byte(3));
+        leg.failed(swap, "dropped".to_string());
+
+        let legs = queue.job("job-1").unwrap();
+        assert_eq!(legs[0].state, LegState::Waiting);
+        assert_eq!(legs[1].state, LegState::Running);
+        let txs = &legs[1].transactions;
+        assert_eq!(txs[0].label, "approve");
+        assert_eq!(txs[0].state, TxState::Confirmed);
+        assert_eq!(txs[0].tx_hash, TxHash::repeat_byte(2));
+        assert_eq!(txs[0].confirmations, 3);
+        assert_eq!(txs[1].label, "swap");
+        assert_eq!(txs[1].state, TxState::Failed);
+        assert!(queue.job("job-2").is_none());
+
+        for i in 0..MAX_JOBS {
+            queue.plan(&format!("other-{}", i), vec![]);
+        }
+        assert!(queue.job("job-1").is_none());
+    }
+}
@@ -0,0 +1,223 @@
+use crate::config::SignerConfig;
+use alloy::consensus::SignableTransaction;
+use alloy::network::{EthereumWallet, TxSigner};
+use alloy::primitives::{Address, ChainId, PrimitiveSignature, B256};
+use alloy::signers::local::PrivateKeySigner;
+use alloy::signers::Signer;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use std::env;
+
+/// Key of a wallet transactions and permits are signed with.
+#[derive(Debug, Clone)]
+pub enum WalletSigner {
+    Local(PrivateKeySigner),
+    Remote(RemoteSigner),
+    /// Known address without a key; everything that needs a signature is refused.
+    ReadOnly(Address),
+}
+
+impl WalletSigner {
+    /// Load the signer `config` describes for `address`, checking the key belongs to it.
+    /// Remote signers are called through `client`.
+    pub fn load(address: Address, config: &SignerConfig, client: &reqwest::Client) -> Result<Self> {
+        let signer = match config {
+            SignerConfig::Env { var } => Self::Local(private_key_from_env(var)?),
+            SignerConfig::Keystore { path, password_env } => {
+                let password = env::var(password_env)
+                    .map_err(|_| anyhow!("{} not set in environment", password_env))?;
+                let wallet = ethers::signers::LocalWallet::decrypt_keystore(path, password)
+                    .map_err(|e| anyhow!("Failed to decrypt keystore {:?}: {}", path, e))?;
+                Self::Local(PrivateKeySigner::from_slice(
+                    wallet.signer().to_bytes().as_slice(),
+                )?)
+            }
+            SignerConfig::Remote {
+                url,
+                auth_token_env,
+            } => {
+                let auth_token = match auth_token_env {
+                    Some(var) => {
+                        Some(env::var(var).map_err(|_| anyhow!("{} not set in environment", var))?)
+                    }
+                    None => None,
+                };
+                Self::Remote(RemoteSigner {
+                    client: client.clone(),
+                    url: url.clone(),
+                    auth_token,
+                    address,
+                    chain_id: None,
+                })
+            }
+            SignerConfig::ReadOnly => Self::ReadOnly(address),
+        };
+        if signer.address() != address {
+            return Err(anyhow!(
+                "Signer configured for wallet {} holds the key of {}",
+                address,
+                signer.address()
+            ));
+        }
+        Ok(signer)
+    }
+
+    pub fn address(&self) -> Address {
+        match self {
+            Self::Local(signer) => signer.address(),
+            Self::Remote(signer) => signer.address,
+            Self::ReadOnly(address) => *address,
+        }
+    }
+
+    /// Wallet for a provider to sign transactions with.
+    pub fn wallet(&self) -> Result<EthereumWallet> {
+        match self {
+            Self::Local(signer) => Ok(EthereumWallet::from(signer.clone())),
+            Self::Remote(signer) => Ok(EthereumWallet::from(signer.clone())),
+            Self::ReadOnly(address) => Err(anyhow!("Wallet {} is read-only", address)),
+        }
+    }
+
+    pub async fn sign_hash(&self, hash: &B256) -> Result<PrimitiveSignature> {
+        match self {
+            Self::Local(signer) => Ok(signer.sign_hash(hash).await?),
+            Self::Remote(signer) => Ok(signer.sign_hash(hash).await?),
+            Self::ReadOnly(address) => Err(anyhow!("Wallet {} is read-only", address)),
+        }
+    }
+}
+
+/// Hex private key (with or without 0x) held in the environment variable `var`.
+pub fn private_key_from_env(var: &str) -> Result<PrivateKeySigner> {
+    let key = env::var(var).map_err(|_| anyhow!("{} not set in environment", var))?;
+    key.trim_start_matches("0x")
+        .parse()
+        .map_err(|_| anyhow!("Invalid private key format in {}", var))
+}
+
+#[derive(Serialize)]
+struct SignHashRequest {
+    address: Address,
+    hash: B256,
+}
+
+#[derive(Deserialize)]
+struct SignHashResponse {
+    signature: PrimitiveSignature,
+}
+
+/// Signs through an HTTP service holding the key (a KMS, HSM or hardware wallet proxy).
+///
+/// The service gets `POST {url}` with `{"address": "0x..", "hash": "0x.."}` and answers
+/// `{"signature": "0x.."}`, the 65 byte signature of the raw 32 byte hash.
+#[derive(Debug, Clone)]
+pub struct RemoteSigner {
+    client: reqwest::Client,
+    url: String,
+    auth_token: Option<String>,
+    address: Address,
+    chain_id: Option<ChainId>,
+}
+
+#[async_trait]
+impl Signer<PrimitiveSignature> for RemoteSigner {
+    async fn sign_hash(&self, hash: &B256) -> alloy::signers::Result<PrimitiveSignature> {
+        let mut request = self.client.post(&self.url).json(&SignHashRequest {
+            address: self.address,
+            hash: *hash,
+        });
+        if let Some(token) = &self.auth_token {
+            request = request.bearer_auth(token);
+        }
+        let response = request
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(alloy::signers::Error::other)?;
+        let signature = response
+            .json::<SignHashResponse>()
+            .await
+            .map_err(alloy::signers::Error::other)?
+            .signature;
+
+        // Never hand out a signature that does not come from the wallet's key
+        let signer = signature.recover_address_from_prehash(hash)?;
+        if signer != self.address {
+            return Err(alloy::signers::Error::other(format!(
+                "Remote signer signed with {} instead of {}",
+                signer, self.address
+            )));
+        }
+        Ok(signature)
+    }
+
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    fn chain_id(&self) -> Option<ChainId> {
+        self.chain_id
+    }
+
+    fn set_chain_id(&mut self, chain_id: Option<ChainId>) {
+        self.chain_id = chain_id;
+    }
+}
+
+#[async_trait]
+impl TxSigner<PrimitiveSignature> for RemoteSigner {
+    fn address(&self) -> Address {
+        self.address
+    }
+
+    async fn sign_transaction(
+        &self,
+        tx: &mut dyn SignableTransaction<PrimitiveSignature>,
+    ) -> alloy::signers::Result<PrimitiveSignature> {
+        if let Some(chain_id) = self.chain_id {
+            if !tx.set_chain_id_checked(chain_id) {
+                return Err(alloy::signers::Error::TransactionChainIdMismatch {
+                    signer: chain_id,
+                    tx: tx.chain_id().unwrap_or_default(),
+                });
+            }
+        }
+        self.sign_hash(&tx.signature_hash()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_load_signers() -> Result<()> {
+        let key = PrivateKeySigner::random();
+        env::set_var(
+            "CHILL_PM_TEST_SIGNER_KEY",
+            format!("0x{}", hex::encode(key.to_bytes())),
+        );
+        let env_config = SignerConfig::Env {
+            var: "CHILL_PM_TEST_SIGNER_KEY".to_string(),
+        };
+        let client = reqwest::Client::new();
+        let signer = WalletSigner::load(key.address(), &env_config, &client)?;
+        assert!(signer.wallet().is_ok());
+        let hash = B256::repeat_byte(7);
+        let signature = signer.sign_hash(&hash).await?;
+        assert_eq!(
+            signature.recover_address_from_prehash(&hash)?,
+            key.address()
+        );
+        // The key has to belong to the wallet it is configured for
+        assert!(WalletSigner::load(Address::repeat_byte(1), &env_config, &client).is_err());
+
+        let read_only = WalletSigner::load(key.address(), &SignerConfig::ReadOnly, &client)?;
+        assert_eq!(read_only.address(), key.address());
+        assert!(read_only.wallet().is_err());
+        assert!(read_only.sign_hash(&hash).await.is_err());
+        Ok(())
+    }
+}
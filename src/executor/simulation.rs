@@ -0,0 +1,226 @@
+use crate::config::SimulationConfig;
+use crate::executor::eisen::{received_from_logs, IERC20};
+use alloy::network::TransactionBuilder;
+use alloy::primitives::{address, Address, U256};
+use alloy::providers::ext::AnvilApi;
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy::rpc::types::simulate::{SimBlock, SimCallResult, SimulatePayload};
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol_types::{decode_revert_reason, SolCall};
+use alloy::transports::TransportError;
+use anyhow::{anyhow, Result};
+
+/// Approval the swap relies on, replayed on the fork since it may postdate the fork block.
+#[derive(Debug, Clone, Copy)]
+pub struct ForkApproval {
+    pub token: Address,
+    pub spender: Address,
+    pub amount: U256,
+}
+
+/// Address `eth_simulateV1` reports native token transfers from, as ERC-20 Transfer logs.
+const TRACED_NATIVE_TRANSFERS: Address = address!("EeeeeEeeeEeEeeEeEeEeeEEEeeeeEeeeeeeeEEeE");
+
+/// Simulate the swap `tx` sent by `from` and return how much of `token_out` (the native
+/// token when `None`) it delivers, when that can be told, or an error if it reverts.
+///
+/// Without a fork the transaction is run with `eth_simulateV1` against the latest block,
+/// and the amount is what its traced Transfer logs pay `from`; nodes without that method
+/// run it with `eth_call`, which tells a revert but not the amount. With `fork_rpc_url`
+/// it is sent on that Anvil fork as an impersonated `from`, the amount is taken from the
+/// receipt's Transfer logs, and the fork is reverted afterwards.
+pub async fn simulate_swap(
+    provider: &dyn Provider,
+    config: &SimulationConfig,
+    tx: &TransactionRequest,
+    from: Address,
+    token_out: Option<Address>,
+    approval: Option<ForkApproval>,
+) -> Result<Option<U256>> {
+    let tx = tx.clone().with_from(from);
+    match &config.fork_rpc_url {
+        Some(fork_rpc_url) => simulate_on_fork(fork_rpc_url, tx, from, token_out, approval).await,
+        None => {
+            let payload = SimulatePayload {
+                block_state_calls: vec![SimBlock::default().call(tx.clone())],
+                trace_transfers: true,
+                ..Default::default()
+            };
+            let simulated = match provider.simulate(&payload).await {
+                Ok(blocks) => blocks.into_iter().flat_map(|block| block.calls).next(),
+                Err(e) => {
+                    println!(
+                        "eth_simulateV1 unavailable, simulating with eth_call: {}",
+                        e
+                    );
+                    None
+                }
+            };
+            match simulated {
+                Some(call) => simulated_output(&call, from, token_out),
+                None => {
+                    provider
+                        .call(&tx)
+                        .await
+                        .map_err(|e| anyhow!("Swap simulation reverted: {}", revert_reason(&e)))?;
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+/// What the simulated `call` paid `from` in `token_out`, or an error if it reverted.
+fn simulated_output(
+    call: &SimCallResult,
+    from: Address,
+    token_out: Option<Address>,
+) -> Result<Option<U256>> {
+    if !call.status {
+        let reason = call
+            .error
+            .as_ref()
+            .map(|error| error.message.clone())
+            .or_else(|| decode_revert_reason(&call.return_data))
+            .unwrap_or_default();
+        return Err(anyhow!("Swap simulation reverted: {}", reason));
+    }
+    match token_out {
+        Some(token) => Ok(Some(
+            received_from_logs(&call.logs, token, from).unwrap_or(U256::ZERO),
+        )),
+        // Nodes that do not trace transfers leave native output unmeasured
+        None => Ok(received_from_logs(
+            &call.logs,
+            TRACED_NATIVE_TRANSFERS,
+            from,
+        )),
+    }
+}
+
+async fn simulate_on_fork(
+    fork_rpc_url: &str,
+    tx: TransactionRequest,
+    from: Address,
+    token_out: Option<Address>,
+    approval: Option<ForkApproval>,
+) -> Result<Option<U256>> {
+    let fork = ProviderBuilder::new().on_http(reqwest::Url::parse(fork_rpc_url)?);
+    let snapshot = fork.anvil_snapshot().await?;
+    fork.anvil_impersonate_account(from).await?;
+
+    let result = async {
+        if let Some(approval) = approval {
+            let approve = TransactionRequest::default()
+                .with_from(from)
+                .with_to(approval.token)
+                .with_input(
+                    IERC20::approveCall {
+                        spender: approval.spender,
+                        amount: approval.amount,
+                    }
+                    .abi_encode(),
+                );
+            fork.send_transaction(approve).await?.get_receipt().await?;
+        }
+        let receipt = fork.send_transaction(tx).await?.get_receipt().await?;
+        if !receipt.status() {
+            return Err(anyhow!(
+                "Swap simulation reverted on the fork in {}",
+                receipt.transaction_hash
+            ));
+        }
+        Ok(token_out.and_then(|token| received_from_logs(receipt.inner.logs(), token, from)))
+    }
+    .await;
+
+    fork.anvil_stop_impersonating_account(from).await?;
+    fork.anvil_revert(snapshot).await?;
+    result
+}
+
+/// Decoded revert reason of a failed call, or the error itself.
+pub fn revert_reason(err: &TransportError) -> String {
+    err.as_error_resp()
+        .and_then(|payload| payload.as_revert_data())
+        .and_then(|data| decode_revert_reason(&data))
+        .unwrap_or_else(|| err.to_string())
+}
+
+/// Fail if the simulated output is below the quote minus the slippage tolerance.
+pub fn check_simulated_output(simulated: U256, min_amount_out: U256) -> Result<()> {
+    if simulated < min_amount_out {
+        return Err(anyhow!(
+            "Swap simulation delivers {} but at least {} is expected",
+            simulated,
+            min_amount_out
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_simulated_output_checks() -> Result<()> {
+        let wallet = address!("00000000000000000000000000000000000000aa");
+        let token = address!("833589fCD6eDb6E08f4c7C32D4f71b54bdA02913");
+        let transfer = |token: Address, to: Address, value: u64| {
+            serde_json::json!({
+                "address": token,
+                "topics": [
+                    "0xddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef",
+                    format!("0x{:0>64}", "bb"),
+                    format!("0x{:0>64}", hex::encode(to)),
+                ],
+                "data": format!("0x{:0>64x}", value),
+                "blockHash": null, "blockNumber": null, "blockTimestamp": null,
+                "transactionHash": null, "transactionIndex": null, "logIndex": null,
+                "removed": false,
+            })
+        };
+        let call = |logs: serde_json::Value, status: &str| -> SimCallResult {
+            serde_json::from_value(serde_json::json!({
+                "returnData": "0x", "logs": logs, "gasUsed": "0x1", "status": status
+            }))
+            .unwrap()
+        };
+
+        // What the wallet is paid, not the return data, is the output
+        let paid = call(
+            serde_json::json!([
+                transfer(token, wallet, 990),
+                transfer(
+                    token,
+                    address!("00000000000000000000000000000000000000cc"),
+                    5
+                ),
+            ]),
+            "0x1",
+        );
+        let simulated = simulated_output(&paid, wallet, Some(token))?.unwrap();
+        assert_eq!(simulated, U256::from(990u64));
+        assert_eq!(
+            simulated_output(&paid, wallet, Some(Address::ZERO))?,
+            Some(U256::ZERO)
+        );
+        assert_eq!(simulated_output(&paid, wallet, None)?, None);
+        let native = call(
+            serde_json::json!([transfer(TRACED_NATIVE_TRANSFERS, wallet, 7)]),
+            "0x1",
+        );
+        assert_eq!(
+            simulated_output(&native, wallet, None)?,
+            Some(U256::from(7u64))
+        );
+        assert!(
+            simulated_output(&call(serde_json::json!([]), "0x0"), wallet, Some(token)).is_err()
+        );
+
+        assert!(check_simulated_output(simulated, U256::from(990u64)).is_ok());
+        assert!(check_simulated_output(simulated, U256::from(991u64)).is_err());
+        Ok(())
+    }
+}
@@ -1,5 +1,7 @@
+use super::indicators::{IndicatorPeriods, TechnicalIndicators};
 use super::{Feed, Processor};
-use crate::{constants::Interval, utils::price::PriceData};
+use crate::constants::Interval;
+use crate::utils::price::{BookDepth, FundingHistory, PriceData, PriceSource};
 use async_trait::async_trait;
 use chrono::Utc;
 use reqwest::Client as ReqwestClient;
@@ -7,8 +9,8 @@ use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, VecDeque},
     error::Error,
+    sync::Mutex,
 };
-use strum::IntoEnumIterator;
 
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -30,12 +32,6 @@ pub struct DepthResponse {
     pub asks: Vec<(String, String)>,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct FundingRateResponse {
-    funding_rates: Vec<FundingRate>,
-}
-
 #[derive(Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct FundingRate {
@@ -45,6 +41,50 @@ pub struct FundingRate {
     pub mark_price: String,
 }
 
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OpenInterestHist {
+    pub symbol: String,
+    pub sum_open_interest: String,       // open interest in contracts
+    pub sum_open_interest_value: String, // open interest in USD
+    pub timestamp: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LongShortRatio {
+    pub symbol: String,
+    pub long_short_ratio: String,
+    pub long_account: String, // share of accounts net long
+    pub short_account: String,
+    pub timestamp: u64,
+}
+
+/// Open interest and account positioning of one symbol.
+#[derive(Debug, Clone, Serialize)]
+pub struct Positioning {
+    pub open_interest_usd: f64,
+    /// Change of the open interest over `POSITIONING_PERIODS` hours, in percent.
+    pub open_interest_change_pct: Option<f64>,
+    pub long_short_ratio: f64,
+    /// Share of accounts net long, in percent.
+    pub long_account_pct: f64,
+}
+
+impl std::fmt::Display for Positioning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "open interest ${:.1}M", self.open_interest_usd / 1e6)?;
+        if let Some(change) = self.open_interest_change_pct {
+            write!(f, " ({:+.2}% {}h)", change, POSITIONING_PERIODS)?;
+        }
+        write!(
+            f,
+            ", long/short account ratio {:.2} ({:.1}% long)",
+            self.long_short_ratio, self.long_account_pct
+        )
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct KlineData(
     u64,    // Open time
@@ -61,8 +101,9 @@ pub struct KlineData(
     String, // Ignore
 );
 
-#[derive(serde::Serialize, Debug, Clone)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct OHLCV {
+    #[serde(deserialize_with = "crate::utils::price::millis")]
     pub timestamp: u128,
     pub open: f64,
     pub high: f64,
@@ -88,6 +129,8 @@ pub struct BinancePriceFeed<'a> {
     pub base_url: &'a String,
     pub client: &'a ReqwestClient,
     pub symbol: &'a String,
+    /// Order book levels summed into `PriceData::depth`.
+    pub depth_levels: usize,
 }
 
 pub struct BinanceOHLCVFeed {
@@ -95,66 +138,151 @@ pub struct BinanceOHLCVFeed {
     pub client: ReqwestClient,
     pub symbol: String,
     pub window_size: usize,
+    pub intervals: Vec<Interval>,
 }
 
+/// Funding history a `PriceData` summarizes.
+const FUNDING_HISTORY_MS: u64 = 7 * 24 * 60 * 60 * 1000;
+
+/// Order book levels of a `BinancePriceFeed` unless configured otherwise.
+pub const DEFAULT_DEPTH_LEVELS: usize = 5;
+
+/// Book sizes the depth endpoint accepts.
+const DEPTH_LIMITS: [usize; 7] = [5, 10, 20, 50, 100, 500, 1000];
+
+/// Hourly open interest and ratio periods fetched for `Positioning`.
+const POSITIONING_PERIODS: usize = 24;
+
 impl<'a> BinancePriceFeed<'a> {
     pub fn new(base_url: &'a String, client: &'a ReqwestClient, symbol: &'a String) -> Self {
         Self {
             base_url,
             client,
             symbol,
+            depth_levels: DEFAULT_DEPTH_LEVELS,
         }
     }
 
+    pub fn with_depth_levels(mut self, depth_levels: usize) -> Self {
+        self.depth_levels = depth_levels;
+        self
+    }
+
     pub async fn fetch_index_price(&self) -> Result<MarketIndexResponse, reqwest::Error> {
         self.client
             .get(format!("{}/fapi/v1/premiumIndex", self.base_url))
             .query(&[("symbol", self.symbol.as_str())])
             .send()
-            .await
-            .expect("Failed to send request")
+            .await?
+            .error_for_status()?
             .json::<MarketIndexResponse>()
             .await
     }
 
-    async fn fetch_market_depth(&self) -> Result<DepthResponse, reqwest::Error> {
+    /// The book down to at least `depth_levels` levels per side.
+    pub async fn fetch_market_depth(&self) -> Result<DepthResponse, reqwest::Error> {
+        let limit = DEPTH_LIMITS
+            .into_iter()
+            .find(|limit| *limit >= self.depth_levels)
+            .unwrap_or(DEPTH_LIMITS[DEPTH_LIMITS.len() - 1]);
         self.client
             .get(format!("{}/fapi/v1/depth", self.base_url))
-            .query(&[("symbol", self.symbol.as_str()), ("limit", "5")])
+            .query(&[
+                ("symbol", self.symbol.as_str()),
+                ("limit", &limit.to_string()),
+            ])
             .send()
-            .await
-            .expect("Failed to send request")
+            .await?
             .json::<DepthResponse>()
             .await
     }
 
+    /// Depth of the first `depth_levels` levels; `None` while a side is empty.
+    pub async fn fetch_book_depth(&self) -> Result<Option<BookDepth>, reqwest::Error> {
+        Ok(book_depth(
+            &self.fetch_market_depth().await?,
+            self.depth_levels,
+        ))
+    }
+
+    pub async fn fetch_open_interest_hist(&self) -> Result<Vec<OpenInterestHist>, reqwest::Error> {
+        self.client
+            .get(format!("{}/futures/data/openInterestHist", self.base_url))
+            .query(&[
+                ("symbol", self.symbol.as_str()),
+                ("period", "1h"),
+                ("limit", &POSITIONING_PERIODS.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<OpenInterestHist>>()
+            .await
+    }
+
+    pub async fn fetch_long_short_ratio(&self) -> Result<Vec<LongShortRatio>, reqwest::Error> {
+        self.client
+            .get(format!(
+                "{}/futures/data/globalLongShortAccountRatio",
+                self.base_url
+            ))
+            .query(&[
+                ("symbol", self.symbol.as_str()),
+                ("period", "1h"),
+                ("limit", &POSITIONING_PERIODS.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<LongShortRatio>>()
+            .await
+    }
+
+    /// Latest open interest and long/short ratio, with the open interest trend.
+    pub async fn fetch_positioning(&self) -> Result<Positioning, Box<dyn Error + Send + Sync>> {
+        let (open_interest, ratios) = tokio::join!(
+            self.fetch_open_interest_hist(),
+            self.fetch_long_short_ratio()
+        );
+        positioning(&open_interest?, &ratios?)
+    }
+
     async fn fetch_funding_rate(
         &self,
         start_time: u64, // time in ms inclusive
         end_time: u64,
-    ) -> Result<FundingRateResponse, reqwest::Error> {
+    ) -> Result<Vec<FundingRate>, reqwest::Error> {
         self.client
             .get(format!("{}/fapi/v1/fundingRate", self.base_url))
             .query(&[
                 ("symbol", self.symbol.as_str()),
                 ("startTime", &start_time.to_string()),
                 ("endTime", &end_time.to_string()),
+                // Enough for a week of hourly funding
+                ("limit", "1000"),
             ])
             .send()
-            .await
-            .expect("Failed to send request")
-            .json::<FundingRateResponse>()
+            .await?
+            .error_for_status()?
+            .json::<Vec<FundingRate>>()
             .await
     }
 }
 
 impl BinanceOHLCVFeed {
-    fn new(base_url: String, client: ReqwestClient, symbol: String, window_size: usize) -> Self {
+    pub fn new(
+        base_url: String,
+        client: ReqwestClient,
+        symbol: String,
+        window_size: usize,
+        intervals: Vec<Interval>,
+    ) -> Self {
         Self {
             base_url,
             client,
             symbol,
             window_size,
+            intervals,
         }
     }
 
@@ -181,42 +309,57 @@ impl BinanceOHLCVFeed {
             .await?
             .error_for_status()?
             .json::<Vec<KlineData>>()
-            .await
-            .expect("Failed to parse response");
-
-        // Parse the candlestick data
+            .await?;
+
+        // Parse the candlestick data; a malformed kline fails the fetch, not the task
+        let parse = |value: &str, field: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|e| format!("Failed to parse kline {} {:?}: {}", field, value, e))
+        };
         let ohlcv_list = response
             .into_iter()
             .map(|kline| {
                 Ok(OHLCV {
                     timestamp: kline.0 as u128,
-                    open: kline.1.parse::<f64>().expect("Failed to parse open price"),
-                    high: kline.2.parse::<f64>().expect("Failed to parse high price"),
-                    low: kline.3.parse::<f64>().expect("Failed to parse low price"),
-                    close: kline.4.parse::<f64>().expect("Failed to parse close price"),
-                    volume: kline.5.parse::<f64>().expect("Failed to parse volume"),
+                    open: parse(&kline.1, "open price")?,
+                    high: parse(&kline.2, "high price")?,
+                    low: parse(&kline.3, "low price")?,
+                    close: parse(&kline.4, "close price")?,
+                    volume: parse(&kline.5, "volume")?,
                 })
             })
-            .collect::<Result<Vec<OHLCV>, Box<dyn Error>>>()
-            .expect("Failed to parse OHLCV data");
+            .collect::<Result<Vec<OHLCV>, String>>()?;
         Ok(ohlcv_list)
     }
 }
 
+/// Depth of the first `levels` levels of `depth`; unparseable levels are skipped.
+fn book_depth(depth: &DepthResponse, levels: usize) -> Option<BookDepth> {
+    let parse = |side: &[(String, String)]| -> Vec<(f64, f64)> {
+        side.iter()
+            .filter_map(|(price, qty)| Some((price.parse().ok()?, qty.parse().ok()?)))
+            .collect()
+    };
+    BookDepth::from_levels(&parse(&depth.bids), &parse(&depth.asks), levels)
+}
+
 #[async_trait]
 impl<'a> Feed<PriceData> for BinancePriceFeed<'a> {
     async fn feed(&self) -> Result<PriceData, Box<dyn Error + Send + Sync>> {
+        let now_ms = Utc::now().timestamp_millis() as u64;
         let (market_index_result, market_depth_result, funding_rate_result) = tokio::join!(
             self.fetch_index_price(),
             self.fetch_market_depth(),
-            self.fetch_funding_rate(
-                (Utc::now().timestamp_millis() - 60 * 60 * 8 * 1000) as u64, // 8 hours ago for funding rate since it's updated every 8 hours
-                Utc::now().timestamp_millis() as u64,
-            )
+            self.fetch_funding_rate(now_ms - FUNDING_HISTORY_MS, now_ms)
         );
         let market_index = market_index_result?;
         let market_depth = market_depth_result?;
         let funding_rate = funding_rate_result?;
+        let funding_history: Vec<(u64, f64)> = funding_rate
+            .iter()
+            .filter_map(|x| Some((x.funding_time, x.funding_rate.parse::<f64>().ok()?)))
+            .collect();
         Ok(PriceData {
             timestamp: market_index.time.into(),
             market_price: market_index.mark_price.parse::<f64>().ok(),
@@ -228,10 +371,13 @@ impl<'a> Feed<PriceData> for BinancePriceFeed<'a> {
                 .bids
                 .first()
                 .and_then(|x| x.0.parse::<f64>().ok()),
-            cur_funding_rate: funding_rate
-                .funding_rates
-                .last()
-                .and_then(|x| x.funding_rate.parse::<f64>().ok()),
+            cur_funding_rate: funding_history.last().map(|(_, rate)| *rate),
+            // Binance's "last" funding rate is the estimate for the running period
+            predicted_funding_rate: market_index.last_funding_rate.parse::<f64>().ok(),
+            next_funding_time: Some(market_index.next_funding_time),
+            funding_history: Some(FundingHistory::from_rates(&funding_history, now_ms)),
+            source: PriceSource::Binance,
+            depth: book_depth(&market_depth, self.depth_levels),
         })
     }
 }
@@ -241,14 +387,16 @@ impl Feed<HashMap<Interval, Vec<OHLCV>>> for BinanceOHLCVFeed {
     async fn feed(&self) -> Result<HashMap<Interval, Vec<OHLCV>>, Box<dyn Error + Send + Sync>> {
         let mut data = HashMap::new();
 
-        let futures_fetch_binance_data: Vec<_> = Interval::iter()
+        let futures_fetch_binance_data: Vec<_> = self
+            .intervals
+            .iter()
             .map(|interval| self.fetch_binance_ohlcv(interval.to_string()))
             .collect();
 
         let ohlcv_data_array: Vec<Result<Vec<OHLCV>, Box<dyn Error + Send + Sync>>> =
             futures::future::join_all(futures_fetch_binance_data).await;
 
-        for (interval, ohlcv_data) in Interval::iter().zip(ohlcv_data_array) {
+        for (interval, ohlcv_data) in self.intervals.iter().copied().zip(ohlcv_data_array) {
             let ohlcv = ohlcv_data?;
             data.insert(interval, ohlcv);
         }
@@ -256,55 +404,72 @@ impl Feed<HashMap<Interval, Vec<OHLCV>>> for BinanceOHLCVFeed {
     }
 }
 
-struct BinanceIndicators {
-    ohlcv: OHLCV,
-    rsi: f64,
-    ema_short: f64,
-    ema_long: f64,
+/// Both histories are oldest first, as Binance returns them.
+fn positioning(
+    open_interest: &[OpenInterestHist],
+    ratios: &[LongShortRatio],
+) -> Result<Positioning, Box<dyn Error + Send + Sync>> {
+    let (Some(first), Some(latest)) = (open_interest.first(), open_interest.last()) else {
+        return Err("No open interest history".into());
+    };
+    let ratio = ratios.last().ok_or("No long/short ratio history")?;
+    let open_interest_usd = latest.sum_open_interest_value.parse::<f64>()?;
+    let first_usd = first.sum_open_interest_value.parse::<f64>()?;
+    Ok(Positioning {
+        open_interest_usd,
+        open_interest_change_pct: (open_interest.len() > 1 && first_usd > 0.0)
+            .then(|| (open_interest_usd / first_usd - 1.0) * 100.0),
+        long_short_ratio: ratio.long_short_ratio.parse::<f64>()?,
+        long_account_pct: ratio.long_account.parse::<f64>()? * 100.0,
+    })
+}
+
+/// A candle with the indicators of the window it closes.
+#[derive(Debug, Clone, Serialize)]
+pub struct BinanceIndicators {
+    pub ohlcv: OHLCV,
+    #[serde(flatten)]
+    pub indicators: TechnicalIndicators,
 }
 
-struct BinanceOHLCVProcessor {
-    data: VecDeque<OHLCV>,
+/// Keeps a window of candles and computes the indicators of each new one over it.
+pub struct BinanceOHLCVProcessor {
+    data: Mutex<VecDeque<OHLCV>>,
     size: usize,
-    rsi_period: usize,
-    ema_short_period: usize,
-    ema_long_period: usize,
-    ema_short: Option<f64>,
-    ema_long: Option<f64>,
+    periods: IndicatorPeriods,
 }
 
 impl BinanceOHLCVProcessor {
-    fn calculate_ema(&self, current_price: f64, previous_ema: Option<f64>, period: usize) -> f64 {
-        let k = 2.0 / (period as f64 + 1.0);
-        match previous_ema {
-            Some(ema) => (current_price - ema) * k + ema,
-            None => self.data.iter().map(|p| p.close).sum::<f64>() / self.data.len() as f64,
+    pub fn new(size: usize, periods: IndicatorPeriods) -> Self {
+        Self {
+            data: Mutex::new(VecDeque::with_capacity(size)),
+            size,
+            periods,
         }
     }
 
-    fn calculate_rsi(&self) -> Option<f64> {
-        if self.data.len() < self.rsi_period + 1 {
-            return None;
+    /// Start from `candles`, oldest first, so indicators are available on the first tick.
+    pub fn with_history(self, candles: Vec<OHLCV>) -> Self {
+        for candle in candles {
+            self.push(candle);
         }
+        self
+    }
 
-        let mut gains = 0.0;
-        let mut losses = 0.0;
-
-        for i in (self.data.len() - self.rsi_period)..(self.data.len() - 1) {
-            let change = self.data[i + 1].close - self.data[i].close;
-            if change > 0.0 {
-                gains += change;
-            } else {
-                losses -= change; // losses are positive
-            }
+    /// Add a candle to the window, replacing the last one while it is still open.
+    fn push(&self, candle: OHLCV) -> Vec<OHLCV> {
+        let mut data = self.data.lock().unwrap_or_else(|e| e.into_inner());
+        if data
+            .back()
+            .is_some_and(|last| last.timestamp == candle.timestamp)
+        {
+            data.pop_back();
         }
-
-        if gains + losses == 0.0 {
-            return Some(50.0);
+        data.push_back(candle);
+        if data.len() > self.size {
+            data.pop_front();
         }
-
-        let rs = gains / losses;
-        Some(100.0 - (100.0 / (1.0 + rs)))
+        data.iter().cloned().collect()
     }
 }
 
@@ -314,15 +479,68 @@ impl Processor<BinanceIndicators, OHLCV> for BinanceOHLCVProcessor {
         &self,
         data: &OHLCV,
     ) -> Result<BinanceIndicators, Box<dyn Error + Send + Sync>> {
-        let rsi = self.calculate_rsi();
-        let ema_short = self.calculate_ema(data.close, self.ema_short, self.ema_short_period);
-        let ema_long = self.calculate_ema(data.close, self.ema_long, self.ema_long_period);
-
+        let candles = self.push(data.to_owned());
         Ok(BinanceIndicators {
             ohlcv: data.to_owned(),
-            rsi: rsi.unwrap_or(0.0),
-            ema_short,
-            ema_long,
+            indicators: TechnicalIndicators::from_candles(&candles, &self.periods),
         })
     }
 }
+
+/// Latest candle of one interval, polled to drive a `BinanceOHLCVProcessor`.
+pub struct BinanceKlineFeed {
+    candles: BinanceOHLCVFeed,
+    interval: Interval,
+}
+
+impl BinanceKlineFeed {
+    pub fn new(
+        base_url: String,
+        client: ReqwestClient,
+        symbol: String,
+        interval: Interval,
+    ) -> Self {
+        Self {
+            candles: BinanceOHLCVFeed::new(base_url, client, symbol, 1, vec![interval]),
+            interval,
+        }
+    }
+}
+
+#[async_trait]
+impl Feed<OHLCV> for BinanceKlineFeed {
+    async fn feed(&self) -> Result<OHLCV, Box<dyn Error + Send + Sync>> {
+        self.candles
+            .fetch_binance_ohlcv(self.interval.to_string())
+            .await?
+            .pop()
+            .ok_or_else(|| {
+                format!("No {} candle for {}", self.interval, self.candles.symbol).into()
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_positioning() {
+        let open_interest: Vec<OpenInterestHist> = serde_json::from_value(serde_json::json!([
+            { "symbol": "BTCUSDT", "sumOpenInterest": "80000.0", "sumOpenInterestValue": "5000000000.0", "timestamp": 1700000000000u64 },
+            { "symbol": "BTCUSDT", "sumOpenInterest": "84000.0", "sumOpenInterestValue": "5250000000.0", "timestamp": 1700082800000u64 }
+        ]))
+        .unwrap();
+        let ratios: Vec<LongShortRatio> = serde_json::from_value(serde_json::json!([
+            { "symbol": "BTCUSDT", "longShortRatio": "1.5000", "longAccount": "0.6000", "shortAccount": "0.4000", "timestamp": 1700082800000u64 }
+        ]))
+        .unwrap();
+        let positioning = positioning(&open_interest, &ratios).unwrap();
+        assert!((positioning.open_interest_change_pct.unwrap() - 5.0).abs() < 1e-9);
+        assert_eq!(
+            positioning.to_string(),
+            "open interest $5250.0M (+5.00% 24h), long/short account ratio 1.50 (60.0% long)"
+        );
+        assert!(super::positioning(&[], &ratios).is_err());
+    }
+}
@@ -0,0 +1,346 @@
+use super::binance::OHLCV;
+use super::binance_ws::ws_base_url;
+use crate::config::MarketDataConfig;
+use anyhow::{anyhow, Result};
+use futures::{SinkExt, StreamExt};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Events of the mark price, book ticker and kline market streams.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "e")]
+pub enum MarketEvent {
+    #[serde(rename = "markPriceUpdate")]
+    MarkPrice {
+        #[serde(rename = "s")]
+        symbol: String,
+        #[serde(rename = "p")]
+        mark_price: String,
+        #[serde(rename = "i")]
+        index_price: String,
+        #[serde(rename = "r")]
+        funding_rate: String,
+        #[serde(rename = "T")]
+        next_funding_time: u64,
+    },
+    #[serde(rename = "bookTicker")]
+    BookTicker {
+        #[serde(rename = "s")]
+        symbol: String,
+        #[serde(rename = "b")]
+        best_bid: String,
+        #[serde(rename = "a")]
+        best_ask: String,
+    },
+    #[serde(rename = "kline")]
+    Kline {
+        #[serde(rename = "s")]
+        symbol: String,
+        #[serde(rename = "k")]
+        kline: StreamKline,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct StreamKline {
+    /// Open time.
+    #[serde(rename = "t")]
+    pub open_time: u64,
+    #[serde(rename = "o")]
+    pub open: String,
+    #[serde(rename = "h")]
+    pub high: String,
+    #[serde(rename = "l")]
+    pub low: String,
+    #[serde(rename = "c")]
+    pub close: String,
+    #[serde(rename = "v")]
+    pub volume: String,
+    /// Whether the candle is closed.
+    #[serde(rename = "x")]
+    pub closed: bool,
+}
+
+/// A market stream message: an event, or the reply to our SUBSCRIBE request.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MarketMessage {
+    Event(MarketEvent),
+    Reply {
+        id: u64,
+        #[serde(default)]
+        error: Option<serde_json::Value>,
+    },
+}
+
+/// Latest streamed prices of one symbol.
+#[derive(Debug, Clone, Default)]
+pub struct SymbolPrices {
+    pub mark_price: Option<f64>,
+    pub index_price: Option<f64>,
+    pub funding_rate: Option<f64>,
+    pub next_funding_time: Option<u64>,
+    pub best_bid: Option<f64>,
+    pub best_ask: Option<f64>,
+    /// Candle of the configured interval, updated while it is still open.
+    pub kline: Option<OHLCV>,
+    /// When any of the prices were last updated.
+    pub updated_at: Option<Instant>,
+}
+
+/// Prices kept current by the market data stream, read synchronously by handlers.
+#[derive(Debug, Clone, Default)]
+pub struct PriceCache {
+    prices: Arc<RwLock<HashMap<String, SymbolPrices>>>,
+}
+
+impl PriceCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn get(&self, symbol: &str) -> Option<SymbolPrices> {
+        self.read().get(symbol).cloned()
+    }
+
+    /// Mark price of `symbol`, if it was updated within `max_age`.
+    pub fn mark_price(&self, symbol: &str, max_age: Duration) -> Option<f64> {
+        let prices = self.get(symbol)?;
+        if prices.updated_at?.elapsed() > max_age {
+            return None;
+        }
+        prices.mark_price
+    }
+
+    /// Write `event` into the cache. An event with an unparsable price is rejected as a
+    /// whole, leaving the symbol's prices as they were.
+    pub fn apply(&self, event: &MarketEvent) -> Result<()> {
+        let parse = |value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|e| anyhow!("Invalid price {:?}: {}", value, e))
+        };
+        match event {
+            MarketEvent::MarkPrice {
+                symbol,
+                mark_price,
+                index_price,
+                funding_rate,
+                next_funding_time,
+            } => {
+                let mark_price = parse(mark_price)?;
+                let index_price = parse(index_price)?;
+                // Funding is empty for delivery contracts
+                let funding_rate = parse(funding_rate).ok();
+                self.update(symbol, |entry| {
+                    entry.mark_price = Some(mark_price);
+                    entry.index_price = Some(index_price);
+                    entry.funding_rate = funding_rate;
+                    entry.next_funding_time = Some(*next_funding_time);
+                });
+            }
+            MarketEvent::BookTicker {
+                symbol,
+                best_bid,
+                best_ask,
+            } => {
+                let best_bid = parse(best_bid)?;
+                let best_ask = parse(best_ask)?;
+                self.update(symbol, |entry| {
+                    entry.best_bid = Some(best_bid);
+                    entry.best_ask = Some(best_ask);
+                });
+            }
+            MarketEvent::Kline { symbol, kline } => {
+                let kline = OHLCV {
+                    timestamp: kline.open_time.into(),
+                    open: parse(&kline.open)?,
+                    high: parse(&kline.high)?,
+                    low: parse(&kline.low)?,
+                    close: parse(&kline.close)?,
+                    volume: parse(&kline.volume)?,
+                };
+                self.update(symbol, |entry| entry.kline = Some(kline));
+            }
+            MarketEvent::Unknown => {}
+        }
+        Ok(())
+    }
+
+    fn update(&self, symbol: &str, update: impl FnOnce(&mut SymbolPrices)) {
+        let mut prices = self.write();
+        let entry = prices.entry(symbol.to_string()).or_default();
+        update(entry);
+        entry.updated_at = Some(Instant::now());
+    }
+
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<String, SymbolPrices>> {
+        self.prices.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<String, SymbolPrices>> {
+        self.prices.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Subscribes to the market streams of the configured symbols and writes every event
+/// into a `PriceCache`.
+pub struct MarketDataStream {
+    ws_url: &'static str,
+    streams: Vec<String>,
+    cache: PriceCache,
+}
+
+impl MarketDataStream {
+    pub fn new(rest_base_url: &str, config: &MarketDataConfig, cache: PriceCache) -> Self {
+        Self {
+            ws_url: ws_base_url(rest_base_url),
+            streams: stream_names(&config.symbols, &config.kline_interval),
+            cache,
+        }
+    }
+
+    /// Run the stream forever in a background task, reconnecting on errors.
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = self.run_once().await {
+                    println!("Binance market data stream error: {}", err);
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        })
+    }
+
+    async fn run_once(&self) -> Result<()> {
+        let (mut socket, _) = connect_async(self.ws_url).await?;
+        let subscribe = serde_json::json!({
+            "method": "SUBSCRIBE",
+            "params": self.streams,
+            "id": 1,
+        });
+        socket
+            .send(Message::Text(subscribe.to_string().into()))
+            .await?;
+        println!("Connected to Binance market data stream");
+
+        while let Some(message) = socket.next().await {
+            match message? {
+                // A malformed tick is skipped; the next one updates the prices again
+                Message::Text(text) => match parse_market_message(text.as_str()) {
+                    Ok(MarketMessage::Event(event)) => {
+                        if let Err(e) = self.cache.apply(&event) {
+                            println!("Skipping Binance market data event: {}", e);
+                        }
+                    }
+                    Ok(MarketMessage::Reply {
+                        id,
+                        error: Some(error),
+                    }) => return Err(anyhow!("SUBSCRIBE request {} failed: {}", id, error)),
+                    Ok(MarketMessage::Reply { .. }) => {}
+                    Err(e) => println!("Skipping Binance market data message: {}", e),
+                },
+                Message::Close(frame) => {
+                    return Err(anyhow!("market data stream closed: {:?}", frame));
+                }
+                _ => {}
+            }
+        }
+        Err(anyhow!("market data stream closed"))
+    }
+}
+
+/// markPrice, bookTicker and kline stream names of each symbol.
+fn stream_names(symbols: &[String], kline_interval: &str) -> Vec<String> {
+    symbols
+        .iter()
+        .flat_map(|symbol| {
+            let symbol = symbol.to_lowercase();
+            [
+                format!("{}@markPrice@1s", symbol),
+                format!("{}@bookTicker", symbol),
+                format!("{}@kline_{}", symbol, kline_interval),
+            ]
+        })
+        .collect()
+}
+
+fn parse_market_message(text: &str) -> Result<MarketMessage> {
+    serde_json::from_str(text).map_err(|e| anyhow!("Failed to parse market data event: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stream_names() {
+        assert_eq!(
+            stream_names(&["BTCUSDT".to_string()], "1m"),
+            vec![
+                "btcusdt@markPrice@1s",
+                "btcusdt@bookTicker",
+                "btcusdt@kline_1m"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_apply_market_events() -> Result<()> {
+        let cache = PriceCache::new();
+        let raw = [
+            r#"{"e":"markPriceUpdate","E":1562305380000,"s":"BTCUSDT","p":"64000.50","i":"64010.00","P":"64005.1","r":"0.00010000","T":1562306400000}"#,
+            r#"{"e":"bookTicker","u":400900217,"E":1568014460893,"T":1568014460891,"s":"BTCUSDT","b":"63999.90","B":"31.21","a":"64000.10","A":"40.66"}"#,
+            r#"{"e":"kline","E":1638747660000,"s":"BTCUSDT","k":{"t":1638747660000,"T":1638747719999,"s":"BTCUSDT","i":"1m","f":100,"L":200,"o":"63990.0","c":"64000.5","h":"64010.0","l":"63980.0","v":"12.5","n":100,"x":false,"q":"800000","V":"6","Q":"380000","B":"0"}}"#,
+            r#"{"result":null,"id":1}"#,
+        ];
+        for text in raw {
+            if let MarketMessage::Event(event) = parse_market_message(text)? {
+                cache.apply(&event)?;
+            }
+        }
+
+        let btc = cache.get("BTCUSDT").unwrap();
+        assert_eq!(btc.mark_price, Some(64000.5));
+        assert_eq!(btc.funding_rate, Some(0.0001));
+        assert_eq!(btc.best_bid, Some(63999.9));
+        assert_eq!(btc.best_ask, Some(64000.1));
+        assert_eq!(btc.kline.unwrap().close, 64000.5);
+        assert_eq!(
+            cache.mark_price("BTCUSDT", Duration::from_secs(10)),
+            Some(64000.5)
+        );
+        assert_eq!(cache.mark_price("BTCUSDT", Duration::ZERO), None);
+        // ETH has not streamed yet
+        assert_eq!(cache.mark_price("ETHUSDT", Duration::from_secs(10)), None);
+
+        // A bad price leaves the whole tick out
+        let bad = r#"{"e":"markPriceUpdate","E":1562305381000,"s":"BTCUSDT","p":"64100.00","i":"","P":"64105.1","r":"0.00010000","T":1562306400000}"#;
+        if let MarketMessage::Event(event) = parse_market_message(bad)? {
+            assert!(cache.apply(&event).is_err());
+        }
+        assert_eq!(cache.get("BTCUSDT").unwrap().mark_price, Some(64000.5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_subscribe_error_reply() -> Result<()> {
+        let reply = parse_market_message(r#"{"error":{"code":2,"msg":"Invalid request"},"id":1}"#)?;
+        assert!(matches!(
+            reply,
+            MarketMessage::Reply {
+                id: 1,
+                error: Some(_)
+            }
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,374 @@
+use crate::audit::{self, AuditKind};
+use anyhow::{anyhow, Result};
+use futures::StreamExt;
+use reqwest::header::HeaderValue;
+use reqwest::Client as ReqwestClient;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// Listen keys expire after 60 minutes without a keepalive.
+const KEEPALIVE_INTERVAL: Duration = Duration::from_secs(30 * 60);
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+const CHANNEL_CAPACITY: usize = 1024;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct ListenKeyResponse {
+    listen_key: String,
+}
+
+/// Events pushed on the USDⓈ-M futures user data stream.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "e")]
+pub enum UserDataEvent {
+    #[serde(rename = "ORDER_TRADE_UPDATE")]
+    OrderTradeUpdate {
+        #[serde(rename = "E")]
+        event_time: u64,
+        #[serde(rename = "T")]
+        transaction_time: u64,
+        #[serde(rename = "o")]
+        order: Box<OrderTradeUpdate>,
+    },
+    #[serde(rename = "ACCOUNT_UPDATE")]
+    AccountUpdate {
+        #[serde(rename = "E")]
+        event_time: u64,
+        #[serde(rename = "T")]
+        transaction_time: u64,
+        #[serde(rename = "a")]
+        account: AccountUpdate,
+    },
+    #[serde(rename = "listenKeyExpired")]
+    ListenKeyExpired {
+        #[serde(rename = "E")]
+        event_time: u64,
+    },
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrderTradeUpdate {
+    /// Symbol.
+    #[serde(rename = "s")]
+    pub symbol: String,
+    /// Client order id.
+    #[serde(rename = "c")]
+    pub client_order_id: String,
+    /// Side (BUY / SELL).
+    #[serde(rename = "S")]
+    pub side: String,
+    /// Order type.
+    #[serde(rename = "o")]
+    pub order_type: String,
+    /// Original quantity.
+    #[serde(rename = "q")]
+    pub orig_qty: Decimal,
+    /// Original price.
+    #[serde(rename = "p")]
+    pub price: Decimal,
+    /// Average fill price.
+    #[serde(rename = "ap")]
+    pub avg_price: Decimal,
+    /// Execution type (NEW, TRADE, CANCELED, ...).
+    #[serde(rename = "x")]
+    pub execution_type: String,
+    /// Order status.
+    #[serde(rename = "X")]
+    pub status: String,
+    /// Order id.
+    #[serde(rename = "i")]
+    pub order_id: i64,
+    /// Last filled quantity.
+    #[serde(rename = "l")]
+    pub last_filled_qty: Decimal,
+    /// Accumulated filled quantity.
+    #[serde(rename = "z")]
+    pub filled_qty: Decimal,
+    /// Last filled price.
+    #[serde(rename = "L")]
+    pub last_filled_price: Decimal,
+    /// Commission of the last fill.
+    #[serde(rename = "n", default)]
+    pub commission: Option<Decimal>,
+    /// Commission asset.
+    #[serde(rename = "N", default)]
+    pub commission_asset: Option<String>,
+    /// Realized profit of the trade.
+    #[serde(rename = "rp", default)]
+    pub realized_profit: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AccountUpdate {
+    /// Reason of the update (ORDER, FUNDING_FEE, DEPOSIT, ...).
+    #[serde(rename = "m")]
+    pub reason: String,
+    #[serde(rename = "B", default)]
+    pub balances: Vec<BalanceUpdate>,
+    #[serde(rename = "P", default)]
+    pub positions: Vec<PositionUpdate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BalanceUpdate {
+    #[serde(rename = "a")]
+    pub asset: String,
+    #[serde(rename = "wb")]
+    pub wallet_balance: Decimal,
+    #[serde(rename = "cw")]
+    pub cross_wallet_balance: Decimal,
+    #[serde(rename = "bc", default)]
+    pub balance_change: Option<Decimal>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PositionUpdate {
+    #[serde(rename = "s")]
+    pub symbol: String,
+    #[serde(rename = "pa")]
+    pub position_amt: Decimal,
+    #[serde(rename = "ep")]
+    pub entry_price: Decimal,
+    #[serde(rename = "up")]
+    pub unrealized_profit: Decimal,
+    #[serde(rename = "ps")]
+    pub position_side: String,
+}
+
+// Listen key lifecycle: POST creates (or returns the active) key, PUT extends it, DELETE closes it.
+pub async fn create_listen_key(
+    client: &ReqwestClient,
+    base_url: &str,
+    api_key: &str,
+) -> Result<String> {
+    let url = format!("{}/fapi/v1/listenKey", base_url);
+    let response = client
+        .post(&url)
+        .header("X-MBX-APIKEY", api_key_header(api_key)?)
+        .send()
+        .await;
+    let response = audit_listen_key("POST", &url, response)?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Failed to create listen key: HTTP {}",
+            response.status()
+        ));
+    }
+
+    let listen_key: ListenKeyResponse = response.json().await?;
+    Ok(listen_key.listen_key)
+}
+
+pub async fn keepalive_listen_key(
+    client: &ReqwestClient,
+    base_url: &str,
+    api_key: &str,
+) -> Result<()> {
+    let response = client
+        .put(format!("{}/fapi/v1/listenKey", base_url))
+        .header("X-MBX-APIKEY", api_key_header(api_key)?)
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Failed to keep listen key alive: HTTP {}",
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
+pub async fn close_listen_key(client: &ReqwestClient, base_url: &str, api_key: &str) -> Result<()> {
+    let url = format!("{}/fapi/v1/listenKey", base_url);
+    let response = client
+        .delete(&url)
+        .header("X-MBX-APIKEY", api_key_header(api_key)?)
+        .send()
+        .await;
+    let response = audit_listen_key("DELETE", &url, response)?;
+
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Failed to close listen key: HTTP {}",
+            response.status()
+        ));
+    }
+    Ok(())
+}
+
+/// Record opening or closing a listen key in the audit log, by status only since the
+/// body holds the key.
+fn audit_listen_key(
+    method: &str,
+    url: &str,
+    response: reqwest::Result<reqwest::Response>,
+) -> Result<reqwest::Response> {
+    let outcome = match &response {
+        Ok(response) if response.status().is_success() => Ok(response.status().to_string()),
+        Ok(response) => Err(format!("HTTP {}", response.status())),
+        Err(e) => Err(e.to_string()),
+    };
+    audit::record(
+        AuditKind::BinanceRequest,
+        format!("{} {}", method, url),
+        audit::sha256_hex(b""),
+        outcome,
+    );
+    Ok(response?)
+}
+
+fn api_key_header(api_key: &str) -> Result<HeaderValue> {
+    HeaderValue::from_str(api_key).map_err(|e| anyhow!("Invalid API key: {}", e))
+}
+
+/// WebSocket host matching a futures REST base url.
+pub fn ws_base_url(rest_base_url: &str) -> &'static str {
+    if rest_base_url.contains("testnet") {
+        "wss://stream.binancefuture.com/ws"
+    } else {
+        "wss://fstream.binance.com/ws"
+    }
+}
+
+/// Consumes the user data stream and republishes every event on a broadcast channel.
+pub struct UserDataStream {
+    client: ReqwestClient,
+    base_url: String,
+    api_key: String,
+    sender: broadcast::Sender<UserDataEvent>,
+}
+
+impl UserDataStream {
+    pub fn new(client: ReqwestClient, base_url: String, api_key: String) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            client,
+            base_url,
+            api_key,
+            sender,
+        }
+    }
+
+    /// Sender handle; call `subscribe()` on it to receive events.
+    pub fn sender(&self) -> broadcast::Sender<UserDataEvent> {
+        self.sender.clone()
+    }
+
+    /// Run the stream forever in a background task, reconnecting on errors.
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                if let Err(err) = self.run_once().await {
+                    println!("Binance user data stream error: {}", err);
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        })
+    }
+
+    /// Run one listen key session until the socket closes or the key expires.
+    async fn run_once(&self) -> Result<()> {
+        let listen_key = create_listen_key(&self.client, &self.base_url, &self.api_key).await?;
+        let url = format!("{}/{}", ws_base_url(&self.base_url), listen_key);
+        let (mut socket, _) = connect_async(url.as_str()).await?;
+        println!("Connected to Binance user data stream");
+
+        let mut keepalive = tokio::time::interval(KEEPALIVE_INTERVAL);
+        // The first tick completes immediately; the key was just created.
+        keepalive.tick().await;
+
+        loop {
+            tokio::select! {
+                _ = keepalive.tick() => {
+                    keepalive_listen_key(&self.client, &self.base_url, &self.api_key).await?;
+                }
+                message = socket.next() => {
+                    let message = match message {
+                        Some(message) => message?,
+                        None => return Err(anyhow!("user data stream closed")),
+                    };
+                    match message {
+                        Message::Text(text) => {
+                            // One malformed event is skipped; the session carries on
+                            let event = match parse_user_data_event(text.as_str()) {
+                                Ok(event) => event,
+                                Err(err) => {
+                                    println!("Skipping user data event: {}", err);
+                                    continue;
+                                }
+                            };
+                            if let UserDataEvent::ListenKeyExpired { .. } = event {
+                                return Err(anyhow!("listen key expired"));
+                            }
+                            // No receivers is not an error; the event is simply dropped.
+                            let _ = self.sender.send(event);
+                        }
+                        Message::Close(frame) => {
+                            return Err(anyhow!("user data stream closed: {:?}", frame));
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn parse_user_data_event(text: &str) -> Result<UserDataEvent> {
+    serde_json::from_str(text).map_err(|e| anyhow!("Failed to parse user data event: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_order_trade_update() -> Result<()> {
+        let raw = r#"{"e":"ORDER_TRADE_UPDATE","E":1568879465651,"T":1568879465650,"o":{"s":"BTCUSDT","c":"TEST","S":"SELL","o":"TRAILING_STOP_MARKET","f":"GTC","q":"0.001","p":"0","ap":"0","sp":"7103.04","x":"NEW","X":"NEW","i":8886774,"l":"0","z":"0","L":"0","N":"USDT","n":"0","T":1568879465650,"t":0,"b":"0","a":"9.91","m":false,"R":false,"wt":"CONTRACT_PRICE","ot":"TRAILING_STOP_MARKET","ps":"LONG","cp":false,"AP":"7476.89","cr":"5.0","pP":false,"si":0,"ss":0,"rp":"0"}}"#;
+        match parse_user_data_event(raw)? {
+            UserDataEvent::OrderTradeUpdate { order, .. } => {
+                assert_eq!(order.symbol, "BTCUSDT");
+                assert_eq!(order.order_id, 8886774);
+                assert_eq!(order.orig_qty, Decimal::new(1, 3));
+                assert_eq!(order.status, "NEW");
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_account_update() -> Result<()> {
+        let raw = r#"{"e":"ACCOUNT_UPDATE","E":1564745798939,"T":1564745798938,"a":{"m":"ORDER","B":[{"a":"USDT","wb":"122624.12345678","cw":"100.12345678","bc":"50.12345678"}],"P":[{"s":"BTCUSDT","pa":"0","ep":"0.00000","bep":"0","cr":"200","up":"0","mt":"isolated","iw":"0.00000000","ps":"BOTH"}]}}"#;
+        match parse_user_data_event(raw)? {
+            UserDataEvent::AccountUpdate { account, .. } => {
+                assert_eq!(account.reason, "ORDER");
+                assert_eq!(account.balances[0].asset, "USDT");
+                assert_eq!(account.positions[0].position_amt, Decimal::ZERO);
+            }
+            other => panic!("unexpected event: {:?}", other),
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_unknown_and_expired() -> Result<()> {
+        assert!(matches!(
+            parse_user_data_event(r#"{"e":"listenKeyExpired","E":1576653824250}"#)?,
+            UserDataEvent::ListenKeyExpired { .. }
+        ));
+        assert!(matches!(
+            parse_user_data_event(r#"{"e":"MARGIN_CALL","E":1587727187525}"#)?,
+            UserDataEvent::Unknown
+        ));
+        Ok(())
+    }
+}
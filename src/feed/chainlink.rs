@@ -0,0 +1,200 @@
+use super::Feed;
+use crate::config::PriceCheckConfig;
+use alloy::network::TransactionBuilder;
+use alloy::primitives::{Address, I256};
+use alloy::providers::Provider;
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol;
+use alloy::sol_types::SolCall;
+use async_trait::async_trait;
+use std::error::Error;
+use thiserror::Error;
+
+sol! {
+    interface AggregatorV3Interface {
+        function decimals() external view returns (uint8);
+        function latestRoundData() external view returns (
+            uint80 roundId,
+            int256 answer,
+            uint256 startedAt,
+            uint256 updatedAt,
+            uint80 answeredInRound
+        );
+    }
+}
+
+/// Latest answer of one Chainlink aggregator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChainlinkPrice {
+    /// Binance symbol the aggregator is compared to.
+    pub symbol: String,
+    pub price: f64,
+    /// Unix seconds of the round the answer is from.
+    pub updated_at: u64,
+}
+
+/// Why a strategy is not executed at the current Binance prices.
+#[derive(Debug, Error, PartialEq)]
+pub enum PriceCheckError {
+    #[error("Chainlink {symbol} price is {age_secs}s old")]
+    Stale { symbol: String, age_secs: u64 },
+    #[error("Chainlink {symbol} answer {answer} is not a price")]
+    InvalidAnswer { symbol: String, answer: f64 },
+    #[error(
+        "Binance {symbol} mark price {binance} is {divergence_bps:.0} bps off Chainlink's {chainlink}"
+    )]
+    Diverged {
+        symbol: String,
+        binance: f64,
+        chainlink: f64,
+        divergence_bps: f64,
+    },
+}
+
+/// Reads the configured Chainlink aggregators through an alloy provider.
+pub struct ChainlinkFeed {
+    provider: Box<dyn Provider>,
+    feeds: Vec<(String, Address)>,
+}
+
+impl ChainlinkFeed {
+    pub fn new(provider: Box<dyn Provider>, config: &PriceCheckConfig) -> anyhow::Result<Self> {
+        let mut feeds = Vec::new();
+        for (symbol, aggregator) in &config.feeds {
+            let aggregator = aggregator.parse::<Address>().map_err(|e| {
+                anyhow::anyhow!("Invalid Chainlink aggregator {}: {}", aggregator, e)
+            })?;
+            feeds.push((symbol.clone(), aggregator));
+        }
+        Ok(Self { provider, feeds })
+    }
+
+    async fn call(
+        &self,
+        aggregator: Address,
+        input: Vec<u8>,
+    ) -> Result<Vec<u8>, Box<dyn Error + Send + Sync>> {
+        let tx = TransactionRequest::default()
+            .with_to(aggregator)
+            .with_input(input);
+        Ok(self.provider.call(&tx).await?.to_vec())
+    }
+
+    async fn latest_price(
+        &self,
+        symbol: &str,
+        aggregator: Address,
+    ) -> Result<ChainlinkPrice, Box<dyn Error + Send + Sync>> {
+        let decimals = AggregatorV3Interface::decimalsCall::abi_decode_returns(
+            &self
+                .call(
+                    aggregator,
+                    AggregatorV3Interface::decimalsCall {}.abi_encode(),
+                )
+                .await?,
+            true,
+        )?
+        ._0;
+        let round = AggregatorV3Interface::latestRoundDataCall::abi_decode_returns(
+            &self
+                .call(
+                    aggregator,
+                    AggregatorV3Interface::latestRoundDataCall {}.abi_encode(),
+                )
+                .await?,
+            true,
+        )?;
+        Ok(ChainlinkPrice {
+            symbol: symbol.to_string(),
+            price: answer_to_f64(round.answer, decimals),
+            updated_at: round.updatedAt.try_into().unwrap_or(u64::MAX),
+        })
+    }
+}
+
+#[async_trait]
+impl Feed<Vec<ChainlinkPrice>> for ChainlinkFeed {
+    async fn feed(&self) -> Result<Vec<ChainlinkPrice>, Box<dyn Error + Send + Sync>> {
+        let mut prices = Vec::new();
+        for (symbol, aggregator) in &self.feeds {
+            prices.push(self.latest_price(symbol, *aggregator).await?);
+        }
+        Ok(prices)
+    }
+}
+
+fn answer_to_f64(answer: I256, decimals: u8) -> f64 {
+    answer.to_string().parse::<f64>().unwrap_or(f64::NAN) / 10f64.powi(decimals.into())
+}
+
+/// Check a Binance mark price against a Chainlink answer fetched at `now` (Unix seconds).
+pub fn cross_check(
+    binance: f64,
+    chainlink: &ChainlinkPrice,
+    now: u64,
+    config: &PriceCheckConfig,
+) -> Result<(), PriceCheckError> {
+    let age_secs = now.saturating_sub(chainlink.updated_at);
+    if age_secs > config.max_age_secs {
+        return Err(PriceCheckError::Stale {
+            symbol: chainlink.symbol.clone(),
+            age_secs,
+        });
+    }
+    if !(chainlink.price.is_finite() && chainlink.price > 0.0) {
+        return Err(PriceCheckError::InvalidAnswer {
+            symbol: chainlink.symbol.clone(),
+            answer: chainlink.price,
+        });
+    }
+    let divergence_bps = (binance - chainlink.price).abs() / chainlink.price * 10_000.0;
+    // A NaN Binance price diverges too
+    if divergence_bps.is_nan() || divergence_bps > config.max_divergence_bps as f64 {
+        return Err(PriceCheckError::Diverged {
+            symbol: chainlink.symbol.clone(),
+            binance,
+            chainlink: chainlink.price,
+            divergence_bps,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_answer_to_f64() {
+        // ETH/USD answers carry 8 decimals
+        let answer = I256::try_from(325_012_345_678i64).unwrap();
+        assert!((answer_to_f64(answer, 8) - 3250.12345678).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_cross_check() {
+        let config = PriceCheckConfig::default();
+        let chainlink = ChainlinkPrice {
+            symbol: "ETHUSDT".to_string(),
+            price: 3000.0,
+            updated_at: 1_700_000_000,
+        };
+        let now = 1_700_000_600;
+        assert_eq!(cross_check(3010.0, &chainlink, now, &config), Ok(()));
+        assert!(matches!(
+            cross_check(3100.0, &chainlink, now, &config),
+            Err(PriceCheckError::Diverged { .. })
+        ));
+        assert!(matches!(
+            cross_check(f64::NAN, &chainlink, now, &config),
+            Err(PriceCheckError::Diverged { .. })
+        ));
+        assert_eq!(
+            cross_check(3000.0, &chainlink, now + 86_400, &config),
+            Err(PriceCheckError::Stale {
+                symbol: "ETHUSDT".to_string(),
+                age_secs: 87_000
+            })
+        );
+    }
+}
@@ -0,0 +1,82 @@
+use super::Feed;
+use crate::config::{DexFilter, OnchainPriceConfig};
+use crate::executor::eisen::{quote_amount_out, ChainData};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::error::Error;
+
+/// USD prices of on-chain tokens, from small Eisen quotes into the configured quote stablecoin.
+pub struct EisenPriceFeed<'a> {
+    base_url: &'a str,
+    client: &'a reqwest::Client,
+    chain_data: &'a ChainData,
+    symbols: Vec<String>,
+    config: &'a OnchainPriceConfig,
+}
+
+impl<'a> EisenPriceFeed<'a> {
+    pub fn new(
+        base_url: &'a str,
+        client: &'a reqwest::Client,
+        chain_data: &'a ChainData,
+        symbols: Vec<String>,
+        config: &'a OnchainPriceConfig,
+    ) -> Self {
+        Self {
+            base_url,
+            client,
+            chain_data,
+            symbols,
+            config,
+        }
+    }
+
+    async fn price(&self, symbol: &str) -> Result<f64, Box<dyn Error + Send + Sync>> {
+        if self
+            .config
+            .stablecoins
+            .iter()
+            .any(|stable| stable.eq_ignore_ascii_case(symbol))
+        {
+            return Ok(1.0);
+        }
+        let amount_out = quote_amount_out(
+            self.client,
+            self.base_url,
+            self.chain_data,
+            symbol,
+            &self.config.quote_token,
+            self.config.quote_amount,
+            &DexFilter::default(),
+        )
+        .await?;
+        Ok(amount_out / self.config.quote_amount)
+    }
+}
+
+/// Prices keyed by symbol as given. Tokens that cannot be quoted are left out so
+/// one illiquid token does not hide the others' values.
+#[async_trait]
+impl Feed<HashMap<String, f64>> for EisenPriceFeed<'_> {
+    async fn feed(&self) -> Result<HashMap<String, f64>, Box<dyn Error + Send + Sync>> {
+        let prices =
+            futures::future::join_all(self.symbols.iter().map(|symbol| self.price(symbol))).await;
+        Ok(self
+            .symbols
+            .iter()
+            .zip(prices)
+            .filter_map(|(symbol, price)| match price {
+                Ok(price) => Some((symbol.clone(), price)),
+                Err(err) => {
+                    println!(
+                        "Failed to price {} on {}: {}",
+                        symbol,
+                        self.chain_data.name(),
+                        err
+                    );
+                    None
+                }
+            })
+            .collect())
+    }
+}
@@ -0,0 +1,129 @@
+use super::Feed;
+use crate::utils::price::PriceData;
+use async_trait::async_trait;
+use chrono::Utc;
+use std::error::Error;
+use std::time::Duration;
+
+/// Prices from `primary`, or from `fallback` when `primary` errors or its price is older
+/// than `max_age`.
+pub struct FallbackPriceFeed<P, F> {
+    primary: P,
+    fallback: F,
+    max_age: Duration,
+}
+
+impl<P, F> FallbackPriceFeed<P, F> {
+    pub fn new(primary: P, fallback: F, max_age: Duration) -> Self {
+        Self {
+            primary,
+            fallback,
+            max_age,
+        }
+    }
+}
+
+/// Why a price cannot be used, if it is too old at `now_ms` or has no market price.
+fn unusable(price: &PriceData, now_ms: u128, max_age: Duration) -> Option<String> {
+    let age_ms = now_ms.saturating_sub(price.timestamp);
+    if age_ms > max_age.as_millis() {
+        return Some(format!("{} price is {}s old", price.source, age_ms / 1000));
+    }
+    if price.market_price.is_none() {
+        return Some(format!("{} returned no market price", price.source));
+    }
+    None
+}
+
+#[async_trait]
+impl<P, F> Feed<PriceData> for FallbackPriceFeed<P, F>
+where
+    P: Feed<PriceData> + Send + Sync,
+    F: Feed<PriceData> + Send + Sync,
+{
+    async fn feed(&self) -> Result<PriceData, Box<dyn Error + Send + Sync>> {
+        let reason = match self.primary.feed().await {
+            Ok(price) => {
+                match unusable(&price, Utc::now().timestamp_millis() as u128, self.max_age) {
+                    None => return Ok(price),
+                    Some(reason) => reason,
+                }
+            }
+            Err(err) => err.to_string(),
+        };
+        println!("Falling back to the secondary price feed: {}", reason);
+        let price = self
+            .fallback
+            .feed()
+            .await
+            .map_err(|e| format!("{}; fallback feed failed too: {}", reason, e))?;
+        if let Some(fallback_reason) =
+            unusable(&price, Utc::now().timestamp_millis() as u128, self.max_age)
+        {
+            return Err(format!("{}; {}", reason, fallback_reason).into());
+        }
+        Ok(price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::price::PriceSource;
+
+    struct FixedFeed(Result<PriceData, String>);
+
+    #[async_trait]
+    impl Feed<PriceData> for FixedFeed {
+        async fn feed(&self) -> Result<PriceData, Box<dyn Error + Send + Sync>> {
+            self.0.clone().map_err(Into::into)
+        }
+    }
+
+    fn price(source: PriceSource, age: Duration) -> PriceData {
+        PriceData {
+            timestamp: Utc::now().timestamp_millis() as u128 - age.as_millis(),
+            market_price: Some(100.0),
+            buy_long_price: None,
+            sell_short_price: None,
+            cur_funding_rate: None,
+            predicted_funding_rate: None,
+            next_funding_time: None,
+            funding_history: None,
+            source,
+            depth: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fallback_on_error_and_stale_price() {
+        let max_age = Duration::from_secs(60);
+        let fresh = FallbackPriceFeed::new(
+            FixedFeed(Ok(price(PriceSource::Binance, Duration::ZERO))),
+            FixedFeed(Ok(price(PriceSource::Pyth, Duration::ZERO))),
+            max_age,
+        );
+        assert_eq!(fresh.feed().await.unwrap().source, PriceSource::Binance);
+
+        let failing = FallbackPriceFeed::new(
+            FixedFeed(Err("HTTP 503".to_string())),
+            FixedFeed(Ok(price(PriceSource::Pyth, Duration::ZERO))),
+            max_age,
+        );
+        assert_eq!(failing.feed().await.unwrap().source, PriceSource::Pyth);
+
+        let stale = FallbackPriceFeed::new(
+            FixedFeed(Ok(price(PriceSource::Binance, Duration::from_secs(600)))),
+            FixedFeed(Ok(price(PriceSource::Pyth, Duration::ZERO))),
+            max_age,
+        );
+        assert_eq!(stale.feed().await.unwrap().source, PriceSource::Pyth);
+
+        let both_stale = FallbackPriceFeed::new(
+            FixedFeed(Ok(price(PriceSource::Binance, Duration::from_secs(600)))),
+            FixedFeed(Ok(price(PriceSource::Pyth, Duration::from_secs(600)))),
+            max_age,
+        );
+        assert!(both_stale.feed().await.is_err());
+    }
+}
@@ -0,0 +1,254 @@
+use super::binance::{BinanceIndicators, OHLCV};
+use crate::constants::Interval;
+use crate::storage::WalStore;
+use crate::utils::price::PriceData;
+use anyhow::Result;
+use chrono::{DateTime, Duration as TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// One candle of `symbol` on `interval`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CandleRecord {
+    pub symbol: String,
+    pub interval: Interval,
+    pub candle: OHLCV,
+}
+
+/// A price of `symbol` as a feed returned it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceRecord {
+    pub symbol: String,
+    pub price: PriceData,
+}
+
+/// Candles and prices the feeds collected within the retention window, persisted in WAL
+/// stores so backtests and post-mortems can replay what a strategy saw.
+pub struct MarketHistory {
+    candles: WalStore<CandleRecord>,
+    prices: WalStore<PriceRecord>,
+    retention: TimeDelta,
+}
+
+impl MarketHistory {
+    pub fn open(data_dir: &Path, retention_days: u32) -> Result<Self> {
+        Ok(Self {
+            candles: WalStore::open(data_dir, "candle_history")?,
+            prices: WalStore::open(data_dir, "price_history")?,
+            retention: TimeDelta::days(retention_days.into()),
+        })
+    }
+
+    /// Store `candles` and drop records past retention as of `now`. A candle replaces
+    /// the stored one with the same open time, so an open candle ends up closed.
+    pub fn record_candles(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        candles: &[OHLCV],
+        now: DateTime<Utc>,
+    ) -> Result<()> {
+        for candle in candles {
+            self.candles.put(
+                format!("{}:{}:{}", symbol, interval, candle.timestamp),
+                CandleRecord {
+                    symbol: symbol.to_string(),
+                    interval,
+                    candle: candle.clone(),
+                },
+            )?;
+        }
+        self.prune(now)
+    }
+
+    /// Store a price snapshot and drop records past retention as of `now`.
+    pub fn record_price(&self, symbol: &str, price: &PriceData, now: DateTime<Utc>) -> Result<()> {
+        self.prices.put(
+            format!("{}:{}:{}", symbol, price.source, price.timestamp),
+            PriceRecord {
+                symbol: symbol.to_string(),
+                price: price.clone(),
+            },
+        )?;
+        self.prune(now)
+    }
+
+    /// Candles of `symbol` on `interval` opened within `[since, until)`, oldest first.
+    pub fn candles(
+        &self,
+        symbol: &str,
+        interval: Interval,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Vec<OHLCV> {
+        let range = millis(since)..millis(until);
+        let mut candles: Vec<OHLCV> = self
+            .candles
+            .entries()
+            .into_iter()
+            .map(|(_, record)| record)
+            .filter(|record| record.symbol == symbol && record.interval == interval)
+            .map(|record| record.candle)
+            .filter(|candle| range.contains(&candle.timestamp))
+            .collect();
+        candles.sort_by_key(|candle| candle.timestamp);
+        candles
+    }
+
+    /// Prices of `symbol` timestamped within `[since, until)`, oldest first.
+    pub fn prices(
+        &self,
+        symbol: &str,
+        since: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Vec<PriceData> {
+        let range = millis(since)..millis(until);
+        let mut prices: Vec<PriceData> = self
+            .prices
+            .entries()
+            .into_iter()
+            .map(|(_, record)| record)
+            .filter(|record| record.symbol == symbol)
+            .map(|record| record.price)
+            .filter(|price| range.contains(&price.timestamp))
+            .collect();
+        prices.sort_by_key(|price| price.timestamp);
+        prices
+    }
+
+    /// Record every candle a feed pipeline publishes until its channel closes.
+    pub fn spawn_recorder(
+        self: Arc<Self>,
+        symbol: String,
+        interval: Interval,
+        mut updates: broadcast::Receiver<BinanceIndicators>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match updates.recv().await {
+                    Ok(update) => {
+                        if let Err(err) =
+                            self.record_candles(&symbol, interval, &[update.ohlcv], Utc::now())
+                        {
+                            println!("Failed to record {} candle: {}", symbol, err);
+                        }
+                    }
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        println!(
+                            "Candle recorder for {} skipped {} update(s)",
+                            symbol, skipped
+                        );
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        })
+    }
+
+    fn prune(&self, now: DateTime<Utc>) -> Result<()> {
+        let before = millis(now - self.retention);
+        for (key, record) in self.candles.entries() {
+            if record.candle.timestamp < before {
+                self.candles.remove(&key)?;
+            }
+        }
+        for (key, record) in self.prices.entries() {
+            if record.price.timestamp < before {
+                self.prices.remove(&key)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn millis(time: DateTime<Utc>) -> u128 {
+    time.timestamp_millis().max(0) as u128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::price::PriceSource;
+    use tempfile::tempdir;
+
+    fn candle(time: DateTime<Utc>, close: f64) -> OHLCV {
+        OHLCV {
+            timestamp: millis(time),
+            open: close,
+            high: close,
+            low: close,
+            close,
+            volume: 1.0,
+        }
+    }
+
+    fn price(time: DateTime<Utc>, market_price: f64) -> PriceData {
+        PriceData {
+            timestamp: millis(time),
+            market_price: Some(market_price),
+            buy_long_price: None,
+            sell_short_price: None,
+            cur_funding_rate: None,
+            predicted_funding_rate: None,
+            next_funding_time: None,
+            funding_history: None,
+            source: PriceSource::Binance,
+            depth: None,
+        }
+    }
+
+    #[test]
+    fn test_history_load_and_retention() -> Result<()> {
+        let dir = tempdir()?;
+        let start = "2026-01-01T00:00:00Z".parse::<DateTime<Utc>>()?;
+        let hour = TimeDelta::hours(1);
+        {
+            let history = MarketHistory::open(dir.path(), 7)?;
+            history.record_candles(
+                "BTCUSDT",
+                Interval::Hour1,
+                &[candle(start, 100.0), candle(start + hour, 101.0)],
+                start + hour,
+            )?;
+            // The open candle is replaced once it closes
+            history.record_candles(
+                "BTCUSDT",
+                Interval::Hour1,
+                &[candle(start + hour, 102.0)],
+                start + hour * 2,
+            )?;
+            history.record_candles("ETHUSDT", Interval::Hour1, &[candle(start, 5.0)], start)?;
+            history.record_price("BTCUSDT", &price(start, 100.0), start)?;
+        }
+
+        let history = MarketHistory::open(dir.path(), 7)?;
+        let closes = |since, until| {
+            history
+                .candles("BTCUSDT", Interval::Hour1, since, until)
+                .iter()
+                .map(|candle| candle.close)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(closes(start, start + hour * 2), vec![100.0, 102.0]);
+        assert_eq!(closes(start + hour, start + hour * 2), vec![102.0]);
+        assert!(history
+            .candles("BTCUSDT", Interval::Min1, start, start + hour * 2)
+            .is_empty());
+        assert_eq!(history.prices("BTCUSDT", start, start + hour).len(), 1);
+
+        let later = start + TimeDelta::days(8);
+        history.record_price("BTCUSDT", &price(later, 110.0), later)?;
+        assert!(closes(start, later).is_empty());
+        let prices = history.prices("BTCUSDT", start, later + hour);
+        assert_eq!(
+            prices.iter().map(|p| p.market_price).collect::<Vec<_>>(),
+            vec![Some(110.0)]
+        );
+        Ok(())
+    }
+}
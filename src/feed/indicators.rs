@@ -0,0 +1,262 @@
+use super::binance::OHLCV;
+use serde::Serialize;
+use std::fmt;
+
+/// Lookback periods of the indicators, defaulting to the usual chart settings.
+#[derive(Debug, Clone, Copy)]
+pub struct IndicatorPeriods {
+    pub rsi: usize,
+    pub ema_short: usize,
+    pub ema_long: usize,
+    /// EMA of the MACD line.
+    pub macd_signal: usize,
+    pub bollinger: usize,
+    /// Standard deviations between the Bollinger middle and outer bands.
+    pub bollinger_width: f64,
+    pub atr: usize,
+    /// RSI values the stochastic RSI ranks the latest one against.
+    pub stoch_rsi: usize,
+}
+
+impl Default for IndicatorPeriods {
+    fn default() -> Self {
+        Self {
+            rsi: 14,
+            ema_short: 12,
+            ema_long: 26,
+            macd_signal: 9,
+            bollinger: 20,
+            bollinger_width: 2.0,
+            atr: 14,
+            stoch_rsi: 14,
+        }
+    }
+}
+
+/// Latest value of each indicator over a candle window. An indicator is `None` while
+/// the window is shorter than its lookback.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TechnicalIndicators {
+    pub rsi: Option<f64>,
+    pub ema_short: Option<f64>,
+    pub ema_long: Option<f64>,
+    pub macd: Option<f64>,
+    pub macd_signal: Option<f64>,
+    pub macd_histogram: Option<f64>,
+    pub bollinger_upper: Option<f64>,
+    pub bollinger_middle: Option<f64>,
+    pub bollinger_lower: Option<f64>,
+    pub atr: Option<f64>,
+    pub vwap: Option<f64>,
+    /// Where the RSI sits in its recent range, from 0 to 1.
+    pub stoch_rsi: Option<f64>,
+}
+
+impl TechnicalIndicators {
+    /// Indicators of `candles`, oldest first.
+    pub fn from_candles(candles: &[OHLCV], periods: &IndicatorPeriods) -> Self {
+        let closes: Vec<f64> = candles.iter().map(|candle| candle.close).collect();
+        let rsi = rsi_series(&closes, periods.rsi);
+
+        let ema_short = ema_series(&closes, periods.ema_short);
+        let ema_long = ema_series(&closes, periods.ema_long);
+        // Both series end at the last close; the long one starts later
+        let macd_line: Vec<f64> = ema_short
+            .iter()
+            .skip(ema_short.len().saturating_sub(ema_long.len()))
+            .zip(&ema_long)
+            .map(|(short, long)| short - long)
+            .collect();
+        let macd_signal = ema_series(&macd_line, periods.macd_signal).last().copied();
+        let macd = macd_line.last().copied();
+
+        let bollinger = bollinger_bands(&closes, periods.bollinger, periods.bollinger_width);
+        Self {
+            rsi: rsi.last().copied(),
+            ema_short: ema_short.last().copied(),
+            ema_long: ema_long.last().copied(),
+            macd,
+            macd_signal,
+            macd_histogram: macd.zip(macd_signal).map(|(macd, signal)| macd - signal),
+            bollinger_upper: bollinger.map(|(_, upper, _)| upper),
+            bollinger_middle: bollinger.map(|(middle, _, _)| middle),
+            bollinger_lower: bollinger.map(|(_, _, lower)| lower),
+            atr: atr(candles, periods.atr),
+            vwap: vwap(candles),
+            stoch_rsi: stoch_rsi(&rsi, periods.stoch_rsi),
+        }
+    }
+}
+
+impl fmt::Display for TechnicalIndicators {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = |value: Option<f64>, dp: usize| match value {
+            Some(value) => format!("{:.*}", dp, value),
+            None => "n/a".to_string(),
+        };
+        write!(
+            f,
+            "RSI {}, StochRSI {}, MACD {} / signal {} (hist {}), BB {} / {} / {}, ATR {}, VWAP {}",
+            value(self.rsi, 1),
+            value(self.stoch_rsi, 2),
+            value(self.macd, 2),
+            value(self.macd_signal, 2),
+            value(self.macd_histogram, 2),
+            value(self.bollinger_lower, 2),
+            value(self.bollinger_middle, 2),
+            value(self.bollinger_upper, 2),
+            value(self.atr, 2),
+            value(self.vwap, 2),
+        )
+    }
+}
+
+/// EMA seeded with the SMA of the first `period` values; one value per input from the
+/// `period`th on.
+fn ema_series(values: &[f64], period: usize) -> Vec<f64> {
+    if period == 0 || values.len() < period {
+        return Vec::new();
+    }
+    let k = 2.0 / (period as f64 + 1.0);
+    let mut ema = values[..period].iter().sum::<f64>() / period as f64;
+    let mut series = vec![ema];
+    for value in &values[period..] {
+        ema = (value - ema) * k + ema;
+        series.push(ema);
+    }
+    series
+}
+
+/// Wilder's RSI; one value per close from the `period + 1`th on.
+fn rsi_series(closes: &[f64], period: usize) -> Vec<f64> {
+    if period == 0 || closes.len() <= period {
+        return Vec::new();
+    }
+    let changes: Vec<f64> = closes.windows(2).map(|pair| pair[1] - pair[0]).collect();
+    let rsi = |gain: f64, loss: f64| {
+        if gain + loss == 0.0 {
+            50.0
+        } else {
+            100.0 * gain / (gain + loss)
+        }
+    };
+    let mut gain = changes[..period].iter().map(|c| c.max(0.0)).sum::<f64>() / period as f64;
+    let mut loss = changes[..period].iter().map(|c| (-c).max(0.0)).sum::<f64>() / period as f64;
+    let mut series = vec![rsi(gain, loss)];
+    for change in &changes[period..] {
+        gain = (gain * (period - 1) as f64 + change.max(0.0)) / period as f64;
+        loss = (loss * (period - 1) as f64 + (-change).max(0.0)) / period as f64;
+        series.push(rsi(gain, loss));
+    }
+    series
+}
+
+fn stoch_rsi(rsi: &[f64], period: usize) -> Option<f64> {
+    if period == 0 || rsi.len() < period {
+        return None;
+    }
+    let window = &rsi[rsi.len() - period..];
+    let min = window.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = window.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    // A flat RSI has no range to rank against
+    (max > min).then(|| (window[period - 1] - min) / (max - min))
+}
+
+/// (middle, upper, lower) of the last `period` closes.
+fn bollinger_bands(closes: &[f64], period: usize, width: f64) -> Option<(f64, f64, f64)> {
+    if period == 0 || closes.len() < period {
+        return None;
+    }
+    let window = &closes[closes.len() - period..];
+    let mean = window.iter().sum::<f64>() / period as f64;
+    let variance = window.iter().map(|c| (c - mean).powi(2)).sum::<f64>() / period as f64;
+    let band = variance.sqrt() * width;
+    Some((mean, mean + band, mean - band))
+}
+
+/// Wilder-smoothed average true range.
+fn atr(candles: &[OHLCV], period: usize) -> Option<f64> {
+    if period == 0 || candles.len() <= period {
+        return None;
+    }
+    let true_ranges: Vec<f64> = candles
+        .windows(2)
+        .map(|pair| {
+            let (prev_close, candle) = (pair[0].close, &pair[1]);
+            (candle.high - candle.low)
+                .max((candle.high - prev_close).abs())
+                .max((candle.low - prev_close).abs())
+        })
+        .collect();
+    let mut atr = true_ranges[..period].iter().sum::<f64>() / period as f64;
+    for true_range in &true_ranges[period..] {
+        atr = (atr * (period - 1) as f64 + true_range) / period as f64;
+    }
+    Some(atr)
+}
+
+/// Volume-weighted typical price over the whole window.
+fn vwap(candles: &[OHLCV]) -> Option<f64> {
+    let volume: f64 = candles.iter().map(|candle| candle.volume).sum();
+    if volume <= 0.0 {
+        return None;
+    }
+    let value: f64 = candles
+        .iter()
+        .map(|candle| (candle.high + candle.low + candle.close) / 3.0 * candle.volume)
+        .sum();
+    Some(value / volume)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn candle(close: f64) -> OHLCV {
+        OHLCV {
+            timestamp: 0,
+            open: close,
+            high: close + 1.0,
+            low: close - 1.0,
+            close,
+            volume: 10.0,
+        }
+    }
+
+    #[test]
+    fn test_indicator_math() {
+        assert_eq!(ema_series(&[1.0, 2.0, 3.0, 4.0], 3), vec![2.0, 3.0]);
+        // Only gains is an RSI of 100, alternating equal moves 50
+        assert_eq!(rsi_series(&[1.0, 2.0, 3.0], 2), vec![100.0]);
+        assert_eq!(rsi_series(&[1.0, 2.0, 1.0], 2), vec![50.0]);
+        assert_eq!(stoch_rsi(&[40.0, 60.0, 50.0], 3), Some(0.5));
+        assert_eq!(stoch_rsi(&[50.0, 50.0], 2), None);
+
+        let (middle, upper, lower) = bollinger_bands(&[1.0, 3.0], 2, 2.0).unwrap();
+        assert_eq!((middle, upper, lower), (2.0, 4.0, 0.0));
+
+        // A gap up widens the true range past the candle's own range
+        let candles = [candle(10.0), candle(10.0), candle(14.0)];
+        assert_eq!(atr(&candles, 2), Some((2.0 + 5.0) / 2.0));
+        assert_eq!(vwap(&candles), Some(34.0 / 3.0));
+    }
+
+    #[test]
+    fn test_from_candles() {
+        let candles: Vec<OHLCV> = (0..60).map(|i| candle(100.0 + i as f64)).collect();
+        let indicators = TechnicalIndicators::from_candles(&candles, &IndicatorPeriods::default());
+        assert_eq!(indicators.rsi, Some(100.0));
+        // A steady trend has a constant MACD, so the signal catches up with it
+        let macd = indicators.macd.unwrap();
+        assert!(macd > 0.0);
+        assert!(indicators.macd_histogram.unwrap().abs() < 1e-9);
+        assert_eq!(indicators.bollinger_middle, Some(149.5));
+        assert_eq!(indicators.atr, Some(2.0));
+
+        let short = TechnicalIndicators::from_candles(&candles[..10], &IndicatorPeriods::default());
+        assert_eq!(short.macd, None);
+        assert_eq!(short.rsi, None);
+        assert!(short.vwap.is_some());
+    }
+}
@@ -2,6 +2,14 @@ use async_trait::async_trait;
 use std::error::Error;
 
 pub mod binance;
+pub mod binance_market_ws;
+pub mod binance_ws;
+pub mod chainlink;
+pub mod eisen;
+pub mod fallback;
+pub mod history;
+pub mod indicators;
+pub mod pyth;
 pub mod service;
 
 #[async_trait]
@@ -0,0 +1,101 @@
+use super::Feed;
+use crate::utils::price::{PriceData, PriceSource};
+use async_trait::async_trait;
+use reqwest::Client as ReqwestClient;
+use serde::Deserialize;
+use std::error::Error;
+
+#[derive(Debug, Deserialize)]
+struct LatestPriceResponse {
+    parsed: Vec<ParsedPriceUpdate>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ParsedPriceUpdate {
+    price: PythPrice,
+}
+
+#[derive(Debug, Deserialize)]
+struct PythPrice {
+    /// Price as an integer scaled by 10^expo.
+    price: String,
+    expo: i32,
+    /// Unix seconds.
+    publish_time: u64,
+}
+
+/// Latest Pyth price of one feed, from the Hermes REST API.
+pub struct PythPriceFeed<'a> {
+    pub base_url: &'a str,
+    pub client: &'a ReqwestClient,
+    /// Hex id of the Pyth price feed.
+    pub price_id: &'a str,
+}
+
+impl<'a> PythPriceFeed<'a> {
+    pub fn new(base_url: &'a str, client: &'a ReqwestClient, price_id: &'a str) -> Self {
+        Self {
+            base_url,
+            client,
+            price_id,
+        }
+    }
+}
+
+#[async_trait]
+impl Feed<PriceData> for PythPriceFeed<'_> {
+    async fn feed(&self) -> Result<PriceData, Box<dyn Error + Send + Sync>> {
+        let response = self
+            .client
+            .get(format!("{}/v2/updates/price/latest", self.base_url))
+            .query(&[("ids[]", self.price_id), ("parsed", "true")])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<LatestPriceResponse>()
+            .await?;
+        let update = response
+            .parsed
+            .first()
+            .ok_or_else(|| format!("No Pyth price for feed {}", self.price_id))?;
+        parse_price(&update.price)
+    }
+}
+
+fn parse_price(price: &PythPrice) -> Result<PriceData, Box<dyn Error + Send + Sync>> {
+    let market_price = price.price.parse::<f64>()? * 10f64.powi(price.expo);
+    Ok(PriceData {
+        timestamp: u128::from(price.publish_time) * 1000,
+        market_price: Some(market_price),
+        buy_long_price: None,
+        sell_short_price: None,
+        cur_funding_rate: None,
+        predicted_funding_rate: None,
+        next_funding_time: None,
+        funding_history: None,
+        source: PriceSource::Pyth,
+        depth: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_price() {
+        let response: LatestPriceResponse = serde_json::from_value(serde_json::json!({
+            "binary": { "encoding": "hex", "data": [] },
+            "parsed": [{
+                "id": "e62df6c8b4a85fe1a67db44dc12de5db330f7ac66b72dc658afedf0f4a415b43",
+                "price": { "price": "6412345000000", "conf": "3500000000", "expo": -8, "publish_time": 1700000000 },
+                "ema_price": { "price": "6400000000000", "conf": "3000000000", "expo": -8, "publish_time": 1700000000 }
+            }]
+        }))
+        .unwrap();
+        let price = parse_price(&response.parsed[0].price).unwrap();
+        assert!((price.market_price.unwrap() - 64123.45).abs() < 1e-6);
+        assert_eq!(price.timestamp, 1_700_000_000_000);
+        assert_eq!(price.source, PriceSource::Pyth);
+    }
+}
@@ -1,12 +1,159 @@
 use super::{Feed, Processor};
+use std::error::Error;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
 
-struct FeedService<T, G> {
-    feed: Box<dyn Feed<T>>,
-    processors: Box<dyn Processor<T, G>>,
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Receiving end of a `FeedService`: a broadcast of every processed value, and the
+/// latest one for readers that only need the current state.
+#[derive(Clone)]
+pub struct FeedHandle<T> {
+    sender: broadcast::Sender<T>,
+    /// The latest value and when it was published.
+    latest: Arc<RwLock<Option<(Instant, T)>>>,
+}
+
+impl<T: Clone> FeedHandle<T> {
+    fn new() -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            sender,
+            latest: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    pub fn subscribe(&self) -> broadcast::Receiver<T> {
+        self.sender.subscribe()
+    }
+
+    /// The latest value, if it was published within `max_age`; a feed failing its ticks
+    /// keeps the last value it published.
+    pub fn latest(&self, max_age: Duration) -> Option<T> {
+        let latest = self.latest.read().unwrap_or_else(|e| e.into_inner());
+        latest
+            .as_ref()
+            .filter(|(published_at, _)| published_at.elapsed() <= max_age)
+            .map(|(_, value)| value.clone())
+    }
+
+    fn publish(&self, value: T) {
+        *self.latest.write().unwrap_or_else(|e| e.into_inner()) =
+            Some((Instant::now(), value.clone()));
+        // No receivers is not an error; the value is simply dropped.
+        let _ = self.sender.send(value);
+    }
+}
+
+/// Runs a feed every `interval`, pipes each value through a processor and publishes
+/// the result on a `FeedHandle`.
+pub struct FeedService<I, O> {
+    name: String,
+    feed: Box<dyn Feed<I> + Send + Sync>,
+    processor: Box<dyn Processor<O, I> + Send + Sync>,
+    interval: Duration,
+    handle: FeedHandle<O>,
+}
+
+impl<I, O> FeedService<I, O>
+where
+    I: Send + Sync + 'static,
+    O: Into<I> + Clone + Send + Sync + 'static,
+{
+    pub fn new(
+        name: String,
+        feed: Box<dyn Feed<I> + Send + Sync>,
+        processor: Box<dyn Processor<O, I> + Send + Sync>,
+        interval: Duration,
+    ) -> Self {
+        Self {
+            name,
+            feed,
+            processor,
+            interval,
+            handle: FeedHandle::new(),
+        }
+    }
+
+    pub fn handle(&self) -> FeedHandle<O> {
+        self.handle.clone()
+    }
+
+    /// Fetch, process and publish one value.
+    pub async fn tick(&self) -> Result<(), Box<dyn Error + Send + Sync>> {
+        let input = self.feed.feed().await?;
+        let output = self.processor.process(&input).await?;
+        self.handle.publish(output);
+        Ok(())
+    }
+
+    /// Tick forever in a background task; a failed tick is logged and retried on the
+    /// next one.
+    pub fn spawn(self) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(self.interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if let Err(err) = self.tick().await {
+                    println!("Feed {} failed: {}", self.name, err);
+                }
+            }
+        })
+    }
 }
 
-impl<T, G> FeedService<T, G> {
-    pub fn new(feed: Box<dyn Feed<T>>, processors: Box<dyn Processor<T, G>>) -> Self {
-        Self { feed, processors }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::feed::binance::{BinanceIndicators, BinanceOHLCVProcessor, OHLCV};
+    use crate::feed::indicators::IndicatorPeriods;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    /// Candles closing one higher every tick.
+    struct RisingFeed(AtomicU64);
+
+    #[async_trait]
+    impl Feed<OHLCV> for RisingFeed {
+        async fn feed(&self) -> Result<OHLCV, Box<dyn Error + Send + Sync>> {
+            let i = self.0.fetch_add(1, Ordering::SeqCst);
+            let close = 100.0 + i as f64;
+            Ok(OHLCV {
+                timestamp: i.into(),
+                open: close,
+                high: close + 1.0,
+                low: close - 1.0,
+                close,
+                volume: 1.0,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_ticks_publish_processed_values() -> Result<(), Box<dyn Error + Send + Sync>> {
+        let service: FeedService<OHLCV, BinanceIndicators> = FeedService::new(
+            "BTCUSDT".to_string(),
+            Box::new(RisingFeed(AtomicU64::new(0))),
+            Box::new(BinanceOHLCVProcessor::new(50, IndicatorPeriods::default())),
+            Duration::from_secs(60),
+        );
+        let handle = service.handle();
+        let mut updates = handle.subscribe();
+        assert!(handle.latest(Duration::from_secs(60)).is_none());
+
+        for _ in 0..20 {
+            service.tick().await?;
+        }
+        assert_eq!(updates.recv().await?.ohlcv.close, 100.0);
+        let latest = handle.latest(Duration::from_secs(60)).unwrap();
+        assert_eq!(latest.ohlcv.close, 119.0);
+        // 20 candles cover the 14 period RSI but not the 26 period MACD
+        assert_eq!(latest.indicators.rsi, Some(100.0));
+        assert_eq!(latest.indicators.macd, None);
+        assert!(handle.latest(Duration::ZERO).is_none());
+        Ok(())
     }
 }
@@ -1,35 +1,92 @@
+use crate::agent::attestation::OperatorSet;
+use crate::agent::memory::{self, ExecutionMemory};
 use crate::agent::othentic::OthenticAgent;
-use crate::agent::Strategy;
-use crate::error::AppError;
+use crate::agent::usage::{DailyUsage, UsageMeter};
+use crate::agent::validation::{
+    scale_to_budget, validate_stable_farming, validate_strategy, StrategyContext,
+    StrategyValidationError,
+};
+use crate::agent::{ReplyEvent, ReplySink, Strategy};
+use crate::audit::{self, AuditFilter, AuditKind, AuditRecord};
+use crate::config::{AgentFallback, CorsConfig, LlmConfig, RiskConfig, StrategyValidationConfig};
+use crate::constants::Interval;
+use crate::engine::prompt::YIELD_FARMING_TEMPLATE;
+use crate::engine::{
+    ChatEngine, EngineKind, FundingRebalancer, GuardedEngine, LlmEngine, NoOpEngine,
+    StrategyEngine, StrategyInput, OTHENTIC_PROVIDER,
+};
+use crate::error::{AppError, ExecutionError};
 use crate::executor;
+use crate::executor::binance::{
+    client_order_id, futures_symbol, FuturesMarket, LiveBinanceExecutor, OrderExecutor, TimeInForce,
+};
+use crate::executor::bridge::BridgeStatus;
+use crate::executor::chains::BASE_CHAIN_ID;
 use crate::executor::eisen::fetch_chain_portfolio;
+use crate::executor::eisen::ChainData;
 use crate::executor::eisen::ChainPortfolio;
-use crate::feed::binance::BinancePriceFeed;
+use crate::executor::eisen::{Permit2Signer, SwapOptions};
+use crate::executor::queue::{LegState, QueuedLeg};
+use crate::feed::binance::{BinanceOHLCVFeed, BinancePriceFeed, Positioning, OHLCV};
+use crate::feed::chainlink::{cross_check, ChainlinkFeed};
+use crate::feed::eisen::EisenPriceFeed;
+use crate::feed::fallback::FallbackPriceFeed;
+use crate::feed::indicators::{IndicatorPeriods, TechnicalIndicators};
+use crate::feed::pyth::PythPriceFeed;
+use crate::feed::Feed;
+use crate::hedge::{plan_hedge, unfilled_share, HedgeContext};
+use crate::notify::NotificationEvent;
+use crate::plan::{
+    preview_amount, project_portfolio, PendingPlan, PlanLookupError, ProjectedPortfolio,
+};
 use crate::portfolio::binance::fetch_binance_portfolio;
-use crate::portfolio::binance::AccountInfo;
-use crate::processors::{process_binance_place_order, process_eisen_swaps};
+use crate::portfolio::binance::{
+    fetch_income, fetch_margin_assets, fetch_spot_balances, AccountInfo,
+};
+use crate::portfolio::eisen::get_onchain_portfolio;
+use crate::portfolio::history::{PnlReport, PortfolioSnapshot};
+use crate::portfolio::margin::MarginHealth;
+use crate::processors::{
+    await_bridge_arrivals, process_binance_place_order, process_binance_transfers,
+    process_bridge_transfers, process_eisen_swaps, queued_legs, unwind_binance_orders,
+};
+use crate::rebalance::{RebalanceError, RebalancePlan};
+use crate::report::{
+    BridgeOutcome, ExecutionReport, FailurePolicy, HedgeReport, OrderOutcome, StoredReport,
+    SwapOutcome, SwapReport,
+};
+use crate::risk::{base_asset, symbol_token};
+use crate::scheduler::{RunGuard, RunStatus, Schedule, ScheduledRun};
+use crate::stop_loss;
 use crate::types;
 use crate::types::MarketPrices;
 use crate::utils::format;
+use crate::utils::parser::extract_binance_place_order;
+use crate::utils::price::PriceData;
 use crate::utils::sign::BinanceKey;
-use crate::yields::Yield;
-use crate::yields::CombinedYields;
-use crate::yields::CombinedYieldFetcher;
-use crate::yields::APR;
-use crate::yields::{Aave, Eigen, Lido};
-use alloy::network::EthereumWallet;
-use alloy::providers::{Provider, ProviderBuilder};
-use alloy::signers::local::PrivateKeySigner;
+use crate::yields::{
+    AprRecord, CachedYields, CostEstimator, NetYields, RankedApr, YieldFilter, YieldSort,
+};
+use alloy::primitives::Address;
 use axum::{
-    extract::{Json, Query, State},
-    http::StatusCode,
-    response::IntoResponse,
+    extract::{Json, Path, Query, Request, State},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        IntoResponse, Response,
+    },
 };
-use reqwest;
+use futures::Stream;
+use rust_decimal::prelude::ToPrimitive;
 use serde::{Deserialize, Serialize};
-use std::env;
-use std::error::Error as StdError;
-use std::io::{self, Error as IoError};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+// Agent model asked for strategies that do not name one
+const DEFAULT_MODEL: &str = "o1";
+/// How long bridge transfers still in flight when a job ends are followed for.
+const BRIDGE_WATCH_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
 
 #[derive(Debug, Serialize)]
 pub struct HealthCheckResponse {
@@ -37,6 +94,19 @@ pub struct HealthCheckResponse {
     pub message: String,
 }
 
+/// Middleware refusing the state-changing requests `cors` says another site could have
+/// sent, before they reach a handler.
+pub async fn reject_cross_site(
+    State(cors): State<Arc<CorsConfig>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    match cors.refuses(request.method(), request.headers()) {
+        Some(reason) => AppError::forbidden(reason).into_response(),
+        None => next.run(request).await,
+    }
+}
+
 // Handler for GET /health
 pub async fn health_check() -> Result<impl IntoResponse, AppError> {
     let response = HealthCheckResponse {
@@ -47,10 +117,40 @@ pub async fn health_check() -> Result<impl IntoResponse, AppError> {
     Ok((StatusCode::OK, Json(response)))
 }
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExecuteMode {
+    /// Fetch the strategy and execute it immediately.
+    #[default]
+    Execute,
+    /// Return the strategy and projected portfolio; execute later via /execute/confirm.
+    Preview,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ExecuteStrategyParams {
     pub wallet_address: String,
+    /// Engine asked for the strategy; `provider` and `model` only apply to the LLM.
+    #[serde(default)]
+    pub engine: EngineKind,
+    /// LLM provider, "othentic" or a configured chat provider; the configured default
+    /// when unset.
+    pub provider: Option<String>,
+    /// The provider's default model when unset.
     pub model: Option<String>,
+    /// Prompt template of the request to a chat provider, by name; the built-in one
+    /// when unset.
+    pub prompt_template: Option<String>,
+    #[serde(default)]
+    pub mode: ExecuteMode,
+    /// Retrying with the same job id never places the same Binance order twice.
+    pub job_id: Option<String>,
+    /// What happens to the remaining legs when one fails.
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+    /// Combined USD notional of the strategy's legs; all of them are scaled down by the
+    /// same factor when they add up to more.
+    pub max_notional_usd: Option<f64>,
 }
 
 fn format_json(value: &serde_json::Value) -> Result<String, AppError> {
@@ -58,90 +158,352 @@ fn format_json(value: &serde_json::Value) -> Result<String, AppError> {
         .map_err(|e| AppError::internal_error(format!("Failed to format JSON: {}", e)))
 }
 
-async fn fetch_prices(
-    binance_base_url: &String,
-    reqwest_cli: &reqwest::Client,
-) -> Result<types::MarketPrices, AppError> {
-    let btcsymbol = "BTCUSDT".to_string();
-    let btc_price_feed = BinancePriceFeed::new(binance_base_url, reqwest_cli, &btcsymbol);
-
-    let ethsymbol = "ETHUSDT".to_string();
-    let eth_price_feed = BinancePriceFeed::new(binance_base_url, reqwest_cli, &ethsymbol);
-
-    let btc_price = btc_price_feed
-        .fetch_index_price()
-        .await
-        .map_err(|e| AppError::internal_error(e.to_string()))?;
-    let eth_price = eth_price_feed
-        .fetch_index_price()
-        .await
-        .map_err(|e| AppError::internal_error(e.to_string()))?;
+async fn fetch_prices(state: &types::AppState) -> Result<types::MarketPrices, AppError> {
+    fetch_prices_reusing(state, &HashMap::new()).await
+}
 
+/// Market prices, taking the mark price of price data already `fetched` by symbol
+/// rather than fetching it again while the stream is behind.
+async fn fetch_prices_reusing(
+    state: &types::AppState,
+    fetched: &HashMap<String, PriceData>,
+) -> Result<types::MarketPrices, AppError> {
+    let mark_price = |symbol: &'static str| async move {
+        let max_age = std::time::Duration::from_secs(state.config.market_data.max_price_age_secs);
+        let reused = state
+            .prices
+            .mark_price(symbol, max_age)
+            .or_else(|| fetched.get(symbol)?.market_price);
+        match reused {
+            Some(price) => Ok(price),
+            None => fetch_mark_price(state, symbol).await,
+        }
+    };
     Ok(types::MarketPrices {
-        eth: eth_price.mark_price.parse::<f64>().unwrap_or(0.0),
-        btc: btc_price.mark_price.parse::<f64>().unwrap_or(0.0),
+        eth: mark_price("ETHUSDT").await?,
+        btc: mark_price("BTCUSDT").await?,
+        redemption_rates: state.yields.redemption_rates(),
     })
 }
 
-fn get_provider(rpc_url: &String) -> Result<Box<dyn Provider>, Box<dyn StdError>> {
-    let signer: PrivateKeySigner = match env::var("PRIVATE_KEY_DEPLOYER") {
-        Ok(key) => {
-            key.chars()
-                .skip(2) // Skip "0x" prefix
-                .collect::<String>()
-                .parse()
-                .unwrap_or_else(|_| {
-                    println!("Error parsing private key");
-                    panic!("Invalid private key format");
-                })
+/// Mark price from the market data stream, or from REST while the stream is behind.
+/// REST falls back to Pyth when Binance errors or lags.
+async fn fetch_mark_price(state: &types::AppState, symbol: &str) -> Result<f64, AppError> {
+    let max_age = std::time::Duration::from_secs(state.config.market_data.max_price_age_secs);
+    if let Some(price) = state.prices.mark_price(symbol, max_age) {
+        return Ok(price);
+    }
+    let fallback = &state.config.price_fallback;
+    let symbol = symbol.to_string();
+    let binance_feed = BinancePriceFeed::new(&state.binance_base_url, &state.reqwest_cli, &symbol)
+        .with_depth_levels(state.config.market_data.depth_levels);
+    let price_data = match fallback.price_ids.get(&symbol) {
+        Some(price_id) => {
+            FallbackPriceFeed::new(
+                binance_feed,
+                PythPriceFeed::new(&fallback.hermes_url, &state.reqwest_cli, price_id),
+                std::time::Duration::from_secs(fallback.max_age_secs),
+            )
+            .feed()
+            .await
         }
-        Err(_) => {
-            println!("PRIVATE_KEY_DEPLOYER not set in environment");
-            return Err(Box::new(IoError::new(
-                io::ErrorKind::NotFound,
-                "PRIVATE_KEY_DEPLOYER not set in environment",
-            )));
+        None => binance_feed.feed().await,
+    }
+    .map_err(|e| AppError::internal_error(format!("Failed to fetch {} price: {}", symbol, e)))?;
+    println!("{} price from {}", symbol, price_data.source);
+    if let Err(e) = state
+        .market_history
+        .record_price(&symbol, &price_data, chrono::Utc::now())
+    {
+        println!("Failed to record {} price: {}", symbol, e);
+    }
+    price_data
+        .market_price
+        .ok_or_else(|| AppError::internal_error(format!("No {} market price", symbol)))
+}
+
+/// Predicted and trailing funding of each streamed symbol, one line each. Symbols whose
+/// funding cannot be fetched are marked unavailable rather than failing the strategy.
+async fn fetch_funding(state: &types::AppState) -> (String, HashMap<String, PriceData>) {
+    let symbols = &state.config.market_data.symbols;
+    let feeds: Vec<_> = symbols
+        .iter()
+        .map(|symbol| BinancePriceFeed::new(&state.binance_base_url, &state.reqwest_cli, symbol))
+        .collect();
+    let prices = futures::future::join_all(feeds.iter().map(|feed| feed.feed())).await;
+    let mut lines = Vec::with_capacity(symbols.len());
+    let mut funding = HashMap::new();
+    for (symbol, price) in symbols.iter().zip(prices) {
+        match price {
+            Ok(price) => {
+                lines.push(format!("{}: {}", symbol, price.funding_summary()));
+                funding.insert(symbol.clone(), price);
+            }
+            Err(err) => {
+                println!("Failed to fetch {} funding: {}", symbol, err);
+                lines.push(format!("{}: unavailable", symbol));
+            }
         }
-    };
-    let wallet = EthereumWallet::from(signer);
+    }
+    (lines.join("\n"), funding)
+}
 
-    let provider = ProviderBuilder::new()
-        .wallet(wallet.clone())
-        .on_http(reqwest::Url::parse(rpc_url).unwrap());
+/// Open interest and long/short ratio of each streamed symbol, one line each, marked
+/// unavailable like funding when they cannot be fetched.
+async fn fetch_positioning(state: &types::AppState) -> (String, HashMap<String, Positioning>) {
+    let symbols = &state.config.market_data.symbols;
+    let feeds: Vec<_> = symbols
+        .iter()
+        .map(|symbol| BinancePriceFeed::new(&state.binance_base_url, &state.reqwest_cli, symbol))
+        .collect();
+    let positioning =
+        futures::future::join_all(feeds.iter().map(|feed| feed.fetch_positioning())).await;
+    let mut lines = Vec::with_capacity(symbols.len());
+    let mut by_symbol = HashMap::new();
+    for (symbol, positioning) in symbols.iter().zip(positioning) {
+        match positioning {
+            Ok(positioning) => {
+                lines.push(format!("{}: {}", symbol, positioning));
+                by_symbol.insert(symbol.clone(), positioning);
+            }
+            Err(err) => {
+                println!("Failed to fetch {} positioning: {}", symbol, err);
+                lines.push(format!("{}: unavailable", symbol));
+            }
+        }
+    }
+    (lines.join("\n"), by_symbol)
+}
 
-    Ok(Box::new(provider))
+/// Technical indicators of each streamed symbol on the configured timeframe, one line
+/// each, marked unavailable like funding when the candles cannot be fetched. Symbols the
+/// feed pipeline has published indicators for are not fetched again.
+async fn fetch_indicators(
+    state: &types::AppState,
+) -> (String, HashMap<String, TechnicalIndicators>) {
+    let config = &state.config.market_data;
+    let interval = config.indicator_interval;
+    // A pipeline that missed a few ticks is behind, so its symbol is fetched again
+    let max_age = std::time::Duration::from_secs(config.pipeline_interval_secs * 3);
+    let indicators = config.symbols.iter().map(|symbol| async move {
+        if let Some(latest) = state
+            .indicator_feeds
+            .get(symbol)
+            .and_then(|handle| handle.latest(max_age))
+        {
+            return Ok(latest.indicators);
+        }
+        let candles = BinanceOHLCVFeed::new(
+            state.binance_base_url.clone(),
+            state.reqwest_cli.clone(),
+            symbol.clone(),
+            config.indicator_candles,
+            vec![interval],
+        )
+        .feed()
+        .await?;
+        Ok::<_, Box<dyn std::error::Error + Send + Sync>>(TechnicalIndicators::from_candles(
+            candles
+                .get(&interval)
+                .map(Vec::as_slice)
+                .unwrap_or_default(),
+            &IndicatorPeriods::default(),
+        ))
+    });
+    let mut lines = Vec::with_capacity(config.symbols.len());
+    let mut by_symbol = HashMap::new();
+    for (symbol, indicators) in config
+        .symbols
+        .iter()
+        .zip(futures::future::join_all(indicators).await)
+    {
+        match indicators {
+            Ok(indicators) => {
+                lines.push(format!("{} {}: {}", symbol, interval, indicators));
+                by_symbol.insert(symbol.clone(), indicators);
+            }
+            Err(err) => {
+                println!("Failed to fetch {} candles: {}", symbol, err);
+                lines.push(format!("{} {}: unavailable", symbol, interval));
+            }
+        }
+    }
+    (lines.join("\n"), by_symbol)
 }
 
-async fn fetch_chain_data(
-    eisen_base_url: &String,
-    rpc_url: &String,
-) -> Result<executor::eisen::ChainData, Box<dyn StdError>> {
-    // Get provider
-    let provider = get_provider(rpc_url)?;
+/// Refuse to trade when the mark prices in `used`, by Binance symbol, diverge from
+/// Chainlink, or Chainlink is stale, since either side may be broken or manipulated.
+/// Symbols not in `used` are checked at their current mark price.
+async fn check_prices(
+    state: &types::AppState,
+    used: &HashMap<String, f64>,
+) -> Result<(), AppError> {
+    let config = &state.config.price_check;
+    if !config.enabled {
+        return Ok(());
+    }
+    let provider = state
+        .chains
+        .read_provider(config.chain_id)
+        .map_err(|e| AppError::internal_error(e.to_string()))?;
+    let chainlink_prices = ChainlinkFeed::new(provider, config)
+        .map_err(|e| AppError::internal_error(e.to_string()))?
+        .feed()
+        .await
+        .map_err(|e| {
+            AppError::service_unavailable(format!("Failed to read Chainlink prices: {}", e))
+        })?;
+    let now = chrono::Utc::now().timestamp() as u64;
+    for chainlink in &chainlink_prices {
+        let binance = match used.get(&chainlink.symbol) {
+            Some(price) => *price,
+            None => fetch_mark_price(state, &chainlink.symbol).await?,
+        };
+        cross_check(binance, chainlink, now, config)
+            .map_err(|e| AppError::service_unavailable(e.to_string()))?;
+    }
+    Ok(())
+}
 
-    // Get chain metadata
-    let chain_id = match provider.get_chain_id().await {
-        Ok(id) => id,
-        Err(err) => {
-            println!("Error getting chain ID: {:?}", err);
-            return Err(Box::new(IoError::new(
-                io::ErrorKind::Other,
-                err.to_string(),
-            )));
+/// Refuse Binance orders that would cross a wide spread or sweep past the summed book
+/// levels, since a thin book fills them far from the mark price. Limit orders, capping
+/// their own fills, are only refused when priced too far past the book.
+async fn check_depth(state: &types::AppState, strategy: &Strategy) -> Result<(), AppError> {
+    let config = &state.config.market_data;
+    for order in extract_binance_place_order(strategy, state.risk.contracts()) {
+        // COIN-M books are not on the USD-M depth endpoint
+        if FuturesMarket::of_symbol(&order.symbol) != FuturesMarket::UsdMargined {
+            continue;
         }
-    };
+        let Some(quantity) = order.quantity.and_then(|quantity| quantity.to_f64()) else {
+            continue;
+        };
+        let depth =
+            BinancePriceFeed::new(&state.binance_base_url, &state.reqwest_cli, &order.symbol)
+                .with_depth_levels(config.depth_levels)
+                .fetch_book_depth()
+                .await
+                .map_err(|e| {
+                    AppError::service_unavailable(format!(
+                        "Failed to fetch {} order book: {}",
+                        order.symbol, e
+                    ))
+                })?
+                .ok_or_else(|| {
+                    AppError::service_unavailable(format!("{} order book is empty", order.symbol))
+                })?;
+        println!("{} book: {}", order.symbol, depth);
+        let checked = match order.price.and_then(|price| price.to_f64()) {
+            Some(price) => depth.check_limit(
+                order.side,
+                price,
+                matches!(order.time_in_force, Some(TimeInForce::Gtx)),
+                config.max_limit_cross_pct,
+            ),
+            None => depth.check_order(order.side, quantity, config.max_spread_bps),
+        };
+        checked.map_err(|e| AppError::service_unavailable(format!("{} {}", order.symbol, e)))?;
+    }
+    Ok(())
+}
 
-    let chain_data = match executor::eisen::get_chain_metadata(eisen_base_url, chain_id).await {
-        Ok(data) => data,
-        Err(err) => {
-            println!("Error getting chain metadata: {:?}", err);
-            return Err(Box::new(IoError::new(
-                io::ErrorKind::Other,
-                err.to_string(),
-            )));
-        }
+/// Base balances of the wallet, valued in USD where Eisen can quote the token, and its
+/// protocol positions on every chain when Eisen can list them.
+async fn fetch_onchain_portfolio(
+    state: &types::AppState,
+    wallet_address: &String,
+) -> Result<ChainPortfolio, AppError> {
+    let onchain = |e: anyhow::Error| {
+        let message = format!(
+            "On-chain portfolio of {} unavailable: {}",
+            wallet_address, e
+        );
+        println!("{}", message);
+        ExecutionError::Onchain(message)
     };
+    let chain_metadata = state
+        .chain_metadata
+        .get(BASE_CHAIN_ID)
+        .await
+        .map_err(onchain)?;
+    let (portfolio, underlying) = tokio::join!(
+        fetch_chain_portfolio(
+            &state.reqwest_cli,
+            &state.eisen_base_url,
+            &chain_metadata,
+            wallet_address,
+        ),
+        get_onchain_portfolio(&state.reqwest_cli, &state.eisen_base_url, wallet_address)
+    );
+    let mut portfolio = portfolio.map_err(onchain)?;
+    if let Some(paper) = &state.paper_onchain {
+        paper.apply_to(BASE_CHAIN_ID, &mut portfolio);
+    }
+    // The legs only act on the balances, so they go ahead without the positions
+    match underlying {
+        Ok(underlying) => portfolio.positions = underlying.positions(),
+        Err(e) => println!(
+            "Protocol positions of {} unavailable, leaving them out: {}",
+            wallet_address, e
+        ),
+    }
+    let symbols = portfolio
+        .balances
+        .iter()
+        .map(|balance| balance.symbol.clone())
+        .collect();
+    match EisenPriceFeed::new(
+        &state.eisen_base_url,
+        &state.reqwest_cli,
+        &chain_metadata,
+        symbols,
+        &state.config.onchain_prices,
+    )
+    .feed()
+    .await
+    {
+        Ok(prices) => portfolio.apply_prices(&prices),
+        Err(err) => println!("Failed to price the Base portfolio: {}", err),
+    }
+    Ok(portfolio)
+}
 
+// Chain metadata for every chain the strategy swaps on or bridges between
+async fn fetch_swap_chain_data(
+    state: &types::AppState,
+    strategy: &Strategy,
+) -> Result<HashMap<u64, ChainData>, AppError> {
+    let swap_chains = strategy
+        .exchanges
+        .eisen
+        .swaps
+        .iter()
+        .flatten()
+        .map(|swap| swap.chain_id());
+    let bridge_chains = strategy
+        .exchanges
+        .bridges
+        .transfers
+        .iter()
+        .flatten()
+        .flat_map(|transfer| [transfer.from_chain_id, transfer.to_chain_id]);
+    let mut chain_data = HashMap::new();
+    for chain_id in swap_chains.chain(bridge_chains) {
+        if chain_data.contains_key(&chain_id) {
+            continue;
+        }
+        state
+            .chains
+            .rpc_url(chain_id)
+            .map_err(|e| AppError::bad_request(e.to_string()))?;
+        let data = state.chain_metadata.get(chain_id).await.map_err(|e| {
+            AppError::internal_error(format!(
+                "Failed to fetch metadata for chain {}: {}",
+                chain_id, e
+            ))
+        })?;
+        chain_data.insert(chain_id, data);
+    }
     Ok(chain_data)
 }
 
@@ -153,103 +515,1169 @@ pub struct ExecuteStrategyResponse {
     pub binance_portfolio: AccountInfo,
     pub onchain_portfolio: ChainPortfolio,
     pub strategy: Strategy,
+    pub report: ExecutionReport,
 }
 
-// Handler for POST /api/v1/execute
-pub async fn execute_strategy(
-    State(state): State<types::AppState>,
-    Json(params): Json<ExecuteStrategyParams>,
-) -> Result<impl IntoResponse, AppError> {
-    println!(
-        "Processing request with wallet address: {}",
-        params.wallet_address
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewStrategyResponse<'a> {
+    pub status: String,
+    pub message: String,
+    pub plan_hash: String,
+    pub expires_in_secs: u64,
+    pub findings: Vec<String>,
+    pub projected_portfolio: ProjectedPortfolio,
+    pub binance_portfolio: &'a AccountInfo,
+    pub onchain_portfolio: &'a ChainPortfolio,
+    pub strategy: &'a Strategy,
+}
+
+// Everything gathered before any order is placed
+struct PreparedStrategy {
+    binance_key: BinanceKey,
+    chain_data: HashMap<u64, ChainData>,
+    binance_portfolio: AccountInfo,
+    onchain_portfolio: ChainPortfolio,
+    strategy: Strategy,
+    /// Factor the legs were scaled by to fit the notional budget, if they were.
+    notional_scale: Option<f64>,
+    /// Gaps to the configured target allocation, when there is one.
+    rebalance: Option<RebalancePlan>,
+}
+
+fn binance_key(state: &types::AppState) -> BinanceKey {
+    state.binance_key.clone()
+}
+
+// Futures account from Binance, or the simulated one when paper trading
+async fn fetch_binance_account(
+    state: &types::AppState,
+    binance_key: &BinanceKey,
+) -> Result<AccountInfo, AppError> {
+    if let Some(paper_executor) = &state.paper_executor {
+        return Ok(paper_executor.account_info());
+    }
+    let mut account =
+        fetch_binance_portfolio(&state.reqwest_cli, &state.binance_base_url, binance_key)
+            .await
+            .map_err(|e| {
+                println!("Binance account unavailable: {}", e);
+                ExecutionError::Binance(e.to_string())
+            })?;
+
+    // Spot and margin need their own API permissions (and testnet keys), so a failure
+    // leaves them out rather than failing the futures account
+    let spot_base_url = executor::binance::spot_base_url(&state.binance_base_url);
+    let accounts = &state.config.binance_accounts;
+    let (spot_balances, margin_assets) = tokio::join!(
+        async {
+            if accounts.spot {
+                Some(fetch_spot_balances(&state.reqwest_cli, spot_base_url, binance_key).await)
+            } else {
+                None
+            }
+        },
+        async {
+            if accounts.margin {
+                Some(fetch_margin_assets(&state.reqwest_cli, spot_base_url, binance_key).await)
+            } else {
+                None
+            }
+        }
+    );
+    match spot_balances {
+        Some(Ok(balances)) => account.spot_balances = balances,
+        Some(Err(e)) => println!("Failed to fetch Binance spot balances: {}", e),
+        None => {}
+    }
+    match margin_assets {
+        Some(Ok(assets)) => account.margin_assets = assets,
+        Some(Err(e)) => println!("Failed to fetch Binance margin account: {}", e),
+        None => {}
+    }
+    Ok(account)
+}
+
+/// Provider the `kind` engine asks: `provider`, else the one configured for the engine.
+fn engine_provider<'a>(
+    config: &'a LlmConfig,
+    kind: EngineKind,
+    provider: Option<&'a str>,
+) -> &'a str {
+    provider.unwrap_or(if kind == EngineKind::StableYieldFarming {
+        &config.yield_farming_provider
+    } else {
+        &config.provider
+    })
+}
+
+/// Engine asking `provider`'s agent, guarded by its timeout, retries and circuit breaker.
+fn llm_engine(
+    state: &types::AppState,
+    kind: EngineKind,
+    provider: Option<&str>,
+    model: Option<&str>,
+    template: Option<&str>,
+    sink: Option<ReplySink>,
+) -> Result<Box<dyn StrategyEngine>, ExecutionError> {
+    let config = &state.config.llm;
+    let farming = kind == EngineKind::StableYieldFarming;
+    let provider = engine_provider(config, kind, provider);
+    let othentic = provider.eq_ignore_ascii_case(OTHENTIC_PROVIDER);
+    if farming && othentic {
+        return Err(ExecutionError::Config(
+            "The stable yield farming engine needs a chat provider, not othentic".to_string(),
+        ));
+    }
+    let engine: Box<dyn StrategyEngine> = if othentic {
+        let othentic = &state.config.othentic;
+        let mut agent = OthenticAgent::new(
+            state.agents.client().clone(),
+            othentic.host.clone(),
+            othentic.port,
+            Some(othentic.task_definition_id.to_string()),
+        )
+        .with_usage(Some(UsageMeter::new(
+            state.agent_usage.clone(),
+            OTHENTIC_PROVIDER,
+            "none",
+        )));
+        if othentic.verify_signatures {
+            let operators = OperatorSet::new(&othentic.operators)
+                .map_err(|e| ExecutionError::Config(e.to_string()))?;
+            agent = agent.with_operators(operators);
+        }
+        Box::new(LlmEngine::new(
+            agent,
+            model.unwrap_or(DEFAULT_MODEL).to_string(),
+        ))
+    } else {
+        let agent = state
+            .agents
+            .create(provider, model)
+            .map_err(|e| ExecutionError::Config(e.to_string()))?;
+        let engine = ChatEngine::new(agent, state.prompts.clone(), template.map(String::from))
+            .with_sink(sink);
+        Box::new(if farming {
+            engine.with_system_template(YIELD_FARMING_TEMPLATE)
+        } else {
+            engine
+        })
+    };
+    let fallback: Box<dyn StrategyEngine> = match config.circuit_breaker.fallback {
+        AgentFallback::NoOp => Box::new(NoOpEngine::new(format!(
+            "The {} agent is unavailable, leaving the portfolio as it is",
+            provider
+        ))),
+        AgentFallback::FundingRebalancer => Box::new(FundingRebalancer::new(
+            &state.config.funding_rebalancer,
+            &state.config.exposure,
+        )),
+    };
+    Ok(Box::new(GuardedEngine::new(
+        provider,
+        engine,
+        fallback,
+        state.agent_breakers.get(provider),
+        config,
+    )))
+}
+
+/// The wallet's latest executions and what came of them, for the agent to build on.
+fn execution_memory(
+    state: &types::AppState,
+    wallet_address: &str,
+    binance: &AccountInfo,
+    onchain: &ChainPortfolio,
+    prices: &MarketPrices,
+) -> Vec<ExecutionMemory> {
+    let config = &state.config.strategy_memory;
+    if config.executions == 0 {
+        return Vec::new();
+    }
+    let now = chrono::Utc::now();
+    let since = now - chrono::Duration::days(config.lookback_days.into());
+    let reports = state
+        .reports
+        .recent(wallet_address, since, config.executions);
+    let Some(oldest) = reports.last() else {
+        return Vec::new();
+    };
+    // From a day before the oldest, so a snapshot precedes it
+    let snapshots = state.portfolio_history.query(
+        wallet_address,
+        oldest.executed_at - chrono::Duration::days(1),
     );
+    let current = PortfolioSnapshot::new(wallet_address, binance, onchain, prices, now);
+    memory::recall(&reports, &snapshots, &current, &state.config.exposure)
+}
+
+// Ask the engine for a strategy scaled to `max_notional_usd`, asking again while the
+// scaled strategy breaks the validation rules
+#[allow(clippy::too_many_arguments)]
+async fn prepare_strategy(
+    state: &types::AppState,
+    wallet_address: &String,
+    engine: EngineKind,
+    provider: Option<&str>,
+    model: Option<&str>,
+    template: Option<&str>,
+    max_notional_usd: Option<f64>,
+    sink: Option<ReplySink>,
+) -> Result<PreparedStrategy, AppError> {
     println!("Using Binance base URL: {}", state.binance_base_url);
     println!("Using Eisen base URL: {}", state.eisen_base_url);
-    let base_rpc_url: String = "https://mainnet.base.org".to_string();
     // Create a Binance key from the API credentials
-    let binance_key: BinanceKey = BinanceKey {
-        api_key: state.binance_api_key.clone(),
-        secret_key: state.binance_api_secret.clone(),
-    };
-    let provider =
-        get_provider(&base_rpc_url).map_err(|e| AppError::internal_error(e.to_string()))?;
+    let binance_key = binance_key(state);
     println!("Fetching crypto prices from Binance...");
-    let market_prices: MarketPrices =
-        fetch_prices(&state.binance_base_url, &state.reqwest_cli).await?;
-    let price_data = format!("Market price:\n{}", market_prices);
+    let ((funding, funding_data), (positioning, positioning_data), (indicators, indicator_data)) = tokio::join!(
+        fetch_funding(state),
+        fetch_positioning(state),
+        fetch_indicators(state)
+    );
+    // The funding fetch already read the mark prices
+    let market_prices: MarketPrices = fetch_prices_reusing(state, &funding_data).await?;
+    let price_data = format!(
+        "Market price:\n{}\n\nFunding rates:\n{}\n\nPositioning:\n{}\n\nIndicators:\n{}",
+        market_prices, funding, positioning, indicators
+    );
 
     println!("Price data: {}", price_data);
     println!("Fetching Binance portfolio data...");
 
-    let binance_portfolio = fetch_binance_portfolio(&state.binance_base_url, &binance_key)
-        .await
-        .map_err(|e| AppError::internal_error(e.to_string()))?;
+    let binance_portfolio = fetch_binance_account(state, &binance_key).await?;
 
     println!("Binance portfolio: {:?}", binance_portfolio);
-    println!("Wallet address: {}", params.wallet_address);
+    println!("Wallet address: {}", wallet_address);
 
-    let chain_data = fetch_chain_data(&state.eisen_base_url, &base_rpc_url)
-        .await
-        .map_err(|e| AppError::internal_error(e.to_string()))?;
-    let onchain_portfolio =
-        executor::eisen::fetch_chain_portfolio(&state.eisen_base_url, 8453, &params.wallet_address)
-            .await
-            .map_err(|e| AppError::internal_error(e.to_string()))?;
-    println!("Base chain portfolio: {:#?}", onchain_portfolio);
+    let onchain_portfolio = fetch_onchain_portfolio(state, wallet_address).await?;
+    println!("On-chain portfolio: {:#?}", onchain_portfolio);
 
-    let portfolio_str = format!(
-        "{}\n\n{:#?}",
+    let mut portfolio_str = format!(
+        "{}\n\n{}",
         format::format_binance_portfolio(&binance_portfolio),
-        onchain_portfolio
+        format::format_onchain_data(&onchain_portfolio)
     );
+    let margin_health = MarginHealth::new(&binance_portfolio, &state.config.margin_health);
+    state.notifier.margin_health(&margin_health);
+    if !margin_health.warnings.is_empty() {
+        portfolio_str = format!("{}\n\n{}", portfolio_str, margin_health);
+    }
 
     println!("Fetching yields...");
-    let yield_fetcher = CombinedYieldFetcher::new();
-    let yields = yield_fetcher.get_apr().await.map_err(|e| AppError::internal_error(e.to_string()))?;
-    let yield_str = format!("Yields: {}", yields);
+    let yields = state.yields.get().await;
+    let mut yield_str = format!("Yields: {}", yields);
+    let prompt_notional_usd = state.config.yields.costs.prompt_notional_usd;
+    if prompt_notional_usd > 0.0 {
+        match cost_estimator(state).await {
+            Ok(estimator) => {
+                let net_yields = estimator
+                    .net_yields(yields.yields.all(), prompt_notional_usd)
+                    .await;
+                yield_str = format!("{}\n{}", yield_str, net_yields);
+            }
+            Err(e) => println!("Leaving effective APRs out of the prompt: {}", e),
+        }
+    }
     println!("Yields: {}", yield_str);
 
-    let othentic_agent = OthenticAgent::new("localhost".to_string(), 4003, Some("0".to_string()));
+    let history = execution_memory(
+        state,
+        wallet_address,
+        &binance_portfolio,
+        &onchain_portfolio,
+        &market_prices,
+    );
+    if !history.is_empty() {
+        println!("{}", memory::format_memories(&history));
+    }
+
+    let strategy_engine: Box<dyn StrategyEngine> = match engine {
+        EngineKind::Llm | EngineKind::StableYieldFarming => {
+            llm_engine(state, engine, provider, model, template, sink).inspect_err(|e| {
+                println!("No {:?} engine to ask: {}", engine, e);
+            })?
+        }
+        EngineKind::FundingRebalancer => Box::new(FundingRebalancer::new(
+            &state.config.funding_rebalancer,
+            &state.config.exposure,
+        )),
+    };
     println!(
-        "Fetching strategy from Othentic... \n\n{}\n\n{}\n\n{}",
-        price_data, portfolio_str, yield_str
+        "Fetching strategy from the {:?} engine... \n\n{}\n\n{}\n\n{}",
+        engine, price_data, portfolio_str, yield_str
     );
-    let strategy = othentic_agent
-        .get_strategy(
-            &params.model.unwrap_or("o1".to_string()),
-            &price_data,
-            &portfolio_str,
-            &yield_str,
-        )
-        .await
-        .map_err(|e| AppError::internal_error(e.to_string()))?;
+    let mut rebalance = match RebalancePlan::new(
+        &state.config.rebalance,
+        &state.config.exposure,
+        &binance_portfolio,
+        &onchain_portfolio,
+        &market_prices,
+    ) {
+        Ok(plan) => Some(plan),
+        Err(RebalanceError::NoTarget) => None,
+        Err(e) => {
+            println!(
+                "Failed to compute the rebalance to the target allocation: {}",
+                e
+            );
+            None
+        }
+    };
+    let input = StrategyInput {
+        price_data: &price_data,
+        portfolio: &portfolio_str,
+        yields: &yield_str,
+        binance: &binance_portfolio,
+        onchain: &onchain_portfolio,
+        prices: &market_prices,
+        funding: &funding_data,
+        positioning: &positioning_data,
+        indicators: &indicator_data,
+        margin_health: &margin_health,
+        yield_data: &yields,
+        risk: &state.config.risk,
+        history: &history,
+    };
+    let mut feedback = None;
+    let mut attempts = 0;
+    let (strategy, chain_data, notional_scale) = loop {
+        let mut strategy = match strategy_engine.strategy(&input, feedback.as_deref()).await {
+            Ok(strategy) => strategy,
+            Err(e) if state.config.rebalance.fallback && rebalance.is_some() => {
+                println!(
+                    "Strategy engine failed ({}), falling back to the target allocation",
+                    e
+                );
+                rebalance.take().unwrap().strategy
+            }
+            Err(e) => {
+                println!("Strategy engine failed: {}", e);
+                return Err(ExecutionError::Agent(e.to_string()).into());
+            }
+        };
+
+        println!("{:#?}", strategy);
+        let chain_data = fetch_swap_chain_data(state, &strategy).await?;
+        let context = StrategyContext {
+            binance: &binance_portfolio,
+            onchain: &onchain_portfolio,
+            prices: &market_prices,
+            exposure: &state.config.exposure,
+            chain_data: &chain_data,
+        };
+        let checked = check_strategy(
+            &mut strategy,
+            &context,
+            engine,
+            max_notional_usd,
+            &state.config.strategy_validation,
+        );
+        match checked {
+            Ok(notional_scale) => {
+                // Validated, the dependencies resolve
+                strategy.resolve_dependencies().map_err(|e| {
+                    println!("Strategy rejected: {}", e);
+                    ExecutionError::Validation(e)
+                })?;
+                break (strategy, chain_data, notional_scale);
+            }
+            Err(e) if attempts < state.config.strategy_validation.reprompt_attempts => {
+                attempts += 1;
+                println!("{}, asking the engine again", e);
+                feedback = Some(e.to_string());
+            }
+            Err(e) => {
+                println!("{}, giving up", e);
+                return Err(ExecutionError::Validation(e.to_string()).into());
+            }
+        }
+    };
+
+    Ok(PreparedStrategy {
+        binance_key,
+        chain_data,
+        binance_portfolio,
+        onchain_portfolio,
+        strategy,
+        notional_scale,
+        rebalance,
+    })
+}
+
+/// Scale `strategy` down to `max_notional_usd`, then validate it as made by `engine`, so
+/// the scaled legs are the ones held to the limits. The factor applied, if any.
+fn check_strategy(
+    strategy: &mut Strategy,
+    context: &StrategyContext,
+    engine: EngineKind,
+    max_notional_usd: Option<f64>,
+    config: &StrategyValidationConfig,
+) -> Result<Option<f64>, StrategyValidationError> {
+    let notional_scale =
+        max_notional_usd.and_then(|budget| scale_to_budget(strategy, context, budget));
+    validate_strategy(strategy, context, config)?;
+    if engine == EngineKind::StableYieldFarming {
+        validate_stable_farming(strategy, context)?;
+    }
+    Ok(notional_scale)
+}
 
-    println!("{:#?}", strategy);
-    process_binance_place_order(&strategy, &state.binance_base_url, &binance_key)
+// Every side effect of the job goes to the audit log under its id and wallet
+#[allow(clippy::too_many_arguments)]
+async fn run_strategy(
+    state: &types::AppState,
+    binance_key: &BinanceKey,
+    chain_data: &HashMap<u64, ChainData>,
+    strategy: &Strategy,
+    binance_portfolio: &AccountInfo,
+    onchain_portfolio: &ChainPortfolio,
+    wallet_address: &String,
+    job_id: &str,
+    failure_policy: FailurePolicy,
+    risk_limits: Option<&RiskConfig>,
+    notional_scale: Option<f64>,
+) -> Result<ExecutionReport, AppError> {
+    let scope = audit::AuditScope {
+        log: state.audit.clone(),
+        job_id: job_id.to_string(),
+        user: wallet_address.to_lowercase(),
+    };
+    let legs = execute_strategy_legs(
+        state,
+        binance_key,
+        chain_data,
+        strategy,
+        binance_portfolio,
+        onchain_portfolio,
+        wallet_address,
+        job_id,
+        failure_policy,
+        risk_limits,
+        notional_scale,
+    );
+    audit::scoped(scope, legs)
         .await
-        .map_err(|e| AppError::internal_error(e.to_string()))?;
+        .inspect_err(|e| println!("Execution of job {} failed: {}", job_id, e))
+}
 
-    // Convert wallet address string to alloy Address type
+#[allow(clippy::too_many_arguments)]
+async fn execute_strategy_legs(
+    state: &types::AppState,
+    binance_key: &BinanceKey,
+    chain_data: &HashMap<u64, ChainData>,
+    strategy: &Strategy,
+    binance_portfolio: &AccountInfo,
+    onchain_portfolio: &ChainPortfolio,
+    wallet_address: &String,
+    job_id: &str,
+    failure_policy: FailurePolicy,
+    risk_limits: Option<&RiskConfig>,
+    notional_scale: Option<f64>,
+) -> Result<ExecutionReport, AppError> {
+    let wallet = wallet_address
+        .parse::<Address>()
+        .map_err(|e| AppError::bad_request(format!("Invalid wallet address: {}", e)))?;
+    ensure_trading(state)?;
+    // Legs are gated against the portfolio the strategy was made for
+    let market_prices = fetch_prices(state).await?;
+    let mut risk = state.risk.gate(
+        binance_portfolio,
+        onchain_portfolio,
+        &market_prices,
+        &state.config.exposure,
+    );
+    // Scheduled runs are held to their own limits as well
+    if let Some(limits) = risk_limits {
+        risk = risk.with_limits(limits);
+    }
+    // Limit prices are checked against the market now, not when the strategy was made
+    let mut marks = HashMap::from([
+        ("ETH".to_string(), market_prices.eth),
+        ("BTC".to_string(), market_prices.btc),
+    ]);
+    for order in extract_binance_place_order(strategy, state.risk.contracts()) {
+        let token = symbol_token(&order.symbol).to_uppercase();
+        if marks.contains_key(&token) {
+            continue;
+        }
+        match fetch_mark_price(state, &futures_symbol(&token, None)).await {
+            Ok(price) => {
+                marks.insert(token, price);
+            }
+            Err(e) => println!("No live {} mark price for the risk limits: {}", token, e),
+        }
+    }
+    // The prices the legs are gated with are the ones checked against Chainlink
+    let used = marks
+        .iter()
+        .map(|(token, price)| (futures_symbol(token, None), *price))
+        .collect();
+    check_prices(state, &used).await?;
+    check_depth(state, strategy).await?;
+    let risk = risk.with_marks(marks);
+    // Build every provider up front so a misconfigured chain or a read-only wallet fails
+    // before any order is placed
+    // Paper trading only reads the chains, so it needs neither a signer nor a relay
+    let mut providers = HashMap::new();
+    for chain_id in chain_data.keys() {
+        let provider = match &state.paper_onchain {
+            Some(_) => state.chains.read_provider(*chain_id),
+            None => state.chains.provider(*chain_id, wallet),
+        }
+        .map_err(|e| ExecutionError::Config(e.to_string()))?;
+        providers.insert(*chain_id, provider);
+    }
+    let mut relays = HashMap::new();
+    for chain_id in chain_data.keys().filter(|_| state.paper_onchain.is_none()) {
+        let relay = state
+            .chains
+            .relay_provider(*chain_id, wallet)
+            .map_err(|e| ExecutionError::Config(e.to_string()))?;
+        if let Some(relay) = relay {
+            println!(
+                "Submitting swaps on chain {} through its private relay",
+                chain_id
+            );
+            relays.insert(*chain_id, relay);
+        }
+    }
 
-    process_eisen_swaps(
-        &strategy,
-        &provider,
-        &state.eisen_base_url,
-        &chain_data,
+    // Transfers run first so margin top-ups land before the orders that need them
+    if state.paper_executor.is_some() {
+        println!("Paper trading: skipping Binance wallet transfers");
+    } else {
+        process_binance_transfers(
+            strategy,
+            &state.reqwest_cli,
+            &state.binance_base_url,
+            binance_key,
+        )
+        .await?;
+    }
+
+    let live_executor = LiveBinanceExecutor {
+        client: &state.reqwest_cli,
+        base_url: &state.binance_base_url,
+        key: binance_key,
+    };
+    let order_executor: &dyn OrderExecutor = match &state.paper_executor {
+        Some(paper_executor) => paper_executor.as_ref(),
+        None => &live_executor,
+    };
+    let mut report = ExecutionReport {
+        job_id: job_id.to_string(),
+        failure_policy,
+        notional_scale,
+        ..Default::default()
+    };
+    let stop = |report: &ExecutionReport| {
+        failure_policy != FailurePolicy::Continue && report.first_failure().is_some()
+    };
+
+    let permit2 = if state.config.swaps.permit2 && state.paper_onchain.is_none() {
+        let signer = state.chains.signer(wallet);
+        let spenders = state.chains.permit2_spenders();
+        Some(Permit2Signer {
+            signer: signer.map_err(|e| ExecutionError::Config(e.to_string()))?,
+            spenders: spenders.map_err(|e| ExecutionError::Config(e.to_string()))?,
+        })
+    } else {
+        None
+    };
+    let swap_options = SwapOptions {
+        slippage_bps: state.config.swaps.slippage_bps,
+        approval: state.config.swaps.approval,
+        permit2,
+        gas: state.config.gas.clone(),
+        simulation: state.config.simulation.clone(),
+        tranches: state.config.swaps.tranches.clone(),
+        native_gas_reserve: state.config.swaps.native_gas_reserve,
+        dex_filters: state.config.swaps.dex_filters.clone(),
+        nonces: state.nonces.clone(),
+        tracker: Some(state.tx_queue.plan(job_id, queued_legs(strategy))),
+        paper: state.paper_onchain.clone(),
+    };
+    // Stages run one after the other, the orders of each before its on-chain legs so a
+    // failed order stops them, or alongside them when failures stop nothing
+    let mut paused = false;
+    let mut open_orders = None;
+    let mut on_chain_failure = None;
+    let stages = strategy.stages();
+    for &stage in &stages {
+        if stop(&report) {
+            break;
+        }
+        if state.paused.is_paused() {
+            paused = true;
+            break;
+        }
+        let orders = process_binance_place_order(
+            strategy,
+            Some(stage),
+            order_executor,
+            job_id,
+            &risk,
+            failure_policy,
+        );
+        // Bridges run before the swaps so bridged funds can be swapped on arrival. A
+        // failure comes back with the legs that ran, so their reports are kept
+        let on_chain = async {
+            let bridges = process_bridge_transfers(
+                strategy,
+                Some(stage),
+                &providers,
+                &state.reqwest_cli,
+                &state.config.bridge,
+                chain_data,
+                wallet_address,
+                &swap_options,
+                failure_policy,
+            )
+            .await;
+            let mut bridges = match bridges {
+                Ok(bridges) => bridges,
+                Err(e) => return (Vec::new(), Vec::new(), Some(e)),
+            };
+            // The swaps may spend what the transfers deliver
+            if state.config.bridge.wait_for_completion {
+                await_bridge_arrivals(
+                    &state.reqwest_cli,
+                    &state.config.bridge,
+                    chain_data,
+                    &mut bridges,
+                    std::time::Duration::from_secs(state.config.bridge.timeout_secs),
+                )
+                .await;
+            }
+            let bridge_failed = bridges
+                .iter()
+                .any(|bridge| matches!(bridge.outcome, BridgeOutcome::Failed { .. }));
+            if bridge_failed && failure_policy != FailurePolicy::Continue {
+                return (bridges, Vec::new(), None);
+            }
+            let swaps = process_eisen_swaps(
+                strategy,
+                Some(stage),
+                &providers,
+                &relays,
+                &state.reqwest_cli,
+                &state.eisen_base_url,
+                chain_data,
+                wallet_address,
+                &swap_options,
+                &risk,
+                failure_policy,
+            )
+            .await;
+            match swaps {
+                Ok(swaps) => (bridges, swaps, None),
+                Err(e) => (bridges, Vec::new(), Some(e)),
+            }
+        };
+        let (orders, (bridges, swaps, failure)) = if failure_policy == FailurePolicy::Continue {
+            tokio::join!(orders, on_chain)
+        } else {
+            let orders = orders.await;
+            let failed = orders
+                .iter()
+                .any(|order| matches!(order.outcome, OrderOutcome::Failed { .. }));
+            if failed {
+                (orders, (Vec::new(), Vec::new(), None))
+            } else {
+                (orders, on_chain.await)
+            }
+        };
+        if let Some(e) = failure {
+            let nothing_ran = report.orders.is_empty()
+                && report.bridges.is_empty()
+                && report.swaps.is_empty()
+                && orders.is_empty()
+                && bridges.is_empty();
+            if nothing_ran {
+                return Err(e.into());
+            }
+            on_chain_failure = Some(format!(
+                "Stopped as the on-chain legs of stage {} failed: {}",
+                stage, e
+            ));
+        }
+        // Later stages count on this one's orders, which resting ones are not yet
+        let open: Vec<&str> = orders
+            .iter()
+            .filter(|order| order.is_open())
+            .map(|order| order.symbol.as_str())
+            .collect();
+        let later = stages.last() != Some(&stage);
+        if !open.is_empty() && later && failure_policy != FailurePolicy::Continue {
+            open_orders = Some(format!(
+                "Stopped as the {} order(s) of stage {} are still open",
+                open.join(", "),
+                stage
+            ));
+        }
+        report.orders.extend(orders);
+        report.bridges.extend(bridges);
+        report.swaps.extend(swaps);
+        if open_orders.is_some() || on_chain_failure.is_some() {
+            break;
+        }
+    }
+    if stop(&report) || paused || open_orders.is_some() || on_chain_failure.is_some() {
+        report.aborted = if paused {
+            Some("Stopped as trading was paused".to_string())
+        } else if open_orders.is_some() {
+            open_orders.clone()
+        } else if on_chain_failure.is_some() {
+            on_chain_failure.clone()
+        } else {
+            report
+                .first_failure()
+                .map(|failure| format!("Stopped after a failed {}", failure))
+        };
+        println!(
+            "Job {}: {}",
+            job_id,
+            report.aborted.as_deref().unwrap_or_default()
+        );
+        // On-chain legs the job never got to
+        if let Some(tracker) = &swap_options.tracker {
+            let queued = state.tx_queue.job(job_id).unwrap_or_default();
+            for (leg, _) in queued
+                .iter()
+                .enumerate()
+                .filter(|(_, queued)| queued.state == LegState::Waiting)
+            {
+                tracker.leg(leg).set_state(LegState::Skipped);
+            }
+        }
+        if failure_policy == FailurePolicy::Unwind && !paused && open_orders.is_none() {
+            unwind_binance_orders(&mut report.orders, order_executor, job_id).await;
+        }
+    } else {
+        println!("Strategy executed");
+    }
+
+    // An unwound job has no hedge left to restore, and a paused one trades no more
+    if state.config.hedge_unwind.enabled && failure_policy != FailurePolicy::Unwind && !paused {
+        let context = HedgeContext {
+            binance: binance_portfolio,
+            onchain: onchain_portfolio,
+            prices: &market_prices,
+            exposure: &state.config.exposure,
+            contracts: state.risk.contracts(),
+        };
+        let hedge = plan_hedge(
+            strategy,
+            &report,
+            &context,
+            state.config.hedge_unwind.tolerance_usd,
+        );
+        for mut leg in hedge {
+            println!(
+                "Job {}: ${:.2} of {} exposure unmatched, compensating",
+                job_id, leg.unmatched_usd, leg.asset
+            );
+            let hedge_job_id = format!("{}:hedge:{}", job_id, leg.asset);
+            let order = process_binance_place_order(
+                &leg.order,
+                None,
+                order_executor,
+                &hedge_job_id,
+                &risk,
+                FailurePolicy::Continue,
+            )
+            .await
+            .pop();
+            // Reverse swaps for what the order did not fill, if it left enough to matter
+            let unfilled = unfilled_share(order.as_ref());
+            let swaps =
+                if leg.unmatched_usd.abs() * unfilled <= state.config.hedge_unwind.tolerance_usd {
+                    Vec::new()
+                } else {
+                    leg.scale_fallback(unfilled);
+                    let options = SwapOptions {
+                        tracker: None,
+                        ..swap_options.clone()
+                    };
+                    let swaps = process_eisen_swaps(
+                        &leg.fallback,
+                        None,
+                        &providers,
+                        &relays,
+                        &state.reqwest_cli,
+                        &state.eisen_base_url,
+                        chain_data,
+                        wallet_address,
+                        &options,
+                        &risk,
+                        FailurePolicy::Continue,
+                    )
+                    .await;
+                    // The strategy has run, so the report is written whatever happens here
+                    match swaps {
+                        Ok(swaps) => swaps,
+                        Err(e) => {
+                            println!("Job {}: {} hedge swaps failed: {}", job_id, leg.asset, e);
+                            leg.fallback
+                                .exchanges
+                                .eisen
+                                .swaps
+                                .iter()
+                                .flatten()
+                                .map(|swap| SwapReport {
+                                    chain_id: swap.chain_id(),
+                                    token_in: swap.token_in.clone(),
+                                    token_out: swap.token_out.clone(),
+                                    amount_in: 0.0,
+                                    outcome: SwapOutcome::Failed {
+                                        error: e.to_string(),
+                                    },
+                                })
+                                .collect()
+                        }
+                    }
+                };
+            report.hedge.push(HedgeReport {
+                asset: leg.asset,
+                unmatched_usd: leg.unmatched_usd,
+                order,
+                swaps,
+            });
+        }
+    }
+    report.risk_violations = risk.violations();
+    let (_, message) = execution_status(&report);
+    state.notifier.execution(wallet_address, &report, &message);
+    // The strategy has run either way, so a failed write only loses the record
+    if let Err(e) = state.reports.record(wallet_address, &report) {
+        println!("Failed to store the report of job {}: {}", job_id, e);
+    }
+    // Transfers still in flight are followed in the background, into the stored report
+    let in_flight = report.bridges.iter().any(|bridge| {
+        matches!(&bridge.outcome, BridgeOutcome::Executed(receipt)
+            if receipt.delivery == BridgeStatus::Pending)
+    });
+    if in_flight {
+        let state = state.clone();
+        let chain_data = chain_data.clone();
+        let mut bridges = report.bridges.clone();
+        let job_id = job_id.to_string();
+        audit::spawn(async move {
+            await_bridge_arrivals(
+                &state.reqwest_cli,
+                &state.config.bridge,
+                &chain_data,
+                &mut bridges,
+                BRIDGE_WATCH_TIMEOUT,
+            )
+            .await;
+            if let Err(e) = state
+                .reports
+                .update(&job_id, |report| report.bridges = bridges)
+            {
+                println!(
+                    "Failed to store the bridge arrivals of job {}: {}",
+                    job_id, e
+                );
+            }
+        });
+    }
+    Ok(report)
+}
+
+// "partial" when a leg failed, with the failure
+fn execution_status(report: &ExecutionReport) -> (String, String) {
+    match (&report.aborted, report.first_failure()) {
+        (Some(aborted), _) => ("partial".to_string(), aborted.clone()),
+        (None, Some(failure)) => (
+            "partial".to_string(),
+            format!("Strategy executed with a failed {}", failure),
+        ),
+        (None, None) => ("success".to_string(), "Strategy executed".to_string()),
+    }
+}
+
+// Quote every swap so the preview can show what the wallet ends up holding
+async fn quote_swap_outputs(
+    state: &types::AppState,
+    chain_data: &HashMap<u64, ChainData>,
+    strategy: &Strategy,
+    onchain_portfolio: &ChainPortfolio,
+) -> Vec<Option<f64>> {
+    let mut outputs = Vec::new();
+    for swap in strategy.exchanges.eisen.swaps.iter().flatten() {
+        let (Some(amount), Some(chain_data)) = (
+            preview_amount(&swap.amount, &swap.token_in, onchain_portfolio),
+            chain_data.get(&swap.chain_id()),
+        ) else {
+            outputs.push(None);
+            continue;
+        };
+        let amount_out = executor::eisen::quote_amount_out(
+            &state.reqwest_cli,
+            &state.eisen_base_url,
+            chain_data,
+            &swap.token_in,
+            &swap.token_out,
+            amount,
+            &swap.dex_filter_or(&state.config.swaps.dex_filters),
+        )
+        .await;
+        match amount_out {
+            Ok(amount_out) => outputs.push(Some(amount_out)),
+            Err(err) => {
+                println!(
+                    "Failed to quote swap {} -> {}: {}",
+                    swap.token_in, swap.token_out, err
+                );
+                outputs.push(None);
+            }
+        }
+    }
+    outputs
+}
+
+// Handler for POST /api/v1/execute
+pub async fn execute_strategy(
+    State(state): State<types::AppState>,
+    Json(params): Json<ExecuteStrategyParams>,
+) -> Result<Response, AppError> {
+    execute(state, params, None).await
+}
+
+// Handler for POST /api/v1/execute/stream
+/// POST /api/v1/execute as server-sent events: `delta` events relay the agent's reply
+/// as it is written, then a `result` event holds the status and body of the response.
+/// An `attempt` event, holding its number, starts each reply after the first, when the
+/// agent is retried or asked to repair; what was relayed before it is void. Only the
+/// OpenAI agents stream, others relay each reply whole once it is in.
+pub async fn execute_strategy_stream(
+    State(state): State<types::AppState>,
+    Json(params): Json<ExecuteStrategyParams>,
+) -> Sse<impl Stream<Item = Result<Event, axum::Error>>> {
+    let (sink, mut deltas) = ReplySink::channel();
+    let task = tokio::spawn(execute(state, params, Some(sink)));
+    let events = async_stream::stream! {
+        // Ends once the engine, holding the sink, is done with the agent
+        while let Some(event) = deltas.recv().await {
+            yield Ok(match event {
+                ReplyEvent::Attempt(attempt) => {
+                    Event::default().event("attempt").data(attempt.to_string())
+                }
+                // Carriage returns cannot be sent in an event
+                ReplyEvent::Delta(text) => Event::default().event("delta").data(text.replace('\r', "")),
+            });
+        }
+        let response = match task.await {
+            Ok(response) => response.into_response(),
+            Err(e) => AppError::internal_error(format!("Execution failed: {}", e)).into_response(),
+        };
+        let status = response.status().as_u16();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap_or_default();
+        let body: serde_json::Value =
+            serde_json::from_slice(&body).unwrap_or(serde_json::Value::Null);
+        yield Event::default()
+            .event("result")
+            .json_data(serde_json::json!({ "status": status, "body": body }));
+    };
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+async fn execute(
+    state: types::AppState,
+    params: ExecuteStrategyParams,
+    sink: Option<ReplySink>,
+) -> Result<Response, AppError> {
+    println!(
+        "Processing request with wallet address: {}",
+        params.wallet_address
+    );
+    if params.mode == ExecuteMode::Execute {
+        ensure_trading(&state)?;
+    }
+    parse_wallet_address("wallet_address", &params.wallet_address)?;
+    check_model(&state, params.model.as_deref())?;
+    check_prompt_template(
+        &state,
+        params.engine,
+        params.provider.as_deref(),
+        params.prompt_template.as_deref(),
+    )?;
+    check_positive("max_notional_usd", params.max_notional_usd)?;
+    let prepared = prepare_strategy(
+        &state,
         &params.wallet_address,
+        params.engine,
+        params.provider.as_deref(),
+        params.model.as_deref(),
+        params.prompt_template.as_deref(),
+        params.max_notional_usd,
+        sink,
     )
-    .await
-    .map_err(|e| AppError::internal_error(e.to_string()))?;
+    .await?;
+    let notional_scale = prepared.notional_scale;
+
+    // Held for confirmation, as a preview, while the agent doubts a leg
+    let doubted = match (
+        params.mode,
+        state.config.strategy_validation.approval_confidence,
+    ) {
+        (ExecuteMode::Execute, Some(min_confidence)) => {
+            prepared.strategy.low_confidence_legs(min_confidence)
+        }
+        _ => Vec::new(),
+    };
+    if params.mode == ExecuteMode::Preview || !doubted.is_empty() {
+        let swap_outputs = quote_swap_outputs(
+            &state,
+            &prepared.chain_data,
+            &prepared.strategy,
+            &prepared.onchain_portfolio,
+        )
+        .await;
+        let (projected_portfolio, mut findings) = project_portfolio(
+            &prepared.strategy,
+            &prepared.binance_portfolio,
+            &prepared.onchain_portfolio,
+            &swap_outputs,
+        );
+        if let Some(rebalance) = &prepared.rebalance {
+            findings.extend(rebalance.findings(&prepared.strategy, &prepared.onchain_portfolio));
+        }
+        if let Some(scale) = notional_scale {
+            findings.push(format!(
+                "Legs scaled by {:.4} to the ${:.2} notional budget",
+                scale,
+                params.max_notional_usd.unwrap_or_default()
+            ));
+        }
+        let message = if doubted.is_empty() {
+            "Strategy previewed, confirm to execute".to_string()
+        } else {
+            println!(
+                "Holding the strategy for confirmation: {}",
+                doubted.join("; ")
+            );
+            findings.extend(
+                doubted
+                    .iter()
+                    .map(|leg| format!("Low-confidence leg needs confirmation: {}", leg)),
+            );
+            "Strategy has low-confidence legs, confirm to execute".to_string()
+        };
+        let plan = PendingPlan::new(
+            params.wallet_address,
+            prepared.strategy,
+            prepared.binance_portfolio,
+            prepared.onchain_portfolio,
+        )
+        .with_notional_scale(notional_scale);
+        let response = serde_json::to_value(PreviewStrategyResponse {
+            status: "pending".to_string(),
+            message,
+            plan_hash: plan.hash(),
+            expires_in_secs: state.plans.ttl().as_secs(),
+            findings,
+            projected_portfolio,
+            binance_portfolio: &plan.binance_portfolio,
+            onchain_portfolio: &plan.onchain_portfolio,
+            strategy: &plan.strategy,
+        })
+        .map_err(|e| AppError::internal_error(format!("Failed to serialize preview: {}", e)))?;
+        state.plans.insert(plan);
+
+        return Ok((StatusCode::OK, Json(response)).into_response());
+    }
+
+    let job_id = params
+        .job_id
+        .unwrap_or_else(|| hex::encode(rand::random::<[u8; 16]>()));
+    println!("Executing strategy as job {}", job_id);
+    let report = run_strategy(
+        &state,
+        &prepared.binance_key,
+        &prepared.chain_data,
+        &prepared.strategy,
+        &prepared.binance_portfolio,
+        &prepared.onchain_portfolio,
+        &params.wallet_address,
+        &job_id,
+        params.on_failure,
+        None,
+        notional_scale,
+    )
+    .await?;
 
-    println!("Strategy executed");
     // Create a response object that we'll populate
+    let (status, message) = execution_status(&report);
     let response = ExecuteStrategyResponse {
-        status: "success".to_string(),
-        message: "Strategy executed".to_string(),
-        binance_portfolio,
-        onchain_portfolio,
-        strategy,
+        status,
+        message,
+        binance_portfolio: prepared.binance_portfolio,
+        onchain_portfolio: prepared.onchain_portfolio,
+        strategy: prepared.strategy,
+        report,
+    };
+
+    Ok((StatusCode::OK, Json(response)).into_response())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfirmStrategyParams {
+    pub plan_hash: String,
+    #[serde(default)]
+    pub on_failure: FailurePolicy,
+}
+
+// Handler for POST /api/v1/execute/confirm
+pub async fn confirm_strategy(
+    State(state): State<types::AppState>,
+    Json(params): Json<ConfirmStrategyParams>,
+) -> Result<impl IntoResponse, AppError> {
+    // Checked before the plan is taken, so it can still be confirmed once resumed
+    ensure_trading(&state)?;
+    let plan = state.plans.take(&params.plan_hash).map_err(|e| match e {
+        PlanLookupError::NotFound => {
+            AppError::not_found(format!("No pending plan {}", params.plan_hash))
+        }
+        PlanLookupError::Expired => {
+            AppError::bad_request(format!("Plan {} has expired", params.plan_hash))
+        }
+    })?;
+    println!(
+        "Executing confirmed plan {} for wallet {}",
+        params.plan_hash, plan.wallet_address
+    );
+
+    // Chain metadata is refetched; the strategy itself is exactly what was previewed
+    let chain_data = fetch_swap_chain_data(&state, &plan.strategy).await?;
+    let report = run_strategy(
+        &state,
+        &binance_key(&state),
+        &chain_data,
+        &plan.strategy,
+        &plan.binance_portfolio,
+        &plan.onchain_portfolio,
+        &plan.wallet_address,
+        &params.plan_hash,
+        params.on_failure,
+        None,
+        plan.notional_scale,
+    )
+    .await?;
+
+    let (status, message) = execution_status(&report);
+    let response = ExecuteStrategyResponse {
+        status,
+        message,
+        binance_portfolio: plan.binance_portfolio,
+        onchain_portfolio: plan.onchain_portfolio,
+        strategy: plan.strategy,
+        report,
     };
 
     Ok((StatusCode::OK, Json(response)))
@@ -278,21 +1706,21 @@ pub async fn get_portfolio(
         "Processing portfolio request with wallet address: {}",
         params.wallet_address
     );
+    parse_wallet_address("wallet_address", &params.wallet_address)?;
 
-    let prices: MarketPrices = fetch_prices(&state.binance_base_url, &state.reqwest_cli).await?;
+    let prices: MarketPrices = fetch_prices(&state).await?;
 
-    let binance_key: BinanceKey = BinanceKey {
-        api_key: state.binance_api_key.clone(),
-        secret_key: state.binance_api_secret.clone(),
-    };
-    let onchain_portfolio =
-        fetch_chain_portfolio(&state.eisen_base_url, 8453, &params.wallet_address)
-            .await
-            .map_err(|e| AppError::internal_error(e.to_string()))?;
+    let binance_key = binance_key(&state);
+    let onchain_portfolio = fetch_onchain_portfolio(&state, &params.wallet_address).await?;
 
-    let binance_portfolio = fetch_binance_portfolio(&state.binance_base_url, &binance_key)
-        .await
-        .map_err(|e| AppError::internal_error(e.to_string()))?;
+    let binance_portfolio = fetch_binance_account(&state, &binance_key).await?;
+    record_portfolio_snapshot(
+        &state,
+        &params.wallet_address,
+        &binance_portfolio,
+        &onchain_portfolio,
+        &prices,
+    );
 
     let response = GetPortfolioResponse {
         status: "success".to_string(),
@@ -305,26 +1733,1222 @@ pub async fn get_portfolio(
     Ok((StatusCode::OK, Json(response)))
 }
 
+fn record_portfolio_snapshot(
+    state: &types::AppState,
+    wallet_address: &str,
+    binance_portfolio: &AccountInfo,
+    onchain_portfolio: &ChainPortfolio,
+    prices: &MarketPrices,
+) {
+    let snapshot = PortfolioSnapshot::new(
+        wallet_address,
+        binance_portfolio,
+        onchain_portfolio,
+        prices,
+        chrono::Utc::now(),
+    );
+    if let Err(e) = state.portfolio_history.record(snapshot) {
+        println!(
+            "Failed to record {} portfolio snapshot: {}",
+            wallet_address, e
+        );
+    }
+}
+
+/// Snapshot every configured wallet's portfolio each `snapshot_interval_secs`, logging
+/// wallets that fail and retrying them on the next round.
+pub async fn record_portfolio_snapshots(state: types::AppState) {
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(
+        state.config.portfolio_history.snapshot_interval_secs,
+    ));
+    loop {
+        ticker.tick().await;
+        for wallet_address in state.config.wallets.keys() {
+            let snapshot = async {
+                let prices = fetch_prices(&state).await?;
+                let onchain_portfolio = fetch_onchain_portfolio(&state, wallet_address).await?;
+                let binance_portfolio = fetch_binance_account(&state, &binance_key(&state)).await?;
+                state.notifier.margin_health(&MarginHealth::new(
+                    &binance_portfolio,
+                    &state.config.margin_health,
+                ));
+                record_portfolio_snapshot(
+                    &state,
+                    wallet_address,
+                    &binance_portfolio,
+                    &onchain_portfolio,
+                    &prices,
+                );
+                Ok::<_, AppError>(())
+            };
+            if let Err(e) = snapshot.await {
+                println!(
+                    "Failed to snapshot the {} portfolio: {:?}",
+                    wallet_address, e
+                );
+            }
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetRebalanceParams {
+    pub wallet_address: String,
+}
+
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
-pub struct GetYieldsResponse {
+pub struct GetRebalanceResponse {
     pub status: String,
     pub message: String,
-    pub yields: CombinedYields
+    pub rebalance: RebalancePlan,
+}
+
+// Handler for GET /api/v1/rebalance
+pub async fn get_rebalance(
+    State(state): State<types::AppState>,
+    Query(params): Query<GetRebalanceParams>,
+) -> Result<impl IntoResponse, AppError> {
+    parse_wallet_address("wallet_address", &params.wallet_address)?;
+    let prices = fetch_prices(&state).await?;
+    let onchain_portfolio = fetch_onchain_portfolio(&state, &params.wallet_address).await?;
+    let binance_portfolio = fetch_binance_account(&state, &binance_key(&state)).await?;
+    let rebalance = RebalancePlan::new(
+        &state.config.rebalance,
+        &state.config.exposure,
+        &binance_portfolio,
+        &onchain_portfolio,
+        &prices,
+    )
+    .map_err(|e| match e {
+        RebalanceError::NoTarget => AppError::not_found(e.to_string()),
+        _ => AppError::internal_error(e.to_string()),
+    })?;
+
+    let response = GetRebalanceResponse {
+        status: "success".to_string(),
+        message: format!(
+            "{} leg(s), {} order(s) and {} swap(s) to the target allocation",
+            rebalance.gaps.len(),
+            rebalance
+                .strategy
+                .exchanges
+                .binance
+                .orders
+                .iter()
+                .flatten()
+                .count(),
+            rebalance
+                .strategy
+                .exchanges
+                .eisen
+                .swaps
+                .iter()
+                .flatten()
+                .count()
+        ),
+        rebalance,
+    };
+    Ok((StatusCode::OK, Json(response)))
 }
 
-pub async fn get_yields() -> Result<impl IntoResponse, AppError> {
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetMarginHealthResponse {
+    pub status: String,
+    pub message: String,
+    pub margin_health: MarginHealth,
+}
 
-    let combined_yield_fetcher = CombinedYieldFetcher::new();
+// Handler for GET /api/v1/binance/margin-health
+pub async fn get_margin_health(
+    State(state): State<types::AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let binance_portfolio = fetch_binance_account(&state, &binance_key(&state)).await?;
+    let margin_health = MarginHealth::new(&binance_portfolio, &state.config.margin_health);
 
-    let yields = combined_yield_fetcher.get_apr().await.map_err(|e| AppError::internal_error(e.to_string()))?;
+    let response = GetMarginHealthResponse {
+        status: "success".to_string(),
+        message: if margin_health.warnings.is_empty() {
+            "Margin healthy".to_string()
+        } else {
+            format!("{} margin warning(s)", margin_health.warnings.len())
+        },
+        margin_health,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
 
-    Ok((
-        StatusCode::OK,
-        Json(GetYieldsResponse {
-            status: "success".to_string(),
-            message: "Yields fetched".to_string(),
-            yields,
-        }),
-    ))
+#[derive(Debug, Deserialize)]
+pub struct GetPortfolioHistoryParams {
+    pub wallet_address: String,
+    /// How many days back to go.
+    #[serde(default = "default_history_days")]
+    pub days: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetPortfolioHistoryResponse {
+    pub status: String,
+    pub message: String,
+    /// Snapshots, oldest first.
+    pub history: Vec<PortfolioSnapshot>,
+}
+
+// Handler for GET /api/v1/portfolio/history
+pub async fn get_portfolio_history(
+    State(state): State<types::AppState>,
+    Query(params): Query<GetPortfolioHistoryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    parse_wallet_address("wallet_address", &params.wallet_address)?;
+    check_days(params.days)?;
+    let since = chrono::Utc::now() - chrono::Duration::days(params.days.into());
+    let history = state.portfolio_history.query(&params.wallet_address, since);
+
+    let response = GetPortfolioHistoryResponse {
+        status: "success".to_string(),
+        message: format!("{} snapshot(s) fetched", history.len()),
+        history,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetPortfolioPnlResponse {
+    pub status: String,
+    pub message: String,
+    pub pnl: PnlReport,
+}
+
+// Handler for GET /api/v1/portfolio/pnl
+pub async fn get_portfolio_pnl(
+    State(state): State<types::AppState>,
+    Query(params): Query<GetPortfolioHistoryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    parse_wallet_address("wallet_address", &params.wallet_address)?;
+    check_days(params.days)?;
+    let until = chrono::Utc::now();
+    let since = until - chrono::Duration::days(params.days.into());
+    let snapshots = state.portfolio_history.query(&params.wallet_address, since);
+    let first = snapshots.first().ok_or_else(|| {
+        AppError::not_found(format!(
+            "No portfolio snapshots of {} in the last {} day(s)",
+            params.wallet_address, params.days
+        ))
+    })?;
+    // Simulated fills book no income
+    let incomes = if state.paper_executor.is_some() {
+        Vec::new()
+    } else {
+        fetch_income(
+            &state.reqwest_cli,
+            &state.binance_base_url,
+            &binance_key(&state),
+            first.taken_at.timestamp_millis() as u64,
+            until.timestamp_millis() as u64,
+        )
+        .await
+        .map_err(|e| {
+            println!("Binance income unavailable: {}", e);
+            ExecutionError::Binance(e.to_string())
+        })?
+    };
+    let pnl = PnlReport::new(&snapshots, &incomes)
+        .ok_or_else(|| AppError::internal_error("No snapshots to compute PnL from".to_string()))?;
+
+    let response = GetPortfolioPnlResponse {
+        status: "success".to_string(),
+        message: format!(
+            "PnL over {} snapshot(s) and {} income record(s)",
+            snapshots.len(),
+            incomes.len()
+        ),
+        pnl,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetYieldsParams {
+    /// Size in USD to also compute the APRs net of entry and exit costs for.
+    pub notional: Option<f64>,
+    /// Only APRs whose symbol or asset contains this, case-insensitively.
+    pub symbol: Option<String>,
+    pub chain_id: Option<u64>,
+    pub min_apr: Option<f64>,
+    pub sort: Option<YieldSort>,
+    /// Most APRs returned across all sources, after sorting.
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetYieldsResponse {
+    pub status: String,
+    pub message: String,
+    pub yields: CachedYields,
+    /// The filtered APRs of every source in one list, in `sort` order and cut to `limit`.
+    pub ranked: Vec<RankedApr>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub net: Option<NetYields>,
+}
+
+/// Cost estimator pricing the entry token at its USD base asset, or its mark price.
+async fn cost_estimator(state: &types::AppState) -> Result<CostEstimator<'_>, AppError> {
+    let config = &state.config.yields.costs;
+    let entry_token_usd = match base_asset(&state.config.exposure, &config.entry_token).as_str() {
+        "USD" => 1.0,
+        asset => fetch_mark_price(state, &futures_symbol(asset, None)).await?,
+    };
+    Ok(CostEstimator {
+        client: &state.reqwest_cli,
+        eisen_base_url: &state.eisen_base_url,
+        chain_metadata: &state.chain_metadata,
+        chains: &state.chains,
+        dex_filters: &state.config.swaps.dex_filters,
+        config,
+        entry_token_usd,
+    })
+}
+
+pub async fn get_yields(
+    State(state): State<types::AppState>,
+    Query(params): Query<GetYieldsParams>,
+) -> Result<impl IntoResponse, AppError> {
+    check_positive("notional", params.notional)?;
+    if let Some(min_apr) = params.min_apr {
+        if !min_apr.is_finite() {
+            return Err(AppError::invalid_field(
+                "min_apr",
+                format!("min_apr must be a number, got {}", min_apr),
+            ));
+        }
+    }
+    let filter = YieldFilter {
+        symbol: params.symbol,
+        chain_id: params.chain_id,
+        min_apr: params.min_apr,
+        sort: params.sort,
+        limit: params.limit,
+    };
+    let mut yields = state.yields.get().await;
+    let ranked = yields.yields.ranked(&filter);
+    yields.yields = yields.yields.filtered(&filter);
+    let net = match params.notional {
+        Some(notional) => Some(
+            cost_estimator(&state)
+                .await?
+                .net_yields(
+                    ranked
+                        .iter()
+                        .map(|ranked| (ranked.source.as_str(), &ranked.apr))
+                        .collect(),
+                    notional,
+                )
+                .await,
+        ),
+        None => None,
+    };
+    let message = match (yields.yields.errors.len(), yields.stale.len()) {
+        (0, 0) => "Yields fetched".to_string(),
+        (failed, stale) => format!(
+            "Yields fetched, {} source(s) failing, {} stale",
+            failed, stale
+        ),
+    };
+
+    Ok((
+        StatusCode::OK,
+        Json(GetYieldsResponse {
+            status: "success".to_string(),
+            message,
+            yields,
+            ranked,
+            net,
+        }),
+    ))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetYieldHistoryParams {
+    /// Symbol to return the history of; every symbol when unset.
+    pub symbol: Option<String>,
+    /// How many days back to go.
+    #[serde(default = "default_history_days")]
+    pub days: u32,
+}
+
+fn default_history_days() -> u32 {
+    7
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetYieldHistoryResponse {
+    pub status: String,
+    pub message: String,
+    /// Fetched APRs, oldest first.
+    pub history: Vec<AprRecord>,
+}
+
+// Handler for GET /api/v1/yields/history
+pub async fn get_yield_history(
+    State(state): State<types::AppState>,
+    Query(params): Query<GetYieldHistoryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    check_days(params.days)?;
+    let history = state
+        .yields
+        .history()
+        .ok_or_else(|| AppError::not_found("Yield history is not recorded".to_string()))?;
+    let since = chrono::Utc::now() - chrono::Duration::days(params.days.into());
+    let history = history.query(params.symbol.as_deref(), since);
+
+    let response = GetYieldHistoryResponse {
+        status: "success".to_string(),
+        message: format!("{} APR record(s) fetched", history.len()),
+        history,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetAgentUsageParams {
+    /// Provider to return the usage of; every provider when unset.
+    pub provider: Option<String>,
+    /// How many days back to go, today included.
+    #[serde(default = "default_history_days")]
+    pub days: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAgentUsageResponse {
+    pub status: String,
+    pub message: String,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+    pub cost_usd: f64,
+    /// Usage per day, provider, API key and model, oldest day first.
+    pub days: Vec<DailyUsage>,
+}
+
+// Handler for GET /api/v1/agent/usage
+pub async fn get_agent_usage(
+    State(state): State<types::AppState>,
+    Query(params): Query<GetAgentUsageParams>,
+) -> Result<impl IntoResponse, AppError> {
+    check_days(params.days)?;
+    let since =
+        chrono::Utc::now().date_naive() - chrono::Duration::days(i64::from(params.days.max(1)) - 1);
+    let days = state.agent_usage.query(since, params.provider.as_deref());
+
+    let response = GetAgentUsageResponse {
+        status: "success".to_string(),
+        message: format!("{} day(s) of agent usage fetched", days.len()),
+        prompt_tokens: days.iter().map(|day| day.prompt_tokens).sum(),
+        completion_tokens: days.iter().map(|day| day.completion_tokens).sum(),
+        cost_usd: days.iter().map(|day| day.cost_usd).sum(),
+        days,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+// Handler for GET /metrics
+pub async fn get_metrics(State(state): State<types::AppState>) -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.agent_usage.metrics(),
+    )
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetMarketHistoryParams {
+    pub symbol: String,
+    /// Timeframe of the returned candles.
+    pub interval: Interval,
+    /// How many days back to go.
+    #[serde(default = "default_history_days")]
+    pub days: u32,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetMarketHistoryResponse {
+    pub status: String,
+    pub message: String,
+    /// Collected candles and prices, oldest first.
+    pub candles: Vec<OHLCV>,
+    pub prices: Vec<PriceData>,
+}
+
+// Handler for GET /api/v1/market/history
+pub async fn get_market_history(
+    State(state): State<types::AppState>,
+    Query(params): Query<GetMarketHistoryParams>,
+) -> Result<impl IntoResponse, AppError> {
+    check_days(params.days)?;
+    let until = chrono::Utc::now();
+    let since = until - chrono::Duration::days(params.days.into());
+    let candles = state
+        .market_history
+        .candles(&params.symbol, params.interval, since, until);
+    let prices = state.market_history.prices(&params.symbol, since, until);
+
+    let response = GetMarketHistoryResponse {
+        status: "success".to_string(),
+        message: format!(
+            "{} candle(s) and {} price(s) fetched",
+            candles.len(),
+            prices.len()
+        ),
+        candles,
+        prices,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RefreshChainMetadataParams {
+    /// Chain to refresh; every cached chain when unset.
+    pub chain_id: Option<u64>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshChainMetadataResponse {
+    pub status: String,
+    pub message: String,
+    pub chain_ids: Vec<u64>,
+}
+
+// Handler for POST /api/v1/chains/metadata/refresh
+pub async fn refresh_chain_metadata(
+    State(state): State<types::AppState>,
+    Query(params): Query<RefreshChainMetadataParams>,
+) -> Result<impl IntoResponse, AppError> {
+    let chain_ids = match params.chain_id {
+        Some(chain_id) => vec![chain_id],
+        None => state.chain_metadata.cached_chain_ids(),
+    };
+    for chain_id in &chain_ids {
+        state.chain_metadata.refresh(*chain_id).await.map_err(|e| {
+            AppError::internal_error(format!(
+                "Failed to refresh metadata for chain {}: {}",
+                chain_id, e
+            ))
+        })?;
+    }
+
+    let response = RefreshChainMetadataResponse {
+        status: "success".to_string(),
+        message: "Chain metadata refreshed".to_string(),
+        chain_ids,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetJobResponse {
+    pub status: String,
+    pub job_id: String,
+    /// Bridge and swap legs with the state of each of their transactions; empty once
+    /// the job has left the queue.
+    pub legs: Vec<QueuedLeg>,
+    /// What the job executed, once it has finished.
+    pub report: Option<StoredReport>,
+}
+
+// Handler for GET /api/v1/jobs/:job_id
+pub async fn get_job(
+    State(state): State<types::AppState>,
+    Path(job_id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let legs = state.tx_queue.job(&job_id);
+    let report = state.reports.get(&job_id);
+    if legs.is_none() && report.is_none() {
+        return Err(AppError::not_found(format!("No job {}", job_id)));
+    }
+    let response = GetJobResponse {
+        status: "success".to_string(),
+        job_id,
+        legs: legs.unwrap_or_default(),
+        report,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Check the schedules every `tick_secs` and start each run that fired in a task of its
+/// own. A schedule firing while its previous run is still going skips that firing.
+pub async fn run_schedules(state: types::AppState) {
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(
+        state.config.scheduler.tick_secs,
+    ));
+    let mut checked = chrono::Utc::now();
+    loop {
+        ticker.tick().await;
+        let now = chrono::Utc::now();
+        for (schedule, fired_at) in state.schedules.due(checked, now) {
+            if state.paused.is_paused() {
+                println!(
+                    "Schedule {}: trading is paused, skipping the {} run",
+                    schedule.id, fired_at
+                );
+                continue;
+            }
+            let job_id = schedule.job_id(fired_at);
+            let Some(run) = state.schedules.start(&schedule.id, &job_id, fired_at) else {
+                println!(
+                    "Schedule {}: previous run still going, skipping the {} run",
+                    schedule.id, fired_at
+                );
+                continue;
+            };
+            tokio::spawn(run_scheduled(state.clone(), schedule, job_id, run));
+        }
+        checked = now;
+    }
+}
+
+// Fetch a strategy for the schedule's wallet and execute it, as POST /api/v1/execute would
+async fn run_scheduled(
+    state: types::AppState,
+    schedule: Schedule,
+    job_id: String,
+    run_guard: RunGuard,
+) {
+    println!(
+        "Schedule {}: executing job {} for wallet {}",
+        schedule.id, job_id, schedule.wallet_address
+    );
+    let run = async {
+        let prepared = prepare_strategy(
+            &state,
+            &schedule.wallet_address,
+            schedule.engine,
+            schedule.provider.as_deref(),
+            schedule.model.as_deref(),
+            schedule.prompt_template.as_deref(),
+            schedule.max_notional_usd,
+            None,
+        )
+        .await?;
+        // Nobody is there to confirm a doubted leg, so the plan waits for someone who can
+        if let Some(min_confidence) = state.config.strategy_validation.approval_confidence {
+            let doubted = prepared.strategy.low_confidence_legs(min_confidence);
+            if !doubted.is_empty() {
+                let plan = PendingPlan::new(
+                    schedule.wallet_address.clone(),
+                    prepared.strategy,
+                    prepared.binance_portfolio,
+                    prepared.onchain_portfolio,
+                )
+                .with_notional_scale(prepared.notional_scale);
+                let hash = state.plans.insert(plan);
+                return Ok((
+                    RunStatus::Held,
+                    format!(
+                        "Held for confirmation as plan {}, low-confidence legs: {}",
+                        hash,
+                        doubted.join("; ")
+                    ),
+                ));
+            }
+        }
+        let report = run_strategy(
+            &state,
+            &prepared.binance_key,
+            &prepared.chain_data,
+            &prepared.strategy,
+            &prepared.binance_portfolio,
+            &prepared.onchain_portfolio,
+            &schedule.wallet_address,
+            &job_id,
+            schedule.on_failure,
+            Some(&schedule.risk),
+            prepared.notional_scale,
+        )
+        .await?;
+        let (status, message) = execution_status(&report);
+        Ok::<_, AppError>(match status.as_str() {
+            "partial" => (RunStatus::Partial, message),
+            _ => (RunStatus::Success, message),
+        })
+    };
+    let (status, message) = run
+        .await
+        .unwrap_or_else(|e| (RunStatus::Failed, e.to_string()));
+    println!(
+        "Schedule {}: job {} {:?}: {}",
+        schedule.id, job_id, status, message
+    );
+    run_guard.finish(status, message);
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleStatus {
+    pub schedule: Schedule,
+    /// `None` for a disabled schedule, or one that never fires.
+    pub next_run_at: Option<chrono::DateTime<chrono::Utc>>,
+    /// Latest run since startup.
+    pub last_run: Option<ScheduledRun>,
+}
+
+impl ScheduleStatus {
+    fn new(state: &types::AppState, schedule: Schedule) -> Self {
+        let next_run_at = schedule
+            .enabled
+            .then(|| schedule.cron.next_after(chrono::Utc::now()))
+            .flatten();
+        Self {
+            last_run: state.schedules.last_run(&schedule.id),
+            next_run_at,
+            schedule,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListSchedulesResponse {
+    pub status: String,
+    pub message: String,
+    /// Whether schedules run at all; see the scheduler config.
+    pub scheduler_enabled: bool,
+    pub schedules: Vec<ScheduleStatus>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduleResponse {
+    pub status: String,
+    pub message: String,
+    pub schedule: ScheduleStatus,
+}
+
+// Job ids embed the schedule id, and runs need a wallet to execute for
+fn validate_schedule(schedule: &Schedule) -> Result<(), AppError> {
+    let id_chars = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    if schedule.id.is_empty() || !schedule.id.chars().all(id_chars) {
+        return Err(AppError::invalid_field(
+            "id",
+            format!(
+                "Invalid schedule id {:?}, use letters, digits, '-' and '_'",
+                schedule.id
+            ),
+        ));
+    }
+    parse_wallet_address("walletAddress", &schedule.wallet_address)?;
+    check_positive("maxNotionalUsd", schedule.max_notional_usd)?;
+    Ok(())
+}
+
+// Longest history a request may go back, well within what dates can represent
+const MAX_HISTORY_DAYS: u32 = 3650;
+
+/// `value` of the `field` parameter as an address; a mixed-case one must match its
+/// EIP-55 checksum, so a mistyped character is caught before any funds move.
+fn parse_wallet_address(field: &str, value: &str) -> Result<Address, AppError> {
+    let address = value.parse::<Address>().map_err(|e| {
+        AppError::invalid_field(field, format!("Invalid wallet address {}: {}", value, e))
+    })?;
+    let hex = value.strip_prefix("0x").unwrap_or(value);
+    let mixed_case =
+        hex.chars().any(|c| c.is_ascii_lowercase()) && hex.chars().any(|c| c.is_ascii_uppercase());
+    let checksummed = address.to_checksum(None);
+    if mixed_case && checksummed[2..] != *hex {
+        return Err(AppError::invalid_field(
+            field,
+            format!(
+                "Wallet address {} does not match its checksum {}",
+                value, checksummed
+            ),
+        ));
+    }
+    Ok(address)
+}
+
+fn check_model(state: &types::AppState, model: Option<&str>) -> Result<(), AppError> {
+    match model {
+        Some(model) if !state.config.llm.allows_model(model) => Err(AppError::invalid_field(
+            "model",
+            format!("Model {} is not allowed", model),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn check_positive(field: &str, value: Option<f64>) -> Result<(), AppError> {
+    match value {
+        Some(value) if !(value.is_finite() && value > 0.0) => Err(AppError::invalid_field(
+            field,
+            format!("{} must be a positive number, got {}", field, value),
+        )),
+        _ => Ok(()),
+    }
+}
+
+fn check_days(days: u32) -> Result<(), AppError> {
+    if days > MAX_HISTORY_DAYS {
+        return Err(AppError::invalid_field(
+            "days",
+            format!("days must be at most {}, got {}", MAX_HISTORY_DAYS, days),
+        ));
+    }
+    Ok(())
+}
+
+// Only chat providers are sent prompts, so the Othentic node cannot take a template
+fn check_prompt_template(
+    state: &types::AppState,
+    engine: EngineKind,
+    provider: Option<&str>,
+    template: Option<&str>,
+) -> Result<(), AppError> {
+    let Some(template) = template else {
+        return Ok(());
+    };
+    let othentic = engine == EngineKind::Llm
+        && engine_provider(&state.config.llm, engine, provider)
+            .eq_ignore_ascii_case(OTHENTIC_PROVIDER);
+    if othentic {
+        return Err(AppError::bad_request(format!(
+            "The {} provider takes no prompt template",
+            OTHENTIC_PROVIDER
+        )));
+    }
+    if !state.prompts.contains(template) {
+        return Err(AppError::bad_request(format!(
+            "No prompt template {}",
+            template
+        )));
+    }
+    Ok(())
+}
+
+fn store_schedule(state: &types::AppState, schedule: Schedule) -> Result<(), AppError> {
+    state
+        .schedules
+        .put(schedule)
+        .map_err(|e| AppError::internal_error(format!("Failed to store the schedule: {}", e)))
+}
+
+// Handler for GET /api/v1/schedules
+pub async fn list_schedules(
+    State(state): State<types::AppState>,
+) -> Result<impl IntoResponse, AppError> {
+    let schedules: Vec<ScheduleStatus> = state
+        .schedules
+        .list()
+        .into_iter()
+        .map(|schedule| ScheduleStatus::new(&state, schedule))
+        .collect();
+    let response = ListSchedulesResponse {
+        status: "success".to_string(),
+        message: format!("{} schedule(s)", schedules.len()),
+        scheduler_enabled: state.config.scheduler.enabled,
+        schedules,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+// Handler for POST /api/v1/schedules
+pub async fn create_schedule(
+    State(state): State<types::AppState>,
+    Json(schedule): Json<Schedule>,
+) -> Result<impl IntoResponse, AppError> {
+    validate_schedule(&schedule)?;
+    check_model(&state, schedule.model.as_deref())?;
+    check_prompt_template(
+        &state,
+        schedule.engine,
+        schedule.provider.as_deref(),
+        schedule.prompt_template.as_deref(),
+    )?;
+    if state.schedules.get(&schedule.id).is_some() {
+        return Err(AppError::bad_request(format!(
+            "Schedule {} already exists",
+            schedule.id
+        )));
+    }
+    store_schedule(&state, schedule.clone())?;
+    let response = ScheduleResponse {
+        status: "success".to_string(),
+        message: "Schedule created".to_string(),
+        schedule: ScheduleStatus::new(&state, schedule),
+    };
+    Ok((StatusCode::CREATED, Json(response)))
+}
+
+// Handler for GET /api/v1/schedules/:id
+pub async fn get_schedule(
+    State(state): State<types::AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let schedule = state
+        .schedules
+        .get(&id)
+        .ok_or_else(|| AppError::not_found(format!("No schedule {}", id)))?;
+    let response = ScheduleResponse {
+        status: "success".to_string(),
+        message: "Schedule fetched".to_string(),
+        schedule: ScheduleStatus::new(&state, schedule),
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+// Handler for PUT /api/v1/schedules/:id; the path's id wins over the body's
+pub async fn update_schedule(
+    State(state): State<types::AppState>,
+    Path(id): Path<String>,
+    Json(mut schedule): Json<Schedule>,
+) -> Result<impl IntoResponse, AppError> {
+    schedule.id = id;
+    validate_schedule(&schedule)?;
+    check_model(&state, schedule.model.as_deref())?;
+    check_prompt_template(
+        &state,
+        schedule.engine,
+        schedule.provider.as_deref(),
+        schedule.prompt_template.as_deref(),
+    )?;
+    if state.schedules.get(&schedule.id).is_none() {
+        return Err(AppError::not_found(format!("No schedule {}", schedule.id)));
+    }
+    store_schedule(&state, schedule.clone())?;
+    let response = ScheduleResponse {
+        status: "success".to_string(),
+        message: "Schedule updated".to_string(),
+        schedule: ScheduleStatus::new(&state, schedule),
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+// Handler for DELETE /api/v1/schedules/:id; a run in progress finishes
+pub async fn delete_schedule(
+    State(state): State<types::AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, AppError> {
+    let schedule = state
+        .schedules
+        .remove(&id)
+        .map_err(|e| AppError::internal_error(format!("Failed to delete the schedule: {}", e)))?
+        .ok_or_else(|| AppError::not_found(format!("No schedule {}", id)))?;
+    let response = ScheduleResponse {
+        status: "success".to_string(),
+        message: "Schedule deleted".to_string(),
+        schedule: ScheduleStatus {
+            schedule,
+            next_run_at: None,
+            last_run: None,
+        },
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+// 503 while the kill switch is on
+fn ensure_trading(state: &types::AppState) -> Result<(), AppError> {
+    if state.paused.is_paused() {
+        return Err(AppError::service_unavailable(
+            "Trading is paused, resume it through the server's /api/v1/admin/resume".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TradingPauseResponse {
+    pub status: String,
+    pub message: String,
+    pub paused: bool,
+}
+
+fn set_paused(state: &types::AppState, paused: bool) -> Result<TradingPauseResponse, AppError> {
+    let was_paused = state
+        .paused
+        .set(paused)
+        .map_err(|e| AppError::internal_error(format!("Failed to store the pause: {}", e)))?;
+    let message = match (was_paused, paused) {
+        (false, true) => "Trading paused",
+        (true, false) => "Trading resumed",
+        (true, true) => "Trading was already paused",
+        (false, false) => "Trading was not paused",
+    };
+    println!("{}", message);
+    Ok(TradingPauseResponse {
+        status: "success".to_string(),
+        message: message.to_string(),
+        paused,
+    })
+}
+
+// The admin token is read when it is needed, so rotating it takes no restart
+fn ensure_admin(state: &types::AppState, headers: &header::HeaderMap) -> Result<(), AppError> {
+    let token_env = &state.config.admin.token_env;
+    let token = std::env::var(token_env)
+        .ok()
+        .filter(|token| !token.is_empty())
+        .ok_or_else(|| {
+            AppError::forbidden(format!(
+                "Admin endpoints are disabled, set {} to enable them",
+                token_env
+            ))
+        })?;
+    let presented = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default();
+    ring::constant_time::verify_slices_are_equal(presented.as_bytes(), token.as_bytes())
+        .map_err(|_| AppError::unauthorized("Missing or invalid admin token".to_string()))
+}
+
+// Handler for POST /api/v1/admin/pause
+pub async fn pause_trading(
+    State(state): State<types::AppState>,
+    headers: header::HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_admin(&state, &headers)?;
+    Ok((StatusCode::OK, Json(set_paused(&state, true)?)))
+}
+
+// Handler for POST /api/v1/admin/resume
+pub async fn resume_trading(
+    State(state): State<types::AppState>,
+    headers: header::HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+    ensure_admin(&state, &headers)?;
+    Ok((StatusCode::OK, Json(set_paused(&state, false)?)))
+}
+
+/// Filters of GET /api/v1/audit; `user` is a wallet address, `limit` defaults to 100.
+#[derive(Debug, Deserialize)]
+pub struct GetAuditParams {
+    pub kind: Option<AuditKind>,
+    pub job_id: Option<String>,
+    pub user: Option<String>,
+    pub since: Option<chrono::DateTime<chrono::Utc>>,
+    pub until: Option<chrono::DateTime<chrono::Utc>>,
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GetAuditResponse {
+    pub status: String,
+    pub message: String,
+    pub records: Vec<AuditRecord>,
+}
+
+// Handler for GET /api/v1/audit
+pub async fn get_audit(
+    State(state): State<types::AppState>,
+    Query(params): Query<GetAuditParams>,
+) -> Result<impl IntoResponse, AppError> {
+    if let Some(user) = &params.user {
+        parse_wallet_address("user", user)?;
+    }
+    let filter = AuditFilter {
+        kind: params.kind,
+        job_id: params.job_id,
+        user: params.user,
+        since: params.since,
+        until: params.until,
+    };
+    let records = state.audit.query(&filter, params.limit.unwrap_or(100));
+    let response = GetAuditResponse {
+        status: "success".to_string(),
+        message: format!("{} audit record(s)", records.len()),
+        records,
+    };
+    Ok((StatusCode::OK, Json(response)))
+}
+
+/// Cut losing and funding-bleeding Binance positions every `interval_secs`, whatever the
+/// strategies are doing. Paused trading pauses this too.
+pub async fn monitor_stop_losses(state: types::AppState) {
+    let mut ticker = tokio::time::interval(std::time::Duration::from_secs(
+        state.config.stop_loss.interval_secs,
+    ));
+    // Per symbol, the limit it last hit and whether closing it went through, so a stop
+    // repeating every tick is notified once
+    let mut notified: HashMap<String, (stop_loss::StopTrigger, bool)> = HashMap::new();
+    loop {
+        ticker.tick().await;
+        if state.paused.is_paused() {
+            continue;
+        }
+        if let Err(e) = check_stop_losses(&state, &mut notified).await {
+            println!("Stop-loss check failed: {:?}", e);
+        }
+    }
+}
+
+async fn check_stop_losses(
+    state: &types::AppState,
+    notified: &mut HashMap<String, (stop_loss::StopTrigger, bool)>,
+) -> Result<(), AppError> {
+    let binance_key = binance_key(state);
+    let binance_portfolio = fetch_binance_account(state, &binance_key).await?;
+    if binance_portfolio.positions.is_empty() {
+        notified.clear();
+        return Ok(());
+    }
+    // Without every wallet, a hedging short would look naked and be cut
+    let mut onchain_portfolios = Vec::new();
+    for wallet_address in state.config.wallets.keys() {
+        onchain_portfolios.push(fetch_onchain_portfolio(state, wallet_address).await?);
+    }
+    let held_usd = stop_loss::held_usd(&state.config.exposure, &onchain_portfolios);
+    let (_, funding) = fetch_funding(state).await;
+    let stops = stop_loss::plan_stop_losses(
+        &state.config.stop_loss,
+        &state.config.exposure,
+        &binance_portfolio,
+        &held_usd,
+        &funding,
+    );
+    notified.retain(|symbol, _| stops.iter().any(|stop| &stop.symbol == symbol));
+    if stops.is_empty() {
+        return Ok(());
+    }
+
+    let live_executor = LiveBinanceExecutor {
+        client: &state.reqwest_cli,
+        base_url: &state.binance_base_url,
+        key: &binance_key,
+    };
+    let order_executor: &dyn OrderExecutor = match &state.paper_executor {
+        Some(paper_executor) => paper_executor.as_ref(),
+        None => &live_executor,
+    };
+    let job_id = format!("stop-loss-{}", chrono::Utc::now().format("%Y%m%dT%H%M%SZ"));
+    let scope = audit::AuditScope {
+        log: state.audit.clone(),
+        job_id: job_id.clone(),
+        user: "stop_loss".to_string(),
+    };
+    audit::scoped(scope, async {
+        for (leg_index, stop) in stops.iter().enumerate() {
+            println!(
+                "Stop-loss on {}: {:?} {}, {}",
+                stop.symbol, stop.side, stop.quantity, stop.reason
+            );
+            let client_order_id = client_order_id(&job_id, leg_index);
+            let (placed, outcome) = match order_executor
+                .place_order(&stop.order(), &client_order_id)
+                .await
+            {
+                Ok(order) => (true, format!("order {} placed", order.order_id)),
+                Err(e) => (false, format!("order failed: {}", e)),
+            };
+            println!("Stop-loss on {}: {}", stop.symbol, outcome);
+            let status = (stop.trigger, placed);
+            if notified.insert(stop.symbol.clone(), status) == Some(status) {
+                continue;
+            }
+            state.notifier.send(
+                NotificationEvent::StopLoss,
+                &[
+                    ("symbol", stop.symbol.clone()),
+                    ("side", format!("{:?}", stop.side)),
+                    ("quantity", stop.quantity.to_string()),
+                    ("reason", stop.reason.clone()),
+                    ("outcome", outcome),
+                ],
+            );
+        }
+    })
+    .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::binance::test_account;
+    use crate::types::test_prices;
+
+    #[test]
+    fn test_check_strategy_scales_before_validating() {
+        let binance = test_account("100000", serde_json::json!([]));
+        let onchain = ChainPortfolio::default();
+        let chain_data = HashMap::new();
+        let context = StrategyContext {
+            binance: &binance,
+            onchain: &onchain,
+            prices: &test_prices(),
+            exposure: &crate::config::ExposureConfig::default(),
+            chain_data: &chain_data,
+        };
+        let config = StrategyValidationConfig::default();
+        // $60000 of ETH, above the $50000 cap on one order
+        let strategy = || -> Strategy {
+            serde_json::from_value(serde_json::json!({
+                "exchanges": {
+                    "binance": {"orders": [
+                        {"position": "short", "token": "eth", "amount": "30", "price": "", "side": "SELL"}
+                    ]},
+                    "eisen": {}
+                },
+                "explanations": []
+            }))
+            .unwrap()
+        };
+
+        let mut unscaled = strategy();
+        let violations = check_strategy(&mut unscaled, &context, EngineKind::Llm, None, &config)
+            .unwrap_err()
+            .violations;
+        assert!(violations[0].contains("notional $60000.00 exceeds"));
+
+        let mut scaled = strategy();
+        assert_eq!(
+            check_strategy(
+                &mut scaled,
+                &context,
+                EngineKind::Llm,
+                Some(30_000.0),
+                &config
+            )
+            .unwrap(),
+            Some(0.5)
+        );
+        assert_eq!(scaled.exchanges.binance.orders.unwrap()[0].amount, "15");
+    }
+
+    #[test]
+    fn test_parse_wallet_address() {
+        let checksummed = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+        let address = parse_wallet_address("wallet_address", checksummed).unwrap();
+        assert_eq!(address.to_checksum(None), checksummed);
+        // All lower or all upper case carries no checksum to check
+        assert_eq!(
+            parse_wallet_address("wallet_address", &checksummed.to_lowercase()).unwrap(),
+            address
+        );
+        assert_eq!(
+            parse_wallet_address(
+                "wallet_address",
+                &format!("0x{}", checksummed[2..].to_uppercase())
+            )
+            .unwrap(),
+            address
+        );
+        // One character in the wrong case breaks the checksum
+        let mistyped = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD";
+        let Err(AppError::BadRequest { message, field }) =
+            parse_wallet_address("wallet_address", mistyped)
+        else {
+            panic!("mistyped checksum accepted");
+        };
+        assert_eq!(field.as_deref(), Some("wallet_address"));
+        assert!(message.contains("does not match its checksum"));
+        assert!(parse_wallet_address("wallet_address", "0x5aAeb6").is_err());
+    }
 }
@@ -0,0 +1,445 @@
+use crate::agent::{
+    BinanceExchange, BinanceOrder, BridgeExchange, EisenExchange, EisenSwap, Exchanges, Strategy,
+};
+use crate::config::ExposureConfig;
+use crate::executor::binance::{ContractSpecs, OrderSide};
+use crate::executor::eisen::{ChainPortfolio, SwapAmount};
+use crate::portfolio::binance::AccountInfo;
+use crate::rebalance::token_price;
+use crate::report::{ExecutionReport, OrderOutcome, OrderReport, SwapOutcome, SwapReport};
+use crate::risk::{base_asset, order_notional, symbol_token};
+use crate::types::MarketPrices;
+use crate::utils::parser::extract_binance_place_order;
+use rust_decimal::prelude::ToPrimitive;
+use std::collections::BTreeMap;
+
+/// How far a strategy was meant to move, and did move, its exposure to one base asset,
+/// in signed USD.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ExposureMove {
+    pub intended: f64,
+    pub executed: f64,
+    /// Whether orders on the asset are still open, so what they will have executed is
+    /// not known yet.
+    pub pending: bool,
+}
+
+impl ExposureMove {
+    /// USD of exposure to add back: executed legs may fall short of the intended move,
+    /// but must not go past it or the other way.
+    pub fn unmatched(&self) -> f64 {
+        let (low, high) = (self.intended.min(0.0), self.intended.max(0.0));
+        self.executed.clamp(low, high) - self.executed
+    }
+}
+
+/// Compensation for one base asset: a perp order adding back the unmatched exposure,
+/// and the executed swaps reversed in its place should the order not go through.
+pub struct HedgeLeg {
+    pub asset: String,
+    pub unmatched_usd: f64,
+    pub order: Strategy,
+    pub fallback: Strategy,
+}
+
+/// Pricing of a strategy's legs, from the portfolio it was made for.
+pub struct HedgeContext<'a> {
+    pub binance: &'a AccountInfo,
+    pub onchain: &'a ChainPortfolio,
+    pub prices: &'a MarketPrices,
+    pub exposure: &'a ExposureConfig,
+    pub contracts: &'a ContractSpecs,
+}
+
+impl HedgeContext<'_> {
+    fn price(&self, token: &str) -> Option<f64> {
+        token_price(
+            token,
+            self.exposure,
+            self.binance,
+            self.onchain,
+            self.prices,
+        )
+    }
+
+    fn base_asset(&self, token: &str) -> String {
+        base_asset(self.exposure, token)
+    }
+}
+
+/// Exposure moves of `strategy` as planned and of `report` as executed, per base
+/// asset. Bridges keep the asset, and legs that cannot be priced are left out.
+pub fn exposure_moves(
+    strategy: &Strategy,
+    report: &ExecutionReport,
+    context: &HedgeContext,
+) -> BTreeMap<String, ExposureMove> {
+    let mut moves: BTreeMap<String, ExposureMove> = BTreeMap::new();
+    let mut add = |asset: String, usd: f64, executed: bool| {
+        let entry = moves.entry(asset).or_default();
+        if executed {
+            entry.executed += usd;
+        } else {
+            entry.intended += usd;
+        }
+    };
+    let signed = |side: OrderSide, usd: f64| match side {
+        OrderSide::Buy => usd,
+        OrderSide::Sell => -usd,
+    };
+
+    for order in extract_binance_place_order(strategy, context.contracts) {
+        let token = symbol_token(&order.symbol);
+        let quantity = order.quantity.and_then(|q| q.to_f64()).unwrap_or(0.0);
+        let price = context.price(token);
+        if let Some(usd) = order_notional(&order.symbol, quantity, price, context.contracts) {
+            add(context.base_asset(token), signed(order.side, usd), false);
+        }
+    }
+    for swap in strategy.exchanges.eisen.swaps.iter().flatten() {
+        let amount = match SwapAmount::parse(&swap.amount) {
+            Ok(SwapAmount::Exact(amount)) => amount,
+            Ok(SwapAmount::Max) => context.onchain.balance_of(&swap.token_in),
+            Err(_) => continue,
+        };
+        if let Some(price) = context.price(&swap.token_in) {
+            add(context.base_asset(&swap.token_in), -amount * price, false);
+            add(context.base_asset(&swap.token_out), amount * price, false);
+        }
+    }
+
+    let mut pending = Vec::new();
+    for placed in &report.orders {
+        let OrderOutcome::Placed { order, unwind, .. } = &placed.outcome else {
+            continue;
+        };
+        let token = symbol_token(&order.symbol);
+        if placed.is_open() {
+            pending.push(context.base_asset(token));
+        }
+        for order in std::iter::once(order.as_ref()).chain(unwind.as_deref()) {
+            let quantity = order.executed_qty.to_f64().unwrap_or(0.0);
+            let price = order.avg_price.to_f64();
+            if let Some(usd) = order_notional(&order.symbol, quantity, price, context.contracts) {
+                add(context.base_asset(token), signed(order.side, usd), true);
+            }
+        }
+    }
+    for swap in &report.swaps {
+        if let Some((_, usd)) = swap_value(swap, context) {
+            add(context.base_asset(&swap.token_in), -usd, true);
+            add(context.base_asset(&swap.token_out), usd, true);
+        }
+    }
+    for asset in pending {
+        moves.entry(asset).or_default().pending = true;
+    }
+    moves
+}
+
+/// Share of the unmatched exposure a hedge order left: all of it when the order was
+/// not placed, else the part of its quantity that did not fill.
+pub fn unfilled_share(order: Option<&OrderReport>) -> f64 {
+    let Some(OrderOutcome::Placed { order, .. }) = order.map(|order| &order.outcome) else {
+        return 1.0;
+    };
+    let ordered = order.orig_qty.to_f64().unwrap_or(0.0);
+    if ordered <= 0.0 {
+        return 1.0;
+    }
+    let executed = order.executed_qty.to_f64().unwrap_or(0.0);
+    (1.0 - executed / ordered).clamp(0.0, 1.0)
+}
+
+impl HedgeLeg {
+    /// Scales the fallback swaps down to reverse only `share` of the unmatched exposure.
+    pub fn scale_fallback(&mut self, share: f64) {
+        for swap in self.fallback.exchanges.eisen.swaps.iter_mut().flatten() {
+            if let Ok(amount) = swap.amount.parse::<f64>() {
+                swap.amount = (amount * share).to_string();
+            }
+        }
+    }
+}
+
+/// Compensations for every base asset left with more than `tolerance_usd` of unmatched
+/// exposure. Dollars are not an exposure, and assets with open orders wait for them.
+pub fn plan_hedge(
+    strategy: &Strategy,
+    report: &ExecutionReport,
+    context: &HedgeContext,
+    tolerance_usd: f64,
+) -> Vec<HedgeLeg> {
+    exposure_moves(strategy, report, context)
+        .into_iter()
+        .filter(|(asset, moved)| {
+            asset != "USD" && !moved.pending && moved.unmatched().abs() > tolerance_usd
+        })
+        .filter_map(|(asset, moved)| {
+            let unmatched_usd = moved.unmatched();
+            let price = context.price(&asset)?;
+            let (position, side) = if unmatched_usd > 0.0 {
+                ("long", "BUY")
+            } else {
+                ("short", "SELL")
+            };
+            let order = BinanceOrder {
+                position: position.to_string(),
+                token: asset.clone(),
+                amount: format!("{:.8}", unmatched_usd.abs() / price),
+                price: String::new(),
+                side: side.to_string(),
+                quote: None,
+                sequence: None,
+                id: None,
+                depends_on: None,
+                confidence: None,
+                priority: None,
+                post_only: None,
+            };
+            // Swaps that bought the asset when it is now too long, or sold it when too
+            // short, reversed until they add back the unmatched exposure and no further
+            let mut left_usd = unmatched_usd.abs();
+            let reversed: Vec<EisenSwap> = report
+                .swaps
+                .iter()
+                .filter(|swap| {
+                    let bought = context.base_asset(&swap.token_out) == asset;
+                    let sold = context.base_asset(&swap.token_in) == asset;
+                    if unmatched_usd < 0.0 {
+                        bought
+                    } else {
+                        sold
+                    }
+                })
+                .filter_map(|swap| {
+                    let (amount_out, usd) = swap_value(swap, context)?;
+                    if left_usd <= 0.0 || usd <= 0.0 {
+                        return None;
+                    }
+                    let share = (left_usd / usd).min(1.0);
+                    left_usd -= usd * share;
+                    Some(EisenSwap {
+                        token_in: swap.token_out.clone(),
+                        token_out: swap.token_in.clone(),
+                        amount: (amount_out * share).to_string(),
+                        chain_id: Some(swap.chain_id),
+                        slippage_bps: None,
+                        dex_filter: None,
+                        sequence: None,
+                        id: None,
+                        depends_on: None,
+                        confidence: None,
+                        priority: None,
+                    })
+                })
+                .collect();
+            Some(HedgeLeg {
+                asset,
+                unmatched_usd,
+                order: strategy_of(Some(vec![order]), None),
+                fallback: strategy_of(None, Some(reversed)),
+            })
+        })
+        .collect()
+}
+
+/// What an executed swap delivered and its USD value, priced by the token out, else
+/// by the token in.
+fn swap_value(swap: &SwapReport, context: &HedgeContext) -> Option<(f64, f64)> {
+    let amount_out = swap_amount_out(swap)?;
+    let usd = context
+        .price(&swap.token_out)
+        .map(|price| amount_out * price)
+        .or_else(|| Some(swap.amount_in * context.price(&swap.token_in)?))?;
+    Some((amount_out, usd))
+}
+
+/// What an executed swap delivered: the measured output, else the least it could have.
+fn swap_amount_out(swap: &SwapReport) -> Option<f64> {
+    match &swap.outcome {
+        SwapOutcome::Executed(receipt) => {
+            Some(receipt.amount_out.unwrap_or(receipt.min_amount_out))
+        }
+        SwapOutcome::Split {
+            amount_out,
+            tranches,
+            ..
+        } => Some(
+            amount_out
+                .unwrap_or_else(|| tranches.iter().map(|tranche| tranche.min_amount_out).sum()),
+        ),
+        SwapOutcome::Skipped { .. } | SwapOutcome::Failed { .. } => None,
+    }
+}
+
+fn strategy_of(orders: Option<Vec<BinanceOrder>>, swaps: Option<Vec<EisenSwap>>) -> Strategy {
+    Strategy {
+        exchanges: Exchanges {
+            binance: BinanceExchange {
+                orders,
+                transfers: None,
+            },
+            eisen: EisenExchange { swaps },
+            bridges: BridgeExchange::default(),
+        },
+        explanations: Vec::new(),
+        rationale: None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::binance::{Status, UsdMarginFuturesOrder};
+    use crate::executor::eisen::{SlippageCheck, SwapReceipt};
+    use crate::portfolio::binance::test_account;
+    use crate::types::test_prices;
+    use rust_decimal::Decimal;
+
+    fn placed(side: &str, quantity: &str) -> OrderReport {
+        let order: UsdMarginFuturesOrder = serde_json::from_value(serde_json::json!({
+            "clientOrderId": "cpm-0", "executedQty": quantity, "orderId": 1,
+            "avgPrice": "2000", "origQty": quantity, "price": "0", "reduceOnly": false,
+            "side": side, "positionSide": "BOTH", "status": "FILLED", "stopPrice": "0",
+            "closePosition": false, "symbol": "ETHUSDT", "timeInForce": "GTC",
+            "type": "MARKET", "updateTime": 0, "workingType": "CONTRACT_PRICE",
+            "priceProtect": false
+        }))
+        .unwrap();
+        OrderReport {
+            symbol: "ETHUSDT".to_string(),
+            side: order.side,
+            quantity: Some(order.orig_qty),
+            outcome: OrderOutcome::Placed {
+                order: Box::new(order),
+                unwind: None,
+                unwind_error: None,
+            },
+        }
+    }
+
+    fn swap(outcome: SwapOutcome) -> SwapReport {
+        SwapReport {
+            chain_id: 8453,
+            token_in: "usdc".to_string(),
+            token_out: "wsteth".to_string(),
+            amount_in: 2000.0,
+            outcome,
+        }
+    }
+
+    #[test]
+    fn test_plan_hedge() {
+        // Short 1 ETH on Binance against 2000 USDC of wstETH
+        let strategy: Strategy = serde_json::from_value(serde_json::json!({
+            "exchanges": {
+                "binance": {"orders": [
+                    {"position": "short", "token": "eth", "amount": "1", "price": "", "side": "SELL"}
+                ]},
+                "eisen": {"swaps": [{"tokenIn": "usdc", "tokenOut": "wsteth", "amount": "2000"}]}
+            },
+            "explanations": []
+        }))
+        .unwrap();
+        let binance = test_account("0", serde_json::json!([]));
+        let onchain = ChainPortfolio::default();
+        let context = HedgeContext {
+            binance: &binance,
+            onchain: &onchain,
+            prices: &test_prices(),
+            exposure: &ExposureConfig::default(),
+            contracts: &ContractSpecs::new(),
+        };
+
+        // The short filled and the swap failed: buy the ETH back
+        let report = ExecutionReport {
+            orders: vec![placed("SELL", "1")],
+            swaps: vec![swap(SwapOutcome::Failed {
+                error: "reverted".to_string(),
+            })],
+            ..Default::default()
+        };
+        let legs = plan_hedge(&strategy, &report, &context, 50.0);
+        assert_eq!(legs.len(), 1);
+        assert_eq!(legs[0].asset, "ETH");
+        assert_eq!(legs[0].unmatched_usd, 2000.0);
+        let order = &legs[0].order.exchanges.binance.orders.as_ref().unwrap()[0];
+        assert_eq!(
+            (order.side.as_str(), order.amount.as_str()),
+            ("BUY", "1.00000000")
+        );
+        assert!(legs[0]
+            .fallback
+            .exchanges
+            .eisen
+            .swaps
+            .as_ref()
+            .unwrap()
+            .is_empty());
+
+        // A short resting on the book is pending: nothing to compensate until it fills
+        let mut open = placed("SELL", "1");
+        if let OrderOutcome::Placed { order, .. } = &mut open.outcome {
+            order.executed_qty = Decimal::ZERO;
+            order.status = Status::New;
+        }
+        let pending = ExecutionReport {
+            orders: vec![open],
+            ..Default::default()
+        };
+        assert!(plan_hedge(&strategy, &pending, &context, 50.0).is_empty());
+
+        // The swap filled and the short did not: short 0.9 ETH, or swap the wstETH back
+        let report = ExecutionReport {
+            swaps: vec![swap(SwapOutcome::Executed(SwapReceipt {
+                tx_hash: Default::default(),
+                block_number: Some(1),
+                gas_used: 150_000,
+                effective_gas_price: 1_000_000,
+                amount_out: Some(0.9),
+                expected_amount_out: 0.9,
+                min_amount_out: 0.89,
+                slippage_check: SlippageCheck::Passed,
+            }))],
+            ..Default::default()
+        };
+        let legs = plan_hedge(&strategy, &report, &context, 50.0);
+        assert_eq!(legs[0].unmatched_usd, -1800.0);
+        let order = &legs[0].order.exchanges.binance.orders.as_ref().unwrap()[0];
+        assert_eq!(
+            (order.side.as_str(), order.amount.as_str()),
+            ("SELL", "0.90000000")
+        );
+        let reversed = &legs[0].fallback.exchanges.eisen.swaps.as_ref().unwrap()[0];
+        assert_eq!(
+            (reversed.token_in.as_str(), reversed.token_out.as_str()),
+            ("wsteth", "usdc")
+        );
+        assert_eq!(reversed.amount, "0.9");
+
+        // The short filled in part: reverse only what it left unmatched
+        let mut report = report;
+        report.orders = vec![placed("SELL", "0.5")];
+        let legs = plan_hedge(&strategy, &report, &context, 50.0);
+        assert_eq!(legs[0].unmatched_usd, -800.0);
+        let reversed = &legs[0].fallback.exchanges.eisen.swaps.as_ref().unwrap()[0];
+        assert!((reversed.amount.parse::<f64>().unwrap() - 0.4).abs() < 1e-9);
+
+        // A hedge order filled in half leaves half of it to the fallback
+        let mut hedge = placed("SELL", "0.4");
+        if let OrderOutcome::Placed { order, .. } = &mut hedge.outcome {
+            order.executed_qty = "0.2".parse().unwrap();
+        }
+        assert_eq!(unfilled_share(Some(&hedge)), 0.5);
+        assert_eq!(unfilled_share(None), 1.0);
+        let mut leg = legs.into_iter().next().unwrap();
+        leg.scale_fallback(0.5);
+        let reversed = &leg.fallback.exchanges.eisen.swaps.as_ref().unwrap()[0];
+        assert!((reversed.amount.parse::<f64>().unwrap() - 0.2).abs() < 1e-9);
+
+        // Both legs filled: nothing is unmatched
+        report.orders = vec![placed("SELL", "0.9")];
+        assert!(plan_hedge(&strategy, &report, &context, 50.0).is_empty());
+    }
+}
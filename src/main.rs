@@ -1,87 +1,486 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
 use axum::{
-    http::{header, Method},
+    extract::{Query, State},
+    response::IntoResponse,
     routing::{get, post},
-    Router,
+    Json, Router,
 };
 use clap::Parser;
 use dotenv::dotenv;
+use feed::Feed;
+use futures::StreamExt;
 use std::env;
 use std::net::SocketAddr;
-use tower_http::cors::{Any, CorsLayer};
+use std::sync::Arc;
 pub mod agent;
+pub mod audit;
+pub mod backtest;
 pub mod cli;
+pub mod config;
 pub mod constants;
+pub mod engine;
 pub mod error;
 pub mod executor;
 pub mod feed;
 pub mod handlers;
+pub mod hedge;
+pub mod notify;
+pub mod plan;
 pub mod portfolio;
 pub mod processors;
+pub mod rebalance;
+pub mod report;
+pub mod risk;
+pub mod scheduler;
+pub mod stop_loss;
+pub mod storage;
 pub mod types;
 pub mod utils;
 pub mod yields;
 
+// How long a previewed strategy can be confirmed for
+const PLAN_TTL_SECS: u64 = 300;
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Load environment variables from .env file
     dotenv()?;
     let args: cli::Args = cli::Args::parse();
-    //  Lets use testnet for now
-    let binance_base_url = "https://testnet.binancefuture.com".to_string();
-
-    // Get API credentials from environment variables
-    let binance_api_key =
-        env::var("BINANCE_API_KEY").expect("BINANCE_API_KEY must be set in environment variables");
-    let binance_api_secret = env::var("BINANCE_API_SECRET")
-        .expect("BINANCE_API_SECRET must be set in environment variables");
+    let config = config::Config::load(args.config.as_deref())?;
+    match args.command.unwrap_or(cli::Command::Serve) {
+        cli::Command::Serve => serve(app_state(config, true).await?, args.serve).await,
+        cli::Command::Portfolio { wallet } => {
+            let state = app_state(config, false).await?;
+            let params = handlers::GetPortfolioParams {
+                wallet_address: wallet,
+            };
+            print_response(handlers::get_portfolio(State(state), Query(params)).await).await
+        }
+        cli::Command::Yields => {
+            let state = app_state(config, false).await?;
+            let params = handlers::GetYieldsParams {
+                notional: None,
+                symbol: None,
+                chain_id: None,
+                min_apr: None,
+                sort: None,
+                limit: None,
+            };
+            print_response(handlers::get_yields(State(state), Query(params)).await).await
+        }
+        cli::Command::Execute { wallet, dry_run } => {
+            let state = app_state(config, false).await?;
+            let params = handlers::ExecuteStrategyParams {
+                wallet_address: wallet,
+                engine: Default::default(),
+                provider: None,
+                model: None,
+                prompt_template: None,
+                mode: if dry_run {
+                    handlers::ExecuteMode::Preview
+                } else {
+                    handlers::ExecuteMode::Execute
+                },
+                job_id: None,
+                on_failure: Default::default(),
+                max_notional_usd: None,
+            };
+            print_response(handlers::execute_strategy(State(state), Json(params)).await).await
+        }
+        cli::Command::Backtest {
+            asset,
+            days,
+            notional_usd,
+            fee_bps,
+        } => {
+            let history = feed::history::MarketHistory::open(
+                &config.data_dir,
+                config.market_data.history_retention_days,
+            )?;
+            let symbol = executor::binance::futures_symbol(&asset.to_uppercase(), None);
+            let now = chrono::Utc::now();
+            let prices = history.prices(&symbol, now - chrono::Duration::days(days.into()), now);
+            let report = backtest::backtest_funding(
+                &symbol,
+                &prices,
+                &config.funding_rebalancer,
+                notional_usd,
+                fee_bps,
+            );
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            Ok(())
+        }
+    }
+}
+
+/// Print the body of a handler's response, failing on an error status.
+async fn print_response(response: Result<impl IntoResponse, error::AppError>) -> Result<()> {
+    let response = match response {
+        Ok(response) => response.into_response(),
+        Err(err) => err.into_response(),
+    };
+    let status = response.status();
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX).await?;
+    let body = match serde_json::from_slice::<serde_json::Value>(&body) {
+        Ok(json) => serde_json::to_string_pretty(&json)?,
+        Err(_) => String::from_utf8_lossy(&body).into_owned(),
+    };
+    if !status.is_success() {
+        bail!("{}: {}", status, body);
+    }
+    println!("{}", body);
+    Ok(())
+}
+
+/// Shared state of every command; the streams and feed pipelines keeping it current
+/// only run with `services`, while serving.
+async fn app_state(config: config::Config, services: bool) -> Result<types::AppState> {
+    // Side effects of background tasks are audited too, under no job
+    let audit = Arc::new(audit::AuditLog::open(&config.data_dir)?);
+    audit::install(audit.clone());
+    let paper_trading = config.paper_trading.enabled;
+    let binance_base_url = config.binance_base_url.clone();
+
+    // Get API credentials from environment variables; paper trading runs without them
+    let binance_var = |name: &str| {
+        env::var(name).or_else(|e| {
+            if paper_trading {
+                Ok(String::new())
+            } else {
+                Err(e)
+            }
+        })
+    };
+    let binance_api_key = binance_var("BINANCE_API_KEY")
+        .expect("BINANCE_API_KEY must be set in environment variables");
+    // Ed25519 API keys sign with the PEM file BINANCE_PRIVATE_KEY_PATH names, HMAC ones
+    // with BINANCE_API_SECRET
+    let binance_key = match env::var("BINANCE_PRIVATE_KEY_PATH") {
+        Ok(path) => {
+            let pem = std::fs::read_to_string(&path).map_err(|e| {
+                anyhow::anyhow!("Failed to read BINANCE_PRIVATE_KEY_PATH {}: {}", path, e)
+            })?;
+            utils::sign::BinanceKey::ed25519(binance_api_key.clone(), &pem)?
+        }
+        Err(_) => utils::sign::BinanceKey::hmac(
+            binance_api_key.clone(),
+            binance_var("BINANCE_API_SECRET")
+                .expect("BINANCE_API_SECRET must be set in environment variables"),
+        ),
+    }
+    .with_recv_window(config.binance_recv_window_ms);
     let eisen_base_url =
         env::var("EISEN_BASE_URL").expect("EISEN_BASE_URL must be set in environment variables");
 
+    // One pooled client per timeout, shared by every module
+    let reqwest_cli = config
+        .http
+        .client(std::time::Duration::from_secs(config.http.timeout_secs))?;
+    let agent_client = config
+        .http
+        .client(std::time::Duration::from_secs(config.llm.timeout_secs))?;
+
+    // Stream order and account updates instead of polling REST
+    let user_data_stream = feed::binance_ws::UserDataStream::new(
+        reqwest_cli.clone(),
+        binance_base_url.clone(),
+        binance_api_key.clone(),
+    );
+    let user_events = user_data_stream.sender();
+
+    // Keep prices current in memory instead of fetching them on every request
+    let prices = feed::binance_market_ws::PriceCache::new();
+    if services {
+        feed::binance_market_ws::MarketDataStream::new(
+            &binance_base_url,
+            &config.market_data,
+            prices.clone(),
+        )
+        .spawn();
+    }
+
+    // Size and value COIN-M orders by their contracts; symbols left unknown go unvalued
+    let contracts = executor::binance::ContractSpecs::new();
+    if services {
+        match contracts.refresh(&reqwest_cli, &binance_base_url).await {
+            Ok(loaded) => println!("Loaded the specs of {} COIN-M contracts", loaded),
+            Err(e) => println!("Failed to load COIN-M contract specs: {}", e),
+        }
+    }
+
+    let market_history = Arc::new(feed::history::MarketHistory::open(
+        &config.data_dir,
+        config.market_data.history_retention_days,
+    )?);
+
+    // Poll the latest candle of each symbol and keep its indicators current
+    let mut indicator_feeds = std::collections::HashMap::new();
+    let symbols = if services {
+        config.market_data.symbols.as_slice()
+    } else {
+        &[]
+    };
+    for symbol in symbols {
+        let interval = config.market_data.indicator_interval;
+        let history = feed::binance::BinanceOHLCVFeed::new(
+            binance_base_url.clone(),
+            reqwest_cli.clone(),
+            symbol.clone(),
+            config.market_data.indicator_candles,
+            vec![interval],
+        )
+        .feed()
+        .await
+        .map(|mut candles| candles.remove(&interval).unwrap_or_default())
+        .unwrap_or_else(|e| {
+            println!("Failed to fetch {} candle history: {}", symbol, e);
+            Vec::new()
+        });
+        if let Err(e) =
+            market_history.record_candles(symbol, interval, &history, chrono::Utc::now())
+        {
+            println!("Failed to record {} candle history: {}", symbol, e);
+        }
+        let service = feed::service::FeedService::new(
+            format!("{} {} indicators", symbol, interval),
+            Box::new(feed::binance::BinanceKlineFeed::new(
+                binance_base_url.clone(),
+                reqwest_cli.clone(),
+                symbol.clone(),
+                interval,
+            )),
+            Box::new(
+                feed::binance::BinanceOHLCVProcessor::new(
+                    config.market_data.indicator_candles,
+                    feed::indicators::IndicatorPeriods::default(),
+                )
+                .with_history(history),
+            ),
+            std::time::Duration::from_secs(config.market_data.pipeline_interval_secs),
+        );
+        market_history.clone().spawn_recorder(
+            symbol.clone(),
+            interval,
+            service.handle().subscribe(),
+        );
+        indicator_feeds.insert(symbol.clone(), service.handle());
+        service.spawn();
+    }
+
+    let reports = Arc::new(report::ReportStore::open(&config.data_dir)?);
+    let (paper_executor, paper_onchain) = if paper_trading {
+        println!("Paper trading enabled, Binance orders, swaps and bridge transfers are simulated");
+        (
+            Some(Arc::new(executor::paper::PaperBinanceExecutor::new(
+                binance_base_url.clone(),
+                reqwest_cli.clone(),
+                &config.paper_trading,
+                &config.data_dir,
+            )?)),
+            Some(Arc::new(executor::paper::PaperOnchain::open(
+                &config.data_dir,
+            )?)),
+        )
+    } else {
+        if services {
+            reports.clone().spawn_reconciler(user_events.subscribe());
+            user_data_stream.spawn();
+        }
+        (None, None)
+    };
+
     // Create shared state
-    let state = types::AppState {
+    let chain_metadata = executor::chains::ChainMetadataCache::new(
+        eisen_base_url.clone(),
+        reqwest_cli.clone(),
+        std::time::Duration::from_secs(config.chain_metadata_ttl_secs),
+    );
+
+    let yield_cache = yields::YieldCache::new(
+        yields::YieldRegistry::from_config(&config.yields, &config.chains, &reqwest_cli)?,
+        std::time::Duration::from_secs(config.yields.refresh_interval_secs),
+    )
+    .with_history(yields::YieldHistory::open(
+        &config.data_dir,
+        config.yields.history_retention_days,
+    )?);
+    if services {
+        yield_cache.spawn();
+    }
+    let notifier = notify::Notifier::new(reqwest_cli.clone(), &config.notifications);
+    let agent_usage = Arc::new(agent::usage::UsageLedger::open(
+        &config.data_dir,
+        &config.llm.pricing,
+    )?);
+    let agents = Arc::new(agent::AgentFactory::from_config(
+        agent_client,
+        &config.llm,
+        Some(agent_usage.clone()),
+    ));
+
+    Ok(types::AppState {
         binance_base_url,
-        binance_api_key,
-        binance_api_secret,
+        binance_key,
         eisen_base_url,
-        reqwest_cli: reqwest::Client::new(),
-    };
+        reqwest_cli: reqwest_cli.clone(),
+        user_events,
+        prices,
+        indicator_feeds,
+        market_history,
+        portfolio_history: Arc::new(portfolio::history::PortfolioHistory::open(
+            &config.data_dir,
+            config.portfolio_history.retention_days,
+        )?),
+        plans: plan::PlanStore::new(std::time::Duration::from_secs(PLAN_TTL_SECS)),
+        paper_executor,
+        paper_onchain,
+        chains: executor::chains::ChainRegistry::with_wallets(
+            config.chains.clone(),
+            &config.wallets,
+            &reqwest_cli,
+        )?,
+        chain_metadata,
+        nonces: Arc::new(executor::nonce::NonceManager::new()),
+        tx_queue: executor::queue::TxQueue::with_notifier(notifier.clone()),
+        yields: yield_cache,
+        reports,
+        risk: Arc::new(
+            risk::RiskEngine::new(config.risk.clone())
+                .with_contracts(contracts)
+                .with_store(&config.data_dir)?,
+        ),
+        schedules: Arc::new(scheduler::ScheduleStore::open(
+            &config.data_dir,
+            &config.scheduler.schedules,
+        )?),
+        paused: Arc::new(types::TradingPause::open(&config.data_dir)?),
+        notifier,
+        agents,
+        agent_usage,
+        agent_breakers: Arc::new(engine::CircuitBreakers::new(&config.llm.circuit_breaker)),
+        prompts: Arc::new(engine::prompt::PromptTemplates::new(&config.llm.prompts)?),
+        audit,
+        config: Arc::new(config),
+    })
+}
+
+async fn serve(state: types::AppState, args: cli::ServeArgs) -> Result<()> {
+    let config = state.config.clone();
+    let mut cors = config.cors.clone();
+    cors.allowed_origins.extend(args.cors_origins);
+    let cors_layer = cors.layer()?;
+    if state.config.portfolio_history.snapshot_interval_secs > 0 {
+        tokio::spawn(handlers::record_portfolio_snapshots(state.clone()));
+    }
+    if state.config.scheduler.enabled {
+        tokio::spawn(handlers::run_schedules(state.clone()));
+    }
+    if state.config.stop_loss.enabled {
+        tokio::spawn(handlers::monitor_stop_losses(state.clone()));
+    }
 
     // Build our application with routes
     let app = Router::new()
         .route("/health", get(handlers::health_check))
         .route("/api/v1/execute", post(handlers::execute_strategy))
+        .route(
+            "/api/v1/execute/stream",
+            post(handlers::execute_strategy_stream),
+        )
+        .route("/api/v1/execute/confirm", post(handlers::confirm_strategy))
         .route("/api/v1/portfolio", get(handlers::get_portfolio))
+        .route(
+            "/api/v1/portfolio/history",
+            get(handlers::get_portfolio_history),
+        )
+        .route("/api/v1/portfolio/pnl", get(handlers::get_portfolio_pnl))
+        .route("/api/v1/rebalance", get(handlers::get_rebalance))
+        .route(
+            "/api/v1/binance/margin-health",
+            get(handlers::get_margin_health),
+        )
         .route("/api/v1/yields", get(handlers::get_yields))
+        .route("/api/v1/yields/history", get(handlers::get_yield_history))
+        .route("/api/v1/market/history", get(handlers::get_market_history))
+        .route("/api/v1/jobs/:job_id", get(handlers::get_job))
+        .route("/api/v1/audit", get(handlers::get_audit))
+        .route("/api/v1/agent/usage", get(handlers::get_agent_usage))
+        .route("/metrics", get(handlers::get_metrics))
+        .route("/api/v1/admin/pause", post(handlers::pause_trading))
+        .route("/api/v1/admin/resume", post(handlers::resume_trading))
+        .route(
+            "/api/v1/schedules",
+            get(handlers::list_schedules).post(handlers::create_schedule),
+        )
+        .route(
+            "/api/v1/schedules/:id",
+            get(handlers::get_schedule)
+                .put(handlers::update_schedule)
+                .delete(handlers::delete_schedule),
+        )
+        .route(
+            "/api/v1/chains/metadata/refresh",
+            post(handlers::refresh_chain_metadata),
+        )
         .with_state(state)
-        .layer(
-            // Configure CORS middleware
-            CorsLayer::new()
-                // Allow requests from any origin
-                .allow_origin(Any)
-                // Allow common HTTP methods
-                .allow_methods([Method::GET, Method::POST])
-                // Specify explicit headers instead of Any when credentials are true
-                .allow_headers([
-                    header::AUTHORIZATION,
-                    header::CONTENT_TYPE,
-                    header::ACCEPT,
-                    header::ORIGIN,
-                    header::HeaderName::from_static("x-requested-with"),
-                    header::HeaderName::from_static("access-control-request-method"),
-                    header::HeaderName::from_static("access-control-request-headers"),
-                ]),
-        );
+        .layer(axum::middleware::from_fn_with_state(
+            Arc::new(cors),
+            handlers::reject_cross_site,
+        ))
+        .layer(cors_layer);
 
     // Run the server with CLI-configured host and port
     let addr: SocketAddr = format!("{}:{}", args.host, args.port)
         .parse()
         .expect("Failed to parse host:port into a socket address");
 
-    println!("Server listening on {}", addr);
+    let tls = &config.tls;
+    if !tls.enabled {
+        println!("Server listening on {}", addr);
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, app).await?;
+        return Ok(());
+    }
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
+    println!("Server listening on {} over HTTPS", addr);
+    let app = app.into_make_service();
+    match (&tls.acme, &tls.cert_path, &tls.key_path) {
+        (Some(acme), _, _) => {
+            let cache_dir = acme
+                .cache_dir
+                .clone()
+                .unwrap_or_else(|| config.data_dir.join("acme"));
+            let mut acme_state = rustls_acme::AcmeConfig::new(acme.domains.clone())
+                .contact(
+                    acme.contacts
+                        .iter()
+                        .map(|email| format!("mailto:{}", email)),
+                )
+                .cache(rustls_acme::caches::DirCache::new(cache_dir))
+                .directory_lets_encrypt(acme.production)
+                .state();
+            let acceptor = acme_state.axum_acceptor(acme_state.default_rustls_config());
+            // Drives issuance and renewal
+            tokio::spawn(async move {
+                while let Some(event) = acme_state.next().await {
+                    match event {
+                        Ok(event) => println!("ACME: {:?}", event),
+                        Err(e) => println!("ACME error: {}", e),
+                    }
+                }
+            });
+            axum_server::bind(addr)
+                .acceptor(acceptor)
+                .serve(app)
+                .await?;
+        }
+        (None, Some(cert_path), Some(key_path)) => {
+            let rustls =
+                axum_server::tls_rustls::RustlsConfig::from_pem_file(cert_path, key_path).await?;
+            axum_server::bind_rustls(addr, rustls).serve(app).await?;
+        }
+        _ => bail!("TLS needs cert_path and key_path, or acme"),
+    }
 
     Ok(())
 }
@@ -0,0 +1,253 @@
+use crate::config::{NotificationChannel, NotificationConfig, NotificationTarget};
+use crate::executor::queue::QueuedTx;
+use crate::portfolio::margin::MarginHealth;
+use crate::report::{BridgeOutcome, ExecutionReport, OrderOutcome, SwapOutcome};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Something worth telling an operator about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NotificationEvent {
+    StrategyExecuted,
+    LegFailed,
+    MarginWarning,
+    TxConfirmed,
+    StopLoss,
+}
+
+impl NotificationEvent {
+    /// Message sent unless the config overrides it; `{field}`s are filled in per event.
+    fn default_template(&self) -> &'static str {
+        match self {
+            NotificationEvent::StrategyExecuted => {
+                "Job {job_id} for {wallet}: {message} ({orders} orders, {bridges} bridges, {swaps} swaps executed)"
+            }
+            NotificationEvent::LegFailed => "Job {job_id} for {wallet}: failed {failure}",
+            NotificationEvent::MarginWarning => "Binance margin warning: {warnings}",
+            NotificationEvent::TxConfirmed => {
+                "Job {job_id}: {label} tx {tx_hash} of {leg} confirmed on chain {chain_id}"
+            }
+            NotificationEvent::StopLoss => {
+                "Stop-loss on {symbol}: {side} {quantity}, {reason}: {outcome}"
+            }
+        }
+    }
+}
+
+/// `template` with each `{name}` of `fields` replaced by its value; unknown names are
+/// left as they are.
+pub fn render(template: &str, fields: &[(&str, String)]) -> String {
+    fields
+        .iter()
+        .fold(template.to_string(), |text, (name, value)| {
+            text.replace(&format!("{{{}}}", name), value)
+        })
+}
+
+/// URL and JSON body delivering `text` to `target`, or why it cannot be delivered.
+fn request(
+    target: &NotificationTarget,
+    event: NotificationEvent,
+    text: &str,
+) -> Result<(String, Value), String> {
+    match target {
+        NotificationTarget::Discord { url } => Ok((url.clone(), json!({ "content": text }))),
+        NotificationTarget::Slack { url } => Ok((url.clone(), json!({ "text": text }))),
+        NotificationTarget::Webhook { url } => {
+            Ok((url.clone(), json!({ "event": event, "text": text })))
+        }
+        NotificationTarget::Telegram {
+            bot_token_env,
+            chat_id,
+        } => {
+            let token = std::env::var(bot_token_env)
+                .map_err(|_| format!("{} is not set", bot_token_env))?;
+            Ok((
+                format!("https://api.telegram.org/bot{}/sendMessage", token),
+                json!({ "chat_id": chat_id, "text": text }),
+            ))
+        }
+    }
+}
+
+/// Pushes event messages to the configured channels. Sends run in the background, so
+/// a slow or failing channel never holds up trading; failures are only logged.
+#[derive(Debug, Clone)]
+pub struct Notifier {
+    client: reqwest::Client,
+    config: Arc<NotificationConfig>,
+    // Set while the account is known to be near liquidation, so the warning goes out
+    // once per episode and not on every check
+    margin_warned: Arc<AtomicBool>,
+}
+
+impl Notifier {
+    pub fn new(client: reqwest::Client, config: &NotificationConfig) -> Self {
+        Self {
+            client,
+            config: Arc::new(config.clone()),
+            margin_warned: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Render `event` and send it to every channel subscribed to it.
+    pub fn send(&self, event: NotificationEvent, fields: &[(&str, String)]) {
+        let channels: Vec<&NotificationChannel> = self
+            .config
+            .channels
+            .iter()
+            .filter(|channel| channel.events.is_empty() || channel.events.contains(&event))
+            .collect();
+        if channels.is_empty() {
+            return;
+        }
+        let template = self
+            .config
+            .templates
+            .get(&event)
+            .map(String::as_str)
+            .unwrap_or(event.default_template());
+        let text = render(template, fields);
+        for channel in channels {
+            let (url, body) = match request(&channel.target, event, &text) {
+                Ok(request) => request,
+                Err(e) => {
+                    println!("Failed to send {:?} notification: {}", event, e);
+                    continue;
+                }
+            };
+            let client = self.client.clone();
+            tokio::spawn(async move {
+                let sent = client
+                    .post(&url)
+                    .json(&body)
+                    .send()
+                    .await
+                    .and_then(|response| response.error_for_status());
+                if let Err(e) = sent {
+                    // The error can hold the URL, and with it a webhook or bot secret
+                    println!(
+                        "Failed to send {:?} notification: {}",
+                        event,
+                        e.without_url()
+                    );
+                }
+            });
+        }
+    }
+
+    /// Summary of an executed job, then each of its failed legs.
+    pub fn execution(&self, wallet_address: &str, report: &ExecutionReport, message: &str) {
+        let orders = report
+            .orders
+            .iter()
+            .filter(|order| matches!(order.outcome, OrderOutcome::Placed { .. }))
+            .count();
+        let bridges = report
+            .bridges
+            .iter()
+            .filter(|bridge| matches!(bridge.outcome, BridgeOutcome::Executed(_)))
+            .count();
+        let swaps = report
+            .swaps
+            .iter()
+            .filter(|swap| {
+                matches!(
+                    swap.outcome,
+                    SwapOutcome::Executed(_) | SwapOutcome::Split { .. }
+                )
+            })
+            .count();
+        self.send(
+            NotificationEvent::StrategyExecuted,
+            &[
+                ("job_id", report.job_id.clone()),
+                ("wallet", wallet_address.to_string()),
+                ("message", message.to_string()),
+                ("orders", orders.to_string()),
+                ("bridges", bridges.to_string()),
+                ("swaps", swaps.to_string()),
+            ],
+        );
+        for failure in report.failures() {
+            self.send(
+                NotificationEvent::LegFailed,
+                &[
+                    ("job_id", report.job_id.clone()),
+                    ("wallet", wallet_address.to_string()),
+                    ("failure", failure),
+                ],
+            );
+        }
+    }
+
+    /// Warn when the account turns unhealthy; quiet until it has recovered.
+    pub fn margin_health(&self, health: &MarginHealth) {
+        let warn = !health.warnings.is_empty();
+        if self.margin_warned.swap(warn, Ordering::SeqCst) || !warn {
+            return;
+        }
+        self.send(
+            NotificationEvent::MarginWarning,
+            &[("warnings", health.warnings.join("; "))],
+        );
+    }
+
+    pub fn tx_confirmed(&self, job_id: &str, leg: &str, chain_id: u64, tx: &QueuedTx) {
+        self.send(
+            NotificationEvent::TxConfirmed,
+            &[
+                ("job_id", job_id.to_string()),
+                ("leg", leg.to_string()),
+                ("chain_id", chain_id.to_string()),
+                ("label", tx.label.clone()),
+                ("tx_hash", tx.tx_hash.to_string()),
+                ("confirmations", tx.confirmations.to_string()),
+            ],
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_notification_request() {
+        let text = render(
+            NotificationEvent::LegFailed.default_template(),
+            &[
+                ("job_id", "job-1".to_string()),
+                ("wallet", "0xabc".to_string()),
+                ("failure", "order on ETHUSDT: rejected".to_string()),
+            ],
+        );
+        assert_eq!(
+            text,
+            "Job job-1 for 0xabc: failed order on ETHUSDT: rejected"
+        );
+        assert_eq!(render("{job_id} {unknown}", &[]), "{job_id} {unknown}");
+
+        let slack = NotificationTarget::Slack {
+            url: "https://hooks.slack.com/services/x".to_string(),
+        };
+        let (url, body) = request(&slack, NotificationEvent::LegFailed, &text).unwrap();
+        assert_eq!(url, "https://hooks.slack.com/services/x");
+        assert_eq!(body, json!({ "text": text }));
+
+        let webhook = NotificationTarget::Webhook {
+            url: "https://example.com/hook".to_string(),
+        };
+        let (_, body) = request(&webhook, NotificationEvent::TxConfirmed, "sent").unwrap();
+        assert_eq!(body, json!({ "event": "tx_confirmed", "text": "sent" }));
+
+        let telegram = NotificationTarget::Telegram {
+            bot_token_env: "CHILL_PM_TEST_UNSET_BOT_TOKEN".to_string(),
+            chat_id: "42".to_string(),
+        };
+        assert!(request(&telegram, NotificationEvent::LegFailed, &text).is_err());
+    }
+}
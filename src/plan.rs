@@ -0,0 +1,426 @@
+use crate::agent::Strategy;
+use crate::executor::binance::futures_symbol;
+use crate::executor::chains::BASE_CHAIN_ID;
+use crate::executor::eisen::{ChainPortfolio, SwapAmount};
+use crate::portfolio::binance::AccountInfo;
+use crate::utils::rounding::DISPLAY_AMOUNT_DP;
+use rust_decimal::prelude::ToPrimitive;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// A strategy that has been previewed but not yet executed.
+pub struct PendingPlan {
+    pub wallet_address: String,
+    pub strategy: Strategy,
+    pub binance_portfolio: AccountInfo,
+    pub onchain_portfolio: ChainPortfolio,
+    /// Factor the strategy was scaled down by to fit the notional budget, if it was.
+    pub notional_scale: Option<f64>,
+    created_at: Instant,
+}
+
+impl PendingPlan {
+    pub fn new(
+        wallet_address: String,
+        strategy: Strategy,
+        binance_portfolio: AccountInfo,
+        onchain_portfolio: ChainPortfolio,
+    ) -> Self {
+        Self {
+            wallet_address,
+            strategy,
+            binance_portfolio,
+            onchain_portfolio,
+            notional_scale: None,
+            created_at: Instant::now(),
+        }
+    }
+
+    pub fn with_notional_scale(mut self, notional_scale: Option<f64>) -> Self {
+        self.notional_scale = notional_scale;
+        self
+    }
+
+    /// Hash identifying this plan in the confirmation call.
+    pub fn hash(&self) -> String {
+        let mut hasher = Sha256::new();
+        hasher.update(self.wallet_address.to_lowercase().as_bytes());
+        hasher.update(serde_json::to_vec(&self.strategy).unwrap_or_default());
+        hasher.update(format!("{:?}", self.created_at).as_bytes());
+        hex::encode(hasher.finalize())
+    }
+}
+
+#[derive(Debug)]
+pub enum PlanLookupError {
+    NotFound,
+    Expired,
+}
+
+/// Previewed plans waiting for confirmation, dropped once their TTL has passed.
+#[derive(Clone)]
+pub struct PlanStore {
+    ttl: Duration,
+    plans: Arc<Mutex<HashMap<String, PendingPlan>>>,
+}
+
+impl PlanStore {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            plans: Arc::new(Mutex::new(HashMap::new())),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// Store a plan and return its hash.
+    pub fn insert(&self, plan: PendingPlan) -> String {
+        let hash = plan.hash();
+        let mut plans = self.plans.lock().unwrap_or_else(|e| e.into_inner());
+        plans.retain(|_, plan| plan.created_at.elapsed() <= self.ttl);
+        plans.insert(hash.clone(), plan);
+        hash
+    }
+
+    /// Remove and return the plan; a plan can only be confirmed once.
+    pub fn take(&self, hash: &str) -> Result<PendingPlan, PlanLookupError> {
+        let mut plans = self.plans.lock().unwrap_or_else(|e| e.into_inner());
+        let plan = plans.remove(hash).ok_or(PlanLookupError::NotFound)?;
+        if plan.created_at.elapsed() > self.ttl {
+            return Err(PlanLookupError::Expired);
+        }
+        Ok(plan)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectedPosition {
+    pub symbol: String,
+    pub current_amount: f64,
+    pub delta: f64,
+    pub projected_amount: f64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectedBalance {
+    pub symbol: String,
+    pub current_balance: f64,
+    pub delta: f64,
+    /// None when the output of a swap into this token could not be quoted.
+    pub projected_balance: Option<f64>,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ProjectedPortfolio {
+    pub binance_positions: Vec<ProjectedPosition>,
+    pub onchain_balances: Vec<ProjectedBalance>,
+}
+
+/// Apply the strategy legs to the current portfolio.
+///
+/// `swap_outputs` holds the quoted output of each swap (same order as the strategy
+/// swaps), `None` if it could not be quoted. Problems found along the way are
+/// returned as human readable findings.
+pub fn project_portfolio(
+    strategy: &Strategy,
+    binance_portfolio: &AccountInfo,
+    onchain_portfolio: &ChainPortfolio,
+    swap_outputs: &[Option<f64>],
+) -> (ProjectedPortfolio, Vec<String>) {
+    let mut findings = Vec::new();
+    let mut projected = ProjectedPortfolio::default();
+
+    for order in strategy.exchanges.binance.orders.iter().flatten() {
+        let symbol = futures_symbol(&order.token, order.quote.as_deref());
+        let amount = match order.amount.parse::<f64>() {
+            Ok(amount) if amount > 0.0 => amount,
+            _ => {
+                findings.push(format!(
+                    "Binance order on {} has invalid amount {:?} and will be skipped",
+                    symbol, order.amount
+                ));
+                continue;
+            }
+        };
+        let delta = match order.side.to_uppercase().as_str() {
+            "BUY" => amount,
+            "SELL" => -amount,
+            _ => {
+                findings.push(format!(
+                    "Binance order on {} has invalid side {:?} and will be skipped",
+                    symbol, order.side
+                ));
+                continue;
+            }
+        };
+
+        match projected
+            .binance_positions
+            .iter_mut()
+            .find(|p| p.symbol == symbol)
+        {
+            Some(position) => {
+                position.delta += delta;
+                position.projected_amount += delta;
+            }
+            None => {
+                let current_amount = binance_portfolio
+                    .positions
+                    .iter()
+                    .filter(|p| p.symbol == symbol)
+                    .filter_map(|p| p.position_amt.to_f64())
+                    .fold(0.0, |sum, amount| sum + amount);
+                projected.binance_positions.push(ProjectedPosition {
+                    symbol,
+                    current_amount,
+                    delta,
+                    projected_amount: current_amount + delta,
+                });
+            }
+        }
+    }
+
+    // Only the Base side of bridge transfers shows up in the (Base) on-chain portfolio
+    for transfer in strategy.exchanges.bridges.transfers.iter().flatten() {
+        if transfer.from_chain_id == BASE_CHAIN_ID {
+            let Some(amount) = preview_amount(&transfer.amount, &transfer.token, onchain_portfolio)
+            else {
+                findings.push(format!(
+                    "Bridge transfer of {} has invalid amount {:?}",
+                    transfer.token, transfer.amount
+                ));
+                continue;
+            };
+            apply_balance_delta(
+                &mut projected.onchain_balances,
+                onchain_portfolio,
+                &transfer.token,
+                Some(-amount),
+            );
+        }
+        if transfer.to_chain_id == BASE_CHAIN_ID {
+            findings.push(format!(
+                "Amount of {} bridged to Base is not known until it arrives",
+                transfer.token_out()
+            ));
+            apply_balance_delta(
+                &mut projected.onchain_balances,
+                onchain_portfolio,
+                transfer.token_out(),
+                None,
+            );
+        }
+    }
+
+    for (i, swap) in strategy.exchanges.eisen.swaps.iter().flatten().enumerate() {
+        let amount = match preview_amount(&swap.amount, &swap.token_in, onchain_portfolio) {
+            Some(amount) if amount > 0.0 => amount,
+            _ => {
+                findings.push(format!(
+                    "Swap {} -> {} has invalid amount {:?}",
+                    swap.token_in, swap.token_out, swap.amount
+                ));
+                continue;
+            }
+        };
+        apply_balance_delta(
+            &mut projected.onchain_balances,
+            onchain_portfolio,
+            &swap.token_in,
+            Some(-amount),
+        );
+        let amount_out = swap_outputs.get(i).copied().flatten();
+        if amount_out.is_none() {
+            findings.push(format!(
+                "Output of swap {} -> {} could not be quoted",
+                swap.token_in, swap.token_out
+            ));
+        }
+        apply_balance_delta(
+            &mut projected.onchain_balances,
+            onchain_portfolio,
+            &swap.token_out,
+            amount_out,
+        );
+    }
+
+    for balance in &projected.onchain_balances {
+        if let Some(projected_balance) = balance.projected_balance {
+            if projected_balance < 0.0 {
+                findings.push(format!(
+                    "Swaps spend {:.*} {} but the wallet only holds {:.*}",
+                    DISPLAY_AMOUNT_DP,
+                    -balance.delta,
+                    balance.symbol,
+                    DISPLAY_AMOUNT_DP,
+                    balance.current_balance
+                ));
+            }
+        }
+    }
+
+    (projected, findings)
+}
+
+/// Amount of `token` a swap or bridge transfer is previewed with: "max" is the wallet's
+/// current balance, anything else the requested amount (execution clamps it to the live
+/// balance).
+pub fn preview_amount(
+    amount: &str,
+    token: &str,
+    onchain_portfolio: &ChainPortfolio,
+) -> Option<f64> {
+    match SwapAmount::parse(amount).ok()? {
+        SwapAmount::Max => Some(onchain_portfolio.balance_of(token)),
+        SwapAmount::Exact(amount) => Some(amount),
+    }
+}
+
+fn apply_balance_delta(
+    balances: &mut Vec<ProjectedBalance>,
+    onchain_portfolio: &ChainPortfolio,
+    symbol: &str,
+    delta: Option<f64>,
+) {
+    let symbol = symbol.to_lowercase();
+    let index = match balances.iter().position(|b| b.symbol == symbol) {
+        Some(index) => index,
+        None => {
+            let current_balance = onchain_portfolio.balance_of(&symbol);
+            balances.push(ProjectedBalance {
+                symbol,
+                current_balance,
+                delta: 0.0,
+                projected_balance: Some(current_balance),
+            });
+            balances.len() - 1
+        }
+    };
+
+    let balance = &mut balances[index];
+    match delta {
+        Some(delta) => {
+            balance.delta += delta;
+            balance.projected_balance = balance.projected_balance.map(|b| b + delta);
+        }
+        None => balance.projected_balance = None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::eisen::TokenBalance;
+    use crate::portfolio::binance::test_account;
+
+    fn strategy() -> Strategy {
+        serde_json::from_value(serde_json::json!({
+            "exchanges": {
+                "binance": {
+                    "orders": [
+                        {"position": "short", "token": "eth", "amount": "0.5", "price": "", "side": "SELL"},
+                        {"position": "short", "token": "eth", "amount": "0.25", "price": "", "side": "SELL"},
+                        {"position": "long", "token": "btc", "amount": "abc", "price": "", "side": "BUY"}
+                    ]
+                },
+                "eisen": {
+                    "swaps": [
+                        {"tokenIn": "USDC", "tokenOut": "wstETH", "amount": "1500"},
+                        {"tokenIn": "eth", "tokenOut": "weeth", "amount": "0.1"},
+                        {"tokenIn": "usdc", "tokenOut": "cbeth", "amount": "max"}
+                    ]
+                },
+                "bridges": {
+                    "transfers": [
+                        {"token": "usdc", "amount": "100", "fromChainId": 8453, "toChainId": 1}
+                    ]
+                }
+            },
+            "explanations": []
+        }))
+        .unwrap()
+    }
+
+    fn account() -> AccountInfo {
+        test_account(
+            "1000",
+            serde_json::json!([{
+                "symbol": "ETHUSDT", "positionSide": "BOTH", "positionAmt": "-1.0",
+                "unrealizedProfit": "0", "notional": "0", "initialMargin": "0",
+                "maintMargin": "0", "updateTime": 0
+            }]),
+        )
+    }
+
+    #[test]
+    fn test_project_portfolio() {
+        let onchain = ChainPortfolio {
+            balances: vec![TokenBalance {
+                symbol: "usdc".to_string(),
+                balance: 1000.0,
+                usd_value: None,
+            }],
+            positions: vec![],
+        };
+        let (projected, findings) = project_portfolio(
+            &strategy(),
+            &account(),
+            &onchain,
+            &[Some(0.4), None, Some(0.3)],
+        );
+
+        assert_eq!(projected.binance_positions.len(), 1);
+        let eth = &projected.binance_positions[0];
+        assert_eq!(eth.symbol, "ETHUSDT");
+        assert_eq!(eth.delta, -0.75);
+        assert_eq!(eth.projected_amount, -1.75);
+
+        // The bridge spends 100 USDC, then "max" spends the 1000 held before the swaps
+        let usdc = &projected.onchain_balances[0];
+        assert_eq!(usdc.projected_balance, Some(-1600.0));
+        let wsteth = &projected.onchain_balances[1];
+        assert_eq!(wsteth.projected_balance, Some(0.4));
+        let weeth = projected
+            .onchain_balances
+            .iter()
+            .find(|b| b.symbol == "weeth")
+            .unwrap();
+        assert_eq!(weeth.projected_balance, None);
+
+        // invalid BTC amount, unquoted weETH output, USDC and ETH overspent
+        assert_eq!(findings.len(), 4, "{:?}", findings);
+    }
+
+    #[test]
+    fn test_plan_store_single_use_and_ttl() {
+        let store = PlanStore::new(Duration::from_secs(60));
+        let onchain = ChainPortfolio::default();
+        let hash = store.insert(PendingPlan::new(
+            "0xabc".to_string(),
+            strategy(),
+            account(),
+            onchain,
+        ));
+        assert!(store.take(&hash).is_ok());
+        assert!(matches!(store.take(&hash), Err(PlanLookupError::NotFound)));
+
+        let expired = PlanStore::new(Duration::ZERO);
+        let hash = expired.insert(PendingPlan::new(
+            "0xabc".to_string(),
+            strategy(),
+            account(),
+            ChainPortfolio::default(),
+        ));
+        std::thread::sleep(Duration::from_millis(5));
+        assert!(matches!(expired.take(&hash), Err(PlanLookupError::Expired)));
+    }
+}
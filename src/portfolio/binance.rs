@@ -2,17 +2,18 @@ use crate::utils::sign::BinanceKey;
 use anyhow::Result;
 use reqwest::header::HeaderValue;
 use reqwest::Client;
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Asset {
-    pub wallet_balance: String,
-    pub unrealized_profit: String,
-    pub margin_balance: String,
-    pub maint_margin: String,
-    pub initial_margin: String,
-    pub available_balance: String,
+    pub wallet_balance: Decimal,
+    pub unrealized_profit: Decimal,
+    pub margin_balance: Decimal,
+    pub maint_margin: Decimal,
+    pub initial_margin: Decimal,
+    pub available_balance: Decimal,
     pub update_time: u64,
     pub asset: String,
 }
@@ -22,26 +23,66 @@ pub struct Asset {
 pub struct Position {
     pub symbol: String,
     pub position_side: String,
-    pub position_amt: String,
-    pub unrealized_profit: String,
-    pub notional: String,
-    pub initial_margin: String,
-    pub maint_margin: String,
-    update_time: u64,
+    pub position_amt: Decimal,
+    pub unrealized_profit: Decimal,
+    pub notional: Decimal,
+    pub initial_margin: Decimal,
+    pub maint_margin: Decimal,
+    pub update_time: u64,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AccountInfo {
-    pub total_maint_margin: String,
-    pub total_wallet_balance: String,
-    pub total_unrealized_profit: String,
-    pub total_margin_balance: String,
-    pub total_position_initial_margin: String,
-    pub total_open_order_initial_margin: String,
-    pub available_balance: String,
+    pub total_maint_margin: Decimal,
+    pub total_wallet_balance: Decimal,
+    pub total_unrealized_profit: Decimal,
+    pub total_margin_balance: Decimal,
+    pub total_position_initial_margin: Decimal,
+    pub total_open_order_initial_margin: Decimal,
+    pub available_balance: Decimal,
     pub assets: Vec<Asset>,
     pub positions: Vec<Position>,
+    /// Spot wallet balances, merged in from the spot API.
+    #[serde(default)]
+    pub spot_balances: Vec<SpotBalance>,
+    /// Cross margin account assets, merged in when margin retrieval is enabled.
+    #[serde(default)]
+    pub margin_assets: Vec<MarginAsset>,
+}
+
+/// A non-zero spot wallet balance.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpotBalance {
+    pub asset: String,
+    pub free: Decimal,
+    /// Held by open orders.
+    pub locked: Decimal,
+}
+
+/// One asset of the cross margin account.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarginAsset {
+    pub asset: String,
+    pub free: Decimal,
+    pub locked: Decimal,
+    pub borrowed: Decimal,
+    pub interest: Decimal,
+    /// Free plus locked, less borrowed and interest.
+    pub net_asset: Decimal,
+}
+
+#[derive(Debug, Deserialize)]
+struct SpotAccount {
+    balances: Vec<SpotBalance>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MarginAccount {
+    user_assets: Vec<MarginAsset>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -54,7 +95,11 @@ pub struct AccountSummary {
     pub total_margin_balance: String,
 }
 
-pub async fn fetch_binance_portfolio(base_url: &str, key: &BinanceKey) -> Result<AccountInfo> {
+pub async fn fetch_binance_portfolio(
+    client: &Client,
+    base_url: &str,
+    key: &BinanceKey,
+) -> Result<AccountInfo> {
     // Create an empty parameter map to sign
     let params: HashMap<String, String> = HashMap::new();
 
@@ -70,13 +115,12 @@ pub async fn fetch_binance_portfolio(base_url: &str, key: &BinanceKey) -> Result
         serde_urlencoded::to_string(signed_params)?
     );
 
-    // Create a client and set the necessary headers
-    let client = Client::new();
+    // Set the necessary headers
     let response = client
         .get(&url)
         .header(
             "X-MBX-APIKEY",
-            HeaderValue::from_str(&key.api_key)
+            HeaderValue::from_str(key.api_key())
                 .map_err(|e| anyhow::anyhow!("Invalid API key: {}", e))?,
         )
         .send()
@@ -96,27 +140,257 @@ pub async fn fetch_binance_portfolio(base_url: &str, key: &BinanceKey) -> Result
     Ok(account_info)
 }
 
+/// Non-zero spot balances from `GET /api/v3/account` on the spot host.
+pub async fn fetch_spot_balances(
+    client: &Client,
+    spot_base_url: &str,
+    key: &BinanceKey,
+) -> Result<Vec<SpotBalance>> {
+    let params = HashMap::from([("omitZeroBalances".to_string(), "true".to_string())]);
+    let account: SpotAccount = signed_get(
+        client,
+        spot_base_url,
+        "/api/v3/account",
+        params,
+        key,
+        "spot account",
+    )
+    .await?;
+    Ok(account.balances)
+}
+
+/// Cross margin assets with a balance or a loan, from `GET /sapi/v1/margin/account`.
+pub async fn fetch_margin_assets(
+    client: &Client,
+    spot_base_url: &str,
+    key: &BinanceKey,
+) -> Result<Vec<MarginAsset>> {
+    let account: MarginAccount = signed_get(
+        client,
+        spot_base_url,
+        "/sapi/v1/margin/account",
+        HashMap::new(),
+        key,
+        "margin account",
+    )
+    .await?;
+    let held = |asset: &MarginAsset| {
+        [asset.free, asset.locked, asset.borrowed]
+            .iter()
+            .any(|amount| !amount.is_zero())
+    };
+    Ok(account.user_assets.into_iter().filter(held).collect())
+}
+
+async fn signed_get<T: serde::de::DeserializeOwned>(
+    client: &Client,
+    base_url: &str,
+    path: &str,
+    params: HashMap<String, String>,
+    key: &BinanceKey,
+    what: &str,
+) -> Result<T> {
+    let signed_params = key
+        .sign(params)
+        .map_err(|e| anyhow::anyhow!("Error signing parameters: {}", e))?;
+    let url = format!(
+        "{}{}?{}",
+        base_url,
+        path,
+        serde_urlencoded::to_string(signed_params)?
+    );
+    let response = client
+        .get(&url)
+        .header(
+            "X-MBX-APIKEY",
+            HeaderValue::from_str(key.api_key())
+                .map_err(|e| anyhow::anyhow!("Invalid API key: {}", e))?,
+        )
+        .send()
+        .await?;
+
+    if !response.status().is_success() {
+        return Err(anyhow::anyhow!(
+            "Failed to fetch {}: HTTP {}",
+            what,
+            response.status()
+        ));
+    }
+
+    response
+        .json()
+        .await
+        .map_err(|e| anyhow::anyhow!("Error parsing {}: {}", what, e))
+}
+
+/// One entry of the futures income history.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Income {
+    pub symbol: String,
+    /// "REALIZED_PNL", "FUNDING_FEE", "COMMISSION", "TRANSFER", ...
+    pub income_type: String,
+    /// Signed amount in `asset`.
+    pub income: String,
+    pub asset: String,
+    /// Unix ms.
+    pub time: u64,
+    #[serde(default)]
+    pub tran_id: u64,
+}
+
+/// Most entries Binance returns per income history call.
+const INCOME_PAGE_LIMIT: usize = 1000;
+
+/// Add the entries of `page` not in `incomes` yet and return where the next page starts,
+/// `None` once the history is exhausted. Pages overlap by the millisecond of their last
+/// entry, since more entries may share it.
+fn merge_income_page(incomes: &mut Vec<Income>, page: Vec<Income>) -> Option<u64> {
+    let full = page.len() >= INCOME_PAGE_LIMIT;
+    let last_time = page.last().map(|income| income.time)?;
+    let before = incomes.len();
+    for income in page {
+        let seen = incomes
+            .iter()
+            .rev()
+            .take_while(|seen| seen.time >= income.time)
+            .any(|seen| {
+                seen.time == income.time
+                    && seen.tran_id == income.tran_id
+                    && seen.income_type == income.income_type
+                    && seen.symbol == income.symbol
+            });
+        if !seen {
+            incomes.push(income);
+        }
+    }
+    match (full, incomes.len() > before) {
+        (false, _) => None,
+        (true, true) => Some(last_time),
+        // A full page of one millisecond would repeat forever
+        (true, false) => Some(last_time + 1),
+    }
+}
+
+/// Income booked within `[start_time, end_time]` (Unix ms), oldest first, fetched page
+/// by page.
+pub async fn fetch_income(
+    client: &Client,
+    base_url: &str,
+    key: &BinanceKey,
+    start_time: u64,
+    end_time: u64,
+) -> Result<Vec<Income>> {
+    let mut incomes = Vec::new();
+    let mut start_time = start_time;
+    while start_time <= end_time {
+        let params = HashMap::from([
+            ("startTime".to_string(), start_time.to_string()),
+            ("endTime".to_string(), end_time.to_string()),
+            ("limit".to_string(), INCOME_PAGE_LIMIT.to_string()),
+        ]);
+        let page: Vec<Income> = signed_get(
+            client,
+            base_url,
+            "/fapi/v1/income",
+            params,
+            key,
+            "income history",
+        )
+        .await?;
+        match merge_income_page(&mut incomes, page) {
+            Some(next) => start_time = next,
+            None => break,
+        }
+    }
+    Ok(incomes)
+}
+
+/// Futures account with `margin_balance` as its wallet, margin and available balance,
+/// holding `positions` as Binance lists them.
+#[cfg(test)]
+pub(crate) fn test_account(margin_balance: &str, positions: serde_json::Value) -> AccountInfo {
+    serde_json::from_value(serde_json::json!({
+        "totalMaintMargin": "0",
+        "totalWalletBalance": margin_balance,
+        "totalUnrealizedProfit": "0",
+        "totalMarginBalance": margin_balance,
+        "totalPositionInitialMargin": "0",
+        "totalOpenOrderInitialMargin": "0",
+        "availableBalance": margin_balance,
+        "assets": [],
+        "positions": positions,
+    }))
+    .unwrap()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use dotenv::dotenv;
     use std::env;
 
+    #[test]
+    fn test_merge_income_pages() {
+        let income = |time: u64, tran_id: u64| Income {
+            symbol: "BTCUSDT".to_string(),
+            income_type: "FUNDING_FEE".to_string(),
+            income: "0.1".to_string(),
+            asset: "USDT".to_string(),
+            time,
+            tran_id,
+        };
+        let mut incomes = Vec::new();
+        let page: Vec<Income> = (0..INCOME_PAGE_LIMIT as u64)
+            .map(|i| income(i / 2, i))
+            .collect();
+        assert_eq!(merge_income_page(&mut incomes, page), Some(499));
+        // The next page repeats the last millisecond
+        let page = vec![income(499, 998), income(499, 999), income(500, 1000)];
+        assert_eq!(merge_income_page(&mut incomes, page), None);
+        assert_eq!(incomes.len(), INCOME_PAGE_LIMIT + 1);
+        assert_eq!(merge_income_page(&mut incomes, Vec::new()), None);
+
+        let same_ms: Vec<Income> = (0..INCOME_PAGE_LIMIT as u64)
+            .map(|i| income(7, i))
+            .collect();
+        let mut incomes = same_ms.clone();
+        assert_eq!(merge_income_page(&mut incomes, same_ms), Some(8));
+    }
+
+    #[test]
+    fn test_parse_spot_and_margin_accounts() -> Result<()> {
+        let spot: SpotAccount = serde_json::from_str(
+            r#"{"makerCommission": 10, "balances": [{"asset": "USDT", "free": "120.5", "locked": "0.00000000"}]}"#,
+        )?;
+        assert_eq!(spot.balances[0].free, Decimal::new(1205, 1));
+        let margin: MarginAccount = serde_json::from_str(
+            r#"{"marginLevel": "999", "userAssets": [{"asset": "BTC", "free": "0.1", "locked": "0", "borrowed": "0.02", "interest": "0.0001", "netAsset": "0.0799"}]}"#,
+        )?;
+        assert_eq!(margin.user_assets[0].net_asset, Decimal::new(799, 4));
+        // Futures account responses carry neither
+        let account: AccountInfo = serde_json::from_str(
+            r#"{"totalMaintMargin": "0", "totalWalletBalance": "0", "totalUnrealizedProfit": "0", "totalMarginBalance": "0", "totalPositionInitialMargin": "0", "totalOpenOrderInitialMargin": "0", "availableBalance": "0", "assets": [], "positions": []}"#,
+        )?;
+        assert!(account.spot_balances.is_empty() && account.margin_assets.is_empty());
+        Ok(())
+    }
+
     #[tokio::test(flavor = "multi_thread")]
     async fn test_get_binance_portfolio() -> Result<()> {
         dotenv().unwrap();
-        let binance_key = BinanceKey {
-            api_key: env::var("BINANCE_API_KEY").expect("BINANCE_API_KEY must be set in .env"),
-            secret_key: env::var("BINANCE_API_SECRET")
-                .expect("BINANCE_SECRET_KEY must be set in .env"),
-        };
+        let binance_key = BinanceKey::hmac(
+            env::var("BINANCE_API_KEY").expect("BINANCE_API_KEY must be set in .env"),
+            env::var("BINANCE_API_SECRET").expect("BINANCE_SECRET_KEY must be set in .env"),
+        );
         let binance_base_url =
             if env::var("ENVIRONMENT").expect("BINANCE_ENV must be set in .env") == "test" {
                 "https://testnet.binancefuture.com"
             } else {
                 "https://fapi.binance.com"
             };
-        let portfolio = fetch_binance_portfolio(binance_base_url, &binance_key).await?;
+        let portfolio =
+            fetch_binance_portfolio(&Client::new(), binance_base_url, &binance_key).await?;
 
         println!("{:?}", portfolio);
         Ok(())
@@ -1,7 +1,7 @@
+use crate::utils::rounding::{units_to_f64, DISPLAY_AMOUNT_DP};
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
-use std::str::FromStr;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -32,7 +32,7 @@ impl std::fmt::Display for UnderlyingBalancesResponse {
                 if !chain.asset_total_amount_in_chain.is_empty() {
                     writeln!(f, "    Assets in Chain:")?;
                     for (symbol, amount) in &chain.asset_total_amount_in_chain {
-                        writeln!(f, "      {}: {:.6}", symbol, amount)?;
+                        writeln!(f, "      {}: {:.*}", symbol, DISPLAY_AMOUNT_DP, amount)?;
                     }
                 }
 
@@ -130,8 +130,7 @@ pub struct Balance {
 
 impl Balance {
     fn to_f64(&self) -> Result<f64> {
-        let amount = f64::from_str(&self.amount).map_err(|_| anyhow!("invalid amount"))?
-            / 10_f64.powi(self.decimals as i32);
+        let amount = units_to_f64(&self.amount, self.decimals)?;
         if self.positive_sign {
             Ok(amount)
         } else {
@@ -140,11 +139,6 @@ impl Balance {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct UserOnchainPortfolio {
-    pub total_exposure: f64,
-    pub chain_details: Vec<ChainDetailFeed>,
-}
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ChainDetailFeed {
     pub chain_id: u64,
@@ -163,61 +157,10 @@ pub struct AssetFeed {
     pub balance: f64,
     pub underlying_amount: f64,
 }
-pub async fn get_onchain_portfolio(
-    base_url: &str,
-    wallet_address: &str,
-) -> Result<UnderlyingBalancesResponse> {
-    // Get base URL from environment variables
-
-    // Construct the endpoint URL
-    let url = format!(
-        "{}/underlying_balances?walletAddress={}",
-        base_url, wallet_address
-    );
-
-    // Create an async HTTP client
-    let client = reqwest::Client::new();
-
-    // Send the GET request
-    let response = client
-        .get(&url)
-        .send()
-        .await
-        .map_err(|_| anyhow!("get request err"))?;
-    let data: UnderlyingBalancesResponse = response.json().await?;
-
-    Ok(data)
-}
-
-// New function to fetch underlying balances.
-pub async fn get_token_exposure_onchain(
-    data: UnderlyingBalancesResponse,
-    token: &str,
-) -> Result<UserOnchainPortfolio> {
-    let base_addr = convert_sym_to_mapped_config_addr(token)?;
-
-    // Deserialize JSON into our structs
-
-    let user_onchain_portfolio = UserOnchainPortfolio {
-        total_exposure: data
-            .chain_details
-            .iter()
-            .map(|chain_detail| {
-                chain_detail
-                    .asset_total_amount_in_chain
-                    .iter()
-                    .filter_map(|(asset, amount)| {
-                        if asset.to_lowercase() == token {
-                            Some(*amount)
-                        } else {
-                            None
-                        }
-                    })
-                    .fold(0 as f64, |sum, ele| sum + ele)
-            })
-            .sum(),
-        chain_details: data
-            .chain_details
+impl UnderlyingBalancesResponse {
+    /// Every non-zero asset of every protocol on every chain, with its underlying amount.
+    pub fn positions(&self) -> Vec<ChainDetailFeed> {
+        self.chain_details
             .iter()
             .map(|chain_detail| ChainDetailFeed {
                 chain_id: chain_detail.chain_id,
@@ -229,23 +172,12 @@ pub async fn get_token_exposure_onchain(
                         assets: protocol_detail
                             .assets
                             .iter()
-                            .filter(|asset| {
-                                asset.base_contract_address == base_addr
-                                    && asset.underlying_balance.amount != "0"
-                            })
+                            .filter(|asset| asset.underlying_balance.amount != "0")
                             .filter_map(|asset| {
-                                let underlying_amount = match asset.underlying_balance.to_f64() {
-                                    Ok(value) => value,
-                                    Err(_) => return None,
-                                };
-                                let balance = match asset.amount_to_calc_underlying.to_f64() {
-                                    Ok(value) => value,
-                                    Err(_) => return None,
-                                };
                                 Some(AssetFeed {
-                                    balance,
                                     symbol: asset.symbol.clone(),
-                                    underlying_amount,
+                                    balance: asset.amount_to_calc_underlying.to_f64().ok()?,
+                                    underlying_amount: asset.underlying_balance.to_f64().ok()?,
                                 })
                             })
                             .collect::<Vec<_>>(),
@@ -254,18 +186,29 @@ pub async fn get_token_exposure_onchain(
                     .collect::<Vec<_>>(),
             })
             .filter(|chain_detail| !chain_detail.protocol_details.is_empty())
-            .collect::<Vec<_>>(),
-    };
-
-    Ok(user_onchain_portfolio)
+            .collect()
+    }
 }
 
-fn convert_sym_to_mapped_config_addr(token_symbol: &str) -> Result<String> {
-    match token_symbol.to_string().to_lowercase().as_str() {
-        "eth" => Ok("0x0000000000000000000000000000000000000000".to_string()),
-        "btc" => Ok("0x0000000000000000000000000000000000000001".to_string()),
-        &_ => Err(anyhow!("Noonchain_portfoliod token")),
+/// Wallet and protocol balances of `wallet_address` on every chain Eisen indexes,
+/// resolved to their underlying assets.
+pub async fn get_onchain_portfolio(
+    client: &reqwest::Client,
+    base_url: &str,
+    wallet_address: &str,
+) -> Result<UnderlyingBalancesResponse> {
+    let url = format!(
+        "{}/underlying_balances?walletAddress={}",
+        base_url, wallet_address
+    );
+    let response = client.get(&url).send().await?;
+    if !response.status().is_success() {
+        return Err(anyhow!(
+            "Failed to fetch underlying balances: HTTP {}",
+            response.status()
+        ));
     }
+    Ok(response.json().await?)
 }
 
 #[cfg(test)]
@@ -274,17 +217,48 @@ mod tests {
     use dotenv::dotenv;
     use std::env;
 
+    #[test]
+    fn test_positions() {
+        let balance = |amount: &str| serde_json::json!({ "amount": amount, "decimals": 18, "positiveSign": true });
+        let asset = |symbol: &str, amount: &str| {
+            serde_json::json!({
+                "contractAddress": "0x1",
+                "symbol": symbol,
+                "baseContractAddress": "0x0000000000000000000000000000000000000000",
+                "amountToCalcUnderlying": balance(amount),
+                "underlyingBalance": balance(amount),
+            })
+        };
+        let response: UnderlyingBalancesResponse = serde_json::from_value(serde_json::json!({
+            "chainDetails": [
+                {
+                    "chainId": 1,
+                    "protocolDetails": [
+                        { "name": "Lido", "assets": [asset("stETH", "1500000000000000000")] },
+                        { "name": "AaveV3", "assets": [asset("aWETH", "0")] }
+                    ]
+                },
+                { "chainId": 10, "protocolDetails": [] }
+            ]
+        }))
+        .unwrap();
+        let positions = response.positions();
+        assert_eq!(positions.len(), 1);
+        assert_eq!(positions[0].chain_id, 1);
+        assert_eq!(positions[0].protocol_details.len(), 1);
+        let steth = &positions[0].protocol_details[0].assets[0];
+        assert_eq!((steth.symbol.as_str(), steth.balance), ("stETH", 1.5));
+    }
+
     #[tokio::test(flavor = "multi_thread")]
-    async fn test_get_token_exposure_onchain() -> Result<()> {
+    async fn test_get_onchain_portfolio() -> Result<()> {
         dotenv().unwrap();
         let base_url = env::var("EISEN_BASE_URL").expect("EISEN_BASE_URL must be set in .env");
         let wallet_address = "0xdAf87a186345f26d107d000fAD351E79Ff696d2C";
-        let token = "eth";
-        let onchain_portfolio = get_onchain_portfolio(base_url.as_str(), wallet_address).await?;
-        let onchain_portfolio = get_token_exposure_onchain(onchain_portfolio, token)
-            .await
-            .map_err(|_| anyhow!("error getting onchain portfolio"))?;
-        println!("{:?}", onchain_portfolio);
+        let onchain_portfolio =
+            get_onchain_portfolio(&reqwest::Client::new(), base_url.as_str(), wallet_address)
+                .await?;
+        println!("{:?}", onchain_portfolio.positions());
         Ok(())
     }
 }
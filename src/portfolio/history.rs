@@ -0,0 +1,320 @@
+use super::binance::{AccountInfo, Income};
+use crate::executor::chains::BASE_CHAIN_ID;
+use crate::executor::eisen::ChainPortfolio;
+use crate::storage::WalStore;
+use crate::types::MarketPrices;
+use anyhow::Result;
+use chrono::{DateTime, Duration as TimeDelta, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// An open Binance futures position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionSnapshot {
+    pub symbol: String,
+    pub amount: f64,
+    pub notional: f64,
+    pub unrealized_pnl: f64,
+}
+
+/// An on-chain wallet balance (`protocol` unset) or protocol position.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HoldingSnapshot {
+    pub chain_id: u64,
+    pub protocol: Option<String>,
+    pub symbol: String,
+    /// Underlying amount for protocol positions.
+    pub amount: f64,
+    pub usd_value: Option<f64>,
+}
+
+/// Values, positions and prices of one wallet's unified portfolio at `taken_at`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PortfolioSnapshot {
+    pub wallet_address: String,
+    pub taken_at: DateTime<Utc>,
+    pub btc_price: f64,
+    pub eth_price: f64,
+    /// Binance margin balance: wallet balance plus unrealized PnL.
+    pub binance_usd: f64,
+    pub unrealized_pnl: f64,
+    /// Priced on-chain wallet balances.
+    pub onchain_usd: f64,
+    pub total_usd: f64,
+    pub positions: Vec<PositionSnapshot>,
+    pub holdings: Vec<HoldingSnapshot>,
+}
+
+impl PortfolioSnapshot {
+    pub fn new(
+        wallet_address: &str,
+        binance: &AccountInfo,
+        onchain: &ChainPortfolio,
+        prices: &MarketPrices,
+        taken_at: DateTime<Utc>,
+    ) -> Self {
+        let number = |value: &Decimal| value.to_f64().unwrap_or(0.0);
+        let positions = binance
+            .positions
+            .iter()
+            .filter(|position| number(&position.position_amt) != 0.0)
+            .map(|position| PositionSnapshot {
+                symbol: position.symbol.clone(),
+                amount: number(&position.position_amt),
+                notional: number(&position.notional),
+                unrealized_pnl: number(&position.unrealized_profit),
+            })
+            .collect();
+        let wallet = onchain.balances.iter().map(|balance| HoldingSnapshot {
+            chain_id: BASE_CHAIN_ID,
+            protocol: None,
+            symbol: balance.symbol.clone(),
+            amount: balance.balance,
+            usd_value: balance.usd_value,
+        });
+        let protocols = onchain.positions.iter().flat_map(|chain| {
+            chain.protocol_details.iter().flat_map(move |protocol| {
+                protocol.assets.iter().map(move |asset| HoldingSnapshot {
+                    chain_id: chain.chain_id,
+                    protocol: Some(protocol.name.clone()),
+                    symbol: asset.symbol.clone(),
+                    amount: asset.underlying_amount,
+                    usd_value: None,
+                })
+            })
+        });
+        let binance_usd = number(&binance.total_margin_balance);
+        let onchain_usd = onchain.total_usd();
+        Self {
+            wallet_address: wallet_address.to_lowercase(),
+            taken_at,
+            btc_price: prices.btc,
+            eth_price: prices.eth,
+            binance_usd,
+            unrealized_pnl: number(&binance.total_unrealized_profit),
+            onchain_usd,
+            total_usd: binance_usd + onchain_usd,
+            positions,
+            holdings: wallet.chain(protocols).collect(),
+        }
+    }
+}
+
+/// PnL accumulated from the first snapshot of a window up to `taken_at`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnlPoint {
+    pub taken_at: DateTime<Utc>,
+    pub total_usd: f64,
+    pub realized_pnl: f64,
+    /// Change of the unrealized PnL since the first snapshot.
+    pub unrealized_pnl: f64,
+    pub funding: f64,
+    /// Trading fees, negative.
+    pub commission: f64,
+    /// Change of the on-chain wallet's value since the first snapshot.
+    pub onchain_pnl: f64,
+    pub net_pnl: f64,
+}
+
+/// PnL between the first and the last snapshot of a window, and at every snapshot in
+/// between. Unlike the change of `total_usd`, it leaves out Binance transfers; on-chain,
+/// the change of the wallet's value counts, price moves and transfers included.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnlReport {
+    pub start_usd: f64,
+    pub end_usd: f64,
+    pub realized_pnl: f64,
+    pub unrealized_pnl: f64,
+    pub funding: f64,
+    pub commission: f64,
+    pub onchain_pnl: f64,
+    pub net_pnl: f64,
+    pub points: Vec<PnlPoint>,
+}
+
+impl PnlReport {
+    /// PnL of `snapshots`, oldest first, from Binance `incomes` booked after the first
+    /// one. USD-M income is in the margin stablecoin and counted as USD. `None` without
+    /// snapshots.
+    pub fn new(snapshots: &[PortfolioSnapshot], incomes: &[Income]) -> Option<Self> {
+        let first = snapshots.first()?;
+        let points: Vec<PnlPoint> = snapshots
+            .iter()
+            .map(|snapshot| {
+                let booked = |income_type: &str| {
+                    incomes
+                        .iter()
+                        .filter(|income| income.income_type == income_type)
+                        .filter(|income| {
+                            let time = income.time as i64;
+                            time > first.taken_at.timestamp_millis()
+                                && time <= snapshot.taken_at.timestamp_millis()
+                        })
+                        .filter_map(|income| income.income.parse::<f64>().ok())
+                        .sum::<f64>()
+                };
+                let realized_pnl = booked("REALIZED_PNL");
+                let funding = booked("FUNDING_FEE");
+                let commission = booked("COMMISSION");
+                let unrealized_pnl = snapshot.unrealized_pnl - first.unrealized_pnl;
+                let onchain_pnl = snapshot.onchain_usd - first.onchain_usd;
+                PnlPoint {
+                    taken_at: snapshot.taken_at,
+                    total_usd: snapshot.total_usd,
+                    realized_pnl,
+                    unrealized_pnl,
+                    funding,
+                    commission,
+                    onchain_pnl,
+                    net_pnl: realized_pnl + unrealized_pnl + funding + commission + onchain_pnl,
+                }
+            })
+            .collect();
+        let last = points.last()?;
+        Some(Self {
+            start_usd: first.total_usd,
+            end_usd: last.total_usd,
+            realized_pnl: last.realized_pnl,
+            unrealized_pnl: last.unrealized_pnl,
+            funding: last.funding,
+            commission: last.commission,
+            onchain_pnl: last.onchain_pnl,
+            net_pnl: last.net_pnl,
+            points,
+        })
+    }
+}
+
+/// Portfolio snapshots within the retention window, persisted in a WAL store.
+pub struct PortfolioHistory {
+    snapshots: WalStore<PortfolioSnapshot>,
+    retention: TimeDelta,
+}
+
+impl PortfolioHistory {
+    pub fn open(data_dir: &Path, retention_days: u32) -> Result<Self> {
+        Ok(Self {
+            snapshots: WalStore::open(data_dir, "portfolio_history")?,
+            retention: TimeDelta::days(retention_days.into()),
+        })
+    }
+
+    /// Store `snapshot` and drop snapshots past retention.
+    pub fn record(&self, snapshot: PortfolioSnapshot) -> Result<()> {
+        let before = snapshot.taken_at - self.retention;
+        let key = format!(
+            "{}:{}",
+            snapshot.taken_at.timestamp_millis(),
+            snapshot.wallet_address
+        );
+        self.snapshots.put(key, snapshot)?;
+        for (key, snapshot) in self.snapshots.entries() {
+            if snapshot.taken_at < before {
+                self.snapshots.remove(&key)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Snapshots of `wallet_address` taken at or after `since`, oldest first.
+    pub fn query(&self, wallet_address: &str, since: DateTime<Utc>) -> Vec<PortfolioSnapshot> {
+        let mut snapshots: Vec<PortfolioSnapshot> = self
+            .snapshots
+            .entries()
+            .into_iter()
+            .map(|(_, snapshot)| snapshot)
+            .filter(|snapshot| snapshot.taken_at >= since)
+            .filter(|snapshot| snapshot.wallet_address.eq_ignore_ascii_case(wallet_address))
+            .collect();
+        snapshots.sort_by_key(|snapshot| snapshot.taken_at);
+        snapshots
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn snapshot(
+        wallet: &str,
+        taken_at: DateTime<Utc>,
+        total_usd: f64,
+        unrealized: f64,
+    ) -> PortfolioSnapshot {
+        PortfolioSnapshot {
+            wallet_address: wallet.to_string(),
+            taken_at,
+            btc_price: 64000.0,
+            eth_price: 3200.0,
+            binance_usd: total_usd,
+            unrealized_pnl: unrealized,
+            onchain_usd: 0.0,
+            total_usd,
+            positions: Vec::new(),
+            holdings: Vec::new(),
+        }
+    }
+
+    fn income(income_type: &str, amount: &str, time: DateTime<Utc>) -> Income {
+        Income {
+            symbol: "BTCUSDT".to_string(),
+            income_type: income_type.to_string(),
+            income: amount.to_string(),
+            asset: "USDT".to_string(),
+            time: time.timestamp_millis() as u64,
+            tran_id: 0,
+        }
+    }
+
+    #[test]
+    fn test_history_and_pnl() -> Result<()> {
+        let dir = tempdir()?;
+        let start = "2026-01-01T00:00:00Z".parse::<DateTime<Utc>>()?;
+        let hour = TimeDelta::hours(1);
+        {
+            let history = PortfolioHistory::open(dir.path(), 30)?;
+            history.record(snapshot("0xAA", start, 1000.0, 0.0))?;
+            history.record(snapshot("0xaa", start + hour, 1010.0, 5.0))?;
+            history.record(snapshot("0xbb", start + hour, 50.0, 0.0))?;
+        }
+        let history = PortfolioHistory::open(dir.path(), 30)?;
+        let snapshots = history.query("0xAA", start);
+        assert_eq!(snapshots.len(), 2);
+
+        let incomes = [
+            // Booked before the first snapshot, so not part of the window
+            income("FUNDING_FEE", "9.0", start),
+            income("REALIZED_PNL", "4.0", start + TimeDelta::minutes(30)),
+            income("FUNDING_FEE", "1.5", start + TimeDelta::minutes(40)),
+            income("COMMISSION", "-0.5", start + TimeDelta::minutes(30)),
+            income("TRANSFER", "100.0", start + TimeDelta::minutes(50)),
+        ];
+        let report = PnlReport::new(&snapshots, &incomes).unwrap();
+        assert_eq!((report.start_usd, report.end_usd), (1000.0, 1010.0));
+        assert_eq!(report.realized_pnl, 4.0);
+        assert_eq!(report.unrealized_pnl, 5.0);
+        assert_eq!(report.funding, 1.5);
+        assert_eq!(report.net_pnl, 10.0);
+        assert_eq!(report.points[0].net_pnl, 0.0);
+        assert!(PnlReport::new(&[], &incomes).is_none());
+
+        // The wallet gaining 20 on-chain adds to the PnL
+        let mut onchain = snapshots.clone();
+        onchain[1].onchain_usd = 20.0;
+        let report = PnlReport::new(&onchain, &incomes).unwrap();
+        assert_eq!(report.onchain_pnl, 20.0);
+        assert_eq!(report.net_pnl, 30.0);
+
+        history.record(snapshot("0xaa", start + TimeDelta::days(31), 1020.0, 0.0))?;
+        assert_eq!(history.query("0xaa", start).len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+use super::binance::AccountInfo;
+use crate::config::MarginHealthConfig;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::fmt;
+
+/// How far one open position is from liquidating the account.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PositionMarginHealth {
+    pub symbol: String,
+    /// Negative for shorts.
+    pub position_amt: f64,
+    pub mark_price: f64,
+    pub maint_margin: f64,
+    /// Share of the margin balance this position's maintenance margin takes.
+    pub maint_margin_usage: f64,
+    /// Price at which this move alone would liquidate the account; `None` when the
+    /// margin balance is already below the maintenance margin.
+    pub liquidation_price: Option<f64>,
+    /// Adverse move to `liquidation_price`, in percent of the mark price.
+    pub distance_to_liquidation_pct: Option<f64>,
+}
+
+/// Margin health of a cross-margin futures account. Binance liquidates once the
+/// margin ratio reaches 1, so each position's distance is the move that would burn
+/// the margin balance above the maintenance margin.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MarginHealth {
+    pub margin_balance: f64,
+    pub maint_margin: f64,
+    /// Maintenance margin over margin balance; `None` without a margin balance.
+    pub margin_ratio: Option<f64>,
+    /// Position and open order initial margin over margin balance.
+    pub initial_margin_usage: Option<f64>,
+    pub positions: Vec<PositionMarginHealth>,
+    /// Set when the account is near liquidation under the configured thresholds.
+    pub warnings: Vec<String>,
+}
+
+impl MarginHealth {
+    pub fn new(account: &AccountInfo, config: &MarginHealthConfig) -> Self {
+        let number = |value: &Decimal| value.to_f64().unwrap_or(0.0);
+        let margin_balance = number(&account.total_margin_balance);
+        let maint_margin = number(&account.total_maint_margin);
+        let initial_margin = number(&account.total_position_initial_margin)
+            + number(&account.total_open_order_initial_margin);
+        let share = |value: f64| (margin_balance > 0.0).then(|| value / margin_balance);
+        let buffer = margin_balance - maint_margin;
+
+        let positions: Vec<PositionMarginHealth> = account
+            .positions
+            .iter()
+            .filter(|position| number(&position.position_amt) != 0.0)
+            .map(|position| {
+                let position_amt = number(&position.position_amt);
+                let mark_price = number(&position.notional) / position_amt;
+                let position_maint = number(&position.maint_margin);
+                // Longs liquidate below the mark, shorts above it
+                let liquidation_price =
+                    (buffer > 0.0).then(|| (mark_price - buffer / position_amt).max(0.0));
+                PositionMarginHealth {
+                    symbol: position.symbol.clone(),
+                    position_amt,
+                    mark_price,
+                    maint_margin: position_maint,
+                    maint_margin_usage: share(position_maint).unwrap_or(0.0),
+                    liquidation_price,
+                    distance_to_liquidation_pct: liquidation_price
+                        .map(|price| (mark_price - price).abs() / mark_price.abs() * 100.0),
+                }
+            })
+            .collect();
+
+        let margin_ratio = share(maint_margin);
+        let mut warnings = Vec::new();
+        match margin_ratio {
+            Some(ratio) if ratio >= config.warn_margin_ratio => warnings.push(format!(
+                "Margin ratio {:.1}% is at or above {:.1}%",
+                ratio * 100.0,
+                config.warn_margin_ratio * 100.0
+            )),
+            None if !positions.is_empty() => {
+                warnings.push("No margin balance left behind open positions".to_string())
+            }
+            _ => {}
+        }
+        for position in &positions {
+            match position.distance_to_liquidation_pct {
+                Some(distance) if distance < config.warn_liquidation_distance_pct => {
+                    warnings.push(format!(
+                        "{} is {:.1}% from liquidation at {:.2}",
+                        position.symbol,
+                        distance,
+                        position.liquidation_price.unwrap_or_default()
+                    ))
+                }
+                None => warnings.push(format!(
+                    "{} is past liquidation: margin balance is below maintenance margin",
+                    position.symbol
+                )),
+                _ => {}
+            }
+        }
+
+        Self {
+            margin_balance,
+            maint_margin,
+            margin_ratio,
+            initial_margin_usage: share(initial_margin),
+            positions,
+            warnings,
+        }
+    }
+}
+
+impl fmt::Display for MarginHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Margin Warnings (account near liquidation):")?;
+        for warning in &self.warnings {
+            writeln!(f, "  {}", warning)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::binance::{test_account, Position};
+
+    fn position(symbol: &str, amount: &str, notional: &str, maint: &str) -> Position {
+        Position {
+            symbol: symbol.to_string(),
+            position_side: "BOTH".to_string(),
+            position_amt: amount.parse().unwrap(),
+            unrealized_profit: Decimal::ZERO,
+            notional: notional.parse().unwrap(),
+            initial_margin: Decimal::ZERO,
+            maint_margin: maint.parse().unwrap(),
+            update_time: 0,
+        }
+    }
+
+    fn account(margin_balance: &str, maint: &str, positions: Vec<Position>) -> AccountInfo {
+        AccountInfo {
+            total_maint_margin: maint.parse().unwrap(),
+            total_position_initial_margin: Decimal::new(200, 0),
+            available_balance: Decimal::ZERO,
+            positions,
+            ..test_account(margin_balance, serde_json::json!([]))
+        }
+    }
+
+    #[test]
+    fn test_margin_health() {
+        let config = MarginHealthConfig::default();
+        let positions = vec![
+            position("BTCUSDT", "0.1", "6000", "24"),
+            position("ETHUSDT", "-2", "-6000", "36"),
+            position("SOLUSDT", "0", "0", "0"),
+        ];
+        let health = MarginHealth::new(&account("1000", "60", positions), &config);
+        assert_eq!(health.margin_ratio, Some(0.06));
+        assert_eq!(health.initial_margin_usage, Some(0.2));
+        assert_eq!(health.positions.len(), 2);
+        // 940 of buffer is 9400 per BTC below the mark and 470 per ETH above it
+        let btc = &health.positions[0];
+        assert_eq!(btc.mark_price, 60000.0);
+        assert_eq!(btc.liquidation_price, Some(50600.0));
+        let eth = &health.positions[1];
+        assert_eq!(eth.liquidation_price, Some(3470.0));
+        assert!((eth.distance_to_liquidation_pct.unwrap() - 15.666).abs() < 0.01);
+        assert!(health.warnings.is_empty());
+
+        let positions = vec![position("BTCUSDT", "0.1", "6000", "24")];
+        let health = MarginHealth::new(&account("100", "60", positions), &config);
+        // Margin ratio 60% and 40 of buffer is a 0.7% move
+        assert_eq!(health.warnings.len(), 2);
+
+        let positions = vec![position("BTCUSDT", "0.1", "6000", "24")];
+        let health = MarginHealth::new(&account("50", "60", positions), &config);
+        assert_eq!(health.positions[0].liquidation_price, None);
+        assert!(health.warnings[1].contains("past liquidation"));
+    }
+}
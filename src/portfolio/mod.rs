@@ -1,2 +1,4 @@
 pub mod binance;
 pub mod eisen;
+pub mod history;
+pub mod margin;
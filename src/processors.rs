@@ -1,86 +1,1127 @@
-use crate::agent::Strategy;
+use crate::agent::{in_stage, BridgeTransfer, EisenSwap, Strategy};
+use crate::config::BridgeConfig;
+use crate::error::ExecutionError;
 use crate::executor;
-use crate::executor::eisen::ChainData;
+use crate::executor::binance::{
+    client_order_id, OrderExecutor, OrderSide, OrderType, PlaceOrder, Status,
+};
+use crate::executor::bridge::BridgeStatus;
+use crate::executor::eisen::{ChainData, SwapAmount, SwapOptions};
+use crate::executor::error::QuoteError;
+use crate::executor::gas;
+use crate::executor::nonce::TxSender;
+use crate::executor::queue::{JobTracker, LegKind, LegState, QueuedLeg};
+use crate::report::{
+    split_outcome, BridgeOutcome, BridgeReport, FailurePolicy, OrderOutcome, OrderReport,
+    SwapOutcome, SwapReport,
+};
+use crate::risk::RiskGate;
 use crate::utils;
-use crate::utils::parser::extract_binance_place_order;
+use crate::utils::parser::{extract_binance_transfers, extract_sequenced_place_orders};
+use alloy::primitives::Address;
 use alloy::providers::Provider;
-use std::error::Error;
+use futures::future::join_all;
+use rust_decimal::prelude::ToPrimitive;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
+// Bridge assets between chains; `providers` and `chain_data` hold an entry per chain involved.
+// Only the transfers of `stage` run, all of them without one.
+// A failed transfer is reported, and ends the transfers unless `policy` is to continue
+#[allow(clippy::too_many_arguments)]
+pub async fn process_bridge_transfers(
+    strategy: &Strategy,
+    stage: Option<u32>,
+    providers: &HashMap<u64, Box<dyn Provider>>,
+    client: &reqwest::Client,
+    config: &BridgeConfig,
+    chain_data: &HashMap<u64, ChainData>,
+    wallet_address: &str,
+    options: &SwapOptions,
+    policy: FailurePolicy,
+) -> Result<Vec<BridgeReport>, ExecutionError> {
+    let wallet_addr = parse_wallet(wallet_address)?;
+    let Some(transfers) = strategy.exchanges.bridges.transfers.as_ref() else {
+        println!("No bridge transfers to execute");
+        return Ok(Vec::new());
+    };
+
+    // Reject invalid amounts before the first transfer is sent
+    let mut amounts = Vec::with_capacity(transfers.len());
+    for (i, transfer) in transfers.iter().enumerate() {
+        if !in_stage(transfer.sequence, stage) {
+            continue;
+        }
+        if transfer.from_chain_id == transfer.to_chain_id {
+            return Err(ExecutionError::InvalidLeg(format!(
+                "Bridge transfer {}: source and destination are both chain {}",
+                i + 1,
+                transfer.from_chain_id
+            )));
+        }
+        amounts.push((
+            i,
+            transfer,
+            SwapAmount::parse(&transfer.amount).map_err(|e| {
+                ExecutionError::InvalidLeg(format!("Bridge transfer {}: {}", i + 1, e))
+            })?,
+        ));
+        println!(
+            "Bridge transfer {}: {} {} from chain {} to {} on chain {}",
+            i + 1,
+            transfer.amount,
+            transfer.token,
+            transfer.from_chain_id,
+            transfer.token_out(),
+            transfer.to_chain_id
+        );
+    }
+
+    let mut reports = Vec::with_capacity(amounts.len());
+    for (i, transfer, amount) in amounts {
+        let tracker = options.tracker.as_ref().map(|t| t.leg(i));
+        if let Some(tracker) = &tracker {
+            tracker.set_state(LegState::Running);
+        }
+        let result = execute_bridge(
+            transfer,
+            amount,
+            providers,
+            client,
+            config,
+            chain_data,
+            wallet_addr,
+            options,
+            tracker.as_ref(),
+        )
+        .await;
+        if let Some(tracker) = &tracker {
+            tracker.set_state(match &result {
+                Ok(report) if matches!(report.outcome, BridgeOutcome::Skipped { .. }) => {
+                    LegState::Skipped
+                }
+                Ok(_) => LegState::Done,
+                Err(_) => LegState::Failed,
+            });
+        }
+        match result {
+            Ok(report) => reports.push(report),
+            Err(e) => {
+                println!("Bridge transfer {} failed: {}", i + 1, e);
+                reports.push(BridgeReport {
+                    from_chain_id: transfer.from_chain_id,
+                    to_chain_id: transfer.to_chain_id,
+                    token: transfer.token.clone(),
+                    token_out: transfer.token_out().to_string(),
+                    amount_in: 0.0,
+                    outcome: BridgeOutcome::Failed {
+                        error: e.to_string(),
+                    },
+                });
+                if policy != FailurePolicy::Continue {
+                    break;
+                }
+            }
+        }
+    }
+
+    Ok(reports)
+}
+
+/// Follow the transfers of `reports` still in flight to their destination together, for
+/// at most `timeout`, recording how they arrived.
+pub async fn await_bridge_arrivals(
+    client: &reqwest::Client,
+    config: &BridgeConfig,
+    chain_data: &HashMap<u64, ChainData>,
+    reports: &mut [BridgeReport],
+    timeout: Duration,
+) {
+    let arrivals = reports.iter_mut().filter_map(|report| {
+        let BridgeOutcome::Executed(receipt) = &mut report.outcome else {
+            return None;
+        };
+        if receipt.delivery != BridgeStatus::Pending {
+            return None;
+        }
+        let (_, to_decimals) = chain_data
+            .get(&report.to_chain_id)?
+            .token(&report.token_out)
+            .ok()?;
+        let (from_chain, to_chain) = (report.from_chain_id, report.to_chain_id);
+        Some(async move {
+            let tx_hash = receipt.tx_hash;
+            if let Err(e) = executor::bridge::await_arrival(
+                client,
+                config,
+                receipt,
+                from_chain,
+                to_chain,
+                to_decimals,
+                timeout,
+            )
+            .await
+            {
+                println!("Failed to follow bridge transfer {}: {}", tx_hash, e);
+            }
+        })
+    });
+    join_all(arrivals).await;
+}
+
+fn parse_wallet(wallet_address: &str) -> Result<Address, ExecutionError> {
+    wallet_address
+        .parse::<Address>()
+        .map_err(|e| ExecutionError::InvalidLeg(format!("Invalid wallet address: {}", e)))
+}
+
+/// Bridge `amount` of the transfer's token, bounded by the live balance.
+#[allow(clippy::too_many_arguments)]
+async fn execute_bridge(
+    transfer: &BridgeTransfer,
+    amount: SwapAmount,
+    providers: &HashMap<u64, Box<dyn Provider>>,
+    client: &reqwest::Client,
+    config: &BridgeConfig,
+    chain_data: &HashMap<u64, ChainData>,
+    wallet_addr: Address,
+    options: &SwapOptions,
+    tracker: Option<&JobTracker>,
+) -> Result<BridgeReport, ExecutionError> {
+    let onchain = |e: anyhow::Error| ExecutionError::Onchain(e.to_string());
+    let provider = providers.get(&transfer.from_chain_id).ok_or_else(|| {
+        ExecutionError::Config(format!("No provider for chain {}", transfer.from_chain_id))
+    })?;
+    let chain_metadata = |chain_id: u64| {
+        chain_data.get(&chain_id).ok_or_else(|| {
+            ExecutionError::Config(format!("No chain metadata for chain {}", chain_id))
+        })
+    };
+    let from_chain = chain_metadata(transfer.from_chain_id)?;
+    let to_chain = chain_metadata(transfer.to_chain_id)?;
+
+    let mut balance = executor::eisen::wallet_balance(
+        provider.as_ref(),
+        from_chain,
+        &transfer.token,
+        wallet_addr,
+    )
+    .await
+    .map_err(onchain)?;
+    if let Some(paper) = &options.paper {
+        balance = paper.balance(transfer.from_chain_id, &transfer.token, balance);
+    }
+    let reserve = if from_chain.is_native(&transfer.token) {
+        options.native_gas_reserve
+    } else {
+        0.0
+    };
+    let amount_in = amount.resolve(balance, reserve);
+    let mut report = BridgeReport {
+        from_chain_id: transfer.from_chain_id,
+        to_chain_id: transfer.to_chain_id,
+        token: transfer.token.clone(),
+        token_out: transfer.token_out().to_string(),
+        amount_in,
+        outcome: BridgeOutcome::Skipped {
+            reason: format!("Wallet holds no {} to bridge", transfer.token),
+        },
+    };
+    if amount_in <= 0.0 {
+        println!("Skipping bridge transfer: no {} to bridge", transfer.token);
+        return Ok(report);
+    }
+
+    let receipt = match &options.paper {
+        Some(paper) => {
+            paper
+                .bridge(
+                    client,
+                    config,
+                    from_chain,
+                    to_chain,
+                    &transfer.token,
+                    transfer.token_out(),
+                    amount_in,
+                    wallet_addr,
+                    options.slippage_bps,
+                )
+                .await
+        }
+        None => {
+            let sender = TxSender {
+                provider: provider.as_ref(),
+                from: wallet_addr,
+                nonces: &options.nonces,
+                fees: gas::estimate_fees(provider.as_ref(), &options.gas)
+                    .await
+                    .map_err(onchain)?,
+                gas: &options.gas,
+                tracker,
+                relay: None,
+            };
+            executor::bridge::bridge(
+                client,
+                config,
+                &sender,
+                from_chain,
+                to_chain,
+                &transfer.token,
+                transfer.token_out(),
+                amount_in,
+                options.slippage_bps,
+                &options.simulation,
+            )
+            .await
+        }
+    };
+    report.outcome = match receipt {
+        Ok(receipt) => {
+            println!("Bridge transfer executed: {:?}", receipt);
+            BridgeOutcome::Executed(receipt)
+        }
+        Err(e) => match e.downcast_ref::<QuoteError>() {
+            Some(quote_error) => {
+                println!("Skipping bridge transfer: {}", quote_error);
+                BridgeOutcome::Skipped {
+                    reason: quote_error.to_string(),
+                }
+            }
+            None => return Err(onchain(e)),
+        },
+    };
+    Ok(report)
+}
+
+// Execute every swap on its own chain; `providers` and `chain_data` hold an entry per swap chain,
+// `relays` one per chain submitting swaps through a private relay. Swaps `risk` blocks are skipped.
+// Only the swaps of `stage` run, all of them without one.
+// A failed swap is reported, and ends the swaps on every chain unless `policy` is to continue
+#[allow(clippy::too_many_arguments)]
 pub async fn process_eisen_swaps(
     strategy: &Strategy,
-    provider: &Box<dyn Provider>,
+    stage: Option<u32>,
+    providers: &HashMap<u64, Box<dyn Provider>>,
+    relays: &HashMap<u64, Box<dyn Provider>>,
+    client: &reqwest::Client,
     base_url: &str,
-    chain_data: &ChainData,
-    wallet_address: &String,
-) -> Result<(), Box<dyn Error>> {
-    let wallet_addr = wallet_address.parse::<alloy::primitives::Address>()?;
+    chain_data: &HashMap<u64, ChainData>,
+    wallet_address: &str,
+    options: &SwapOptions,
+    risk: &RiskGate<'_>,
+    policy: FailurePolicy,
+) -> Result<Vec<SwapReport>, ExecutionError> {
+    let wallet_addr = parse_wallet(wallet_address)?;
 
     if strategy.exchanges.eisen.swaps.is_none() {
         println!("No swaps to execute");
-        return Ok(());
+        return Ok(Vec::new());
     }
 
     let swaps = strategy.exchanges.eisen.swaps.as_ref().unwrap();
 
+    // Reject invalid amounts, tolerances and unknown tokens before the first swap is sent
+    let mut legs = HashMap::with_capacity(swaps.len());
     for (i, swap) in swaps.iter().enumerate() {
+        if !in_stage(swap.sequence, stage) {
+            continue;
+        }
+        let invalid = |e: &dyn std::fmt::Display| {
+            ExecutionError::InvalidLeg(format!("Swap {}: {}", i + 1, e))
+        };
+        if let Some(chain_data) = chain_data.get(&swap.chain_id()) {
+            chain_data
+                .token(&swap.token_in)
+                .and_then(|_| chain_data.token(&swap.token_out))
+                .map_err(|e| invalid(&e))?;
+        }
+        let amount = SwapAmount::parse(&swap.amount).map_err(|e| invalid(&e))?;
+        let slippage_bps = swap.slippage_bps.unwrap_or(options.slippage_bps);
+        legs.insert(
+            i,
+            (
+                amount,
+                executor::eisen::validate_slippage_bps(slippage_bps).map_err(|e| invalid(&e))?,
+            ),
+        );
         println!(
-            "Swap {}: {} -> {} (amount: {})",
+            "Swap {}: {} -> {} (amount: {}, chain: {}, slippage: {} bps)",
             i + 1,
             swap.token_in,
             swap.token_out,
-            swap.amount
+            swap.amount,
+            swap.chain_id(),
+            slippage_bps
         );
     }
 
+    // Swaps on different chains run concurrently, those on one chain one after the other
+    let mut by_chain: Vec<(u64, Vec<usize>)> = Vec::new();
+    for (i, swap) in swaps.iter().enumerate() {
+        if !legs.contains_key(&i) {
+            continue;
+        }
+        match by_chain
+            .iter_mut()
+            .find(|(chain_id, _)| *chain_id == swap.chain_id())
+        {
+            Some((_, indices)) => indices.push(i),
+            None => by_chain.push((swap.chain_id(), vec![i])),
+        }
+    }
+    // Swap legs come after the bridge legs in the job's queue
+    let first_leg = strategy
+        .exchanges
+        .bridges
+        .transfers
+        .as_ref()
+        .map_or(0, Vec::len);
+    let stop = AtomicBool::new(false);
+    let chains = by_chain.iter().map(|(_, indices)| {
+        let legs = &legs;
+        let stop = &stop;
+        async move {
+            let mut reports = Vec::with_capacity(indices.len());
+            for &i in indices {
+                let tracker = options.tracker.as_ref().map(|t| t.leg(first_leg + i));
+                if stop.load(Ordering::SeqCst) {
+                    if let Some(tracker) = &tracker {
+                        tracker.set_state(LegState::Skipped);
+                    }
+                    continue;
+                }
+                if let Some(tracker) = &tracker {
+                    tracker.set_state(LegState::Running);
+                }
+                let (amount, slippage_bps) = legs[&i];
+                let swap_options = SwapOptions {
+                    slippage_bps,
+                    tracker: tracker.clone(),
+                    ..options.clone()
+                };
+                let result = execute_swap(
+                    &swaps[i],
+                    amount,
+                    providers,
+                    relays,
+                    client,
+                    base_url,
+                    chain_data,
+                    wallet_addr,
+                    swap_options,
+                    risk,
+                )
+                .await;
+                if let Some(tracker) = &tracker {
+                    tracker.set_state(match &result {
+                        Ok(report) if matches!(report.outcome, SwapOutcome::Skipped { .. }) => {
+                            LegState::Skipped
+                        }
+                        Ok(_) => LegState::Done,
+                        Err(_) => LegState::Failed,
+                    });
+                }
+                match result {
+                    Ok(report) => reports.push((i, report)),
+                    Err(e) => {
+                        println!("Swap {} failed: {}", i + 1, e);
+                        if policy != FailurePolicy::Continue {
+                            stop.store(true, Ordering::SeqCst);
+                        }
+                        let swap = &swaps[i];
+                        reports.push((
+                            i,
+                            SwapReport {
+                                chain_id: swap.chain_id(),
+                                token_in: swap.token_in.clone(),
+                                token_out: swap.token_out.clone(),
+                                amount_in: 0.0,
+                                outcome: SwapOutcome::Failed {
+                                    error: e.to_string(),
+                                },
+                            },
+                        ));
+                    }
+                }
+            }
+            reports
+        }
+    });
+
+    let mut reports = Vec::with_capacity(legs.len());
+    for chain_reports in join_all(chains).await {
+        reports.extend(chain_reports);
+    }
+    reports.sort_by_key(|(i, _)| *i);
+    Ok(reports.into_iter().map(|(_, report)| report).collect())
+}
+
+/// Swap `amount` of the swap's `token_in`, in tranches when it is large.
+#[allow(clippy::too_many_arguments)]
+async fn execute_swap(
+    swap: &EisenSwap,
+    amount: SwapAmount,
+    providers: &HashMap<u64, Box<dyn Provider>>,
+    relays: &HashMap<u64, Box<dyn Provider>>,
+    client: &reqwest::Client,
+    base_url: &str,
+    chain_data: &HashMap<u64, ChainData>,
+    wallet_addr: Address,
+    mut options: SwapOptions,
+    risk: &RiskGate<'_>,
+) -> Result<SwapReport, ExecutionError> {
+    let chain_id = swap.chain_id();
+    let provider = providers
+        .get(&chain_id)
+        .ok_or_else(|| ExecutionError::Config(format!("No provider for chain {}", chain_id)))?;
+    let chain_data = chain_data.get(&chain_id).ok_or_else(|| {
+        ExecutionError::Config(format!("No chain metadata for chain {}", chain_id))
+    })?;
+
+    // The live balance, not the agent's view of it, bounds what is swapped
+    let mut balance =
+        executor::eisen::wallet_balance(provider.as_ref(), chain_data, &swap.token_in, wallet_addr)
+            .await
+            .map_err(|e| ExecutionError::Onchain(e.to_string()))?;
+    if let Some(paper) = &options.paper {
+        balance = paper.balance(chain_id, &swap.token_in, balance);
+    }
+    let reserve = if chain_data.is_native(&swap.token_in) {
+        options.native_gas_reserve
+    } else {
+        0.0
+    };
+    let amount_in = amount.resolve(balance, reserve);
+    if amount != SwapAmount::Exact(amount_in) {
+        println!(
+            "Swapping {} {} of a {} balance (requested: {})",
+            amount_in, swap.token_in, balance, swap.amount
+        );
+    }
+    if amount_in <= 0.0 {
+        println!(
+            "Skipping swap on chain {}: no {} to swap",
+            chain_id, swap.token_in
+        );
+        return Ok(SwapReport {
+            chain_id,
+            token_in: swap.token_in.clone(),
+            token_out: swap.token_out.clone(),
+            amount_in,
+            outcome: SwapOutcome::Skipped {
+                reason: format!("Wallet holds no {} to swap", swap.token_in),
+            },
+        });
+    }
+    let admission = match risk.admit_swap(&swap.token_in, &swap.token_out, amount_in) {
+        Ok(admission) => admission,
+        Err(violation) => {
+            return Ok(SwapReport {
+                chain_id,
+                token_in: swap.token_in.clone(),
+                token_out: swap.token_out.clone(),
+                amount_in,
+                outcome: SwapOutcome::Skipped {
+                    reason: violation.to_string(),
+                },
+            })
+        }
+    };
+    let dex_filter = swap.dex_filter_or(&options.dex_filters);
+    options.dex_filters.insert(chain_id, dex_filter);
+    let tranches = tranche_amounts(
+        client,
+        base_url,
+        chain_data,
+        &swap.token_in,
+        amount_in,
+        &options,
+    )
+    .await;
+
+    // A leg without an on-chain route is skipped; the rest of the strategy still runs
+    let mut receipts = Vec::with_capacity(tranches.len());
+    let mut stopped = None;
+    for (i, tranche) in tranches.iter().enumerate() {
+        if i > 0 && options.tranches.delay_secs > 0 {
+            tokio::time::sleep(Duration::from_secs(options.tranches.delay_secs)).await;
+        }
+        let receipt = match &options.paper {
+            Some(paper) => {
+                paper
+                    .swap(
+                        client,
+                        base_url,
+                        chain_data,
+                        &swap.token_in,
+                        &swap.token_out,
+                        *tranche,
+                        options.slippage_bps,
+                        &options.dex_filters[&chain_id],
+                    )
+                    .await
+            }
+            None => {
+                executor::eisen::quote_and_send_tx(
+                    provider.as_ref(),
+                    relays.get(&chain_id).map(|relay| relay.as_ref()),
+                    client,
+                    base_url,
+                    chain_data,
+                    &swap.token_in,
+                    &swap.token_out,
+                    *tranche,
+                    &wallet_addr,
+                    &options,
+                )
+                .await
+            }
+        };
+        match receipt {
+            Ok(receipt) => {
+                println!(
+                    "Eisen swap executed on chain {} (tranche {}/{}): {:?}",
+                    chain_id,
+                    i + 1,
+                    tranches.len(),
+                    receipt
+                );
+                receipts.push(receipt);
+            }
+            Err(e) => match e.downcast_ref::<QuoteError>() {
+                Some(quote_error) => {
+                    println!("Skipping swap on chain {}: {}", chain_id, quote_error);
+                    stopped = Some(quote_error.to_string());
+                    break;
+                }
+                None => {
+                    admission.filled(swapped_fraction(&tranches, receipts.len(), amount_in));
+                    return Err(ExecutionError::Onchain(e.to_string()));
+                }
+            },
+        }
+    }
+    admission.filled(swapped_fraction(&tranches, receipts.len(), amount_in));
+
+    let outcome = match (tranches.len(), receipts.pop(), stopped) {
+        (_, None, reason) => SwapOutcome::Skipped {
+            reason: reason.unwrap_or_default(),
+        },
+        (1, Some(receipt), _) => SwapOutcome::Executed(receipt),
+        (_, Some(receipt), stopped) => {
+            receipts.push(receipt);
+            split_outcome(receipts, stopped)
+        }
+    };
+    Ok(SwapReport {
+        chain_id,
+        token_in: swap.token_in.clone(),
+        token_out: swap.token_out.clone(),
+        amount_in,
+        outcome,
+    })
+}
+
+/// Part of a swap of `amount_in` the first `sent` of its `tranches` make up.
+fn swapped_fraction(tranches: &[f64], sent: usize, amount_in: f64) -> f64 {
+    tranches.iter().take(sent).sum::<f64>() / amount_in
+}
+
+/// On-chain legs of a strategy as queued for its job: the bridge transfers, then the
+/// swaps. Within a stage the transfers go one after the other, a chain's first swap waits
+/// for the transfers touching that chain and every later one for the swap before it on
+/// the same chain. Legs with nothing to wait for in their stage wait for the stage before.
+pub fn queued_legs(strategy: &Strategy) -> Vec<QueuedLeg> {
+    let transfers = strategy
+        .exchanges
+        .bridges
+        .transfers
+        .as_deref()
+        .unwrap_or_default();
+    let swaps = strategy
+        .exchanges
+        .eisen
+        .swaps
+        .as_deref()
+        .unwrap_or_default();
+
+    let stages: Vec<u32> = transfers
+        .iter()
+        .map(|t| t.sequence)
+        .chain(swaps.iter().map(|s| s.sequence))
+        .map(|sequence| sequence.unwrap_or_default())
+        .collect();
+    // Legs of the latest stage before `current` that has on-chain legs
+    let stage_before = |current: u32| -> Vec<usize> {
+        let Some(previous) = stages.iter().copied().filter(|s| *s < current).max() else {
+            return Vec::new();
+        };
+        (0..stages.len())
+            .filter(|i| stages[*i] == previous)
+            .collect()
+    };
+
+    let mut legs = Vec::with_capacity(transfers.len() + swaps.len());
+    for (i, transfer) in transfers.iter().enumerate() {
+        let current = stages[i];
+        let depends_on = match (0..i).rev().find(|j| stages[*j] == current) {
+            Some(previous) => vec![previous],
+            None => stage_before(current),
+        };
+        legs.push(QueuedLeg::new(
+            LegKind::Bridge,
+            transfer.from_chain_id,
+            format!(
+                "{} {} to {} on chain {}",
+                transfer.amount,
+                transfer.token,
+                transfer.token_out(),
+                transfer.to_chain_id
+            ),
+            depends_on,
+        ));
+    }
+    let mut last_on_chain: HashMap<(u64, u32), usize> = HashMap::new();
     for swap in swaps {
-        // Call the quote_and_send_tx function from executor/eisen
-        let result = executor::eisen::quote_and_send_tx(
-            provider.as_ref(),
-            base_url,
-            chain_data,
-            &swap.token_in,
-            &swap.token_out,
-            swap.amount.parse::<f64>()?,
-            &wallet_addr,
-            100, // Default slippage of 1% (100 basis points)
-        )
-        .await?;
+        let chain_id = swap.chain_id();
+        let current = stages[legs.len()];
+        let depends_on = match last_on_chain.get(&(chain_id, current)) {
+            Some(previous) => vec![*previous],
+            None => {
+                let bridges: Vec<usize> = transfers
+                    .iter()
+                    .enumerate()
+                    .filter(|(i, t)| {
+                        stages[*i] == current
+                            && (t.from_chain_id == chain_id || t.to_chain_id == chain_id)
+                    })
+                    .map(|(i, _)| i)
+                    .collect();
+                if bridges.is_empty() {
+                    stage_before(current)
+                } else {
+                    bridges
+                }
+            }
+        };
+        last_on_chain.insert((chain_id, current), legs.len());
+        legs.push(QueuedLeg::new(
+            LegKind::Swap,
+            chain_id,
+            format!("{} {} -> {}", swap.amount, swap.token_in, swap.token_out),
+            depends_on,
+        ));
+    }
+    legs
+}
 
-        // Handle the result as needed
-        println!("Eisen swap executed: {:?}", result);
+/// Amounts a swap is executed in: a single one, or `options.tranches.count` tranches when
+/// the swap is worth more than `split_above_native` in the chain's native token.
+async fn tranche_amounts(
+    client: &reqwest::Client,
+    base_url: &str,
+    chain_data: &ChainData,
+    token_in: &str,
+    amount_in: f64,
+    options: &SwapOptions,
+) -> Vec<f64> {
+    let Some(threshold) = options.tranches.split_above_native else {
+        return vec![amount_in];
+    };
+    if options.tranches.count <= 1 {
+        return vec![amount_in];
+    }
+    match executor::eisen::quote_native_value(client, base_url, chain_data, token_in, amount_in)
+        .await
+    {
+        Ok(notional) if notional > threshold => {
+            println!(
+                "Splitting {} {} (worth {} {}) into {} tranches",
+                amount_in,
+                token_in,
+                notional,
+                chain_data.native_symbol(),
+                options.tranches.count
+            );
+            utils::rounding::split_amount(amount_in, options.tranches.count)
+        }
+        Ok(_) => vec![amount_in],
+        Err(e) => {
+            println!(
+                "Could not value {} {} in {}, not splitting it: {}",
+                amount_in,
+                token_in,
+                chain_data.native_symbol(),
+                e
+            );
+            vec![amount_in]
+        }
+    }
+}
+
+// Function to process Binance wallet transfers (e.g. margin top-ups) from the strategy JSON
+pub async fn process_binance_transfers(
+    strategy: &Strategy,
+    client: &reqwest::Client,
+    binance_base_url: &str,
+    binance_key: &utils::sign::BinanceKey,
+) -> Result<(), ExecutionError> {
+    let spot_base_url = executor::binance::spot_base_url(binance_base_url);
+
+    for transfer in extract_binance_transfers(strategy) {
+        let description = format!(
+            "{} {} ({:?})",
+            transfer.amount, transfer.asset, transfer.transfer_type
+        );
+        let tran_id =
+            executor::binance::universal_transfer(client, spot_base_url, binance_key, transfer)
+                .await
+                .map_err(|e| ExecutionError::Binance(format!("Transfer {}: {}", description, e)))?;
+        println!("Binance transfer {} executed: {}", description, tran_id);
     }
 
     Ok(())
 }
 
-// Function to process Binance positions from the strategy JSON
+// Function to process Binance positions from the strategy JSON.
+// Each leg gets a client order id derived from `job_id`, so re-running a job never double-submits.
+// Only the orders of `stage` are placed, all of them without one.
+// Orders `risk` blocks are not placed; a failed order ends the orders unless `policy` is to continue.
 pub async fn process_binance_place_order(
     strategy: &Strategy,
-    binance_base_url: &str,
-    binance_key: &utils::sign::BinanceKey,
-) -> Result<(), Box<dyn Error>> {
-    let binance_orders = extract_binance_place_order(strategy);
+    stage: Option<u32>,
+    order_executor: &dyn OrderExecutor,
+    job_id: &str,
+    risk: &RiskGate<'_>,
+    policy: FailurePolicy,
+) -> Vec<OrderReport> {
+    let binance_orders = extract_sequenced_place_orders(strategy, risk.contracts());
 
-    if binance_orders.is_empty() {
+    // Once per strategy, not per stage
+    if binance_orders.is_empty()
+        && stage.is_none_or(|stage| strategy.stages().first() == Some(&stage))
+    {
         println!("No positions to execute");
     }
 
-    for order in binance_orders {
-        // Call the place_binance_order function
-        let result = executor::binance::place_binance_order(
-            binance_base_url,
-            binance_key,
-            &order.symbol, // Use token directly as symbol is constructed inside the function
-            order.side,
-            order.quantity,
-            order.price,
-            None, // No stop price for now
+    let mut reports = Vec::with_capacity(binance_orders.len());
+    for (leg_index, (sequence, order)) in binance_orders.into_iter().enumerate() {
+        if !in_stage(sequence, stage) {
+            continue;
+        }
+        let outcome = match risk.admit_order(&order) {
+            Err(violation) => OrderOutcome::Skipped {
+                reason: violation.to_string(),
+            },
+            Ok(admission) => {
+                let client_order_id = client_order_id(job_id, leg_index);
+                match order_executor.place_order(&order, &client_order_id).await {
+                    Ok(result) => {
+                        // Only the fill counts towards the day's turnover
+                        let quantity = order.quantity.and_then(|q| q.to_f64()).unwrap_or(0.0);
+                        let executed = result.executed_qty.to_f64().unwrap_or(0.0);
+                        admission.filled(if quantity > 0.0 {
+                            executed / quantity
+                        } else {
+                            0.0
+                        });
+                        if matches!(result.status, Status::New | Status::PartiallyFilled) {
+                            println!("Binance order open, pending its fill: {:?}", result);
+                        } else {
+                            println!("Binance position executed: {:?}", result);
+                        }
+                        OrderOutcome::Placed {
+                            order: Box::new(result),
+                            unwind: None,
+                            unwind_error: None,
+                        }
+                    }
+                    Err(e) => {
+                        println!("Binance order on {} failed: {}", order.symbol, e);
+                        OrderOutcome::Failed {
+                            error: e.to_string(),
+                        }
+                    }
+                }
+            }
+        };
+        let failed = matches!(outcome, OrderOutcome::Failed { .. });
+        reports.push(OrderReport {
+            symbol: order.symbol,
+            side: order.side,
+            quantity: order.quantity,
+            outcome,
+        });
+        if failed && policy != FailurePolicy::Continue {
+            break;
+        }
+    }
+
+    reports
+}
+
+// Close the filled part of every placed order with a reduce-only market order on the other side.
+// Unwind orders get client order ids of their own, so unwinding a job twice closes it once.
+pub async fn unwind_binance_orders(
+    orders: &mut [OrderReport],
+    order_executor: &dyn OrderExecutor,
+    job_id: &str,
+) {
+    let unwind_job_id = format!("{}:unwind", job_id);
+    for (leg_index, report) in orders.iter_mut().enumerate() {
+        let OrderOutcome::Placed {
+            order,
+            unwind,
+            unwind_error,
+        } = &mut report.outcome
+        else {
+            continue;
+        };
+        if order.executed_qty.is_zero() || unwind.is_some() {
+            continue;
+        }
+        let close = PlaceOrder {
+            symbol: order.symbol.clone(),
+            side: match order.side {
+                OrderSide::Buy => OrderSide::Sell,
+                OrderSide::Sell => OrderSide::Buy,
+            },
+            position_side: None,
+            order_type: OrderType::Market,
+            reduce_only: Some(true),
+            quantity: Some(order.executed_qty),
+            price: None,
+            new_client_order_id: None,
+            stop_price: None,
+            close_position: None,
+            activation_price: None,
+            callback_rate: None,
+            // Binance refuses a time in force on market orders
+            time_in_force: None,
+            working_type: None,
+            price_protect: None,
+            new_order_resp_type: None,
+        };
+        let client_order_id = client_order_id(&unwind_job_id, leg_index);
+        match order_executor.place_order(&close, &client_order_id).await {
+            Ok(result) => {
+                println!("Binance order on {} unwound: {:?}", order.symbol, result);
+                *unwind = Some(Box::new(result));
+                *unwind_error = None;
+            }
+            Err(e) => {
+                println!("Failed to unwind Binance order on {}: {}", order.symbol, e);
+                *unwind_error = Some(e.to_string());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ExposureConfig, RiskConfig};
+    use crate::executor::binance::UsdMarginFuturesOrder;
+    use crate::executor::eisen::ChainPortfolio;
+    use crate::portfolio::binance::test_account;
+    use crate::risk::RiskEngine;
+    use crate::types::test_prices;
+    use anyhow::{anyhow, Result};
+    use async_trait::async_trait;
+    use std::sync::Mutex;
+
+    /// Fills every order at 100 except on `failing`, recording what it was sent.
+    struct MockExecutor {
+        failing: &'static str,
+        placed: Mutex<Vec<(String, String)>>,
+    }
+
+    #[async_trait]
+    impl OrderExecutor for MockExecutor {
+        async fn place_order(
+            &self,
+            order: &PlaceOrder,
+            client_order_id: &str,
+        ) -> Result<UsdMarginFuturesOrder> {
+            if order.symbol == self.failing {
+                return Err(anyhow!("Margin is insufficient"));
+            }
+            self.placed
+                .lock()
+                .unwrap()
+                .push((order.symbol.clone(), client_order_id.to_string()));
+            Ok(serde_json::from_value(serde_json::json!({
+                "clientOrderId": client_order_id, "executedQty": order.quantity, "orderId": 1,
+                "avgPrice": "100", "origQty": order.quantity, "price": "0",
+                "reduceOnly": order.reduce_only.unwrap_or_default(), "side": order.side,
+                "positionSide": "BOTH", "status": "FILLED", "stopPrice": "0",
+                "closePosition": false, "symbol": order.symbol, "timeInForce": "GTC",
+                "type": "MARKET", "updateTime": 0, "workingType": "CONTRACT_PRICE",
+                "priceProtect": false
+            }))?)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_order_policies() -> Result<()> {
+        let strategy: Strategy = serde_json::from_value(serde_json::json!({
+            "exchanges": {"binance": {"orders": [
+                {"position": "short", "token": "eth", "amount": "1", "price": "", "side": "SELL"},
+                {"position": "short", "token": "btc", "amount": "1", "price": "", "side": "SELL"},
+                {"position": "long", "token": "sol", "amount": "1", "price": "", "side": "BUY"}
+            ]}, "eisen": {}},
+            "explanations": []
+        }))?;
+        let binance = test_account("0", serde_json::json!([]));
+        let onchain = ChainPortfolio::default();
+        let prices = test_prices();
+        let exposure = ExposureConfig::default();
+        let engine = RiskEngine::new(RiskConfig::default());
+        let gate = engine.gate(&binance, &onchain, &prices, &exposure);
+        let executor = MockExecutor {
+            failing: "BTCUSDT",
+            placed: Mutex::new(Vec::new()),
+        };
+
+        let reports = process_binance_place_order(
+            &strategy,
+            None,
+            &executor,
+            "job",
+            &gate,
+            FailurePolicy::Continue,
+        )
+        .await;
+        assert_eq!(reports.len(), 3);
+        assert!(matches!(reports[1].outcome, OrderOutcome::Failed { .. }));
+        assert!(matches!(reports[2].outcome, OrderOutcome::Placed { .. }));
+
+        let mut reports = process_binance_place_order(
+            &strategy,
+            None,
+            &executor,
+            "job",
+            &gate,
+            FailurePolicy::AbortRemaining,
         )
-        .await?;
+        .await;
+        assert_eq!(reports.len(), 2);
 
-        // Handle the result as needed
-        println!("Binance position executed: {:?}", result);
+        unwind_binance_orders(&mut reports, &executor, "job").await;
+        let OrderOutcome::Placed { unwind, .. } = &reports[0].outcome else {
+            panic!("first order was not placed");
+        };
+        let unwind = unwind.as_ref().unwrap();
+        assert!(matches!(unwind.side, OrderSide::Buy));
+        assert!(unwind.reduce_only);
+        let placed = executor.placed.lock().unwrap();
+        assert_eq!(placed.len(), 4);
+        assert_eq!(placed[3].1, client_order_id("job:unwind", 0));
+        Ok(())
     }
 
-    Ok(())
+    #[test]
+    fn test_queued_legs() -> Result<()> {
+        let strategy: Strategy = serde_json::from_value(serde_json::json!({
+            "exchanges": {
+                "binance": {"orders": []},
+                "bridges": {"transfers": [
+                    {"token": "usdc", "amount": "100", "fromChainId": 8453, "toChainId": 1},
+                    {"token": "usdc", "amount": "50", "tokenOut": "usdt", "fromChainId": 8453, "toChainId": 42161}
+                ]},
+                "eisen": {"swaps": [
+                    {"tokenIn": "usdc", "tokenOut": "weth", "amount": "100", "chainId": 1},
+                    {"tokenIn": "weth", "tokenOut": "usdc", "amount": "1"},
+                    {"tokenIn": "weth", "tokenOut": "wsteth", "amount": "max", "chainId": 1},
+                    {"tokenIn": "usdc", "tokenOut": "weth", "amount": "10", "chainId": 10, "sequence": 1},
+                    {"tokenIn": "weth", "tokenOut": "usdc", "amount": "max", "chainId": 10, "sequence": 1}
+                ]}
+            },
+            "explanations": []
+        }))?;
+
+        let legs = queued_legs(&strategy);
+        let summary: Vec<(LegKind, u64, &str)> = legs
+            .iter()
+            .map(|leg| (leg.kind, leg.chain_id, leg.description.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (LegKind::Bridge, 8453, "100 usdc to usdc on chain 1"),
+                (LegKind::Bridge, 8453, "50 usdc to usdt on chain 42161"),
+                (LegKind::Swap, 1, "100 usdc -> weth"),
+                (LegKind::Swap, 8453, "1 weth -> usdc"),
+                (LegKind::Swap, 1, "max weth -> wsteth"),
+                (LegKind::Swap, 10, "10 usdc -> weth"),
+                (LegKind::Swap, 10, "max weth -> usdc"),
+            ]
+        );
+        // Transfers go one after the other, a chain's first swap waits for the transfers
+        // touching it, later ones for the swap before them and the next stage for this one
+        let depends_on: Vec<&[usize]> = legs.iter().map(|leg| leg.depends_on.as_slice()).collect();
+        assert_eq!(
+            depends_on,
+            vec![&[][..], &[0], &[0], &[0, 1], &[2], &[0, 1, 2, 3, 4], &[5],]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_staged_legs() -> Result<()> {
+        // Sell wstETH first, then bridge and open the short
+        let strategy: Strategy = serde_json::from_value(serde_json::json!({
+            "exchanges": {
+                "binance": {"orders": [
+                    {"position": "long", "token": "btc", "amount": "1", "price": "", "side": "BUY"},
+                    {"position": "short", "token": "eth", "amount": "1", "price": "", "side": "SELL", "sequence": 2}
+                ]},
+                "bridges": {"transfers": [
+                    {"token": "usdc", "amount": "max", "fromChainId": 8453, "toChainId": 1, "sequence": 2}
+                ]},
+                "eisen": {"swaps": [
+                    {"tokenIn": "wsteth", "tokenOut": "usdc", "amount": "1"},
+                    {"tokenIn": "usdc", "tokenOut": "weth", "amount": "max", "chainId": 1, "sequence": 2},
+                    {"tokenIn": "weth", "tokenOut": "usdc", "amount": "1"}
+                ]}
+            },
+            "explanations": []
+        }))?;
+        assert_eq!(strategy.stages(), vec![0, 2]);
+
+        let depends_on: Vec<Vec<usize>> = queued_legs(&strategy)
+            .into_iter()
+            .map(|leg| leg.depends_on)
+            .collect();
+        assert_eq!(depends_on, vec![vec![1, 3], vec![], vec![0], vec![1]]);
+
+        let binance = test_account("0", serde_json::json!([]));
+        let onchain = ChainPortfolio::default();
+        let prices = test_prices();
+        let exposure = ExposureConfig::default();
+        let engine = RiskEngine::new(RiskConfig::default());
+        let gate = engine.gate(&binance, &onchain, &prices, &exposure);
+        let executor = MockExecutor {
+            failing: "",
+            placed: Mutex::new(Vec::new()),
+        };
+        let reports = process_binance_place_order(
+            &strategy,
+            Some(2),
+            &executor,
+            "job",
+            &gate,
+            FailurePolicy::AbortRemaining,
+        )
+        .await;
+        assert_eq!(reports.len(), 1);
+        // Client order ids number the legs of the whole strategy
+        let placed = executor.placed.lock().unwrap();
+        assert_eq!(
+            placed[0],
+            ("ETHUSDT".to_string(), client_order_id("job", 1))
+        );
+        Ok(())
+    }
 }
@@ -0,0 +1,452 @@
+use crate::agent::{
+    BinanceExchange, BinanceOrder, BridgeExchange, EisenExchange, EisenSwap, Exchanges,
+    Explanation, Strategy,
+};
+use crate::config::{AllocationLeg, ExposureConfig, RebalanceConfig};
+use crate::executor::binance::futures_symbol;
+use crate::executor::eisen::ChainPortfolio;
+use crate::plan::preview_amount;
+use crate::portfolio::binance::AccountInfo;
+use crate::types::MarketPrices;
+use crate::utils::rounding::{round_order_quantity, DISPLAY_AMOUNT_DP};
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use serde::Serialize;
+use std::collections::HashMap;
+
+#[derive(Debug, thiserror::Error)]
+pub enum RebalanceError {
+    #[error("No target allocation configured")]
+    NoTarget,
+    #[error("Portfolio has no value to allocate")]
+    EmptyPortfolio,
+    #[error("No USD price for {0}")]
+    MissingPrice(String),
+}
+
+/// How far one leg of the portfolio is from its target.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AllocationGap {
+    pub leg: AllocationLeg,
+    /// USD price of the leg's token the gap is sized with.
+    pub price: f64,
+    pub current_usd: f64,
+    pub target_usd: f64,
+    /// Positive when the leg has to grow (buy), negative when it has to shrink (sell).
+    pub gap_usd: f64,
+}
+
+/// The orders and swaps that move the portfolio onto a target allocation, as a strategy
+/// that executes like an agent's.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RebalancePlan {
+    pub total_usd: f64,
+    pub gaps: Vec<AllocationGap>,
+    pub strategy: Strategy,
+    #[serde(skip)]
+    prices: HashMap<String, f64>,
+    #[serde(skip)]
+    min_trade_usd: f64,
+}
+
+impl RebalancePlan {
+    /// Gaps between `config.target` and the portfolio. Tokens are priced from their
+    /// on-chain value or Binance position, or else as their base asset in `exposure`.
+    pub fn new(
+        config: &RebalanceConfig,
+        exposure: &ExposureConfig,
+        binance: &AccountInfo,
+        onchain: &ChainPortfolio,
+        market: &MarketPrices,
+    ) -> Result<Self, RebalanceError> {
+        if config.target.is_empty() {
+            return Err(RebalanceError::NoTarget);
+        }
+        let total_usd = number(&binance.total_margin_balance) + onchain.total_usd();
+        if total_usd <= 0.0 {
+            return Err(RebalanceError::EmptyPortfolio);
+        }
+
+        let mut prices = HashMap::new();
+        let mut gaps = Vec::new();
+        for leg in &config.target {
+            let price = token_price(leg.token(), exposure, binance, onchain, market)
+                .ok_or_else(|| RebalanceError::MissingPrice(leg.token().to_string()))?;
+            prices.insert(leg.token().to_lowercase(), price);
+            let current_usd = match leg {
+                AllocationLeg::Perp { token, .. } => perp_notional(binance, token),
+                AllocationLeg::Onchain { token, .. } => onchain.balance_of(token) * price,
+            };
+            let target_usd = leg.weight() * total_usd;
+            gaps.push(AllocationGap {
+                leg: leg.clone(),
+                price,
+                current_usd,
+                target_usd,
+                gap_usd: target_usd - current_usd,
+            });
+        }
+        // Swaps spend and receive the funding token
+        let funding_price = token_price(&config.funding_token, exposure, binance, onchain, market);
+
+        let open = |gap: &&AllocationGap| gap.gap_usd.abs() >= config.min_trade_usd;
+        let orders: Vec<BinanceOrder> = gaps
+            .iter()
+            .filter(open)
+            .filter_map(|gap| {
+                let AllocationLeg::Perp { token, weight } = &gap.leg else {
+                    return None;
+                };
+                // Toward zero, so the order never overshoots the gap
+                let quantity = round_order_quantity(
+                    Decimal::from_f64((gap.gap_usd / gap.price).abs()).unwrap_or(Decimal::ZERO),
+                );
+                (!quantity.is_zero()).then(|| BinanceOrder {
+                    position: if *weight < 0.0 { "short" } else { "long" }.to_string(),
+                    token: token.clone(),
+                    amount: format!("{}", quantity.normalize()),
+                    price: String::new(),
+                    side: if gap.gap_usd > 0.0 { "BUY" } else { "SELL" }.to_string(),
+                    quote: None,
+                    sequence: None,
+                    id: None,
+                    depends_on: None,
+                    confidence: None,
+                    priority: None,
+                    post_only: None,
+                })
+            })
+            .collect();
+
+        // Sales first, so their proceeds fund the purchases
+        let mut onchain_gaps: Vec<&AllocationGap> = gaps
+            .iter()
+            .filter(open)
+            .filter(|gap| matches!(gap.leg, AllocationLeg::Onchain { .. }))
+            .filter(|gap| !gap.leg.token().eq_ignore_ascii_case(&config.funding_token))
+            .collect();
+        onchain_gaps.sort_by(|a, b| a.gap_usd.total_cmp(&b.gap_usd));
+        let mut swaps = Vec::new();
+        for gap in onchain_gaps {
+            let token = gap.leg.token().to_string();
+            let (token_in, token_out, amount) = if gap.gap_usd < 0.0 {
+                let amount = -gap.gap_usd / gap.price;
+                (token, config.funding_token.clone(), amount)
+            } else {
+                let funding_price = funding_price
+                    .ok_or_else(|| RebalanceError::MissingPrice(config.funding_token.clone()))?;
+                (
+                    config.funding_token.clone(),
+                    token,
+                    gap.gap_usd / funding_price,
+                )
+            };
+            swaps.push(EisenSwap {
+                token_in,
+                token_out,
+                amount: format!("{:.*}", DISPLAY_AMOUNT_DP, amount),
+                chain_id: None,
+                slippage_bps: None,
+                dex_filter: None,
+                sequence: None,
+                id: None,
+                depends_on: None,
+                confidence: None,
+                priority: None,
+            });
+        }
+        if let Some(funding_price) = funding_price {
+            prices.insert(config.funding_token.to_lowercase(), funding_price);
+        }
+
+        let content = gaps
+            .iter()
+            .map(|gap| {
+                format!(
+                    "{}: ${:.2} -> ${:.2}",
+                    leg_label(&gap.leg),
+                    gap.current_usd,
+                    gap.target_usd
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("; ");
+        let strategy = Strategy {
+            exchanges: Exchanges {
+                binance: BinanceExchange {
+                    orders: Some(orders),
+                    transfers: None,
+                },
+                eisen: EisenExchange { swaps: Some(swaps) },
+                bridges: BridgeExchange::default(),
+            },
+            explanations: vec![Explanation {
+                title: "Rebalance to target allocation".to_string(),
+                content,
+            }],
+            rationale: None,
+        };
+
+        Ok(Self {
+            total_usd,
+            gaps,
+            strategy,
+            prices,
+            min_trade_usd: config.min_trade_usd,
+        })
+    }
+
+    /// Legs `strategy` leaves further from their target than the minimum trade, as
+    /// human readable findings. Perp orders are valued at the leg's price and swaps at
+    /// their input token's.
+    pub fn findings(&self, strategy: &Strategy, onchain: &ChainPortfolio) -> Vec<String> {
+        let mut findings = Vec::new();
+        for gap in &self.gaps {
+            let traded_usd = match &gap.leg {
+                AllocationLeg::Perp { token, .. } => {
+                    let symbol = futures_symbol(token, None);
+                    strategy
+                        .exchanges
+                        .binance
+                        .orders
+                        .iter()
+                        .flatten()
+                        .filter(|order| {
+                            futures_symbol(&order.token, order.quote.as_deref()) == symbol
+                        })
+                        .map(|order| {
+                            let quantity = order.amount.parse::<f64>().unwrap_or(0.0) * gap.price;
+                            if order.side.eq_ignore_ascii_case("SELL") {
+                                -quantity
+                            } else {
+                                quantity
+                            }
+                        })
+                        .sum::<f64>()
+                }
+                AllocationLeg::Onchain { token, .. } => {
+                    let mut traded_usd = 0.0;
+                    for swap in strategy.exchanges.eisen.swaps.iter().flatten() {
+                        let bought = swap.token_out.eq_ignore_ascii_case(token);
+                        if !bought && !swap.token_in.eq_ignore_ascii_case(token) {
+                            continue;
+                        }
+                        let amount = preview_amount(&swap.amount, &swap.token_in, onchain);
+                        let price = self.prices.get(&swap.token_in.to_lowercase());
+                        let (Some(amount), Some(price)) = (amount, price) else {
+                            findings.push(format!(
+                                "Swap {} -> {} cannot be valued against the target",
+                                swap.token_in, swap.token_out
+                            ));
+                            continue;
+                        };
+                        traded_usd += if bought {
+                            amount * price
+                        } else {
+                            -amount * price
+                        };
+                    }
+                    traded_usd
+                }
+            };
+            let residual = gap.gap_usd - traded_usd;
+            if residual.abs() >= self.min_trade_usd {
+                findings.push(format!(
+                    "Strategy leaves ${:.2} of {} to {} to reach its target of ${:.2}",
+                    residual.abs(),
+                    leg_label(&gap.leg),
+                    if residual > 0.0 { "buy" } else { "sell" },
+                    gap.target_usd
+                ));
+            }
+        }
+        findings
+    }
+}
+
+fn leg_label(leg: &AllocationLeg) -> String {
+    match leg {
+        AllocationLeg::Perp { token, .. } => format!("{} perp", futures_symbol(token, None)),
+        AllocationLeg::Onchain { token, .. } => format!("on-chain {}", token),
+    }
+}
+
+fn number(value: &Decimal) -> f64 {
+    value.to_f64().unwrap_or(0.0)
+}
+
+/// Signed notional of the USDT-margined position on `token`.
+fn perp_notional(binance: &AccountInfo, token: &str) -> f64 {
+    let symbol = futures_symbol(token, None);
+    binance
+        .positions
+        .iter()
+        .filter(|position| position.symbol == symbol)
+        .map(|position| number(&position.notional))
+        .sum()
+}
+
+/// USD price of `token`: its on-chain value per unit, the mark of its Binance position,
+/// or the price of its base asset in `exposure` times the token's redemption rate.
+pub fn token_price(
+    token: &str,
+    exposure: &ExposureConfig,
+    binance: &AccountInfo,
+    onchain: &ChainPortfolio,
+    market: &MarketPrices,
+) -> Option<f64> {
+    let held = onchain
+        .balances
+        .iter()
+        .filter(|balance| balance.symbol.eq_ignore_ascii_case(token) && balance.balance > 0.0)
+        .find_map(|balance| Some(balance.usd_value? / balance.balance));
+    let symbol = futures_symbol(token, None);
+    let position = binance
+        .positions
+        .iter()
+        .filter(|position| position.symbol == symbol && number(&position.position_amt) != 0.0)
+        .map(|position| number(&position.notional) / number(&position.position_amt))
+        .next();
+    held.or(position).or_else(|| {
+        let base_price = match exposure.base_asset(token)?.to_uppercase().as_str() {
+            "ETH" => market.eth,
+            "BTC" => market.btc,
+            "USD" => 1.0,
+            _ => return None,
+        };
+        // wstETH redeems for more than one stETH
+        let rate = market
+            .redemption_rates
+            .iter()
+            .find(|(symbol, _)| symbol.eq_ignore_ascii_case(token))
+            .map_or(1.0, |(_, rate)| *rate);
+        Some(base_price * rate)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::eisen::TokenBalance;
+    use crate::portfolio::binance::test_account;
+    use crate::types::test_prices;
+
+    fn onchain(balances: &[(&str, f64, f64)]) -> ChainPortfolio {
+        ChainPortfolio {
+            balances: balances
+                .iter()
+                .map(|(symbol, balance, price)| TokenBalance {
+                    symbol: symbol.to_string(),
+                    balance: *balance,
+                    usd_value: Some(balance * price),
+                })
+                .collect(),
+            positions: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_token_price() {
+        let market = MarketPrices {
+            btc: 60000.0,
+            eth: 2000.0,
+            redemption_rates: HashMap::from([("wstETH".to_string(), 1.2)]),
+        };
+        let exposure = ExposureConfig::default();
+        let binance = test_account("0", serde_json::json!([]));
+        let price = |token: &str, onchain: &ChainPortfolio| {
+            token_price(token, &exposure, &binance, onchain, &market)
+        };
+
+        // Unheld tokens are worth what they redeem for
+        let empty = onchain(&[]);
+        assert_eq!(price("wsteth", &empty), Some(2400.0));
+        assert_eq!(price("steth", &empty), Some(2000.0));
+        assert_eq!(price("usdc", &empty), Some(1.0));
+        assert_eq!(price("pepe", &empty), None);
+        // The wallet's own valuation comes first
+        assert_eq!(
+            price("wsteth", &onchain(&[("wsteth", 0.5, 2350.0)])),
+            Some(2350.0)
+        );
+    }
+
+    #[test]
+    fn test_rebalance_plan_and_findings() -> Result<(), RebalanceError> {
+        let config = RebalanceConfig {
+            target: vec![
+                AllocationLeg::Perp {
+                    token: "eth".to_string(),
+                    weight: -0.5,
+                },
+                AllocationLeg::Onchain {
+                    token: "wsteth".to_string(),
+                    weight: 0.5,
+                },
+            ],
+            ..RebalanceConfig::default()
+        };
+        let market = test_prices();
+        let positions = serde_json::json!([{
+            "symbol": "ETHUSDT", "positionSide": "BOTH", "positionAmt": "-0.2",
+            "unrealizedProfit": "0", "notional": "-400", "initialMargin": "0",
+            "maintMargin": "0", "updateTime": 0
+        }]);
+        let binance = test_account("1000", positions);
+        let onchain = onchain(&[("usdc", 800.0, 1.0), ("wsteth", 0.1, 2400.0)]);
+        let plan = RebalancePlan::new(
+            &config,
+            &ExposureConfig::default(),
+            &binance,
+            &onchain,
+            &market,
+        )?;
+
+        // $2040 in total: a $1020 short and $1020 of wstETH
+        assert_eq!(plan.total_usd, 2040.0);
+        let orders = plan.strategy.exchanges.binance.orders.as_ref().unwrap();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(
+            (orders[0].side.as_str(), orders[0].amount.as_str()),
+            ("SELL", "0.31")
+        );
+        let swaps = plan.strategy.exchanges.eisen.swaps.as_ref().unwrap();
+        assert_eq!(swaps.len(), 1);
+        assert_eq!(
+            (swaps[0].token_in.as_str(), swaps[0].token_out.as_str()),
+            ("usdc", "wsteth")
+        );
+        assert_eq!(swaps[0].amount, "780.000000");
+        assert!(plan.findings(&plan.strategy, &onchain).is_empty());
+
+        let strategy: Strategy = serde_json::from_value(serde_json::json!({
+            "exchanges": {
+                "binance": {
+                    "orders": [{"position": "short", "token": "eth", "amount": "0.1", "price": "", "side": "SELL"}],
+                    "transfers": null
+                },
+                "eisen": {"swaps": [{"tokenIn": "usdc", "tokenOut": "wsteth", "amount": "max"}]}
+            },
+            "explanations": []
+        }))
+        .unwrap();
+        let findings = plan.findings(&strategy, &onchain);
+        assert_eq!(findings.len(), 2);
+        assert!(findings[0].contains("$420.00 of ETHUSDT perp to sell"));
+        assert!(findings[1].contains("$20.00 of on-chain wsteth to sell"));
+
+        assert!(matches!(
+            RebalancePlan::new(
+                &RebalanceConfig::default(),
+                &ExposureConfig::default(),
+                &binance,
+                &onchain,
+                &market
+            ),
+            Err(RebalanceError::NoTarget)
+        ));
+        Ok(())
+    }
+}
@@ -0,0 +1,539 @@
+use crate::executor::binance::{OrderSide, Status, UsdMarginFuturesOrder};
+use crate::executor::bridge::BridgeReceipt;
+use crate::executor::eisen::{SlippageCheck, SwapReceipt};
+use crate::feed::binance_ws::{OrderTradeUpdate, UserDataEvent};
+use crate::risk::RiskViolation;
+use crate::storage::WalStore;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+/// Aggregate of the executed tranches of a split swap.
+pub fn split_outcome(tranches: Vec<SwapReceipt>, stopped: Option<String>) -> SwapOutcome {
+    let amount_out = tranches
+        .iter()
+        .map(|tranche| tranche.amount_out)
+        .try_fold(0.0, |sum, amount| amount.map(|amount| sum + amount));
+    let expected_amount_out = tranches
+        .iter()
+        .fold(0.0, |sum, tranche| sum + tranche.expected_amount_out);
+    let slippage_check = tranches
+        .iter()
+        .map(|tranche| tranche.slippage_check)
+        .max_by_key(|check| match check {
+            SlippageCheck::Passed => 0,
+            SlippageCheck::Unverified => 1,
+            SlippageCheck::Failed => 2,
+        })
+        .unwrap_or(SlippageCheck::Unverified);
+    SwapOutcome::Split {
+        amount_out,
+        expected_amount_out,
+        slippage_check,
+        tranches,
+        stopped,
+    }
+}
+
+/// What a strategy execution actually did, returned alongside the strategy.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExecutionReport {
+    pub job_id: String,
+    pub failure_policy: FailurePolicy,
+    /// Why the legs after a failed one were not executed, if they were not.
+    pub aborted: Option<String>,
+    pub orders: Vec<OrderReport>,
+    pub bridges: Vec<BridgeReport>,
+    pub swaps: Vec<SwapReport>,
+    /// Orders and swaps the risk limits kept from executing.
+    pub risk_violations: Vec<RiskViolation>,
+    /// Trades compensating the exposure failed legs left unhedged.
+    pub hedge: Vec<HedgeReport>,
+    /// Factor the strategy's legs were scaled down by to fit the request's notional
+    /// budget, if they were.
+    #[serde(default)]
+    pub notional_scale: Option<f64>,
+}
+
+impl ExecutionReport {
+    /// Every order the job placed: its orders, their unwinds and the hedge orders.
+    fn placed_orders_mut(&mut self) -> impl Iterator<Item = &mut UsdMarginFuturesOrder> {
+        self.orders
+            .iter_mut()
+            .chain(
+                self.hedge
+                    .iter_mut()
+                    .filter_map(|hedge| hedge.order.as_mut()),
+            )
+            .flat_map(|report| match &mut report.outcome {
+                OrderOutcome::Placed { order, unwind, .. } => {
+                    let mut orders = vec![order.as_mut()];
+                    orders.extend(unwind.as_deref_mut());
+                    orders
+                }
+                _ => Vec::new(),
+            })
+    }
+
+    /// The first failed leg and its error, orders first, then bridges and swaps.
+    pub fn first_failure(&self) -> Option<String> {
+        self.failures().next()
+    }
+
+    /// Every failed leg and its error, in the order of `first_failure`, then the
+    /// failed hedge legs.
+    pub fn failures(&self) -> impl Iterator<Item = String> + '_ {
+        let orders = self.orders.iter().filter_map(order_failure);
+        let bridges = self
+            .bridges
+            .iter()
+            .filter_map(|bridge| match &bridge.outcome {
+                BridgeOutcome::Failed { error } => Some(format!(
+                    "bridge transfer of {} to chain {}: {}",
+                    bridge.token, bridge.to_chain_id, error
+                )),
+                _ => None,
+            });
+        let swaps = self.swaps.iter().filter_map(swap_failure);
+        let hedge = self.hedge.iter().flat_map(|hedge| {
+            let order = hedge.order.iter().filter_map(order_failure);
+            let swaps = hedge.swaps.iter().filter_map(swap_failure);
+            order
+                .chain(swaps)
+                .map(move |failure| format!("hedge of {}: {}", hedge.asset, failure))
+        });
+        orders.chain(bridges).chain(swaps).chain(hedge)
+    }
+}
+
+fn order_failure(order: &OrderReport) -> Option<String> {
+    match &order.outcome {
+        OrderOutcome::Failed { error } => Some(format!("order on {}: {}", order.symbol, error)),
+        _ => None,
+    }
+}
+
+fn swap_failure(swap: &SwapReport) -> Option<String> {
+    match &swap.outcome {
+        SwapOutcome::Failed { error } => Some(format!(
+            "swap {} -> {} on chain {}: {}",
+            swap.token_in, swap.token_out, swap.chain_id, error
+        )),
+        _ => None,
+    }
+}
+
+/// What to do with the rest of a strategy once one of its legs fails.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum FailurePolicy {
+    /// Execute no further legs; those already executed stay.
+    #[default]
+    AbortRemaining,
+    /// Execute every leg regardless.
+    Continue,
+    /// Execute no further legs and close the Binance orders already filled, so a
+    /// failed on-chain leg does not leave its hedge behind.
+    Unwind,
+}
+
+/// Compensation of the exposure to one base asset a partly failed execution left
+/// unmatched: a perp order, or the reversed swaps when the order was not placed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HedgeReport {
+    pub asset: String,
+    /// Signed USD of exposure added back.
+    pub unmatched_usd: f64,
+    pub order: Option<OrderReport>,
+    pub swaps: Vec<SwapReport>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderReport {
+    pub symbol: String,
+    pub side: OrderSide,
+    pub quantity: Option<Decimal>,
+    #[serde(flatten)]
+    pub outcome: OrderOutcome,
+}
+
+impl OrderReport {
+    /// Placed and resting on the book, not filled yet or only in part: pending rather
+    /// than filled until it is.
+    pub fn is_open(&self) -> bool {
+        matches!(
+            &self.outcome,
+            OrderOutcome::Placed { order, .. }
+                if matches!(order.status, Status::New | Status::PartiallyFilled)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum OrderOutcome {
+    Placed {
+        order: Box<UsdMarginFuturesOrder>,
+        /// Reduce-only order closing it, when the job was unwound.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unwind: Option<Box<UsdMarginFuturesOrder>>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        unwind_error: Option<String>,
+    },
+    /// Not placed, e.g. because the risk limits blocked it.
+    Skipped {
+        reason: String,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BridgeReport {
+    pub from_chain_id: u64,
+    pub to_chain_id: u64,
+    pub token: String,
+    pub token_out: String,
+    pub amount_in: f64,
+    #[serde(flatten)]
+    pub outcome: BridgeOutcome,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum BridgeOutcome {
+    Executed(BridgeReceipt),
+    /// Not sent because no bridge route exists or there was nothing to bridge.
+    Skipped {
+        reason: String,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SwapReport {
+    pub chain_id: u64,
+    pub token_in: String,
+    pub token_out: String,
+    pub amount_in: f64,
+    #[serde(flatten)]
+    pub outcome: SwapOutcome,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum SwapOutcome {
+    Executed(SwapReceipt),
+    /// Executed in tranches, with outputs summed over the executed ones.
+    Split {
+        amount_out: Option<f64>,
+        expected_amount_out: f64,
+        slippage_check: SlippageCheck,
+        tranches: Vec<SwapReceipt>,
+        /// Why the remaining tranches were not sent, if any were not.
+        stopped: Option<String>,
+    },
+    /// Not sent because no on-chain route exists.
+    Skipped {
+        reason: String,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// An execution report as persisted, with who it ran for and when.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredReport {
+    pub wallet_address: String,
+    pub executed_at: DateTime<Utc>,
+    pub report: ExecutionReport,
+}
+
+/// Execution reports by job id, persisted in a WAL store so they outlive the
+/// in-memory job queue.
+pub struct ReportStore {
+    reports: WalStore<StoredReport>,
+}
+
+impl ReportStore {
+    pub fn open(data_dir: &Path) -> Result<Self> {
+        Ok(Self {
+            reports: WalStore::open(data_dir, "execution_reports")?,
+        })
+    }
+
+    /// Store `report`, replacing an earlier one of the same job.
+    pub fn record(&self, wallet_address: &str, report: &ExecutionReport) -> Result<()> {
+        self.reports.put(
+            report.job_id.clone(),
+            StoredReport {
+                wallet_address: wallet_address.to_lowercase(),
+                executed_at: Utc::now(),
+                report: report.clone(),
+            },
+        )
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<StoredReport> {
+        self.reports.get(job_id)
+    }
+
+    /// Change the stored report of `job_id` with `update`, if there is one.
+    pub fn update(&self, job_id: &str, update: impl FnOnce(&mut ExecutionReport)) -> Result<()> {
+        let Some(mut stored) = self.reports.get(job_id) else {
+            return Ok(());
+        };
+        update(&mut stored.report);
+        self.reports.put(job_id, stored)
+    }
+
+    /// Apply an order update from the user data stream to the stored order it is for,
+    /// so orders left resting report their fills; returns the job that placed it, `None`
+    /// when no stored report did.
+    pub fn reconcile(&self, update: &OrderTradeUpdate, update_time: u64) -> Result<Option<String>> {
+        let status: Status = serde_json::from_value(update.status.clone().into())?;
+        for (job_id, mut stored) in self.reports.entries() {
+            let Some(order) = stored
+                .report
+                .placed_orders_mut()
+                .find(|order| order.order_id == update.order_id && order.symbol == update.symbol)
+            else {
+                continue;
+            };
+            // Updates can arrive out of order; an older one changes nothing
+            if order.update_time > update_time as i64 {
+                return Ok(Some(job_id));
+            }
+            order.status = status;
+            order.executed_qty = update.filled_qty;
+            if !update.avg_price.is_zero() {
+                order.avg_price = update.avg_price;
+            }
+            order.update_time = update_time as i64;
+            self.reports.put(job_id.clone(), stored)?;
+            return Ok(Some(job_id));
+        }
+        Ok(None)
+    }
+
+    /// Reconcile every order update the user data stream publishes until its channel
+    /// closes.
+    pub fn spawn_reconciler(
+        self: Arc<Self>,
+        mut events: broadcast::Receiver<UserDataEvent>,
+    ) -> JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                match events.recv().await {
+                    Ok(UserDataEvent::OrderTradeUpdate {
+                        transaction_time,
+                        order,
+                        ..
+                    }) => match self.reconcile(&order, transaction_time) {
+                        Ok(Some(job_id)) => println!(
+                            "Order {} of job {} {}: filled {} of {} at {}",
+                            order.order_id,
+                            job_id,
+                            order.status,
+                            order.filled_qty,
+                            order.orig_qty,
+                            order.avg_price
+                        ),
+                        Ok(None) => {}
+                        Err(err) => {
+                            println!("Failed to reconcile order {}: {}", order.order_id, err)
+                        }
+                    },
+                    Ok(_) => {}
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        println!("Order reconciler skipped {} user data event(s)", skipped);
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return,
+                }
+            }
+        })
+    }
+
+    /// The latest `limit` reports of `wallet_address` executed at or after `since`,
+    /// newest first.
+    pub fn recent(
+        &self,
+        wallet_address: &str,
+        since: DateTime<Utc>,
+        limit: usize,
+    ) -> Vec<StoredReport> {
+        let mut reports: Vec<StoredReport> = self
+            .reports
+            .entries()
+            .into_iter()
+            .map(|(_, stored)| stored)
+            .filter(|stored| stored.executed_at >= since)
+            .filter(|stored| stored.wallet_address.eq_ignore_ascii_case(wallet_address))
+            .collect();
+        reports.sort_by_key(|stored| std::cmp::Reverse(stored.executed_at));
+        reports.truncate(limit);
+        reports
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[test]
+    fn test_report_store() -> Result<()> {
+        let dir = tempdir()?;
+        let report = ExecutionReport {
+            job_id: "job-1".to_string(),
+            swaps: vec![SwapReport {
+                chain_id: 8453,
+                token_in: "usdc".to_string(),
+                token_out: "wsteth".to_string(),
+                amount_in: 100.0,
+                outcome: SwapOutcome::Split {
+                    amount_out: Some(0.05),
+                    expected_amount_out: 0.05,
+                    slippage_check: SlippageCheck::Passed,
+                    tranches: vec![SwapReceipt {
+                        tx_hash: Default::default(),
+                        block_number: Some(1),
+                        gas_used: 150_000,
+                        effective_gas_price: 1_000_000,
+                        amount_out: Some(0.05),
+                        expected_amount_out: 0.05,
+                        min_amount_out: 0.0495,
+                        slippage_check: SlippageCheck::Passed,
+                    }],
+                    stopped: None,
+                },
+            }],
+            ..Default::default()
+        };
+        ReportStore::open(dir.path())?.record("0xAA", &report)?;
+
+        let stored = ReportStore::open(dir.path())?.get("job-1").unwrap();
+        assert_eq!(stored.wallet_address, "0xaa");
+        assert_eq!(
+            serde_json::to_value(&stored.report)?,
+            serde_json::to_value(&report)?
+        );
+        assert!(ReportStore::open(dir.path())?.get("job-2").is_none());
+
+        let store = ReportStore::open(dir.path())?;
+        store.record(
+            "0xaa",
+            &ExecutionReport {
+                job_id: "job-2".to_string(),
+                ..Default::default()
+            },
+        )?;
+        store.record("0xbb", &ExecutionReport::default())?;
+        let since = stored.executed_at;
+        let recent: Vec<String> = store
+            .recent("0xAA", since, 5)
+            .into_iter()
+            .map(|stored| stored.report.job_id)
+            .collect();
+        assert_eq!(recent, ["job-2", "job-1"]);
+        assert_eq!(store.recent("0xaa", since, 1)[0].report.job_id, "job-2");
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconcile_order_update() -> Result<()> {
+        let dir = tempdir()?;
+        let store = ReportStore::open(dir.path())?;
+        let order: UsdMarginFuturesOrder = serde_json::from_value(serde_json::json!({
+            "clientOrderId": "x", "executedQty": "0", "orderId": 7, "avgPrice": "0",
+            "origQty": "1", "price": "2000", "reduceOnly": false, "side": "BUY",
+            "positionSide": "BOTH", "status": "NEW", "stopPrice": "0",
+            "closePosition": false, "symbol": "ETHUSDT", "timeInForce": "GTC",
+            "type": "LIMIT", "updateTime": 10, "workingType": "CONTRACT_PRICE",
+            "priceProtect": false
+        }))?;
+        store.record(
+            "0xaa",
+            &ExecutionReport {
+                job_id: "job-1".to_string(),
+                orders: vec![OrderReport {
+                    symbol: "ETHUSDT".to_string(),
+                    side: OrderSide::Buy,
+                    quantity: Some(Decimal::ONE),
+                    outcome: OrderOutcome::Placed {
+                        order: Box::new(order),
+                        unwind: None,
+                        unwind_error: None,
+                    },
+                }],
+                ..Default::default()
+            },
+        )?;
+        let update = |order_id: i64, status: &str, filled: &str| -> OrderTradeUpdate {
+            serde_json::from_value(serde_json::json!({
+                "s": "ETHUSDT", "c": "x", "S": "BUY", "o": "LIMIT", "q": "1", "p": "2000",
+                "ap": "1999.5", "x": "TRADE", "X": status, "i": order_id, "l": filled,
+                "z": filled, "L": "1999.5"
+            }))
+            .unwrap()
+        };
+
+        assert_eq!(
+            store.reconcile(&update(7, "PARTIALLY_FILLED", "0.4"), 20)?,
+            Some("job-1".to_string())
+        );
+        // A stale update does not roll the fill back
+        store.reconcile(&update(7, "NEW", "0"), 15)?;
+        assert_eq!(store.reconcile(&update(8, "FILLED", "1"), 30)?, None);
+
+        let stored = ReportStore::open(dir.path())?.get("job-1").unwrap();
+        let report = &stored.report.orders[0];
+        assert!(report.is_open());
+        let OrderOutcome::Placed { order, .. } = &report.outcome else {
+            panic!("unexpected outcome: {:?}", report.outcome);
+        };
+        assert_eq!(order.status, Status::PartiallyFilled);
+        assert_eq!(order.executed_qty, Decimal::new(4, 1));
+        assert_eq!(order.avg_price, Decimal::new(19995, 1));
+        Ok(())
+    }
+
+    #[test]
+    fn test_failures_include_hedge() {
+        let report = ExecutionReport {
+            hedge: vec![HedgeReport {
+                asset: "ETH".to_string(),
+                unmatched_usd: -1800.0,
+                order: Some(OrderReport {
+                    symbol: "ETHUSDT".to_string(),
+                    side: OrderSide::Sell,
+                    quantity: None,
+                    outcome: OrderOutcome::Failed {
+                        error: "margin is insufficient".to_string(),
+                    },
+                }),
+                swaps: vec![],
+            }],
+            ..Default::default()
+        };
+        assert_eq!(
+            report.first_failure().as_deref(),
+            Some("hedge of ETH: order on ETHUSDT: margin is insufficient")
+        );
+    }
+}
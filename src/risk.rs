@@ -0,0 +1,703 @@
+use crate::config::{ExposureConfig, RiskConfig};
+use crate::executor::binance::{ContractSpecs, FuturesMarket, OrderSide, PlaceOrder};
+use crate::executor::eisen::ChainPortfolio;
+use crate::portfolio::binance::AccountInfo;
+use crate::rebalance::token_price;
+use crate::storage::WalStore;
+use crate::types::MarketPrices;
+use anyhow::Result;
+use chrono::{NaiveDate, Utc};
+use rust_decimal::prelude::ToPrimitive;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// Risk limit a leg broke.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RiskLimit {
+    ForbiddenSymbol,
+    /// The leg could not be valued against the value limits that are set.
+    Unpriced,
+    OrderNotional,
+    Leverage,
+    NetDelta,
+    DailyTurnover,
+    /// A limit price too far from the mark price.
+    PriceDeviation,
+}
+
+/// A leg the risk limits kept from executing.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RiskViolation {
+    /// The order's symbol, or `token_in -> token_out` for a swap.
+    pub leg: String,
+    pub limit: RiskLimit,
+    pub reason: String,
+}
+
+impl fmt::Display for RiskViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} blocked by risk limits: {}", self.leg, self.reason)
+    }
+}
+
+/// USD value of one UTC day: filled, and admitted but not filled yet.
+struct Turnover {
+    day: NaiveDate,
+    filled: f64,
+    reserved: f64,
+}
+
+/// Risk limits and the USD value executed today, shared by every job. Filled turnover
+/// is persisted per day once a store is opened.
+pub struct RiskEngine {
+    config: RiskConfig,
+    turnover: Mutex<Turnover>,
+    store: Option<WalStore<f64>>,
+    contracts: ContractSpecs,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl RiskEngine {
+    pub fn new(config: RiskConfig) -> Self {
+        Self {
+            config,
+            turnover: Mutex::new(Turnover {
+                day: Utc::now().date_naive(),
+                filled: 0.0,
+                reserved: 0.0,
+            }),
+            store: None,
+            contracts: ContractSpecs::new(),
+        }
+    }
+
+    /// Keep the filled turnover of each day in `data_dir`, picking up today's.
+    pub fn with_store(mut self, data_dir: &Path) -> Result<Self> {
+        let store: WalStore<f64> = WalStore::open(data_dir, "risk_turnover")?;
+        {
+            let mut turnover = lock(&self.turnover);
+            turnover.filled = store.get(&turnover.day.to_string()).unwrap_or(0.0);
+        }
+        self.store = Some(store);
+        Ok(self)
+    }
+
+    pub fn contracts(&self) -> &ContractSpecs {
+        &self.contracts
+    }
+
+    /// Value COIN-M orders with the sizes in `contracts`.
+    pub fn with_contracts(mut self, contracts: ContractSpecs) -> Self {
+        self.contracts = contracts;
+        self
+    }
+
+    /// USD value filled since the start of the UTC day.
+    pub fn daily_turnover(&self) -> f64 {
+        self.today().filled
+    }
+
+    /// Today's turnover, started over when the day has changed.
+    fn today(&self) -> MutexGuard<'_, Turnover> {
+        let today = Utc::now().date_naive();
+        let mut turnover = lock(&self.turnover);
+        if turnover.day != today {
+            *turnover = Turnover {
+                day: today,
+                filled: 0.0,
+                reserved: 0.0,
+            };
+        }
+        turnover
+    }
+
+    /// Reserve `usd` of today's turnover unless it breaks the daily limit `max` together
+    /// with what is filled and reserved already; the error is that turnover.
+    fn reserve_turnover(&self, usd: f64, max: Option<f64>) -> Result<(), f64> {
+        let mut turnover = self.today();
+        let taken = turnover.filled + turnover.reserved;
+        match max {
+            Some(max) if taken + usd > max => Err(taken),
+            _ => {
+                turnover.reserved += usd;
+                Ok(())
+            }
+        }
+    }
+
+    /// Release a reservation of `reserved` and count `filled` of it towards today.
+    fn settle_turnover(&self, reserved: f64, filled: f64) {
+        let mut turnover = self.today();
+        turnover.reserved = (turnover.reserved - reserved).max(0.0);
+        if filled <= 0.0 {
+            return;
+        }
+        turnover.filled += filled;
+        if let Some(store) = &self.store {
+            if let Err(e) = store.put(turnover.day.to_string(), turnover.filled) {
+                println!("Failed to persist the daily turnover: {}", e);
+            }
+        }
+    }
+
+    /// Gate for one execution of a strategy made for `binance` and `onchain`.
+    pub fn gate<'a>(
+        &'a self,
+        binance: &'a AccountInfo,
+        onchain: &'a ChainPortfolio,
+        prices: &'a MarketPrices,
+        exposure: &'a ExposureConfig,
+    ) -> RiskGate<'a> {
+        let mut state = GateState::default();
+        for position in &binance.positions {
+            let notional = position.notional.to_f64().unwrap_or(0.0);
+            *state.positions.entry(position.symbol.clone()).or_default() += notional;
+            *state
+                .deltas
+                .entry(base_asset(exposure, symbol_token(&position.symbol)))
+                .or_default() += notional;
+        }
+        for balance in &onchain.balances {
+            *state
+                .deltas
+                .entry(base_asset(exposure, &balance.symbol))
+                .or_default() += balance.usd_value.unwrap_or(0.0);
+        }
+        RiskGate {
+            engine: self,
+            config: self.config.clone(),
+            binance,
+            onchain,
+            prices,
+            exposure,
+            marks: HashMap::new(),
+            state: Mutex::new(state),
+        }
+    }
+}
+
+#[derive(Default)]
+struct GateState {
+    /// Signed USD notional per futures symbol.
+    positions: HashMap<String, f64>,
+    /// Signed USD exposure per base asset.
+    deltas: HashMap<String, f64>,
+    violations: Vec<RiskViolation>,
+}
+
+/// Turnover an admitted leg holds until it is known how much of it filled. Dropped
+/// unsettled, none of it counts.
+#[must_use]
+pub struct Admission<'a> {
+    engine: &'a RiskEngine,
+    notional: f64,
+}
+
+impl Admission<'_> {
+    /// Count the `fraction` of the leg that filled towards today's turnover.
+    pub fn filled(mut self, fraction: f64) {
+        let notional = std::mem::take(&mut self.notional);
+        self.engine
+            .settle_turnover(notional, notional * fraction.clamp(0.0, 1.0));
+    }
+}
+
+impl Drop for Admission<'_> {
+    fn drop(&mut self) {
+        if self.notional > 0.0 {
+            self.engine.settle_turnover(self.notional, 0.0);
+        }
+    }
+}
+
+/// How an admitted leg moves the portfolio, in signed USD.
+#[derive(Default)]
+struct LegMoves {
+    deltas: Vec<(String, f64)>,
+    position: Option<(String, f64)>,
+}
+
+/// Checks the legs of one execution as they are sent. Each leg is checked against the
+/// portfolio the strategy was made for, moved by the legs admitted before it.
+pub struct RiskGate<'a> {
+    engine: &'a RiskEngine,
+    config: RiskConfig,
+    binance: &'a AccountInfo,
+    onchain: &'a ChainPortfolio,
+    prices: &'a MarketPrices,
+    exposure: &'a ExposureConfig,
+    /// Live mark price per token, fetched as the execution starts.
+    marks: HashMap<String, f64>,
+    state: Mutex<GateState>,
+}
+
+impl RiskGate<'_> {
+    /// COIN-M contract specs the legs are sized and valued with.
+    pub fn contracts(&self) -> &ContractSpecs {
+        &self.engine.contracts
+    }
+
+    /// Also hold the execution to `limits`; turnover still counts towards the day.
+    pub fn with_limits(mut self, limits: &RiskConfig) -> Self {
+        self.config = self.config.tightened(limits);
+        self
+    }
+
+    /// Check limit prices against these live mark prices, keyed by token. While a price
+    /// deviation limit is set, a limit order on a token without one is refused.
+    pub fn with_marks(mut self, marks: HashMap<String, f64>) -> Self {
+        self.marks = marks
+            .into_iter()
+            .map(|(token, price)| (token.to_uppercase(), price))
+            .collect();
+        self
+    }
+
+    /// Admit `order`, or record and return why it must not be placed.
+    pub fn admit_order(&self, order: &PlaceOrder) -> Result<Admission<'_>, RiskViolation> {
+        let token = symbol_token(&order.symbol);
+        let quantity = order.quantity.and_then(|q| q.to_f64()).unwrap_or(0.0);
+        let live_mark = self.marks.get(&token.to_uppercase()).copied();
+        let mark_price = live_mark.or_else(|| self.price(token));
+        let notional = order_notional(&order.symbol, quantity, mark_price, self.contracts());
+        let signed = |notional: f64| match order.side {
+            OrderSide::Buy => notional,
+            OrderSide::Sell => -notional,
+        };
+        self.admit(
+            &order.symbol,
+            &[&order.symbol, token],
+            notional,
+            |state, notional| {
+                let limit_price = order.price.and_then(|price| price.to_f64());
+                if let (Some(max), Some(limit_price)) =
+                    (self.config().max_price_deviation_pct, limit_price)
+                {
+                    let Some(mark_price) = live_mark else {
+                        return Err((
+                            RiskLimit::Unpriced,
+                            "no live mark price to check the limit price against".to_string(),
+                        ));
+                    };
+                    let deviation = (limit_price - mark_price).abs() / mark_price * 100.0;
+                    if deviation > max {
+                        return Err((
+                            RiskLimit::PriceDeviation,
+                            format!(
+                                "limit price {} is {:.2}% from the mark price {:.2}, above {:.2}%",
+                                limit_price, deviation, mark_price, max
+                            ),
+                        ));
+                    }
+                }
+                let margin_balance = self.binance.total_margin_balance.to_f64().unwrap_or(0.0);
+                let gross = |positions: &HashMap<String, f64>| -> f64 {
+                    positions.values().map(|notional| notional.abs()).sum()
+                };
+                let before = gross(&state.positions);
+                let mut positions = state.positions.clone();
+                *positions.entry(order.symbol.clone()).or_default() += signed(notional);
+                let after = gross(&positions);
+                if let Some(max) = self.config().max_leverage {
+                    if after > before && after > max * margin_balance {
+                        return Err((
+                            RiskLimit::Leverage,
+                            format!(
+                                "leverage {:.2}x exceeds {:.2}x",
+                                after / margin_balance,
+                                max
+                            ),
+                        ));
+                    }
+                }
+                let deltas = vec![(base_asset(self.exposure, token), signed(notional))];
+                self.check_deltas(state, &deltas)?;
+                Ok(LegMoves {
+                    deltas,
+                    position: Some((order.symbol.clone(), signed(notional))),
+                })
+            },
+        )
+    }
+
+    /// Admit swapping `amount_in` of `token_in` for `token_out`, or record and return
+    /// why it must not be sent.
+    pub fn admit_swap(
+        &self,
+        token_in: &str,
+        token_out: &str,
+        amount_in: f64,
+    ) -> Result<Admission<'_>, RiskViolation> {
+        let leg = format!("{} -> {}", token_in, token_out);
+        let notional = self.price(token_in).map(|price| amount_in * price);
+        self.admit(&leg, &[token_in, token_out], notional, |state, notional| {
+            let deltas = vec![
+                (base_asset(self.exposure, token_in), -notional),
+                (base_asset(self.exposure, token_out), notional),
+            ];
+            self.check_deltas(state, &deltas)?;
+            Ok(LegMoves {
+                deltas,
+                position: None,
+            })
+        })
+    }
+
+    /// Legs blocked so far, in the order they were checked.
+    pub fn violations(&self) -> Vec<RiskViolation> {
+        lock(&self.state).violations.clone()
+    }
+
+    fn config(&self) -> &RiskConfig {
+        &self.config
+    }
+
+    fn price(&self, token: &str) -> Option<f64> {
+        token_price(
+            token,
+            self.exposure,
+            self.binance,
+            self.onchain,
+            self.prices,
+        )
+    }
+
+    /// Checks shared by every leg around `check`, which returns how the leg moves the
+    /// portfolio once its own limits pass. Turnover is reserved last, so a blocked leg
+    /// never holds any.
+    fn admit(
+        &self,
+        leg: &str,
+        symbols: &[&str],
+        notional: Option<f64>,
+        check: impl FnOnce(&GateState, f64) -> Result<LegMoves, (RiskLimit, String)>,
+    ) -> Result<Admission<'_>, RiskViolation> {
+        let mut state = lock(&self.state);
+        let result = (|| {
+            let config = self.config();
+            if let Some(symbol) = symbols.iter().find(|symbol| {
+                config
+                    .forbidden_symbols
+                    .iter()
+                    .any(|forbidden| forbidden.eq_ignore_ascii_case(symbol))
+            }) {
+                return Err((
+                    RiskLimit::ForbiddenSymbol,
+                    format!("{} is forbidden", symbol),
+                ));
+            }
+            let Some(notional) = notional else {
+                let valued = config.max_order_notional_usd.is_some()
+                    || config.max_leverage.is_some()
+                    || config.max_daily_turnover_usd.is_some()
+                    || config.max_net_delta_usd.is_some()
+                    || config.max_price_deviation_pct.is_some();
+                if valued {
+                    return Err((RiskLimit::Unpriced, "no price to value it".to_string()));
+                }
+                return Ok((LegMoves::default(), 0.0));
+            };
+            if let Some(max) = config.max_order_notional_usd {
+                if notional > max {
+                    return Err((
+                        RiskLimit::OrderNotional,
+                        format!("notional ${:.2} exceeds ${:.2}", notional, max),
+                    ));
+                }
+            }
+            let moves = check(&state, notional)?;
+            self.engine
+                .reserve_turnover(notional, config.max_daily_turnover_usd)
+                .map_err(|turnover| {
+                    (
+                        RiskLimit::DailyTurnover,
+                        format!(
+                            "${:.2} on top of ${:.2} today exceeds ${:.2}",
+                            notional,
+                            turnover,
+                            config.max_daily_turnover_usd.unwrap_or_default()
+                        ),
+                    )
+                })?;
+            Ok((moves, notional))
+        })();
+        match result {
+            Ok((moves, notional)) => {
+                for (asset, usd) in moves.deltas {
+                    *state.deltas.entry(asset).or_default() += usd;
+                }
+                if let Some((symbol, usd)) = moves.position {
+                    *state.positions.entry(symbol).or_default() += usd;
+                }
+                Ok(Admission {
+                    engine: self.engine,
+                    notional,
+                })
+            }
+            Err((limit, reason)) => {
+                let violation = RiskViolation {
+                    leg: leg.to_string(),
+                    limit,
+                    reason,
+                };
+                println!("{}", violation);
+                state.violations.push(violation.clone());
+                Err(violation)
+            }
+        }
+    }
+
+    /// Block `moves` when they push a base asset's net exposure past the limit. Dollars
+    /// are not an exposure.
+    fn check_deltas(
+        &self,
+        state: &GateState,
+        moves: &[(String, f64)],
+    ) -> Result<(), (RiskLimit, String)> {
+        let Some(max) = self.config().max_net_delta_usd else {
+            return Ok(());
+        };
+        for (asset, usd) in moves.iter().filter(|(asset, _)| asset != "USD") {
+            let before = state.deltas.get(asset).copied().unwrap_or(0.0);
+            let after = before + usd;
+            if after.abs() > max && after.abs() > before.abs() {
+                return Err((
+                    RiskLimit::NetDelta,
+                    format!("net {} exposure ${:.2} exceeds ${:.2}", asset, after, max),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Base asset `token` is exposure to, or the token itself when it maps to none.
+pub fn base_asset(exposure: &ExposureConfig, token: &str) -> String {
+    exposure.base_asset(token).unwrap_or(token).to_uppercase()
+}
+
+/// Token a futures symbol trades, e.g. ETH for ETHUSDT and BTCUSD_PERP.
+pub fn symbol_token(symbol: &str) -> &str {
+    let symbol = symbol.split('_').next().unwrap_or(symbol);
+    ["USDT", "USDC", "USD"]
+        .iter()
+        .find_map(|quote| symbol.strip_suffix(quote))
+        .unwrap_or(symbol)
+}
+
+/// USD notional of `quantity` on `symbol` at `price`. COIN-M orders are sized in
+/// contracts of a fixed USD value, so they need no price but the contract's size.
+pub fn order_notional(
+    symbol: &str,
+    quantity: f64,
+    price: Option<f64>,
+    contracts: &ContractSpecs,
+) -> Option<f64> {
+    match FuturesMarket::of_symbol(symbol) {
+        FuturesMarket::CoinMargined => {
+            let contract_size = contracts.get(symbol)?.contract_size.to_f64()?;
+            Some(quantity * contract_size)
+        }
+        FuturesMarket::UsdMargined => price.map(|price| quantity * price),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::executor::binance::{ContractSpec, OrderType};
+    use crate::executor::eisen::TokenBalance;
+    use crate::portfolio::binance::test_account;
+    use crate::types::test_prices;
+    use rust_decimal::Decimal;
+
+    fn order(symbol: &str, side: OrderSide, quantity: &str) -> PlaceOrder {
+        PlaceOrder {
+            symbol: symbol.to_string(),
+            side,
+            position_side: None,
+            order_type: OrderType::Market,
+            reduce_only: None,
+            quantity: Some(quantity.parse::<Decimal>().unwrap()),
+            price: None,
+            new_client_order_id: None,
+            stop_price: None,
+            close_position: None,
+            activation_price: None,
+            callback_rate: None,
+            time_in_force: None,
+            working_type: None,
+            price_protect: None,
+            new_order_resp_type: None,
+        }
+    }
+
+    #[test]
+    fn test_risk_gate() {
+        let binance = test_account(
+            "10000",
+            serde_json::json!([{
+                "symbol": "ETHUSDT", "positionSide": "BOTH", "positionAmt": "-10",
+                "unrealizedProfit": "0", "notional": "-20000", "initialMargin": "0",
+                "maintMargin": "0", "updateTime": 0
+            }]),
+        );
+        let onchain = ChainPortfolio {
+            balances: vec![
+                TokenBalance {
+                    symbol: "wsteth".to_string(),
+                    balance: 9.0,
+                    usd_value: Some(18000.0),
+                },
+                TokenBalance {
+                    symbol: "usdc".to_string(),
+                    balance: 50000.0,
+                    usd_value: Some(50000.0),
+                },
+            ],
+            positions: vec![],
+        };
+        let prices = test_prices();
+        let exposure = ExposureConfig::default();
+        let engine = RiskEngine::new(RiskConfig {
+            max_order_notional_usd: Some(15000.0),
+            max_leverage: Some(3.0),
+            max_daily_turnover_usd: Some(30000.0),
+            max_net_delta_usd: Some(15000.0),
+            max_price_deviation_pct: Some(5.0),
+            forbidden_symbols: vec!["PEPE".to_string()],
+        });
+        let gate = engine
+            .gate(&binance, &onchain, &prices, &exposure)
+            .with_marks(HashMap::from([("eth".to_string(), 2000.0)]));
+
+        // Net ETH is -2000: buying 3 ETH takes it to +4000, 6 more to +16000
+        gate.admit_order(&order("ETHUSDT", OrderSide::Buy, "3"))
+            .unwrap()
+            .filled(1.0);
+        let limit = |result: Result<Admission<'_>, RiskViolation>| result.err().unwrap().limit;
+        assert_eq!(
+            limit(gate.admit_order(&order("ETHUSDT", OrderSide::Buy, "6"))),
+            RiskLimit::NetDelta
+        );
+        assert_eq!(
+            limit(gate.admit_order(&order("BTCUSDT", OrderSide::Sell, "0.3"))),
+            RiskLimit::OrderNotional
+        );
+        // 14000 of ETH and 12000 of BTC are 2.6x, another 6000 of BTC 3.2x
+        gate.admit_order(&order("BTCUSDT", OrderSide::Sell, "0.2"))
+            .unwrap()
+            .filled(1.0);
+        assert_eq!(
+            limit(gate.admit_order(&order("BTCUSDT", OrderSide::Sell, "0.1"))),
+            RiskLimit::Leverage
+        );
+        assert_eq!(
+            limit(gate.admit_swap("usdc", "pepe", 100.0)),
+            RiskLimit::ForbiddenSymbol
+        );
+        // Swapping wstETH into dollars only reduces the ETH exposure. Until it fills its
+        // 8000 are held against the daily limit without counting as turnover.
+        let swap = gate.admit_swap("wsteth", "usdc", 4.0).unwrap();
+        assert_eq!(engine.daily_turnover(), 18000.0);
+        assert_eq!(
+            limit(gate.admit_swap("usdc", "wsteth", 5000.0)),
+            RiskLimit::DailyTurnover
+        );
+        swap.filled(1.0);
+        assert_eq!(engine.daily_turnover(), 26000.0);
+        // An order that never fills releases what it held
+        drop(
+            gate.admit_order(&order("ETHUSDT", OrderSide::Buy, "1"))
+                .unwrap(),
+        );
+        gate.admit_order(&order("ETHUSDT", OrderSide::Buy, "1"))
+            .unwrap()
+            .filled(0.5);
+        assert_eq!(engine.daily_turnover(), 27000.0);
+        // Limit prices within 5% of the 2000 mark price
+        let limit_order = |price: &str| PlaceOrder {
+            order_type: OrderType::Limit,
+            price: Some(price.parse::<Decimal>().unwrap()),
+            ..order("ETHUSDT", OrderSide::Sell, "1")
+        };
+        assert_eq!(
+            limit(gate.admit_order(&limit_order("1500"))),
+            RiskLimit::PriceDeviation
+        );
+        assert!(gate.admit_order(&limit_order("2050")).is_ok());
+        // BTC has no live mark for the limit price to be checked against
+        assert_eq!(
+            limit(gate.admit_order(&PlaceOrder {
+                order_type: OrderType::Limit,
+                price: Some(Decimal::from(60000)),
+                ..order("BTCUSDT", OrderSide::Sell, "0.1")
+            })),
+            RiskLimit::Unpriced
+        );
+        assert_eq!(gate.violations().len(), 7);
+    }
+
+    #[test]
+    fn test_turnover_persists() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let binance = test_account("10000", serde_json::json!([]));
+        let onchain = ChainPortfolio::default();
+        let prices = test_prices();
+        let exposure = ExposureConfig::default();
+        let config = RiskConfig {
+            max_daily_turnover_usd: Some(5000.0),
+            ..RiskConfig::default()
+        };
+        {
+            let engine = RiskEngine::new(config.clone()).with_store(dir.path())?;
+            let gate = engine.gate(&binance, &onchain, &prices, &exposure);
+            gate.admit_order(&order("ETHUSDT", OrderSide::Buy, "2"))
+                .unwrap()
+                .filled(1.0);
+        }
+        let engine = RiskEngine::new(config).with_store(dir.path())?;
+        assert_eq!(engine.daily_turnover(), 4000.0);
+        let gate = engine.gate(&binance, &onchain, &prices, &exposure);
+        assert_eq!(
+            gate.admit_order(&order("ETHUSDT", OrderSide::Buy, "1"))
+                .err()
+                .map(|violation| violation.limit),
+            Some(RiskLimit::DailyTurnover)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_coin_margined_notional() {
+        let contracts = ContractSpecs::new();
+        contracts.insert(
+            "BTCUSD_PERP",
+            ContractSpec {
+                contract_size: Decimal::from(100),
+                step_size: Decimal::ONE,
+            },
+        );
+        assert_eq!(
+            order_notional("BTCUSD_PERP", 3.0, None, &contracts),
+            Some(300.0)
+        );
+        // Without its contract size a COIN-M order cannot be valued
+        assert_eq!(
+            order_notional("ETHUSD_PERP", 3.0, Some(2000.0), &contracts),
+            None
+        );
+        assert_eq!(
+            order_notional("ETHUSDT", 3.0, Some(2000.0), &contracts),
+            Some(6000.0)
+        );
+    }
+}
@@ -0,0 +1,443 @@
+use crate::config::RiskConfig;
+use crate::engine::EngineKind;
+use crate::report::FailurePolicy;
+use crate::storage::WalStore;
+use anyhow::Result;
+use chrono::{DateTime, Datelike, Duration, DurationRound, Timelike, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+/// Years searched for the next firing of a cron expression, so one that never fires
+/// (e.g. "0 0 31 2 *") gives up.
+const MAX_CRON_YEARS: i32 = 5;
+
+#[derive(Debug, thiserror::Error)]
+pub enum CronError {
+    #[error("Cron expression {0:?} needs 5 fields: minute hour day month weekday")]
+    Fields(String),
+    #[error("Invalid cron field {field:?}, expected values in {min}-{max}")]
+    Field { field: String, min: u32, max: u32 },
+}
+
+/// Five field cron expression (minute, hour, day of month, month, day of week) in UTC.
+/// Fields take `*`, values, `a-b` ranges, `/n` steps and comma separated lists; weekdays
+/// go from 0 (Sunday) to 7 (Sunday again). Like cron, a restricted day of month and
+/// day of week match when either does.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cron {
+    expr: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Self, CronError> {
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(CronError::Fields(expr.to_string()));
+        };
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        // 7 is Sunday as well
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            expr: fields.join(" "),
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    pub fn matches(&self, at: DateTime<Utc>) -> bool {
+        self.matches_day(at) && bit(self.hours, at.hour()) && bit(self.minutes, at.minute())
+    }
+
+    /// First minute after `after` the expression fires at.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let mut at = after.duration_trunc(Duration::minutes(1)).ok()? + Duration::minutes(1);
+        let last_year = at.year() + MAX_CRON_YEARS;
+        // Skip whole days and hours that cannot match
+        while at.year() <= last_year {
+            if !self.matches_day(at) {
+                at = (at + Duration::days(1))
+                    .duration_trunc(Duration::days(1))
+                    .ok()?;
+            } else if !bit(self.hours, at.hour()) {
+                at = (at + Duration::hours(1))
+                    .duration_trunc(Duration::hours(1))
+                    .ok()?;
+            } else if !bit(self.minutes, at.minute()) {
+                at += Duration::minutes(1);
+            } else {
+                return Some(at);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, at: DateTime<Utc>) -> bool {
+        let day = bit(self.days, at.day());
+        let weekday = bit(self.weekdays, at.weekday().num_days_from_sunday());
+        bit(self.months, at.month())
+            && match (self.any_day, self.any_weekday) {
+                (true, true) => true,
+                (true, false) => weekday,
+                (false, true) => day,
+                (false, false) => day || weekday,
+            }
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.expr)
+    }
+}
+
+impl TryFrom<String> for Cron {
+    type Error = CronError;
+
+    fn try_from(expr: String) -> Result<Self, Self::Error> {
+        Self::parse(&expr)
+    }
+}
+
+impl From<Cron> for String {
+    fn from(cron: Cron) -> Self {
+        cron.expr
+    }
+}
+
+fn bit(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// Values of one cron field as a bit set.
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, CronError> {
+    let invalid = || CronError::Field {
+        field: field.to_string(),
+        min,
+        max,
+    };
+    let mut set = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (item, 1),
+        };
+        let (low, high) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((low, high)) => (
+                low.parse().map_err(|_| invalid())?,
+                high.parse().map_err(|_| invalid())?,
+            ),
+            // "5/15" runs from 5 to the end of the range
+            None if item.contains('/') => (range.parse().map_err(|_| invalid())?, max),
+            None => {
+                let value = range.parse().map_err(|_| invalid())?;
+                (value, value)
+            }
+        };
+        if step == 0 || low < min || high > max || low > high {
+            return Err(invalid());
+        }
+        for value in (low..=high).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+/// A strategy run for `wallet_address` each time `cron` fires. Fields are camelCase like
+/// the rest of the API; schedules stored or configured in snake_case still load.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Schedule {
+    pub id: String,
+    #[serde(alias = "wallet_address")]
+    pub wallet_address: String,
+    pub cron: Cron,
+    #[serde(default)]
+    pub engine: EngineKind,
+    /// LLM provider asked for the strategy; the configured one when unset.
+    #[serde(default)]
+    pub provider: Option<String>,
+    /// Agent model asked for the strategy; the provider's default when unset.
+    pub model: Option<String>,
+    /// Prompt template of the request to a chat provider; the built-in one when unset.
+    #[serde(default, alias = "prompt_template")]
+    pub prompt_template: Option<String>,
+    #[serde(default, alias = "on_failure")]
+    pub on_failure: FailurePolicy,
+    /// Budget the legs of each run are scaled down to, in USD notional.
+    #[serde(default, alias = "max_notional_usd")]
+    pub max_notional_usd: Option<f64>,
+    /// Limits of this schedule's runs, on top of the configured risk limits.
+    #[serde(default)]
+    pub risk: RiskConfig,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+impl Schedule {
+    /// Job id of the run firing at `at`, so a firing never runs twice under two ids.
+    pub fn job_id(&self, at: DateTime<Utc>) -> String {
+        format!("schedule-{}-{}", self.id, at.format("%Y%m%dT%H%MZ"))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum RunStatus {
+    Running,
+    Success,
+    /// A leg failed; see the job's report.
+    Partial,
+    /// The run errored before it had a report.
+    Failed,
+    /// The strategy had a leg below `approval_confidence` and waits, as a previewed
+    /// plan, for confirmation.
+    Held,
+}
+
+/// The latest run of a schedule.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScheduledRun {
+    pub job_id: String,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub status: RunStatus,
+    pub message: String,
+}
+
+/// Schedules, kept across restarts, and how their latest runs went. Runs are kept in
+/// memory; their reports are in the execution store.
+pub struct ScheduleStore {
+    schedules: WalStore<Schedule>,
+    runs: Mutex<HashMap<String, ScheduledRun>>,
+    running: Mutex<HashSet<String>>,
+}
+
+impl ScheduleStore {
+    /// Open the store, replacing stored schedules the config declares again.
+    pub fn open(data_dir: &Path, configured: &[Schedule]) -> Result<Self> {
+        let schedules = WalStore::open(data_dir, "schedules")?;
+        for schedule in configured {
+            schedules.put(schedule.id.clone(), schedule.clone())?;
+        }
+        Ok(Self {
+            schedules,
+            runs: Mutex::new(HashMap::new()),
+            running: Mutex::new(HashSet::new()),
+        })
+    }
+
+    /// Every schedule, by id.
+    pub fn list(&self) -> Vec<Schedule> {
+        let mut schedules: Vec<Schedule> = self
+            .schedules
+            .entries()
+            .into_iter()
+            .map(|(_, schedule)| schedule)
+            .collect();
+        schedules.sort_by(|a, b| a.id.cmp(&b.id));
+        schedules
+    }
+
+    pub fn get(&self, id: &str) -> Option<Schedule> {
+        self.schedules.get(id)
+    }
+
+    /// Store `schedule`, replacing the one with the same id.
+    pub fn put(&self, schedule: Schedule) -> Result<()> {
+        self.schedules.put(schedule.id.clone(), schedule)
+    }
+
+    pub fn remove(&self, id: &str) -> Result<Option<Schedule>> {
+        lock(&self.runs).remove(id);
+        self.schedules.remove(id)
+    }
+
+    /// Enabled schedules firing after `after` and up to `until`, each with its latest
+    /// firing in between; firings missed while the process was down are not caught up.
+    pub fn due(
+        &self,
+        after: DateTime<Utc>,
+        until: DateTime<Utc>,
+    ) -> Vec<(Schedule, DateTime<Utc>)> {
+        self.list()
+            .into_iter()
+            .filter(|schedule| schedule.enabled)
+            .filter_map(|schedule| {
+                let mut fired = None;
+                let mut at = after;
+                while let Some(next) = schedule.cron.next_after(at).filter(|next| *next <= until) {
+                    fired = Some(next);
+                    at = next;
+                }
+                fired.map(|at| (schedule, at))
+            })
+            .collect()
+    }
+
+    /// Mark `schedule` as running from `at` under `job_id`, unless its previous run is
+    /// still going. The run lasts until the returned guard finishes it or is dropped.
+    pub fn start(
+        self: &Arc<Self>,
+        schedule: &str,
+        job_id: &str,
+        at: DateTime<Utc>,
+    ) -> Option<RunGuard> {
+        if !lock(&self.running).insert(schedule.to_string()) {
+            return None;
+        }
+        lock(&self.runs).insert(
+            schedule.to_string(),
+            ScheduledRun {
+                job_id: job_id.to_string(),
+                started_at: at,
+                finished_at: None,
+                status: RunStatus::Running,
+                message: "Running".to_string(),
+            },
+        );
+        Some(RunGuard {
+            store: self.clone(),
+            schedule: schedule.to_string(),
+            finished: false,
+        })
+    }
+
+    fn finish(&self, schedule: &str, status: RunStatus, message: String) {
+        if let Some(run) = lock(&self.runs).get_mut(schedule) {
+            run.finished_at = Some(Utc::now());
+            run.status = status;
+            run.message = message;
+        }
+        lock(&self.running).remove(schedule);
+    }
+
+    pub fn last_run(&self, schedule: &str) -> Option<ScheduledRun> {
+        lock(&self.runs).get(schedule).cloned()
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A schedule's run while it is going. Dropped unfinished, when the run panicked or was
+/// cancelled, it fails the run so the schedule fires again.
+pub struct RunGuard {
+    store: Arc<ScheduleStore>,
+    schedule: String,
+    finished: bool,
+}
+
+impl RunGuard {
+    pub fn finish(mut self, status: RunStatus, message: String) {
+        self.finished = true;
+        self.store.finish(&self.schedule, status, message);
+    }
+}
+
+impl Drop for RunGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.store.finish(
+                &self.schedule,
+                RunStatus::Failed,
+                "Run ended without finishing".to_string(),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use tempfile::tempdir;
+
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        // 2024-01-01 was a Monday
+        Utc.with_ymd_and_hms(2024, 1, day, hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_cron() {
+        let cron = Cron::parse("*/15 9-17 * * 1-5").unwrap();
+        assert!(cron.matches(at(1, 9, 45)));
+        assert!(!cron.matches(at(1, 9, 50)));
+        assert!(!cron.matches(at(6, 10, 0)));
+        assert_eq!(cron.next_after(at(1, 9, 45)), Some(at(1, 10, 0)));
+        assert_eq!(cron.next_after(at(5, 17, 45)), Some(at(8, 9, 0)));
+
+        // Either the day of month or the day of week
+        let cron = Cron::parse("0 0 15 * 0").unwrap();
+        assert_eq!(cron.next_after(at(1, 0, 0)), Some(at(7, 0, 0)));
+        assert_eq!(cron.next_after(at(14, 0, 0)), Some(at(15, 0, 0)));
+
+        assert_eq!(
+            Cron::parse("0 0 31 2 *").unwrap().next_after(at(1, 0, 0)),
+            None
+        );
+        assert!(Cron::parse("* * *").is_err());
+        assert!(Cron::parse("60 * * * *").is_err());
+        assert!(Cron::parse("*/0 * * * *").is_err());
+    }
+
+    #[test]
+    fn test_schedule_store() -> Result<()> {
+        let dir = tempdir()?;
+        let schedule: Schedule = serde_json::from_value(serde_json::json!({
+            "id": "hourly", "wallet_address": "0xabc", "cron": "0 * * * *"
+        }))?;
+        let store = Arc::new(ScheduleStore::open(dir.path(), &[schedule])?);
+        assert!(store.get("hourly").unwrap().enabled);
+
+        // Missed firings coalesce into the latest one
+        let due = store.due(at(1, 9, 30), at(1, 12, 10));
+        assert_eq!(due.len(), 1);
+        assert_eq!(due[0].1, at(1, 12, 0));
+        assert!(store.due(at(1, 12, 0), at(1, 12, 59)).is_empty());
+
+        let job_id = due[0].0.job_id(due[0].1);
+        assert_eq!(job_id, "schedule-hourly-20240101T1200Z");
+        let run = store.start("hourly", &job_id, due[0].1).unwrap();
+        assert!(store.start("hourly", &job_id, due[0].1).is_none());
+        run.finish(RunStatus::Success, "Strategy executed".to_string());
+        assert_eq!(store.last_run("hourly").unwrap().status, RunStatus::Success);
+        // A run that never finishes, e.g. one that panicked, fails and frees the schedule
+        drop(store.start("hourly", &job_id, due[0].1).unwrap());
+        assert_eq!(store.last_run("hourly").unwrap().status, RunStatus::Failed);
+        assert!(store.start("hourly", &job_id, due[0].1).is_some());
+        assert_eq!(
+            serde_json::to_value(store.get("hourly").unwrap())?["walletAddress"],
+            "0xabc"
+        );
+
+        let reopened = ScheduleStore::open(dir.path(), &[])?;
+        assert_eq!(reopened.list().len(), 1);
+        Ok(())
+    }
+}
@@ -0,0 +1,228 @@
+use crate::config::{ExposureConfig, StopLossConfig};
+use crate::executor::binance::{FuturesMarket, OrderSide, OrderType, PlaceOrder};
+use crate::executor::eisen::ChainPortfolio;
+use crate::portfolio::binance::AccountInfo;
+use crate::risk::{base_asset, symbol_token};
+use crate::utils::price::PriceData;
+use crate::utils::rounding::round_order_quantity;
+use rust_decimal::prelude::{FromPrimitive, ToPrimitive};
+use rust_decimal::Decimal;
+use std::collections::HashMap;
+
+/// Which limit a position hit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StopTrigger {
+    Loss,
+    Funding,
+}
+
+/// Part of a Binance position to close and why.
+#[derive(Debug, Clone)]
+pub struct StopLoss {
+    pub symbol: String,
+    /// Opposite to the position.
+    pub side: OrderSide,
+    pub quantity: Decimal,
+    pub trigger: StopTrigger,
+    pub reason: String,
+}
+
+impl StopLoss {
+    /// Reduce-only market order closing `quantity`.
+    pub fn order(&self) -> PlaceOrder {
+        PlaceOrder {
+            symbol: self.symbol.clone(),
+            side: self.side,
+            position_side: None,
+            order_type: OrderType::Market,
+            reduce_only: Some(true),
+            quantity: Some(self.quantity),
+            price: None,
+            new_client_order_id: None,
+            stop_price: None,
+            close_position: None,
+            activation_price: None,
+            callback_rate: None,
+            time_in_force: None,
+            working_type: None,
+            price_protect: None,
+            new_order_resp_type: None,
+        }
+    }
+}
+
+/// USD held on-chain per base asset across `portfolios`.
+pub fn held_usd(exposure: &ExposureConfig, portfolios: &[ChainPortfolio]) -> HashMap<String, f64> {
+    let mut held = HashMap::new();
+    for balance in portfolios.iter().flat_map(|portfolio| &portfolio.balances) {
+        if let Some(usd_value) = balance.usd_value {
+            *held
+                .entry(base_asset(exposure, &balance.symbol))
+                .or_insert(0.0) += usd_value;
+        }
+    }
+    held
+}
+
+/// Positions to cut: those paying at least `max_funding_paid_rate` of funding, and
+/// otherwise those losing `max_loss_pct` or more. A short hedging tokens held on-chain
+/// loses what the tokens gain, so only the short beyond the holdings is closed, and
+/// the holdings stay hedged.
+pub fn plan_stop_losses(
+    config: &StopLossConfig,
+    exposure: &ExposureConfig,
+    binance: &AccountInfo,
+    held_usd: &HashMap<String, f64>,
+    funding: &HashMap<String, PriceData>,
+) -> Vec<StopLoss> {
+    let number = |value: &Decimal| value.to_f64().unwrap_or(0.0);
+    let mut stops = Vec::new();
+    for position in &binance.positions {
+        let amount = number(&position.position_amt);
+        if amount == 0.0 || FuturesMarket::of_symbol(&position.symbol) != FuturesMarket::UsdMargined
+        {
+            continue;
+        }
+        let side = if amount > 0.0 {
+            OrderSide::Sell
+        } else {
+            OrderSide::Buy
+        };
+        let pct = |rate: f64| format!("{:.4}%", rate * 100.0);
+        let notional = number(&position.notional);
+        let hedged = if amount < 0.0 && notional != 0.0 {
+            let asset = base_asset(exposure, symbol_token(&position.symbol));
+            let held = held_usd.get(&asset).copied().unwrap_or(0.0);
+            (held / notional.abs()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let naked = Decimal::from_f64(1.0 - hedged).unwrap_or(Decimal::ZERO);
+        let quantity = round_order_quantity(position.position_amt.abs() * naked);
+        if quantity.is_zero() {
+            continue;
+        }
+
+        // Longs pay positive funding, shorts negative; the running period's rate, as a
+        // flip is what this reacts to
+        let rate = funding
+            .get(&position.symbol)
+            .and_then(|price| price.predicted_funding_rate.or(price.cur_funding_rate));
+        if let Some(rate) = rate {
+            let paid_rate = rate * amount.signum();
+            if config.max_funding_paid_rate > 0.0 && paid_rate >= config.max_funding_paid_rate {
+                stops.push(StopLoss {
+                    symbol: position.symbol.clone(),
+                    side,
+                    quantity,
+                    trigger: StopTrigger::Funding,
+                    reason: format!(
+                        "paying {} funding per period, at or above the {} limit, {:.0}% of it offset on-chain",
+                        pct(paid_rate),
+                        pct(config.max_funding_paid_rate),
+                        hedged * 100.0
+                    ),
+                });
+                continue;
+            }
+        }
+
+        let pnl = number(&position.unrealized_profit);
+        let entry_notional = (notional - pnl).abs();
+        if entry_notional <= 0.0 {
+            continue;
+        }
+        let loss_pct = -pnl / entry_notional * 100.0;
+        if loss_pct < config.max_loss_pct {
+            continue;
+        }
+        stops.push(StopLoss {
+            symbol: position.symbol.clone(),
+            side,
+            quantity,
+            trigger: StopTrigger::Loss,
+            reason: format!(
+                "down {:.2}% on entry, at or above the {:.2}% limit, {:.0}% of it offset on-chain",
+                loss_pct,
+                config.max_loss_pct,
+                hedged * 100.0
+            ),
+        });
+    }
+    stops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::portfolio::binance::test_account;
+
+    fn position(symbol: &str, amount: &str, notional: &str, pnl: &str) -> serde_json::Value {
+        serde_json::json!({
+            "symbol": symbol, "positionSide": "BOTH", "positionAmt": amount,
+            "unrealizedProfit": pnl, "notional": notional, "initialMargin": "0",
+            "maintMargin": "0", "updateTime": 0
+        })
+    }
+
+    fn funding(rate: f64) -> PriceData {
+        serde_json::from_value(serde_json::json!({
+            "timestamp": 0, "market_price": null, "buy_long_price": null,
+            "sell_short_price": null, "cur_funding_rate": rate
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_plan_stop_losses() {
+        let config = StopLossConfig::default();
+        let exposure = ExposureConfig::default();
+        let binance = test_account(
+            "1000",
+            serde_json::json!([
+                // Short 2 ETH entered at $2000, now $2500: down 25%, half of it held on-chain
+                position("ETHUSDT", "-2", "-5000", "-1000"),
+                // Long 0.1 BTC, down 5%
+                position("BTCUSDT", "0.1", "5700", "-300"),
+                // Long 1 SOL, slightly up but paying heavy funding
+                position("SOLUSDT", "1", "150", "5"),
+            ]),
+        );
+        let held = HashMap::from([("ETH".to_string(), 2500.0)]);
+        let funding = HashMap::from([
+            ("BTCUSDT".to_string(), funding(0.0001)),
+            ("SOLUSDT".to_string(), funding(0.002)),
+        ]);
+
+        let stops = plan_stop_losses(&config, &exposure, &binance, &held, &funding);
+        let closed: Vec<(&str, String, Decimal)> = stops
+            .iter()
+            .map(|stop| {
+                (
+                    stop.symbol.as_str(),
+                    format!("{:?}", stop.side),
+                    stop.quantity,
+                )
+            })
+            .collect();
+        assert_eq!(
+            closed,
+            vec![
+                ("ETHUSDT", "Buy".to_string(), Decimal::ONE),
+                ("SOLUSDT", "Sell".to_string(), Decimal::ONE),
+            ]
+        );
+        assert!(stops[1].reason.contains("funding"));
+
+        // Shorts paying funding close beyond their hedge only
+        let short_funding = HashMap::from([("ETHUSDT".to_string(), self::funding(-0.002))]);
+        let stops = plan_stop_losses(&config, &exposure, &binance, &held, &short_funding);
+        assert_eq!(stops[0].trigger, StopTrigger::Funding);
+        assert_eq!(stops[0].quantity, Decimal::ONE);
+
+        // Fully hedged, the short's loss is made up on-chain and its funding too
+        let held = HashMap::from([("ETH".to_string(), 6000.0)]);
+        let stops = plan_stop_losses(&config, &exposure, &binance, &held, &short_funding);
+        assert!(stops.is_empty());
+    }
+}
@@ -0,0 +1,3 @@
+pub mod wal;
+
+pub use wal::WalStore;
@@ -0,0 +1,324 @@
+use anyhow::{anyhow, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+
+/// Number of WAL records after which `put`/`remove` compact the log into a snapshot.
+const DEFAULT_COMPACT_EVERY: usize = 1024;
+
+/// A single write-ahead log record.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "camelCase")]
+enum WalRecord<V> {
+    Put { key: String, value: V },
+    Remove { key: String },
+}
+
+struct WalFile {
+    file: File,
+    records: usize,
+}
+
+/// In-memory key/value state backed by a write-ahead log and periodic snapshots.
+///
+/// Reads are served from memory only. Every mutation is appended to `<name>.wal`
+/// and synced before it is applied in memory, so the state can be rebuilt after a
+/// crash by loading `<name>.snapshot.json` and replaying the log on top of it.
+pub struct WalStore<V> {
+    snapshot_path: PathBuf,
+    wal_path: PathBuf,
+    state: RwLock<HashMap<String, V>>,
+    wal: Mutex<WalFile>,
+    compact_every: usize,
+}
+
+impl<V> WalStore<V>
+where
+    V: Serialize + DeserializeOwned + Clone,
+{
+    /// Open (or create) the store `name` inside `dir`, recovering any persisted state.
+    pub fn open(dir: impl AsRef<Path>, name: &str) -> Result<Self> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir)?;
+        let snapshot_path = dir.join(format!("{}.snapshot.json", name));
+        let wal_path = dir.join(format!("{}.wal", name));
+
+        let mut state: HashMap<String, V> = if snapshot_path.exists() {
+            let file = File::open(&snapshot_path)?;
+            serde_json::from_reader(BufReader::new(file))
+                .map_err(|e| anyhow!("Failed to read snapshot {:?}: {}", snapshot_path, e))?
+        } else {
+            HashMap::new()
+        };
+        let (records, good_len) = replay_wal(&wal_path, &mut state)?;
+
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&wal_path)?;
+        // Cut a torn trailing record, or the next append would extend it into a corrupt one
+        if file.metadata()?.len() > good_len {
+            file.set_len(good_len)?;
+            file.sync_all()?;
+        }
+
+        Ok(Self {
+            snapshot_path,
+            wal_path,
+            state: RwLock::new(state),
+            wal: Mutex::new(WalFile { file, records }),
+            compact_every: DEFAULT_COMPACT_EVERY,
+        })
+    }
+
+    /// Compact the log into a snapshot once it holds `records` entries (0 disables).
+    pub fn with_compact_every(mut self, records: usize) -> Self {
+        self.compact_every = records;
+        self
+    }
+
+    pub fn get(&self, key: &str) -> Option<V> {
+        self.read_state().get(key).cloned()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.read_state().contains_key(key)
+    }
+
+    pub fn len(&self) -> usize {
+        self.read_state().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read_state().is_empty()
+    }
+
+    /// Clone of every entry currently held in memory.
+    pub fn entries(&self) -> Vec<(String, V)> {
+        self.read_state()
+            .iter()
+            .map(|(key, value)| (key.clone(), value.clone()))
+            .collect()
+    }
+
+    pub fn put(&self, key: impl Into<String>, value: V) -> Result<()> {
+        let key = key.into();
+        let record = WalRecord::Put {
+            key: key.clone(),
+            value: value.clone(),
+        };
+        let mut wal = self.append(&record)?;
+        self.write_state().insert(key, value);
+        self.maybe_compact(&mut wal)
+    }
+
+    pub fn remove(&self, key: &str) -> Result<Option<V>> {
+        if !self.contains_key(key) {
+            return Ok(None);
+        }
+        let record: WalRecord<V> = WalRecord::Remove {
+            key: key.to_string(),
+        };
+        let mut wal = self.append(&record)?;
+        let removed = self.write_state().remove(key);
+        self.maybe_compact(&mut wal)?;
+        Ok(removed)
+    }
+
+    /// Write the full in-memory state to the snapshot file and truncate the log.
+    pub fn snapshot(&self) -> Result<()> {
+        let mut wal = self.lock_wal()?;
+        self.compact(&mut wal)
+    }
+
+    fn append(&self, record: &WalRecord<V>) -> Result<std::sync::MutexGuard<'_, WalFile>> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+
+        let mut wal = self.lock_wal()?;
+        wal.file.write_all(&line)?;
+        wal.file.sync_data()?;
+        wal.records += 1;
+        Ok(wal)
+    }
+
+    fn maybe_compact(&self, wal: &mut WalFile) -> Result<()> {
+        if self.compact_every > 0 && wal.records >= self.compact_every {
+            self.compact(wal)?;
+        }
+        Ok(())
+    }
+
+    fn compact(&self, wal: &mut WalFile) -> Result<()> {
+        // Write to a temp file and rename so a crash never leaves a half-written snapshot.
+        let tmp_path = self.snapshot_path.with_extension("json.tmp");
+        {
+            let mut tmp = File::create(&tmp_path)?;
+            serde_json::to_writer(&mut tmp, &*self.read_state())?;
+            tmp.sync_all()?;
+        }
+        fs::rename(&tmp_path, &self.snapshot_path)?;
+
+        wal.file.set_len(0)?;
+        wal.file.sync_all()?;
+        wal.records = 0;
+        Ok(())
+    }
+
+    fn lock_wal(&self) -> Result<std::sync::MutexGuard<'_, WalFile>> {
+        self.wal
+            .lock()
+            .map_err(|_| anyhow!("WAL lock poisoned for {:?}", self.wal_path))
+    }
+
+    fn read_state(&self) -> std::sync::RwLockReadGuard<'_, HashMap<String, V>> {
+        self.state.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write_state(&self) -> std::sync::RwLockWriteGuard<'_, HashMap<String, V>> {
+        self.state.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Apply every complete record in the log to `state`, returning the number replayed
+/// and the length of the log up to the end of the last of them.
+///
+/// A torn trailing line (crash in the middle of an append) is ignored; corruption
+/// anywhere else is reported as an error.
+fn replay_wal<V: DeserializeOwned>(
+    path: &Path,
+    state: &mut HashMap<String, V>,
+) -> Result<(usize, u64)> {
+    if !path.exists() {
+        return Ok((0, 0));
+    }
+    let bytes = fs::read(path)?;
+    let lines: Vec<&[u8]> = bytes.split_inclusive(|byte| *byte == b'\n').collect();
+
+    let mut records = 0;
+    let mut good_len = 0;
+    for (i, line) in lines.iter().enumerate() {
+        let last = i == lines.len() - 1;
+        // A record is appended with its newline, so one without was never synced
+        if last && !line.ends_with(b"\n") {
+            println!("Ignoring torn trailing record in {:?}", path);
+            break;
+        }
+        let text = String::from_utf8_lossy(line);
+        if text.trim().is_empty() {
+            good_len += line.len() as u64;
+            continue;
+        }
+        match serde_json::from_str::<WalRecord<V>>(&text) {
+            Ok(WalRecord::Put { key, value }) => {
+                state.insert(key, value);
+            }
+            Ok(WalRecord::Remove { key }) => {
+                state.remove(&key);
+            }
+            Err(_) if last => {
+                println!("Ignoring torn trailing record in {:?}", path);
+                break;
+            }
+            Err(e) => return Err(anyhow!("Corrupt WAL record at {:?}:{}: {}", path, i + 1, e)),
+        }
+        records += 1;
+        good_len += line.len() as u64;
+    }
+    Ok((records, good_len))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Reservation {
+        amount: f64,
+        symbol: String,
+    }
+
+    fn reservation(symbol: &str, amount: f64) -> Reservation {
+        Reservation {
+            amount,
+            symbol: symbol.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_recovers_from_wal() -> Result<()> {
+        let dir = tempdir()?;
+        {
+            let store = WalStore::open(dir.path(), "reservations")?;
+            store.put("a", reservation("ETH", 1.0))?;
+            store.put("b", reservation("BTC", 0.1))?;
+            store.put("a", reservation("ETH", 2.0))?;
+            assert_eq!(store.remove("b")?, Some(reservation("BTC", 0.1)));
+        }
+
+        let store: WalStore<Reservation> = WalStore::open(dir.path(), "reservations")?;
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.get("a"), Some(reservation("ETH", 2.0)));
+        assert!(store.get("b").is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_snapshot_truncates_wal() -> Result<()> {
+        let dir = tempdir()?;
+        {
+            let store = WalStore::open(dir.path(), "positions")?.with_compact_every(2);
+            store.put("a", reservation("ETH", 1.0))?;
+            store.put("b", reservation("BTC", 0.1))?;
+            store.put("c", reservation("SOL", 3.0))?;
+        }
+        assert!(dir.path().join("positions.snapshot.json").exists());
+        let wal = fs::read_to_string(dir.path().join("positions.wal"))?;
+        assert_eq!(wal.lines().count(), 1);
+
+        let store: WalStore<Reservation> = WalStore::open(dir.path(), "positions")?;
+        assert_eq!(store.len(), 3);
+        assert_eq!(store.get("c"), Some(reservation("SOL", 3.0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_ignores_torn_trailing_record() -> Result<()> {
+        let dir = tempdir()?;
+        {
+            let store = WalStore::open(dir.path(), "breakers")?;
+            store.put("othentic", reservation("open", 1.0))?;
+        }
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.path().join("breakers.wal"))?;
+        file.write_all(b"{\"op\":\"put\",\"key\":\"half")?;
+
+        let store: WalStore<Reservation> = WalStore::open(dir.path(), "breakers")?;
+        assert_eq!(store.get("othentic"), Some(reservation("open", 1.0)));
+        assert_eq!(store.len(), 1);
+
+        // The torn record is cut, so records appended after it replay
+        store.put("openai", reservation("closed", 0.0))?;
+        drop(store);
+        let store: WalStore<Reservation> = WalStore::open(dir.path(), "breakers")?;
+        assert_eq!(store.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn test_rejects_corrupt_middle_record() -> Result<()> {
+        let dir = tempdir()?;
+        fs::write(
+            dir.path().join("bad.wal"),
+            "not json\n{\"op\":\"remove\",\"key\":\"a\"}\n",
+        )?;
+        assert!(WalStore::<Reservation>::open(dir.path(), "bad").is_err());
+        Ok(())
+    }
+}
@@ -1,23 +1,152 @@
+use crate::agent::usage::UsageLedger;
+use crate::agent::AgentFactory;
+use crate::audit::AuditLog;
+use crate::config::Config;
+use crate::engine::prompt::PromptTemplates;
+use crate::engine::CircuitBreakers;
+use crate::executor::chains::{ChainMetadataCache, ChainRegistry};
+use crate::executor::nonce::NonceManager;
+use crate::executor::paper::{PaperBinanceExecutor, PaperOnchain};
+use crate::executor::queue::TxQueue;
+use crate::feed::binance::BinanceIndicators;
+use crate::feed::binance_market_ws::PriceCache;
+use crate::feed::binance_ws::UserDataEvent;
+use crate::feed::history::MarketHistory;
+use crate::feed::service::FeedHandle;
+use crate::notify::Notifier;
+use crate::plan::PlanStore;
+use crate::portfolio::history::PortfolioHistory;
+use crate::report::ReportStore;
+use crate::risk::RiskEngine;
+use crate::scheduler::ScheduleStore;
+use crate::utils::rounding::DISPLAY_PRICE_DP;
+use crate::utils::sign::BinanceKey;
+use crate::yields::YieldCache;
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast;
 // Application state that will be shared between handlers
 #[derive(Clone)]
 pub struct AppState {
     pub binance_base_url: String,
-    pub binance_api_key: String,
-    pub binance_api_secret: String,
+    pub binance_key: BinanceKey,
     pub eisen_base_url: String,
     pub reqwest_cli: reqwest::Client,
+    // Order and account updates from the Binance user data stream
+    pub user_events: broadcast::Sender<UserDataEvent>,
+    // Mark prices, book tickers and klines from the Binance market data stream
+    pub prices: PriceCache,
+    // Latest candle and indicators of each market data symbol, from the feed pipeline
+    pub indicator_feeds: HashMap<String, FeedHandle<BinanceIndicators>>,
+    // Candles and prices collected by the feeds, for backtests and post-mortems
+    pub market_history: Arc<MarketHistory>,
+    // Snapshots of wallet portfolios, for history and PnL
+    pub portfolio_history: Arc<PortfolioHistory>,
+    // Previewed strategies waiting for confirmation
+    pub plans: PlanStore,
+    // Set when paper trading is enabled; Binance orders are then simulated
+    pub paper_executor: Option<Arc<PaperBinanceExecutor>>,
+    // Set along with it; swaps and bridge transfers are then simulated as well
+    pub paper_onchain: Option<Arc<PaperOnchain>>,
+    // RPC endpoints per chain id for routing Eisen swaps
+    pub chains: ChainRegistry,
+    // Eisen chain metadata, cached per chain id
+    pub chain_metadata: ChainMetadataCache,
+    // Nonces handed out per chain and signer, shared by concurrent jobs
+    pub nonces: Arc<NonceManager>,
+    // On-chain legs of recent jobs and their transactions
+    pub tx_queue: TxQueue,
+    // APRs of the configured yield sources, refreshed in the background
+    pub yields: YieldCache,
+    // Reports of executed jobs
+    pub reports: Arc<ReportStore>,
+    // Risk limits gating every execution, and today's turnover
+    pub risk: Arc<RiskEngine>,
+    // Scheduled strategy runs and how their latest runs went
+    pub schedules: Arc<ScheduleStore>,
+    // Kill switch: while set, nothing is executed and running jobs stop at their next stage
+    pub paused: Arc<TradingPause>,
+    // Pushes execution, margin and transaction events to the configured channels
+    pub notifier: Notifier,
+    // Chat agents of the configured LLM providers
+    pub agents: Arc<AgentFactory>,
+    // Tokens and estimated cost of the chat agents' calls, per day and API key
+    pub agent_usage: Arc<UsageLedger>,
+    // Circuit breaker of each LLM provider, open while the provider is down
+    pub agent_breakers: Arc<CircuitBreakers>,
+    // Prompt templates of the chat agents, by name
+    pub prompts: Arc<PromptTemplates>,
+    // Append-only record of every signed Binance request and broadcast transaction
+    pub audit: Arc<AuditLog>,
+    pub config: Arc<Config>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// Kill switch, kept as a marker file in the data directory so a pause outlives a
+/// restart and holds for one-shot CLI runs as well.
+#[derive(Debug)]
+pub struct TradingPause {
+    paused: AtomicBool,
+    marker: PathBuf,
+}
+
+impl TradingPause {
+    pub fn open(data_dir: &Path) -> io::Result<Self> {
+        fs::create_dir_all(data_dir)?;
+        let marker = data_dir.join("trading_paused");
+        Ok(Self {
+            paused: AtomicBool::new(marker.exists()),
+            marker,
+        })
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Pause or resume trading, returning whether it was paused.
+    pub fn set(&self, paused: bool) -> io::Result<bool> {
+        if paused {
+            fs::write(&self.marker, chrono::Utc::now().to_rfc3339())?;
+        } else if let Err(e) = fs::remove_file(&self.marker) {
+            if e.kind() != io::ErrorKind::NotFound {
+                return Err(e);
+            }
+        }
+        Ok(self.paused.swap(paused, Ordering::SeqCst))
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
 pub struct MarketPrices {
     pub btc: f64,
     pub eth: f64,
+    /// Base asset one yield-bearing token (e.g. wstETH) redeems for, by symbol.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub redemption_rates: HashMap<String, f64>,
+}
+
+/// BTC at $60000 and ETH at $2000.
+#[cfg(test)]
+pub(crate) fn test_prices() -> MarketPrices {
+    MarketPrices {
+        btc: 60000.0,
+        eth: 2000.0,
+        ..Default::default()
+    }
 }
 
 impl fmt::Display for MarketPrices {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "BTC: ${:.2}, ETH: ${:.2}", self.btc, self.eth)
+        write!(
+            f,
+            "BTC: ${:.*}, ETH: ${:.*}",
+            DISPLAY_PRICE_DP, self.btc, DISPLAY_PRICE_DP, self.eth
+        )
     }
 }
@@ -7,15 +7,15 @@ pub fn format_binance_portfolio(account_info: &AccountInfo) -> String {
     output.push_str("Binance Portfolio Summary:\n");
     output.push_str(&format!(
         "Wallet Balance: {}\n",
-        account_info.total_wallet_balance
+        account_info.total_wallet_balance.normalize()
     ));
     output.push_str(&format!(
         "Unrealized Profit: {}\n",
-        account_info.total_unrealized_profit
+        account_info.total_unrealized_profit.normalize()
     ));
     output.push_str(&format!(
         "Margin Balance: {}\n",
-        account_info.total_margin_balance
+        account_info.total_margin_balance.normalize()
     ));
 
     // Format assets
@@ -26,7 +26,7 @@ pub fn format_binance_portfolio(account_info: &AccountInfo) -> String {
                 "  Asset {}: {} - Balance: {}\n",
                 i + 1,
                 asset.asset,
-                asset.wallet_balance
+                asset.wallet_balance.normalize()
             ));
         }
         if account_info.assets.len() > 5 {
@@ -37,11 +37,36 @@ pub fn format_binance_portfolio(account_info: &AccountInfo) -> String {
         }
     }
 
+    if !account_info.spot_balances.is_empty() {
+        output.push_str("\nSpot Balances:\n");
+        for balance in &account_info.spot_balances {
+            output.push_str(&format!(
+                "  {} - Free: {}, Locked: {}\n",
+                balance.asset,
+                balance.free.normalize(),
+                balance.locked.normalize()
+            ));
+        }
+    }
+
+    if !account_info.margin_assets.is_empty() {
+        output.push_str("\nCross Margin Assets:\n");
+        for asset in &account_info.margin_assets {
+            output.push_str(&format!(
+                "  {} - Net: {}, Borrowed: {}, Interest: {}\n",
+                asset.asset,
+                asset.net_asset.normalize(),
+                asset.borrowed.normalize(),
+                asset.interest.normalize()
+            ));
+        }
+    }
+
     // Format positions
     let active_positions: Vec<_> = account_info
         .positions
         .iter()
-        .filter(|p| p.position_amt != "0")
+        .filter(|p| !p.position_amt.is_zero())
         .collect();
 
     if !active_positions.is_empty() {
@@ -51,8 +76,8 @@ pub fn format_binance_portfolio(account_info: &AccountInfo) -> String {
                 "  Position {}: {} - Amount: {}, Unrealized PnL: {}\n",
                 i + 1,
                 position.symbol,
-                position.position_amt,
-                position.unrealized_profit
+                position.position_amt.normalize(),
+                position.unrealized_profit.normalize()
             ));
         }
         if active_positions.len() > 5 {
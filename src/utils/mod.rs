@@ -2,4 +2,5 @@ pub mod format;
 pub mod parser;
 pub mod price;
 pub mod price_data;
+pub mod rounding;
 pub mod sign;
@@ -1,7 +1,25 @@
 use crate::agent::Strategy;
-use crate::executor::binance::PlaceOrder;
+use crate::executor::binance::{
+    futures_symbol, ContractSpecs, FuturesMarket, PlaceOrder, TransferType, UniversalTransfer,
+};
+use crate::utils::rounding::{round_order_quantity, round_to_step};
 
-pub fn extract_binance_place_order(strategy: &Strategy) -> Vec<PlaceOrder> {
+pub fn extract_binance_place_order(
+    strategy: &Strategy,
+    contracts: &ContractSpecs,
+) -> Vec<PlaceOrder> {
+    extract_sequenced_place_orders(strategy, contracts)
+        .into_iter()
+        .map(|(_, order)| order)
+        .collect()
+}
+
+/// Orders of the strategy with the stage each one executes in. COIN-M quantities are
+/// rounded to the step in `contracts`, whole contracts for symbols it does not know.
+pub fn extract_sequenced_place_orders(
+    strategy: &Strategy,
+    contracts: &ContractSpecs,
+) -> Vec<(Option<u32>, PlaceOrder)> {
     let mut orders = Vec::new();
 
     let binance_orders = &strategy.exchanges.binance.orders;
@@ -15,7 +33,7 @@ pub fn extract_binance_place_order(strategy: &Strategy) -> Vec<PlaceOrder> {
 
     for order in binance_orders {
         println!("Order: {:?}", order);
-        let symbol = format!("{}USDT", order.token.to_uppercase());
+        let symbol = futures_symbol(&order.token, order.quote.as_deref());
 
         // Convert string to OrderSide enum
         let side = match order.side.to_uppercase().as_str() {
@@ -24,39 +42,68 @@ pub fn extract_binance_place_order(strategy: &Strategy) -> Vec<PlaceOrder> {
             _ => continue, // Skip invalid side
         };
 
-        // Convert string to OrderType enum
-        let order_type = crate::executor::binance::OrderType::Market;
+        // Limit orders rest at the strategy's price; an empty one is a market order
+        let price = match order.limit_price() {
+            Ok(price) => price,
+            Err(e) => {
+                println!("Skipping order on {}: {}", symbol, e);
+                continue;
+            }
+        };
+        let order_type = match price {
+            Some(_) => crate::executor::binance::OrderType::Limit,
+            None => crate::executor::binance::OrderType::Market,
+        };
 
         let quantity = Some(order.amount.clone())
             .and_then(|q| rust_decimal::Decimal::from_str_exact(q.as_str()).ok())
-            .map(|q| q.round_dp_with_strategy(3, rust_decimal::RoundingStrategy::ToZero));
+            .map(|q| match FuturesMarket::of_symbol(&symbol) {
+                // COIN-M orders are sized in contracts
+                FuturesMarket::CoinMargined => {
+                    let step = contracts
+                        .get(&symbol)
+                        .map_or(rust_decimal::Decimal::ONE, |spec| spec.step_size);
+                    round_to_step(q, step)
+                }
+                FuturesMarket::UsdMargined => round_order_quantity(q),
+            });
 
-        let time_in_force = Some(crate::executor::binance::TimeInForce::Gtc);
-        let close_position = None;
-        let price = None;
-
-        orders.push(PlaceOrder {
-            symbol,
-            side,
-            position_side: None,
-            order_type,
-            reduce_only: None,
-            quantity,
-            price,
-            new_client_order_id: None,
-            stop_price: None,
-            close_position,
-            activation_price: None,
-            callback_rate: None,
-            time_in_force,
-            working_type: None,
-            price_protect: None,
+        // Only limit orders take a time in force; post-only ones are GTX
+        let time_in_force = price.map(|_| {
+            if order.is_post_only() {
+                crate::executor::binance::TimeInForce::Gtx
+            } else {
+                crate::executor::binance::TimeInForce::Gtc
+            }
         });
+        let close_position = None;
+
+        orders.push((
+            order.sequence,
+            PlaceOrder {
+                symbol,
+                side,
+                position_side: None,
+                order_type,
+                reduce_only: None,
+                quantity,
+                price,
+                new_client_order_id: None,
+                stop_price: None,
+                close_position,
+                activation_price: None,
+                callback_rate: None,
+                time_in_force,
+                working_type: None,
+                price_protect: None,
+                new_order_resp_type: None,
+            },
+        ));
     }
 
     // Print orders for debugging
     println!("Extracted Binance orders:");
-    for (i, order) in orders.iter().enumerate() {
+    for (i, (_, order)) in orders.iter().enumerate() {
         println!("Order {}: {:?}", i + 1, order);
     }
 
@@ -65,3 +112,38 @@ pub fn extract_binance_place_order(strategy: &Strategy) -> Vec<PlaceOrder> {
     }
     orders
 }
+
+pub fn extract_binance_transfers(strategy: &Strategy) -> Vec<UniversalTransfer> {
+    let mut transfers = Vec::new();
+
+    for transfer in strategy.exchanges.binance.transfers.iter().flatten() {
+        let Some(transfer_type) = TransferType::from_wallets(&transfer.from, &transfer.to) else {
+            println!(
+                "Skipping transfer with unsupported wallets {} -> {}",
+                transfer.from, transfer.to
+            );
+            continue;
+        };
+        let amount = match rust_decimal::Decimal::from_str_exact(transfer.amount.as_str()) {
+            Ok(amount) if amount > rust_decimal::Decimal::ZERO => amount,
+            _ => {
+                println!(
+                    "Skipping transfer with invalid amount {:?}",
+                    transfer.amount
+                );
+                continue;
+            }
+        };
+
+        transfers.push(UniversalTransfer {
+            transfer_type,
+            asset: transfer.asset.to_uppercase(),
+            amount,
+        });
+    }
+
+    if !transfers.is_empty() {
+        println!("Extracted Binance transfers: {:?}", transfers);
+    }
+    transfers
+}
@@ -1,83 +1,273 @@
-use anyhow::Result;
+use crate::executor::binance::OrderSide;
+use crate::feed::binance::BinancePriceFeed;
+use crate::feed::Feed;
+use anyhow::{anyhow, Result};
 use reqwest::Client as ReqwestClient;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 use std::collections::HashMap;
+use std::fmt;
+
+/// Where a `PriceData` came from.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum PriceSource {
+    #[default]
+    Binance,
+    Pyth,
+}
+
+impl fmt::Display for PriceSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceSource::Binance => write!(f, "Binance"),
+            PriceSource::Pyth => write!(f, "Pyth"),
+        }
+    }
+}
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
 pub struct PriceData {
+    #[serde(deserialize_with = "millis")]
     pub timestamp: u128,
     pub market_price: Option<f64>,
     pub buy_long_price: Option<f64>,
     pub sell_short_price: Option<f64>,
     pub cur_funding_rate: Option<f64>,
+    /// Estimated rate of the running funding period.
+    #[serde(default)]
+    pub predicted_funding_rate: Option<f64>,
+    /// Unix ms of the next funding.
+    #[serde(default)]
+    pub next_funding_time: Option<u64>,
+    #[serde(default)]
+    pub funding_history: Option<FundingHistory>,
+    #[serde(default)]
+    pub source: PriceSource,
+    #[serde(default)]
+    pub depth: Option<BookDepth>,
+}
+
+/// Deserialize a ms timestamp through u64, since u128 cannot be read back from buffered
+/// formats such as the WAL store's tagged records.
+pub fn millis<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u128, D::Error> {
+    u64::deserialize(deserializer).map(u128::from)
+}
+
+/// Resting volume near the top of the order book.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct BookDepth {
+    /// Price levels summed on each side.
+    pub levels: usize,
+    /// Bid and ask volume in base units.
+    pub bid_volume: f64,
+    pub ask_volume: f64,
+    /// (bids - asks) / (bids + asks): positive when buyers outweigh sellers.
+    pub imbalance: f64,
+    /// Best ask over best bid, in bps of the mid price.
+    pub spread_bps: f64,
+    #[serde(default)]
+    pub best_bid: f64,
+    #[serde(default)]
+    pub best_ask: f64,
+}
+
+#[derive(Debug, thiserror::Error, PartialEq)]
+pub enum DepthCheckError {
+    #[error("spread is {spread_bps:.1} bps, above the {max_bps:.1} bps limit")]
+    WideSpread { spread_bps: f64, max_bps: f64 },
+    #[error(
+        "order of {quantity} needs more than the {available} resting in the top {levels} levels"
+    )]
+    Thin {
+        quantity: f64,
+        available: f64,
+        levels: usize,
+    },
+    #[error("limit price {price} crosses the best price {best} by more than {max_pct}%")]
+    Crosses { price: f64, best: f64, max_pct: f64 },
+    #[error("post-only limit price {price} crosses the best price {best}")]
+    PostOnlyCrosses { price: f64, best: f64 },
+}
+
+impl BookDepth {
+    /// Depth of the first `levels` (price, quantity) levels of each side, best first.
+    /// `None` when either side is empty.
+    pub fn from_levels(bids: &[(f64, f64)], asks: &[(f64, f64)], levels: usize) -> Option<Self> {
+        let (best_bid, best_ask) = (bids.first()?.0, asks.first()?.0);
+        let volume =
+            |side: &[(f64, f64)]| side.iter().take(levels).map(|(_, qty)| qty).sum::<f64>();
+        let (bid_volume, ask_volume) = (volume(bids), volume(asks));
+        let total = bid_volume + ask_volume;
+        let mid = (best_bid + best_ask) / 2.0;
+        Some(Self {
+            levels,
+            bid_volume,
+            ask_volume,
+            imbalance: if total > 0.0 {
+                (bid_volume - ask_volume) / total
+            } else {
+                0.0
+            },
+            spread_bps: (best_ask - best_bid) / mid * 10_000.0,
+            best_bid,
+            best_ask,
+        })
+    }
+
+    /// Whether a limit order at `price` stays within `max_cross_pct` of the other side's
+    /// best price; post-only orders may not cross it at all.
+    pub fn check_limit(
+        &self,
+        side: OrderSide,
+        price: f64,
+        post_only: bool,
+        max_cross_pct: f64,
+    ) -> Result<(), DepthCheckError> {
+        // How far past the best price it would take liquidity, in % of it
+        let (best, cross_pct) = match side {
+            OrderSide::Buy => (
+                self.best_ask,
+                (price - self.best_ask) / self.best_ask * 100.0,
+            ),
+            OrderSide::Sell => (
+                self.best_bid,
+                (self.best_bid - price) / self.best_bid * 100.0,
+            ),
+        };
+        if post_only && cross_pct >= 0.0 {
+            return Err(DepthCheckError::PostOnlyCrosses { price, best });
+        }
+        if cross_pct > max_cross_pct {
+            return Err(DepthCheckError::Crosses {
+                price,
+                best,
+                max_pct: max_cross_pct,
+            });
+        }
+        Ok(())
+    }
+
+    /// Whether a market order of `quantity` base units can fill within the book without
+    /// crossing a wide spread or sweeping past the summed levels.
+    pub fn check_order(
+        &self,
+        side: OrderSide,
+        quantity: f64,
+        max_spread_bps: f64,
+    ) -> Result<(), DepthCheckError> {
+        if self.spread_bps > max_spread_bps {
+            return Err(DepthCheckError::WideSpread {
+                spread_bps: self.spread_bps,
+                max_bps: max_spread_bps,
+            });
+        }
+        // Buys take the asks, sells the bids
+        let available = match side {
+            OrderSide::Buy => self.ask_volume,
+            OrderSide::Sell => self.bid_volume,
+        };
+        if quantity > available {
+            return Err(DepthCheckError::Thin {
+                quantity,
+                available,
+                levels: self.levels,
+            });
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for BookDepth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "spread {:.1} bps, top {} levels {:.3} bid / {:.3} ask (imbalance {:+.2})",
+            self.spread_bps, self.levels, self.bid_volume, self.ask_volume, self.imbalance
+        )
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct MarketIndexResponse {
-    mark_price: String,             // mark price
-    index_price: String,            // index price
-    estimated_settle_price: String, // Estimated Settle Price, only useful in the last hour before the settlement starts.
-    last_funding_rate: String,      // This is the Latest funding rate
-    next_funding_time: u64,
-    interest_rate: String,
-    time: u64,
+/// Average settled funding rate over trailing windows, per funding period.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Default, PartialEq)]
+pub struct FundingHistory {
+    pub avg_8h: Option<f64>,
+    pub avg_24h: Option<f64>,
+    pub avg_7d: Option<f64>,
 }
 
-#[derive(Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-struct DepthResponse {
-    last_update_id: u64,
-    bids: Vec<(String, String)>,
-    asks: Vec<(String, String)>,
+impl FundingHistory {
+    /// Summarize `(funding time in ms, rate)` pairs as of `now_ms`.
+    pub fn from_rates(rates: &[(u64, f64)], now_ms: u64) -> Self {
+        const HOUR_MS: u64 = 60 * 60 * 1000;
+        let average = |window_ms: u64| {
+            let since = now_ms.saturating_sub(window_ms);
+            let window: Vec<f64> = rates
+                .iter()
+                .filter(|(time, _)| *time >= since)
+                .map(|(_, rate)| *rate)
+                .collect();
+            (!window.is_empty()).then(|| window.iter().sum::<f64>() / window.len() as f64)
+        };
+        Self {
+            avg_8h: average(8 * HOUR_MS),
+            avg_24h: average(24 * HOUR_MS),
+            avg_7d: average(7 * 24 * HOUR_MS),
+        }
+    }
 }
 
-pub async fn fetch_binance_prices(client: &ReqwestClient, symbol: &String) -> Result<PriceData> {
-    let mut price_data = PriceData {
-        timestamp: 0,
-        market_price: None,
-        buy_long_price: None,
-        sell_short_price: None,
-        cur_funding_rate: None,
-    };
-    // Fetch the market index price
-    let market_response: MarketIndexResponse = client
-        .get("https://testnet.binancefuture.com/fapi/v1/premiumIndex")
-        .query(&[("symbol", symbol)]) // Fix the query formatting
-        .send()
-        .await?
-        .json()
-        .await?;
-
-    price_data.market_price = Some(market_response.mark_price.parse::<f64>().unwrap());
-    price_data.timestamp = market_response.time.into();
-
-    // Fetch the order book depth
-    let response: DepthResponse = client
-        .get("https://fapi.binance.comfapi/v1/depth")
-        .query(&[("symbol", symbol.as_str()), ("limit", "5")]) // Correct the format here
-        .send()
-        .await?
-        .json()
-        .await?;
-
-    price_data.buy_long_price = Some(response.asks[0].0.parse::<f64>().unwrap());
-    price_data.sell_short_price = Some(response.bids[0].0.parse::<f64>().unwrap());
-
-    Ok(price_data)
+impl PriceData {
+    /// Predicted and trailing funding as percentages per period, for the strategy prompt.
+    pub fn funding_summary(&self) -> String {
+        let pct = |rate: Option<f64>| match rate {
+            Some(rate) => format!("{:.4}%", rate * 100.0),
+            None => "n/a".to_string(),
+        };
+        let history = self.funding_history.unwrap_or_default();
+        let mut summary = format!("predicted {}", pct(self.predicted_funding_rate));
+        if let Some(next_funding_time) = self
+            .next_funding_time
+            .and_then(|time| chrono::DateTime::from_timestamp_millis(time as i64))
+        {
+            summary.push_str(&format!(
+                " (settles {})",
+                next_funding_time.format("%H:%M UTC")
+            ));
+        }
+        format!(
+            "{}, avg 8h {}, 24h {}, 7d {}",
+            summary,
+            pct(history.avg_8h),
+            pct(history.avg_24h),
+            pct(history.avg_7d)
+        )
+    }
+}
+
+/// Prices of `symbol` from the Binance futures API at `base_url`.
+pub async fn fetch_binance_prices(
+    client: &ReqwestClient,
+    base_url: &String,
+    symbol: &String,
+) -> Result<PriceData> {
+    BinancePriceFeed::new(base_url, client, symbol)
+        .feed()
+        .await
+        .map_err(|e| anyhow!("Failed to fetch {} prices: {}", symbol, e))
 }
 
 /// Fetches prices for both BTC and ETH in parallel
 pub async fn fetch_major_crypto_prices(
     client: &ReqwestClient,
+    base_url: &String,
 ) -> Result<HashMap<String, PriceData>> {
     let btc_symbol = "BTCUSDT".to_string();
     let eth_symbol = "ETHUSDT".to_string();
 
     // Fetch both prices in parallel
     let (btc_result, eth_result) = tokio::join!(
-        fetch_binance_prices(client, &btc_symbol),
-        fetch_binance_prices(client, &eth_symbol)
+        fetch_binance_prices(client, base_url, &btc_symbol),
+        fetch_binance_prices(client, base_url, &eth_symbol)
     );
 
     // Create a HashMap to store the results
@@ -110,6 +300,83 @@ pub async fn fetch_major_crypto_prices(
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_funding_history_and_summary() {
+        const HOUR_MS: u64 = 60 * 60 * 1000;
+        let now_ms = 1_700_000_000_000;
+        let rates = [
+            (now_ms - 72 * HOUR_MS, 0.0004),
+            (now_ms - 16 * HOUR_MS, 0.0002),
+            (now_ms - 8 * HOUR_MS, 0.0001),
+        ];
+        let history = FundingHistory::from_rates(&rates, now_ms);
+        assert_eq!(history.avg_8h, Some(0.0001));
+        assert!((history.avg_24h.unwrap() - 0.00015).abs() < 1e-12);
+        assert!((history.avg_7d.unwrap() - 0.0007 / 3.0).abs() < 1e-12);
+        assert_eq!(FundingHistory::from_rates(&[], now_ms).avg_7d, None);
+
+        let price = PriceData {
+            timestamp: now_ms.into(),
+            market_price: Some(64000.0),
+            buy_long_price: None,
+            sell_short_price: None,
+            cur_funding_rate: Some(0.0001),
+            predicted_funding_rate: Some(0.00012),
+            next_funding_time: Some(now_ms + 2 * HOUR_MS),
+            funding_history: Some(history),
+            source: PriceSource::Binance,
+            depth: None,
+        };
+        assert_eq!(
+            price.funding_summary(),
+            "predicted 0.0120% (settles 00:13 UTC), avg 8h 0.0100%, 24h 0.0150%, 7d 0.0233%"
+        );
+    }
+
+    #[test]
+    fn test_book_depth() {
+        let bids = [(99.0, 2.0), (98.0, 3.0), (97.0, 10.0)];
+        let asks = [(101.0, 1.0), (102.0, 4.0)];
+        let depth = BookDepth::from_levels(&bids, &asks, 2).unwrap();
+        assert_eq!((depth.bid_volume, depth.ask_volume), (5.0, 5.0));
+        assert_eq!(depth.imbalance, 0.0);
+        assert_eq!(depth.spread_bps, 200.0);
+        assert!(BookDepth::from_levels(&bids, &[], 2).is_none());
+
+        assert!(depth.check_order(OrderSide::Buy, 5.0, 250.0).is_ok());
+        assert_eq!(
+            depth.check_order(OrderSide::Sell, 6.0, 250.0),
+            Err(DepthCheckError::Thin {
+                quantity: 6.0,
+                available: 5.0,
+                levels: 2
+            })
+        );
+        assert!(matches!(
+            depth.check_order(OrderSide::Buy, 1.0, 100.0),
+            Err(DepthCheckError::WideSpread { .. })
+        ));
+
+        // Limits may take the book up to 1% past the best price, post-only ones not at all
+        assert!(depth.check_limit(OrderSide::Buy, 102.0, false, 1.0).is_ok());
+        assert!(depth.check_limit(OrderSide::Sell, 100.0, true, 1.0).is_ok());
+        assert_eq!(
+            depth.check_limit(OrderSide::Sell, 95.0, false, 1.0),
+            Err(DepthCheckError::Crosses {
+                price: 95.0,
+                best: 99.0,
+                max_pct: 1.0
+            })
+        );
+        assert!(matches!(
+            depth.check_limit(OrderSide::Buy, 101.0, true, 1.0),
+            Err(DepthCheckError::PostOnlyCrosses { .. })
+        ));
+
+        let bid_heavy = BookDepth::from_levels(&bids, &asks, 3).unwrap();
+        assert!((bid_heavy.imbalance - 10.0 / 20.0).abs() < 1e-12);
+    }
+
     #[tokio::test]
     async fn test_fetch_binance_prices() {
         // Skip this test if running in CI environment
@@ -125,7 +392,8 @@ mod tests {
         let symbol = "BTCUSDT".to_string();
 
         // Test the function with the actual Binance API
-        let result = fetch_binance_prices(&client, &symbol).await;
+        let base_url = crate::config::Config::default().binance_base_url;
+        let result = fetch_binance_prices(&client, &base_url, &symbol).await;
 
         // Check the result
         assert!(result.is_ok(), "Failed to fetch prices: {:?}", result.err());
@@ -166,7 +434,8 @@ mod tests {
         let client = reqwest::Client::new();
 
         // Test the function with the actual Binance API
-        let result = fetch_major_crypto_prices(&client).await;
+        let base_url = crate::config::Config::default().binance_base_url;
+        let result = fetch_major_crypto_prices(&client, &base_url).await;
 
         // Check the result
         assert!(
@@ -3,7 +3,6 @@ use anyhow::Result;
 use reqwest::Client as ReqwestClient;
 use serde_json::Value;
 use std::collections::{HashMap, VecDeque};
-use strum::IntoEnumIterator;
 
 #[derive(serde::Serialize, Debug, Clone)]
 pub struct OHLCV {
@@ -210,21 +209,29 @@ pub async fn fetch_binance_ohlcv(
 pub struct BinanceData {
     pub window_size: usize,
     pub symbol: String,
+    /// Timeframes kept, in the order `update_ohlcv_data` returns their indicators.
+    pub intervals: Vec<Interval>,
     pub data: HashMap<Interval, TimeframeData>,
     pub binance_prices: VecDeque<PriceData>,
 }
 
 impl BinanceData {
-    pub async fn new(client: &ReqwestClient, window_size: usize, symbol: &String) -> Result<Self> {
+    pub async fn new(
+        client: &ReqwestClient,
+        window_size: usize,
+        symbol: &String,
+        intervals: &[Interval],
+    ) -> Result<Self> {
         let mut data = HashMap::new();
-        let futures_fetch_binance_data: Vec<_> = Interval::iter()
+        let futures_fetch_binance_data: Vec<_> = intervals
+            .iter()
             .map(|interval| fetch_binance_ohlcv(client, interval.to_string(), window_size, symbol))
             .collect();
 
         let ohlcv_data_array: Vec<Result<Vec<OHLCV>>> =
             futures::future::join_all(futures_fetch_binance_data).await;
 
-        for (interval, ohlcv_data) in Interval::iter().zip(ohlcv_data_array) {
+        for (interval, ohlcv_data) in intervals.iter().copied().zip(ohlcv_data_array) {
             let mut binance_data = TimeframeData::with_initial_data(
                 window_size,
                 ohlcv_data
@@ -237,6 +244,7 @@ impl BinanceData {
         Ok(Self {
             window_size,
             symbol: symbol.to_owned(),
+            intervals: intervals.to_vec(),
             data,
             binance_prices: VecDeque::with_capacity(window_size),
         })
@@ -267,7 +275,9 @@ impl BinanceData {
         &mut self,
         client: &ReqwestClient,
     ) -> Result<Vec<(Indicators, Indicators)>> {
-        let futures_fetch_binance_data: Vec<_> = Interval::iter()
+        let futures_fetch_binance_data: Vec<_> = self
+            .intervals
+            .iter()
             .map(|interval| fetch_binance_ohlcv(client, interval.to_string(), 1, &self.symbol))
             .collect();
 
@@ -285,9 +295,9 @@ impl BinanceData {
             .collect();
 
         let mut indicators: Vec<(Indicators, Indicators)> =
-            Vec::with_capacity(Interval::iter().count());
+            Vec::with_capacity(self.intervals.len());
 
-        for (interval, ohlcv_data) in Interval::iter().zip(data_intervals) {
+        for (interval, ohlcv_data) in self.intervals.iter().copied().zip(data_intervals) {
             let binance_data = self.data.get_mut(&interval).unwrap();
             binance_data.update_ohlcv(ohlcv_data);
             indicators.push(binance_data.update_indicators());
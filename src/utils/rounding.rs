@@ -0,0 +1,237 @@
+//! Rounding policy shared by every module that converts or rounds amounts.
+//!
+//! Rules:
+//! - Order quantities are rounded toward zero to the exchange step, so we never
+//!   order more than the strategy asked for.
+//! - Token amounts are converted to base units from their decimal representation
+//!   (not via `f64` multiplication) and truncated, so we never try to spend more
+//!   than we hold and `0.29` stays `0.29`.
+//! - Minimum outputs round down and maximum inputs round up when applying bps.
+//! - Display precision is fixed per kind of value.
+
+use alloy::primitives::U256;
+use anyhow::{anyhow, Result};
+use rust_decimal::{Decimal, RoundingStrategy};
+
+/// Basis points in 100%.
+pub const BPS_DENOMINATOR: u32 = 10_000;
+
+/// Default quantity step for USDⓈ-M futures orders (0.001).
+pub const ORDER_QTY_DP: u32 = 3;
+
+/// Decimal places leg amounts are truncated to when a strategy is scaled down.
+pub const SCALED_AMOUNT_DP: u32 = 8;
+
+/// Decimal places used when displaying USD prices and percentages.
+pub const DISPLAY_PRICE_DP: usize = 2;
+
+/// Decimal places used when displaying token amounts.
+pub const DISPLAY_AMOUNT_DP: usize = 6;
+
+/// Round an order quantity toward zero to the default futures step.
+pub fn round_order_quantity(quantity: Decimal) -> Decimal {
+    quantity.round_dp_with_strategy(ORDER_QTY_DP, RoundingStrategy::ToZero)
+}
+
+/// Round a scaled-down leg amount toward zero to `SCALED_AMOUNT_DP` places.
+pub fn round_scaled_amount(amount: Decimal) -> Decimal {
+    amount.round_dp_with_strategy(SCALED_AMOUNT_DP, RoundingStrategy::ToZero)
+}
+
+/// Round a quantity toward zero to a multiple of `step` (e.g. a symbol's LOT_SIZE).
+pub fn round_to_step(quantity: Decimal, step: Decimal) -> Decimal {
+    if step <= Decimal::ZERO {
+        return quantity;
+    }
+    (quantity / step).trunc() * step
+}
+
+/// Convert a decimal string (e.g. "1.5") to integer base units, truncating digits
+/// beyond `decimals`.
+pub fn parse_units(amount: &str, decimals: u8) -> Result<U256> {
+    let amount = amount.trim();
+    if amount.starts_with('-') {
+        return Err(anyhow!("Negative amount: {}", amount));
+    }
+    let amount = amount.strip_prefix('+').unwrap_or(amount);
+    let (integer, fraction) = amount.split_once('.').unwrap_or((amount, ""));
+    if integer.is_empty() && fraction.is_empty() {
+        return Err(anyhow!("Empty amount"));
+    }
+    if !integer
+        .chars()
+        .chain(fraction.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return Err(anyhow!("Invalid decimal amount: {}", amount));
+    }
+
+    let decimals = decimals as usize;
+    let mut digits = String::with_capacity(integer.len() + decimals);
+    digits.push_str(integer);
+    if fraction.len() >= decimals {
+        digits.push_str(&fraction[..decimals]);
+    } else {
+        digits.push_str(fraction);
+        digits.extend(std::iter::repeat_n('0', decimals - fraction.len()));
+    }
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return Ok(U256::ZERO);
+    }
+    U256::from_str_radix(digits, 10).map_err(|e| anyhow!("Amount {} out of range: {}", amount, e))
+}
+
+/// Convert an `f64` amount to base units using its shortest decimal representation.
+pub fn f64_to_units(amount: f64, decimals: u8) -> Result<U256> {
+    if !amount.is_finite() {
+        return Err(anyhow!("Non-finite amount: {}", amount));
+    }
+    parse_units(&amount.to_string(), decimals)
+}
+
+/// Convert integer base units (as returned by APIs, e.g. "5162992717092596") to a float.
+pub fn units_to_f64(raw: &str, decimals: u8) -> Result<f64> {
+    let raw = raw.trim();
+    if raw.is_empty() || !raw.chars().all(|c| c.is_ascii_digit()) {
+        return Err(anyhow!("Invalid base unit amount: {:?}", raw));
+    }
+    let decimals = decimals as usize;
+    let padded = format!("{:0>width$}", raw, width = decimals + 1);
+    let (integer, fraction) = padded.split_at(padded.len() - decimals);
+    format!("{}.{}", integer, fraction)
+        .trim_end_matches('.')
+        .parse::<f64>()
+        .map_err(|e| anyhow!("Invalid base unit amount {:?}: {}", raw, e))
+}
+
+/// `amount * (1 - bps / 10_000)`, rounded down. Used for minimum outputs.
+pub fn apply_bps_down(amount: U256, bps: u16) -> U256 {
+    let bps = (bps as u32).min(BPS_DENOMINATOR);
+    amount * U256::from(BPS_DENOMINATOR - bps) / U256::from(BPS_DENOMINATOR)
+}
+
+/// `amount * (1 + bps / 10_000)`, rounded up. Used for maximum inputs.
+pub fn apply_bps_up(amount: U256, bps: u16) -> U256 {
+    let numerator = amount * U256::from(BPS_DENOMINATOR + bps as u32);
+    numerator.div_ceil(U256::from(BPS_DENOMINATOR))
+}
+
+/// Fraction represented by `bps` (100 bps = 0.01).
+pub fn bps_to_fraction(bps: u16) -> f64 {
+    bps as f64 / BPS_DENOMINATOR as f64
+}
+
+/// Difference between `actual` and `expected` in bps of `expected` (positive when below).
+pub fn shortfall_bps(expected: f64, actual: f64) -> f64 {
+    if expected == 0.0 {
+        return 0.0;
+    }
+    (expected - actual) / expected * BPS_DENOMINATOR as f64
+}
+
+/// Split `amount` into `count` equal parts, the last one taking the rounding remainder
+/// so the parts add up to `amount`.
+pub fn split_amount(amount: f64, count: u32) -> Vec<f64> {
+    let count = count.max(1);
+    let part = amount / count as f64;
+    let mut parts = vec![part; count as usize - 1];
+    parts.push(amount - part * (count - 1) as f64);
+    parts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn test_round_order_quantity() {
+        assert_eq!(round_order_quantity(dec("1.23456")), dec("1.234"));
+        assert_eq!(round_order_quantity(dec("0.0009")), dec("0"));
+        assert_eq!(round_order_quantity(dec("-1.2399")), dec("-1.239"));
+        assert_eq!(round_order_quantity(dec("2")), dec("2"));
+    }
+
+    #[test]
+    fn test_round_to_step() {
+        assert_eq!(round_to_step(dec("1.2399"), dec("0.01")), dec("1.23"));
+        assert_eq!(round_to_step(dec("7"), dec("5")), dec("5"));
+        assert_eq!(round_to_step(dec("0.15"), dec("0.05")), dec("0.15"));
+        assert_eq!(round_to_step(dec("1.5"), Decimal::ZERO), dec("1.5"));
+    }
+
+    #[test]
+    fn test_parse_units() -> Result<()> {
+        assert_eq!(parse_units("1.1", 6)?, U256::from(1_100_000u64));
+        assert_eq!(parse_units("0.29", 2)?, U256::from(29u64));
+        assert_eq!(
+            parse_units("1", 18)?,
+            U256::from(10u64).pow(U256::from(18u64))
+        );
+        assert_eq!(parse_units(".5", 1)?, U256::from(5u64));
+        assert_eq!(parse_units("5.", 1)?, U256::from(50u64));
+        assert_eq!(parse_units("1.23456789", 6)?, U256::from(1_234_567u64));
+        assert_eq!(parse_units("0.0000001", 6)?, U256::ZERO);
+        assert_eq!(parse_units("42", 0)?, U256::from(42u64));
+        assert_eq!(parse_units(" 007.50 ", 2)?, U256::from(750u64));
+        assert!(parse_units("-1", 6).is_err());
+        assert!(parse_units("1e6", 6).is_err());
+        assert!(parse_units("abc", 6).is_err());
+        assert!(parse_units(".", 6).is_err());
+        assert!(parse_units("", 6).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_f64_to_units_avoids_float_dust() -> Result<()> {
+        // 0.29 * 100.0 == 28.999999999999996 with plain float math
+        assert_eq!(f64_to_units(0.29, 2)?, U256::from(29u64));
+        assert_eq!(f64_to_units(1.1, 6)?, U256::from(1_100_000u64));
+        assert_eq!(
+            f64_to_units(0.1, 18)?,
+            U256::from(100_000_000_000_000_000u64)
+        );
+        assert!(f64_to_units(f64::NAN, 6).is_err());
+        assert!(f64_to_units(-0.5, 6).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_units_to_f64() -> Result<()> {
+        assert_eq!(units_to_f64("1100000", 6)?, 1.1);
+        assert_eq!(units_to_f64("5", 6)?, 0.000005);
+        assert_eq!(units_to_f64("0", 18)?, 0.0);
+        assert_eq!(units_to_f64("42", 0)?, 42.0);
+        assert_eq!(units_to_f64("5162992717092596", 18)?, 0.005162992717092596);
+        assert!(units_to_f64("-1", 6).is_err());
+        assert!(units_to_f64("1.5", 6).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_bps() {
+        let amount = U256::from(1_000_001u64);
+        assert_eq!(apply_bps_down(amount, 100), U256::from(990_000u64));
+        assert_eq!(apply_bps_up(amount, 100), U256::from(1_010_002u64));
+        assert_eq!(apply_bps_down(amount, 0), amount);
+        assert_eq!(apply_bps_down(amount, 20_000), U256::ZERO);
+        assert_eq!(bps_to_fraction(100), 0.01);
+        assert_eq!(shortfall_bps(100.0, 99.0), 100.0);
+        assert_eq!(shortfall_bps(0.0, 1.0), 0.0);
+    }
+
+    #[test]
+    fn test_split_amount() {
+        assert_eq!(split_amount(10.0, 4), vec![2.5, 2.5, 2.5, 2.5]);
+        assert_eq!(split_amount(1.5, 1), vec![1.5]);
+        assert_eq!(split_amount(1.5, 0), vec![1.5]);
+        let parts = split_amount(1.0, 3);
+        assert_eq!(parts.len(), 3);
+        assert!((parts.iter().sum::<f64>() - 1.0).abs() < 1e-12);
+    }
+}
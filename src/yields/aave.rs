@@ -1,12 +1,33 @@
-use crate::yields::{Yield, APR};
+use crate::config::{AaveMarketConfig, AaveYieldConfig};
+use crate::yields::{apr_to_apy, RiskTier, Yield, YieldFetchError, APR};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
+use std::time::Duration;
+
+/// Attempts at a subgraph query before a temporary error is returned.
+const MAX_FETCH_ATTEMPTS: u32 = 3;
+
+/// Wait before the first retry, doubled for each one after it.
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Aave's fixed point unit for rates.
+const RAY: f64 = 1e27;
+
+/// Rates above this APR (in percent) are taken to be a broken index, not a market.
+const MAX_SANE_APR: f64 = 1_000.0;
 
 // Define structures to deserialize the GraphQL response
 #[derive(Debug, Deserialize)]
 struct AaveResponse {
-    data: AaveData,
+    data: Option<AaveData>,
+    #[serde(default)]
+    errors: Vec<GraphQlError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct GraphQlError {
+    message: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -14,28 +35,67 @@ struct AaveData {
     reserves: Vec<Reserve>,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Aave {}
+/// Reserve rates of the configured Aave markets, each APR tagged with its chain.
+#[derive(Debug)]
+pub struct Aave {
+    client: reqwest::Client,
+    graph_api_key_env: String,
+    markets: Vec<AaveMarketConfig>,
+}
+
+impl Aave {
+    pub fn new(config: &AaveYieldConfig, client: reqwest::Client) -> Self {
+        Self {
+            client,
+            graph_api_key_env: config.graph_api_key_env.clone(),
+            markets: config.markets.clone(),
+        }
+    }
+}
 
 #[async_trait]
 impl Yield for Aave {
-    fn get_symbol() -> String {
+    fn get_symbol(&self) -> String {
         "aave".to_string()
     }
 
     async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
-        // Example function to demonstrate usage
-        let yields = fetch_aave_yields().await?;
+        let api_key = std::env::var(&self.graph_api_key_env)
+            .map_err(|_| format!("{} not set in environment", self.graph_api_key_env))?;
         let mut aprs = Vec::new();
-        for yield_data in yields {
-            aprs.push(APR {
-                symbol: yield_data.symbol,
-                deposit_apr: yield_data.deposit_apr,
-                borrow_apr: Some(yield_data.borrow_apr),
-            });
+        let mut last_error = None;
+        for market in &self.markets {
+            // One failing market leaves the others' yields listed
+            let yields = match fetch_aave_yields(&self.client, &api_key, market).await {
+                Ok(yields) => yields,
+                Err(e) => {
+                    println!(
+                        "Skipping Aave {} market on chain {}: {}",
+                        market.market, market.chain_id, e
+                    );
+                    last_error = Some(e.to_string());
+                    continue;
+                }
+            };
+            for yield_data in yields {
+                aprs.push(APR {
+                    borrow_apr: Some(yield_data.borrow_apr),
+                    deposit_apy: Some(apr_to_apy(yield_data.deposit_apr)),
+                    borrow_apy: Some(apr_to_apy(yield_data.borrow_apr)),
+                    chain_id: Some(market.chain_id),
+                    tvl_usd: yield_data.tvl_usd,
+                    utilization: yield_data.utilization,
+                    risk_tier: Some(RiskTier::Low),
+                    asset: Some(yield_data.asset),
+                    ..APR::new(yield_data.symbol, yield_data.deposit_apr)
+                });
+            }
         }
 
-        Ok(aprs)
+        match last_error {
+            Some(e) if aprs.is_empty() => Err(e.into()),
+            _ => Ok(aprs),
+        }
     }
 }
 
@@ -71,11 +131,16 @@ struct AaveYield {
     pub symbol: String,
     pub deposit_apr: f64,
     pub borrow_apr: f64,
+    pub tvl_usd: Option<f64>,
+    pub utilization: Option<f64>,
+    pub asset: String,
 }
 
-async fn fetch_aave_yields() -> Result<Vec<AaveYield>, Box<dyn Error>> {
-    let client = reqwest::Client::new();
-
+async fn fetch_aave_yields(
+    client: &reqwest::Client,
+    api_key: &str,
+    market: &AaveMarketConfig,
+) -> Result<Vec<AaveYield>, Box<dyn Error>> {
     // GraphQL query to fetch AAVE reserves data
     let query = r#"
     {
@@ -101,60 +166,325 @@ async fn fetch_aave_yields() -> Result<Vec<AaveYield>, Box<dyn Error>> {
     }
     "#;
 
-    // Aave subgraph on base
-    let response = client.post("https://gateway-arbitrum.network.thegraph.com/api/a820147ae9eec25fbfa2f206671706b8/subgraphs/id/GQFbb95cE6d8mV989mL5figjaGaKCQB3xqYrr1bRyXqF")
+    let mut attempt = 0;
+    let data = loop {
+        attempt += 1;
+        match query_subgraph(client, api_key, market, query).await {
+            Ok(data) => break data,
+            Err(err) if attempt < MAX_FETCH_ATTEMPTS && err.is_temporary() => {
+                println!(
+                    "Aave {} subgraph query failed (attempt {}): {}, retrying",
+                    market.chain_id, attempt, err
+                );
+                tokio::time::sleep(RETRY_BACKOFF * 2u32.pow(attempt - 1)).await;
+            }
+            Err(err) => return Err(err.into()),
+        }
+    };
+    if data.reserves.is_empty() {
+        return Err(YieldFetchError::UnexpectedResponse(format!(
+            "no reserves in the Aave subgraph of chain {}",
+            market.chain_id
+        ))
+        .into());
+    }
+
+    let mut yields = Vec::new();
+    for reserve in data.reserves {
+        match reserve_yield(market, reserve) {
+            Ok(yield_data) => yields.push(yield_data),
+            // One broken reserve should not hide the rest of the market
+            Err(err) => println!(
+                "Skipping Aave reserve on chain {}: {}",
+                market.chain_id, err
+            ),
+        }
+    }
+
+    Ok(yields)
+}
+
+async fn query_subgraph(
+    client: &reqwest::Client,
+    api_key: &str,
+    market: &AaveMarketConfig,
+    query: &str,
+) -> Result<AaveData, YieldFetchError> {
+    let response = client
+        .post(subgraph_url(api_key, &market.subgraph_id))
         .json(&serde_json::json!({
             "query": query
         }))
         .send()
-        .await?
-        .json::<AaveResponse>()
         .await?;
+    let status = response.status();
+    let body = response.text().await?;
+    parse_subgraph_response(status, &body)
+}
 
-    let mut yields = Vec::new();
-
-    for reserve in response.data.reserves {
-        // Parse rates from strings to f64
-        // AAVE rates are in ray units (1e27) and represent per-second rates
-        let liquidity_rate = parse_ray_to_apr(&reserve.liquidityRate);
-        let variable_borrow_rate = parse_ray_to_apr(&reserve.variableBorrowRate);
+/// Reserves from a subgraph response, with the GraphQL `errors` array and gateway
+/// rate limits surfaced as errors instead of a failed deserialization.
+fn parse_subgraph_response(
+    status: reqwest::StatusCode,
+    body: &str,
+) -> Result<AaveData, YieldFetchError> {
+    if status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        return Err(YieldFetchError::RateLimited(body.to_string()));
+    }
+    if !status.is_success() {
+        return Err(YieldFetchError::Http {
+            status: status.as_u16(),
+            body: body.to_string(),
+        });
+    }
+    let response: AaveResponse = serde_json::from_str(body)
+        .map_err(|e| YieldFetchError::UnexpectedResponse(format!("{}: {}", e, body)))?;
+    if !response.errors.is_empty() {
+        let messages: Vec<String> = response
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect();
+        if messages
+            .iter()
+            .any(|message| message.to_lowercase().contains("rate limit"))
+        {
+            return Err(YieldFetchError::RateLimited(messages.join("; ")));
+        }
+        return Err(YieldFetchError::GraphQl(messages));
+    }
+    response
+        .data
+        .ok_or_else(|| YieldFetchError::UnexpectedResponse("neither data nor errors".to_string()))
+}
 
-        yields.push(AaveYield {
-            symbol: format!("aBas{}", reserve.symbol),
-            deposit_apr: liquidity_rate,
-            borrow_apr: variable_borrow_rate,
+/// Rates of `reserve`, rejected if they fall outside what a real market could have.
+fn reserve_yield(
+    market: &AaveMarketConfig,
+    reserve: Reserve,
+) -> Result<AaveYield, YieldFetchError> {
+    let symbol = format!("{}{}", market.symbol_prefix, reserve.symbol);
+    let deposit_apr = parse_ray_to_apr(&symbol, "liquidityRate", &reserve.liquidityRate)?;
+    let borrow_apr = parse_ray_to_apr(&symbol, "variableBorrowRate", &reserve.variableBorrowRate)?;
+    for (field, value) in [
+        ("liquidityRate", deposit_apr),
+        ("variableBorrowRate", borrow_apr),
+    ] {
+        if !(0.0..=MAX_SANE_APR).contains(&value) {
+            return Err(YieldFetchError::OutOfBounds {
+                symbol,
+                field,
+                value,
+            });
+        }
+    }
+    // Suppliers share the interest borrowers pay, so they never earn more than it
+    if deposit_apr > borrow_apr {
+        return Err(YieldFetchError::OutOfBounds {
+            symbol,
+            field: "liquidityRate",
+            value: deposit_apr,
+        });
+    }
+    let utilization = reserve.utilizationRate.parse::<f64>().ok();
+    if let Some(value) = utilization.filter(|value| !(0.0..=1.0).contains(value)) {
+        return Err(YieldFetchError::OutOfBounds {
+            symbol,
+            field: "utilizationRate",
+            value,
         });
     }
 
-    Ok(yields)
+    Ok(AaveYield {
+        symbol,
+        deposit_apr,
+        borrow_apr,
+        tvl_usd: reserve_tvl_usd(&reserve),
+        utilization,
+        asset: reserve.symbol,
+    })
 }
 
-// Helper function to convert AAVE's ray format (1e27) per-second rate to yearly APR percentage
-fn parse_ray_to_apr(ray_rate: &str) -> f64 {
-    // Parse the string to a number, defaulting to 0 if parsing fails
-    let rate = ray_rate.parse::<f64>().unwrap_or(0.0);
+/// USD value of the reserve's total liquidity. Aave v3 reports `priceInEth` in the
+/// market's base currency, USD with 8 decimals, despite the name.
+fn reserve_tvl_usd(reserve: &Reserve) -> Option<f64> {
+    let total_liquidity = reserve.totalLiquidity.parse::<f64>().ok()?;
+    let price = reserve.price.priceInEth.parse::<f64>().ok()?;
+    Some(total_liquidity / 10f64.powi(reserve.decimals.into()) * price / 1e8)
+}
 
-    // AAVE rates are in ray units (1e27) and represent per-second rates
-    let ray = 1e27;
+fn subgraph_url(api_key: &str, subgraph_id: &str) -> String {
+    format!(
+        "https://gateway-arbitrum.network.thegraph.com/api/{}/subgraphs/id/{}",
+        api_key, subgraph_id
+    )
+}
 
-    // Convert to yearly APR percentage
-    // The formula appears to be different from the standard calculation
-    // Based on the provided examples, we need to adjust the calculation
-    (rate / ray) * 100.0 // This seems to match the provided examples better
+/// Yearly APR in percent of an Aave rate. Aave stores `liquidityRate` and
+/// `variableBorrowRate` as yearly rates in ray (1e27), not per-second ones; interest
+/// accrues on them every second, which `apr_to_apy` accounts for.
+fn parse_ray_to_apr(symbol: &str, field: &str, ray_rate: &str) -> Result<f64, YieldFetchError> {
+    let rate = ray_rate.parse::<f64>().map_err(|e| {
+        YieldFetchError::UnexpectedResponse(format!(
+            "{} {} {:?} is not a number: {}",
+            symbol, field, ray_rate, e
+        ))
+    })?;
+    Ok(rate / RAY * 100.0)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::yields::{apy_to_apr, fixture};
+    use serde_json::json;
+
+    #[test]
+    fn test_subgraph_url() {
+        let config = AaveYieldConfig::default();
+        assert!(config.markets.iter().any(|market| market.chain_id == 42161));
+        assert_eq!(
+            subgraph_url("key", &config.markets[0].subgraph_id),
+            "https://gateway-arbitrum.network.thegraph.com/api/key/subgraphs/id/GQFbb95cE6d8mV989mL5figjaGaKCQB3xqYrr1bRyXqF"
+        );
+    }
+
+    fn reserve(liquidity_rate: &str, variable_borrow_rate: &str) -> Reserve {
+        fixture(json!({
+            "__typename": "Reserve",
+            "availableLiquidity": "400000000000",
+            "decimals": 6,
+            "id": "usdc",
+            "liquidityIndex": "1000000000000000000000000000",
+            "liquidityRate": liquidity_rate,
+            "name": "USD Coin",
+            "price": { "__typename": "PriceOracleAsset", "priceInEth": "100000000" },
+            "stableBorrowRate": "0",
+            "symbol": "USDC",
+            "totalCurrentVariableDebt": "1600000000000",
+            "totalLiquidity": "2000000000000",
+            "utilizationRate": "0.8",
+            "variableBorrowRate": variable_borrow_rate
+        }))
+    }
+
+    #[test]
+    fn test_reserve_tvl_usd() {
+        let reserve = reserve("45000000000000000000000000", "60000000000000000000000000");
+        assert!((reserve_tvl_usd(&reserve).unwrap() - 2_000_000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_reserve_yield_bounds() {
+        let market = &AaveYieldConfig::default().markets[0];
+        let yield_data = reserve_yield(
+            market,
+            reserve("45000000000000000000000000", "60000000000000000000000000"),
+        )
+        .unwrap();
+        assert!((yield_data.deposit_apr - 4.5).abs() < 1e-9);
+        assert_eq!(yield_data.asset, "USDC");
+
+        // A supply rate above the borrow rate, or a rate nobody pays, is a broken index
+        for (liquidity_rate, borrow_rate) in [
+            ("70000000000000000000000000", "60000000000000000000000000"),
+            (
+                "45000000000000000000000000",
+                "60000000000000000000000000000",
+            ),
+            ("-1", "60000000000000000000000000"),
+        ] {
+            let err = reserve_yield(market, reserve(liquidity_rate, borrow_rate)).unwrap_err();
+            assert!(
+                matches!(err, YieldFetchError::OutOfBounds { .. }),
+                "{}",
+                err
+            );
+        }
+        assert!(matches!(
+            reserve_yield(market, reserve("n/a", "0")),
+            Err(YieldFetchError::UnexpectedResponse(_))
+        ));
+    }
+
+    #[test]
+    fn test_ray_rates() {
+        // (liquidityRate, APR, APY per-second compounded), in percent
+        let golden = [
+            ("45000000000000000000000000", 4.5, 4.6027859875133),
+            (
+                "62719876543210987654321098",
+                6.271987654321099,
+                6.472854194709459,
+            ),
+            (
+                "3141592653589793238462643",
+                0.3141592653589793,
+                0.3146532627407408,
+            ),
+            ("0", 0.0, 0.0),
+        ];
+        for (ray_rate, apr, apy) in golden {
+            let parsed = parse_ray_to_apr("aBasUSDC", "liquidityRate", ray_rate).unwrap();
+            assert!((parsed - apr).abs() < 1e-12, "{} -> {}", ray_rate, parsed);
+            assert!(
+                (apr_to_apy(parsed) - apy).abs() < 1e-9,
+                "{}",
+                apr_to_apy(parsed)
+            );
+            assert!((apy_to_apr(apy) - apr).abs() < 1e-9, "{}", apy_to_apr(apy));
+        }
+    }
+
+    #[test]
+    fn test_parse_subgraph_errors() {
+        let ok = reqwest::StatusCode::OK;
+        let err = parse_subgraph_response(
+            ok,
+            r#"{"errors":[{"message":"bad indexers: BadResponse(unattestable)"}]}"#,
+        )
+        .unwrap_err();
+        assert!(matches!(err, YieldFetchError::GraphQl(_)));
+        assert!(err.is_temporary());
+
+        let err = parse_subgraph_response(
+            ok,
+            r#"{"errors":[{"message":"Type `Query` has no field `reservs`"}]}"#,
+        )
+        .unwrap_err();
+        assert!(!err.is_temporary());
+
+        let err = parse_subgraph_response(ok, r#"{"errors":[{"message":"Rate limit exceeded"}]}"#)
+            .unwrap_err();
+        assert!(matches!(err, YieldFetchError::RateLimited(_)));
+        let err = parse_subgraph_response(reqwest::StatusCode::TOO_MANY_REQUESTS, "slow down")
+            .unwrap_err();
+        assert!(err.is_temporary());
+        let err = parse_subgraph_response(reqwest::StatusCode::BAD_GATEWAY, "").unwrap_err();
+        assert!(err.is_temporary());
+
+        let data = parse_subgraph_response(ok, r#"{"data":{"reserves":[]}}"#).unwrap();
+        assert!(data.reserves.is_empty());
+        assert!(matches!(
+            parse_subgraph_response(ok, "{}"),
+            Err(YieldFetchError::UnexpectedResponse(_))
+        ));
+    }
 
     #[tokio::test]
     async fn test_fetch_aave_yields() {
-        let yields = fetch_aave_yields().await.unwrap();
+        dotenv::dotenv().ok();
+        let config = AaveYieldConfig::default();
+        let api_key = std::env::var(&config.graph_api_key_env).unwrap();
+        let yields = fetch_aave_yields(&reqwest::Client::new(), &api_key, &config.markets[0])
+            .await
+            .unwrap();
         assert!(!yields.is_empty());
     }
     #[tokio::test]
     async fn test_get_aave_yields() -> Result<(), Box<dyn Error>> {
-        let aave = Aave {};
+        dotenv::dotenv().ok();
+        let aave = Aave::new(&AaveYieldConfig::default(), reqwest::Client::new());
         let result = aave.get_apr().await?;
         println!("{:?}", result);
         Ok(())
@@ -0,0 +1,198 @@
+use super::{CombinedYields, YieldHistory, YieldRegistry, YieldSourceError, APR};
+use chrono::{DateTime, Duration as TimeDelta, Utc};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+/// APRs served from the cache, with when each source was last fetched.
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedYields {
+    #[serde(flatten)]
+    pub yields: CombinedYields,
+    pub fetched_at: BTreeMap<String, DateTime<Utc>>,
+    /// Sources not fetched successfully for more than two refresh intervals.
+    pub stale: Vec<String>,
+}
+
+impl std::fmt::Display for CachedYields {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.yields)
+    }
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    sources: BTreeMap<String, (DateTime<Utc>, Vec<APR>)>,
+    errors: Vec<YieldSourceError>,
+    refreshed: bool,
+}
+
+/// APRs of the registered yield sources, refreshed in the background so handlers never
+/// wait on the protocols' APIs. A source failing a refresh keeps its last APRs.
+#[derive(Clone)]
+pub struct YieldCache {
+    registry: Arc<YieldRegistry>,
+    refresh_interval: Duration,
+    entries: Arc<Mutex<CacheEntries>>,
+    history: Option<Arc<YieldHistory>>,
+}
+
+impl YieldCache {
+    pub fn new(registry: YieldRegistry, refresh_interval: Duration) -> Self {
+        Self {
+            registry: Arc::new(registry),
+            refresh_interval,
+            entries: Arc::new(Mutex::new(CacheEntries::default())),
+            history: None,
+        }
+    }
+
+    /// Also record every successful fetch in `history`.
+    pub fn with_history(mut self, history: YieldHistory) -> Self {
+        self.history = Some(Arc::new(history));
+        self
+    }
+
+    pub fn history(&self) -> Option<&YieldHistory> {
+        self.history.as_deref()
+    }
+
+    /// Cached APRs, fetched first if the background task has not filled the cache yet.
+    pub async fn get(&self) -> CachedYields {
+        let refreshed = self.lock().refreshed;
+        if !refreshed {
+            self.refresh().await;
+        }
+        self.snapshot(Utc::now())
+    }
+
+    /// Fetch every source now and update the cache with those that succeed. The cache
+    /// only counts as filled once a source succeeded, or there was nothing to fetch.
+    pub async fn refresh(&self) {
+        let yields = self.registry.get_apr().await;
+        let now = Utc::now();
+        if let Some(history) = &self.history {
+            for (name, aprs) in &yields.sources {
+                if let Err(e) = history.record(name, aprs, now) {
+                    println!("Failed to record {} yield history: {}", name, e);
+                }
+            }
+        }
+        let mut entries = self.lock();
+        if !yields.sources.is_empty() || yields.errors.is_empty() {
+            entries.refreshed = true;
+        }
+        for (name, aprs) in yields.sources {
+            entries.sources.insert(name, (now, aprs));
+        }
+        entries.errors = yields.errors;
+    }
+
+    /// Exchange rates of the cached yield-bearing tokens into their underlying, by
+    /// symbol, without fetching anything.
+    pub fn redemption_rates(&self) -> HashMap<String, f64> {
+        self.lock()
+            .sources
+            .values()
+            .flat_map(|(_, aprs)| aprs)
+            .filter_map(|apr| Some((apr.symbol.clone(), apr.redemption.as_ref()?.exchange_rate)))
+            .collect()
+    }
+
+    /// Refresh the cache every refresh interval in a background task.
+    pub fn spawn(&self) -> JoinHandle<()> {
+        let cache = self.clone();
+        tokio::spawn(async move {
+            loop {
+                cache.refresh().await;
+                tokio::time::sleep(cache.refresh_interval).await;
+            }
+        })
+    }
+
+    fn snapshot(&self, now: DateTime<Utc>) -> CachedYields {
+        let max_age = TimeDelta::from_std(self.refresh_interval * 2).unwrap_or(TimeDelta::MAX);
+        let entries = self.lock();
+        let mut cached = CachedYields {
+            yields: CombinedYields {
+                sources: BTreeMap::new(),
+                errors: entries.errors.clone(),
+            },
+            fetched_at: BTreeMap::new(),
+            stale: Vec::new(),
+        };
+        for (name, (fetched_at, aprs)) in &entries.sources {
+            if now - *fetched_at > max_age {
+                cached.stale.push(name.clone());
+            }
+            cached.yields.sources.insert(name.clone(), aprs.clone());
+            cached.fetched_at.insert(name.clone(), *fetched_at);
+        }
+        cached
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CacheEntries> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yields::Yield;
+    use async_trait::async_trait;
+    use std::error::Error;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// Source that fails once `down` is set.
+    struct FlakyYield {
+        down: Arc<AtomicBool>,
+    }
+
+    #[async_trait]
+    impl Yield for FlakyYield {
+        fn get_symbol(&self) -> String {
+            "flaky".to_string()
+        }
+
+        async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
+            if self.down.load(Ordering::SeqCst) {
+                return Err("rate limited".into());
+            }
+            Ok(vec![APR::new("wstETH", 3.0)])
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_keeps_last_good_aprs() {
+        let down = Arc::new(AtomicBool::new(true));
+        let mut registry = YieldRegistry::new();
+        registry.register(Box::new(FlakyYield { down: down.clone() }));
+        let cache = YieldCache::new(registry, Duration::from_secs(60));
+
+        // Nothing fetched yet, so every get tries again
+        let cached = cache.get().await;
+        assert!(cached.yields.sources.is_empty());
+        assert!(!cache.lock().refreshed);
+        down.store(false, Ordering::SeqCst);
+
+        let cached = cache.get().await;
+        assert_eq!(cached.yields.sources["flaky"][0].deposit_apr, 3.0);
+        assert!(cached.yields.errors.is_empty());
+        assert!(cached.stale.is_empty());
+        let fetched_at = cached.fetched_at["flaky"];
+
+        down.store(true, Ordering::SeqCst);
+        cache.refresh().await;
+        let cached = cache.get().await;
+        assert_eq!(cached.yields.sources["flaky"][0].deposit_apr, 3.0);
+        assert_eq!(cached.fetched_at["flaky"], fetched_at);
+        assert_eq!(cached.yields.errors[0].source, "flaky");
+
+        let later = cache.snapshot(fetched_at + TimeDelta::seconds(121));
+        assert_eq!(later.stale, vec!["flaky".to_string()]);
+    }
+}
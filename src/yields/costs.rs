@@ -0,0 +1,330 @@
+use super::APR;
+use crate::config::{DexFilter, YieldCostConfig};
+use crate::executor::chains::{ChainMetadataCache, ChainRegistry, BASE_CHAIN_ID};
+use crate::executor::eisen::quote_amount_out;
+use anyhow::Result;
+use futures::stream::{self, StreamExt};
+use serde::Serialize;
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+/// Quotes in flight at once while estimating slippage.
+const QUOTE_CONCURRENCY: usize = 8;
+
+/// One-off costs of entering a yield and exiting it again.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EntryCosts {
+    /// Loss of swapping the notional into the deposit token and back, in percent.
+    pub slippage_pct: f64,
+    /// Gas of the round trip in USD.
+    pub gas_usd: f64,
+    /// Protocol deposit plus withdraw fee, in percent.
+    pub fee_pct: f64,
+}
+
+impl EntryCosts {
+    /// All costs as a percentage of `notional_usd`.
+    pub fn total_pct(&self, notional_usd: f64) -> f64 {
+        self.slippage_pct + self.fee_pct + self.gas_usd / notional_usd * 100.0
+    }
+
+    /// `deposit_apr` with the costs annualized over a hold of `holding_days`.
+    pub fn effective_apr(&self, deposit_apr: f64, notional_usd: f64, holding_days: f64) -> f64 {
+        deposit_apr - self.total_pct(notional_usd) * 365.0 / holding_days
+    }
+}
+
+/// APR of a yield after entry and exit costs, or why the costs could not be estimated.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetApr {
+    pub source: String,
+    pub symbol: String,
+    pub chain_id: u64,
+    pub deposit_apr: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effective_apr: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub costs: Option<EntryCosts>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Effective APRs of every cached yield at one notional.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetYields {
+    pub notional_usd: f64,
+    pub holding_days: f64,
+    pub aprs: Vec<NetApr>,
+}
+
+impl std::fmt::Display for NetYields {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Effective APRs for ${:.0} held {} days, after swap slippage, gas and protocol fees:",
+            self.notional_usd, self.holding_days
+        )?;
+        for apr in &self.aprs {
+            if let Some(effective_apr) = apr.effective_apr {
+                writeln!(
+                    f,
+                    "  {} {}: {:.2}% (raw {:.2}%)",
+                    apr.source, apr.symbol, effective_apr, apr.deposit_apr
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Prices entering and exiting yields with Eisen quotes and live gas prices.
+///
+/// Yields without a chain are entered on Base, and the token swapped into is the APR's
+/// `asset`, or its symbol for yield-bearing tokens like wstETH. Only the
+/// `max_quoted_yields` best APRs are priced, quoting each token once.
+pub struct CostEstimator<'a> {
+    pub client: &'a reqwest::Client,
+    pub eisen_base_url: &'a str,
+    pub chain_metadata: &'a ChainMetadataCache,
+    pub chains: &'a ChainRegistry,
+    pub dex_filters: &'a HashMap<u64, DexFilter>,
+    pub config: &'a YieldCostConfig,
+    /// USD price of one `config.entry_token`, to turn notionals into token amounts.
+    pub entry_token_usd: f64,
+}
+
+impl CostEstimator<'_> {
+    pub async fn net_yields(&self, aprs: Vec<(&str, &APR)>, notional_usd: f64) -> NetYields {
+        let mut by_apr: Vec<usize> = (0..aprs.len()).collect();
+        by_apr.sort_by(|a, b| aprs[*b].1.deposit_apr.total_cmp(&aprs[*a].1.deposit_apr));
+        by_apr.truncate(self.config.max_quoted_yields);
+        let quoted: HashSet<usize> = by_apr.into_iter().collect();
+
+        let entries: BTreeSet<(u64, &str)> = quoted
+            .iter()
+            .map(|i| (entry_chain(aprs[*i].1), entry_token(aprs[*i].1)))
+            .collect();
+        let chain_ids: BTreeSet<u64> = entries.iter().map(|(chain_id, _)| *chain_id).collect();
+        let gas_costs: Vec<_> = chain_ids
+            .into_iter()
+            .map(|chain_id| async move {
+                let gas_usd = self.gas_cost_usd(chain_id).await;
+                (chain_id, gas_usd.map_err(|e| e.to_string()))
+            })
+            .collect();
+        let gas_costs: HashMap<u64, Result<f64, String>> = stream::iter(gas_costs)
+            .buffer_unordered(QUOTE_CONCURRENCY)
+            .collect()
+            .await;
+        let amount = notional_usd / self.entry_token_usd;
+        let slippages: Vec<_> = entries
+            .into_iter()
+            .map(|(chain_id, token)| async move {
+                let slippage_pct = self.round_trip_slippage_pct(chain_id, token, amount).await;
+                ((chain_id, token), slippage_pct.map_err(|e| e.to_string()))
+            })
+            .collect();
+        let slippages: HashMap<(u64, &str), Result<f64, String>> = stream::iter(slippages)
+            .buffer_unordered(QUOTE_CONCURRENCY)
+            .collect()
+            .await;
+
+        let aprs = aprs
+            .into_iter()
+            .enumerate()
+            .map(|(i, (source, apr))| {
+                let chain_id = entry_chain(apr);
+                let costs = if quoted.contains(&i) {
+                    self.entry_costs(
+                        source,
+                        chain_id,
+                        &gas_costs[&chain_id],
+                        &slippages[&(chain_id, entry_token(apr))],
+                    )
+                } else {
+                    Err(format!(
+                        "Not priced: outside the {} best APRs",
+                        self.config.max_quoted_yields
+                    ))
+                };
+                self.net_apr(source, apr, costs, notional_usd)
+            })
+            .collect();
+
+        NetYields {
+            notional_usd,
+            holding_days: self.config.holding_days,
+            aprs,
+        }
+    }
+
+    fn net_apr(
+        &self,
+        source: &str,
+        apr: &APR,
+        costs: Result<EntryCosts, String>,
+        notional_usd: f64,
+    ) -> NetApr {
+        let (costs, error) = match costs {
+            Ok(costs) => (Some(costs), None),
+            Err(error) => (None, Some(error)),
+        };
+        NetApr {
+            source: source.to_string(),
+            symbol: apr.symbol.clone(),
+            chain_id: entry_chain(apr),
+            deposit_apr: apr.deposit_apr,
+            effective_apr: costs.map(|costs| {
+                costs.effective_apr(apr.deposit_apr, notional_usd, self.config.holding_days)
+            }),
+            costs,
+            error,
+        }
+    }
+
+    fn entry_costs(
+        &self,
+        source: &str,
+        chain_id: u64,
+        gas_usd: &Result<f64, String>,
+        slippage_pct: &Result<f64, String>,
+    ) -> Result<EntryCosts, String> {
+        let gas_usd = gas_usd
+            .as_ref()
+            .map_err(|e| format!("No gas price for chain {}: {}", chain_id, e))?;
+        let slippage_pct = slippage_pct.clone()?;
+        let fees = self
+            .config
+            .protocol_fees
+            .get(source)
+            .copied()
+            .unwrap_or_default();
+        Ok(EntryCosts {
+            slippage_pct,
+            gas_usd: *gas_usd,
+            fee_pct: (fees.deposit_bps + fees.withdraw_bps) as f64 / 100.0,
+        })
+    }
+
+    /// Percent of `amount` of the entry token lost swapping it into `token` and back.
+    async fn round_trip_slippage_pct(
+        &self,
+        chain_id: u64,
+        token: &str,
+        amount: f64,
+    ) -> Result<f64> {
+        let chain_data = self.chain_metadata.get(chain_id).await?;
+        let entry_token = self.config.entry_token.as_str();
+        let (token_addr, _) = chain_data.token(token)?;
+        let (entry_token_addr, _) = chain_data.token(entry_token)?;
+        if token_addr.eq_ignore_ascii_case(entry_token_addr) {
+            return Ok(0.0);
+        }
+
+        let dex_filter = self.dex_filter(chain_id);
+        let amount_out = quote_amount_out(
+            self.client,
+            self.eisen_base_url,
+            &chain_data,
+            entry_token,
+            token,
+            amount,
+            &dex_filter,
+        )
+        .await?;
+        let returned = quote_amount_out(
+            self.client,
+            self.eisen_base_url,
+            &chain_data,
+            token,
+            entry_token,
+            amount_out,
+            &dex_filter,
+        )
+        .await?;
+        Ok(((amount - returned) / amount * 100.0).max(0.0))
+    }
+
+    /// USD cost of the round trip gas at the chain's current gas price.
+    async fn gas_cost_usd(&self, chain_id: u64) -> Result<f64> {
+        let provider = self.chains.read_provider(chain_id)?;
+        let gas_price = provider.get_gas_price().await?;
+        let native_cost = gas_price as f64 * self.config.round_trip_gas as f64 / 1e18;
+
+        let chain_data = self.chain_metadata.get(chain_id).await?;
+        let entry_token_cost = quote_amount_out(
+            self.client,
+            self.eisen_base_url,
+            &chain_data,
+            chain_data.native_symbol(),
+            &self.config.entry_token,
+            native_cost,
+            &self.dex_filter(chain_id),
+        )
+        .await?;
+        Ok(entry_token_cost * self.entry_token_usd)
+    }
+
+    fn dex_filter(&self, chain_id: u64) -> DexFilter {
+        self.dex_filters.get(&chain_id).cloned().unwrap_or_default()
+    }
+}
+
+fn entry_chain(apr: &APR) -> u64 {
+    apr.chain_id.unwrap_or(BASE_CHAIN_ID)
+}
+
+fn entry_token(apr: &APR) -> &str {
+    apr.asset.as_deref().unwrap_or(&apr.symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_effective_apr() {
+        let costs = EntryCosts {
+            slippage_pct: 0.1,
+            gas_usd: 5.0,
+            fee_pct: 0.05,
+        };
+        // 0.1% + 0.05% + $5 of $10k is 0.2%, paid 12.17 times a year over 30 day holds
+        assert!((costs.total_pct(10_000.0) - 0.2).abs() < 1e-12);
+        let effective_apr = costs.effective_apr(8.0, 10_000.0, 30.0);
+        assert!((effective_apr - (8.0 - 0.2 * 365.0 / 30.0)).abs() < 1e-12);
+        // Gas matters less at size
+        assert!(costs.effective_apr(8.0, 1_000_000.0, 30.0) > effective_apr);
+    }
+
+    #[tokio::test]
+    async fn test_quotes_only_the_best_aprs() {
+        let client = reqwest::Client::new();
+        let chain_metadata =
+            ChainMetadataCache::new(String::new(), client.clone(), Duration::from_secs(60));
+        // Nothing to quote, so nothing is fetched
+        let config = YieldCostConfig {
+            max_quoted_yields: 0,
+            ..YieldCostConfig::default()
+        };
+        let estimator = CostEstimator {
+            client: &client,
+            eisen_base_url: "",
+            chain_metadata: &chain_metadata,
+            chains: &ChainRegistry::new(HashMap::new()),
+            dex_filters: &HashMap::new(),
+            config: &config,
+            entry_token_usd: 1.0,
+        };
+        let apr = APR::new("USDC", 4.0);
+        let net = estimator.net_yields(vec![("aave", &apr)], 10_000.0).await;
+        assert_eq!(net.aprs[0].effective_apr, None);
+        assert_eq!(
+            net.aprs[0].error.as_deref(),
+            Some("Not priced: outside the 0 best APRs")
+        );
+    }
+}
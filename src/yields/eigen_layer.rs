@@ -1,52 +1,12 @@
 // // https://api.eigenexplorer.com/stakers/{address}
 
-// // etherfi
-// // https://api.sevenseas.capital/info
-// // ['ethereum', 'arbitrum', 'avalanche', 'base', 'corn', 'bnb', 'sonic', 'swell', 'bob'
-// // https://api.sevenseas.capital/etherfi/apy/<network>/<vault_address>
-// //
-
-// // symbiotic etherfi
-// // https://api.sevenseas.capital/etherfi/ethereum/performance/0x917ceE801a67f933F2e6b33fC0cD1ED2d5909D88
-// // karak etherfi
-// // https://api.sevenseas.capital/etherfi/ethereum/performance/0x7223442cad8e9cA474fC40109ab981608F8c4273
-
-// // https://app.ether.fi/api/portfolio/v3/{address}
-// use reqwest::Error;
-// use serde::Deserialize;
-
-// struct Protocols {
-//     data: HashMap<String, f64>,
-// }
-
-// #[derive(Deserialize, Debug)]
-// struct VaultData {
-//     address: String,
-//     block: u64,
-//     timestamp: String,
-//     apy: Protocols,
-//     #[serde(rename = "7_day_apy")]
-//     seven_day_apy: Protocols,
-//     #[serde(rename = "14_day_apy")]
-//     fourteen_day_apy: Protocols,
-//     #[serde(rename = "30_day_apy")]
-//     thirty_day_apy: Protocols,
-//     allocation: Protocols,
-// }
-
-// #[derive(Deserialize, Debug)]
-// struct ApiResponse {
-//     response: Vec<VaultData>,
-// }
-
-// // kelp dao
-// // https://universe.kelpdao.xyz/rseth/totalApy
-// // https://universe.kelpdao.xyz/rseth/gainApy
-use super::{Yield, APR};
+use super::{RiskTier, Yield, APR};
+use crate::config::EigenYieldConfig;
 use async_trait::async_trait;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, Duration as TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
 use std::error::Error;
+use std::time::{Duration, Instant};
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct EigenYield {
@@ -60,9 +20,13 @@ pub struct EigenYield {
     pub eth_eigen_price_ratio: f64,
 }
 
+const DUNE_API_URL: &str = "https://api.dune.com/api/v1";
+
 // Define structures to parse the Dune API response
 #[derive(Debug, Deserialize)]
 struct DuneResponse {
+    /// When the execution that produced the result finished.
+    execution_ended_at: Option<DateTime<Utc>>,
     result: DuneResult,
 }
 
@@ -81,29 +45,157 @@ struct DuneRow {
     eigen_staking_apr: f64,
     eth_eigen_price_ratio: f64,
 }
-async fn fetch_eigen_apr() -> Result<EigenYield, Box<dyn Error>> {
-    // You'll need to get an API key from Dune Analytics
-    let dune_api_key = std::env::var("DUNE_API_KEY").expect("DUNE_API_KEY must be set");
-    let client = reqwest::Client::new();
-
-    // Query ID for the APR calculation
-    let query_id = "4127474"; // Updated to your actual query ID
-    let response = client
-        .get(format!(
-            "https://api.dune.com/api/v1/query/{}/results?limit=1000",
-            query_id
-        ))
-        .header("X-Dune-API-Key", &dune_api_key)
-        .send()
-        .await?;
-
-    let data: DuneResponse = response.json().await?;
 
+#[derive(Debug, Deserialize)]
+struct ExecuteResponse {
+    execution_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct ExecutionStatus {
+    /// e.g. "QUERY_STATE_EXECUTING", "QUERY_STATE_COMPLETED", "QUERY_STATE_FAILED".
+    state: String,
+    is_execution_finished: bool,
+}
+
+const QUERY_STATE_COMPLETED: &str = "QUERY_STATE_COMPLETED";
+
+/// EigenLayer EIGEN and ETH restaking APRs from a Dune query. The query's saved
+/// result is used while it is fresh; an older one is refreshed by executing the query.
+#[derive(Debug)]
+pub struct Eigen {
+    client: reqwest::Client,
+    api_key: String,
+    config: EigenYieldConfig,
+}
+
+impl Eigen {
+    /// `None` when the Dune API key is not set.
+    pub fn new(config: &EigenYieldConfig, client: reqwest::Client) -> Option<Self> {
+        let api_key = std::env::var(&config.dune_api_key_env).ok()?;
+        Some(Self {
+            client,
+            api_key,
+            config: config.clone(),
+        })
+    }
+
+    async fn fetch_eigen_apr(&self) -> Result<EigenYield, Box<dyn Error>> {
+        let saved = self
+            .get_results(
+                &self.client,
+                &format!("query/{}/results?limit=1000", self.config.query_id),
+            )
+            .await?;
+
+        let max_age = TimeDelta::seconds(self.config.max_result_age_secs as i64);
+        let response = if is_stale(saved.execution_ended_at, Utc::now(), max_age) {
+            match self.execute_query(&self.client).await {
+                Ok(response) => response,
+                Err(e) => {
+                    println!(
+                        "Executing Dune query {} failed, using its saved result: {}",
+                        self.config.query_id, e
+                    );
+                    saved
+                }
+            }
+        } else {
+            saved
+        };
+        eigen_yield(response)
+    }
+
+    /// Execute the query and wait for its result.
+    async fn execute_query(
+        &self,
+        client: &reqwest::Client,
+    ) -> Result<DuneResponse, Box<dyn Error>> {
+        let execution = client
+            .post(format!(
+                "{}/query/{}/execute",
+                DUNE_API_URL, self.config.query_id
+            ))
+            .header("X-Dune-API-Key", &self.api_key)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<ExecuteResponse>()
+            .await?;
+        println!(
+            "Executing Dune query {} as {}",
+            self.config.query_id, execution.execution_id
+        );
+
+        let deadline = Instant::now() + Duration::from_secs(self.config.execution_timeout_secs);
+        loop {
+            let status = client
+                .get(format!(
+                    "{}/execution/{}/status",
+                    DUNE_API_URL, execution.execution_id
+                ))
+                .header("X-Dune-API-Key", &self.api_key)
+                .send()
+                .await?
+                .error_for_status()?
+                .json::<ExecutionStatus>()
+                .await?;
+            if status.is_execution_finished {
+                if status.state != QUERY_STATE_COMPLETED {
+                    return Err(format!(
+                        "Dune execution {} ended in {}",
+                        execution.execution_id, status.state
+                    )
+                    .into());
+                }
+                break;
+            }
+            if Instant::now() >= deadline {
+                return Err(format!(
+                    "Dune execution {} still {} after {}s",
+                    execution.execution_id, status.state, self.config.execution_timeout_secs
+                )
+                .into());
+            }
+            tokio::time::sleep(Duration::from_secs(self.config.poll_interval_secs)).await;
+        }
+
+        self.get_results(
+            client,
+            &format!("execution/{}/results", execution.execution_id),
+        )
+        .await
+    }
+
+    async fn get_results(
+        &self,
+        client: &reqwest::Client,
+        path: &str,
+    ) -> Result<DuneResponse, Box<dyn Error>> {
+        let response = client
+            .get(format!("{}/{}", DUNE_API_URL, path))
+            .header("X-Dune-API-Key", &self.api_key)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<DuneResponse>()
+            .await?;
+        Ok(response)
+    }
+}
+
+/// Whether a result from an execution that ended at `ended_at` is older than `max_age`.
+/// A result without an end time is always refreshed.
+fn is_stale(ended_at: Option<DateTime<Utc>>, now: DateTime<Utc>, max_age: TimeDelta) -> bool {
+    ended_at.is_none_or(|ended_at| now - ended_at > max_age)
+}
+
+fn eigen_yield(response: DuneResponse) -> Result<EigenYield, Box<dyn Error>> {
     // Extract the first row which contains the APR data
-    let row = data.result.rows.first().ok_or("No data found")?;
+    let row = response.result.rows.first().ok_or("No data found")?;
 
     Ok(EigenYield {
-        date: Utc::now(), // Current time as we don't have a specific date in the response
+        date: response.execution_ended_at.unwrap_or_else(Utc::now),
         eth_staking_apr: row.eth_staking_apr,
         eigen_staking_apr: row.eigen_staking_apr,
         total_eth_tvl: row.total_eth_tvl,
@@ -114,26 +206,22 @@ async fn fetch_eigen_apr() -> Result<EigenYield, Box<dyn Error>> {
     })
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Eigen {}
 #[async_trait]
 impl Yield for Eigen {
-    fn get_symbol() -> String {
+    fn get_symbol(&self) -> String {
         "eigenlayer".to_string()
     }
 
     async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
-        let apr = fetch_eigen_apr().await?;
+        let apr = self.fetch_eigen_apr().await?;
         Ok(vec![
             APR {
-                symbol: "StrategyBase(EIGEN)".to_string(),
-                deposit_apr: apr.eigen_staking_apr,
-                borrow_apr: None,
+                risk_tier: Some(RiskTier::Medium),
+                ..APR::new("StrategyBase(EIGEN)", apr.eigen_staking_apr)
             },
             APR {
-                symbol: "StrategyBase(ETH)".to_string(),
-                deposit_apr: apr.eth_staking_apr,
-                borrow_apr: None,
+                risk_tier: Some(RiskTier::Medium),
+                ..APR::new("StrategyBase(ETH)", apr.eth_staking_apr)
             },
         ])
     }
@@ -141,11 +229,52 @@ impl Yield for Eigen {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::yields::fixture;
+    use serde_json::json;
+
+    #[test]
+    fn test_saved_result_age() {
+        let response: DuneResponse = fixture(json!({
+            "execution_id": "01JFG",
+            "query_id": 4127474,
+            "state": "QUERY_STATE_COMPLETED",
+            "execution_ended_at": "2026-10-13T06:00:12.724658237Z",
+            "result": { "rows": [{
+                "total_eth_tvl": 4200000.0,
+                "total_eigen_tvl": 900000000.0,
+                "eigen_price_usd": 3.1,
+                "eth_price_usd": 3900.0,
+                "eth_staking_apr": 2.4,
+                "eigen_staking_apr": 7.8,
+                "eth_eigen_price_ratio": 1258.06
+            }] }
+        }));
+        let ended_at = response.execution_ended_at;
+        let day = TimeDelta::days(1);
+        let now = "2026-10-14T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        assert!(!is_stale(ended_at, now, day));
+        assert!(is_stale(ended_at, now + day, day));
+        assert!(is_stale(None, now, day));
+
+        let eigen = eigen_yield(response).unwrap();
+        assert_eq!(eigen.eigen_staking_apr, 7.8);
+        assert_eq!(Some(eigen.date), ended_at);
+    }
+
+    #[test]
+    fn test_skipped_without_api_key() {
+        let config = EigenYieldConfig {
+            dune_api_key_env: "CHILL_PM_TEST_UNSET_DUNE_API_KEY".to_string(),
+            ..EigenYieldConfig::default()
+        };
+        assert!(Eigen::new(&config, reqwest::Client::new()).is_none());
+    }
 
     #[tokio::test]
     async fn test_fetch_eigen_apr() {
         dotenv::dotenv().ok();
-        let apr = fetch_eigen_apr().await.unwrap();
+        let eigen = Eigen::new(&EigenYieldConfig::default(), reqwest::Client::new()).unwrap();
+        let apr = eigen.fetch_eigen_apr().await.unwrap();
         println!("Eigen Layer APR: {:.2}%", apr.eigen_staking_apr);
     }
 }
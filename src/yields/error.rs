@@ -0,0 +1,51 @@
+use thiserror::Error;
+
+/// Why a yield source's API response could not be turned into APRs.
+#[derive(Debug, Error)]
+pub enum YieldFetchError {
+    /// The request did not get a response.
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    /// The API or gateway rate limited us.
+    #[error("rate limited: {0}")]
+    RateLimited(String),
+    /// Non-success HTTP status.
+    #[error("HTTP {status}: {body}")]
+    Http { status: u16, body: String },
+    /// The GraphQL response carried an `errors` array.
+    #[error("GraphQL errors: {}", .0.join("; "))]
+    GraphQl(Vec<String>),
+    /// The response did not match the expected schema.
+    #[error("unexpected response: {0}")]
+    UnexpectedResponse(String),
+    /// A value outside the bounds any real market would have.
+    #[error("{symbol} {field} of {value} is out of bounds")]
+    OutOfBounds {
+        symbol: String,
+        field: &'static str,
+        value: f64,
+    },
+}
+
+/// Substrings of The Graph's errors for indexers that are syncing, overloaded or
+/// unreachable, which go away on a retry.
+const TEMPORARY_GRAPHQL_ERRORS: [&str; 5] =
+    ["indexer", "indexing", "timeout", "timed out", "unavailable"];
+
+impl YieldFetchError {
+    /// Whether retrying the same request may succeed.
+    pub fn is_temporary(&self) -> bool {
+        match self {
+            YieldFetchError::Request(err) => err.is_timeout() || err.is_connect(),
+            YieldFetchError::RateLimited(_) => true,
+            YieldFetchError::Http { status, .. } => *status >= 500,
+            YieldFetchError::GraphQl(messages) => messages.iter().any(|message| {
+                let message = message.to_lowercase();
+                TEMPORARY_GRAPHQL_ERRORS
+                    .iter()
+                    .any(|needle| message.contains(needle))
+            }),
+            YieldFetchError::UnexpectedResponse(_) | YieldFetchError::OutOfBounds { .. } => false,
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use super::{RiskTier, Yield, APR};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::error::Error;
+
+const SEVENSEAS_API_URL: &str = "https://api.sevenseas.capital";
+
+/// EtherFi weETH restaking vaults run by Seven Seas: network, vault address, symbol.
+/// All of them live on Ethereum mainnet.
+const ETHERFI_VAULTS: [(&str, &str, &str); 2] = [
+    // Symbiotic
+    (
+        "ethereum",
+        "0x917ceE801a67f933F2e6b33fC0cD1ED2d5909D88",
+        "weETHs",
+    ),
+    // Karak
+    (
+        "ethereum",
+        "0x7223442cad8e9cA474fC40109ab981608F8c4273",
+        "weETHk",
+    ),
+];
+
+#[derive(Debug, Deserialize)]
+struct ApiResponse {
+    #[serde(alias = "Response")]
+    response: Vec<VaultData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct VaultData {
+    /// APY contributed by each protocol the vault allocates to, in percent.
+    #[serde(rename = "7_day_apy")]
+    seven_day_apy: HashMap<String, f64>,
+}
+
+impl VaultData {
+    fn total_apy(&self) -> f64 {
+        self.seven_day_apy.values().sum()
+    }
+}
+
+/// 7 day APYs of the EtherFi weETH vaults.
+#[derive(Debug)]
+pub struct EtherFi {
+    client: reqwest::Client,
+}
+
+impl EtherFi {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl Yield for EtherFi {
+    fn get_symbol(&self) -> String {
+        "etherfi".to_string()
+    }
+
+    async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
+        let mut aprs = Vec::with_capacity(ETHERFI_VAULTS.len());
+        for (network, vault, symbol) in ETHERFI_VAULTS {
+            let vault_data = fetch_vault_apy(&self.client, network, vault).await?;
+            aprs.push(APR {
+                chain_id: Some(1),
+                risk_tier: Some(RiskTier::High),
+                ..APR::from_apy(symbol.to_string(), vault_data.total_apy())
+            });
+        }
+        Ok(aprs)
+    }
+}
+
+async fn fetch_vault_apy(
+    client: &reqwest::Client,
+    network: &str,
+    vault: &str,
+) -> Result<VaultData, Box<dyn Error>> {
+    let response = client
+        .get(format!(
+            "{}/etherfi/apy/{}/{}",
+            SEVENSEAS_API_URL, network, vault
+        ))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<ApiResponse>()
+        .await?;
+    // Entries are newest first
+    response
+        .response
+        .into_iter()
+        .next()
+        .ok_or_else(|| format!("No APY data for vault {} on {}", vault, network).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yields::fixture;
+    use serde_json::json;
+
+    #[test]
+    fn test_vault_apy() {
+        let response: ApiResponse = fixture(json!({
+            "Response": [{
+                "address": "0x917ceE801a67f933F2e6b33fC0cD1ED2d5909D88",
+                "block": 21000000,
+                "timestamp": "2024-10-20T00:00:00Z",
+                "apy": { "symbiotic": 3.1, "weeth": 2.9 },
+                "7_day_apy": { "symbiotic": 3.0, "weeth": 2.75 },
+                "allocation": { "symbiotic": 100.0 }
+            }]
+        }));
+        assert!((response.response[0].total_apy() - 5.75).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,220 @@
+use super::{CombinedYields, APR};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Field APRs are ranked by, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum YieldSort {
+    DepositApr,
+    TvlUsd,
+}
+
+/// Subset of the combined yields a client asked for.
+#[derive(Debug, Clone, Default)]
+pub struct YieldFilter {
+    /// Case-insensitive substring of the APR's symbol or asset.
+    pub symbol: Option<String>,
+    pub chain_id: Option<u64>,
+    pub min_apr: Option<f64>,
+    pub sort: Option<YieldSort>,
+    /// Most APRs returned across all sources, after sorting.
+    pub limit: Option<usize>,
+}
+
+impl YieldFilter {
+    fn matches(&self, apr: &APR) -> bool {
+        let symbol_matches = self.symbol.as_deref().is_none_or(|symbol| {
+            let symbol = symbol.to_lowercase();
+            apr.symbol.to_lowercase().contains(&symbol)
+                || apr
+                    .asset
+                    .as_deref()
+                    .is_some_and(|asset| asset.to_lowercase().contains(&symbol))
+        });
+        symbol_matches
+            && self
+                .chain_id
+                .is_none_or(|chain_id| apr.chain_id == Some(chain_id))
+            && self
+                .min_apr
+                .is_none_or(|min_apr| apr.deposit_apr >= min_apr)
+    }
+}
+
+/// An APR with the source it comes from.
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RankedApr {
+    pub source: String,
+    #[serde(flatten)]
+    pub apr: APR,
+}
+
+impl CombinedYields {
+    /// Every APR with its source, in source order.
+    pub fn all(&self) -> Vec<(&str, &APR)> {
+        self.sources
+            .iter()
+            .flat_map(|(source, aprs)| aprs.iter().map(move |apr| (source.as_str(), apr)))
+            .collect()
+    }
+
+    /// The APRs of every source matching `filter` in one list, ordered by `filter.sort`
+    /// and cut to `filter.limit`.
+    pub fn ranked(&self, filter: &YieldFilter) -> Vec<RankedApr> {
+        let mut aprs: Vec<(&str, &APR)> = self
+            .all()
+            .into_iter()
+            .filter(|(_, apr)| filter.matches(apr))
+            .collect();
+        match filter.sort {
+            Some(YieldSort::DepositApr) => {
+                aprs.sort_by(|(_, a), (_, b)| b.deposit_apr.total_cmp(&a.deposit_apr))
+            }
+            // APRs without a TVL rank last
+            Some(YieldSort::TvlUsd) => aprs.sort_by(|(_, a), (_, b)| {
+                b.tvl_usd
+                    .unwrap_or(f64::NEG_INFINITY)
+                    .total_cmp(&a.tvl_usd.unwrap_or(f64::NEG_INFINITY))
+            }),
+            None => {}
+        }
+        if let Some(limit) = filter.limit {
+            aprs.truncate(limit);
+        }
+        aprs.into_iter()
+            .map(|(source, apr)| RankedApr {
+                source: source.to_string(),
+                apr: apr.clone(),
+            })
+            .collect()
+    }
+
+    /// The APRs `ranked` keeps, grouped by source again and in their ranked order within
+    /// each. Sources left without APRs are dropped; errors are kept.
+    pub fn filtered(&self, filter: &YieldFilter) -> CombinedYields {
+        let mut sources: BTreeMap<String, Vec<APR>> = BTreeMap::new();
+        for ranked in self.ranked(filter) {
+            sources.entry(ranked.source).or_default().push(ranked.apr);
+        }
+        CombinedYields {
+            sources,
+            errors: self.errors.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apr(symbol: &str, deposit_apr: f64, chain_id: Option<u64>) -> APR {
+        APR {
+            chain_id,
+            ..APR::new(symbol, deposit_apr)
+        }
+    }
+
+    fn yields() -> CombinedYields {
+        let mut usdc = apr("Morpho USDC Prime", 6.0, Some(8453));
+        usdc.asset = Some("USDC".to_string());
+        CombinedYields {
+            sources: BTreeMap::from([
+                (
+                    "aave".to_string(),
+                    vec![apr("USDC", 4.0, Some(1)), apr("WETH", 2.0, Some(1))],
+                ),
+                ("lido".to_string(), vec![apr("stETH", 3.0, None)]),
+                ("morpho".to_string(), vec![usdc]),
+            ]),
+            errors: vec![],
+        }
+    }
+
+    fn symbols(yields: &CombinedYields) -> Vec<(&str, &str)> {
+        yields
+            .sources
+            .iter()
+            .flat_map(|(source, aprs)| {
+                aprs.iter()
+                    .map(move |apr| (source.as_str(), apr.symbol.as_str()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_by_symbol_chain_and_min_apr() {
+        let yields = yields();
+        let usdc = yields.filtered(&YieldFilter {
+            symbol: Some("usdc".to_string()),
+            ..Default::default()
+        });
+        assert_eq!(
+            symbols(&usdc),
+            vec![("aave", "USDC"), ("morpho", "Morpho USDC Prime")]
+        );
+
+        let mainnet = yields.filtered(&YieldFilter {
+            chain_id: Some(1),
+            min_apr: Some(3.0),
+            ..Default::default()
+        });
+        assert_eq!(symbols(&mainnet), vec![("aave", "USDC")]);
+    }
+
+    #[test]
+    fn test_sort_and_limit() {
+        let top = yields().filtered(&YieldFilter {
+            sort: Some(YieldSort::DepositApr),
+            limit: Some(3),
+            ..Default::default()
+        });
+        // WETH is cut; lido and morpho keep their only APR
+        assert_eq!(
+            symbols(&top),
+            vec![
+                ("aave", "USDC"),
+                ("lido", "stETH"),
+                ("morpho", "Morpho USDC Prime")
+            ]
+        );
+        assert_eq!(top.sources.len(), 3);
+    }
+
+    #[test]
+    fn test_ranked_across_sources() {
+        let yields = yields();
+        let ranked = |filter: &YieldFilter| -> Vec<(String, String)> {
+            yields
+                .ranked(filter)
+                .into_iter()
+                .map(|ranked| (ranked.source, ranked.apr.symbol))
+                .collect()
+        };
+        let pair = |source: &str, symbol: &str| (source.to_string(), symbol.to_string());
+        // Sorted over every source, not within each, then cut
+        assert_eq!(
+            ranked(&YieldFilter {
+                sort: Some(YieldSort::DepositApr),
+                limit: Some(2),
+                ..Default::default()
+            }),
+            vec![pair("morpho", "Morpho USDC Prime"), pair("aave", "USDC")]
+        );
+        // The limit applies to what the filter leaves
+        assert_eq!(
+            ranked(&YieldFilter {
+                min_apr: Some(2.5),
+                sort: Some(YieldSort::DepositApr),
+                limit: Some(5),
+                ..Default::default()
+            }),
+            vec![
+                pair("morpho", "Morpho USDC Prime"),
+                pair("aave", "USDC"),
+                pair("lido", "stETH")
+            ]
+        );
+    }
+}
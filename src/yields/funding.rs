@@ -0,0 +1,165 @@
+use super::{RiskTier, Yield, APR};
+use crate::config::FundingYieldConfig;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::error::Error;
+
+const MS_PER_YEAR: f64 = 365.0 * 24.0 * 60.0 * 60.0 * 1000.0;
+
+/// Funding interval of most USDⓈ-M perps, used until the history shows another one.
+const DEFAULT_FUNDING_INTERVAL_MS: u64 = 8 * 60 * 60 * 1000;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PremiumIndex {
+    last_funding_rate: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FundingRate {
+    funding_rate: String,
+    funding_time: u64,
+}
+
+/// Carry of shorting Binance perps: positive while longs pay shorts, annualized so it
+/// compares directly to deposit APRs.
+#[derive(Debug)]
+pub struct BinanceFunding {
+    client: reqwest::Client,
+    base_url: String,
+    symbols: Vec<String>,
+    trailing_periods: u32,
+}
+
+impl BinanceFunding {
+    pub fn new(config: &FundingYieldConfig, client: reqwest::Client) -> Self {
+        Self {
+            client,
+            base_url: config.base_url.clone(),
+            symbols: config.symbols.clone(),
+            trailing_periods: config.trailing_periods,
+        }
+    }
+
+    async fn symbol_aprs(
+        &self,
+        client: &reqwest::Client,
+        symbol: &str,
+    ) -> Result<Vec<APR>, Box<dyn Error>> {
+        let index = client
+            .get(format!("{}/fapi/v1/premiumIndex", self.base_url))
+            .query(&[("symbol", symbol)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<PremiumIndex>()
+            .await?;
+        let history = client
+            .get(format!("{}/fapi/v1/fundingRate", self.base_url))
+            .query(&[
+                ("symbol", symbol.to_string()),
+                ("limit", self.trailing_periods.to_string()),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Vec<FundingRate>>()
+            .await?;
+
+        let interval_ms = funding_interval_ms(&history);
+        let current = index.last_funding_rate.parse::<f64>()?;
+        let mut aprs = vec![funding_apr(
+            format!("{} short funding (current)", symbol),
+            current,
+            interval_ms,
+        )];
+        if let Some(average) = average_rate(&history)? {
+            aprs.push(funding_apr(
+                format!("{} short funding (last {} periods)", symbol, history.len()),
+                average,
+                interval_ms,
+            ));
+        }
+        Ok(aprs)
+    }
+}
+
+#[async_trait]
+impl Yield for BinanceFunding {
+    fn get_symbol(&self) -> String {
+        "binance_funding".to_string()
+    }
+
+    async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
+        let mut aprs = Vec::new();
+        for symbol in &self.symbols {
+            aprs.extend(self.symbol_aprs(&self.client, symbol).await?);
+        }
+        Ok(aprs)
+    }
+}
+
+/// Shortest gap between consecutive fundings, so a perp moved to 4h funding is not
+/// annualized as if it paid every 8h.
+fn funding_interval_ms(history: &[FundingRate]) -> u64 {
+    history
+        .windows(2)
+        .map(|pair| pair[1].funding_time.saturating_sub(pair[0].funding_time))
+        .filter(|gap| *gap > 0)
+        .min()
+        // Funding times carry a few ms of jitter; round to the minute
+        .map(|gap| (gap + 30_000) / 60_000 * 60_000)
+        .unwrap_or(DEFAULT_FUNDING_INTERVAL_MS)
+}
+
+fn average_rate(history: &[FundingRate]) -> Result<Option<f64>, Box<dyn Error>> {
+    if history.is_empty() {
+        return Ok(None);
+    }
+    let mut total = 0.0;
+    for rate in history {
+        total += rate.funding_rate.parse::<f64>()?;
+    }
+    Ok(Some(total / history.len() as f64))
+}
+
+/// Funding `rate` per `interval_ms` period as an annual percentage.
+fn funding_apr(symbol: String, rate: f64, interval_ms: u64) -> APR {
+    APR {
+        risk_tier: Some(RiskTier::Medium),
+        ..APR::new(symbol, rate * (MS_PER_YEAR / interval_ms as f64) * 100.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yields::fixture;
+    use serde_json::json;
+
+    #[test]
+    fn test_funding_apr() {
+        let history: Vec<FundingRate> = fixture(json!([
+            { "symbol": "BTCUSDT", "fundingRate": "0.00010000", "fundingTime": 1700000000001u64, "markPrice": "37000" },
+            { "symbol": "BTCUSDT", "fundingRate": "0.00030000", "fundingTime": 1700028800003u64, "markPrice": "37100" }
+        ]));
+        assert_eq!(funding_interval_ms(&history), DEFAULT_FUNDING_INTERVAL_MS);
+        assert_eq!(
+            funding_interval_ms(&history[..1]),
+            DEFAULT_FUNDING_INTERVAL_MS
+        );
+        let average = average_rate(&history).unwrap().unwrap();
+        assert!((average - 0.0002).abs() < 1e-12);
+
+        // 0.01% every 8h is 10.95% a year
+        let apr = funding_apr("BTCUSDT".to_string(), 0.0001, DEFAULT_FUNDING_INTERVAL_MS);
+        assert!((apr.deposit_apr - 10.95).abs() < 1e-9);
+        let apr = funding_apr(
+            "BTCUSDT".to_string(),
+            0.0001,
+            DEFAULT_FUNDING_INTERVAL_MS / 2,
+        );
+        assert!((apr.deposit_apr - 21.9).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,134 @@
+use super::APR;
+use crate::storage::WalStore;
+use anyhow::Result;
+use chrono::{DateTime, Duration as TimeDelta, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+
+/// One APR as fetched from a source at `fetched_at`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AprRecord {
+    pub source: String,
+    pub symbol: String,
+    pub deposit_apr: f64,
+    pub borrow_apr: Option<f64>,
+    pub fetched_at: DateTime<Utc>,
+}
+
+/// Every APR the yield cache fetched within the retention window, persisted in a WAL
+/// store so the history survives restarts.
+pub struct YieldHistory {
+    records: WalStore<AprRecord>,
+    /// Record keys by fetch time, so pruning and queries skip the records they leave.
+    by_time: Mutex<BTreeSet<(DateTime<Utc>, String)>>,
+    retention: TimeDelta,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+impl YieldHistory {
+    pub fn open(data_dir: &Path, retention_days: u32) -> Result<Self> {
+        let records = WalStore::open(data_dir, "yield_history")?;
+        let by_time = records
+            .entries()
+            .into_iter()
+            .map(|(key, record): (String, AprRecord)| (record.fetched_at, key))
+            .collect();
+        Ok(Self {
+            records,
+            by_time: Mutex::new(by_time),
+            retention: TimeDelta::days(retention_days.into()),
+        })
+    }
+
+    /// Store the APRs `source` returned at `fetched_at` and drop records past retention.
+    pub fn record(&self, source: &str, aprs: &[APR], fetched_at: DateTime<Utc>) -> Result<()> {
+        for apr in aprs {
+            let key = format!(
+                "{}:{}:{}",
+                fetched_at.timestamp_millis(),
+                source,
+                apr.symbol
+            );
+            self.records.put(
+                key.clone(),
+                AprRecord {
+                    source: source.to_string(),
+                    symbol: apr.symbol.clone(),
+                    deposit_apr: apr.deposit_apr,
+                    borrow_apr: apr.borrow_apr,
+                    fetched_at,
+                },
+            )?;
+            lock(&self.by_time).insert((fetched_at, key));
+        }
+        self.prune(fetched_at - self.retention)
+    }
+
+    /// Records fetched at or after `since`, oldest first, optionally for one symbol only.
+    pub fn query(&self, symbol: Option<&str>, since: DateTime<Utc>) -> Vec<AprRecord> {
+        lock(&self.by_time)
+            .range((since, String::new())..)
+            .filter_map(|(_, key)| self.records.get(key))
+            .filter(|record| symbol.is_none_or(|symbol| record.symbol == symbol))
+            .collect()
+    }
+
+    fn prune(&self, before: DateTime<Utc>) -> Result<()> {
+        let mut by_time = lock(&self.by_time);
+        while let Some((fetched_at, key)) = by_time.first() {
+            if *fetched_at >= before {
+                break;
+            }
+            self.records.remove(key)?;
+            by_time.pop_first();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn apr(symbol: &str, deposit_apr: f64) -> APR {
+        APR::new(symbol, deposit_apr)
+    }
+
+    #[test]
+    fn test_history_query_and_retention() -> Result<()> {
+        let dir = tempdir()?;
+        let start = "2026-01-01T00:00:00Z".parse::<DateTime<Utc>>()?;
+        {
+            let history = YieldHistory::open(dir.path(), 7)?;
+            history.record("lido", &[apr("stETH", 3.0)], start)?;
+            history.record(
+                "lido",
+                &[apr("stETH", 3.2), apr("wstETH", 3.2)],
+                start + TimeDelta::days(1),
+            )?;
+        }
+
+        let history = YieldHistory::open(dir.path(), 7)?;
+        let steth = history.query(Some("stETH"), start);
+        assert_eq!(
+            steth.iter().map(|r| r.deposit_apr).collect::<Vec<_>>(),
+            vec![3.0, 3.2]
+        );
+        assert_eq!(history.query(None, start + TimeDelta::hours(1)).len(), 2);
+
+        history.record("lido", &[apr("stETH", 3.1)], start + TimeDelta::days(8))?;
+        let steth = history.query(Some("stETH"), start);
+        assert_eq!(
+            steth.iter().map(|r| r.deposit_apr).collect::<Vec<_>>(),
+            vec![3.2, 3.1]
+        );
+        Ok(())
+    }
+}
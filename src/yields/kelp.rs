@@ -0,0 +1,90 @@
+use super::{apy_to_apr, number_or_string, AprBreakdown, RiskTier, Yield, APR};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::error::Error;
+
+const KELP_API_URL: &str = "https://universe.kelpdao.xyz/rseth";
+
+#[derive(Debug, Deserialize)]
+struct ApyResponse {
+    /// APY in percent.
+    #[serde(deserialize_with = "number_or_string")]
+    value: f64,
+}
+
+async fn fetch_kelp_apy(client: &reqwest::Client, metric: &str) -> Result<f64, Box<dyn Error>> {
+    let response = client
+        .get(format!("{}/{}", KELP_API_URL, metric))
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<ApyResponse>()
+        .await?;
+    Ok(response.value)
+}
+
+/// rsETH APR as the total and its split into staking and restaking: `gainApy` is what
+/// restaking earns on top of the underlying LST's staking yield. Kelp quotes APYs.
+fn rseth_apr(total_apy: f64, gain_apy: f64) -> APR {
+    let total_apr = apy_to_apr(total_apy);
+    let restaking_apr = apy_to_apr(gain_apy);
+    APR {
+        breakdown: Some(AprBreakdown {
+            staking_apr: (total_apr - restaking_apr).max(0.0),
+            restaking_apr,
+        }),
+        risk_tier: Some(RiskTier::Medium),
+        ..APR::from_apy("rsETH", total_apy)
+    }
+}
+
+/// Kelp DAO rsETH restaking yield.
+#[derive(Debug)]
+pub struct Kelp {
+    client: reqwest::Client,
+}
+
+impl Kelp {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl Yield for Kelp {
+    fn get_symbol(&self) -> String {
+        "kelp".to_string()
+    }
+
+    async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
+        let total_apy = fetch_kelp_apy(&self.client, "totalApy").await?;
+        let gain_apy = fetch_kelp_apy(&self.client, "gainApy").await?;
+        Ok(vec![rseth_apr(total_apy, gain_apy)])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yields::{assert_quoted_apy, fixture};
+    use serde_json::json;
+
+    #[test]
+    fn test_rseth_apr() {
+        let response: ApyResponse = fixture(json!({ "value": "3.75" }));
+        let apr = rseth_apr(response.value, 0.75);
+        // Kelp quotes APYs, split as APRs
+        assert_quoted_apy(&apr, 3.75);
+        assert_eq!(
+            apr.breakdown,
+            Some(AprBreakdown {
+                staking_apr: apy_to_apr(3.75) - apy_to_apr(0.75),
+                restaking_apr: apy_to_apr(0.75)
+            })
+        );
+        assert_eq!(
+            apr.to_string(),
+            "rsETH: Deposit APR: 3.68% (APY 3.75%) (Staking: 2.93%, Restaking: 0.75%), Risk: medium"
+        );
+    }
+}
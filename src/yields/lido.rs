@@ -1,8 +1,34 @@
-use super::{Yield, APR};
+use super::{Redemption, RiskTier, Yield, APR};
+use crate::utils::rounding::units_to_f64;
+use alloy::network::TransactionBuilder;
+use alloy::primitives::{address, Address, U256};
+use alloy::providers::{Provider, ProviderBuilder};
+use alloy::rpc::types::TransactionRequest;
+use alloy::sol;
+use alloy::sol_types::SolCall;
 use async_trait::async_trait;
 use reqwest::Error as ReqwestError;
 use serde::Deserialize;
 use std::error::Error;
+
+const WSTETH_ADDRESS: Address = address!("7f39C581F595B53c5cb19bD0b3f8dA6c935E2Ca0");
+const WITHDRAWAL_QUEUE_ADDRESS: Address = address!("889edC2eDab5f40e902b864aD4d7AdE8E412F9B1");
+
+/// Lido's estimate of how long a withdrawal requested now waits to be finalized.
+const WITHDRAWAL_TIME_URL: &str = "https://wq-api.lido.fi/v2/request-time/calculate";
+
+sol! {
+    interface IWstETH {
+        function stEthPerToken() external view returns (uint256);
+    }
+
+    interface IWithdrawalQueue {
+        function unfinalizedStETH() external view returns (uint256);
+        function getLastRequestId() external view returns (uint256);
+        function getLastFinalizedRequestId() external view returns (uint256);
+    }
+}
+
 #[derive(Deserialize, Debug)]
 struct AprData {
     timeUnix: u64,
@@ -30,9 +56,9 @@ struct StethMeta {
 
 /// Fetches the current stETH APR from Lido's API
 /// Returns the SMA (Simple Moving Average) APR as a percentage
-async fn fetch_steth_apr() -> Result<f64, ReqwestError> {
+async fn fetch_steth_apr(client: &reqwest::Client) -> Result<f64, ReqwestError> {
     let url = "https://eth-api.lido.fi/v1/protocol/steth/apr/sma";
-    let response = reqwest::get(url).await?;
+    let response = client.get(url).send().await?;
 
     let apr_data: StethAprResponse = response.json().await?;
 
@@ -42,9 +68,9 @@ async fn fetch_steth_apr() -> Result<f64, ReqwestError> {
 
 /// Alternative implementation that calculates the average manually
 /// from the daily APR values
-async fn calculate_steth_apr() -> Result<f64, ReqwestError> {
+async fn calculate_steth_apr(client: &reqwest::Client) -> Result<f64, ReqwestError> {
     let url = "https://eth-api.lido.fi/v1/protocol/steth/apr/sma";
-    let response = reqwest::get(url).await?;
+    let response = client.get(url).send().await?;
 
     let apr_data: StethAprResponse = response.json().await?;
 
@@ -55,36 +81,187 @@ async fn calculate_steth_apr() -> Result<f64, ReqwestError> {
     Ok(avg_apr)
 }
 
-pub struct Lido {}
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestTimeResponse {
+    request_info: RequestInfo,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct RequestInfo {
+    /// Milliseconds until the request would be finalized.
+    finalization_in: u64,
+}
+
+async fn fetch_exit_latency_secs(client: &reqwest::Client) -> Result<u64, ReqwestError> {
+    let response = client
+        .get(WITHDRAWAL_TIME_URL)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<RequestTimeResponse>()
+        .await?;
+    Ok(response.request_info.finalization_in / 1000)
+}
+
+async fn call_uint(
+    provider: &dyn Provider,
+    to: Address,
+    input: Vec<u8>,
+) -> Result<U256, Box<dyn Error>> {
+    let tx = TransactionRequest::default().with_to(to).with_input(input);
+    let output = provider.call(&tx).await?;
+    Ok(U256::try_from_be_slice(&output).ok_or("Call did not return a uint256")?)
+}
+
+/// wstETH's stETH exchange rate and the state of the withdrawal queue, read from
+/// Ethereum mainnet.
+async fn fetch_redemption(
+    client: &reqwest::Client,
+    rpc_url: &str,
+) -> Result<Redemption, Box<dyn Error>> {
+    let provider = ProviderBuilder::new().on_http(reqwest::Url::parse(rpc_url)?);
+    let steth_per_token = call_uint(
+        &provider,
+        WSTETH_ADDRESS,
+        IWstETH::stEthPerTokenCall {}.abi_encode(),
+    )
+    .await?;
+    let unfinalized = call_uint(
+        &provider,
+        WITHDRAWAL_QUEUE_ADDRESS,
+        IWithdrawalQueue::unfinalizedStETHCall {}.abi_encode(),
+    )
+    .await?;
+    let last_request = call_uint(
+        &provider,
+        WITHDRAWAL_QUEUE_ADDRESS,
+        IWithdrawalQueue::getLastRequestIdCall {}.abi_encode(),
+    )
+    .await?;
+    let last_finalized = call_uint(
+        &provider,
+        WITHDRAWAL_QUEUE_ADDRESS,
+        IWithdrawalQueue::getLastFinalizedRequestIdCall {}.abi_encode(),
+    )
+    .await?;
+    let exit_latency_secs = match fetch_exit_latency_secs(client).await {
+        Ok(secs) => Some(secs),
+        Err(e) => {
+            println!("Failed to fetch the Lido withdrawal time: {}", e);
+            None
+        }
+    };
+
+    Ok(Redemption {
+        underlying: "stETH".to_string(),
+        exchange_rate: units_to_f64(&steth_per_token.to_string(), 18)?,
+        exit_latency_secs,
+        queued_underlying: Some(units_to_f64(&unfinalized.to_string(), 18)?),
+        queued_requests: Some(last_request.saturating_sub(last_finalized).saturating_to()),
+    })
+}
+
+/// stETH staking APR, with wstETH's exchange rate and the withdrawal queue when an
+/// Ethereum RPC is configured.
+pub struct Lido {
+    client: reqwest::Client,
+    rpc_url: Option<String>,
+}
+
+impl Lido {
+    pub fn new(rpc_url: Option<String>, client: reqwest::Client) -> Self {
+        Self { client, rpc_url }
+    }
+}
 
 #[async_trait]
 impl Yield for Lido {
-    fn get_symbol() -> String {
+    fn get_symbol(&self) -> String {
         "lido".to_string()
     }
 
     async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
-        let apr = fetch_steth_apr().await?;
+        let apr = fetch_steth_apr(&self.client).await?;
+        let redemption = match &self.rpc_url {
+            Some(rpc_url) => match fetch_redemption(&self.client, rpc_url).await {
+                Ok(redemption) => Some(redemption),
+                Err(e) => {
+                    println!("Failed to fetch the wstETH exchange rate: {}", e);
+                    None
+                }
+            },
+            None => None,
+        };
         Ok(vec![APR {
-            symbol: "wstETH".to_string(),
-            deposit_apr: apr,
-            borrow_apr: None,
+            risk_tier: Some(RiskTier::Low),
+            redemption,
+            ..APR::new("wstETH", apr)
         }])
     }
 }
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::yields::fixture;
+    use serde_json::json;
+
+    #[test]
+    fn test_request_time_response() {
+        let response: RequestTimeResponse = fixture(json!({
+            "requestInfo": {
+                "finalizationIn": 345600000,
+                "finalizationAt": "2026-10-18T12:00:00.000Z",
+                "type": "exitValidators"
+            },
+            "status": "calculated"
+        }));
+        assert_eq!(
+            response.request_info.finalization_in / 1000,
+            4 * 24 * 60 * 60
+        );
+    }
+
+    #[test]
+    fn test_redemption_display() {
+        let apr = APR {
+            redemption: Some(Redemption {
+                underlying: "stETH".to_string(),
+                exchange_rate: 1.21703,
+                exit_latency_secs: Some(4 * 24 * 60 * 60),
+                queued_underlying: Some(85_120.4),
+                queued_requests: Some(1_302),
+            }),
+            ..APR::new("wstETH", 2.9)
+        };
+        assert_eq!(
+            apr.to_string(),
+            "wstETH: Deposit APR: 2.90%, 1 wstETH = 1.2170 stETH, Exit: ~4.0 days, Withdrawal queue: 85120 stETH in 1302 requests"
+        );
+    }
+
+    #[tokio::test]
+    #[ignore = "calls a public Ethereum RPC"]
+    async fn test_fetch_redemption() {
+        let redemption = fetch_redemption(
+            &reqwest::Client::new(),
+            "https://ethereum-rpc.publicnode.com",
+        )
+        .await
+        .unwrap();
+        assert!(redemption.exchange_rate > 1.0);
+    }
 
     #[tokio::test]
     async fn test_fetch_steth_apr() {
-        let apr = fetch_steth_apr().await.unwrap();
+        let apr = fetch_steth_apr(&reqwest::Client::new()).await.unwrap();
         println!("Current stETH APR: {:.2}%", apr);
     }
 
     #[tokio::test]
     async fn test_calculate_steth_apr() {
-        let apr = calculate_steth_apr().await.unwrap();
+        let apr = calculate_steth_apr(&reqwest::Client::new()).await.unwrap();
         println!("Current stETH APR: {:.2}%", apr);
     }
 }
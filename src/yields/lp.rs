@@ -0,0 +1,149 @@
+use super::{RiskTier, Yield, APR};
+use crate::config::LpYieldConfig;
+use crate::executor::chains::BASE_CHAIN_ID;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::error::Error;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFILLAMA_POOLS_URL: &str = "https://yields.llama.fi/pools";
+
+#[derive(Debug, Deserialize)]
+struct PoolsResponse {
+    data: Vec<Pool>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Pool {
+    chain: String,
+    project: String,
+    /// Pair as "WETH-USDC".
+    symbol: String,
+    tvl_usd: f64,
+    /// Trading fee APY in percent, without reward emissions.
+    apy_base: Option<f64>,
+    /// Fee tier, e.g. "0.05%".
+    pool_meta: Option<String>,
+}
+
+/// Fee APRs of the largest concentrated liquidity pools on Base, from DefiLlama.
+#[derive(Debug)]
+pub struct LpFees {
+    client: reqwest::Client,
+    config: LpYieldConfig,
+    /// Base pools of the configured projects as of the last download.
+    pools: Mutex<Option<(Instant, Vec<Pool>)>>,
+}
+
+impl LpFees {
+    pub fn new(config: &LpYieldConfig, client: reqwest::Client) -> Self {
+        Self {
+            client,
+            config: config.clone(),
+            pools: Mutex::new(None),
+        }
+    }
+
+    /// Base pools of the configured projects, downloaded if missing or expired.
+    async fn pools(&self) -> Result<Vec<Pool>, Box<dyn Error>> {
+        let ttl = Duration::from_secs(self.config.pools_ttl_secs);
+        if let Some((fetched_at, pools)) = &*self.pools.lock().unwrap_or_else(|e| e.into_inner()) {
+            if fetched_at.elapsed() <= ttl {
+                return Ok(pools.clone());
+            }
+        }
+
+        let mut pools = self
+            .client
+            .get(DEFILLAMA_POOLS_URL)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<PoolsResponse>()
+            .await?
+            .data;
+        pools.retain(|pool| pool.chain == "Base" && self.config.projects.contains(&pool.project));
+        *self.pools.lock().unwrap_or_else(|e| e.into_inner()) =
+            Some((Instant::now(), pools.clone()));
+        Ok(pools)
+    }
+}
+
+#[async_trait]
+impl Yield for LpFees {
+    fn get_symbol(&self) -> String {
+        "lp_fees".to_string()
+    }
+
+    async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
+        Ok(lp_aprs(self.pools().await?, &self.config))
+    }
+}
+
+/// The `top_pools` largest pools of each configured project on Base.
+fn lp_aprs(mut pools: Vec<Pool>, config: &LpYieldConfig) -> Vec<APR> {
+    pools.retain(|pool| {
+        pool.chain == "Base"
+            && pool.tvl_usd >= config.min_tvl_usd
+            && config.projects.contains(&pool.project)
+    });
+    pools.sort_by(|a, b| b.tvl_usd.total_cmp(&a.tvl_usd));
+
+    let mut aprs = Vec::new();
+    for project in &config.projects {
+        let top = pools
+            .iter()
+            .filter(|pool| &pool.project == project)
+            .filter_map(|pool| Some((pool, pool.apy_base?)))
+            .take(config.top_pools);
+        for (pool, fee_apy) in top {
+            let symbol = match &pool.pool_meta {
+                Some(fee_tier) => format!("{} {} {}", pool.project, pool.symbol, fee_tier),
+                None => format!("{} {}", pool.project, pool.symbol),
+            };
+            aprs.push(APR {
+                chain_id: Some(BASE_CHAIN_ID),
+                tvl_usd: Some(pool.tvl_usd),
+                // Concentrated positions go out of range and carry impermanent loss
+                risk_tier: Some(RiskTier::High),
+                ..APR::from_apy(symbol, fee_apy)
+            });
+        }
+    }
+    aprs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yields::assert_quoted_apy;
+    use crate::yields::fixture;
+    use serde_json::json;
+
+    #[test]
+    fn test_lp_aprs() {
+        let response: PoolsResponse = fixture(json!({
+            "status": "success",
+            "data": [
+                { "chain": "Base", "project": "aerodrome-slipstream", "symbol": "WETH-USDC", "tvlUsd": 48000000.0, "apyBase": 21.4, "apyReward": 30.2, "poolMeta": "0.05%" },
+                { "chain": "Base", "project": "uniswap-v3", "symbol": "WETH-USDC", "tvlUsd": 30000000.0, "apyBase": 12.5, "poolMeta": "0.05%" },
+                { "chain": "Base", "project": "uniswap-v3", "symbol": "CBBTC-USDC", "tvlUsd": 60000000.0, "apyBase": null, "poolMeta": "0.3%" },
+                { "chain": "Base", "project": "uniswap-v3", "symbol": "TINY-WETH", "tvlUsd": 5000.0, "apyBase": 900.0 },
+                { "chain": "Ethereum", "project": "uniswap-v3", "symbol": "WETH-USDC", "tvlUsd": 200000000.0, "apyBase": 8.0, "poolMeta": "0.05%" }
+            ]
+        }));
+        let aprs = lp_aprs(response.data, &LpYieldConfig::default());
+        let symbols: Vec<&str> = aprs.iter().map(|apr| apr.symbol.as_str()).collect();
+        assert_eq!(
+            symbols,
+            vec![
+                "uniswap-v3 WETH-USDC 0.05%",
+                "aerodrome-slipstream WETH-USDC 0.05%"
+            ]
+        );
+        assert_quoted_apy(&aprs[0], 12.5);
+        assert_eq!(aprs[1].tvl_usd, Some(48_000_000.0));
+    }
+}
@@ -1,13 +1,42 @@
 mod aave;
+mod cache;
+mod costs;
 mod eigen_layer;
+mod error;
+mod etherfi;
+mod filter;
+mod funding;
+mod history;
+mod kelp;
 mod lido;
+mod lp;
+mod morpho;
+mod pendle;
+mod rocket_pool;
+use crate::config::{ChainConfig, YieldConfig};
+use crate::utils::rounding::DISPLAY_PRICE_DP;
 use async_trait::async_trait;
-use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+use futures::future::join_all;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 
 pub use aave::*;
+pub use cache::*;
+pub use costs::*;
 pub use eigen_layer::*;
+pub use error::*;
+pub use etherfi::*;
+pub use filter::*;
+pub use funding::*;
+pub use history::*;
+pub use kelp::*;
 pub use lido::*;
+pub use lp::*;
+pub use morpho::*;
+pub use pendle::*;
+pub use rocket_pool::*;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(rename_all = "camelCase")]
@@ -15,54 +44,342 @@ pub struct APR {
     pub symbol: String,
     pub deposit_apr: f64,
     pub borrow_apr: Option<f64>,
+    /// `deposit_apr` compounded, for sources whose rates compound (e.g. Aave every second)
+    /// or that quote APYs, whose `deposit_apr` is then converted from it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deposit_apy: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub borrow_apy: Option<f64>,
+    /// When a fixed rate (e.g. a Pendle PT) matures; variable rates have none.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub maturity: Option<DateTime<Utc>>,
+    /// Split of `deposit_apr` between base staking and restaking, for restaked tokens.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub breakdown: Option<AprBreakdown>,
+    /// Chain the yield is earned on, for on-chain deposits.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<u64>,
+    /// USD deposited in the pool, vault or market the yield comes from.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tvl_usd: Option<f64>,
+    /// Share of the deposits that is borrowed, as a fraction.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub utilization: Option<f64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub risk_tier: Option<RiskTier>,
+    /// Token deposited to earn the yield when it is not `symbol` itself, e.g. USDC for
+    /// aBasUSDC.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub asset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub redemption: Option<Redemption>,
+}
+
+impl APR {
+    /// `deposit_apr` on `symbol`, in percent, with nothing else known about it.
+    pub fn new(symbol: impl Into<String>, deposit_apr: f64) -> Self {
+        Self {
+            symbol: symbol.into(),
+            deposit_apr,
+            borrow_apr: None,
+            deposit_apy: None,
+            borrow_apy: None,
+            maturity: None,
+            breakdown: None,
+            chain_id: None,
+            tvl_usd: None,
+            utilization: None,
+            risk_tier: None,
+            asset: None,
+            redemption: None,
+        }
+    }
+
+    /// A quoted `deposit_apy` on `symbol`, in percent, kept next to its APR.
+    pub fn from_apy(symbol: impl Into<String>, deposit_apy: f64) -> Self {
+        Self {
+            deposit_apy: Some(deposit_apy),
+            ..Self::new(symbol, apy_to_apr(deposit_apy))
+        }
+    }
+}
+
+/// How a yield-bearing token converts back into its underlying, and how long exiting
+/// through the protocol takes.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Redemption {
+    pub underlying: String,
+    /// Underlying one token redeems for.
+    pub exchange_rate: f64,
+    /// Expected wait for a withdrawal requested now.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exit_latency_secs: Option<u64>,
+    /// Underlying waiting in the withdrawal queue.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queued_underlying: Option<f64>,
+    /// Withdrawal requests not finalized yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub queued_requests: Option<u64>,
+}
+
+/// Coarse risk of earning a yield, set by each source from the protocol's track
+/// record and audits and from how the yield is earned.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum RiskTier {
+    /// Long-running, widely audited lending or liquid staking (Aave, Lido, Rocket Pool).
+    Low,
+    /// Audited but younger or layered on other protocols: restaking, curated vaults,
+    /// fixed-rate PTs, or a carry that can turn negative like perp funding.
+    Medium,
+    /// Newer restaking venues whose slashing and withdrawal terms are still settling.
+    High,
+}
+
+impl std::fmt::Display for RiskTier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RiskTier::Low => write!(f, "low"),
+            RiskTier::Medium => write!(f, "medium"),
+            RiskTier::High => write!(f, "high"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct AprBreakdown {
+    pub staking_apr: f64,
+    pub restaking_apr: f64,
 }
 
 #[async_trait]
-pub trait Yield {
-    fn get_symbol() -> String;
+pub trait Yield: Send + Sync {
+    /// Name the source's APRs are listed under, e.g. "aave".
+    fn get_symbol(&self) -> String;
     async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>>;
 }
 
-pub struct CombinedYieldFetcher {
-    pub aave: Aave,
-    pub lido: Lido,
-    pub eigen: Eigen,
+/// The yield source called `name`, or `None` if it cannot run in this environment
+/// (e.g. its API key is not set). A new protocol only has to be added here to be
+/// usable in the config.
+fn yield_source(
+    name: &str,
+    config: &YieldConfig,
+    chains: &HashMap<u64, ChainConfig>,
+    client: &reqwest::Client,
+) -> anyhow::Result<Option<Box<dyn Yield>>> {
+    let client = client.clone();
+    let source: Box<dyn Yield> = match name {
+        "aave" => Box::new(Aave::new(&config.aave, client)),
+        "lido" => Box::new(Lido::new(
+            chains.get(&1).map(|chain| chain.rpc_url.clone()),
+            client,
+        )),
+        "eigenlayer" => match Eigen::new(&config.eigen, client) {
+            Some(eigen) => Box::new(eigen),
+            None => {
+                println!(
+                    "{} not set in environment, skipping eigenlayer yields",
+                    config.eigen.dune_api_key_env
+                );
+                return Ok(None);
+            }
+        },
+        "morpho" => Box::new(Morpho::new(client)),
+        "pendle" => Box::new(Pendle::new(client)),
+        "etherfi" => Box::new(EtherFi::new(client)),
+        "rocketpool" => Box::new(RocketPool::new(client)),
+        "kelp" => Box::new(Kelp::new(client)),
+        "binance_funding" => Box::new(BinanceFunding::new(&config.funding, client)),
+        "lp_fees" => Box::new(LpFees::new(&config.lp, client)),
+        _ => return Err(anyhow::anyhow!("Unknown yield source {:?}", name)),
+    };
+    Ok(Some(source))
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
-pub struct CombinedYields {
-    pub aave: Vec<APR>,
-    pub lido: Vec<APR>,
-    pub eigen: Vec<APR>,
+/// Yield sources queried for APRs, one per protocol.
+#[derive(Default)]
+pub struct YieldRegistry {
+    sources: Vec<Box<dyn Yield>>,
 }
 
-impl CombinedYieldFetcher {
+impl YieldRegistry {
     pub fn new() -> Self {
-        Self {
-            aave: Aave {},
-            lido: Lido {},
-            eigen: Eigen {},
+        Self::default()
+    }
+
+    /// Registry with the sources the config names, in order, calling their APIs through
+    /// `client`. Sources reading on-chain state use the RPCs in `chains`.
+    pub fn from_config(
+        config: &YieldConfig,
+        chains: &HashMap<u64, ChainConfig>,
+        client: &reqwest::Client,
+    ) -> anyhow::Result<Self> {
+        let mut registry = Self::new();
+        for name in &config.sources {
+            if let Some(source) = yield_source(name, config, chains, client)? {
+                registry.register(source);
+            }
         }
+        Ok(registry)
     }
 
-    pub async fn get_apr(&self) -> Result<CombinedYields, Box<dyn Error>> {
-        let aave_apr = self.aave.get_apr().await?;
-        let lido_apr = self.lido.get_apr().await?;
-        let eigen_apr = self.eigen.get_apr().await?;
+    pub fn register(&mut self, source: Box<dyn Yield>) {
+        self.sources.push(source);
+    }
+
+    /// Query every source concurrently. A source that fails is listed in `errors`
+    /// instead of failing the others.
+    pub async fn get_apr(&self) -> CombinedYields {
+        let results = join_all(self.sources.iter().map(|source| async move {
+            let result = source.get_apr().await.map_err(|e| e.to_string());
+            (source.get_symbol(), result)
+        }))
+        .await;
+
+        let mut yields = CombinedYields::default();
+        for (name, result) in results {
+            match result {
+                Ok(aprs) => {
+                    yields.sources.insert(name, aprs);
+                }
+                Err(error) => {
+                    println!("Failed to fetch {} yields: {}", name, error);
+                    yields.errors.push(YieldSourceError {
+                        source: name,
+                        error,
+                    });
+                }
+            }
+        }
+        yields
+    }
+}
+
+/// APRs per yield source name, and the sources that could not be fetched.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct CombinedYields {
+    pub sources: BTreeMap<String, Vec<APR>>,
+    pub errors: Vec<YieldSourceError>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct YieldSourceError {
+    pub source: String,
+    pub error: String,
+}
+
+pub const SECONDS_PER_YEAR: f64 = 31_536_000.0;
+
+/// APY of an APR compounded every second, both in percent, as Aave's UI computes it.
+pub fn apr_to_apy(apr: f64) -> f64 {
+    // (1 + r/n)^n - 1 through ln_1p/exp_m1, since 1 + r/n loses most of r's digits
+    (SECONDS_PER_YEAR * (apr / 100.0 / SECONDS_PER_YEAR).ln_1p()).exp_m1() * 100.0
+}
+
+/// APR that compounded every second gives `apy`, both in percent; the inverse of
+/// `apr_to_apy`, so sources quoting APYs fill `deposit_apr` with an APR too.
+pub fn apy_to_apr(apy: f64) -> f64 {
+    SECONDS_PER_YEAR * ((apy / 100.0).ln_1p() / SECONDS_PER_YEAR).exp_m1() * 100.0
+}
 
-        Ok(CombinedYields {
-            aave: aave_apr,
-            lido: lido_apr,
-            eigen: eigen_apr,
-        })
+/// A source's API response parsed from a JSON fixture.
+#[cfg(test)]
+pub(crate) fn fixture<T: serde::de::DeserializeOwned>(json: serde_json::Value) -> T {
+    serde_json::from_value(json).expect("fixture matches the response type")
+}
+
+/// Asserts `apr` keeps the quoted `deposit_apy` and carries the APR it compounds from.
+#[cfg(test)]
+pub(crate) fn assert_quoted_apy(apr: &APR, deposit_apy: f64) {
+    assert!(
+        (apr.deposit_apy.unwrap() - deposit_apy).abs() < 1e-9,
+        "{:?}",
+        apr
+    );
+    assert!(
+        (apr.deposit_apr - apy_to_apr(deposit_apy)).abs() < 1e-9,
+        "{:?}",
+        apr
+    );
+}
+
+/// Deserialize a number APIs send either as a JSON number or as a string.
+fn number_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum NumberOrString {
+        Number(f64),
+        String(String),
+    }
+    match NumberOrString::deserialize(deserializer)? {
+        NumberOrString::Number(value) => Ok(value),
+        NumberOrString::String(value) => value.trim().parse().map_err(serde::de::Error::custom),
     }
 }
 
 impl std::fmt::Display for APR {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}: Deposit APR: {:.2}%", self.symbol, self.deposit_apr)?;
+        write!(
+            f,
+            "{}: Deposit APR: {:.*}%",
+            self.symbol, DISPLAY_PRICE_DP, self.deposit_apr
+        )?;
+        if let Some(deposit_apy) = self.deposit_apy {
+            write!(f, " (APY {:.*}%)", DISPLAY_PRICE_DP, deposit_apy)?;
+        }
         if let Some(borrow_apr) = self.borrow_apr {
-            write!(f, ", Borrow APR: {:.2}%", borrow_apr)?;
+            write!(f, ", Borrow APR: {:.*}%", DISPLAY_PRICE_DP, borrow_apr)?;
+            if let Some(borrow_apy) = self.borrow_apy {
+                write!(f, " (APY {:.*}%)", DISPLAY_PRICE_DP, borrow_apy)?;
+            }
+        }
+        if let Some(breakdown) = self.breakdown {
+            write!(
+                f,
+                " (Staking: {:.*}%, Restaking: {:.*}%)",
+                DISPLAY_PRICE_DP, breakdown.staking_apr, DISPLAY_PRICE_DP, breakdown.restaking_apr
+            )?;
+        }
+        if let Some(maturity) = self.maturity {
+            write!(f, ", Fixed until {}", maturity.format("%Y-%m-%d"))?;
+        }
+        if let Some(redemption) = &self.redemption {
+            write!(
+                f,
+                ", 1 {} = {:.4} {}",
+                self.symbol, redemption.exchange_rate, redemption.underlying
+            )?;
+            if let Some(secs) = redemption.exit_latency_secs {
+                write!(f, ", Exit: ~{:.1} days", secs as f64 / 86_400.0)?;
+            }
+            if let (Some(queued), Some(requests)) =
+                (redemption.queued_underlying, redemption.queued_requests)
+            {
+                write!(
+                    f,
+                    ", Withdrawal queue: {:.0} {} in {} requests",
+                    queued, redemption.underlying, requests
+                )?;
+            }
+        }
+        if let Some(tvl_usd) = self.tvl_usd {
+            write!(f, ", TVL: ${:.1}M", tvl_usd / 1e6)?;
+        }
+        if let Some(utilization) = self.utilization {
+            write!(
+                f,
+                ", Utilization: {:.*}%",
+                DISPLAY_PRICE_DP,
+                utilization * 100.0
+            )?;
+        }
+        if let Some(risk_tier) = self.risk_tier {
+            write!(f, ", Risk: {}", risk_tier)?;
         }
         Ok(())
     }
@@ -70,21 +387,102 @@ impl std::fmt::Display for APR {
 
 impl std::fmt::Display for CombinedYields {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Aave Yields:")?;
-        for apr in &self.aave {
-            writeln!(f, "  {}", apr)?;
+        for (i, (name, aprs)) in self.sources.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "{} Yields:", name)?;
+            for apr in aprs {
+                writeln!(f, "  {}", apr)?;
+            }
+        }
+        for error in &self.errors {
+            writeln!(
+                f,
+                "\n{} Yields: unavailable ({})",
+                error.source, error.error
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct FixedYield;
+
+    #[async_trait]
+    impl Yield for FixedYield {
+        fn get_symbol(&self) -> String {
+            "fixed".to_string()
         }
 
-        writeln!(f, "\nLido Yields:")?;
-        for apr in &self.lido {
-            writeln!(f, "  {}", apr)?;
+        async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
+            Ok(vec![APR {
+                borrow_apr: Some(6.0),
+                tvl_usd: Some(125_000_000.0),
+                utilization: Some(0.8),
+                risk_tier: Some(RiskTier::Low),
+                ..APR::new("USDC", 4.5)
+            }])
         }
+    }
 
-        writeln!(f, "\nEigen Yields:")?;
-        for apr in &self.eigen {
-            writeln!(f, "  {}", apr)?;
+    struct FailingYield;
+
+    #[async_trait]
+    impl Yield for FailingYield {
+        fn get_symbol(&self) -> String {
+            "failing".to_string()
         }
 
+        async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
+            Err("rate limited".into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_registry_combines_sources() -> Result<(), Box<dyn Error>> {
+        let mut registry = YieldRegistry::new();
+        registry.register(Box::new(FixedYield));
+        let yields = registry.get_apr().await;
+        assert_eq!(yields.sources["fixed"].len(), 1);
+        assert_eq!(
+            yields.to_string(),
+            "fixed Yields:\n  USDC: Deposit APR: 4.50%, Borrow APR: 6.00%, TVL: $125.0M, Utilization: 80.00%, Risk: low\n"
+        );
+        let json = serde_json::to_value(&yields)?;
+        assert_eq!(json["sources"]["fixed"][0]["depositApr"], 4.5);
+        assert_eq!(json["sources"]["fixed"][0]["riskTier"], "low");
+
+        // A failing source is reported without dropping the others
+        registry.register(Box::new(FailingYield));
+        let yields = registry.get_apr().await;
+        assert_eq!(yields.sources.len(), 1);
+        assert_eq!(yields.errors.len(), 1);
+        assert_eq!(yields.errors[0].source, "failing");
+        assert_eq!(yields.errors[0].error, "rate limited");
+        assert!(yields
+            .to_string()
+            .ends_with("\nfailing Yields: unavailable (rate limited)\n"));
+
+        let mut config = YieldConfig::default();
+        config.eigen.dune_api_key_env = "CHILL_PM_TEST_UNSET_DUNE_API_KEY".to_string();
+        let (chains, client) = (HashMap::new(), reqwest::Client::new());
+        // Eigen is skipped without a Dune API key
+        assert_eq!(
+            YieldRegistry::from_config(&config, &chains, &client)?
+                .sources
+                .len(),
+            9
+        );
+        let unknown = YieldConfig {
+            sources: vec!["compound".to_string()],
+            ..YieldConfig::default()
+        };
+        assert!(YieldRegistry::from_config(&unknown, &chains, &client).is_err());
         Ok(())
     }
 }
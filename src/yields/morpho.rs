@@ -0,0 +1,207 @@
+use super::{apy_to_apr, RiskTier, Yield, APR};
+use crate::executor::chains::BASE_CHAIN_ID;
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::error::Error;
+
+const MORPHO_API_URL: &str = "https://blue-api.morpho.org/graphql";
+
+/// Vaults and markets listed, largest first.
+const MORPHO_LISTED: u32 = 20;
+
+#[derive(Debug, Deserialize)]
+struct MorphoResponse {
+    data: MorphoData,
+}
+
+#[derive(Debug, Deserialize)]
+struct MorphoData {
+    vaults: Items<Vault>,
+    markets: Items<Market>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Items<T> {
+    items: Vec<T>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Vault {
+    symbol: String,
+    asset: Option<Asset>,
+    state: Option<VaultState>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct VaultState {
+    /// APY after the curator fee, including rewards, as a fraction.
+    net_apy: Option<f64>,
+    total_assets_usd: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Market {
+    loan_asset: Asset,
+    collateral_asset: Option<Asset>,
+    state: Option<MarketState>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Asset {
+    symbol: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct MarketState {
+    supply_apy: Option<f64>,
+    borrow_apy: Option<f64>,
+    supply_assets_usd: Option<f64>,
+    /// Borrowed share of the supplied assets, as a fraction.
+    utilization: Option<f64>,
+}
+
+/// Morpho Blue vault and market APYs on Base.
+#[derive(Debug)]
+pub struct Morpho {
+    client: reqwest::Client,
+}
+
+impl Morpho {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl Yield for Morpho {
+    fn get_symbol(&self) -> String {
+        "morpho".to_string()
+    }
+
+    async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
+        let response = fetch_morpho(&self.client, BASE_CHAIN_ID).await?;
+        Ok(morpho_aprs(response))
+    }
+}
+
+async fn fetch_morpho(
+    client: &reqwest::Client,
+    chain_id: u64,
+) -> Result<MorphoResponse, Box<dyn Error>> {
+    let query = format!(
+        r#"
+    {{
+        vaults(first: {listed}, orderBy: TotalAssetsUsd, orderDirection: Desc, where: {{ chainId_in: [{chain_id}] }}) {{
+            items {{
+                symbol
+                asset {{ symbol }}
+                state {{ netApy totalAssetsUsd }}
+            }}
+        }}
+        markets(first: {listed}, orderBy: SupplyAssetsUsd, orderDirection: Desc, where: {{ chainId_in: [{chain_id}] }}) {{
+            items {{
+                loanAsset {{ symbol }}
+                collateralAsset {{ symbol }}
+                state {{ supplyApy borrowApy supplyAssetsUsd utilization }}
+            }}
+        }}
+    }}
+    "#,
+        listed = MORPHO_LISTED,
+        chain_id = chain_id
+    );
+
+    let response = client
+        .post(MORPHO_API_URL)
+        .json(&serde_json::json!({ "query": query }))
+        .send()
+        .await?
+        .json::<MorphoResponse>()
+        .await?;
+    Ok(response)
+}
+
+/// Vault APYs and market APYs per loan/collateral pair, with their APRs, in percent.
+fn morpho_aprs(response: MorphoResponse) -> Vec<APR> {
+    let vaults = response.data.vaults.items.into_iter().filter_map(|vault| {
+        let state = vault.state?;
+        Some(APR {
+            chain_id: Some(BASE_CHAIN_ID),
+            tvl_usd: state.total_assets_usd,
+            risk_tier: Some(RiskTier::Medium),
+            asset: vault.asset.map(|asset| asset.symbol),
+            ..APR::from_apy(vault.symbol, state.net_apy? * 100.0)
+        })
+    });
+    // Idle markets without collateral only hold undeployed vault funds
+    let markets = response
+        .data
+        .markets
+        .items
+        .into_iter()
+        .filter_map(|market| {
+            let collateral = market.collateral_asset?;
+            let state = market.state?;
+            let symbol = format!("{}/{}", market.loan_asset.symbol, collateral.symbol);
+            Some(APR {
+                borrow_apr: state.borrow_apy.map(|apy| apy_to_apr(apy * 100.0)),
+                borrow_apy: state.borrow_apy.map(|apy| apy * 100.0),
+                chain_id: Some(BASE_CHAIN_ID),
+                tvl_usd: state.supply_assets_usd,
+                utilization: state.utilization,
+                risk_tier: Some(RiskTier::Medium),
+                asset: Some(market.loan_asset.symbol),
+                ..APR::from_apy(symbol, state.supply_apy? * 100.0)
+            })
+        });
+    vaults.chain(markets).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yields::assert_quoted_apy;
+    use crate::yields::fixture;
+    use serde_json::json;
+
+    #[test]
+    fn test_morpho_aprs() {
+        let response: MorphoResponse = fixture(json!({
+            "data": {
+                "vaults": { "items": [
+                    { "symbol": "mwUSDC", "asset": { "symbol": "USDC" }, "state": { "netApy": 0.0525, "totalAssetsUsd": 48000000.0 } },
+                    { "symbol": "empty", "state": null }
+                ] },
+                "markets": { "items": [
+                    {
+                        "loanAsset": { "symbol": "USDC" },
+                        "collateralAsset": { "symbol": "cbBTC" },
+                        "state": { "supplyApy": 0.041, "borrowApy": 0.055, "supplyAssetsUsd": 12000000.0, "utilization": 0.9 }
+                    },
+                    {
+                        "loanAsset": { "symbol": "USDC" },
+                        "collateralAsset": null,
+                        "state": { "supplyApy": 0.0, "borrowApy": 0.0 }
+                    }
+                ] }
+            }
+        }));
+        let aprs = morpho_aprs(response);
+        assert_eq!(aprs.len(), 2);
+        assert_eq!(aprs[0].symbol, "mwUSDC");
+        assert_quoted_apy(&aprs[0], 5.25);
+        assert_eq!(aprs[0].borrow_apr, None);
+        assert_eq!(aprs[1].symbol, "USDC/cbBTC");
+        assert_quoted_apy(&aprs[1], 4.1);
+        assert!((aprs[1].borrow_apy.unwrap() - 5.5).abs() < 1e-9);
+        assert!((aprs[1].borrow_apr.unwrap() - apy_to_apr(5.5)).abs() < 1e-9);
+        assert_eq!(aprs[0].tvl_usd, Some(48_000_000.0));
+        assert_eq!(aprs[1].utilization, Some(0.9));
+        assert_eq!(aprs[0].asset.as_deref(), Some("USDC"));
+        assert_eq!(aprs[1].asset.as_deref(), Some("USDC"));
+        assert_eq!(aprs[1].risk_tier, Some(RiskTier::Medium));
+    }
+}
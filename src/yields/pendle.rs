@@ -0,0 +1,143 @@
+use super::{RiskTier, Yield, APR};
+use crate::executor::chains::BASE_CHAIN_ID;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::error::Error;
+
+const PENDLE_API_URL: &str = "https://api-v2.pendle.finance/core/v1";
+
+/// Markets with less liquidity than this (in USD) are too thin to enter.
+const PENDLE_MIN_LIQUIDITY_USD: f64 = 100_000.0;
+
+#[derive(Debug, Deserialize)]
+struct MarketsResponse {
+    results: Vec<PendleMarket>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PendleMarket {
+    expiry: DateTime<Utc>,
+    pt: PendleToken,
+    /// Fixed APY of buying the PT now and holding it to expiry, as a fraction.
+    implied_apy: Option<f64>,
+    liquidity: Option<Liquidity>,
+}
+
+#[derive(Debug, Deserialize)]
+struct PendleToken {
+    symbol: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct Liquidity {
+    usd: f64,
+}
+
+/// Fixed APYs of Pendle PT markets on Base, with their maturities.
+#[derive(Debug)]
+pub struct Pendle {
+    client: reqwest::Client,
+}
+
+impl Pendle {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl Yield for Pendle {
+    fn get_symbol(&self) -> String {
+        "pendle".to_string()
+    }
+
+    async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
+        let markets = fetch_pendle_markets(&self.client, BASE_CHAIN_ID).await?;
+        Ok(pendle_aprs(markets, Utc::now()))
+    }
+}
+
+async fn fetch_pendle_markets(
+    client: &reqwest::Client,
+    chain_id: u64,
+) -> Result<Vec<PendleMarket>, Box<dyn Error>> {
+    let response = client
+        .get(format!("{}/{}/markets", PENDLE_API_URL, chain_id))
+        .query(&[("order_by", "liquidity:-1"), ("limit", "100")])
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<MarketsResponse>()
+        .await?;
+    Ok(response.results)
+}
+
+/// Implied APYs, with their APRs, in percent, of the liquid markets that have not
+/// matured at `now`.
+fn pendle_aprs(markets: Vec<PendleMarket>, now: DateTime<Utc>) -> Vec<APR> {
+    markets
+        .into_iter()
+        .filter(|market| market.expiry > now)
+        .filter(|market| {
+            market
+                .liquidity
+                .as_ref()
+                .is_some_and(|liquidity| liquidity.usd >= PENDLE_MIN_LIQUIDITY_USD)
+        })
+        .filter_map(|market| {
+            Some(APR {
+                maturity: Some(market.expiry),
+                chain_id: Some(BASE_CHAIN_ID),
+                tvl_usd: market.liquidity.map(|liquidity| liquidity.usd),
+                risk_tier: Some(RiskTier::Medium),
+                ..APR::from_apy(market.pt.symbol, market.implied_apy? * 100.0)
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yields::assert_quoted_apy;
+    use crate::yields::fixture;
+    use serde_json::json;
+
+    #[test]
+    fn test_pendle_aprs() {
+        let response: MarketsResponse = fixture(json!({
+            "total": 3,
+            "results": [
+                {
+                    "expiry": "2026-12-24T00:00:00.000Z",
+                    "pt": { "symbol": "PT-weETH-24DEC2026" },
+                    "impliedApy": 0.0712,
+                    "liquidity": { "usd": 2500000.0 }
+                },
+                {
+                    "expiry": "2025-06-26T00:00:00.000Z",
+                    "pt": { "symbol": "PT-weETH-26JUN2025" },
+                    "impliedApy": 0.05,
+                    "liquidity": { "usd": 900000.0 }
+                },
+                {
+                    "expiry": "2026-12-24T00:00:00.000Z",
+                    "pt": { "symbol": "PT-thin-24DEC2026" },
+                    "impliedApy": 0.4,
+                    "liquidity": { "usd": 1200.0 }
+                }
+            ]
+        }));
+        let now = "2026-01-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap();
+        let aprs = pendle_aprs(response.results, now);
+        assert_eq!(aprs.len(), 1);
+        assert_eq!(aprs[0].symbol, "PT-weETH-24DEC2026");
+        assert_quoted_apy(&aprs[0], 7.12);
+        assert_eq!(
+            aprs[0].to_string(),
+            "PT-weETH-24DEC2026: Deposit APR: 6.88% (APY 7.12%), Fixed until 2026-12-24, TVL: $2.5M, Risk: medium"
+        );
+    }
+}
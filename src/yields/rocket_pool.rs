@@ -0,0 +1,66 @@
+use super::{number_or_string, RiskTier, Yield, APR};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::error::Error;
+
+const ROCKET_POOL_APR_URL: &str = "https://api.rocketpool.net/api/mainnet/apr";
+
+#[derive(Debug, Deserialize)]
+struct RethAprResponse {
+    /// 7 day average rETH APR in percent, sent as a string.
+    #[serde(rename = "yearlyAPR", deserialize_with = "number_or_string")]
+    yearly_apr: f64,
+}
+
+async fn fetch_reth_apr(client: &reqwest::Client) -> Result<f64, Box<dyn Error>> {
+    let response = client
+        .get(ROCKET_POOL_APR_URL)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<RethAprResponse>()
+        .await?;
+    Ok(response.yearly_apr)
+}
+
+/// rETH staking APR.
+#[derive(Debug)]
+pub struct RocketPool {
+    client: reqwest::Client,
+}
+
+impl RocketPool {
+    pub fn new(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl Yield for RocketPool {
+    fn get_symbol(&self) -> String {
+        "rocketpool".to_string()
+    }
+
+    async fn get_apr(&self) -> Result<Vec<APR>, Box<dyn Error>> {
+        let apr = fetch_reth_apr(&self.client).await?;
+        Ok(vec![APR {
+            risk_tier: Some(RiskTier::Low),
+            ..APR::new("rETH", apr)
+        }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::yields::fixture;
+    use serde_json::json;
+
+    #[test]
+    fn test_reth_apr_response() {
+        let response: RethAprResponse = fixture(json!({ "yearlyAPR": "2.6845" }));
+        assert_eq!(response.yearly_apr, 2.6845);
+        let response: RethAprResponse = fixture(json!({ "yearlyAPR": 3.1 }));
+        assert_eq!(response.yearly_apr, 3.1);
+    }
+}
@@ -12,11 +12,22 @@ export interface Message {
 export interface Exchanges {
   binance: BinanceExchange;
   eisen: EisenExchange;
+  bridges?: BridgeExchange;
 }
 
 // Equivalent to the BinanceExchange struct
 export interface BinanceExchange {
   orders?: BinanceOrder[];
+  transfers?: BinanceTransfer[];
+}
+
+// Equivalent to the BinanceTransfer struct
+// Wallets: "spot", "futures" (USDⓈ-M) or "coin_futures" (COIN-M)
+export interface BinanceTransfer {
+  from: string;
+  to: string;
+  asset: string;
+  amount: string;
 }
 
 // Equivalent to the BinanceOrder struct
@@ -26,6 +37,30 @@ export interface BinanceOrder {
   amount: string;
   price: string;
   side: string;
+  // "USDT" (default), "USDC" or "USD" for coin-margined perpetuals
+  quote?: string;
+  // Stage to execute the order in; 0 when unset. Stages run in ascending order, and the
+  // legs of one stage concurrently
+  sequence?: number;
+}
+
+// Equivalent to the BridgeExchange struct
+export interface BridgeExchange {
+  transfers?: BridgeTransfer[];
+}
+
+// Equivalent to the BridgeTransfer struct
+// Executed before the swaps of its stage, so bridged funds can be swapped on the destination chain
+export interface BridgeTransfer {
+  token: string;
+  // Token received on the destination chain; token when unset
+  tokenOut?: string;
+  // Amount of token, or "max" for the wallet's whole balance
+  amount: string;
+  fromChainId: number;
+  toChainId: number;
+  // Stage to execute the transfer in; 0 when unset
+  sequence?: number;
 }
 
 // Equivalent to the EisenExchange struct
@@ -38,7 +73,22 @@ export interface EisenExchange {
 export interface EisenSwap {
   tokenIn: string;
   tokenOut: string;
+  // Amount of tokenIn, or "max" for the wallet's whole balance
   amount: string;
+  // Chain to swap on; Base (8453) when unset
+  chainId?: number;
+  // Slippage tolerance in bps (1-1000); the configured default when unset
+  slippageBps?: number;
+  // DEX ids the swap may (allow) or may not (deny) use; the chain's configured filter when unset
+  dexFilter?: DexFilter;
+  // Stage to execute the swap in; 0 when unset
+  sequence?: number;
+}
+
+// Equivalent to the DexFilter struct
+export interface DexFilter {
+  allow?: string[];
+  deny?: string[];
 }
 
 // Equivalent to the Strategy struct